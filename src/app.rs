@@ -1,24 +1,52 @@
-use std::collections::{HashMap, VecDeque};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::prelude::Rect;
+use regex::Regex;
 use tokio::sync::mpsc;
+use unicode_width::UnicodeWidthChar;
 
+use crate::approval::{self, Approval, ApprovalRequest};
+use crate::bookmarks;
 use crate::checkpoints;
-use crate::config::AppConfig;
+use crate::clipboard::{self, CopyTarget};
+use crate::config::{AppConfig, ConfigLayer, ProjectLayer};
+use crate::confine::{Confinement, Enforcement};
+use crate::daemon;
+use crate::editor::{self, EditorRequest};
 use crate::events::AppEvent;
-use crate::llm::{call_llm, ChatMessage, LlmProvider};
+use crate::export::{self, ExportFormat};
+use crate::llm::{
+    call_llm, is_read_only_tool, list_models, AgentError, ChatMessage, LlmProvider, LlmTarget,
+    ModelInfo, RetryPolicy, TokenUsage, ToolSet,
+};
+use crate::llm_debug;
+use crate::memory::consolidation::ConsolidationPolicy;
+use crate::memory::types::{Memory, MemoryScope};
 use crate::memory::MemoryManager;
+use crate::notify::{self, Notify, NotifyEvent};
+use crate::replay;
 use crate::sandbox::SandboxPolicy;
+use crate::semantic_search::chunker::truncate_at_boundaries;
+use crate::semantic_search::types::LanguageOverrides;
 use crate::semantic_search::{index_dir_for_workspace, SearchConfig, SemanticSearch};
-use crate::session::{replay_into, SessionStore};
-use crate::render_store::{RenderStore, RenderedBlockId};
-use crate::tools::execute_tool;
+use crate::session::{
+    message_from_event, replay_into, tool_from_event, SessionEvent, SessionStore,
+};
+use crate::stats::{self, file_changes, SessionStats, TurnStats, TurnTiming};
+use crate::theme::{ThemeCache, UiTheme};
+use crate::tokens;
+use crate::tools::{command_arg, execute_tool, is_infra_failure};
+use crate::trust::{self, TrustLevel};
 use crate::types::ToolCallMessage;
-use crate::verify::detect_suggestions;
+use crate::verify::suggestions_for;
+use crate::workspaces;
 
 #[derive(Debug, Clone)]
 pub struct Message {
@@ -38,6 +66,10 @@ pub enum Role {
     Agent,
     System,
     Tool,
+    /// Partial reply cut off by an error: shown greyed out, never sent to the model
+    Draft,
+    /// An executed plan, pinned in the transcript; never folded or sent to the model
+    Plan,
 }
 
 impl Message {
@@ -49,11 +81,12 @@ impl Message {
                 tool_calls: None,
                 tool_call_id: self.reasoning.clone(), // We store tool_call_id in reasoning field for Tool messages
                 name: None,
+                cache: false,
             },
             _ => ChatMessage {
                 role: match self.role {
                     Role::User => "user".into(),
-                    Role::Agent => "assistant".into(),
+                    Role::Agent | Role::Draft | Role::Plan => "assistant".into(),
                     Role::System => "system".into(),
                     Role::Tool => "tool".into(),
                 },
@@ -65,11 +98,195 @@ impl Message {
                 tool_calls: self.tool_calls.clone(),
                 tool_call_id: None,
                 name: None,
+                cache: false,
             },
         }
     }
 }
 
+/// A file opened from a search result (Ctrl+G), shown in the right pane.
+#[derive(Debug, Clone)]
+pub struct FileView {
+    pub path: String,
+    /// 1-based line the view was opened at (highlighted).
+    pub line: usize,
+    pub lines: Vec<String>,
+    pub scroll: usize,
+}
+
+/// The diagnostics log as `/logs` shows it in the text popup, reloaded as it grows.
+#[derive(Debug, Clone)]
+pub struct LogTail {
+    pub path: PathBuf,
+    /// File length when last read.
+    len: u64,
+    /// Keep the newest lines in view; scrolling up stops it, `G` resumes it.
+    pub follow: bool,
+}
+
+/// Full-screen pager over one tool call's complete output (Ctrl+O, or `p` in normal mode).
+#[derive(Debug, Clone)]
+pub struct ToolPager {
+    pub call_id: String,
+    /// The output split at newlines; the last entry is the line still being written.
+    pub lines: Vec<String>,
+    /// Bytes of the tool's output already split into `lines`.
+    synced: usize,
+    /// First line shown.
+    pub scroll: usize,
+    /// Keep the end in view as output arrives; scrolling up stops it, `G` resumes it.
+    pub follow: bool,
+    pub wrap: bool,
+    pub line_numbers: bool,
+    pub search_prompt: Option<String>,
+    pub query: Option<String>,
+    /// Line of the current search match.
+    pub match_line: Option<usize>,
+    /// Rows the pager had on screen last frame, for paging.
+    pub page: usize,
+}
+
+impl ToolPager {
+    fn new(call_id: String) -> Self {
+        Self {
+            call_id,
+            lines: vec![String::new()],
+            synced: 0,
+            scroll: 0,
+            follow: true,
+            wrap: false,
+            line_numbers: true,
+            search_prompt: None,
+            query: None,
+            match_line: None,
+            page: 0,
+        }
+    }
+
+    /// Lines to show: the one still being written only once it has text.
+    pub fn line_count(&self) -> usize {
+        match self.lines.last() {
+            Some(last) if last.is_empty() => self.lines.len() - 1,
+            _ => self.lines.len(),
+        }
+    }
+
+    fn append(&mut self, text: &str) {
+        for (i, seg) in text.split('\n').enumerate() {
+            match self.lines.last_mut() {
+                Some(last) if i == 0 => last.push_str(seg),
+                _ => self.lines.push(seg.to_string()),
+            }
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.line_count().saturating_sub(self.page.max(1))
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        let max = self.max_scroll();
+        self.scroll = self.scroll.min(max).saturating_add_signed(delta).min(max);
+        self.follow = self.scroll == max;
+    }
+
+    /// Jump to the next (or previous) line matching the query, wrapping around.
+    fn search_step(&mut self, forward: bool) {
+        let Some(query) = self.query.as_deref() else {
+            return;
+        };
+        let hits: Vec<usize> = (0..self.line_count())
+            .filter(|&i| !render_store::match_ranges(&self.lines[i], query).is_empty())
+            .collect();
+        let top = self.scroll;
+        let next = match (forward, self.match_line) {
+            (true, Some(cur)) => hits.iter().find(|&&l| l > cur).or(hits.first()),
+            (true, None) => hits.iter().find(|&&l| l >= top).or(hits.first()),
+            (false, Some(cur)) => hits.iter().rev().find(|&&l| l < cur).or(hits.last()),
+            (false, None) => hits.iter().rev().find(|&&l| l < top).or(hits.last()),
+        }
+        .copied();
+        self.match_line = next;
+        if let Some(line) = next {
+            self.follow = false;
+            if line < self.scroll || line >= self.scroll + self.page.max(1) {
+                self.scroll = line.saturating_sub(self.page / 3).min(self.max_scroll());
+            }
+        }
+    }
+}
+
+/// Tools whose output lists `path:line` locations Ctrl+G can open.
+const SEARCH_TOOLS: &[&str] = &["semantic_search", "find_symbol", "rg", "smart_search"];
+
+/// Raw PTY output kept per tool call for `/raw`.
+const MAX_RAW_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Tool output held in memory. Past this the whole output goes to a file in the session
+/// directory and only the most recent half of it stays in `ToolOutput::output`.
+const MAX_IN_MEMORY_OUTPUT: usize = 64 * 1024;
+
+/// Lines of the diagnostics log `/logs` keeps loaded.
+const LOG_TAIL_LINES: usize = 2_000;
+
+/// Recent files listed (and selectable) in the Context sidebar.
+pub const RECENT_FILES_SHOWN: usize = 10;
+
+/// How often the screen is redrawn while something on it moves (spinners, elapsed times,
+/// the reconnect countdown). Otherwise it's only redrawn when an event arrives.
+pub const ANIMATION_TICK: Duration = Duration::from_millis(250);
+
+/// How often an idle screen with a custom theme is redrawn, so edits to the theme file
+/// show up without waiting for a key press.
+const THEME_POLL: Duration = Duration::from_secs(1);
+
+/// Least time between indexing progress updates sent to the UI.
+const INDEX_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pause before re-running a tool whose process couldn't be started.
+const TOOL_INFRA_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Tools whose long results `[llm] tool_summary_tokens` condenses. File reads are left
+/// alone: edits need the exact text.
+const SUMMARIZED_TOOLS: &[&str] = &[
+    "bash",
+    "verify",
+    "rg",
+    "smart_search",
+    "semantic_search",
+    "list_files",
+    "lsp",
+];
+
+/// Files edited since the repository map was generated before it's rebuilt.
+const REPO_MAP_REFRESH_EDITS: usize = 15;
+
+/// Pinned files longer than this are cut off in the injected context.
+const MAX_PINNED_FILE_BYTES: usize = 32 * 1024;
+
+/// A `/quick` answer that takes longer than this is abandoned.
+const QUICK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The whole system prompt for `/quick`: no tools, no workspace, no memory.
+const QUICK_SYSTEM_PROMPT: &str =
+    "Answer the question directly and briefly. You have no tools and no access to the user's files.";
+
+/// Added to the system prompt in read-only mode, so the agent explains instead of editing.
+/// Sent with calls routed to `[routing] explore_model`.
+const EXPLORE_HINT: &str = "EXPLORATION: you are gathering context for the main model, with \
+read-only tools. Search and read what the task needs. When you know enough to act or answer, \
+reply with a short summary of what you found (files, line numbers, facts) and stop; the main \
+model writes the code and the answer.";
+
+/// Hands a turn back to the main model after an exploration summary; never stored.
+const EXPLORE_HANDOFF: &str = "Continue the task using the exploration notes above: make the \
+changes or write the answer.";
+
+const READ_ONLY_HINT: &str = "READ-ONLY MODE: the user asked you not to change this workspace. \
+write_file, edit_file and apply_patch are disabled, and bash commands that write files, \
+change git state or install packages are refused. Read, search and run read-only commands \
+to answer; when a change is needed, describe it (or show a diff) instead of making it.";
+
 #[derive(Debug, Clone)]
 pub struct ToolOutput {
     pub call_id: String,
@@ -83,8 +300,14 @@ pub struct ToolOutput {
     // Retained for compatibility with existing summary/memory code paths.
     pub target: String,
 
-    // Full aggregated output (model-visible). The UI should prefer output_lines/tail_lines.
+    // Aggregated output: all of it, or its tail once it spilled to `spill_path` (see
+    // `full_output`). The UI should prefer output_lines/tail_lines.
     pub output: String,
+    /// Bytes of output received, including what's only on disk.
+    pub output_bytes: usize,
+    /// Where the complete output goes once it outgrows memory.
+    spill_path: Option<PathBuf>,
+    spilled: bool,
 
     // Append-only line buffer for stable UI tails.
     pub output_lines: VecDeque<String>,
@@ -92,6 +315,9 @@ pub struct ToolOutput {
     output_total_lines: usize,
     output_truncated: bool,
 
+    /// Output as the terminal got it, for commands run under a PTY (see `/raw`).
+    pub raw_output: String,
+
     pub status: ToolStatus,
     pub turn_id: u64,
     pub group_id: u64,
@@ -122,10 +348,14 @@ impl ToolOutput {
             sandbox,
             target,
             output: String::new(),
+            output_bytes: 0,
+            spill_path: None,
+            spilled: false,
             output_lines: VecDeque::new(),
             output_partial: String::new(),
             output_total_lines: 0,
             output_truncated: false,
+            raw_output: String::new(),
             status: ToolStatus::Running,
             turn_id,
             group_id,
@@ -141,6 +371,11 @@ impl ToolOutput {
         }
     }
 
+    /// Report `elapsed` as the call's run time (for calls restored from a session).
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.end_time = Some(self.start_time + elapsed);
+    }
+
     pub fn complete(&mut self, success: bool) {
         self.status = if success {
             ToolStatus::Success
@@ -156,8 +391,44 @@ impl ToolOutput {
         }
     }
 
+    /// Keep a raw terminal line, up to [`MAX_RAW_OUTPUT_BYTES`].
+    pub fn append_raw(&mut self, chunk: &str) {
+        if self.raw_output.len() + chunk.len() <= MAX_RAW_OUTPUT_BYTES {
+            self.raw_output.push_str(chunk);
+        }
+    }
+
+    /// Write the complete output to `path` once it outgrows memory.
+    pub fn spill_to(&mut self, path: PathBuf) {
+        self.spill_path = Some(path);
+    }
+
+    /// Point at the complete output a previous run spilled to `path`, if it's still there.
+    pub fn attach_spilled(&mut self, path: PathBuf) {
+        if let Ok(meta) = std::fs::metadata(&path) {
+            self.output_bytes = self.output_bytes.max(meta.len() as usize);
+            self.spill_path = Some(path);
+            self.spilled = true;
+        }
+    }
+
+    /// The file holding the complete output, once it has spilled.
+    pub fn spill_file(&self) -> Option<&PathBuf> {
+        self.spill_path.as_ref().filter(|_| self.spilled)
+    }
+
+    /// The complete output, read back from disk if it spilled there.
+    pub fn full_output(&self) -> Cow<'_, str> {
+        match self.spill_file().and_then(|p| std::fs::read(p).ok()) {
+            Some(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+            None => Cow::Borrowed(&self.output),
+        }
+    }
+
     pub fn append_chunk(&mut self, chunk: String) {
         self.output.push_str(&chunk);
+        self.output_bytes += chunk.len();
+        self.spill(&chunk);
 
         for seg in chunk.split_inclusive('\n') {
             if let Some(stripped) = seg.strip_suffix('\n') {
@@ -172,6 +443,8 @@ impl ToolOutput {
 
     pub fn set_output(&mut self, content: String) {
         self.output.clear();
+        self.output_bytes = 0;
+        self.spilled = false;
         self.output_lines.clear();
         self.output_partial.clear();
         self.output_total_lines = 0;
@@ -180,6 +453,47 @@ impl ToolOutput {
         self.append_chunk(content);
     }
 
+    /// Move output past [`MAX_IN_MEMORY_OUTPUT`] to disk (best-effort: if the file can't
+    /// be written, everything stays in memory).
+    fn spill(&mut self, chunk: &str) {
+        let Some(path) = &self.spill_path else {
+            return;
+        };
+        if !self.spilled {
+            if self.output.len() <= MAX_IN_MEMORY_OUTPUT {
+                return;
+            }
+            let written = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::write(path, &self.output));
+            if written.is_err() {
+                self.spill_path = None;
+                return;
+            }
+            self.spilled = true;
+        } else {
+            let appended = std::fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .and_then(|mut f| f.write_all(chunk.as_bytes()));
+            if appended.is_err() {
+                return;
+            }
+        }
+        if self.output.len() > MAX_IN_MEMORY_OUTPUT {
+            // Keep whole lines where possible.
+            let mut cut = self.output.len() - MAX_IN_MEMORY_OUTPUT / 2;
+            while !self.output.is_char_boundary(cut) {
+                cut += 1;
+            }
+            if let Some(nl) = self.output[cut..].find('\n') {
+                cut += nl + 1;
+            }
+            self.output.drain(..cut);
+        }
+    }
+
     fn push_line(&mut self, mut line: String) {
         const MAX_STORED_LINES: usize = 5_000;
         const MAX_STORED_CHARS: usize = 80_000;
@@ -237,53 +551,12 @@ impl ToolOutput {
 
     /// Get the icon for this tool type
     pub fn icon(&self) -> &'static str {
-        match self.tool.as_str() {
-            "bash" => "$",
-            "rg" => "⌕",
-            "smart_search" => "≈",
-            "lsp" => "λ",
-            "read_file" => "▶",
-            "write_file" => "◀",
-            "list_files" => "◇",
-            "edit_file" => "±",
-            "apply_patch" => "▦",
-            "open_at" => "↗",
-            "semantic_search" => "?",
-            "verify" => "✓",
-            _ => "○",
-        }
+        crate::tool_registry::icon(&self.tool)
     }
 
     /// Get the action verb (present tense while running, past tense when done)
     pub fn action_verb(&self) -> &'static str {
-        match (&self.tool.as_str(), &self.status) {
-            (&"bash", ToolStatus::Running) => "Running",
-            (&"bash", _) => "Ran",
-            (&"rg", ToolStatus::Running) => "Searching",
-            (&"rg", _) => "Searched",
-            (&"smart_search", ToolStatus::Running) => "Searching",
-            (&"smart_search", _) => "Searched",
-            (&"lsp", ToolStatus::Running) => "Querying",
-            (&"lsp", _) => "Queried",
-            (&"read_file", ToolStatus::Running) => "Reading",
-            (&"read_file", _) => "Read",
-            (&"write_file", ToolStatus::Running) => "Writing",
-            (&"write_file", _) => "Wrote",
-            (&"list_files", ToolStatus::Running) => "Listing",
-            (&"list_files", _) => "Listed",
-            (&"edit_file", ToolStatus::Running) => "Editing",
-            (&"edit_file", _) => "Edited",
-            (&"apply_patch", ToolStatus::Running) => "Applying",
-            (&"apply_patch", _) => "Applied",
-            (&"open_at", ToolStatus::Running) => "Opening",
-            (&"open_at", _) => "Opened",
-            (&"semantic_search", ToolStatus::Running) => "Searching",
-            (&"semantic_search", _) => "Searched",
-            (&"verify", ToolStatus::Running) => "Verifying",
-            (&"verify", _) => "Verified",
-            (_, ToolStatus::Running) => "Processing",
-            (_, _) => "Done",
-        }
+        crate::tool_registry::verb(&self.tool, self.status == ToolStatus::Running)
     }
 }
 
@@ -300,6 +573,36 @@ pub enum Pane {
     Context,
 }
 
+/// Most memories the /memory browser loads.
+const MEMORY_BROWSER_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryField {
+    Content,
+    Tags,
+    Importance,
+}
+
+/// The /memory browser's edit form for one memory; fields are raw text until saved.
+#[derive(Debug, Clone)]
+pub struct MemoryEdit {
+    pub id: String,
+    pub field: MemoryField,
+    pub content: String,
+    pub tags: String,
+    pub importance: String,
+}
+
+impl MemoryEdit {
+    fn buffer_mut(&mut self) -> &mut String {
+        match self.field {
+            MemoryField::Content => &mut self.content,
+            MemoryField::Tags => &mut self.tags,
+            MemoryField::Importance => &mut self.importance,
+        }
+    }
+}
+
 const SYSTEM_PROMPT: &str = r#"You are Lorikeet, an autonomous coding agent.
 
 Tools:
@@ -313,8 +616,9 @@ Tools:
 - edit_file: Make surgical edits to files. Args: path, old_string, new_string. The old_string must be unique in the file.
 - apply_patch: Apply a patch (*** Begin Patch / Update File / Add File / Delete File). Use for refactors and non-trivial edits.
 - open_at: Read a file around a specific line with context + line numbers. Use after search results (path:line).
+- read_tool_output: Read the lines a long bash/verify result left out. Args: call_id from the omitted-lines note, start_line, end_line.
 - semantic_search: Search code semantically using natural language. Returns ranked results with file:line. Use for finding code related to concepts, features, or functionality. Auto-indexes on first use.
-- verify: Run a verify command (tests/build). If omitted, auto-detects a suggestion. Respects sandbox.
+- verify: Run a verify command (tests/build). If omitted, runs the fmt/lint/build/test pipeline and returns a per-stage summary. Respects sandbox.
 - memory_recall: Retrieve relevant long-term memory. Use before repeating actions or making risky changes.
 - memory_save: Save long-term memory about mistakes, preferences, and decisions. Never store secrets.
 - memory_list: List memories.
@@ -331,6 +635,8 @@ pub enum IndexingStatus {
     Indexing {
         files_done: usize,
         total_files: usize,
+        /// File being chunked or embedded
+        current: Option<String>,
     },
     Complete {
         chunks: usize,
@@ -348,21 +654,39 @@ pub struct App {
     pub tool_outputs: Vec<ToolOutput>,
     pub active_pane: Pane,
     pub should_quit: bool,
+    /// Quit was asked for while a response or tool was still running
+    pub quit_confirm_open: bool,
     pub is_processing: bool,
+    /// The call in flight is a `/quick` question; its answer stays out of the conversation.
+    quick_call: bool,
     pub processing_start: Option<Instant>,
+    /// When the current turn was submitted (for finished-turn notifications)
+    turn_started: Option<Instant>,
+    /// Whether the terminal has focus, as last reported by focus events
+    pub terminal_focused: bool,
     pub current_response: String,
     pub current_reasoning: String,
-    pub spinner_frame: usize,
-    pub tool_spinner_frame: usize,
+    /// Spinners pick their frame from the time since this, not from how often we draw.
+    spinner_epoch: Instant,
     pub command_suggest_selected: usize,
     event_tx: mpsc::UnboundedSender<AppEvent>,
     api_key: String,
     llm_provider: LlmProvider,
     codex_account_id: Option<String>,
     pub model: String,
+    /// Model serving the current call: `model`, or `[routing] explore_model` while exploring.
+    pub call_model: String,
+    /// Calls in a row routed to the explore model this turn.
+    explore_calls: u32,
+    /// Whether the last tool round only searched and read (`[routing] explore_tools`).
+    last_round_exploring: bool,
+    /// Model that wrote each agent message (by message id), shown while routing is on.
+    pub message_models: HashMap<u64, String>,
     sandbox_policy: Arc<SandboxPolicy>,
     pub memory: Arc<MemoryManager>,
     pub config: AppConfig,
+    /// `[languages]` from `config`, compiled once instead of on every frame
+    pub languages: LanguageOverrides,
     workspace_root: PathBuf,
     // Layout areas for mouse handling
     pub chat_area: Rect,
@@ -381,22 +705,99 @@ pub struct App {
     pub settings_selected: usize,
     pub settings_input: String,
     pub settings_cursor: usize,
+    /// Why the last save was refused; cleared when the selection changes.
+    pub settings_error: Option<String>,
     settings_focus: SettingsFocus,
     settings_original: AppConfig,
     settings_draft: AppConfig,
+    /// Keys set by `<workspace>/.lorikeet/config.toml`
+    project_layer: ProjectLayer,
     // Theme picker UI
     pub themes_open: bool,
     pub themes_query: String,
     pub themes_selected: usize,
     pub themes_cursor: usize,
+    // Model picker UI (/model)
+    pub models_open: bool,
+    pub models_query: String,
+    pub models_selected: usize,
+    pub models: Vec<ModelInfo>,
+    /// "Loading…" or the fetch error, shown under the list
+    pub models_status: Option<String>,
+    // Memory browser (/memory)
+    pub memories_open: bool,
+    pub memories_query: String,
+    pub memories_selected: usize,
+    /// Grouped by scope, then type (see `sort_memories`)
+    pub memories: Vec<Memory>,
+    /// "Loading…", an error or the result of the last edit, shown under the list
+    pub memories_status: Option<String>,
+    pub memory_edit: Option<MemoryEdit>,
+    /// Id of the memory a second Delete press will forget
+    memory_delete_pending: Option<String>,
+    /// LLM tokens (and cost) since launch or the last /new
+    pub session_usage: TokenUsage,
+    /// Model time and tokens per turn, for the `/stats` dashboard
+    pub turn_timings: BTreeMap<u64, TurnTiming>,
+    pub stats_open: bool,
+    pub stats_scroll: usize,
+    // Copy mode (pick a message or code block for the clipboard)
+    pub copy_open: bool,
+    pub copy_targets: Vec<CopyTarget>,
+    pub copy_selected: usize,
+    // Vim-style normal mode for the transcript (Esc on an empty input enters, `i` leaves)
+    pub chat_normal_mode: bool,
+    /// First `g` of a `gg`
+    pending_g: bool,
+    /// `/` search being typed in normal mode
+    pub transcript_search_prompt: Option<String>,
+    /// Last transcript search; its matches are highlighted in the chat pane
+    pub transcript_query: Option<String>,
+    /// Transcript row of the current match
+    pub transcript_match_row: Option<usize>,
+    /// `path:line` reference picked with `f`/`F` in normal mode (transcript row, reference)
+    pub selected_file_ref: Option<(usize, FileRef)>,
+    /// Editor run the main loop should hand the terminal to
+    pub pending_editor: Option<EditorRequest>,
+    // Allowed-but-unusual command waiting on a y/a/n answer
+    pub pending_approval: Option<ApprovalRequest>,
+    /// `a` was pressed in the approval popup; Enter confirms "always allow"
+    pub approval_always_armed: bool,
+    // Workspace trust: `None` until decided; the prompt stays up until then
+    pub workspace_trust: Option<TrustLevel>,
+    pub trust_prompt_open: bool,
+    /// Option highlighted in the trust prompt; nothing until an arrow key picks one, so
+    /// typing that was meant for the input can't decide it.
+    pub trust_choice: Option<TrustLevel>,
+    /// The codex provider's sign-in expired; offers to sign in again.
+    pub reauth_prompt_open: bool,
+    // `/restore` waiting for confirmation, with its dry-run summary
+    pub restore_prompt: Option<RestorePrompt>,
+    codex_login_running: bool,
     // Turn tracking (for memory extraction)
     turn_user_message: Option<String>,
     turn_tool_start_idx: usize,
     next_message_id: u64,
     pub render_store: RenderStore,
+    theme_cache: ThemeCache,
 
     // Turn tracking (for memory extraction and grouping user messages)
     pub current_turn_id: u64,
+    /// Edits this turn whose new language-server errors were sent back (`[lsp] auto_diagnostics`).
+    auto_diagnostics_reports: Arc<AtomicU32>,
+    /// Calls per tool this turn, for `[tools] max_calls_per_turn`.
+    tool_calls_this_turn: HashMap<String, u32>,
+    /// Tool-call rounds this turn, and the (round, time) the autonomy limits count from;
+    /// continuing a paused turn moves it.
+    turn_tool_rounds: u32,
+    turn_limits_from: (u32, Instant),
+    /// A turn paused at `[general] max_tool_rounds` / `max_turn_minutes`.
+    pub turn_limit_prompt: Option<TurnLimitPrompt>,
+    /// The provider is unreachable; the turn is resent when the countdown runs out.
+    pub offline: Option<Offline>,
+    /// The `/go` override and ephemeral message the last LLM call went out with, so a
+    /// resend asks the same thing.
+    call_overrides: (bool, Option<String>),
 
     // Tool trace grouping (one group per assistant tool-call phase)
     next_tool_group_id: u64,
@@ -406,6 +807,9 @@ pub struct App {
     // Inline tool trace UI state (keyed by tool_group_id)
     pub tool_trace_expanded: HashMap<u64, bool>,
     pub tool_trace_show_details: HashMap<u64, bool>,
+    // Agent message folding (keyed by message id); long ones start collapsed, except the
+    // latest answer
+    pub message_expanded: HashMap<u64, bool>,
 
     // Tool run index
     tool_index_by_call_id: HashMap<String, usize>,
@@ -416,11 +820,46 @@ pub struct App {
 
     // Context sidebar
     pub recent_files: VecDeque<String>,
+    // Successful edits per file this session, and (with memory.file_heatmap) before it
+    pub file_edits: HashMap<String, usize>,
+    pub file_edits_before: HashMap<String, u64>,
     pub last_searches: VecDeque<String>,
     pub last_checkpoint: Option<checkpoints::CheckpointMeta>,
+    // (turn, unix seconds) of the last `[checkpoints] auto` checkpoint
+    last_auto_checkpoint: Option<(u64, i64)>,
+    pub file_view: Option<FileView>,
+    // Selected entry in the sidebar's recent files, and the read-only viewer opened from it
+    pub recent_selected: usize,
+    pub recent_file_popup: Option<FileView>,
+    pub tool_pager: Option<ToolPager>,
+    // Read-only text over the chat, e.g. the last model request (/debug last)
+    pub text_popup: Option<FileView>,
+    pub log_tail: Option<LogTail>,
+    // Recent files whose content goes along with the next LLM call
+    pub pinned_files: Vec<String>,
+    // Paths bookmarked with /bookmark (persisted per workspace)
+    pub bookmarks: Vec<String>,
+    // Roots registered with /workspace add, and the one tools run in (workspace_root
+    // unless switched with /workspace use)
+    pub workspaces: Vec<PathBuf>,
+    pub active_root: PathBuf,
+    // This conversation's persistent shell (`[shell] persistent`), handed to tool calls
+    pub shell: crate::shell::SharedShell,
+    // Workspace outline sent with each LLM call, and the edit count when it was built
+    repo_map: Option<String>,
+    // Estimated context tokens, keyed by (message count, last message length, repo map
+    // length, model)
+    context_tokens_cache: Option<((usize, usize, usize, String), usize)>,
+    repo_map_edits: usize,
+    // (tool_outputs index, next location) for cycling through a search result with Ctrl+G
+    goto_cursor: Option<(usize, usize)>,
 
     // Plan mode: tools gated unless explicitly executed (/go).
     pub plan_mode: bool,
+    /// `--read-only` / `/readonly`: the sandbox denies edits and mutating commands.
+    pub read_only: bool,
+    /// Status bar banner while `lorikeet replay` plays a session; nothing is sent then.
+    pub replaying: Option<String>,
     tools_override_next: bool,
     ephemeral_user_message: Option<String>,
     pub plan_generating: bool,
@@ -433,16 +872,21 @@ pub struct App {
     pub plan_answer_cursor: usize,
     pub plan_preview_scroll: usize,
     pub plan_button_selected: usize, // 0 execute, 1 cancel
+    // Plan steps (1-based) the current execution turn is working on.
+    pub plan_steps_running: Vec<usize>,
 
     // Indexing status
     pub indexing_status: IndexingStatus,
-    pub indexing_spinner_frame: usize,
 
     // Verify suggestions
     pub verify_suggestions: Vec<crate::verify::VerifySuggestion>,
+    /// Outcome of each verify call this session, oldest first (the sidebar sparkline)
+    pub verify_history: Vec<bool>,
 
     // Session persistence
     pub session: Option<SessionStore>,
+    /// Short name for the session: from the first request, or set with `/rename`
+    pub session_title: Option<String>,
 }
 
 impl App {
@@ -466,8 +910,17 @@ impl App {
             .as_ref()
             .and_then(|g| g.model.clone())
             .unwrap_or_else(|| crate::llm::MODEL.to_string());
+        let plan_mode = config.default_plan_mode(&workspace_root);
         let settings_draft = config.clone();
         let settings_original = config.clone();
+        let project_layer = ProjectLayer::load(&workspace_root);
+        let bookmarks = bookmarks::load(&workspace_root);
+        let workspaces = workspaces::load(&workspace_root);
+        let sandbox_policy = if workspaces.is_empty() {
+            sandbox_policy
+        } else {
+            Arc::new(sandbox_policy.as_ref().clone().with_roots(&workspaces))
+        };
         Self {
             input: String::new(),
             cursor_pos: 0,
@@ -496,20 +949,28 @@ impl App {
             tool_outputs: vec![],
             active_pane: Pane::Chat,
             should_quit: false,
+            quit_confirm_open: false,
             is_processing: false,
+            quick_call: false,
             processing_start: None,
+            turn_started: None,
+            terminal_focused: true,
             current_response: String::new(),
             current_reasoning: String::new(),
-            spinner_frame: 0,
-            tool_spinner_frame: 0,
+            spinner_epoch: Instant::now(),
             command_suggest_selected: 0,
             event_tx,
             api_key,
             llm_provider,
             codex_account_id,
+            call_model: model.clone(),
+            explore_calls: 0,
+            last_round_exploring: false,
+            message_models: HashMap::new(),
             model,
             sandbox_policy,
             memory,
+            languages: LanguageOverrides::from_config(config.languages.as_ref()),
             config,
             workspace_root,
             chat_area: Rect::default(),
@@ -527,30 +988,95 @@ impl App {
             settings_selected: 0,
             settings_input: String::new(),
             settings_cursor: 0,
+            settings_error: None,
             settings_focus: SettingsFocus::Items,
             settings_original,
             settings_draft,
+            project_layer,
             themes_open: false,
             themes_query: String::new(),
             themes_selected: 0,
             themes_cursor: 0,
+            models_open: false,
+            models_query: String::new(),
+            models_selected: 0,
+            models: Vec::new(),
+            models_status: None,
+            memories_open: false,
+            memories_query: String::new(),
+            memories_selected: 0,
+            memories: Vec::new(),
+            memories_status: None,
+            memory_edit: None,
+            memory_delete_pending: None,
+            session_usage: TokenUsage::default(),
+            turn_timings: BTreeMap::new(),
+            stats_open: false,
+            stats_scroll: 0,
+            copy_open: false,
+            copy_targets: Vec::new(),
+            copy_selected: 0,
+            chat_normal_mode: false,
+            pending_g: false,
+            transcript_search_prompt: None,
+            transcript_query: None,
+            transcript_match_row: None,
+            selected_file_ref: None,
+            pending_editor: None,
+            pending_approval: None,
+            approval_always_armed: false,
+            workspace_trust: None,
+            trust_prompt_open: false,
+            trust_choice: None,
+            reauth_prompt_open: false,
+            restore_prompt: None,
+            codex_login_running: false,
             turn_user_message: None,
             turn_tool_start_idx: 0,
             next_message_id: 3,
             render_store: RenderStore::new(),
+            theme_cache: ThemeCache::default(),
             current_turn_id: 0,
+            auto_diagnostics_reports: Arc::new(AtomicU32::new(0)),
+            tool_calls_this_turn: HashMap::new(),
+            turn_tool_rounds: 0,
+            turn_limits_from: (0, Instant::now()),
+            turn_limit_prompt: None,
+            offline: None,
+            call_overrides: (false, None),
+            context_tokens_cache: None,
             next_tool_group_id: 1,
             last_tool_group_id: None,
             tool_group_by_call_id: HashMap::new(),
             tool_trace_expanded: HashMap::new(),
+            message_expanded: HashMap::new(),
             tool_trace_show_details: HashMap::new(),
             tool_index_by_call_id: HashMap::new(),
             tool_failure_counts: HashMap::new(),
             tool_loop_abort: None,
             recent_files: VecDeque::new(),
+            file_edits: HashMap::new(),
+            file_edits_before: HashMap::new(),
             last_searches: VecDeque::new(),
             last_checkpoint: None,
-            plan_mode: false,
+            last_auto_checkpoint: None,
+            file_view: None,
+            recent_selected: 0,
+            recent_file_popup: None,
+            tool_pager: None,
+            text_popup: None,
+            log_tail: None,
+            pinned_files: Vec::new(),
+            bookmarks,
+            workspaces,
+            active_root: workspace_root.clone(),
+            shell: Default::default(),
+            repo_map: None,
+            repo_map_edits: 0,
+            goto_cursor: None,
+            plan_mode,
+            read_only: sandbox_policy.read_only,
+            replaying: None,
             tools_override_next: false,
             ephemeral_user_message: None,
             plan_generating: false,
@@ -563,10 +1089,12 @@ impl App {
             plan_answer_cursor: 0,
             plan_preview_scroll: 0,
             plan_button_selected: 0,
+            plan_steps_running: Vec::new(),
             indexing_status: load_existing_index_status(),
-            indexing_spinner_frame: 0,
             verify_suggestions: Vec::new(),
+            verify_history: Vec::new(),
             session: None,
+            session_title: None,
         }
     }
 
@@ -598,16 +1126,94 @@ impl App {
         self.next_message_id = next;
     }
 
+    /// Play one step of `lorikeet replay` into the transcript. Tool calls show as running
+    /// until their `ToolDone` cue.
+    pub fn replay_cue(
+        &mut self,
+        events: &[SessionEvent],
+        cue: replay::Cue,
+        outputs_dir: &std::path::Path,
+    ) {
+        let turn_id = self
+            .messages
+            .iter()
+            .filter(|m| m.role == Role::User)
+            .count() as u64;
+        match cue {
+            replay::Cue::Event(i) => match &events[i] {
+                SessionEvent::Message { model, .. } => {
+                    if let Some(msg) = message_from_event(&events[i]) {
+                        self.push_message(msg);
+                        if let (Some(model), Some(last)) = (model, self.messages.last()) {
+                            self.message_models.insert(last.id, model.clone());
+                        }
+                    }
+                }
+                SessionEvent::Title { title, .. } => self.session_title = Some(title.clone()),
+                _ => {}
+            },
+            replay::Cue::ToolStart(i) => {
+                let Some(t) = tool_from_event(&events[i], turn_id, outputs_dir) else {
+                    return;
+                };
+                let group_id = t.group_id;
+                self.tool_index_by_call_id
+                    .insert(t.call_id.clone(), self.tool_outputs.len());
+                self.tool_outputs.push(ToolOutput::new(
+                    t.call_id,
+                    t.tool,
+                    t.args_raw,
+                    t.args_summary,
+                    t.cwd,
+                    t.sandbox,
+                    turn_id,
+                    group_id,
+                ));
+                if group_id > 0 {
+                    self.tool_trace_expanded.entry(group_id).or_insert(false);
+                    self.tool_trace_show_details.entry(group_id).or_insert(true);
+                    self.render_store
+                        .mark_dirty(RenderedBlockId::ToolGroup(group_id));
+                }
+            }
+            replay::Cue::ToolDone(i) => {
+                let Some(t) = tool_from_event(&events[i], turn_id, outputs_dir) else {
+                    return;
+                };
+                let group_id = t.group_id;
+                match self.tool_index_by_call_id.get(&t.call_id) {
+                    Some(&idx) => self.tool_outputs[idx] = t,
+                    None => self.tool_outputs.push(t),
+                }
+                if group_id > 0 {
+                    self.render_store
+                        .mark_dirty(RenderedBlockId::ToolGroup(group_id));
+                }
+                self.sync_tool_pager();
+            }
+        }
+    }
+
     pub fn init_session(&mut self, resume: bool) {
+        // Why the latest session couldn't be resumed, shown once the new one has started.
+        let mut busy = None;
         // Decide whether to resume
         if resume {
             if let Ok(Some(store)) = SessionStore::open_latest(&self.workspace_root) {
-                if let Ok(events) = store.load_events() {
+                if let Err(owner) = store.claim() {
+                    busy = Some(format!(
+                        "The latest session ({}) is open in another Lorikeet ({}), so this \
+                         one starts fresh. Resume it once that one exits or has been idle \
+                         for 15 minutes.",
+                        store.session_id, owner
+                    ));
+                } else if let Ok(events) = store.load_events() {
                     // Rebuild app state from events.
                     self.messages.clear();
                     self.tool_outputs.clear();
                     self.tool_trace_expanded.clear();
                     self.tool_trace_show_details.clear();
+                    self.message_expanded.clear();
                     self.tool_index_by_call_id.clear();
                     self.tool_failure_counts.clear();
                     self.tool_loop_abort = None;
@@ -615,8 +1221,26 @@ impl App {
                     self.last_tool_group_id = None;
                     self.next_tool_group_id = 1;
                     self.recent_files.clear();
-                    replay_into(&events, &mut self.messages, &mut self.tool_outputs);
+                    self.recent_selected = 0;
+                    self.pinned_files.clear();
+                    self.session_usage = TokenUsage::default();
+                    self.turn_timings.clear();
+                    replay_into(
+                        &events,
+                        &store.tool_outputs_dir(),
+                        &mut self.messages,
+                        &mut self.tool_outputs,
+                    );
+                    self.plan_draft = crate::session::latest_plan(&events);
+                    self.session_title = crate::session::latest_title(&events);
                     self.reassign_message_ids_if_needed();
+                    self.message_models.clear();
+                    let models = crate::session::message_models(&events);
+                    for (msg, model) in self.messages.iter().zip(models) {
+                        if let Some(model) = model {
+                            self.message_models.insert(msg.id, model);
+                        }
+                    }
                     self.render_store = RenderStore::new();
 
                     // Rebuild call_id -> group_id mapping from persisted tool events (best-effort).
@@ -680,6 +1304,14 @@ impl App {
                     for p in recent_paths {
                         self.push_recent_file(&p);
                     }
+                    self.file_edits.clear();
+                    for t in &self.tool_outputs {
+                        if t.status == ToolStatus::Success {
+                            for (path, _, _) in file_changes(&t.tool, &t.args_raw) {
+                                *self.file_edits.entry(path).or_insert(0) += 1;
+                            }
+                        }
+                    }
 
                     // Ensure we always have a system prompt to guide the agent.
                     if !self.messages.iter().any(|m| m.role == Role::System) {
@@ -697,11 +1329,15 @@ impl App {
                         );
                     }
 
+                    let conflicts = store.conflicted_copies();
+                    if let Some(prev) = self.session.take() {
+                        if prev.events_path != store.events_path {
+                            prev.release();
+                        }
+                    }
                     self.session = Some(store);
                     self.refresh_verify_suggestions();
-                    self.last_checkpoint = checkpoints::list_checkpoints(&self.workspace_root, 1)
-                        .ok()
-                        .and_then(|v| v.into_iter().next());
+                    self.refresh_last_checkpoint();
                     // User-visible notice.
                     self.push_message(Message {
                         id: 0,
@@ -712,37 +1348,210 @@ impl App {
                         tool_group_id: None,
                         local: true,
                     });
+                    if !conflicts.is_empty() {
+                        let mut note = "Your sync tool kept conflicting copies of this \
+                                        session's log; the transcript above is from the \
+                                        main file. Merge or delete them by hand:"
+                            .to_string();
+                        for path in &conflicts {
+                            note.push_str(&format!("\n- {}", path.display()));
+                        }
+                        self.push_local_note(&note);
+                    }
                     return;
                 }
             }
         }
 
         self.new_session();
+        if let Some(note) = busy {
+            self.push_local_note(&note);
+        }
     }
 
     pub fn new_session(&mut self) {
+        // A new conversation starts from a fresh shell too; a call still running in the
+        // old one finishes there, and the old shell ends with it.
+        self.shell = Default::default();
         let session_id = format!("{}", crate::memory::types::unix_ts());
         if let Ok(store) = SessionStore::new(&self.workspace_root, session_id) {
+            // The previous session is closed now, unless it's the same file.
+            if self
+                .session
+                .as_ref()
+                .is_some_and(|prev| prev.events_path != store.events_path)
+            {
+                self.close_session();
+            }
             store.init_file();
             self.session = Some(store);
         }
+        self.session_title = None;
 
         self.refresh_verify_suggestions();
-        self.last_checkpoint = checkpoints::list_checkpoints(&self.workspace_root, 1)
-            .ok()
-            .and_then(|v| v.into_iter().next());
+        self.refresh_last_checkpoint();
 
         // Persist the current system/hello messages.
         if let Some(store) = &self.session {
             for m in &self.messages {
-                store.record_message(m);
+                store.record_message(m, None);
             }
         }
     }
 
+    /// Show a one-shot summary of the agent's posture (workspace, model, sandbox, index).
+    pub fn push_startup_banner(&mut self) {
+        let mode = if self.plan_mode { "plan" } else { "auto" };
+        let sandbox = if self.sandbox_policy.enabled {
+            // Checked once a session: an older kernel runs commands unconfined silently.
+            let os = match Confinement::from_policy(&self.sandbox_policy).map(|c| c.probe()) {
+                None => "not enforced by the OS ([sandbox] enforce = false)".to_string(),
+                Some(Ok(Enforcement::Full)) => "enforced by the OS".to_string(),
+                Some(Ok(Enforcement::Partial)) => {
+                    "partly enforced by the OS (this kernel's Landlock is older)".to_string()
+                }
+                Some(Ok(Enforcement::Unsupported)) => {
+                    "not enforced by the OS (no Landlock in this kernel); policy checks only"
+                        .to_string()
+                }
+                Some(Err(e)) => format!("not enforced by the OS ({}); policy checks only", e),
+            };
+            format!("on (root: {}), {}", self.sandbox_policy.root.display(), os)
+        } else {
+            "off".to_string()
+        };
+        let index = match &self.indexing_status {
+            IndexingStatus::NotStarted => "not indexed".to_string(),
+            IndexingStatus::Indexing { .. } => "indexing".to_string(),
+            IndexingStatus::Complete { chunks, files } => {
+                let age = index_age_secs(&self.workspace_root)
+                    .map(|s| format!(", updated {} ago", format_age(s)))
+                    .unwrap_or_default();
+                format!("{} chunks / {} files{}", chunks, files, age)
+            }
+            IndexingStatus::Error(e) => format!("error: {}", e),
+        };
+
+        let mut content = format!(
+            "Workspace: {}\nMode: {} (Tab to toggle)\nModel: {} via {}\nSandbox: {}\nIndex: {}",
+            self.workspace_root_display(),
+            mode,
+            self.model,
+            self.llm_provider_name(),
+            sandbox,
+            index
+        );
+        if self.read_only {
+            content.push_str("\nRead-only: edits and mutating commands are blocked (/readonly)");
+        }
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
+
+    /// With `[checkpoints] auto`, checkpoint the workspace before a tool group that edits
+    /// files, named after the turn. See [`checkpoints::auto_checkpoint_due`] for the dedup.
+    fn maybe_auto_checkpoint(&mut self, tool_calls: &[ToolCallMessage]) {
+        let cfg = self.config.checkpoints.as_ref();
+        if !cfg.and_then(|c| c.auto).unwrap_or(false) {
+            return;
+        }
+        let min_interval = cfg.and_then(|c| c.auto_min_interval_secs).unwrap_or(120);
+        let now = crate::memory::types::unix_ts();
+        let due = checkpoints::auto_checkpoint_due(
+            tool_calls.iter().map(|tc| tc.function.name.as_str()),
+            self.last_auto_checkpoint,
+            self.current_turn_id,
+            now,
+            min_interval,
+        );
+        if !due {
+            return;
+        }
+        let Some(store) = self.session.as_ref() else {
+            return;
+        };
+        let request = self
+            .turn_user_message
+            .as_deref()
+            .and_then(|m| m.lines().find(|l| !l.trim().is_empty()))
+            .unwrap_or_default();
+        let request: String = request.trim().chars().take(48).collect();
+        let name = format!("auto: turn {} {}", self.current_turn_id, request);
+        match checkpoints::create_checkpoint(
+            &self.workspace_root,
+            store,
+            Some(name.trim_end().to_string()),
+        ) {
+            Ok(meta) => {
+                self.last_auto_checkpoint = Some((self.current_turn_id, now));
+                self.last_checkpoint = Some(meta);
+                self.prune_checkpoints_quietly();
+            }
+            Err(e) => self.push_local_note(&format!("Auto-checkpoint failed: {}", e)),
+        }
+    }
+
+    fn refresh_last_checkpoint(&mut self) {
+        self.last_checkpoint = checkpoints::list_checkpoints(&self.workspace_root, 1)
+            .ok()
+            .and_then(|v| v.into_iter().next());
+    }
+
+    /// Apply `[checkpoints]` retention after a new checkpoint; failures are ignored.
+    fn prune_checkpoints_quietly(&self) {
+        let retention = checkpoints::Retention::from_config(self.config.checkpoints.as_ref());
+        if !retention.is_unlimited() {
+            let _ = checkpoints::prune_checkpoints(&self.workspace_root, retention);
+        }
+    }
+
+    fn set_session_title(&mut self, title: String) {
+        if let Some(store) = &self.session {
+            store.record_title(&title);
+        }
+        self.session_title = Some(title);
+    }
+
+    /// Give up the current session's lease and, if configured, compress it on disk. Used
+    /// when `/new` replaces it and when the app quits.
+    pub fn close_session(&mut self) {
+        let Some(store) = self.session.take() else {
+            return;
+        };
+        store.release();
+        if self.compress_sessions_enabled() {
+            let _ = store.compress();
+        }
+    }
+
+    fn compress_sessions_enabled(&self) -> bool {
+        self.config
+            .general
+            .as_ref()
+            .and_then(|g| g.compress_sessions)
+            .unwrap_or(false)
+    }
+
     fn session_record_message(&self, msg: &Message) {
         if let Some(store) = &self.session {
-            store.record_message(msg);
+            let meta = (msg.role == Role::Agent && !msg.local).then(|| self.turn_meta());
+            store.record_message(msg, meta.as_ref());
+        }
+    }
+
+    fn turn_meta(&self) -> crate::session::TurnMeta {
+        crate::session::TurnMeta {
+            model: self.call_model.clone(),
+            plan_mode: self.plan_mode,
+            config_hash: self.config.fingerprint(),
         }
     }
 
@@ -754,14 +1563,39 @@ impl App {
         }
     }
     fn refresh_verify_suggestions(&mut self) {
-        self.verify_suggestions = detect_suggestions(&self.workspace_root);
+        self.verify_suggestions = suggestions_for(&self.active_root, self.config.verify.as_ref());
+    }
+
+    /// Bring everything derived from `self.config` up to date after it changed.
+    fn apply_config(&mut self) {
+        self.languages = LanguageOverrides::from_config(self.config.languages.as_ref());
+        self.rebuild_sandbox_policy();
     }
 
-    /// Start background indexing of the current directory
+    /// The sandbox policy for the current config, rooted at the active workspace and
+    /// allowing every registered root.
+    fn rebuild_sandbox_policy(&mut self) {
+        let roots = self.all_roots();
+        self.sandbox_policy = Arc::new(
+            SandboxPolicy::from_config(
+                self.config.clone(),
+                self.active_root.clone(),
+                &crate::tool_registry::names(),
+            )
+            .with_roots(&roots)
+            .with_read_only(self.read_only),
+        );
+    }
+
+    /// Start background indexing of the active workspace
     pub fn start_background_indexing(&self) {
+        self.start_indexing(self.active_root.clone());
+    }
+
+    /// Index `workspace_root` in the background (into its own per-workspace index)
+    fn start_indexing(&self, workspace_root: PathBuf) {
         let tx = self.event_tx.clone();
         let policy = self.sandbox_policy.clone();
-        let workspace_root = self.workspace_root.clone();
         tokio::spawn(async move {
             let _ = tx.send(AppEvent::IndexingStarted);
 
@@ -776,6 +1610,7 @@ impl App {
 
             if let Err(e) = result {
                 // Task panicked
+                tracing::error!(error = %e, "indexing task failed");
                 let _ = tx.send(AppEvent::IndexingError(format!(
                     "Indexing task failed: {}",
                     e
@@ -792,10 +1627,22 @@ impl App {
         self.split_ratio = next as u16;
     }
 
+    /// Submit `prompt` as if it had been typed (headless runs such as `lorikeet eval`).
+    pub fn submit_prompt(&mut self, prompt: &str) {
+        self.input = prompt.to_string();
+        self.cursor_pos = self.input.len();
+        self.submit_message();
+    }
+
     fn submit_message(&mut self) {
         if self.input.trim().is_empty() || self.is_processing {
             return;
         }
+        if self.replaying.is_some() {
+            self.set_input("");
+            self.push_local_note("[Replay: nothing is sent or run. Ctrl+C quits]");
+            return;
+        }
 
         let user_msg = self.input.clone();
         let user_trimmed = user_msg.trim().to_string();
@@ -863,7 +1710,7 @@ impl App {
                 self.session_record_message(last);
             }
 
-            let msg = "Unknown command. Try: /plan, /auto, /go, /settings, /themes, /verify, /checkpoint, /checkpoints, /restore, /checkpoint-diff, /new, /resume, /sessions, /help".to_string();
+            let msg = "Unknown command. Try: /plan, /plan show, /auto, /go, /go <step>, /settings, /themes, /model, /memory, /bookmark, /verify, /quick, /checkpoint, /checkpoints, /restore, /checkpoint-diff, /undo, /new, /resume, /rename, /sessions, /export, /stats, /find, /trust, /readonly, /login, /copy, /help".to_string();
             self.push_message(Message {
                 id: 0,
                 role: Role::Agent,
@@ -887,6 +1734,12 @@ impl App {
 
         // Each submitted user message is a new turn.
         self.current_turn_id = self.current_turn_id.saturating_add(1);
+        self.turn_started = Some(Instant::now());
+        self.auto_diagnostics_reports.store(0, Ordering::SeqCst);
+        self.tool_calls_this_turn.clear();
+        self.turn_tool_rounds = 0;
+        self.explore_calls = 0;
+        self.last_round_exploring = false;
         self.turn_tool_start_idx = self.tool_outputs.len();
         self.turn_user_message = Some(user_msg_for_mem.clone());
 
@@ -903,6 +1756,11 @@ impl App {
         if let Some(last) = self.messages.last() {
             self.session_record_message(last);
         }
+        if self.session_title.is_none() {
+            if let Some(title) = crate::session::title_from_request(&user_msg_for_mem) {
+                self.set_session_title(title);
+            }
+        }
 
         // Commands are handled before creating a normal user turn above.
 
@@ -943,6 +1801,10 @@ impl App {
     }
 
     fn start_llm_call(&mut self) {
+        if !self.is_processing {
+            // A request, `/go` or a continued pause: the autonomy limits count from here.
+            self.turn_limits_from = (self.turn_tool_rounds, Instant::now());
+        }
         self.is_processing = true;
         self.processing_start = Some(Instant::now());
         self.current_response.clear();
@@ -970,10 +1832,17 @@ impl App {
                 .as_ref()
                 .and_then(|m| m.auto_inject)
                 .unwrap_or(true);
+        let inject_scopes: Vec<MemoryScope> = self
+            .config
+            .memory
+            .as_ref()
+            .and_then(|m| m.inject_scopes.as_ref())
+            .map(|scopes| scopes.iter().filter_map(|s| s.parse().ok()).collect())
+            .unwrap_or_else(|| vec![MemoryScope::Project, MemoryScope::Global]);
 
         // Build a per-turn LLM message list with ephemeral memory injection.
         // This keeps memory out of the persisted transcript and avoids blocking the UI.
-        let base_chat_messages: Vec<ChatMessage> = self
+        let mut base_chat_messages: Vec<ChatMessage> = self
             .messages
             .iter()
             .filter(|m| !m.local)
@@ -981,14 +1850,69 @@ impl App {
             .map(|m| m.to_chat_message())
             .collect();
 
+        // The system prompt and memory block rarely change between calls; let providers
+        // that need explicit breakpoints cache them.
+        let prompt_cache = self
+            .config
+            .llm
+            .as_ref()
+            .and_then(|l| l.prompt_cache)
+            .unwrap_or(true);
+        if prompt_cache {
+            if let Some(system) = base_chat_messages.iter_mut().find(|m| m.role == "system") {
+                system.cache = true;
+            }
+        }
+
+        self.call_overrides = (
+            self.tools_override_next,
+            self.ephemeral_user_message.clone(),
+        );
         let (tools_enabled, ephemeral_user) = self.take_next_call_overrides();
+        let pinned_context = self.take_pinned_context();
+        let bookmarks_hint = bookmarks::system_hint(&self.bookmarks);
+        let workspaces_hint = workspaces::system_hint(&self.active_root, &self.all_roots());
+        let read_only_hint = self.read_only.then(|| READ_ONLY_HINT.to_string());
+        let (repo_map, repo_map_root) = self.repo_map_for_call();
         self.plan_generating = self.plan_mode && !tools_enabled;
+        let tool_set = if self.plan_generating {
+            ToolSet::Plan
+        } else if !tools_enabled {
+            ToolSet::None
+        } else if self.workspace_restricted() {
+            ToolSet::ReadOnly
+        } else if self.intent_router_enabled()
+            && ephemeral_user.is_none()
+            && is_informational_prompt(&user_message)
+        {
+            // Questions only need to look around; don't advertise tools that modify things.
+            ToolSet::ReadOnly
+        } else {
+            ToolSet::Full
+        };
+        let explore_model = self.explore_model_for_call(tool_set);
+        let tool_set = if explore_model.is_some() {
+            // Exploring never writes; the main model does that.
+            self.explore_calls += 1;
+            ToolSet::ReadOnly
+        } else {
+            self.explore_calls = 0;
+            tool_set
+        };
+        self.call_model = explore_model.unwrap_or_else(|| self.model.clone());
+        let explore_hint = (self.explore_calls > 0).then(|| EXPLORE_HINT.to_string());
 
         let tx = self.event_tx.clone();
-        let api_key = self.api_key.clone();
-        let llm_provider = self.llm_provider;
-        let codex_account_id = self.codex_account_id.clone();
-        let model = self.model.clone();
+        let target = LlmTarget {
+            provider: self.llm_provider,
+            api_key: self.api_key.clone(),
+            codex_account_id: self.codex_account_id.clone(),
+            model: self.call_model.clone(),
+            hidden_tools: self.hidden_tools_for(&self.call_model),
+            responses_api: self.responses_api_for(self.llm_provider, &self.call_model),
+        };
+        let fallback = self.llm_fallback_target();
+        let retry = self.llm_retry_policy();
         let memory = self.memory.clone();
 
         tokio::spawn(async move {
@@ -1011,12 +1935,15 @@ impl App {
                         tool_calls: None,
                         tool_call_id: None,
                         name: None,
+                        cache: false,
                     },
                 );
             }
 
             if inject_memory {
-                let memory_context = memory.build_injection_context(&user_message, &[]).await;
+                let memory_context = memory
+                    .build_injection_context(&user_message, &[], &inject_scopes)
+                    .await;
 
                 if !memory_context.is_empty() {
                     // Insert right after the first system prompt (if present).
@@ -1034,112 +1961,896 @@ impl App {
                             tool_calls: None,
                             tool_call_id: None,
                             name: None,
+                            cache: prompt_cache,
                         },
                     );
                 }
             }
 
-            if let Some(msg) = ephemeral_user {
-                chat_messages.push(ChatMessage {
-                    role: "user".into(),
-                    content: Some(msg),
-                    tool_calls: None,
-                    tool_call_id: None,
-                    name: None,
-                });
+            for hint in [bookmarks_hint, workspaces_hint, read_only_hint, explore_hint]
+                .into_iter()
+                .flatten()
+            {
+                let insert_at = chat_messages
+                    .iter()
+                    .position(|m| m.role == "system")
+                    .map(|idx| idx + 1)
+                    .unwrap_or(0);
+                chat_messages.insert(
+                    insert_at,
+                    ChatMessage {
+                        role: "system".into(),
+                        content: Some(hint),
+                        tool_calls: None,
+                        tool_call_id: None,
+                        name: None,
+                        cache: prompt_cache,
+                    },
+                );
             }
 
-            call_llm(
-                tx,
-                llm_provider,
-                api_key,
-                codex_account_id,
-                model,
-                chat_messages,
-                tools_enabled,
-            )
-            .await;
+            let repo_map = match (repo_map, repo_map_root) {
+                (Some(map), _) => Some(map),
+                (None, Some(root)) => {
+                    let map = tokio::task::spawn_blocking(move || crate::repo_map::generate(&root))
+                        .await
+                        .ok()
+                        .flatten();
+                    if let Some(map) = &map {
+                        let _ = tx.send(AppEvent::RepoMapReady(map.clone()));
+                    }
+                    map
+                }
+                (None, None) => None,
+            };
+            if let Some(map) = repo_map {
+                // Right after the system prompt: it changes rarely, so it caches with it.
+                let insert_at = chat_messages
+                    .iter()
+                    .position(|m| m.role == "system")
+                    .map(|idx| idx + 1)
+                    .unwrap_or(0);
+                chat_messages.insert(
+                    insert_at,
+                    ChatMessage {
+                        role: "system".into(),
+                        content: Some(map),
+                        tool_calls: None,
+                        tool_call_id: None,
+                        name: None,
+                        cache: prompt_cache,
+                    },
+                );
+            }
+
+            if let Some(context) = pinned_context {
+                // Just before the newest user message, so it reads as part of the request.
+                let insert_at = chat_messages
+                    .iter()
+                    .rposition(|m| m.role == "user")
+                    .unwrap_or(chat_messages.len());
+                chat_messages.insert(
+                    insert_at,
+                    ChatMessage {
+                        role: "system".into(),
+                        content: Some(context),
+                        tool_calls: None,
+                        tool_call_id: None,
+                        name: None,
+                        cache: false,
+                    },
+                );
+            }
+
+            if let Some(msg) = ephemeral_user {
+                chat_messages.push(ChatMessage {
+                    role: "user".into(),
+                    content: Some(msg),
+                    tool_calls: None,
+                    tool_call_id: None,
+                    name: None,
+                    cache: false,
+                });
+            }
+
+            call_llm(tx, target, fallback, retry, chat_messages, tool_set).await;
         });
     }
 
-    fn tools_enabled_for_next_call(&self) -> bool {
-        !self.plan_mode || self.tools_override_next
+    /// `/quick`: just the question and a one-line system prompt, without history, memory,
+    /// pinned files or tool schemas. The answer is shown but never sent back to the model.
+    fn start_quick_call(&mut self, question: String) {
+        self.is_processing = true;
+        self.quick_call = true;
+        self.processing_start = Some(Instant::now());
+        self.current_response.clear();
+        self.current_reasoning.clear();
+        self.chat_follow = true;
+
+        let chat_messages = vec![
+            ChatMessage {
+                role: "system".into(),
+                content: Some(QUICK_SYSTEM_PROMPT.into()),
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+                cache: false,
+            },
+            ChatMessage {
+                role: "user".into(),
+                content: Some(question),
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+                cache: false,
+            },
+        ];
+        let tx = self.event_tx.clone();
+        let target = LlmTarget {
+            provider: self.llm_provider,
+            api_key: self.api_key.clone(),
+            codex_account_id: self.codex_account_id.clone(),
+            model: self.model.clone(),
+            hidden_tools: self.hidden_tools_for(&self.model),
+            responses_api: self.responses_api_for(self.llm_provider, &self.model),
+        };
+        let fallback = self.llm_fallback_target();
+        let retry = self.llm_retry_policy();
+
+        tokio::spawn(async move {
+            let call = call_llm(
+                tx.clone(),
+                target,
+                fallback,
+                retry,
+                chat_messages,
+                ToolSet::None,
+            );
+            if tokio::time::timeout(QUICK_TIMEOUT, call).await.is_err() {
+                let _ = tx.send(AppEvent::AgentError(
+                    format!(
+                        "quick question timed out after {}s",
+                        QUICK_TIMEOUT.as_secs()
+                    )
+                    .into(),
+                ));
+            }
+        });
     }
 
-    fn take_next_call_overrides(&mut self) -> (bool, Option<String>) {
-        let tools_enabled = self.tools_enabled_for_next_call();
-        let ephemeral_user = self.ephemeral_user_message.take();
-        // /go is a one-shot override.
-        self.tools_override_next = false;
-        (tools_enabled, ephemeral_user)
+    fn llm_retry_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        let llm = self.config.llm.as_ref();
+        RetryPolicy {
+            max_retries: llm
+                .and_then(|l| l.max_retries)
+                .unwrap_or(defaults.max_retries),
+            base_delay: llm
+                .and_then(|l| l.retry_base_ms)
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.base_delay),
+        }
     }
 
-    fn prepare_go(&mut self) {
-        self.tools_override_next = true;
-        self.ephemeral_user_message =
-            Some("Execute the plan above. Start running tools now. Verify when reasonable.".into());
+    /// `[llm] tool_summary_tokens` and the model that writes the summaries, when enabled.
+    fn tool_summarizer(&self) -> Option<ToolSummarizer> {
+        let llm = self.config.llm.as_ref()?;
+        let threshold_tokens = llm.tool_summary_tokens.filter(|t| *t > 0)?;
+        Some(ToolSummarizer {
+            provider: self.llm_provider,
+            api_key: self.api_key.clone(),
+            model: llm
+                .tool_summary_model
+                .clone()
+                .unwrap_or_else(|| self.model.clone()),
+            threshold_tokens,
+        })
     }
 
-    fn prepare_go_with_plan(&mut self, plan: &PlanDraft) {
-        self.tools_override_next = true;
-        let mut msg = String::new();
-        msg.push_str("Execute the plan below.\n\nPlan:\n");
-        msg.push_str(&plan.plan_markdown);
-        msg.push_str("\n\nAnswers:\n");
-        if plan.questions.is_empty() {
-            msg.push_str("(none)\n");
-        } else {
-            for q in &plan.questions {
-                let ans = plan
-                    .answers
-                    .get(&q.id)
-                    .cloned()
-                    .unwrap_or_else(|| q.default.clone().unwrap_or_default());
-                msg.push_str(&format!("- {}: {}\n", q.prompt, ans));
+    /// `[llm] fallback_provider`, with its API key from the environment. Codex needs no
+    /// key here: call_llm refreshes its OAuth token per request.
+    fn llm_fallback_target(&self) -> Option<LlmTarget> {
+        let llm = self.config.llm.as_ref()?;
+        let provider = LlmProvider::parse(llm.fallback_provider.as_deref()?)?;
+        let model = llm
+            .fallback_model
+            .clone()
+            .unwrap_or_else(|| self.model.clone());
+        let api_key = match provider {
+            LlmProvider::OpenRouter => std::env::var("OPENROUTER_API_KEY").ok()?,
+            LlmProvider::OpenAI => std::env::var("OPENAI_API_KEY").ok()?,
+            LlmProvider::Gemini => crate::llm::gemini_api_key()?,
+            LlmProvider::Codex => String::new(),
+        };
+        let api_key = api_key.trim().to_string();
+        if api_key.is_empty() && provider != LlmProvider::Codex {
+            return None;
+        }
+        Some(LlmTarget {
+            provider,
+            api_key,
+            codex_account_id: None,
+            hidden_tools: self.hidden_tools_for(model.as_str()),
+            responses_api: self.responses_api_for(provider, &model),
+            model,
+        })
+    }
+
+    /// Whether calls to `model` go through OpenAI's Responses API (`[llm] responses_models`).
+    fn responses_api_for(&self, provider: LlmProvider, model: &str) -> bool {
+        provider == LlmProvider::OpenAI
+            && self
+                .config
+                .llm
+                .as_ref()
+                .is_some_and(|l| l.uses_responses_api(model))
+    }
+
+    /// `[routing] explore_model` when this call continues a search/read loop: the previous
+    /// tool round only explored, and fewer than `max_explore_calls` calls in a row went there.
+    fn explore_model_for_call(&self, tool_set: ToolSet) -> Option<String> {
+        let routing = self.config.routing.as_ref()?;
+        let model = routing.explore_model()?;
+        let max = routing.max_explore_calls.unwrap_or(6);
+        (tool_set.enabled()
+            && self.last_round_exploring
+            && self.explore_calls < max
+            && model != self.model)
+            .then(|| model.to_string())
+    }
+
+    /// The model to show under agent message `id`; only while routing is on.
+    pub fn message_model(&self, id: u64) -> Option<&str> {
+        self.config.routing.as_ref()?.explore_model()?;
+        self.message_models.get(&id).map(String::as_str)
+    }
+
+    /// Remember which model wrote the message just pushed.
+    fn tag_last_message_model(&mut self) {
+        if let Some(last) = self.messages.last() {
+            self.message_models.insert(last.id, self.call_model.clone());
+        }
+    }
+
+    /// Tools `model` isn't offered right now: `[tools] disabled`, `hide_for_models`, and
+    /// those that used up `max_calls_per_turn` this turn.
+    fn hidden_tools_for(&self, model: &str) -> Vec<String> {
+        let tools = self.config.tools.clone().unwrap_or_default();
+        let mut hidden = tools.hidden_for(model);
+        for (name, calls) in &self.tool_calls_this_turn {
+            if tools.max_calls(name).is_some_and(|max| *calls >= max) && !hidden.contains(name) {
+                hidden.push(name.clone());
             }
         }
-        msg.push_str("\nStart running tools now. Verify when reasonable.");
-        self.ephemeral_user_message = Some(msg);
+        hidden
     }
 
-    fn load_plan_answer_input(&mut self) {
-        let Some(draft) = &self.plan_draft else {
-            self.plan_answer_input.clear();
-            self.plan_answer_cursor = 0;
+    /// Count this group's calls against the turn's budgets. Returns the calls that must not
+    /// run (call id -> result for the model): tools that are disabled or hidden from the
+    /// current model, and calls past a tool's `max_calls_per_turn`.
+    fn tool_budget_refusals(&mut self, tool_calls: &[ToolCallMessage]) -> HashMap<String, String> {
+        let tools = self.config.tools.clone().unwrap_or_default();
+        let hidden = tools.hidden_for(&self.model);
+        let disabled = tools.disabled.unwrap_or_default();
+        let mut refusals = HashMap::new();
+        for tc in tool_calls {
+            let name = tc.function.name.as_str();
+            let calls = self.tool_calls_this_turn.entry(name.to_string()).or_insert(0);
+            *calls += 1;
+            let reason = if disabled.iter().any(|d| d == name) {
+                format!("`{}` is disabled ([tools] disabled).", name)
+            } else if hidden.iter().any(|h| h == name) {
+                format!(
+                    "`{}` is not available to {} ([tools] hide_for_models).",
+                    name, self.model
+                )
+            } else if let Some(max) = tools.max_calls(name).filter(|max| *calls > *max) {
+                format!(
+                    "Budget exceeded: `{}` is limited to {} call{} per turn \
+                     ([tools] max_calls_per_turn). Work with the results you have or use \
+                     another tool.",
+                    name,
+                    max,
+                    if max == 1 { "" } else { "s" }
+                )
+            } else {
+                continue;
+            };
+            refusals.insert(tc.id.clone(), reason);
+        }
+        refusals
+    }
+
+    fn esc_quits(&self) -> bool {
+        self.config
+            .general
+            .as_ref()
+            .and_then(|g| g.esc_quits)
+            .unwrap_or(false)
+    }
+
+    /// What quitting now would cut short, for the confirmation prompt.
+    pub fn quit_blockers(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.is_processing {
+            out.push("The model is still responding.".to_string());
+        }
+        let running: Vec<&str> = self
+            .tool_outputs
+            .iter()
+            .filter(|t| t.status == ToolStatus::Running)
+            .map(|t| t.tool.as_str())
+            .collect();
+        if !running.is_empty() {
+            out.push(format!("Still running: {}", running.join(", ")));
+        }
+        out
+    }
+
+    /// How a finished `event` that took `elapsed` should notify: off when it's configured
+    /// off, was quicker than `notifications.min_seconds`, or the terminal has focus.
+    fn notification_for(&self, event: NotifyEvent, elapsed: Duration) -> Notify {
+        let settings = self.config.notifications.as_ref();
+        let min_seconds = settings.and_then(|n| n.min_seconds).unwrap_or(30);
+        let when_focused = settings.and_then(|n| n.when_focused).unwrap_or(false);
+        if elapsed.as_secs() < min_seconds || (self.terminal_focused && !when_focused) {
+            return Notify::Off;
+        }
+        notify::channel(&self.config, event)
+    }
+
+    fn notify_finished(&self, event: NotifyEvent, elapsed: Duration, message: &str) {
+        notify::send(self.notification_for(event, elapsed), message);
+    }
+
+    fn notify_turn_finished(&mut self, outcome: &str) {
+        let Some(started) = self.turn_started.take() else {
             return;
         };
-        let Some(q) = draft.questions.get(self.plan_question_selected) else {
-            self.plan_answer_input.clear();
-            self.plan_answer_cursor = 0;
+        let elapsed = started.elapsed();
+        let message = format!(
+            "lorikeet: turn {} after {}",
+            outcome,
+            stats::format_duration(elapsed)
+        );
+        self.notify_finished(NotifyEvent::Turn, elapsed, &message);
+    }
+
+    /// `[general] max_tool_rounds` and `max_turn_minutes`; 0 turns a limit off.
+    fn turn_limits(&self) -> (u32, u64) {
+        let general = self.config.general.as_ref();
+        (
+            general.and_then(|g| g.max_tool_rounds).unwrap_or(40),
+            general.and_then(|g| g.max_turn_minutes).unwrap_or(20),
+        )
+    }
+
+    /// The autonomy limit this turn has run into since it started or was last continued.
+    fn turn_limit_reached(&self) -> Option<String> {
+        let (max_rounds, max_minutes) = self.turn_limits();
+        let (from_round, since) = self.turn_limits_from;
+        let rounds = self.turn_tool_rounds.saturating_sub(from_round);
+        if max_rounds > 0 && rounds >= max_rounds {
+            return Some(format!("{} tool rounds", rounds));
+        }
+        let minutes = since.elapsed().as_secs() / 60;
+        if max_minutes > 0 && minutes >= max_minutes {
+            return Some(format!("{} minutes", minutes));
+        }
+        None
+    }
+
+    /// Files this turn's edit tools changed so far.
+    fn turn_changed_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for t in self.tool_outputs.iter().skip(self.turn_tool_start_idx) {
+            let edit = crate::tool_registry::access(&t.tool)
+                == Some(crate::tool_registry::ToolAccess::Edit);
+            if edit && t.status == ToolStatus::Success && !files.contains(&t.target) {
+                files.push(t.target.clone());
+            }
+        }
+        files
+    }
+
+    /// Hold the turn before its next LLM call and ask whether to keep going. The note left
+    /// in the transcript (and session) records how far it got, so stopping loses nothing.
+    fn pause_turn(&mut self, limit: String) {
+        let elapsed = self.turn_started.map(|s| s.elapsed()).unwrap_or_default();
+        let files = self.turn_changed_files();
+        let mut note = format!(
+            "[Paused: this turn reached {} ({} tool rounds in {})]",
+            limit,
+            self.turn_tool_rounds,
+            stats::format_duration(elapsed)
+        );
+        if !files.is_empty() {
+            note.push_str(&format!("\nFiles changed so far: {}", files.join(", ")));
+        }
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: note,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        if let Some(last) = self.messages.last() {
+            self.session_record_message(last);
+        }
+        self.scroll_messages_to_bottom();
+        self.is_processing = false;
+        self.processing_start = None;
+        self.notify_finished(
+            NotifyEvent::Turn,
+            elapsed,
+            &format!("lorikeet: turn paused after {}", limit),
+        );
+        self.turn_limit_prompt = Some(TurnLimitPrompt {
+            limit,
+            rounds: self.turn_tool_rounds,
+            elapsed,
+            files,
+        });
+    }
+
+    fn handle_turn_limit_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                self.turn_limit_prompt = None;
+                self.start_llm_call();
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                self.turn_limit_prompt = None;
+                self.turn_started = None;
+                self.push_local_note("Stopped here. Send a message to pick the work up again.");
+            }
+            _ => {}
+        }
+    }
+
+    /// The provider couldn't be reached: keep the turn and schedule a resend, backing off
+    /// from 5s to a minute between attempts.
+    fn go_offline(&mut self, error: AgentError) {
+        let attempts = self.offline.as_ref().map_or(0, |o| o.attempts) + 1;
+        let delay = Duration::from_secs((5u64 << (attempts - 1).min(4)).min(60));
+        if attempts == 1 {
+            self.push_local_note(&format!(
+                "[Offline: {}]\nYour message is queued and will be resent once the provider \
+                 is reachable (Enter retries now, Esc gives up).",
+                error
+            ));
+        }
+        self.offline = Some(Offline {
+            error,
+            attempts,
+            since: self.offline.as_ref().map_or_else(Instant::now, |o| o.since),
+            retry_at: Some(Instant::now() + delay),
+        });
+        let tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = tx.send(AppEvent::Reconnect(attempts));
+        });
+    }
+
+    /// Resend the turn waiting in `offline` with the same overrides as the failed call.
+    fn reconnect_now(&mut self) {
+        let Some(offline) = self.offline.as_mut() else {
             return;
         };
-        let val = draft
-            .answers
-            .get(&q.id)
-            .cloned()
-            .or_else(|| q.default.clone())
-            .unwrap_or_default();
-        self.plan_answer_input = val;
-        self.plan_answer_cursor = self.plan_answer_input.len();
+        if offline.retry_at.take().is_none() {
+            return; // already resending
+        }
+        let (tools_override, ephemeral) = self.call_overrides.clone();
+        self.tools_override_next = tools_override;
+        self.ephemeral_user_message = ephemeral;
+        self.start_llm_call();
     }
 
-    fn save_plan_answer_input(&mut self) {
-        let Some(draft) = &mut self.plan_draft else {
+    fn back_online(&mut self) {
+        if let Some(offline) = self.offline.take() {
+            self.push_local_note(&format!(
+                "[Reconnected after {}]",
+                stats::format_duration(offline.since.elapsed())
+            ));
+        }
+    }
+
+    /// Give up on the queued turn; it ends with the last connection error.
+    fn stop_reconnecting(&mut self) {
+        if let Some(offline) = self.offline.take() {
+            self.handle_event(AppEvent::AgentError(offline.error));
+        }
+    }
+
+    /// Status bar banner while a turn waits out an outage.
+    pub fn offline_banner(&self) -> Option<String> {
+        let offline = self.offline.as_ref()?;
+        Some(match offline.retry_at {
+            Some(at) => format!(
+                " OFFLINE: resending in {}s (attempt {}) · ENTER now · ESC give up ",
+                at.saturating_duration_since(Instant::now()).as_secs() + 1,
+                offline.attempts + 1
+            ),
+            None => format!(" OFFLINE: reconnecting (attempt {}) ", offline.attempts + 1),
+        })
+    }
+
+    /// Estimated tokens the next call sends: the transcript the model sees, the repo map and
+    /// the tool schemas. Cached until the transcript or model changes.
+    pub fn context_tokens(&mut self) -> usize {
+        let key = (
+            self.messages.len(),
+            self.messages.last().map_or(0, |m| m.content.len()),
+            self.repo_map.as_ref().map_or(0, |m| m.len()),
+            self.model.clone(),
+        );
+        if let Some((cached_key, total)) = &self.context_tokens_cache {
+            if *cached_key == key {
+                return *total;
+            }
+        }
+        let model = self.model.as_str();
+        let mut total: usize = self
+            .messages
+            .iter()
+            .filter(|m| !m.local)
+            .map(|m| {
+                let calls = m.tool_calls.iter().flatten().map(|c| {
+                    tokens::estimate(&c.function.name, model)
+                        + tokens::estimate(&c.function.arguments, model)
+                });
+                tokens::PER_MESSAGE + tokens::estimate(&m.content, model) + calls.sum::<usize>()
+            })
+            .sum();
+        total += self
+            .repo_map
+            .as_deref()
+            .map_or(0, |map| tokens::estimate(map, model));
+        total += crate::tool_registry::specs()
+            .iter()
+            .map(|spec| {
+                tokens::estimate(&spec.description, model)
+                    + tokens::estimate(&spec.parameters.to_string(), model)
+            })
+            .sum::<usize>();
+        self.context_tokens_cache = Some((key, total));
+        total
+    }
+
+    /// The current model's context window: from the provider's model list once `/model`
+    /// has loaded it, else from the known model families.
+    pub fn context_window(&self) -> Option<u64> {
+        self.models
+            .iter()
+            .find(|m| m.id == self.model)
+            .and_then(|m| m.context_window)
+            .or_else(|| tokens::context_window(&self.model))
+    }
+
+    /// Quit, or ask first when that would interrupt work in flight.
+    fn request_quit(&mut self) {
+        if self.quit_blockers().is_empty() {
+            self.should_quit = true;
+        } else {
+            self.quit_confirm_open = true;
+        }
+    }
+
+    fn handle_reauth_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                self.reauth_prompt_open = false;
+                self.start_codex_login();
+            }
+            KeyCode::Char('n') | KeyCode::Esc => self.reauth_prompt_open = false,
+            _ => {}
+        }
+    }
+
+    /// Rewrite the workspace to checkpoint `meta` and rewind the session to match.
+    fn restore_checkpoint_now(&mut self, meta: checkpoints::CheckpointMeta) {
+        if self.session.is_none() {
+            self.new_session();
+        }
+        let Some(store) = self.session.as_ref() else {
             return;
         };
-        let Some(q) = draft.questions.get(self.plan_question_selected) else {
+
+        if let Err(e) = checkpoints::restore_checkpoint(&self.workspace_root, store, &meta) {
+            self.push_local_note(&format!("Restore error: {}", e));
             return;
-        };
-        draft
-            .answers
-            .insert(q.id.clone(), self.plan_answer_input.clone());
+        }
+
+        if let Err(e) = checkpoints::truncate_session_to(store, meta.session_event_count) {
+            self.push_local_note(&format!("Session rewind error: {}", e));
+            return;
+        }
+
+        // Reload the rewound session so UI matches the restored timeline.
+        self.init_session(true);
+        self.last_checkpoint = Some(meta.clone());
+        self.push_local_note(&format!("(restored checkpoint {})", meta.id));
     }
 
-    fn handle_plan_key(&mut self, key: KeyEvent) {
+    fn handle_restore_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = &mut self.restore_prompt else {
+            return;
+        };
         match key.code {
-            KeyCode::Esc => {
-                self.plan_popup_open = false;
-            }
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let meta = prompt.meta.clone();
+                self.restore_prompt = None;
+                self.restore_checkpoint_now(meta);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => self.restore_prompt = None,
+            KeyCode::Up | KeyCode::Char('k') => prompt.scroll = prompt.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => prompt.scroll = prompt.scroll.saturating_add(1),
+            KeyCode::PageUp => prompt.scroll = prompt.scroll.saturating_sub(10),
+            KeyCode::PageDown => prompt.scroll = prompt.scroll.saturating_add(10),
+            _ => {}
+        }
+    }
+
+    /// Sign in with ChatGPT in the browser (`/login`); the result arrives as
+    /// `AppEvent::CodexLoginDone`.
+    fn start_codex_login(&mut self) {
+        if self.codex_login_running {
+            self.push_local_note("[Sign-in already in progress: finish it in the browser]");
+            return;
+        }
+        self.codex_login_running = true;
+        let tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let result = match crate::codex_oauth::start_login().await {
+                Ok(request) => {
+                    let _ = tx.send(AppEvent::CodexLoginUrl(request.url.clone()));
+                    crate::codex_oauth::open_browser(&request.url);
+                    request.complete().await
+                }
+                Err(e) => Err(e),
+            };
+            let _ = tx.send(AppEvent::CodexLoginDone(result));
+        });
+    }
+
+    fn push_local_note(&mut self, content: &str) {
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: content.to_string(),
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
+
+    fn handle_quit_confirm_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => self.should_quit = true,
+            KeyCode::Char('c') | KeyCode::Char('q') if ctrl => self.should_quit = true,
+            KeyCode::Char('n') | KeyCode::Esc => self.quit_confirm_open = false,
+            _ => {}
+        }
+    }
+
+    /// The repository map to send with the next call, or the workspace to build it for
+    /// (on the first call, and after enough edits that the cached one is out of date).
+    fn repo_map_for_call(&mut self) -> (Option<String>, Option<PathBuf>) {
+        let enabled = self
+            .config
+            .general
+            .as_ref()
+            .and_then(|g| g.repo_map)
+            .unwrap_or(true);
+        if !enabled {
+            return (None, None);
+        }
+        let edits: usize = self.file_edits.values().sum();
+        // Edit counts restart with a resumed or new session.
+        self.repo_map_edits = self.repo_map_edits.min(edits);
+        if self.repo_map.is_some() && edits >= self.repo_map_edits + REPO_MAP_REFRESH_EDITS {
+            self.repo_map = None;
+        }
+        match &self.repo_map {
+            Some(map) => (Some(map.clone()), None),
+            None => {
+                self.repo_map_edits = edits;
+                (None, Some(self.active_root.clone()))
+            }
+        }
+    }
+
+    fn intent_router_enabled(&self) -> bool {
+        self.config
+            .general
+            .as_ref()
+            .and_then(|g| g.intent_router)
+            .unwrap_or(true)
+    }
+
+    fn tools_enabled_for_next_call(&self) -> bool {
+        !self.plan_mode || self.tools_override_next
+    }
+
+    fn take_next_call_overrides(&mut self) -> (bool, Option<String>) {
+        let tools_enabled = self.tools_enabled_for_next_call();
+        let ephemeral_user = self.ephemeral_user_message.take();
+        // /go is a one-shot override.
+        self.tools_override_next = false;
+        (tools_enabled, ephemeral_user)
+    }
+
+    fn prepare_go(&mut self) {
+        self.tools_override_next = true;
+        self.ephemeral_user_message =
+            Some("Execute the plan above. Start running tools now. Verify when reasonable.".into());
+    }
+
+    /// Queue execution of `plan`: only step `only` when given, else every pending step.
+    /// Plans without numbered steps are sent whole.
+    fn prepare_go_with_plan(&mut self, plan: &PlanDraft, only: Option<usize>) {
+        self.tools_override_next = true;
+        let steps = plan.steps();
+        let mut msg = String::new();
+        if steps.is_empty() {
+            self.plan_steps_running.clear();
+            msg.push_str("Execute the plan below.\n\nPlan:\n");
+            msg.push_str(&plan.plan_markdown);
+        } else {
+            let mut selected = match only {
+                Some(n) => vec![n],
+                None => plan.pending_steps(),
+            };
+            if selected.is_empty() {
+                // Everything is done already: run the whole plan again.
+                selected = (1..=steps.len()).collect();
+            }
+            if let [n] = selected.as_slice() {
+                msg.push_str(&format!(
+                    "Execute only step {} of the plan. Do not start other steps.\n\nStep:\n",
+                    n
+                ));
+            } else {
+                msg.push_str("Execute the pending plan steps below.\n\nSteps:\n");
+            }
+            let listed: Vec<String> = selected
+                .iter()
+                .map(|n| format!("{}. {}", n, steps[n - 1]))
+                .collect();
+            msg.push_str(&listed.join("\n"));
+            self.plan_steps_running = selected;
+        }
+        msg.push_str("\n\nAnswers:\n");
+        if plan.questions.is_empty() {
+            msg.push_str("(none)\n");
+        } else {
+            for q in &plan.questions {
+                let ans = plan
+                    .answers
+                    .get(&q.id)
+                    .cloned()
+                    .unwrap_or_else(|| q.default.clone().unwrap_or_default());
+                msg.push_str(&format!("- {}: {}\n", q.prompt, ans));
+            }
+        }
+        msg.push_str("\nStart running tools now. Verify when reasonable.");
+        self.ephemeral_user_message = Some(msg);
+    }
+
+    fn open_plan_popup(&mut self) {
+        self.plan_popup_open = true;
+        self.plan_focus = PlanFocus::Questions;
+        self.plan_question_selected = 0;
+        self.plan_button_selected = 0;
+        self.plan_preview_scroll = 0;
+        self.load_plan_answer_input();
+    }
+
+    /// Close without executing, keeping any edited answers for `/plan show`.
+    fn close_plan_popup(&mut self) {
+        self.plan_popup_open = false;
+        if let (Some(store), Some(draft)) = (&self.session, &self.plan_draft) {
+            store.record_plan(draft, false);
+        }
+    }
+
+    /// Pin the plan being executed into the transcript so it stays visible after the popup.
+    fn pin_accepted_plan(&mut self, plan: &PlanDraft) {
+        let mut content = String::from("**Plan**\n\n");
+        content.push_str(plan.plan_markdown.trim());
+        if !plan.questions.is_empty() {
+            content.push_str("\n\n**Answers**\n");
+            for q in &plan.questions {
+                let ans = plan
+                    .answers
+                    .get(&q.id)
+                    .cloned()
+                    .unwrap_or_else(|| q.default.clone().unwrap_or_default());
+                content.push_str(&format!("- {}: {}\n", q.prompt, ans));
+            }
+        }
+        self.push_message(Message {
+            id: 0,
+            role: Role::Plan,
+            content,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        if let Some(last) = self.messages.last() {
+            self.session_record_message(last);
+        }
+        if let Some(store) = &self.session {
+            store.record_plan(plan, true);
+        }
+        self.scroll_messages_to_bottom();
+    }
+
+    /// Check off the plan steps the finished turn was executing.
+    fn complete_running_plan_steps(&mut self) {
+        let running = std::mem::take(&mut self.plan_steps_running);
+        let Some(draft) = &mut self.plan_draft else {
+            return;
+        };
+        if running.is_empty() {
+            return;
+        }
+        for n in running {
+            if !draft.completed_steps.contains(&n) {
+                draft.completed_steps.push(n);
+            }
+        }
+        draft.completed_steps.sort_unstable();
+        if let Some(store) = &self.session {
+            store.record_plan(draft, true);
+        }
+    }
+
+    fn load_plan_answer_input(&mut self) {
+        let Some(draft) = &self.plan_draft else {
+            self.plan_answer_input.clear();
+            self.plan_answer_cursor = 0;
+            return;
+        };
+        let Some(q) = draft.questions.get(self.plan_question_selected) else {
+            self.plan_answer_input.clear();
+            self.plan_answer_cursor = 0;
+            return;
+        };
+        let val = draft
+            .answers
+            .get(&q.id)
+            .cloned()
+            .or_else(|| q.default.clone())
+            .unwrap_or_default();
+        self.plan_answer_input = val;
+        self.plan_answer_cursor = self.plan_answer_input.len();
+    }
+
+    fn save_plan_answer_input(&mut self) {
+        let Some(draft) = &mut self.plan_draft else {
+            return;
+        };
+        let Some(q) = draft.questions.get(self.plan_question_selected) else {
+            return;
+        };
+        draft
+            .answers
+            .insert(q.id.clone(), self.plan_answer_input.clone());
+    }
+
+    fn handle_plan_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.close_plan_popup();
+            }
             KeyCode::Tab => {
                 self.plan_focus = match self.plan_focus {
                     PlanFocus::Questions => PlanFocus::Answer,
@@ -1256,14 +2967,15 @@ impl App {
                         }
                         if let Some(draft) = self.plan_draft.clone() {
                             self.plan_popup_open = false;
-                            self.prepare_go_with_plan(&draft);
+                            self.pin_accepted_plan(&draft);
+                            self.prepare_go_with_plan(&draft, None);
                             self.start_llm_call();
                         } else {
                             self.plan_popup_open = false;
                         }
                     } else {
                         // Cancel
-                        self.plan_popup_open = false;
+                        self.close_plan_popup();
                     }
                 }
             }
@@ -1332,6 +3044,28 @@ impl App {
     }
 
     fn handle_key(&mut self, key: KeyEvent) {
+        if self.quit_confirm_open {
+            self.handle_quit_confirm_key(key);
+            return;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('q'))
+        {
+            self.request_quit();
+            return;
+        }
+        if self.reauth_prompt_open {
+            self.handle_reauth_key(key);
+            return;
+        }
+        if self.restore_prompt.is_some() {
+            self.handle_restore_key(key);
+            return;
+        }
+        if self.turn_limit_prompt.is_some() {
+            self.handle_turn_limit_key(key);
+            return;
+        }
         if self.plan_popup_open {
             self.handle_plan_key(key);
             return;
@@ -1344,6 +3078,49 @@ impl App {
             self.handle_themes_key(key);
             return;
         }
+        if self.models_open {
+            self.handle_models_key(key);
+            return;
+        }
+        if self.memories_open {
+            self.handle_memories_key(key);
+            return;
+        }
+        if self.copy_open {
+            self.handle_copy_key(key);
+            return;
+        }
+        if self.stats_open {
+            self.handle_stats_key(key);
+            return;
+        }
+        if self.pending_approval.is_some() {
+            self.handle_approval_key(key);
+            return;
+        }
+        if self.trust_prompt_open {
+            self.handle_trust_key(key);
+            return;
+        }
+        if self.recent_file_popup.is_some() {
+            self.handle_recent_file_popup_key(key);
+            return;
+        }
+        if self.text_popup.is_some() {
+            self.handle_text_popup_key(key);
+            return;
+        }
+        if self.tool_pager.is_some() {
+            self.handle_tool_pager_key(key);
+            return;
+        }
+
+        if editor::compose_key(&self.config) == Some((key.modifiers, key.code))
+            && self.active_pane == Pane::Chat
+        {
+            self.compose_in_editor();
+            return;
+        }
 
         if key.modifiers.contains(KeyModifiers::CONTROL) {
             match key.code {
@@ -1374,6 +3151,34 @@ impl App {
                     }
                     return;
                 }
+                KeyCode::Char('y') => {
+                    self.open_copy_mode();
+                    return;
+                }
+                KeyCode::Char('k') => {
+                    self.copy_last_tool_command();
+                    return;
+                }
+                KeyCode::Char('o') => {
+                    self.open_tool_pager(None);
+                    return;
+                }
+                KeyCode::Char('g') => {
+                    self.goto_next_search_result();
+                    return;
+                }
+                KeyCode::Char('f') => {
+                    self.transcript_search_prompt = Some(String::new());
+                    return;
+                }
+                KeyCode::Char('n') if self.transcript_query.is_some() => {
+                    self.transcript_search_step(true);
+                    return;
+                }
+                KeyCode::Char('p') if self.transcript_query.is_some() => {
+                    self.transcript_search_step(false);
+                    return;
+                }
                 KeyCode::Char('i') => {
                     if self.active_pane == Pane::Chat {
                         if let Some(group_id) = self.last_tool_group_id {
@@ -1397,8 +3202,36 @@ impl App {
             }
         }
 
+        if self.transcript_search_prompt.is_some() {
+            self.handle_transcript_search_key(key);
+            return;
+        }
+        if self.chat_normal_mode && self.active_pane == Pane::Chat && self.handle_normal_key(key) {
+            return;
+        }
+
         match key.code {
-            KeyCode::Esc => self.should_quit = true,
+            // Esc closes the file viewer, clears the input, then switches the transcript to
+            // normal mode; it only quits (from normal mode) when `general.esc_quits` is set.
+            KeyCode::Esc if self.file_view.is_some() => self.file_view = None,
+            KeyCode::Esc if !self.input.is_empty() => {
+                self.input.clear();
+                self.cursor_pos = 0;
+            }
+            KeyCode::Esc if self.offline.is_some() => self.stop_reconnecting(),
+            KeyCode::Esc if self.active_pane == Pane::Chat => self.chat_normal_mode = true,
+            KeyCode::Esc if self.esc_quits() => self.request_quit(),
+            KeyCode::Esc => {}
+            // With the Context pane focused (and nothing typed), keys act on recent files.
+            KeyCode::Up if self.recent_files_focused() => self.select_recent_file(-1),
+            KeyCode::Down if self.recent_files_focused() => self.select_recent_file(1),
+            KeyCode::Enter if self.recent_files_focused() && self.input.is_empty() => {
+                self.open_recent_file()
+            }
+            KeyCode::Char('p') if self.recent_files_focused() && self.input.is_empty() => {
+                self.toggle_recent_file_pin()
+            }
+            KeyCode::Tab if !self.mention_suggestions().is_empty() => self.complete_mention(),
             KeyCode::Tab => {
                 self.plan_mode = !self.plan_mode;
             }
@@ -1408,6 +3241,9 @@ impl App {
                     Pane::Context => Pane::Chat,
                 };
             }
+            KeyCode::Enter if self.offline.is_some() && self.input.trim().is_empty() => {
+                self.reconnect_now()
+            }
             KeyCode::Enter => self.submit_message(),
             KeyCode::Backspace => {
                 if self.cursor_pos > 0 {
@@ -1442,13 +3278,20 @@ impl App {
                         return;
                     }
                 }
+                if !key.modifiers.contains(KeyModifiers::SHIFT)
+                    && self.active_pane == Pane::Chat
+                    && !self.mention_suggestions().is_empty()
+                {
+                    self.command_suggest_selected = self.command_suggest_selected.saturating_sub(1);
+                    return;
+                }
                 if key.modifiers.contains(KeyModifiers::SHIFT) {
                     match self.active_pane {
                         Pane::Chat => {
                             self.chat_follow = false;
                             self.chat_scroll_rows = self.chat_scroll_rows.saturating_sub(1);
                         }
-                        Pane::Context => {}
+                        Pane::Context => self.scroll_file_view(-1),
                     }
                 }
             }
@@ -1465,12 +3308,21 @@ impl App {
                         return;
                     }
                 }
+                if !key.modifiers.contains(KeyModifiers::SHIFT) && self.active_pane == Pane::Chat {
+                    let mentions = self.mention_suggestions();
+                    if !mentions.is_empty() {
+                        let max = mentions.len().saturating_sub(1);
+                        self.command_suggest_selected =
+                            (self.command_suggest_selected + 1).min(max);
+                        return;
+                    }
+                }
                 if key.modifiers.contains(KeyModifiers::SHIFT) {
                     match self.active_pane {
                         Pane::Chat => {
                             self.chat_scroll_rows = self.chat_scroll_rows.saturating_add(1);
                         }
-                        Pane::Context => {}
+                        Pane::Context => self.scroll_file_view(1),
                     }
                 }
             }
@@ -1479,18 +3331,20 @@ impl App {
                     self.chat_follow = false;
                     self.chat_scroll_rows = self.chat_scroll_rows.saturating_sub(20);
                 }
-                Pane::Context => {}
+                Pane::Context => self.scroll_file_view(-20),
             },
             KeyCode::PageDown => match self.active_pane {
                 Pane::Chat => {
                     self.chat_scroll_rows = self.chat_scroll_rows.saturating_add(20);
                 }
-                Pane::Context => {}
+                Pane::Context => self.scroll_file_view(20),
             },
             KeyCode::Char(c) => {
                 self.input.insert(self.cursor_pos, c);
                 self.cursor_pos += 1;
-                if self.input.trim_start().starts_with('/') {
+                if self.input.trim_start().starts_with('/')
+                    || bookmarks::mention_at(&self.input, self.cursor_pos).is_some()
+                {
                     self.command_suggest_selected = 0;
                 }
             }
@@ -1498,46 +3352,450 @@ impl App {
         }
     }
 
-    fn handle_settings_key(&mut self, key: KeyEvent) {
+    /// Normal-mode keys for the transcript. Returns false for keys that keep their usual
+    /// meaning (Tab, Shift+Tab, PgUp/PgDn).
+    fn handle_normal_key(&mut self, key: KeyEvent) -> bool {
+        let pending_g = std::mem::take(&mut self.pending_g);
         match key.code {
-            KeyCode::Esc => {
-                // Revert any live preview changes made while settings are open.
-                self.config = self.settings_original.clone();
-                self.sandbox_policy = Arc::new(SandboxPolicy::from_config(
-                    self.config.clone(),
-                    self.workspace_root.clone(),
-                    crate::tools::TOOL_NAMES,
-                ));
-                self.settings_open = false;
-                self.settings_input.clear();
-                self.settings_cursor = 0;
-            }
-            KeyCode::Up => match self.settings_focus {
-                SettingsFocus::Categories => {
-                    if self.settings_category_selected > 0 {
-                        self.settings_category_selected -= 1;
-                        self.settings_selected = 0;
-                        self.load_settings_input();
-                    }
+            KeyCode::Char('i') | KeyCode::Char('a') | KeyCode::Enter => {
+                self.chat_normal_mode = false;
+            }
+            KeyCode::Esc if self.transcript_query.is_some() => {
+                self.transcript_query = None;
+                self.transcript_match_row = None;
+            }
+            KeyCode::Esc if self.esc_quits() => self.request_quit(),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.chat_scroll_rows = self.chat_scroll_rows.saturating_add(1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.chat_follow = false;
+                self.chat_scroll_rows = self.chat_scroll_rows.saturating_sub(1);
+            }
+            KeyCode::Char('g') if pending_g => {
+                self.chat_follow = false;
+                self.chat_scroll_rows = 0;
+            }
+            KeyCode::Char('g') => self.pending_g = true,
+            KeyCode::Char('G') => self.scroll_messages_to_bottom(),
+            KeyCode::Char('/') => self.transcript_search_prompt = Some(String::new()),
+            KeyCode::Char('n') => self.transcript_search_step(true),
+            KeyCode::Char('N') => self.transcript_search_step(false),
+            KeyCode::Char('y') => self.yank_selected_message(),
+            KeyCode::Char('f') => self.select_file_ref(true),
+            KeyCode::Char('F') => self.select_file_ref(false),
+            KeyCode::Char('o') => {
+                if let Some((_, file_ref)) = self.selected_file_ref.clone() {
+                    self.open_file_ref(&file_ref.path, file_ref.line);
                 }
-                SettingsFocus::Items => {
-                    if self.settings_selected > 0 {
-                        self.settings_selected -= 1;
-                        self.load_settings_input();
-                    }
+            }
+            KeyCode::Char('z') => {
+                if let Some(id) = self.selected_message_id() {
+                    self.toggle_message_fold(id);
                 }
+            }
+            KeyCode::Char('p') => match self.selected_tool_call_id() {
+                Some(call_id) => self.open_tool_pager(Some(call_id)),
+                None => self.push_local_note(
+                    "[No tool call on screen: scroll to one, or press Ctrl+O for the latest]",
+                ),
             },
-            KeyCode::Down => match self.settings_focus {
-                SettingsFocus::Categories => {
-                    if self.settings_category_selected + 1 < self.settings_categories().len() {
-                        self.settings_category_selected += 1;
-                        self.settings_selected = 0;
-                        self.load_settings_input();
-                    }
-                }
-                SettingsFocus::Items => {
-                    if self.settings_selected + 1 < self.current_settings_items().len() {
-                        self.settings_selected += 1;
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::PageUp | KeyCode::PageDown => {
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_transcript_search_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = self.transcript_search_prompt.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.transcript_search_prompt = None,
+            KeyCode::Backspace if prompt.is_empty() => self.transcript_search_prompt = None,
+            KeyCode::Backspace => {
+                prompt.pop();
+            }
+            KeyCode::Char(c) => prompt.push(c),
+            KeyCode::Enter => {
+                let query = self.transcript_search_prompt.take().unwrap_or_default();
+                if query.is_empty() {
+                    return;
+                }
+                self.transcript_query = Some(query);
+                self.transcript_match_row = None;
+                self.transcript_search_step(true);
+            }
+            _ => {}
+        }
+    }
+
+    /// Jump to the next (or previous) transcript match, wrapping around. The first jump
+    /// after a new search starts from the top of the viewport.
+    fn transcript_search_step(&mut self, forward: bool) {
+        let Some(query) = self.transcript_query.as_deref() else {
+            return;
+        };
+        let rows = self.render_store.find(query);
+        let top = self.chat_scroll_rows;
+        let next = match (forward, self.transcript_match_row) {
+            (true, Some(cur)) => rows.iter().find(|&&r| r > cur).or(rows.first()),
+            (true, None) => rows.iter().find(|&&r| r >= top).or(rows.first()),
+            (false, Some(cur)) => rows.iter().rev().find(|&&r| r < cur).or(rows.last()),
+            (false, None) => rows.iter().rev().find(|&&r| r < top).or(rows.last()),
+        }
+        .copied();
+        self.transcript_match_row = next;
+        if let Some(row) = next {
+            self.scroll_chat_to_row(row);
+        }
+    }
+
+    /// `/find`: highlight `query` and jump to its most recent occurrence.
+    fn find_in_transcript(&mut self, query: String) {
+        let rows = self.render_store.find(&query);
+        self.transcript_query = Some(query);
+        self.transcript_match_row = rows.last().copied();
+        if let Some(row) = self.transcript_match_row {
+            self.scroll_chat_to_row(row);
+        }
+    }
+
+    /// Scroll so `row` sits in the middle of the chat pane.
+    fn scroll_chat_to_row(&mut self, row: usize) {
+        let visible = self.chat_area.height.saturating_sub(2) as usize;
+        self.chat_follow = false;
+        self.chat_scroll_rows = row.saturating_sub(visible / 2);
+    }
+
+    /// The message `y` copies and `z` folds: the one holding the current match, else the
+    /// first one starting at or below the top of the chat pane.
+    fn selected_message_id(&self) -> Option<u64> {
+        let message_at = |row: usize| match self.render_store.block_at(row) {
+            Some((RenderedBlockId::Message(id), _)) => Some(*id),
+            _ => None,
+        };
+        if let Some(id) = self.transcript_match_row.and_then(message_at) {
+            return Some(id);
+        }
+        (self.chat_scroll_rows..self.render_store.total_height()).find_map(message_at)
+    }
+
+    /// The tool call whose trace line is the first one on screen, counting from the current
+    /// search match if there is one.
+    fn selected_tool_call_id(&self) -> Option<String> {
+        let top = self.transcript_match_row.unwrap_or(self.chat_scroll_rows);
+        let visible = self.chat_area.height.saturating_sub(2) as usize;
+        self.render_store
+            .visible_lines(top, visible)
+            .iter()
+            .find_map(|line| {
+                let text = render_store::line_text(line);
+                let (_, rest) = text.split_once(" [id=")?;
+                let (id, _) = rest.split_once("] (cwd=")?;
+                self.tool_outputs
+                    .iter()
+                    .any(|t| t.call_id == id)
+                    .then(|| id.to_string())
+            })
+    }
+
+    /// Newest model answer; it stays unfolded until the user folds it.
+    pub fn latest_agent_message_id(&self) -> Option<u64> {
+        self.display_messages()
+            .rev()
+            .find(|m| m.role == Role::Agent && !m.local)
+            .map(|m| m.id)
+    }
+
+    /// Whether message `id` renders folded (only long agent messages actually shrink).
+    pub fn message_collapsed(&self, id: u64, latest_agent: Option<u64>) -> bool {
+        match self.message_expanded.get(&id) {
+            Some(expanded) => !expanded,
+            None => Some(id) != latest_agent,
+        }
+    }
+
+    fn toggle_message_fold(&mut self, id: u64) {
+        let collapsed = self.message_collapsed(id, self.latest_agent_message_id());
+        self.message_expanded.insert(id, collapsed);
+        self.render_store.mark_dirty(RenderedBlockId::Message(id));
+    }
+
+    /// `path:line` references on the rows currently in the chat pane, top to bottom.
+    fn visible_file_refs(&self) -> Vec<(usize, FileRef)> {
+        let visible = self.chat_area.height.saturating_sub(2) as usize;
+        self.render_store
+            .visible_lines(self.chat_scroll_rows, visible)
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                render_store::file_refs(&render_store::line_text(line))
+                    .into_iter()
+                    .map(move |r| (self.chat_scroll_rows + i, r))
+            })
+            .collect()
+    }
+
+    /// Move the normal-mode reference selection to the next (or previous) visible one.
+    fn select_file_ref(&mut self, forward: bool) {
+        let refs = self.visible_file_refs();
+        if refs.is_empty() {
+            self.selected_file_ref = None;
+            return;
+        }
+        let current = self
+            .selected_file_ref
+            .as_ref()
+            .and_then(|sel| refs.iter().position(|r| r == sel));
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % refs.len(),
+            (Some(i), false) => (i + refs.len() - 1) % refs.len(),
+            (None, true) => 0,
+            (None, false) => refs.len() - 1,
+        };
+        self.selected_file_ref = Some(refs[next].clone());
+    }
+
+    /// Open a clicked/selected reference: in `$EDITOR` with `editor.open_references`,
+    /// otherwise in the file viewer.
+    fn open_file_ref(&mut self, path: &str, line: usize) {
+        let full = self.active_root.join(path);
+        let in_editor = self
+            .config
+            .editor
+            .as_ref()
+            .and_then(|e| e.open_references)
+            .unwrap_or(false);
+        if in_editor
+            && full.is_file()
+            && editor::editor_command(&self.config, self.workspace_restricted()).is_some()
+        {
+            self.pending_editor = Some(EditorRequest::Open { path: full, line });
+            return;
+        }
+        self.open_file_view(path, line);
+    }
+
+    /// Run the editor the UI asked for; called by the main loop, which owns the terminal.
+    pub fn run_pending_editor(&mut self) {
+        let Some(request) = self.pending_editor.take() else {
+            return;
+        };
+        let command = editor::editor_command(&self.config, self.workspace_restricted());
+        let result = match (&request, command) {
+            (_, None) => Err("no editor configured (set $EDITOR or editor.command)".to_string()),
+            (EditorRequest::Open { path, line }, Some(command)) => {
+                editor::run(&editor::open_args(&command, path, *line))
+            }
+            (EditorRequest::Compose { path }, Some(command)) => {
+                editor::run(&editor::compose_args(&command, path)).and_then(|()| {
+                    std::fs::read_to_string(path)
+                        .map(|draft| self.set_input(draft.trim_end_matches('\n')))
+                        .map_err(|e| format!("can't read the draft back: {}", e))
+                })
+            }
+        };
+        if let EditorRequest::Compose { path } = &request {
+            let _ = std::fs::remove_file(path);
+        }
+        if let Err(e) = result {
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: format!("Editor: {}", e),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
+            });
+            self.scroll_messages_to_bottom();
+        }
+    }
+
+    /// Save the input to a draft file and ask the main loop to open it in the editor.
+    fn compose_in_editor(&mut self) {
+        let path = editor::draft_path();
+        if let Err(e) = std::fs::write(&path, &self.input) {
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: format!("Editor: can't save the draft: {}", e),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
+            });
+            self.scroll_messages_to_bottom();
+            return;
+        }
+        self.pending_editor = Some(EditorRequest::Compose { path });
+    }
+
+    /// Insert pasted text verbatim at the cursor (newlines included), instead of
+    /// replaying it as keys where the first newline would send the message. Popups
+    /// other than the transcript search ignore pastes.
+    fn handle_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if let Some(prompt) = self.transcript_search_prompt.as_mut() {
+            prompt.push_str(text.lines().next().unwrap_or(""));
+            return;
+        }
+        let popup_open = self.plan_popup_open
+            || self.settings_open
+            || self.themes_open
+            || self.models_open
+            || self.memories_open
+            || self.copy_open
+            || self.stats_open
+            || self.quit_confirm_open
+            || self.pending_approval.is_some()
+            || self.trust_prompt_open
+            || self.reauth_prompt_open
+            || self.restore_prompt.is_some()
+            || self.turn_limit_prompt.is_some()
+            || self.recent_file_popup.is_some()
+            || self.text_popup.is_some()
+            || self.tool_pager.is_some();
+        if popup_open {
+            return;
+        }
+        self.chat_normal_mode = false;
+        self.active_pane = Pane::Chat;
+        let at = self.cursor_pos.min(self.input.len());
+        self.input.insert_str(at, &text);
+        self.cursor_pos = at + text.len();
+        self.command_suggest_selected = 0;
+    }
+
+    fn set_input(&mut self, text: &str) {
+        self.input = text.to_string();
+        self.cursor_pos = self.input.len();
+        self.command_suggest_selected = 0;
+    }
+
+    /// A click on a `path:line` reference opens it. `col` counts display cells from the
+    /// chat pane's inner left edge.
+    fn click_file_ref(&mut self, row: usize, col: usize) -> bool {
+        let Some(line) = self.render_store.visible_lines(row, 1).into_iter().next() else {
+            return false;
+        };
+        let text = render_store::line_text(&line);
+        let mut width = 0;
+        let Some(byte) = text.char_indices().find_map(|(i, c)| {
+            width += c.width().unwrap_or(0);
+            (width > col).then_some(i)
+        }) else {
+            return false;
+        };
+        let Some(file_ref) = render_store::file_refs(&text)
+            .into_iter()
+            .find(|r| r.range.contains(&byte))
+        else {
+            return false;
+        };
+        self.open_file_ref(&file_ref.path, file_ref.line);
+        true
+    }
+
+    /// A click on a message's first line (or the fold marker under a collapsed one)
+    /// toggles the fold.
+    fn click_chat_row(&mut self, row: usize) {
+        let Some((RenderedBlockId::Message(id), rows)) = self.render_store.block_at(row) else {
+            return;
+        };
+        let id = *id;
+        let is_agent = self
+            .messages
+            .iter()
+            .any(|m| m.id == id && m.role == Role::Agent);
+        let on_marker =
+            row + 1 == rows.end && self.message_collapsed(id, self.latest_agent_message_id());
+        if is_agent && (row == rows.start || on_marker) {
+            self.toggle_message_fold(id);
+        }
+    }
+
+    fn yank_selected_message(&mut self) {
+        let Some(content) = self
+            .selected_message_id()
+            .and_then(|id| self.messages.iter().find(|m| m.id == id))
+            .map(|m| m.content.clone())
+        else {
+            return;
+        };
+        let notice = match clipboard::copy(&content) {
+            Ok(()) => format!(
+                "Copied {} lines to the clipboard.",
+                content.lines().count().max(1)
+            ),
+            Err(e) => format!("Copy failed: {}", e),
+        };
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: notice,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+    }
+
+    fn handle_settings_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                // Revert any live preview changes made while settings are open.
+                self.config = self.settings_original.clone();
+                self.apply_config();
+                self.settings_open = false;
+                self.settings_input.clear();
+                self.settings_cursor = 0;
+            }
+            KeyCode::Up | KeyCode::Down
+                if key.modifiers.contains(KeyModifiers::ALT)
+                    && matches!(self.settings_focus, SettingsFocus::Items)
+                    && matches!(
+                        self.current_settings_item(),
+                        SettingsItem::VerifyCommands | SettingsItem::VerifyPipeline
+                    ) =>
+            {
+                let earlier = key.code == KeyCode::Up;
+                if let Some((input, cursor)) =
+                    move_command_entry(&self.settings_input, self.settings_cursor, earlier)
+                {
+                    self.settings_input = input;
+                    self.settings_cursor = cursor;
+                }
+            }
+            KeyCode::Up => match self.settings_focus {
+                SettingsFocus::Categories => {
+                    if self.settings_category_selected > 0 {
+                        self.settings_category_selected -= 1;
+                        self.settings_selected = 0;
+                        self.load_settings_input();
+                    }
+                }
+                SettingsFocus::Items => {
+                    if self.settings_selected > 0 {
+                        self.settings_selected -= 1;
+                        self.load_settings_input();
+                    }
+                }
+            },
+            KeyCode::Down => match self.settings_focus {
+                SettingsFocus::Categories => {
+                    if self.settings_category_selected + 1 < self.settings_categories().len() {
+                        self.settings_category_selected += 1;
+                        self.settings_selected = 0;
+                        self.load_settings_input();
+                    }
+                }
+                SettingsFocus::Items => {
+                    if self.settings_selected + 1 < self.current_settings_items().len() {
+                        self.settings_selected += 1;
                         self.load_settings_input();
                     }
                 }
@@ -1571,6 +3829,8 @@ impl App {
                     && self.settings_provider_name() == "codex"
                 {
                     self.cycle_codex_model(false);
+                } else if let SettingsItem::Tool(name) = self.current_settings_item() {
+                    self.toggle_settings_tool(name);
                 } else {
                     self.settings_cursor = self.settings_cursor.saturating_sub(1);
                 }
@@ -1589,6 +3849,8 @@ impl App {
                     && self.settings_provider_name() == "codex"
                 {
                     self.cycle_codex_model(true);
+                } else if let SettingsItem::Tool(name) = self.current_settings_item() {
+                    self.toggle_settings_tool(name);
                 } else if self.settings_cursor < self.settings_input.len() {
                     self.settings_cursor += 1;
                 }
@@ -1606,13 +3868,17 @@ impl App {
                     return;
                 }
                 self.apply_settings_input();
-                let _ = self.settings_draft.save();
+                if let Err(e) = validate_verify_settings(&self.settings_draft, &self.workspace_root)
+                {
+                    self.settings_error = Some(e);
+                    return;
+                }
+                let _ = self
+                    .settings_draft
+                    .save_layered(&self.settings_original, &self.workspace_root);
+                self.project_layer = ProjectLayer::load(&self.workspace_root);
                 self.config = self.settings_draft.clone();
-                self.sandbox_policy = Arc::new(SandboxPolicy::from_config(
-                    self.config.clone(),
-                    self.workspace_root.clone(),
-                    crate::tools::TOOL_NAMES,
-                ));
+                self.apply_config();
                 if self
                     .config
                     .general
@@ -1620,7 +3886,7 @@ impl App {
                     .and_then(|g| g.auto_index)
                     .unwrap_or(true)
                     && matches!(self.indexing_status, IndexingStatus::NotStarted)
-                    && !index_file_exists(&self.workspace_root)
+                    && !index_file_exists(&self.active_root)
                 {
                     self.start_background_indexing();
                 }
@@ -1636,10 +3902,11 @@ impl App {
             SettingsCategory::Appearance,
             SettingsCategory::Memory,
             SettingsCategory::Sandbox,
+            SettingsCategory::Verify,
         ]
     }
 
-    fn current_settings_items(&self) -> &'static [SettingsItem] {
+    fn current_settings_items(&self) -> Vec<SettingsItem> {
         let cats = self.settings_categories();
         let cat = cats
             .get(self.settings_category_selected)
@@ -1659,6 +3926,40 @@ impl App {
         let current = self.read_settings_value(self.current_settings_item());
         self.settings_input = current;
         self.settings_cursor = self.settings_input.len();
+        self.settings_error = None;
+    }
+
+    fn settings_tool_enabled(&self, name: &str) -> bool {
+        self.settings_draft
+            .sandbox
+            .as_ref()
+            .and_then(|s| s.allow_tools.as_ref())
+            .is_none_or(|tools| tools.iter().any(|t| t == name))
+    }
+
+    /// Add or remove a tool from the draft's `allow_tools`, starting from every tool when
+    /// the list isn't set yet.
+    fn set_settings_tool_enabled(&mut self, name: &str, enabled: bool) {
+        let mut sandbox = self.settings_draft.sandbox.clone().unwrap_or_default();
+        let mut tools = sandbox.allow_tools.take().unwrap_or_else(|| {
+            crate::tool_registry::names()
+                .into_iter()
+                .map(|t| t.to_string())
+                .collect()
+        });
+        tools.retain(|t| t != name);
+        if enabled {
+            tools.push(name.to_string());
+        }
+        sandbox.allow_tools = Some(tools);
+        self.settings_draft.sandbox = Some(sandbox);
+    }
+
+    fn toggle_settings_tool(&mut self, name: &'static str) {
+        let enabled = !self.settings_tool_enabled(name);
+        self.set_settings_tool_enabled(name, enabled);
+        self.settings_input = if enabled { "on" } else { "off" }.to_string();
+        self.settings_cursor = self.settings_input.len();
     }
 
     fn read_settings_value(&self, item: SettingsItem) -> String {
@@ -1703,6 +4004,12 @@ impl App {
                 .and_then(|g| g.resume_last)
                 .map(|v| v.to_string())
                 .unwrap_or_else(|| "true".to_string()),
+            SettingsItem::DefaultMode => self
+                .settings_draft
+                .general
+                .as_ref()
+                .and_then(|g| g.default_mode.clone())
+                .unwrap_or_else(|| "auto".to_string()),
             SettingsItem::MemoryEnabled => self
                 .settings_draft
                 .memory
@@ -1786,6 +4093,28 @@ impl App {
                 .and_then(|s| s.allow_tools.clone())
                 .map(|list| list.join(", "))
                 .unwrap_or_default(),
+            SettingsItem::VerifyCommands => self
+                .settings_draft
+                .verify
+                .as_ref()
+                .and_then(|v| v.commands.clone())
+                .map(|list| list.join("; "))
+                .unwrap_or_default(),
+            SettingsItem::VerifyPipeline => self
+                .settings_draft
+                .verify
+                .as_ref()
+                .and_then(|v| v.pipeline.clone())
+                .map(|list| list.join("; "))
+                .unwrap_or_default(),
+            SettingsItem::Tool(name) => {
+                let value = if self.settings_tool_enabled(name) {
+                    "on"
+                } else {
+                    "off"
+                };
+                value.to_string()
+            }
         }
     }
 
@@ -1880,6 +4209,15 @@ impl App {
                     self.settings_draft.general = Some(general);
                 }
             }
+            SettingsItem::DefaultMode => {
+                let mut general = self.settings_draft.general.clone().unwrap_or_default();
+                general.default_mode =
+                    Some(match self.settings_input.trim().to_lowercase().as_str() {
+                        "plan" => "plan".to_string(),
+                        _ => "auto".to_string(),
+                    });
+                self.settings_draft.general = Some(general);
+            }
             SettingsItem::MemoryEnabled => {
                 let mut mem = self.settings_draft.memory.clone().unwrap_or_default();
                 if let Ok(v) = parse_bool(&self.settings_input) {
@@ -1962,6 +4300,23 @@ impl App {
                 sandbox.allow_tools = Some(split_list(&self.settings_input));
                 self.settings_draft.sandbox = Some(sandbox);
             }
+            SettingsItem::VerifyCommands => {
+                let mut verify = self.settings_draft.verify.clone().unwrap_or_default();
+                let list = split_commands(&self.settings_input);
+                verify.commands = (!list.is_empty()).then_some(list);
+                self.settings_draft.verify = Some(verify);
+            }
+            SettingsItem::VerifyPipeline => {
+                let mut verify = self.settings_draft.verify.clone().unwrap_or_default();
+                let list = split_commands(&self.settings_input);
+                verify.pipeline = (!list.is_empty()).then_some(list);
+                self.settings_draft.verify = Some(verify);
+            }
+            SettingsItem::Tool(name) => {
+                if let Ok(enabled) = parse_bool(&self.settings_input) {
+                    self.set_settings_tool_enabled(name, enabled);
+                }
+            }
         }
     }
 
@@ -2034,6 +4389,13 @@ impl App {
                 self.codex_account_id = None;
                 (LlmProvider::OpenAI, k)
             }
+            "gemini" | "google" => {
+                reload_env();
+                let k = crate::llm::gemini_api_key()
+                    .ok_or_else(|| "GEMINI_API_KEY is not set".to_string())?;
+                self.codex_account_id = None;
+                (LlmProvider::Gemini, k)
+            }
             "codex" | "codex_oauth" => {
                 let token_res = match tokio::runtime::Handle::try_current() {
                     Ok(h) => tokio::task::block_in_place(|| {
@@ -2054,7 +4416,9 @@ impl App {
                 (LlmProvider::Codex, auth.access_token)
             }
             _ => {
-                return Err("Unknown provider. Use: openrouter | openai | codex".to_string());
+                return Err(
+                    "Unknown provider. Use: openrouter | openai | gemini | codex".to_string(),
+                );
             }
         };
 
@@ -2072,6 +4436,7 @@ impl App {
         self.settings_draft = self.config.clone();
         self.settings_input.clear();
         self.settings_cursor = 0;
+        self.settings_error = None;
         self.load_settings_input();
     }
 
@@ -2117,6 +4482,7 @@ impl App {
             KeyCode::Enter => {
                 let items = self.filtered_themes();
                 if let Some(name) = items.get(self.themes_selected).cloned() {
+                    let previous = self.config.clone();
                     let mut theme_cfg = self.config.theme.clone().unwrap_or_default();
                     if name == "system" {
                         theme_cfg.preset = Some("system".to_string());
@@ -2125,7 +4491,7 @@ impl App {
                     }
                     self.config.theme = Some(theme_cfg.clone());
                     // Persist immediately.
-                    let _ = self.config.save();
+                    let _ = self.config.save_layered(&previous, &self.workspace_root);
                     // Keep settings draft in sync if settings is opened later.
                     self.settings_draft.theme = Some(theme_cfg);
                 }
@@ -2135,29 +4501,574 @@ impl App {
         }
     }
 
-    pub fn open_themes(&mut self) {
-        self.themes_open = true;
-        self.themes_query.clear();
-        self.themes_cursor = 0;
-        self.themes_selected = 0;
-    }
+    fn handle_models_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.models_open = false;
+                self.models_query.clear();
+                self.models_selected = 0;
+            }
+            KeyCode::Up => {
+                self.models_selected = self.models_selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.models_selected + 1 < self.filtered_models().len() {
+                    self.models_selected += 1;
+                }
+            }
+            KeyCode::PageUp => {
+                self.models_selected = self.models_selected.saturating_sub(10);
+            }
+            KeyCode::PageDown => {
+                let len = self.filtered_models().len();
+                self.models_selected = (self.models_selected + 10).min(len.saturating_sub(1));
+            }
+            KeyCode::Backspace => {
+                if self.models_query.pop().is_some() {
+                    self.models_selected = 0;
+                }
+            }
+            KeyCode::Char(c) => {
+                self.models_query.push(c);
+                self.models_selected = 0;
+            }
+            KeyCode::Enter => {
+                let Some(id) = self
+                    .filtered_models()
+                    .get(self.models_selected)
+                    .map(|m| m.id.clone())
+                else {
+                    return;
+                };
+                self.models_open = false;
+                self.models_query.clear();
+
+                let previous = self.config.clone();
+                let mut general = self.config.general.clone().unwrap_or_default();
+                general.model = Some(id.clone());
+                self.config.general = Some(general.clone());
+                let _ = self.config.save_layered(&previous, &self.workspace_root);
+                // Keep settings draft in sync if settings is opened later.
+                self.settings_draft.general = Some(general);
+                self.model = id;
 
-    pub fn filtered_themes(&self) -> Vec<String> {
-        let all = crate::theme::list_ui_themes(Some(&self.workspace_root));
-        let q = self.themes_query.trim().to_lowercase();
-        if q.is_empty() {
-            return all;
+                self.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: format!("Model: {}", self.model),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+                self.scroll_messages_to_bottom();
+            }
+            _ => {}
         }
-        all.into_iter()
-            .filter(|t| t.to_lowercase().contains(&q))
-            .collect()
     }
 
-    fn cycle_theme_preset(&mut self, next: bool) {
-        let themes = crate::theme::list_ui_themes(Some(&self.workspace_root));
-        let cur = crate::theme::ui_theme_name(&self.settings_draft);
-        let idx = themes.iter().position(|t| t == &cur).unwrap_or(0);
-        let new_idx = if themes.is_empty() {
+    /// Open the model picker and (re)fetch the active provider's model list.
+    pub fn open_models(&mut self) {
+        self.models_open = true;
+        self.models_query.clear();
+        self.models_selected = self.current_model_index();
+        self.models_status = Some("Loading models…".to_string());
+
+        let tx = self.event_tx.clone();
+        let provider = self.llm_provider;
+        let api_key = self.api_key.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(AppEvent::ModelsLoaded(list_models(provider, api_key).await));
+        });
+    }
+
+    pub fn filtered_models(&self) -> Vec<&ModelInfo> {
+        let q = self.models_query.trim().to_lowercase();
+        self.models
+            .iter()
+            .filter(|m| q.is_empty() || m.id.to_lowercase().contains(&q))
+            .collect()
+    }
+
+    fn current_model_index(&self) -> usize {
+        self.models
+            .iter()
+            .position(|m| m.id == self.model)
+            .unwrap_or(0)
+    }
+
+    /// Open the memory browser and load this project's memories.
+    pub fn open_memories(&mut self) {
+        self.memories_open = true;
+        self.memories_query.clear();
+        self.memories_selected = 0;
+        self.memory_edit = None;
+        self.memory_delete_pending = None;
+        self.memories_status = Some("Loading memories…".to_string());
+
+        let tx = self.event_tx.clone();
+        let memory = self.memory.clone();
+        tokio::spawn(async move { send_memories(&memory, &tx, None).await });
+    }
+
+    /// Memories matching the filter, in display (grouped) order. Every word of the
+    /// query must match the type, scope, content or tags, either as a substring or as
+    /// the letters of one word in order ("cfg" finds "config").
+    pub fn filtered_memories(&self) -> Vec<&Memory> {
+        let terms: Vec<String> = self
+            .memories_query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect();
+        self.memories
+            .iter()
+            .filter(|m| {
+                if terms.is_empty() {
+                    return true;
+                }
+                let haystack = format!(
+                    "{} {} {} {}",
+                    m.memory_type.as_str(),
+                    m.scope.as_str(),
+                    m.content,
+                    m.tags.join(" ")
+                )
+                .to_lowercase();
+                terms.iter().all(|t| fuzzy_term_matches(t, &haystack))
+            })
+            .collect()
+    }
+
+    fn handle_memories_key(&mut self, key: KeyEvent) {
+        if self.memory_edit.is_some() {
+            self.handle_memory_edit_key(key);
+            return;
+        }
+        let delete_pending = self.memory_delete_pending.take();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Delete || (ctrl && key.code == KeyCode::Char('d')) {
+            self.forget_selected_memory(delete_pending);
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                if delete_pending.is_some() {
+                    self.memories_status = None;
+                } else {
+                    self.memories_open = false;
+                    self.memories_query.clear();
+                }
+            }
+            KeyCode::Up => {
+                self.memories_selected = self.memories_selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.memories_selected + 1 < self.filtered_memories().len() {
+                    self.memories_selected += 1;
+                }
+            }
+            KeyCode::PageUp => {
+                self.memories_selected = self.memories_selected.saturating_sub(10);
+            }
+            KeyCode::PageDown => {
+                let len = self.filtered_memories().len();
+                self.memories_selected = (self.memories_selected + 10).min(len.saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let Some(m) = self
+                    .filtered_memories()
+                    .get(self.memories_selected)
+                    .copied()
+                else {
+                    return;
+                };
+                self.memory_edit = Some(MemoryEdit {
+                    id: m.id.clone(),
+                    field: MemoryField::Content,
+                    content: m.content.clone(),
+                    tags: m.tags.join(", "),
+                    importance: format!("{:.2}", m.importance),
+                });
+                self.memories_status = None;
+            }
+            KeyCode::Backspace => {
+                if self.memories_query.pop().is_some() {
+                    self.memories_selected = 0;
+                }
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.memories_query.push(c);
+                self.memories_selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Delete asks first; a second Delete on the same memory forgets it.
+    fn forget_selected_memory(&mut self, pending: Option<String>) {
+        let Some(id) = self
+            .filtered_memories()
+            .get(self.memories_selected)
+            .map(|m| m.id.clone())
+        else {
+            return;
+        };
+        if pending.as_deref() != Some(id.as_str()) {
+            self.memories_status =
+                Some("Press Delete again to forget this memory (Esc keeps it).".into());
+            self.memory_delete_pending = Some(id);
+            return;
+        }
+        self.memories.retain(|m| m.id != id);
+        let len = self.filtered_memories().len();
+        self.memories_selected = self.memories_selected.min(len.saturating_sub(1));
+        self.memories_status = Some("Forgetting…".into());
+
+        let tx = self.event_tx.clone();
+        let memory = self.memory.clone();
+        tokio::spawn(async move {
+            let status = match memory.forget(&id).await {
+                Ok(_) => "Memory forgotten.".to_string(),
+                Err(e) => format!("Delete failed: {}", e),
+            };
+            send_memories(&memory, &tx, Some(status)).await;
+        });
+    }
+
+    fn handle_memory_edit_key(&mut self, key: KeyEvent) {
+        let Some(edit) = self.memory_edit.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.memory_edit = None;
+                self.memories_status = None;
+            }
+            KeyCode::Tab | KeyCode::Down => {
+                edit.field = match edit.field {
+                    MemoryField::Content => MemoryField::Tags,
+                    MemoryField::Tags => MemoryField::Importance,
+                    MemoryField::Importance => MemoryField::Content,
+                };
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                edit.field = match edit.field {
+                    MemoryField::Content => MemoryField::Importance,
+                    MemoryField::Tags => MemoryField::Content,
+                    MemoryField::Importance => MemoryField::Tags,
+                };
+            }
+            KeyCode::Backspace => {
+                edit.buffer_mut().pop();
+            }
+            KeyCode::Char(c) => edit.buffer_mut().push(c),
+            KeyCode::Enter => self.save_memory_edit(),
+            _ => {}
+        }
+    }
+
+    fn save_memory_edit(&mut self) {
+        let Some(edit) = self.memory_edit.as_ref() else {
+            return;
+        };
+        let content = edit.content.trim().to_string();
+        if content.is_empty() {
+            self.memories_status = Some("Content can't be empty (Delete forgets a memory).".into());
+            return;
+        }
+        let importance = match edit.importance.trim().parse::<f32>() {
+            Ok(v) if (0.0..=1.0).contains(&v) => v,
+            _ => {
+                self.memories_status = Some("Importance must be a number from 0 to 1.".into());
+                return;
+            }
+        };
+        let tags = split_list(&edit.tags);
+        let id = edit.id.clone();
+        self.memory_edit = None;
+
+        // Show the edit right away; the reload brings back the stored (redacted) version.
+        if let Some(m) = self.memories.iter_mut().find(|m| m.id == id) {
+            m.content = content.clone();
+            m.tags = tags.clone();
+            m.importance = importance;
+        }
+        sort_memories(&mut self.memories);
+        self.memories_status = Some("Saving…".into());
+
+        let tx = self.event_tx.clone();
+        let memory = self.memory.clone();
+        tokio::spawn(async move {
+            let status = match memory.update(&id, &content, tags, importance).await {
+                Ok(true) => "Memory saved.".to_string(),
+                Ok(false) => "That memory no longer exists.".to_string(),
+                Err(e) => format!("Save failed: {}", e),
+            };
+            send_memories(&memory, &tx, Some(status)).await;
+        });
+    }
+
+    fn handle_stats_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.stats_open = false,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.stats_scroll = self.stats_scroll.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.stats_scroll += 1,
+            KeyCode::PageUp => self.stats_scroll = self.stats_scroll.saturating_sub(10),
+            KeyCode::PageDown => self.stats_scroll += 10,
+            KeyCode::Home => self.stats_scroll = 0,
+            _ => {}
+        }
+    }
+
+    fn handle_copy_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.copy_open = false;
+                self.copy_targets.clear();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.copy_selected = self.copy_selected.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.copy_selected + 1 < self.copy_targets.len() {
+                    self.copy_selected += 1;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('y') => {
+                let Some(target) = self.copy_targets.get(self.copy_selected).cloned() else {
+                    return;
+                };
+                self.copy_open = false;
+                self.copy_targets.clear();
+                let notice = match clipboard::copy(&target.content) {
+                    Ok(()) => format!(
+                        "Copied {} lines to the clipboard.",
+                        target.content.lines().count().max(1)
+                    ),
+                    Err(e) => format!("Copy failed: {}", e),
+                };
+                self.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: notice,
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+                self.scroll_messages_to_bottom();
+                if let Some(last) = self.messages.last() {
+                    self.session_record_message(last);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_approval_key(&mut self, key: KeyEvent) {
+        let destructive = self
+            .pending_approval
+            .as_ref()
+            .is_some_and(|r| r.destructive);
+        let approval = match key.code {
+            // "Always" is saved to the config, so a stray `a` typed while the popup came up
+            // mustn't be enough: it takes `a` and then Enter.
+            KeyCode::Enter if self.approval_always_armed => Approval::Always,
+            _ if self.approval_always_armed => {
+                self.approval_always_armed = false;
+                return;
+            }
+            KeyCode::Char('y') => Approval::Once,
+            // Destructive commands need the explicit `y`, and are never trusted for good.
+            KeyCode::Enter if !destructive => Approval::Once,
+            KeyCode::Char('a') if !destructive => {
+                self.approval_always_armed = true;
+                return;
+            }
+            KeyCode::Char('n') | KeyCode::Esc => Approval::Deny,
+            _ => return,
+        };
+        self.approval_always_armed = false;
+        let Some(request) = self.pending_approval.take() else {
+            return;
+        };
+        if approval == Approval::Always {
+            let previous = self.config.clone();
+            let mut sandbox_cfg = self.config.sandbox.clone().unwrap_or_default();
+            let trusted = sandbox_cfg.trusted_commands.get_or_insert_with(Vec::new);
+            for key in &request.keys {
+                if !trusted.contains(key) {
+                    trusted.push(key.clone());
+                }
+            }
+            self.config.sandbox = Some(sandbox_cfg);
+            let _ = self.config.save_layered(&previous, &self.workspace_root);
+        }
+        request.answer(approval);
+    }
+
+    /// The trust prompt can open while the user is typing, so it has no letter shortcuts
+    /// and no default: an arrow key picks an option and Enter confirms it.
+    fn handle_trust_key(&mut self, key: KeyEvent) {
+        let (level, remember) = match key.code {
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => {
+                let up = matches!(key.code, KeyCode::Up | KeyCode::Left);
+                self.trust_choice = Some(match self.trust_choice {
+                    Some(TrustLevel::Trusted) => TrustLevel::Restricted,
+                    Some(TrustLevel::Restricted) => TrustLevel::Trusted,
+                    None if up => TrustLevel::Trusted,
+                    None => TrustLevel::Restricted,
+                });
+                return;
+            }
+            KeyCode::Enter => match self.trust_choice {
+                Some(level) => (level, true),
+                None => return,
+            },
+            // Undecided: restricted for now, ask again next launch.
+            KeyCode::Esc => (TrustLevel::Restricted, false),
+            _ => return,
+        };
+        self.trust_prompt_open = false;
+        self.trust_choice = None;
+        self.workspace_trust = Some(level);
+        let saved = if remember {
+            trust::remember(&self.workspace_root, level).map_err(|e| e.to_string())
+        } else {
+            Ok(())
+        };
+        if remember {
+            // The project's config file only applies to trusted workspaces.
+            self.config = AppConfig::load_layered(&self.workspace_root);
+            self.apply_config();
+            self.refresh_verify_suggestions();
+        }
+        let content = match (level, saved) {
+            (_, Err(e)) => format!("Couldn't save the trust decision: {}", e),
+            (TrustLevel::Trusted, _) => "Workspace trusted.".to_string(),
+            (TrustLevel::Restricted, _) => {
+                "Restricted mode: the agent can read and search but not edit files or run commands. /trust to change.".to_string()
+            }
+        };
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
+
+    /// Load the remembered trust decision, asking if there isn't one.
+    pub fn init_trust(&mut self) {
+        self.workspace_trust = trust::load(&self.workspace_root);
+        self.trust_prompt_open = self.workspace_trust.is_none();
+        self.trust_choice = None;
+    }
+
+    pub fn trust_summary(&self) -> Vec<String> {
+        trust::summary(&self.workspace_root, self.sandbox_policy.enabled)
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        self.rebuild_sandbox_policy();
+    }
+
+    fn workspace_restricted(&self) -> bool {
+        self.workspace_trust != Some(TrustLevel::Trusted)
+    }
+
+    /// Enter copy mode with the latest message/code block preselected.
+    pub fn open_copy_mode(&mut self) {
+        self.copy_targets = clipboard::copy_targets(&self.messages);
+        // The last command goes first so the default selection stays the latest message.
+        if let Some(command) = self.last_tool_command() {
+            self.copy_targets.insert(
+                0,
+                CopyTarget {
+                    label: format!("$ {} (last command)", command.lines().next().unwrap_or("")),
+                    content: command,
+                },
+            );
+        }
+        if self.copy_targets.is_empty() {
+            return;
+        }
+        self.copy_selected = self.copy_targets.len() - 1;
+        self.copy_open = true;
+    }
+
+    /// The shell command behind the most recent bash or verify call, as it would be
+    /// typed in a terminal (a verify pipeline becomes its stages joined with `&&`).
+    pub fn last_tool_command(&self) -> Option<String> {
+        self.tool_outputs.iter().rev().find_map(|t| {
+            if t.tool != "bash" && t.tool != "verify" {
+                return None;
+            }
+            let args = serde_json::from_str(&t.args_raw).unwrap_or(serde_json::Value::Null);
+            let command = command_arg(&args, "command");
+            if !command.trim().is_empty() {
+                return Some(command.trim().to_string());
+            }
+            let output = t.full_output();
+            let stages: Vec<&str> = output
+                .lines()
+                .filter_map(|l| l.strip_prefix("── "))
+                .filter_map(|l| l.split_once(": ").map(|(_, cmd)| cmd.trim()))
+                .collect();
+            (!stages.is_empty()).then(|| stages.join(" && "))
+        })
+    }
+
+    fn copy_last_tool_command(&mut self) {
+        let notice = match self.last_tool_command() {
+            Some(command) => match clipboard::copy(&command) {
+                Ok(()) => format!("Copied to the clipboard: {}", command),
+                Err(e) => format!("Copy failed: {}", e),
+            },
+            None => "No bash or verify command to copy yet.".to_string(),
+        };
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: notice,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
+
+    pub fn open_themes(&mut self) {
+        self.themes_open = true;
+        self.themes_query.clear();
+        self.themes_cursor = 0;
+        self.themes_selected = 0;
+    }
+
+    pub fn filtered_themes(&self) -> Vec<String> {
+        let all = crate::theme::list_ui_themes(Some(&self.workspace_root));
+        let q = self.themes_query.trim().to_lowercase();
+        if q.is_empty() {
+            return all;
+        }
+        all.into_iter()
+            .filter(|t| t.to_lowercase().contains(&q))
+            .collect()
+    }
+
+    fn cycle_theme_preset(&mut self, next: bool) {
+        let themes = crate::theme::list_ui_themes(Some(&self.workspace_root));
+        let cur = crate::theme::ui_theme_name(&self.settings_draft);
+        let idx = themes.iter().position(|t| t == &cur).unwrap_or(0);
+        let new_idx = if themes.is_empty() {
             0
         } else if next {
             (idx + 1) % themes.len()
@@ -2182,7 +5093,7 @@ impl App {
     }
 
     fn cycle_provider(&mut self, next: bool) {
-        let providers = ["openrouter", "openai", "codex"];
+        let providers = ["openrouter", "openai", "gemini", "codex"];
         let cur = self.settings_input.trim().to_lowercase();
         let idx = providers.iter().position(|p| *p == cur).unwrap_or(0);
         let new_idx = if next {
@@ -2261,6 +5172,14 @@ impl App {
             self.plan_mode = true;
             return true;
         }
+        if trimmed == "/plan show" {
+            if self.plan_draft.is_some() {
+                self.open_plan_popup();
+            } else {
+                self.push_local_note("No plan yet. Use /plan, then send a request to draft one.");
+            }
+            return true;
+        }
         if trimmed == "/auto" {
             self.plan_mode = false;
             return true;
@@ -2273,6 +5192,29 @@ impl App {
             self.start_llm_call();
             return true;
         }
+        if let Some(arg) = trimmed.strip_prefix("/go ") {
+            if self.is_processing {
+                return true;
+            }
+            let Some(plan) = self.plan_draft.clone() else {
+                self.push_local_note("No plan yet. Use /plan, then send a request to draft one.");
+                return true;
+            };
+            let count = plan.steps().len();
+            match arg.trim().parse::<usize>() {
+                Ok(n) if (1..=count).contains(&n) => {
+                    self.prepare_go_with_plan(&plan, Some(n));
+                    self.start_llm_call();
+                }
+                _ if count == 0 => {
+                    self.push_local_note("The plan has no numbered steps. Use /go to run it whole.");
+                }
+                _ => {
+                    self.push_local_note(&format!("Usage: /go <step>, where step is 1-{}", count));
+                }
+            }
+            return true;
+        }
         if matches!(trimmed, "/settings" | "/ settings" | "/s") {
             self.open_settings();
             return true;
@@ -2281,6 +5223,35 @@ impl App {
             self.open_themes();
             return true;
         }
+        if matches!(trimmed, "/model" | "/models" | "/m") {
+            self.open_models();
+            return true;
+        }
+        if matches!(trimmed, "/copy" | "/y") {
+            self.open_copy_mode();
+            return true;
+        }
+        if matches!(trimmed, "/memory" | "/memories") {
+            self.open_memories();
+            return true;
+        }
+        if trimmed == "/bookmarks" || trimmed == "/bookmark" || trimmed.starts_with("/bookmark ") {
+            let args = trimmed
+                .trim_start_matches("/bookmarks")
+                .trim_start_matches("/bookmark")
+                .trim();
+            self.handle_bookmark_command(args);
+            return true;
+        }
+        if trimmed == "/workspace" || trimmed.starts_with("/workspace ") || trimmed == "/workspaces"
+        {
+            let args = trimmed
+                .trim_start_matches("/workspaces")
+                .trim_start_matches("/workspace")
+                .trim();
+            self.handle_workspace_command(args);
+            return true;
+        }
         if trimmed.starts_with("/checkpoint-diff") {
             let arg = trimmed
                 .trim_start_matches("/checkpoint-diff")
@@ -2340,49 +5311,26 @@ impl App {
             }
             return true;
         }
-        if trimmed.starts_with("/checkpoint") {
-            if self.session.is_none() {
-                self.new_session();
-            }
-            let Some(store) = self.session.as_ref() else {
-                return true;
-            };
-            let name = trimmed.trim_start_matches("/checkpoint").trim();
-            let name = if name.is_empty() {
-                None
+        if trimmed == "/checkpoints prune" {
+            let retention = checkpoints::Retention::from_config(self.config.checkpoints.as_ref());
+            let msg = if retention.is_unlimited() {
+                "No retention configured. Set max_count, max_age_days or max_size_mb under [checkpoints].".to_string()
             } else {
-                Some(name.to_string())
+                match checkpoints::prune_checkpoints(&self.workspace_root, retention) {
+                    Ok(report) if report.removed.is_empty() => {
+                        "Nothing to prune: checkpoints are within the retention limits.".to_string()
+                    }
+                    Ok(report) => format!(
+                        "Pruned {} checkpoint(s), freed {}KB: {}",
+                        report.removed.len(),
+                        report.freed_bytes.div_ceil(1024),
+                        report.removed.join(", ")
+                    ),
+                    Err(e) => format!("Prune error: {}", e),
+                }
             };
-            match checkpoints::create_checkpoint(&self.workspace_root, store, name) {
-                Ok(meta) => {
-                    self.last_checkpoint = Some(meta.clone());
-                    self.push_message(Message {
-                        id: 0,
-                        role: Role::Agent,
-                        content: format!("Checkpoint created: {} ({:?})", meta.id, meta.backend),
-                        reasoning: None,
-                        tool_calls: None,
-                        tool_group_id: None,
-                        local: true,
-                    });
-                    self.scroll_messages_to_bottom();
-                    if let Some(last) = self.messages.last() {
-                        self.session_record_message(last);
-                    }
-                }
-                Err(e) => {
-                    self.push_message(Message {
-                        id: 0,
-                        role: Role::Agent,
-                        content: format!("Checkpoint error: {}", e),
-                        reasoning: None,
-                        tool_calls: None,
-                        tool_group_id: None,
-                        local: true,
-                    });
-                    self.scroll_messages_to_bottom();
-                }
-            }
+            self.refresh_last_checkpoint();
+            self.push_local_note(&msg);
             return true;
         }
         if trimmed == "/checkpoints" {
@@ -2412,7 +5360,13 @@ impl App {
                 } else {
                     format!(" — {}", nm)
                 };
-                out.push_str(&format!("- {} ({:?}){}\n", m.id, m.backend, nm));
+                out.push_str(&format!(
+                    "- {} ({:?}, {}KB){}\n",
+                    m.id,
+                    m.backend,
+                    checkpoints::checkpoint_size(&self.workspace_root, &m.id).div_ceil(1024),
+                    nm
+                ));
             }
             self.push_message(Message {
                 id: 0,
@@ -2429,6 +5383,52 @@ impl App {
             }
             return true;
         }
+        if trimmed.starts_with("/checkpoint") {
+            if self.session.is_none() {
+                self.new_session();
+            }
+            let Some(store) = self.session.as_ref() else {
+                return true;
+            };
+            let name = trimmed.trim_start_matches("/checkpoint").trim();
+            let name = if name.is_empty() {
+                None
+            } else {
+                Some(name.to_string())
+            };
+            match checkpoints::create_checkpoint(&self.workspace_root, store, name) {
+                Ok(meta) => {
+                    self.last_checkpoint = Some(meta.clone());
+                    self.prune_checkpoints_quietly();
+                    self.push_message(Message {
+                        id: 0,
+                        role: Role::Agent,
+                        content: format!("Checkpoint created: {} ({:?})", meta.id, meta.backend),
+                        reasoning: None,
+                        tool_calls: None,
+                        tool_group_id: None,
+                        local: true,
+                    });
+                    self.scroll_messages_to_bottom();
+                    if let Some(last) = self.messages.last() {
+                        self.session_record_message(last);
+                    }
+                }
+                Err(e) => {
+                    self.push_message(Message {
+                        id: 0,
+                        role: Role::Agent,
+                        content: format!("Checkpoint error: {}", e),
+                        reasoning: None,
+                        tool_calls: None,
+                        tool_group_id: None,
+                        local: true,
+                    });
+                    self.scroll_messages_to_bottom();
+                }
+            }
+            return true;
+        }
         if trimmed.starts_with("/restore") {
             if self.is_processing
                 || self
@@ -2449,13 +5449,6 @@ impl App {
                 return true;
             }
 
-            if self.session.is_none() {
-                self.new_session();
-            }
-            let Some(store) = self.session.as_ref() else {
-                return true;
-            };
-
             let arg = trimmed.trim_start_matches("/restore").trim();
             let meta = if arg.is_empty() || arg == "latest" {
                 checkpoints::list_checkpoints(&self.workspace_root, 1)
@@ -2478,47 +5471,14 @@ impl App {
                 return true;
             };
 
-            if let Err(e) = checkpoints::restore_checkpoint(&self.workspace_root, store, &meta) {
-                self.push_message(Message {
-                    id: 0,
-                    role: Role::Agent,
-                    content: format!("Restore error: {}", e),
-                    reasoning: None,
-                    tool_calls: None,
-                    tool_group_id: None,
-                    local: true,
-                });
-                self.scroll_messages_to_bottom();
-                return true;
-            }
-
-            if let Err(e) = checkpoints::truncate_session_to(store, meta.session_event_count) {
-                self.push_message(Message {
-                    id: 0,
-                    role: Role::Agent,
-                    content: format!("Session rewind error: {}", e),
-                    reasoning: None,
-                    tool_calls: None,
-                    tool_group_id: None,
-                    local: true,
-                });
-                self.scroll_messages_to_bottom();
-                return true;
-            }
-
-            // Reload the rewound session so UI matches the restored timeline.
-            self.init_session(true);
-            self.last_checkpoint = Some(meta.clone());
-            self.push_message(Message {
-                id: 0,
-                role: Role::Agent,
-                content: format!("(restored checkpoint {})", meta.id),
-                reasoning: None,
-                tool_calls: None,
-                tool_group_id: None,
-                local: true,
+            // Nothing is rewritten until the dry-run summary is confirmed.
+            let preview = checkpoints::restore_preview(&self.workspace_root, &meta)
+                .map_err(|e| e.to_string());
+            self.restore_prompt = Some(RestorePrompt {
+                meta,
+                preview,
+                scroll: 0,
             });
-            self.scroll_messages_to_bottom();
             return true;
         }
         if matches!(trimmed, "/help" | "/h" | "/?") {
@@ -2526,7 +5486,7 @@ impl App {
                 id: 0,
                 role: Role::Agent,
                 content:
-                    "Commands: /plan, /auto, /go, /settings (/s), /themes (/t), /verify, /checkpoint, /checkpoints, /restore, /checkpoint-diff, /new, /resume, /sessions"
+                    "Commands: /plan, /plan show, /auto, /go, /go <step>, /settings (/s), /themes (/t), /model (/m), /memory, /bookmark [add|remove <path>], /verify, /quick <question>, /checkpoint, /checkpoints [prune], /restore, /checkpoint-diff, /undo [force], /new, /resume, /rename <title>, /sessions, /export [md|html|json] [path], /stats, /find <text> (Ctrl+F; Ctrl+N/Ctrl+P next/prev), /trust, /readonly [on|off], /login, /copy (Ctrl+Y; Ctrl+K copies the last command)"
                         .into(),
                 reasoning: None,
                 tool_calls: None,
@@ -2547,15 +5507,28 @@ impl App {
             self.tool_group_by_call_id.clear();
             self.tool_trace_expanded.clear();
             self.tool_trace_show_details.clear();
+            self.message_expanded.clear();
             self.tool_failure_counts.clear();
+            self.tool_calls_this_turn.clear();
+            self.turn_tool_rounds = 0;
+            self.turn_limit_prompt = None;
+            self.message_models.clear();
             self.tool_loop_abort = None;
             self.recent_files.clear();
+            self.recent_selected = 0;
+            self.pinned_files.clear();
+            self.file_edits.clear();
             self.last_searches.clear();
+            self.verify_history.clear();
             self.turn_user_message = None;
             self.turn_tool_start_idx = 0;
             self.current_turn_id = 0;
             self.next_tool_group_id = 1;
             self.last_tool_group_id = None;
+            self.session_usage = TokenUsage::default();
+            self.turn_timings.clear();
+            self.plan_draft = None;
+            self.plan_steps_running.clear();
             self.new_session();
             self.push_message(Message {
                 id: 0,
@@ -2570,6 +5543,18 @@ impl App {
             return true;
         }
 
+        if trimmed == "/rename" || trimmed.starts_with("/rename ") {
+            let title = trimmed.trim_start_matches("/rename").trim();
+            if title.is_empty() {
+                let current = self.session_title.as_deref().unwrap_or("(untitled)");
+                self.push_local_note(&format!("Session: {}. Usage: /rename <title>", current));
+            } else {
+                self.set_session_title(title.to_string());
+                self.push_local_note(&format!("Session renamed to \"{}\"", title));
+            }
+            return true;
+        }
+
         if trimmed == "/resume" {
             self.init_session(true);
             self.scroll_messages_to_bottom();
@@ -2577,8 +5562,16 @@ impl App {
         }
 
         if trimmed == "/verify" {
+            if self.workspace_restricted() {
+                self.push_local_note(
+                    "[Workspace is not trusted: /verify runs commands and is disabled in restricted mode (/trust to change)]",
+                );
+                return true;
+            }
             self.refresh_verify_suggestions();
-            if self.verify_suggestions.is_empty() {
+            let pipeline =
+                crate::verify::pipeline_for(&self.active_root, self.config.verify.as_ref());
+            if pipeline.is_empty() && self.verify_suggestions.is_empty() {
                 self.push_message(Message {
                     id: 0,
                     role: Role::Agent,
@@ -2595,13 +5588,24 @@ impl App {
                 return true;
             }
 
-            let cmds = self
-                .verify_suggestions
-                .iter()
-                .take(2)
-                .map(|s| s.command.clone())
-                .collect::<Vec<_>>()
-                .join(" && ");
+            // The verify tool runs the pipeline itself when given no command.
+            let (cmds, args_raw) = if pipeline.is_empty() {
+                let cmds = self
+                    .verify_suggestions
+                    .iter()
+                    .take(2)
+                    .map(|s| s.command.clone())
+                    .collect::<Vec<_>>()
+                    .join(" && ");
+                let args = serde_json::json!({ "command": cmds }).to_string();
+                (cmds, args)
+            } else {
+                let stages: Vec<String> = pipeline
+                    .iter()
+                    .map(|s| format!("{} `{}`", s.name, s.command))
+                    .collect();
+                (stages.join(" → "), "{}".to_string())
+            };
 
             let group_id = self.next_tool_group_id;
             self.next_tool_group_id = self.next_tool_group_id.saturating_add(1);
@@ -2621,25 +5625,15 @@ impl App {
                 self.session_record_message(last);
             }
 
-            // Execute via bash tool (respects sandbox).
+            // Execute via the verify tool (respects sandbox).
             let tx = self.event_tx.clone();
             let policy = self.sandbox_policy.clone();
+            let shell = self.shell.clone();
             let call_id = format!("internal:verify:{}", crate::memory::types::unix_ts());
             self.tool_group_by_call_id.insert(call_id.clone(), group_id);
-            let args_raw = serde_json::json!({"command": cmds}).to_string();
-            let args_val: serde_json::Value = serde_json::from_str(&args_raw)
-                .unwrap_or_else(|_| serde_json::json!({"command": cmds}));
-            let args_summary = summarize_tool_call("bash", &args_val);
-            let sandbox = sandbox_decision_for_tool("bash", &args_val, &policy);
-
-            let _ = tx.send(AppEvent::ToolStart(crate::events::ToolStartEvent {
-                call_id: call_id.clone(),
-                tool: "bash".to_string(),
-                args_raw: args_raw.clone(),
-                args_summary,
-                cwd: policy.root.clone(),
-                sandbox: sandbox.clone(),
-            }));
+            let start = tool_start_event("verify", &args_raw, &call_id, &policy);
+            let sandbox = start.sandbox.clone();
+            let _ = tx.send(AppEvent::ToolStart(start));
 
             if !sandbox.allowed {
                 let msg = sandbox
@@ -2658,21 +5652,222 @@ impl App {
             }
 
             tokio::spawn(async move {
-                let _ = crate::tools::execute_tool("bash", &args_raw, &call_id, &tx, &policy).await;
+                let _ =
+                    crate::tools::execute_tool("verify", &args_raw, &call_id, &tx, &policy, &shell)
+                        .await;
+            });
+            return true;
+        }
+
+        if trimmed == "/quick" || trimmed.starts_with("/quick ") {
+            let question = trimmed.trim_start_matches("/quick").trim();
+            if question.is_empty() {
+                self.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: "Usage: /quick <question>".into(),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+                self.scroll_messages_to_bottom();
+            } else {
+                self.start_quick_call(question.to_string());
+            }
+            return true;
+        }
+
+        if trimmed == "/trust" {
+            self.trust_prompt_open = true;
+            self.trust_choice = None;
+            return true;
+        }
+
+        if trimmed == "/readonly" || trimmed.starts_with("/readonly ") {
+            let arg = trimmed.trim_start_matches("/readonly").trim();
+            let read_only = match arg {
+                "" => !self.read_only,
+                "on" => true,
+                "off" => false,
+                _ => {
+                    self.push_local_note("Usage: /readonly [on|off]");
+                    return true;
+                }
+            };
+            self.set_read_only(read_only);
+            self.push_local_note(if read_only {
+                "Read-only mode on: file edits and commands that change files, git state or \
+                 packages are blocked. /readonly again to turn it off."
+            } else {
+                "Read-only mode off: the agent can edit files again."
+            });
+            return true;
+        }
+
+        if trimmed == "/login" {
+            self.start_codex_login();
+            return true;
+        }
+
+        if trimmed == "/logs" {
+            self.open_log_tail();
+            return true;
+        }
+
+        if trimmed == "/debug" || trimmed.starts_with("/debug ") {
+            match trimmed["/debug".len()..].trim() {
+                "last" => self.show_last_request(),
+                _ => self.push_local_note("[Usage: /debug last]"),
+            }
+            return true;
+        }
+
+        if trimmed == "/raw" {
+            // The latest command run under a terminal, escape codes shown.
+            let raw = self
+                .tool_outputs
+                .iter()
+                .rev()
+                .find(|t| !t.raw_output.is_empty())
+                .map(|t| (t.args_summary.clone(), t.raw_output.clone()));
+            match raw {
+                Some((summary, raw)) => {
+                    self.file_view = Some(FileView {
+                        path: format!("raw: {}", summary),
+                        line: 0,
+                        lines: raw.lines().map(crate::pty::visible_escapes).collect(),
+                        scroll: 0,
+                    });
+                }
+                None => {
+                    self.push_message(Message {
+                        id: 0,
+                        role: Role::Agent,
+                        content: "[No terminal output yet: /raw shows bash commands run with tty]"
+                            .to_string(),
+                        reasoning: None,
+                        tool_calls: None,
+                        tool_group_id: None,
+                        local: true,
+                    });
+                    self.scroll_messages_to_bottom();
+                }
+            }
+            return true;
+        }
+        if trimmed == "/undo" || trimmed == "/undo force" {
+            let force = trimmed.ends_with("force");
+            let content = match checkpoints::undo_last(&self.active_root, force) {
+                Ok(Some((entry, remaining))) => {
+                    let files: Vec<String> = entry
+                        .files
+                        .iter()
+                        .map(|f| {
+                            f.path
+                                .strip_prefix(&self.active_root)
+                                .unwrap_or(&f.path)
+                                .display()
+                                .to_string()
+                        })
+                        .collect();
+                    format!(
+                        "Undid {}: {} ({} more to undo)",
+                        entry.tool,
+                        files.join(", "),
+                        remaining
+                    )
+                }
+                Ok(None) => "Nothing to undo.".to_string(),
+                Err(e) => format!("Undo error: {}", e),
+            };
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content,
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
+            });
+            self.scroll_messages_to_bottom();
+            return true;
+        }
+
+        if trimmed == "/find" || trimmed.starts_with("/find ") {
+            let query = trimmed.trim_start_matches("/find").trim();
+            if query.is_empty() {
+                self.transcript_query = None;
+                self.transcript_match_row = None;
+            } else {
+                self.find_in_transcript(query.to_string());
+            }
+            return true;
+        }
+
+        if trimmed == "/stats" {
+            self.stats_open = true;
+            self.stats_scroll = 0;
+            self.scroll_messages_to_bottom();
+            return true;
+        }
+
+        if trimmed == "/export" || trimmed.starts_with("/export ") {
+            let msg = self.export_session(trimmed.trim_start_matches("/export").trim());
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: msg,
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
             });
+            self.scroll_messages_to_bottom();
+            if let Some(last) = self.messages.last() {
+                self.session_record_message(last);
+            }
             return true;
         }
 
         if trimmed == "/sessions" {
             let msg = if let Some(store) = &self.session {
-                format!(
+                let mut msg = format!(
                     "Sessions dir: {}",
                     store
                         .events_path
                         .parent()
                         .unwrap_or_else(|| std::path::Path::new(""))
                         .display()
-                )
+                );
+                let sessions =
+                    crate::session::list_sessions(&self.workspace_root).unwrap_or_default();
+                for info in sessions.iter().take(10) {
+                    let current = if info.session_id == store.session_id {
+                        " (current)"
+                    } else {
+                        ""
+                    };
+                    let zst = if info.compressed { " [zst]" } else { "" };
+                    let open = info
+                        .owner()
+                        .map(|o| format!(" (open: {})", o))
+                        .unwrap_or_default();
+                    let title = info
+                        .title()
+                        .map(|t| format!(" — {}", t))
+                        .unwrap_or_default();
+                    msg.push_str(&format!(
+                        "\n- {}{}{} {}KB{}{}",
+                        info.session_id,
+                        title,
+                        zst,
+                        info.bytes.div_ceil(1024),
+                        current,
+                        open
+                    ));
+                }
+                msg
             } else {
                 "No session store initialized".to_string()
             };
@@ -2694,6 +5889,60 @@ impl App {
         false
     }
 
+    /// `/export [md|html|json] [path]`: write the transcript to a file and describe the result.
+    fn export_session(&self, args: &str) -> String {
+        let mut parts = args.split_whitespace().peekable();
+        let format = match parts.peek().and_then(|p| ExportFormat::parse(p)) {
+            Some(f) => {
+                parts.next();
+                f
+            }
+            None => ExportFormat::Markdown,
+        };
+        let session_id = self
+            .session
+            .as_ref()
+            .map(|s| s.session_id.clone())
+            .unwrap_or_else(|| crate::memory::types::unix_ts().to_string());
+        let path = match parts.next() {
+            Some(p) => self.workspace_root.join(p),
+            None => self.workspace_root.join(format!(
+                "lorikeet-session-{}.{}",
+                session_id,
+                format.extension()
+            )),
+        };
+        let path = match self.sandbox_policy.check_path_allowed(&path) {
+            Ok(p) => p,
+            Err(e) => return format!("Export failed: {}", e),
+        };
+
+        let title = format!(
+            "Lorikeet session {} — {}",
+            session_id,
+            self.workspace_root_display()
+        );
+        let doc = export::render(
+            format,
+            &title,
+            &self.messages,
+            &self.tool_outputs,
+            &self.session_stats(),
+        );
+        match std::fs::write(&path, doc) {
+            Ok(()) => format!("Exported session to {}", path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        }
+    }
+
+    pub fn session_stats(&self) -> SessionStats {
+        SessionStats::collect(&self.messages, &self.tool_outputs, self.session_usage)
+    }
+
+    pub fn turn_stats(&self) -> Vec<TurnStats> {
+        stats::turn_breakdown(&self.turn_timings, &self.tool_outputs)
+    }
+
     pub fn command_suggestions(&self, prefix: &str) -> Vec<(String, String)> {
         let p = prefix.trim().to_lowercase();
         if !p.starts_with('/') {
@@ -2702,18 +5951,54 @@ impl App {
 
         let all: Vec<(String, String)> = vec![
             ("/plan".into(), "Plan mode (no tools)".into()),
+            ("/plan show".into(), "Re-open the latest plan".into()),
             ("/auto".into(), "Normal mode (tools)".into()),
-            ("/go".into(), "Execute plan (tools once)".into()),
+            (
+                "/go".into(),
+                "Execute plan (tools once), or one step with /go <n>".into(),
+            ),
             ("/settings".into(), "Open settings".into()),
             ("/themes".into(), "Pick a theme".into()),
-            ("/verify".into(), "Run suggested verify".into()),
+            ("/model".into(), "Pick a model".into()),
+            ("/memory".into(), "Browse, edit and delete memories".into()),
+            (
+                "/bookmark".into(),
+                "List, add or remove bookmarked paths".into(),
+            ),
+            ("/verify".into(), "Run the verify pipeline".into()),
+            (
+                "/quick".into(),
+                "Ask a throwaway question (no tools or memory)".into(),
+            ),
             ("/checkpoint".into(), "Create checkpoint".into()),
-            ("/checkpoints".into(), "List checkpoints".into()),
+            (
+                "/checkpoints".into(),
+                "List checkpoints (prune: apply retention)".into(),
+            ),
             ("/restore".into(), "Restore checkpoint".into()),
             ("/checkpoint-diff".into(), "Show checkpoint diff".into()),
+            ("/undo".into(), "Revert the last file edit".into()),
+            ("/raw".into(), "Show raw terminal output".into()),
+            (
+                "/debug last".into(),
+                "Show the last request sent to the model".into(),
+            ),
+            ("/logs".into(), "Tail the diagnostics log".into()),
+            (
+                "/workspace".into(),
+                "List, add, remove or switch workspace roots".into(),
+            ),
             ("/new".into(), "New session".into()),
             ("/resume".into(), "Resume last session".into()),
+            ("/rename".into(), "Rename this session".into()),
             ("/sessions".into(), "Show sessions dir".into()),
+            ("/export".into(), "Export session to md/html/json".into()),
+            ("/stats".into(), "Session statistics".into()),
+            ("/find".into(), "Search the transcript".into()),
+            ("/trust".into(), "Change workspace trust".into()),
+            ("/readonly".into(), "Toggle read-only mode".into()),
+            ("/login".into(), "Sign in with ChatGPT (codex provider)".into()),
+            ("/copy".into(), "Copy a message or code block".into()),
             ("/help".into(), "Show commands".into()),
         ];
 
@@ -2721,6 +6006,7 @@ impl App {
         let expanded = match p.as_str() {
             "/s" => "/settings",
             "/t" => "/themes",
+            "/m" => "/model",
             _ => "",
         };
 
@@ -2736,10 +6022,23 @@ impl App {
     pub fn settings_rows(&self) -> Vec<(String, String)> {
         self.current_settings_items()
             .iter()
-            .map(|item| (item.label().to_string(), self.read_settings_value(*item)))
+            .map(|item| {
+                let label = match self.project_layer.layer_of(item.config_key()) {
+                    ConfigLayer::Project => format!("{} [project]", item.label()),
+                    ConfigLayer::User => item.label().to_string(),
+                };
+                (label, self.read_settings_value(*item))
+            })
             .collect()
     }
 
+    /// Config file the selected setting is read from (and saved to)
+    pub fn settings_selected_source(&self) -> &'static str {
+        self.project_layer
+            .layer_of(self.current_settings_item().config_key())
+            .label()
+    }
+
     pub fn settings_category_rows(&self) -> Vec<String> {
         self.settings_categories()
             .iter()
@@ -2760,14 +6059,37 @@ impl App {
             col >= area.x && col < area.right() && row >= area.y && row < area.bottom()
         };
 
-        if self.settings_open {
-            let in_popup = contains(self.settings_popup_area, mouse.column, mouse.row);
-            let in_cats = contains(self.settings_categories_area, mouse.column, mouse.row);
-            let in_items = contains(self.settings_items_area, mouse.column, mouse.row);
-            let in_editor = contains(self.settings_editor_area, mouse.column, mouse.row);
-
+        if let Some(pager) = self.tool_pager.as_mut() {
             match mouse.kind {
-                MouseEventKind::Down(MouseButton::Left) => {
+                MouseEventKind::ScrollUp => pager.scroll_by(-3),
+                MouseEventKind::ScrollDown => pager.scroll_by(3),
+                _ => {}
+            }
+            return;
+        }
+        if let Some(view) = self.text_popup.as_mut() {
+            let max = view.lines.len().saturating_sub(1);
+            match mouse.kind {
+                MouseEventKind::ScrollUp => {
+                    view.scroll = view.scroll.saturating_sub(3);
+                    if let Some(tail) = self.log_tail.as_mut() {
+                        tail.follow = false;
+                    }
+                }
+                MouseEventKind::ScrollDown => view.scroll = (view.scroll + 3).min(max),
+                _ => {}
+            }
+            return;
+        }
+
+        if self.settings_open {
+            let in_popup = contains(self.settings_popup_area, mouse.column, mouse.row);
+            let in_cats = contains(self.settings_categories_area, mouse.column, mouse.row);
+            let in_items = contains(self.settings_items_area, mouse.column, mouse.row);
+            let in_editor = contains(self.settings_editor_area, mouse.column, mouse.row);
+
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
                     if !in_popup {
                         return;
                     }
@@ -2870,10 +6192,18 @@ impl App {
                 } else if in_context {
                 }
             }
-            MouseEventKind::Down(_) => {
+            MouseEventKind::Down(button) => {
                 // Click to focus pane
                 if in_chat {
                     self.active_pane = Pane::Chat;
+                    // Row 0 of the chat area is its border.
+                    if button == MouseButton::Left && mouse.row > chat_area.y {
+                        let row = self.chat_scroll_rows + (mouse.row - chat_area.y - 1) as usize;
+                        let col = mouse.column.saturating_sub(chat_area.x + 1) as usize;
+                        if !self.click_file_ref(row, col) {
+                            self.click_chat_row(row);
+                        }
+                    }
                 } else if in_context {
                     self.active_pane = Pane::Context;
                 } else if in_splitter {
@@ -2895,11 +6225,25 @@ impl App {
     }
 
     pub fn handle_event(&mut self, event: AppEvent) {
+        if self.offline.is_some()
+            && matches!(
+                event,
+                AppEvent::AgentChunk(_)
+                    | AppEvent::AgentReasoning(_)
+                    | AppEvent::AgentDone
+                    | AppEvent::AgentToolCalls(_)
+            )
+        {
+            self.back_online();
+        }
         match event {
             AppEvent::Input(key) => self.handle_key(key),
             AppEvent::Mouse(mouse) => {
                 self.handle_mouse(mouse, self.chat_area, self.context_area);
             }
+            AppEvent::Paste(text) => self.handle_paste(&text),
+            AppEvent::Focus(focused) => self.terminal_focused = focused,
+            AppEvent::Resize => {}
             AppEvent::AgentChunk(chunk) => {
                 self.current_response.push_str(&chunk);
             }
@@ -2911,6 +6255,27 @@ impl App {
                 let reasoning = std::mem::take(&mut self.current_reasoning);
                 let response_for_mem = response.clone();
 
+                if std::mem::take(&mut self.quick_call) {
+                    if !response.is_empty() {
+                        self.push_message(Message {
+                            id: 0,
+                            role: Role::Agent,
+                            content: response,
+                            reasoning: (!reasoning.is_empty()).then_some(reasoning),
+                            tool_calls: None,
+                            tool_group_id: None,
+                            local: true,
+                        });
+                        self.scroll_messages_to_bottom();
+                        if let Some(last) = self.messages.last() {
+                            self.session_record_message(last);
+                        }
+                    }
+                    self.is_processing = false;
+                    self.processing_start = None;
+                    return;
+                }
+
                 if self.plan_generating {
                     self.plan_generating = false;
                     let parsed = parse_plan_response(&response);
@@ -2924,17 +6289,16 @@ impl App {
                                 plan_markdown: response.clone(),
                                 questions: Vec::new(),
                                 answers: HashMap::new(),
+                                completed_steps: Vec::new(),
                             });
                             self.plan_parse_error = Some(e);
                         }
                     }
 
-                    self.plan_popup_open = true;
-                    self.plan_focus = PlanFocus::Questions;
-                    self.plan_question_selected = 0;
-                    self.plan_button_selected = 0;
-                    self.plan_preview_scroll = 0;
-                    self.load_plan_answer_input();
+                    if let (Some(store), Some(draft)) = (&self.session, &self.plan_draft) {
+                        store.record_plan(draft, false);
+                    }
+                    self.open_plan_popup();
 
                     self.is_processing = false;
                     self.processing_start = None;
@@ -2955,11 +6319,21 @@ impl App {
                         tool_group_id: None,
                         local: false,
                     });
+                    self.tag_last_message_model();
                     self.scroll_messages_to_bottom();
                     if let Some(last) = self.messages.last() {
                         self.session_record_message(last);
                     }
                 }
+                if self.explore_calls > 0 {
+                    // The explore model has said what it found; the main model finishes.
+                    self.explore_calls = 0;
+                    self.last_round_exploring = false;
+                    self.ephemeral_user_message = Some(EXPLORE_HANDOFF.into());
+                    self.start_llm_call();
+                    return;
+                }
+                self.complete_running_plan_steps();
 
                 // Optional: LLM-based extraction of durable long-term memories.
                 let memory_enabled = self
@@ -3018,6 +6392,7 @@ impl App {
 
                 self.is_processing = false;
                 self.processing_start = None;
+                self.notify_turn_finished("finished");
             }
             AppEvent::AgentToolCalls(tool_calls) => {
                 // Save assistant message with tool calls
@@ -3046,15 +6421,46 @@ impl App {
                     tool_group_id: Some(group_id),
                     local: false,
                 });
+                self.tag_last_message_model();
+                self.last_round_exploring = self.config.routing.as_ref().is_some_and(|r| {
+                    r.explores(tool_calls.iter().map(|tc| tc.function.name.as_str()))
+                });
                 self.scroll_messages_to_bottom();
                 if let Some(last) = self.messages.last() {
                     self.session_record_message(last);
                 }
+                self.maybe_auto_checkpoint(&tool_calls);
+                let refusals = self.tool_budget_refusals(&tool_calls);
+                self.turn_tool_rounds = self.turn_tool_rounds.saturating_add(1);
 
                 // Execute tools and continue
                 let tx = self.event_tx.clone();
                 let policy = self.sandbox_policy.clone();
+                let shell = self.shell.clone();
                 let memory = self.memory.clone();
+                let workspace_root = self.workspace_root.clone();
+                let sandbox_cfg = self.config.sandbox.as_ref();
+                let confirm_unusual = sandbox_cfg.and_then(|s| s.confirm_unusual).unwrap_or(true);
+                let destructive_patterns = approval::destructive_patterns(
+                    sandbox_cfg.and_then(|s| s.confirm_patterns.as_deref()),
+                );
+                let mut trusted: std::collections::HashSet<String> = sandbox_cfg
+                    .and_then(|s| s.trusted_commands.clone())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect();
+                let restricted = self.workspace_restricted();
+                let summarizer = self.tool_summarizer();
+                let lsp_cfg = self.config.lsp.as_ref();
+                let diagnostics_budget =
+                    if lsp_cfg.and_then(|l| l.auto_diagnostics).unwrap_or(false) {
+                        lsp_cfg
+                            .and_then(|l| l.auto_diagnostics_per_turn)
+                            .unwrap_or(3)
+                    } else {
+                        0
+                    };
+                let diagnostics_reports = self.auto_diagnostics_reports.clone();
 
                 tokio::spawn(async move {
                     let mut tool_results = Vec::new();
@@ -3095,7 +6501,16 @@ impl App {
                         };
 
                         let args_summary = summarize_tool_call(name, &args_val);
-                        let sandbox = sandbox_decision_for_tool(name, &args_val, &policy);
+                        let sandbox = if let Some(reason) = refusals.get(&call_id) {
+                            crate::sandbox::SandboxDecision::deny(reason.clone())
+                        } else if restricted && !is_read_only_tool(name) {
+                            crate::sandbox::SandboxDecision::deny(format!(
+                                "Workspace is not trusted: `{}` is disabled in restricted mode (/trust to change).",
+                                name
+                            ))
+                        } else {
+                            sandbox_decision_for_tool(name, &args_val, &policy)
+                        };
 
                         let _ = tx.send(AppEvent::ToolStart(crate::events::ToolStartEvent {
                             call_id: call_id.clone(),
@@ -3124,6 +6539,73 @@ impl App {
                             continue;
                         }
 
+                        if name == "bash" || name == "run_background" || name == "verify" {
+                            let command = args_val
+                                .get("command")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("");
+                            // `verify` runs builds and tests: only destructive commands ask.
+                            let keys = if name == "verify" {
+                                Vec::new()
+                            } else {
+                                approval::command_keys(command)
+                            };
+                            let untrusted: Vec<String> = keys
+                                .iter()
+                                .filter(|k| !trusted.contains(*k))
+                                .cloned()
+                                .collect();
+                            let mut reasons = if confirm_unusual && !untrusted.is_empty() {
+                                approval::anomalies(
+                                    command,
+                                    &untrusted,
+                                    &approval::load_history(&workspace_root),
+                                )
+                            } else {
+                                Vec::new()
+                            };
+                            // Destructive commands ask every time, trusted or not.
+                            let destructive = approval::destructive(command, &destructive_patterns);
+                            let is_destructive = !destructive.is_empty();
+                            reasons.splice(0..0, destructive);
+                            if !reasons.is_empty() {
+                                let (request, reply) = ApprovalRequest::new(
+                                    call_id.clone(),
+                                    command.to_string(),
+                                    untrusted.clone(),
+                                    reasons,
+                                    is_destructive,
+                                );
+                                let _ = tx.send(AppEvent::ToolApproval(request));
+                                match reply.await.unwrap_or(Approval::Deny) {
+                                    Approval::Once => {}
+                                    Approval::Always => {
+                                        trusted.extend(untrusted);
+                                    }
+                                    Approval::Deny => {
+                                        let msg = "User declined to run this command.".to_string();
+                                        let _ = tx.send(AppEvent::ToolOutput(
+                                            crate::events::ToolOutputEvent {
+                                                call_id: call_id.clone(),
+                                                chunk: msg.clone(),
+                                            },
+                                        ));
+                                        let _ = tx.send(AppEvent::ToolComplete(
+                                            crate::events::ToolCompleteEvent {
+                                                call_id: call_id.clone(),
+                                                success: false,
+                                            },
+                                        ));
+                                        tool_results.push((call_id, msg));
+                                        continue;
+                                    }
+                                }
+                            }
+                            for key in &keys {
+                                approval::record(&workspace_root, key);
+                            }
+                        }
+
                         let result = match name {
                             "memory_recall" | "memory_save" | "memory_list" | "memory_forget" => {
                                 let out = match name {
@@ -3286,9 +6768,69 @@ impl App {
                                 ));
                                 out
                             }
-                            _ => execute_tool(name, &args_raw, &call_id, &tx, &policy).await,
+                            _ => {
+                                // Only pay for the language server while the turn has budget.
+                                let edited: Vec<PathBuf> = if diagnostics_reports
+                                    .load(Ordering::SeqCst)
+                                    < diagnostics_budget
+                                {
+                                    file_changes(name, &args_raw)
+                                        .into_iter()
+                                        .map(|(path, _, _)| policy.root.join(path))
+                                        .collect()
+                                } else {
+                                    Vec::new()
+                                };
+                                crate::lsp::open_before_edit(&policy, &edited).await;
+
+                                let out =
+                                    execute_tool(name, &args_raw, &call_id, &tx, &policy, &shell)
+                                        .await;
+                                let out = if is_infra_failure(&out) {
+                                    // Not the call's fault; run it once more before the model
+                                    // sees a failure. The retry gets its own trace row.
+                                    let _ = tx.send(AppEvent::AgentRetry(format!(
+                                        "`{}` couldn't run ({}); trying once more",
+                                        name,
+                                        out.lines().next().unwrap_or("").trim()
+                                    )));
+                                    tokio::time::sleep(TOOL_INFRA_RETRY_DELAY).await;
+                                    let _ = tx.send(AppEvent::ToolStart(
+                                        crate::events::ToolStartEvent {
+                                            call_id: call_id.clone(),
+                                            tool: name.to_string(),
+                                            args_raw: args_raw.clone(),
+                                            args_summary: args_summary.clone(),
+                                            cwd: policy.root.clone(),
+                                            sandbox: sandbox.clone(),
+                                        },
+                                    ));
+                                    execute_tool(name, &args_raw, &call_id, &tx, &policy, &shell)
+                                        .await
+                                } else {
+                                    out
+                                };
+                                if edited.is_empty() || out.starts_with("Error") {
+                                    out
+                                } else {
+                                    append_new_lsp_errors(
+                                        out,
+                                        &edited,
+                                        &call_id,
+                                        &tx,
+                                        &policy,
+                                        &diagnostics_reports,
+                                        diagnostics_budget,
+                                    )
+                                    .await
+                                }
+                            }
                         };
 
+                        let result = match &summarizer {
+                            Some(s) => s.condense(name, &call_id, result, &tx).await,
+                            None => result,
+                        };
                         tool_results.push((call_id, result));
                     }
 
@@ -3325,18 +6867,78 @@ impl App {
                         self.scroll_messages_to_bottom();
                         self.is_processing = false;
                         self.processing_start = None;
+                        self.notify_turn_finished("stopped");
                         return;
                     }
                 }
 
+                if let Some(limit) = self.turn_limit_reached() {
+                    self.pause_turn(limit);
+                    return;
+                }
+
                 // Continue the conversation
                 self.start_llm_call();
             }
+            AppEvent::AgentRetry(notice) => {
+                self.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: format!("[Retry: {}]", notice),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+                self.scroll_messages_to_bottom();
+            }
+            AppEvent::AgentOffline(err) if !self.quick_call => self.go_offline(err),
+            AppEvent::AgentOffline(err) => self.handle_event(AppEvent::AgentError(err)),
+            AppEvent::Reconnect(attempt) => {
+                let due = self
+                    .offline
+                    .as_ref()
+                    .is_some_and(|o| o.attempts == attempt && o.retry_at.is_some());
+                if due {
+                    self.reconnect_now();
+                }
+            }
             AppEvent::AgentError(err) => {
+                self.offline = None;
+                // The steps stay pending so `/go` picks them up again.
+                self.plan_steps_running.clear();
+                // Keep whatever had streamed so far instead of dropping it with the error.
+                let partial = std::mem::take(&mut self.current_response);
+                let reasoning = std::mem::take(&mut self.current_reasoning);
+                if !partial.trim().is_empty() {
+                    self.push_message(Message {
+                        id: 0,
+                        role: Role::Draft,
+                        content: partial,
+                        reasoning: (!reasoning.trim().is_empty()).then_some(reasoning),
+                        tool_calls: None,
+                        tool_group_id: None,
+                        local: true,
+                    });
+                    if let Some(last) = self.messages.last() {
+                        self.session_record_message(last);
+                    }
+                }
+                if self.llm_provider == LlmProvider::Codex
+                    && crate::codex_oauth::needs_reauth(&err.message)
+                {
+                    self.reauth_prompt_open = true;
+                }
+                // Say what to do about it on the line under the error.
+                let mut content = format!("[Error: {}]", err);
+                if let Some(hint) = err.guidance(self.llm_provider) {
+                    content.push('\n');
+                    content.push_str(&hint);
+                }
                 self.push_message(Message {
                     id: 0,
                     role: Role::Agent,
-                    content: format!("[Error: {}]", err),
+                    content,
                     reasoning: None,
                     tool_calls: None,
                     tool_group_id: None,
@@ -3344,7 +6946,69 @@ impl App {
                 });
                 self.scroll_messages_to_bottom();
                 self.is_processing = false;
+                self.quick_call = false;
                 self.processing_start = None;
+                self.notify_turn_finished("failed");
+            }
+
+            AppEvent::LlmUsage(mut usage) => {
+                // Providers other than OpenRouter don't report cost; price it from the
+                // model catalog when /model has loaded one.
+                if usage.cost.is_none() {
+                    usage.cost = self
+                        .models
+                        .iter()
+                        .find(|m| m.id == self.call_model)
+                        .and_then(|m| m.cost_of(&usage));
+                }
+                self.session_usage.add(usage);
+                self.turn_timings
+                    .entry(self.current_turn_id)
+                    .or_default()
+                    .usage
+                    .add(usage);
+            }
+
+            AppEvent::LlmTiming(elapsed) => {
+                let timing = self.turn_timings.entry(self.current_turn_id).or_default();
+                timing.llm += elapsed;
+                timing.llm_calls += 1;
+            }
+
+            AppEvent::ModelsLoaded(result) => match result {
+                Ok(models) => {
+                    self.models = models;
+                    self.models_status = if self.models.is_empty() {
+                        Some("The provider returned no models.".to_string())
+                    } else {
+                        None
+                    };
+                    if self.models_query.is_empty() {
+                        self.models_selected = self.current_model_index();
+                    }
+                }
+                Err(e) => self.models_status = Some(format!("Could not list models: {}", e)),
+            },
+            AppEvent::MemoriesLoaded { memories, status } => match memories {
+                Ok(mut memories) => {
+                    sort_memories(&mut memories);
+                    self.memories = memories;
+                    let len = self.filtered_memories().len();
+                    self.memories_selected = self.memories_selected.min(len.saturating_sub(1));
+                    self.memories_status = status.or_else(|| {
+                        self.memories
+                            .is_empty()
+                            .then(|| "No memories stored for this project yet.".to_string())
+                    });
+                }
+                Err(e) => self.memories_status = Some(format!("Could not load memories: {}", e)),
+            },
+
+            AppEvent::ToolApproval(request) => {
+                self.approval_always_armed = false;
+                if let Some(stale) = self.pending_approval.replace(request) {
+                    stale.answer(Approval::Deny);
+                }
             }
 
             AppEvent::ToolStart(ev) => {
@@ -3359,7 +7023,7 @@ impl App {
                 }
 
                 let idx = self.tool_outputs.len();
-                let tool_run = ToolOutput::new(
+                let mut tool_run = ToolOutput::new(
                     ev.call_id.clone(),
                     ev.tool,
                     ev.args_raw,
@@ -3369,6 +7033,9 @@ impl App {
                     turn_id,
                     group_id,
                 );
+                if let Some(store) = &self.session {
+                    tool_run.spill_to(store.tool_output_path(&ev.call_id));
+                }
                 self.tool_outputs.push(tool_run);
                 self.tool_index_by_call_id.insert(ev.call_id, idx);
 
@@ -3390,6 +7057,14 @@ impl App {
                         }
                     }
                 }
+                self.sync_tool_pager();
+            }
+            AppEvent::ToolOutputRaw(ev) => {
+                if let Some(&idx) = self.tool_index_by_call_id.get(&ev.call_id) {
+                    if let Some(t) = self.tool_outputs.get_mut(idx) {
+                        t.append_raw(&ev.chunk);
+                    }
+                }
             }
             AppEvent::ToolComplete(ev) => {
                 let Some(&idx) = self.tool_index_by_call_id.get(&ev.call_id) else {
@@ -3397,8 +7072,19 @@ impl App {
                 };
 
                 let mut snapshot: Option<(String, String, String, bool, u64, u64)> = None;
+                let mut edited: Vec<String> = Vec::new();
+                let mut verify_elapsed = None;
                 if let Some(t) = self.tool_outputs.get_mut(idx) {
                     t.complete(ev.success);
+                    if t.tool == "verify" {
+                        verify_elapsed = Some(t.elapsed());
+                    }
+                    if ev.success {
+                        edited = file_changes(&t.tool, &t.args_raw)
+                            .into_iter()
+                            .map(|(path, _, _)| path)
+                            .collect();
+                    }
                     snapshot = Some((
                         t.tool.clone(),
                         t.target.clone(),
@@ -3433,6 +7119,29 @@ impl App {
 
                 self.refresh_verify_suggestions();
 
+                if let Some(elapsed) = verify_elapsed {
+                    let outcome = if ev.success { "passed" } else { "failed" };
+                    self.notify_finished(
+                        NotifyEvent::Verify,
+                        elapsed,
+                        &format!(
+                            "lorikeet: verify {} ({})",
+                            outcome,
+                            stats::format_duration(elapsed)
+                        ),
+                    );
+                }
+
+                for path in &edited {
+                    *self.file_edits.entry(path.clone()).or_insert(0) += 1;
+                }
+                if !edited.is_empty() && self.file_heatmap_enabled() {
+                    let memory = self.memory.clone();
+                    tokio::spawn(async move {
+                        memory.record_file_edits(&edited).await;
+                    });
+                }
+
                 // Auto-collapse the tool trace group when no tools are running for it.
                 if let Some((tool, target, output, success, _turn_id, group_id)) = snapshot {
                     if group_id > 0 {
@@ -3446,8 +7155,10 @@ impl App {
                     }
 
                     // Tool loop guard: if the same tool keeps failing with the same target in one turn,
-                    // stop retrying and force the user/model to correct course.
-                    if !success {
+                    // stop retrying and force the user/model to correct course. Failures to even
+                    // start the tool are retried automatically and don't count.
+                    let infra_failure = !success && is_infra_failure(&output);
+                    if !success && !infra_failure {
                         let key = (self.current_turn_id, format!("{}|{}", tool, target));
                         let count = self.tool_failure_counts.entry(key).or_insert(0);
                         *count = count.saturating_add(1);
@@ -3467,7 +7178,26 @@ impl App {
                         self.push_recent_file(&target);
                     }
 
-                    if learn_failures {
+                    if tool == "verify" {
+                        self.verify_history.push(success);
+                    }
+                    if tool == "verify" && !success {
+                        let failures = crate::verify::parse_test_failures(&output);
+                        if !failures.is_empty() {
+                            self.push_message(Message {
+                                id: 0,
+                                role: Role::Agent,
+                                content: failing_tests_notice(&failures),
+                                reasoning: None,
+                                tool_calls: None,
+                                tool_group_id: None,
+                                local: true,
+                            });
+                            self.scroll_messages_to_bottom();
+                        }
+                    }
+
+                    if learn_failures && !infra_failure {
                         let memory = self.memory.clone();
                         tokio::spawn(async move {
                             memory
@@ -3483,889 +7213,3566 @@ impl App {
                 self.indexing_status = IndexingStatus::Indexing {
                     files_done: 0,
                     total_files: 0,
+                    current: None,
                 };
             }
-            AppEvent::IndexingProgress(files_done, total_files) => {
+            AppEvent::IndexingProgress(p) => {
                 self.indexing_status = IndexingStatus::Indexing {
-                    files_done,
-                    total_files,
+                    files_done: p.files_done,
+                    total_files: p.total_files,
+                    current: p.current.map(|c| c.display().to_string()),
                 };
             }
             AppEvent::IndexingComplete(chunks, files) => {
                 self.indexing_status = IndexingStatus::Complete { chunks, files };
             }
             AppEvent::IndexingError(err) => {
+                tracing::warn!(error = %err, "indexing failed");
                 self.indexing_status = IndexingStatus::Error(err);
             }
+            AppEvent::RepoMapReady(map) => {
+                self.repo_map = Some(map);
+            }
+            AppEvent::CodexLoginUrl(url) => {
+                self.push_local_note(&format!(
+                    "[Sign in with ChatGPT in your browser. If it didn't open, visit: {}]",
+                    url
+                ));
+            }
+            AppEvent::CodexLoginDone(result) => {
+                self.codex_login_running = false;
+                let note = match result {
+                    Ok(auth) if self.llm_provider == LlmProvider::Codex => {
+                        self.api_key = auth.access_token;
+                        self.codex_account_id = auth.account_id.or(self.codex_account_id.take());
+                        "[Signed in with ChatGPT. Send your message again to continue.]".to_string()
+                    }
+                    Ok(_) => "[Signed in with ChatGPT. Pick the codex provider in /settings to use it.]"
+                        .to_string(),
+                    Err(e) => format!("[Sign-in failed: {}]", e),
+                };
+                self.push_local_note(&note);
+            }
         }
     }
 
-    fn push_recent_file(&mut self, path: &str) {
-        if path.trim().is_empty() {
+    /// Open the next location from the latest search result (semantic_search, rg,
+    /// smart_search) or failed verify run in the file viewer. Repeated presses walk
+    /// through the hits, or the failing tests.
+    fn goto_next_search_result(&mut self) {
+        let found = self
+            .tool_outputs
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, t)| match t.status {
+                ToolStatus::Success => SEARCH_TOOLS.contains(&t.tool.as_str()),
+                ToolStatus::Error => t.tool == "verify",
+                ToolStatus::Running => false,
+            })
+            .find_map(|(i, t)| {
+                let mut locations = if t.tool == "verify" {
+                    crate::verify::parse_test_failures(&t.full_output())
+                        .into_iter()
+                        .filter_map(|f| f.location)
+                        .collect()
+                } else {
+                    search_result_locations(&t.full_output())
+                };
+                locations.retain(|(path, _)| self.active_root.join(path).is_file());
+                (!locations.is_empty()).then_some((i, locations))
+            });
+        let Some((idx, locations)) = found else {
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: "No search results or failing tests to open yet.".to_string(),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
+            });
+            self.scroll_messages_to_bottom();
             return;
-        }
-
-        if let Some(pos) = self.recent_files.iter().position(|p| p == path) {
-            self.recent_files.remove(pos);
-        }
-        self.recent_files.push_front(path.to_string());
-        while self.recent_files.len() > 20 {
-            self.recent_files.pop_back();
-        }
-    }
+        };
 
-    pub fn display_messages(&self) -> impl Iterator<Item = &Message> {
-        self.messages
-            .iter()
-            .filter(|m| m.role != Role::System && m.role != Role::Tool)
+        let next = match self.goto_cursor {
+            Some((i, n)) if i == idx => n % locations.len(),
+            _ => 0,
+        };
+        self.goto_cursor = Some((idx, next + 1));
+        let (path, line) = locations[next].clone();
+        self.open_file_view(&path, line);
     }
 
-    pub fn workspace_root_display(&self) -> String {
-        self.workspace_root.display().to_string()
+    fn open_file_view(&mut self, path: &str, line: usize) {
+        let Some(view) = self.load_file_view(path, line) else {
+            return;
+        };
+        let display = view.path.clone();
+        self.file_view = Some(view);
+        self.push_recent_file(&display);
     }
 
-    pub fn workspace_root_path(&self) -> &std::path::Path {
-        &self.workspace_root
-    }
+    /// Read `path` for a viewer opened at `line` (0 highlights nothing). Reports a
+    /// file that can't be read in the chat.
+    fn load_file_view(&mut self, path: &str, line: usize) -> Option<FileView> {
+        let full = self.active_root.join(path);
+        let content = match std::fs::read_to_string(&full) {
+            Ok(c) => crate::notebook::readable(&full, &c).unwrap_or(c),
+            Err(e) => {
+                self.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: format!("Can't open {}: {}", path, e),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+                self.scroll_messages_to_bottom();
+                return None;
+            }
+        };
 
-    pub fn llm_provider_name(&self) -> &'static str {
-        match self.llm_provider {
-            LlmProvider::OpenRouter => "openrouter",
-            LlmProvider::OpenAI => "openai",
-            LlmProvider::Codex => "codex",
-        }
+        // Show workspace files by their relative path (rg reports absolute ones).
+        let display = full
+            .strip_prefix(&self.active_root)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        Some(FileView {
+            path: display,
+            line,
+            lines: content.lines().map(str::to_string).collect(),
+            // A few lines of context above the hit.
+            scroll: line.saturating_sub(6),
+        })
     }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum PlanFocus {
-    Questions,
-    Answer,
-    Buttons,
-}
-
-#[derive(Debug, Clone)]
-pub(crate) struct PlanQuestion {
-    pub id: String,
-    pub prompt: String,
-    pub kind: PlanQuestionKind,
-    pub options: Vec<String>,
-    pub default: Option<String>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum PlanQuestionKind {
-    Text,
-    Select,
-}
 
-#[derive(Debug, Clone)]
-pub(crate) struct PlanDraft {
-    pub plan_markdown: String,
-    pub questions: Vec<PlanQuestion>,
-    pub answers: HashMap<String, String>,
-}
-
-fn parse_plan_response(raw: &str) -> Result<PlanDraft, String> {
-    fn strip_code_fences(s: &str) -> &str {
-        let t = s.trim();
-        if !t.starts_with("```") {
-            return t;
-        }
-        // Handle ```json\n{...}\n``` or ```\n{...}\n```.
-        let Some(nl) = t.find('\n') else {
-            return t;
+    /// The sidebar lists recent files (rather than a file view) and has focus.
+    /// Paths offered for the `@` mention under the cursor: bookmarks, then recent files.
+    pub fn mention_suggestions(&self) -> Vec<String> {
+        let Some((_, partial)) = bookmarks::mention_at(&self.input, self.cursor_pos) else {
+            return Vec::new();
         };
-        let inner = t[nl + 1..].trim();
-        if let Some(end) = inner.rfind("```") {
-            inner[..end].trim()
-        } else {
-            inner
-        }
+        let recent: Vec<String> = self.recent_files.iter().cloned().collect();
+        bookmarks::mention_candidates(partial, &self.bookmarks, &recent)
     }
 
-    fn extract_json_object(s: &str) -> Option<&str> {
-        let t = s.trim();
-        let start = t.find('{')?;
-        let end = t.rfind('}')?;
-        if end <= start {
-            return None;
-        }
-        Some(&t[start..=end])
+    /// Replace the mention under the cursor with the selected suggestion.
+    fn complete_mention(&mut self) {
+        let suggestions = self.mention_suggestions();
+        let Some((start, _)) = bookmarks::mention_at(&self.input, self.cursor_pos) else {
+            return;
+        };
+        let idx = self
+            .command_suggest_selected
+            .min(suggestions.len().saturating_sub(1));
+        let Some(path) = suggestions.get(idx) else {
+            return;
+        };
+        let completed = format!("@{} ", path);
+        self.input.replace_range(start..self.cursor_pos, &completed);
+        self.cursor_pos = start + completed.len();
+        self.command_suggest_selected = 0;
     }
 
-    let cleaned = strip_code_fences(raw);
-    let value: serde_json::Value = match serde_json::from_str(cleaned) {
-        Ok(v) => v,
-        Err(_) => {
-            let Some(obj) = extract_json_object(cleaned) else {
-                return Err("Expected JSON object response".into());
-            };
-            serde_json::from_str(obj).map_err(|e| format!("Invalid JSON: {e}"))?
-        }
-    };
-
-    let obj = value
-        .as_object()
-        .ok_or_else(|| "Expected top-level JSON object".to_string())?;
+    fn handle_bookmark_command(&mut self, args: &str) {
+        let (action, path) = args.split_once(' ').unwrap_or((args, ""));
+        let notice = match action {
+            "" | "list" => {
+                if self.bookmarks.is_empty() {
+                    "No bookmarks yet. Add one with /bookmark add <path>.".to_string()
+                } else {
+                    format!("Bookmarks:\n- {}", self.bookmarks.join("\n- "))
+                }
+            }
+            "add" => match bookmarks::normalize(&self.workspace_root, path) {
+                Ok(path) if self.bookmarks.contains(&path) => {
+                    format!("Already bookmarked: {}", path)
+                }
+                Ok(path) => {
+                    self.bookmarks.push(path.clone());
+                    match bookmarks::save(&self.workspace_root, &self.bookmarks) {
+                        Ok(()) => format!("Bookmarked {}", path),
+                        Err(e) => format!("Bookmarked {} (not saved: {})", path, e),
+                    }
+                }
+                Err(e) => e,
+            },
+            "remove" | "rm" => {
+                let path = path.trim().trim_start_matches('@').trim_end_matches('/');
+                match self.bookmarks.iter().position(|b| b == path) {
+                    Some(pos) => {
+                        self.bookmarks.remove(pos);
+                        let _ = bookmarks::save(&self.workspace_root, &self.bookmarks);
+                        format!("Removed bookmark {}", path)
+                    }
+                    None => format!("Not bookmarked: {}", path),
+                }
+            }
+            _ => "Usage: /bookmark [list|add <path>|remove <path>]".to_string(),
+        };
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: notice,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
 
-    let plan_markdown = obj
-        .get("plan")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| "Missing required field: plan".to_string())?
-        .to_string();
+    /// `/workspace [list|add <path>|remove <name>|use <name>]`
+    fn handle_workspace_command(&mut self, args: &str) {
+        let (action, arg) = args.split_once(' ').unwrap_or((args, ""));
+        let notice = match action {
+            "" | "list" => {
+                let mut out = String::from("Workspaces:");
+                for root in self.all_roots() {
+                    let marker = if root == self.active_root {
+                        " [active]"
+                    } else {
+                        ""
+                    };
+                    out.push_str(&format!(
+                        "\n- {} ({}){}",
+                        workspaces::name(&root),
+                        root.display(),
+                        marker
+                    ));
+                }
+                if self.workspaces.is_empty() {
+                    out.push_str("\nAdd a related repo with /workspace add <path>.");
+                }
+                out
+            }
+            "add" => match workspaces::normalize(&self.workspace_root, arg) {
+                Ok(root) if self.all_roots().contains(&root) => {
+                    format!("Already a workspace: {}", root.display())
+                }
+                Ok(_) if self.workspaces.len() >= workspaces::MAX_EXTRA_ROOTS => format!(
+                    "At most {} extra workspaces; remove one first.",
+                    workspaces::MAX_EXTRA_ROOTS
+                ),
+                Ok(root) => {
+                    self.workspaces.push(root.clone());
+                    self.rebuild_sandbox_policy();
+                    let saved = workspaces::save(&self.workspace_root, &self.workspaces);
+                    let indexing = self.auto_index_enabled() && !index_file_exists(&root);
+                    if indexing {
+                        self.start_indexing(root.clone());
+                    }
+                    let mut out = format!(
+                        "Added workspace {} ({}). Tools can read and edit it; /workspace use {} makes it the active one.",
+                        workspaces::name(&root),
+                        root.display(),
+                        workspaces::name(&root)
+                    );
+                    if indexing {
+                        out.push_str(" Indexing it in the background.");
+                    }
+                    if let Err(e) = saved {
+                        out.push_str(&format!(" (not saved: {})", e));
+                    }
+                    out
+                }
+                Err(e) => e,
+            },
+            "remove" | "rm" => {
+                match workspaces::find(&self.workspaces, &self.workspace_root, arg).cloned() {
+                    Some(root) => {
+                        self.workspaces.retain(|r| *r != root);
+                        let _ = workspaces::save(&self.workspace_root, &self.workspaces);
+                        let mut out = format!("Removed workspace {}", workspaces::name(&root));
+                        if root == self.active_root {
+                            let main = self.workspace_root.clone();
+                            self.switch_workspace(main);
+                            out.push_str(&format!(
+                                "; back in {}",
+                                workspaces::name(&self.workspace_root)
+                            ));
+                        } else {
+                            self.rebuild_sandbox_policy();
+                        }
+                        out
+                    }
+                    None => format!("Not an extra workspace: {}", arg.trim()),
+                }
+            }
+            "use" | "switch" => {
+                let roots = self.all_roots();
+                match workspaces::find(&roots, &self.workspace_root, arg).cloned() {
+                    Some(root) if root == self.active_root => {
+                        format!("Already in {}", workspaces::name(&root))
+                    }
+                    Some(root) => {
+                        self.switch_workspace(root.clone());
+                        format!(
+                            "Active workspace: {} ({}). Tools now run there.",
+                            workspaces::name(&root),
+                            root.display()
+                        )
+                    }
+                    None => format!("Unknown workspace: {} (see /workspace list)", arg.trim()),
+                }
+            }
+            _ => "Usage: /workspace [list|add <path>|remove <name>|use <name>]".to_string(),
+        };
+        self.push_message(Message {
+            id: 0,
+            role: Role::Agent,
+            content: notice,
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: true,
+        });
+        self.scroll_messages_to_bottom();
+    }
 
-    let mut questions = Vec::new();
-    if let Some(qs) = obj.get("questions").and_then(|v| v.as_array()) {
-        for (idx, qv) in qs.iter().enumerate() {
-            let qobj = match qv.as_object() {
-                Some(o) => o,
-                None => continue,
-            };
-            let id = qobj
-                .get("id")
-                .and_then(|v| v.as_str())
-                .filter(|s| !s.trim().is_empty())
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| format!("q{}", idx + 1));
-            let prompt = qobj
-                .get("prompt")
-                .and_then(|v| v.as_str())
-                .filter(|s| !s.trim().is_empty())
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| id.clone());
+    /// The workspace Lorikeet started in, then the registered extra roots.
+    pub fn all_roots(&self) -> Vec<PathBuf> {
+        let mut roots = vec![self.workspace_root.clone()];
+        roots.extend(self.workspaces.iter().cloned());
+        roots
+    }
 
-            let kind = match qobj.get("type").and_then(|v| v.as_str()) {
-                Some(t) if t.eq_ignore_ascii_case("select") => PlanQuestionKind::Select,
-                _ => PlanQuestionKind::Text,
-            };
+    /// Make `root` the workspace tools run in: the process directory, the sandbox root,
+    /// a fresh repository map and its own index. The persistent shell is restarted by the
+    /// next `bash` call, which sees it was started in another root.
+    fn switch_workspace(&mut self, root: PathBuf) {
+        let _ = std::env::set_current_dir(&root);
+        self.active_root = root;
+        self.rebuild_sandbox_policy();
+        self.repo_map = None;
+        self.indexing_status = IndexingStatus::NotStarted;
+        if self.auto_index_enabled() && !index_file_exists(&self.active_root) {
+            self.start_background_indexing();
+        }
+    }
 
-            let options = qobj
-                .get("options")
-                .and_then(|v| v.as_array())
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
+    fn auto_index_enabled(&self) -> bool {
+        self.config
+            .general
+            .as_ref()
+            .and_then(|g| g.auto_index)
+            .unwrap_or(true)
+    }
 
-            let default = qobj
-                .get("default")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
+    fn recent_files_focused(&self) -> bool {
+        self.active_pane == Pane::Context
+            && self.file_view.is_none()
+            && !self.recent_files.is_empty()
+    }
 
-            questions.push(PlanQuestion {
-                id,
-                prompt,
-                kind,
-                options,
-                default,
-            });
-        }
+    pub fn selected_recent_file(&self) -> Option<&String> {
+        let shown = self.recent_files.len().min(RECENT_FILES_SHOWN);
+        self.recent_files
+            .get(self.recent_selected.min(shown.saturating_sub(1)))
     }
 
-    Ok(PlanDraft {
-        plan_markdown,
-        questions,
-        answers: HashMap::new(),
-    })
-}
+    fn select_recent_file(&mut self, delta: isize) {
+        let max = self
+            .recent_files
+            .len()
+            .min(RECENT_FILES_SHOWN)
+            .saturating_sub(1);
+        self.recent_selected = self.recent_selected.saturating_add_signed(delta).min(max);
+    }
 
-#[derive(Debug, Clone, Copy)]
-enum SettingsFocus {
-    Categories,
-    Items,
-}
+    fn open_recent_file(&mut self) {
+        let Some(path) = self.selected_recent_file().cloned() else {
+            return;
+        };
+        self.recent_file_popup = self.load_file_view(&path, 0);
+    }
 
-#[derive(Debug, Clone, Copy)]
-enum SettingsCategory {
-    General,
-    Appearance,
-    Memory,
-    Sandbox,
-}
+    fn toggle_recent_file_pin(&mut self) {
+        let Some(path) = self.selected_recent_file().cloned() else {
+            return;
+        };
+        if let Some(pos) = self.pinned_files.iter().position(|p| *p == path) {
+            self.pinned_files.remove(pos);
+        } else {
+            self.pinned_files.push(path);
+        }
+    }
 
-impl SettingsCategory {
-    fn label(&self) -> &'static str {
-        match self {
-            SettingsCategory::General => "General",
-            SettingsCategory::Appearance => "Appearance",
-            SettingsCategory::Memory => "Memory",
-            SettingsCategory::Sandbox => "Sandbox",
+    fn handle_recent_file_popup_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Char('p') {
+            self.toggle_recent_file_pin();
+            return;
+        }
+        let Some(view) = self.recent_file_popup.as_mut() else {
+            return;
+        };
+        let max = view.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.recent_file_popup = None,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll = view.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll = (view.scroll + 1).min(max),
+            KeyCode::PageUp => view.scroll = view.scroll.saturating_sub(20),
+            KeyCode::PageDown => view.scroll = (view.scroll + 20).min(max),
+            KeyCode::Home | KeyCode::Char('g') => view.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => view.scroll = max,
+            _ => {}
         }
     }
 
-    fn items(&self) -> &'static [SettingsItem] {
-        match self {
-            SettingsCategory::General => &[
-                SettingsItem::Provider,
-                SettingsItem::Model,
-                SettingsItem::SplitRatio,
-                SettingsItem::AutoIndex,
-                SettingsItem::ResumeLastSession,
-            ],
-            SettingsCategory::Appearance => &[
-                SettingsItem::ThemePreset,
-                SettingsItem::ThemeBackground,
-            ],
-            SettingsCategory::Memory => &[
-                SettingsItem::MemoryEnabled,
-                SettingsItem::MemoryAutoInject,
-                SettingsItem::MemoryAutoLearnFailures,
-                SettingsItem::MemoryAutoLearnUser,
-                SettingsItem::MemoryAutoExtract,
-                SettingsItem::MemoryExtractionModel,
-            ],
-            SettingsCategory::Sandbox => &[
-                SettingsItem::SandboxEnabled,
-                SettingsItem::SandboxRoot,
-                SettingsItem::SandboxAllowPaths,
-                SettingsItem::SandboxDenyPaths,
-                SettingsItem::SandboxAllowCommands,
-                SettingsItem::SandboxAllowTools,
-            ],
+    fn handle_text_popup_key(&mut self, key: KeyEvent) {
+        let Some(view) = self.text_popup.as_mut() else {
+            return;
+        };
+        let max = view.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                self.text_popup = None;
+                self.log_tail = None;
+                return;
+            }
+            KeyCode::Up | KeyCode::Char('k') => view.scroll = view.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll = (view.scroll + 1).min(max),
+            KeyCode::PageUp => view.scroll = view.scroll.saturating_sub(20),
+            KeyCode::PageDown => view.scroll = (view.scroll + 20).min(max),
+            KeyCode::Home | KeyCode::Char('g') => view.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => view.scroll = max,
+            _ => return,
+        }
+        if let Some(tail) = self.log_tail.as_mut() {
+            tail.follow = matches!(key.code, KeyCode::End | KeyCode::Char('G'));
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SettingsItem {
-    Provider,
-    Model,
-    ThemePreset,
-    ThemeBackground,
-    SplitRatio,
-    AutoIndex,
-    ResumeLastSession,
-    SandboxEnabled,
-    SandboxRoot,
-    SandboxAllowPaths,
-    SandboxDenyPaths,
-    SandboxAllowCommands,
-    SandboxAllowTools,
-    MemoryEnabled,
-    MemoryAutoInject,
-    MemoryAutoLearnFailures,
-    MemoryAutoLearnUser,
-    MemoryAutoExtract,
-    MemoryExtractionModel,
-}
+    /// `/logs`: the end of the diagnostics log, following new lines.
+    fn open_log_tail(&mut self) {
+        let Some(path) = crate::logging::log_path().filter(|p| p.exists()) else {
+            self.push_local_note("[No log file yet]");
+            return;
+        };
+        self.text_popup = Some(FileView {
+            path: path.display().to_string(),
+            line: 0,
+            lines: Vec::new(),
+            scroll: 0,
+        });
+        self.log_tail = Some(LogTail {
+            path,
+            len: u64::MAX,
+            follow: true,
+        });
+        self.refresh_log_tail();
+    }
 
-impl SettingsItem {
-    fn label(&self) -> &'static str {
-        match self {
-            SettingsItem::Provider => "Provider",
-            SettingsItem::Model => "Model",
-            SettingsItem::ThemePreset => "Theme",
-            SettingsItem::ThemeBackground => "Theme bg",
-            SettingsItem::SplitRatio => "Split ratio",
-            SettingsItem::AutoIndex => "Auto index",
-            SettingsItem::ResumeLastSession => "Resume last session",
-            SettingsItem::SandboxEnabled => "Sandbox enabled",
-            SettingsItem::SandboxRoot => "Sandbox root",
-            SettingsItem::SandboxAllowPaths => "Sandbox allow paths",
-            SettingsItem::SandboxDenyPaths => "Sandbox deny paths",
-            SettingsItem::SandboxAllowCommands => "Sandbox allow commands",
-            SettingsItem::SandboxAllowTools => "Sandbox allow tools",
-            SettingsItem::MemoryEnabled => "Memory enabled",
-            SettingsItem::MemoryAutoInject => "Memory auto inject",
-            SettingsItem::MemoryAutoLearnFailures => "Memory learn failures",
-            SettingsItem::MemoryAutoLearnUser => "Memory learn user",
-            SettingsItem::MemoryAutoExtract => "Memory auto extract",
-            SettingsItem::MemoryExtractionModel => "Memory extraction model",
-        }
+    /// The frame a spinner with `frames` frames shows now: one step per [`ANIMATION_TICK`].
+    pub fn spinner_frame(&self, frames: usize) -> usize {
+        let ticks = self.spinner_epoch.elapsed().as_millis() / ANIMATION_TICK.as_millis();
+        (ticks % frames.max(1) as u128) as usize
     }
-}
 
-fn parse_bool(input: &str) -> Result<bool, ()> {
-    let v = input.trim().to_lowercase();
-    match v.as_str() {
-        "true" | "1" | "yes" | "y" | "on" => Ok(true),
-        "false" | "0" | "no" | "n" | "off" => Ok(false),
-        _ => Err(()),
+    /// Whether anything on screen changes with time alone, so the main loop has to keep
+    /// ticking instead of sleeping until the next event.
+    pub fn animating(&self) -> bool {
+        self.is_processing
+            || self.log_tail.is_some()
+            || matches!(self.indexing_status, IndexingStatus::Indexing { .. })
+            || self.offline.is_some()
+            || self
+                .tool_outputs
+                .iter()
+                .any(|t| t.status == ToolStatus::Running)
+            || crate::processes::list().iter().any(|p| p.running())
     }
-}
 
-fn split_list(input: &str) -> Vec<String> {
-    input
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect()
-}
+    /// How long the main loop may wait for an event before redrawing: a short tick while
+    /// animating, a slow one while a custom theme file is watched, else until input.
+    pub fn redraw_tick(&self) -> Option<Duration> {
+        if self.animating() {
+            Some(ANIMATION_TICK)
+        } else if self.theme_cache.watches_files() {
+            Some(THEME_POLL)
+        } else {
+            None
+        }
+    }
 
-fn split_paths(input: &str) -> Vec<PathBuf> {
-    input
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(PathBuf::from)
-        .collect()
-}
-
-fn join_paths(paths: &[PathBuf]) -> String {
-    paths
-        .iter()
-        .map(|p| p.display().to_string())
-        .collect::<Vec<_>>()
-        .join(", ")
-}
-
-fn pretty_args_lines(raw: &str) -> Vec<String> {
-    // Avoid spewing huge JSON blobs (e.g. write_file content) into the UI details.
-    // We parse JSON, replace very large strings with a placeholder, and pretty-print.
-    const MAX_STRING: usize = 240;
-
-    fn redact(v: &mut serde_json::Value) {
-        match v {
-            serde_json::Value::String(s) => {
-                if s.len() > MAX_STRING {
-                    *s = format!("<{} chars>", s.len());
-                }
-            }
-            serde_json::Value::Array(arr) => {
-                for x in arr {
-                    redact(x);
-                }
-            }
-            serde_json::Value::Object(map) => {
-                for (_k, x) in map.iter_mut() {
-                    redact(x);
-                }
-            }
-            _ => {}
+    /// Reload the `/logs` popup if the log has grown. Called every frame; a `stat` when
+    /// the popup is open, nothing otherwise.
+    pub fn refresh_log_tail(&mut self) {
+        let (Some(tail), Some(view)) = (self.log_tail.as_mut(), self.text_popup.as_mut()) else {
+            return;
+        };
+        let len = std::fs::metadata(&tail.path).map_or(0, |m| m.len());
+        if len == tail.len {
+            return;
         }
+        tail.len = len;
+        view.lines = crate::logging::tail(&tail.path, LOG_TAIL_LINES);
     }
 
-    let mut v: serde_json::Value = match serde_json::from_str(raw) {
-        Ok(v) => v,
-        Err(_) => return vec![raw.to_string()],
-    };
-    redact(&mut v);
-    match serde_json::to_string_pretty(&v) {
-        Ok(s) => s.lines().map(|l| l.to_string()).collect(),
-        Err(_) => vec![raw.to_string()],
+    /// `/debug last`: the messages of the latest request sent to the model, as sent.
+    fn show_last_request(&mut self) {
+        let Some(last) = llm_debug::last_request() else {
+            self.push_local_note("[No request sent to the model yet]");
+            return;
+        };
+        let mut lines = llm_debug::request_lines(&last);
+        if let Some(log) = llm_debug::log_path() {
+            let note = if llm_debug::enabled() {
+                format!("Logged with its response in {}", log.display())
+            } else {
+                format!(
+                    "Start with --debug-llm to log every request and response to {}",
+                    log.display()
+                )
+            };
+            lines.insert(2, note);
+        }
+        let age = crate::memory::types::unix_ts().saturating_sub(last.ts);
+        self.text_popup = Some(FileView {
+            path: format!("last request, {}s ago", age),
+            line: 0,
+            lines,
+            scroll: 0,
+        });
     }
-}
 
-fn summarize_tool_call(name: &str, args: &serde_json::Value) -> String {
-    fn trunc(s: &str, max: usize) -> String {
-        let t = s.trim();
-        if t.len() <= max {
-            return t.to_string();
+    /// Open the pager on tool call `call_id`, or on the latest one.
+    fn open_tool_pager(&mut self, call_id: Option<String>) {
+        let call_id = call_id.or_else(|| self.tool_outputs.last().map(|t| t.call_id.clone()));
+        let Some(call_id) = call_id else {
+            self.push_local_note("[No tool output yet]");
+            return;
+        };
+        let mut pager = ToolPager::new(call_id);
+        if let Some(old) = &self.tool_pager {
+            pager.wrap = old.wrap;
+            pager.line_numbers = old.line_numbers;
         }
-        let mut out = t.to_string();
-        out.truncate(max.saturating_sub(3));
-        out.push_str("...");
-        out
+        self.tool_pager = Some(pager);
+        self.sync_tool_pager();
     }
 
-    match name {
-        "bash" => {
-            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
-            trunc(cmd, 120)
-        }
-        "verify" => {
-            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
-            if cmd.trim().is_empty() {
-                "auto".to_string()
-            } else {
-                trunc(cmd, 120)
-            }
-        }
-        "rg" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            if q.is_empty() {
-                format!("in {}", path)
-            } else {
-                trunc(&format!("{} in {}", q, path), 120)
-            }
-        }
-        "read_file" | "write_file" | "list_files" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            trunc(path, 140)
-        }
-        "open_at" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            let line = args.get("line").and_then(|v| v.as_u64()).unwrap_or(1);
-            trunc(&format!("{}:{}", path, line), 140)
-        }
-        "apply_patch" => {
-            let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
-            if patch.trim().is_empty() {
-                "patch".to_string()
-            } else {
-                // Keep this short; full patch is visible in details with redaction.
-                "patch".to_string()
-            }
-        }
-        "edit_file" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            let old_str = args
-                .get("old_string")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            if old_str.is_empty() {
-                trunc(path, 140)
-            } else {
-                trunc(
-                    &format!("{} (replace: {})", path, old_str.replace('\n', " ")),
-                    140,
-                )
-            }
+    /// Bring the pager up to date with its tool's output, which only ever grows. New output
+    /// is taken from the in-memory tail; the rest is read from disk.
+    fn sync_tool_pager(&mut self) {
+        let Some(pager) = self.tool_pager.as_mut() else {
+            return;
+        };
+        let Some(tool) = self
+            .tool_outputs
+            .iter()
+            .rev()
+            .find(|t| t.call_id == pager.call_id)
+        else {
+            return;
+        };
+        let new = tool.output_bytes.saturating_sub(pager.synced);
+        if new == 0 {
+            return;
         }
-        "semantic_search" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            trunc(q, 140)
+        if new <= tool.output.len() {
+            pager.append(&tool.output[tool.output.len() - new..]);
+        } else {
+            pager.lines = vec![String::new()];
+            pager.append(&tool.full_output());
         }
-        "smart_search" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            if q.is_empty() {
-                format!("in {}", path)
-            } else {
-                trunc(&format!("{} in {}", q, path), 140)
-            }
+        pager.synced = tool.output_bytes;
+    }
+
+    /// Page through the previous (`delta` -1) or next tool call's output instead.
+    fn step_tool_pager(&mut self, delta: isize) {
+        let Some(pager) = &self.tool_pager else {
+            return;
+        };
+        let next = self
+            .tool_outputs
+            .iter()
+            .position(|t| t.call_id == pager.call_id)
+            .and_then(|pos| pos.checked_add_signed(delta))
+            .and_then(|pos| self.tool_outputs.get(pos))
+            .map(|t| t.call_id.clone());
+        if let Some(call_id) = next {
+            self.open_tool_pager(Some(call_id));
         }
-        "lsp" => {
-            let action = args.get("action").and_then(|v| v.as_str()).unwrap_or("");
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            let line = args.get("line").and_then(|v| v.as_u64());
-            let col = args.get("column").and_then(|v| v.as_u64());
-            let mut s = if let (Some(l), Some(c)) = (line, col) {
-                format!("{} {}:{}:{}", action, path, l, c)
-            } else {
-                format!("{} {}", action, path)
-            };
-            if action == "rename" {
-                if let Some(nn) = args.get("new_name").and_then(|v| v.as_str()) {
-                    if !nn.trim().is_empty() {
-                        s.push_str(&format!(" -> {}", nn.trim()));
-                    }
+    }
+
+    fn handle_tool_pager_key(&mut self, key: KeyEvent) {
+        let Some(pager) = self.tool_pager.as_mut() else {
+            return;
+        };
+        if let Some(prompt) = pager.search_prompt.as_mut() {
+            match key.code {
+                KeyCode::Esc => pager.search_prompt = None,
+                KeyCode::Backspace => {
+                    prompt.pop();
+                }
+                KeyCode::Char(c) => prompt.push(c),
+                KeyCode::Enter => {
+                    let query = pager.search_prompt.take().unwrap_or_default();
+                    pager.query = (!query.is_empty()).then_some(query);
+                    pager.match_line = None;
+                    pager.search_step(true);
                 }
+                _ => {}
             }
-            trunc(&s, 160)
+            return;
         }
-        "memory_recall" => {
-            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
-            trunc(&format!("recall: {}", q), 140)
+        let page = pager.page.max(1) as isize;
+        match key.code {
+            KeyCode::Esc if pager.query.is_some() => {
+                pager.query = None;
+                pager.match_line = None;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.tool_pager = None,
+            KeyCode::Up | KeyCode::Char('k') => pager.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => pager.scroll_by(1),
+            KeyCode::PageUp | KeyCode::Char('b') => pager.scroll_by(-page),
+            KeyCode::PageDown | KeyCode::Char(' ') => pager.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => pager.scroll_by(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => pager.scroll_by(isize::MAX),
+            KeyCode::Char('w') => pager.wrap = !pager.wrap,
+            KeyCode::Char('l') => pager.line_numbers = !pager.line_numbers,
+            KeyCode::Char('/') => pager.search_prompt = Some(String::new()),
+            KeyCode::Char('n') => pager.search_step(true),
+            KeyCode::Char('N') => pager.search_step(false),
+            KeyCode::Char('[') => self.step_tool_pager(-1),
+            KeyCode::Char(']') => self.step_tool_pager(1),
+            _ => {}
         }
-        "memory_save" => {
-            let t = args.get("type").and_then(|v| v.as_str()).unwrap_or("fact");
-            let c = args.get("content").and_then(|v| v.as_str()).unwrap_or("");
-            trunc(&format!("save({}): {}", t, c), 140)
+    }
+
+    /// Content of the pinned files for the next LLM call, which unpins them.
+    fn take_pinned_context(&mut self) -> Option<String> {
+        if self.pinned_files.is_empty() {
+            return None;
         }
-        "memory_list" => "list".to_string(),
-        "memory_forget" => {
-            let id = args.get("id").and_then(|v| v.as_str()).unwrap_or("");
-            trunc(&format!("forget: {}", id), 140)
+        let mut out =
+            String::from("[Pinned files]\nThe user pinned these files for this request:\n");
+        for path in std::mem::take(&mut self.pinned_files) {
+            let full = self.active_root.join(&path);
+            let content = match std::fs::read_to_string(&full) {
+                Ok(c) => {
+                    let c = crate::notebook::readable(&full, &c).unwrap_or(c);
+                    truncate_at_boundaries(&c, &full, MAX_PINNED_FILE_BYTES)
+                }
+                Err(e) => format!("(unreadable: {})", e),
+            };
+            out.push_str(&format!("\n--- {} ---\n{}\n", path, content.trim_end()));
         }
-        _ => trunc(&args.to_string(), 120),
+        Some(out)
     }
-}
 
-fn sandbox_decision_for_tool(
-    name: &str,
-    args: &serde_json::Value,
-    policy: &SandboxPolicy,
-) -> crate::sandbox::SandboxDecision {
-    use crate::sandbox::SandboxDecision;
-    use std::path::Path;
+    fn scroll_file_view(&mut self, delta: isize) {
+        if let Some(view) = self.file_view.as_mut() {
+            let max = view.lines.len().saturating_sub(1);
+            view.scroll = view.scroll.saturating_add_signed(delta).min(max);
+        }
+    }
 
-    if let Err(e) = policy.check_tool_allowed(name) {
-        return SandboxDecision::deny(e.to_string());
+    pub fn file_heatmap_enabled(&self) -> bool {
+        let memory = self.config.memory.as_ref();
+        memory.and_then(|m| m.enabled).unwrap_or(true)
+            && memory.and_then(|m| m.file_heatmap).unwrap_or(false)
     }
 
-    match name {
-        "bash" => {
-            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
-            if let Err(e) = policy.check_command_allowed(cmd) {
-                return SandboxDecision::deny(e.to_string());
-            }
-            if let Err(e) = policy.check_bash_paths(cmd) {
-                return SandboxDecision::deny(e.to_string());
-            }
-            SandboxDecision::allow()
+    /// Run the memory consolidation pass in the background (a no-op if it already ran
+    /// today).
+    pub fn start_memory_consolidation(&self) {
+        let cfg = self.config.memory.as_ref();
+        if !cfg.and_then(|m| m.enabled).unwrap_or(true)
+            || !cfg.and_then(|m| m.consolidate).unwrap_or(true)
+        {
+            return;
         }
-        "verify" => {
-            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
-            // If omitted, tool will auto-detect; allow the tool to decide at runtime.
-            if cmd.trim().is_empty() {
-                return SandboxDecision::allow();
-            }
-            if let Err(e) = policy.check_command_allowed(cmd) {
-                return SandboxDecision::deny(e.to_string());
-            }
-            if let Err(e) = policy.check_bash_paths(cmd) {
-                return SandboxDecision::deny(e.to_string());
+        let defaults = ConsolidationPolicy::default();
+        let policy = ConsolidationPolicy {
+            decay_after_days: cfg
+                .and_then(|m| m.decay_after_days)
+                .unwrap_or(defaults.decay_after_days),
+            archive_below: cfg
+                .and_then(|m| m.archive_below)
+                .unwrap_or(defaults.archive_below),
+            ..defaults
+        };
+        let memory = self.memory.clone();
+        tokio::spawn(async move {
+            if let Err(e) = memory.consolidate(&policy).await {
+                tracing::warn!(error = %e, "memory consolidation failed");
             }
-            SandboxDecision::allow()
+        });
+    }
+
+    /// Files by edit count this session, then by earlier sessions' counts, as
+    /// (path, this session, before). Repeated edits to one file often mean the agent is
+    /// thrashing.
+    pub fn most_touched_files(&self, limit: usize) -> Vec<(String, usize, u64)> {
+        let mut files: Vec<(String, usize, u64)> = self
+            .file_edits
+            .keys()
+            .chain(self.file_edits_before.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .map(|path| {
+                let now = self.file_edits.get(path).copied().unwrap_or(0);
+                let before = self.file_edits_before.get(path).copied().unwrap_or(0);
+                (path.clone(), now, before)
+            })
+            .collect();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)));
+        files.truncate(limit);
+        files
+    }
+
+    fn push_recent_file(&mut self, path: &str) {
+        if path.trim().is_empty() {
+            return;
         }
-        "rg" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            match policy.check_path_allowed(Path::new(path)) {
-                Ok(_) => SandboxDecision::allow(),
-                Err(e) => SandboxDecision::deny(e.to_string()),
-            }
+
+        if let Some(pos) = self.recent_files.iter().position(|p| p == path) {
+            self.recent_files.remove(pos);
         }
-        "open_at" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            match policy.check_path_allowed(Path::new(path)) {
-                Ok(_) => SandboxDecision::allow(),
-                Err(e) => SandboxDecision::deny(e.to_string()),
-            }
+        self.recent_files.push_front(path.to_string());
+        while self.recent_files.len() > 20 {
+            self.recent_files.pop_back();
         }
-        "smart_search" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
-            match policy.check_path_allowed(Path::new(path)) {
+    }
+
+    pub fn display_messages(&self) -> impl Iterator<Item = &Message> {
+        self.messages
+            .iter()
+            .filter(|m| m.role != Role::System && m.role != Role::Tool)
+    }
+
+    pub fn workspace_root_display(&self) -> String {
+        self.workspace_root.display().to_string()
+    }
+
+    pub fn workspace_root_path(&self) -> &std::path::Path {
+        &self.workspace_root
+    }
+
+    /// The active theme, picking up edits to its theme file.
+    pub fn ui_theme(&mut self) -> UiTheme {
+        let theme = self
+            .theme_cache
+            .get(&self.config, Some(&self.workspace_root));
+        if let Some(err) = self.theme_cache.take_error() {
+            self.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: format!("[Theme not reloaded: {}]", err),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: true,
+            });
+            self.scroll_messages_to_bottom();
+        }
+        theme
+    }
+
+    pub fn llm_provider_name(&self) -> &'static str {
+        self.llm_provider.name()
+    }
+}
+
+/// A turn waiting out a provider outage, to be resent when its countdown runs out.
+#[derive(Debug, Clone)]
+pub struct Offline {
+    pub error: AgentError,
+    /// Sends that found the provider unreachable
+    pub attempts: u32,
+    pub since: Instant,
+    /// When the next resend goes out; `None` while one is in flight.
+    pub retry_at: Option<Instant>,
+}
+
+/// A turn held at an autonomy limit, waiting for the user to continue or stop it.
+#[derive(Debug, Clone)]
+pub struct TurnLimitPrompt {
+    /// The limit it reached, e.g. "40 tool rounds".
+    pub limit: String,
+    pub rounds: u32,
+    pub elapsed: Duration,
+    /// Files changed so far this turn
+    pub files: Vec<String>,
+}
+
+/// A `/restore` awaiting confirmation.
+#[derive(Debug, Clone)]
+pub struct RestorePrompt {
+    pub meta: checkpoints::CheckpointMeta,
+    /// What the restore would change, or why that couldn't be worked out
+    pub preview: Result<checkpoints::RestorePreview, String>,
+    pub scroll: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PlanFocus {
+    Questions,
+    Answer,
+    Buttons,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlanQuestion {
+    pub id: String,
+    pub prompt: String,
+    pub kind: PlanQuestionKind,
+    pub options: Vec<String>,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanQuestionKind {
+    Text,
+    Select,
+}
+
+/// The latest plan-mode reply plus the user's answers; persisted in the session log.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PlanDraft {
+    pub plan_markdown: String,
+    pub questions: Vec<PlanQuestion>,
+    pub answers: HashMap<String, String>,
+    /// 1-based numbers of steps that finished executing.
+    #[serde(default)]
+    pub completed_steps: Vec<usize>,
+}
+
+impl PlanDraft {
+    /// The plan's top-level numbered items, each with its indented detail lines.
+    pub fn steps(&self) -> Vec<String> {
+        plan_steps(&self.plan_markdown)
+    }
+
+    /// Step numbers not completed yet, in order.
+    pub fn pending_steps(&self) -> Vec<usize> {
+        (1..=self.steps().len())
+            .filter(|n| !self.completed_steps.contains(n))
+            .collect()
+    }
+}
+
+/// Split plan markdown into its top-level numbered items (`1.` or `1)`).
+fn plan_steps(markdown: &str) -> Vec<String> {
+    static STEP_RE: OnceLock<Regex> = OnceLock::new();
+    let re = STEP_RE.get_or_init(|| Regex::new(r"^\d+[.)]\s+(.+)$").unwrap());
+
+    let mut steps: Vec<String> = Vec::new();
+    for line in markdown.lines() {
+        if let Some(caps) = re.captures(line) {
+            steps.push(caps[1].trim().to_string());
+        } else if let Some(step) = steps.last_mut() {
+            // Indented lines (sub-bullets, wrapped text) belong to the step above.
+            if line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+                step.push('\n');
+                step.push_str(line);
+            }
+        }
+    }
+    steps
+}
+
+fn parse_plan_response(raw: &str) -> Result<PlanDraft, String> {
+    fn strip_code_fences(s: &str) -> &str {
+        let t = s.trim();
+        if !t.starts_with("```") {
+            return t;
+        }
+        // Handle ```json\n{...}\n``` or ```\n{...}\n```.
+        let Some(nl) = t.find('\n') else {
+            return t;
+        };
+        let inner = t[nl + 1..].trim();
+        if let Some(end) = inner.rfind("```") {
+            inner[..end].trim()
+        } else {
+            inner
+        }
+    }
+
+    fn extract_json_object(s: &str) -> Option<&str> {
+        let t = s.trim();
+        let start = t.find('{')?;
+        let end = t.rfind('}')?;
+        if end <= start {
+            return None;
+        }
+        Some(&t[start..=end])
+    }
+
+    let cleaned = strip_code_fences(raw);
+    let value: serde_json::Value = match serde_json::from_str(cleaned) {
+        Ok(v) => v,
+        Err(_) => {
+            let Some(obj) = extract_json_object(cleaned) else {
+                return Err("Expected JSON object response".into());
+            };
+            serde_json::from_str(obj).map_err(|e| format!("Invalid JSON: {e}"))?
+        }
+    };
+
+    let obj = value
+        .as_object()
+        .ok_or_else(|| "Expected top-level JSON object".to_string())?;
+
+    let plan_markdown = obj
+        .get("plan")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing required field: plan".to_string())?
+        .to_string();
+
+    let mut questions = Vec::new();
+    if let Some(qs) = obj.get("questions").and_then(|v| v.as_array()) {
+        for (idx, qv) in qs.iter().enumerate() {
+            let qobj = match qv.as_object() {
+                Some(o) => o,
+                None => continue,
+            };
+            let id = qobj
+                .get("id")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("q{}", idx + 1));
+            let prompt = qobj
+                .get("prompt")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| id.clone());
+
+            let kind = match qobj.get("type").and_then(|v| v.as_str()) {
+                Some(t) if t.eq_ignore_ascii_case("select") => PlanQuestionKind::Select,
+                _ => PlanQuestionKind::Text,
+            };
+
+            let options = qobj
+                .get("options")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            let default = qobj
+                .get("default")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+
+            questions.push(PlanQuestion {
+                id,
+                prompt,
+                kind,
+                options,
+                default,
+            });
+        }
+    }
+
+    Ok(PlanDraft {
+        plan_markdown,
+        questions,
+        answers: HashMap::new(),
+        completed_steps: Vec::new(),
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SettingsFocus {
+    Categories,
+    Items,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SettingsCategory {
+    General,
+    Appearance,
+    Memory,
+    Sandbox,
+    Verify,
+}
+
+impl SettingsCategory {
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsCategory::General => "General",
+            SettingsCategory::Appearance => "Appearance",
+            SettingsCategory::Memory => "Memory",
+            SettingsCategory::Sandbox => "Sandbox",
+            SettingsCategory::Verify => "Verify",
+        }
+    }
+
+    fn items(&self) -> Vec<SettingsItem> {
+        match self {
+            SettingsCategory::General => vec![
+                SettingsItem::Provider,
+                SettingsItem::Model,
+                SettingsItem::SplitRatio,
+                SettingsItem::AutoIndex,
+                SettingsItem::ResumeLastSession,
+                SettingsItem::DefaultMode,
+            ],
+            SettingsCategory::Appearance => vec![
+                SettingsItem::ThemePreset,
+                SettingsItem::ThemeBackground,
+            ],
+            SettingsCategory::Memory => vec![
+                SettingsItem::MemoryEnabled,
+                SettingsItem::MemoryAutoInject,
+                SettingsItem::MemoryAutoLearnFailures,
+                SettingsItem::MemoryAutoLearnUser,
+                SettingsItem::MemoryAutoExtract,
+                SettingsItem::MemoryExtractionModel,
+            ],
+            SettingsCategory::Sandbox => vec![
+                SettingsItem::SandboxEnabled,
+                SettingsItem::SandboxRoot,
+                SettingsItem::SandboxAllowPaths,
+                SettingsItem::SandboxDenyPaths,
+                SettingsItem::SandboxAllowCommands,
+                SettingsItem::SandboxAllowTools,
+            ],
+            // One on/off row per tool after the command lists.
+            SettingsCategory::Verify => [SettingsItem::VerifyCommands, SettingsItem::VerifyPipeline]
+                .into_iter()
+                .chain(crate::tool_registry::names().into_iter().map(SettingsItem::Tool))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingsItem {
+    Provider,
+    Model,
+    ThemePreset,
+    ThemeBackground,
+    SplitRatio,
+    AutoIndex,
+    ResumeLastSession,
+    DefaultMode,
+    SandboxEnabled,
+    SandboxRoot,
+    SandboxAllowPaths,
+    SandboxDenyPaths,
+    SandboxAllowCommands,
+    SandboxAllowTools,
+    MemoryEnabled,
+    MemoryAutoInject,
+    MemoryAutoLearnFailures,
+    MemoryAutoLearnUser,
+    MemoryAutoExtract,
+    MemoryExtractionModel,
+    VerifyCommands,
+    VerifyPipeline,
+    /// Whether a tool is in `sandbox.allow_tools`.
+    Tool(&'static str),
+}
+
+impl SettingsItem {
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsItem::Provider => "Provider",
+            SettingsItem::Model => "Model",
+            SettingsItem::ThemePreset => "Theme",
+            SettingsItem::ThemeBackground => "Theme bg",
+            SettingsItem::SplitRatio => "Split ratio",
+            SettingsItem::AutoIndex => "Auto index",
+            SettingsItem::ResumeLastSession => "Resume last session",
+            SettingsItem::DefaultMode => "Default mode",
+            SettingsItem::SandboxEnabled => "Sandbox enabled",
+            SettingsItem::SandboxRoot => "Sandbox root",
+            SettingsItem::SandboxAllowPaths => "Sandbox allow paths",
+            SettingsItem::SandboxDenyPaths => "Sandbox deny paths",
+            SettingsItem::SandboxAllowCommands => "Sandbox allow commands",
+            SettingsItem::SandboxAllowTools => "Sandbox allow tools",
+            SettingsItem::MemoryEnabled => "Memory enabled",
+            SettingsItem::MemoryAutoInject => "Memory auto inject",
+            SettingsItem::MemoryAutoLearnFailures => "Memory learn failures",
+            SettingsItem::MemoryAutoLearnUser => "Memory learn user",
+            SettingsItem::MemoryAutoExtract => "Memory auto extract",
+            SettingsItem::MemoryExtractionModel => "Memory extraction model",
+            SettingsItem::VerifyCommands => "Verify commands",
+            SettingsItem::VerifyPipeline => "Verify pipeline",
+            SettingsItem::Tool(name) => *name,
+        }
+    }
+
+    /// Dotted config key, for attributing the value to a config layer
+    fn config_key(&self) -> &'static str {
+        match self {
+            SettingsItem::Provider => "general.provider",
+            SettingsItem::Model => "general.model",
+            SettingsItem::ThemePreset => "theme.preset",
+            SettingsItem::ThemeBackground => "theme.background",
+            SettingsItem::SplitRatio => "general.split_ratio",
+            SettingsItem::AutoIndex => "general.auto_index",
+            SettingsItem::ResumeLastSession => "general.resume_last",
+            SettingsItem::DefaultMode => "general.default_mode",
+            SettingsItem::SandboxEnabled => "sandbox.enabled",
+            SettingsItem::SandboxRoot => "sandbox.root",
+            SettingsItem::SandboxAllowPaths => "sandbox.allow_paths",
+            SettingsItem::SandboxDenyPaths => "sandbox.deny_paths",
+            SettingsItem::SandboxAllowCommands => "sandbox.allow_commands",
+            SettingsItem::SandboxAllowTools => "sandbox.allow_tools",
+            SettingsItem::MemoryEnabled => "memory.enabled",
+            SettingsItem::MemoryAutoInject => "memory.auto_inject",
+            SettingsItem::MemoryAutoLearnFailures => "memory.auto_learn_failures",
+            SettingsItem::MemoryAutoLearnUser => "memory.auto_learn_user",
+            SettingsItem::MemoryAutoExtract => "memory.auto_extract",
+            SettingsItem::MemoryExtractionModel => "memory.extraction_model",
+            SettingsItem::VerifyCommands => "verify.commands",
+            SettingsItem::VerifyPipeline => "verify.pipeline",
+            SettingsItem::Tool(_) => "sandbox.allow_tools",
+        }
+    }
+}
+
+fn parse_bool(input: &str) -> Result<bool, ()> {
+    let v = input.trim().to_lowercase();
+    match v.as_str() {
+        "true" | "1" | "yes" | "y" | "on" => Ok(true),
+        "false" | "0" | "no" | "n" | "off" => Ok(false),
+        _ => Err(()),
+    }
+}
+
+fn split_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Commands may contain commas, so command lists are separated by `;`.
+fn split_commands(input: &str) -> Vec<String> {
+    input
+        .split(';')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Move the `;`-separated entry under `cursor` one place earlier (or later). Returns the
+/// new text and the cursor at the start of the moved entry, or `None` at either end.
+fn move_command_entry(input: &str, cursor: usize, earlier: bool) -> Option<(String, usize)> {
+    let mut entries = split_commands(input);
+    // The cursor's entry is the number of non-empty segments that end before it.
+    let mut idx = 0;
+    let mut start = 0;
+    for segment in input.split(';') {
+        let end = start + segment.len();
+        if cursor <= end {
+            break;
+        }
+        if !segment.trim().is_empty() {
+            idx += 1;
+        }
+        start = end + 1;
+    }
+    let target = if earlier {
+        idx.checked_sub(1)?
+    } else {
+        idx + 1
+    };
+    if idx >= entries.len() || target >= entries.len() {
+        return None;
+    }
+    entries.swap(idx, target);
+    let cursor = entries[..target].iter().map(|e| e.len() + 2).sum();
+    Some((entries.join("; "), cursor))
+}
+
+/// Problems that would make the verify and tool settings misbehave, checked before the
+/// settings popup saves.
+fn validate_verify_settings(
+    draft: &AppConfig,
+    workspace_root: &std::path::Path,
+) -> Result<(), String> {
+    let verify = draft.verify.clone().unwrap_or_default();
+    let policy = SandboxPolicy::from_config(
+        draft.clone(),
+        workspace_root.to_path_buf(),
+        &crate::tool_registry::names(),
+    );
+    // /verify runs commands regardless of the sandbox, but the verify tool runs the
+    // pipeline under it.
+    for (label, list, sandboxed) in [
+        ("Verify commands", verify.commands, false),
+        ("Verify pipeline", verify.pipeline, true),
+    ] {
+        let list = list.unwrap_or_default();
+        for (i, cmd) in list.iter().enumerate() {
+            if list[..i].contains(cmd) {
+                return Err(format!("{}: `{}` is listed twice", label, cmd));
+            }
+            if sandboxed {
+                if let Err(e) = policy.check_command_allowed(cmd) {
+                    return Err(format!("{}: `{}`: {}", label, cmd, e));
+                }
+            }
+        }
+    }
+    let tools = draft
+        .sandbox
+        .as_ref()
+        .and_then(|s| s.allow_tools.clone())
+        .unwrap_or_default();
+    if let Some(unknown) = tools
+        .iter()
+        .find(|t| !crate::tool_registry::names().contains(&t.as_str()))
+    {
+        return Err(format!("Unknown tool `{}` in allow tools", unknown));
+    }
+    Ok(())
+}
+
+fn split_paths(input: &str) -> Vec<PathBuf> {
+    input
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn pretty_args_lines(raw: &str) -> Vec<String> {
+    // Avoid spewing huge JSON blobs (e.g. write_file content) into the UI details.
+    // We parse JSON, replace very large strings with a placeholder, and pretty-print.
+    const MAX_STRING: usize = 240;
+
+    fn redact(v: &mut serde_json::Value) {
+        match v {
+            serde_json::Value::String(s) => {
+                if s.len() > MAX_STRING {
+                    *s = format!("<{} chars>", s.len());
+                }
+            }
+            serde_json::Value::Array(arr) => {
+                for x in arr {
+                    redact(x);
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for (_k, x) in map.iter_mut() {
+                    redact(x);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut v: serde_json::Value = match serde_json::from_str(raw) {
+        Ok(v) => v,
+        Err(_) => return vec![raw.to_string()],
+    };
+    redact(&mut v);
+    match serde_json::to_string_pretty(&v) {
+        Ok(s) => s.lines().map(|l| l.to_string()).collect(),
+        Err(_) => vec![raw.to_string()],
+    }
+}
+
+/// The `ToolStart` event for a tool run outside a model turn (`/verify`, `lorikeet exec`).
+pub fn tool_start_event(
+    name: &str,
+    args_raw: &str,
+    call_id: &str,
+    policy: &SandboxPolicy,
+) -> crate::events::ToolStartEvent {
+    let args: serde_json::Value =
+        serde_json::from_str(args_raw).unwrap_or_else(|_| serde_json::json!({}));
+    crate::events::ToolStartEvent {
+        call_id: call_id.to_string(),
+        tool: name.to_string(),
+        args_raw: args_raw.to_string(),
+        args_summary: summarize_tool_call(name, &args),
+        cwd: policy.root.clone(),
+        sandbox: sandbox_decision_for_tool(name, &args, policy),
+    }
+}
+
+fn summarize_tool_call(name: &str, args: &serde_json::Value) -> String {
+    fn trunc(s: &str, max: usize) -> String {
+        let t = s.trim();
+        if t.len() <= max {
+            return t.to_string();
+        }
+        let mut out = t.to_string();
+        out.truncate(max.saturating_sub(3));
+        out.push_str("...");
+        out
+    }
+
+    match name {
+        "bash" | "run_background" => {
+            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
+            trunc(cmd, 120)
+        }
+        "process_status" | "kill_process" => match args.get("id") {
+            Some(id) => format!("#{}", id),
+            None => "all".to_string(),
+        },
+        "verify" => {
+            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
+            if cmd.trim().is_empty() {
+                "pipeline".to_string()
+            } else {
+                trunc(cmd, 120)
+            }
+        }
+        "rg" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            if q.is_empty() {
+                format!("in {}", path)
+            } else {
+                trunc(&format!("{} in {}", q, path), 120)
+            }
+        }
+        "read_file" | "write_file" | "list_files" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            trunc(path, 140)
+        }
+        "open_at" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            let line = args.get("line").and_then(|v| v.as_u64()).unwrap_or(1);
+            trunc(&format!("{}:{}", path, line), 140)
+        }
+        "github" => {
+            let action = args.get("action").and_then(|v| v.as_str()).unwrap_or("?");
+            let target = match args.get("number") {
+                Some(serde_json::Value::String(n)) => format!(" #{}", n.trim_start_matches('#')),
+                Some(n) => format!(" #{}", n),
+                None => String::new(),
+            };
+            trunc(&format!("{}{}", action, target), 140)
+        }
+        "read_tool_output" => {
+            let id = args.get("call_id").and_then(|v| v.as_str()).unwrap_or("?");
+            let start = args.get("start_line").and_then(|v| v.as_u64()).unwrap_or(1);
+            match args.get("end_line").and_then(|v| v.as_u64()) {
+                Some(end) => trunc(&format!("{} lines {}-{}", id, start, end), 140),
+                None => trunc(&format!("{} from line {}", id, start), 140),
+            }
+        }
+        "apply_patch" => {
+            let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
+            if patch.trim().is_empty() {
+                "patch".to_string()
+            } else {
+                // Keep this short; full patch is visible in details with redaction.
+                "patch".to_string()
+            }
+        }
+        "edit_file" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let old_str = args
+                .get("old_string")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            if old_str.is_empty() {
+                trunc(path, 140)
+            } else {
+                trunc(
+                    &format!("{} (replace: {})", path, old_str.replace('\n', " ")),
+                    140,
+                )
+            }
+        }
+        "semantic_search" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            trunc(q, 140)
+        }
+        "find_symbol" => {
+            let name = args.get("name").and_then(|v| v.as_str()).unwrap_or("");
+            match args.get("prefix").and_then(|v| v.as_bool()) {
+                Some(true) => trunc(&format!("{}*", name), 140),
+                _ => trunc(name, 140),
+            }
+        }
+        "smart_search" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            if q.is_empty() {
+                format!("in {}", path)
+            } else {
+                trunc(&format!("{} in {}", q, path), 140)
+            }
+        }
+        "lsp" => {
+            let action = args.get("action").and_then(|v| v.as_str()).unwrap_or("");
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let line = args.get("line").and_then(|v| v.as_u64());
+            let col = args.get("column").and_then(|v| v.as_u64());
+            let mut s = if let (Some(l), Some(c)) = (line, col) {
+                format!("{} {}:{}:{}", action, path, l, c)
+            } else {
+                format!("{} {}", action, path)
+            };
+            if action == "rename" {
+                if let Some(nn) = args.get("new_name").and_then(|v| v.as_str()) {
+                    if !nn.trim().is_empty() {
+                        s.push_str(&format!(" -> {}", nn.trim()));
+                    }
+                }
+            }
+            trunc(&s, 160)
+        }
+        "memory_recall" => {
+            let q = args.get("query").and_then(|v| v.as_str()).unwrap_or("");
+            trunc(&format!("recall: {}", q), 140)
+        }
+        "memory_save" => {
+            let t = args.get("type").and_then(|v| v.as_str()).unwrap_or("fact");
+            let c = args.get("content").and_then(|v| v.as_str()).unwrap_or("");
+            trunc(&format!("save({}): {}", t, c), 140)
+        }
+        "memory_list" => "list".to_string(),
+        "memory_forget" => {
+            let id = args.get("id").and_then(|v| v.as_str()).unwrap_or("");
+            trunc(&format!("forget: {}", id), 140)
+        }
+        _ => trunc(&args.to_string(), 120),
+    }
+}
+
+/// Rough token count for thresholds: about four characters per token.
+fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Condenses long tool results with a cheap model before they go into the conversation.
+struct ToolSummarizer {
+    provider: LlmProvider,
+    api_key: String,
+    model: String,
+    threshold_tokens: usize,
+}
+
+impl ToolSummarizer {
+    fn wants(&self, tool: &str, result: &str) -> bool {
+        SUMMARIZED_TOOLS.contains(&tool) && estimate_tokens(result) > self.threshold_tokens
+    }
+
+    /// The result the model gets: a summary when it's long enough to be worth one, else
+    /// (or when summarizing fails) the result itself. The trace still has everything.
+    async fn condense(
+        &self,
+        tool: &str,
+        call_id: &str,
+        result: String,
+        tx: &mpsc::UnboundedSender<AppEvent>,
+    ) -> String {
+        if !self.wants(tool, &result) {
+            return result;
+        }
+        let summary = crate::llm::summarize_tool_output(
+            self.provider,
+            self.api_key.clone(),
+            self.model.clone(),
+            tool,
+            &result,
+        )
+        .await;
+        let Ok(summary) = summary else {
+            return result;
+        };
+        let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+            call_id: call_id.to_string(),
+            chunk: format!(
+                "\n[sent to the model as a {}-token summary by {}]\n",
+                estimate_tokens(&summary),
+                self.model
+            ),
+        }));
+        format!(
+            "[Summary of {} tokens of `{}` output; the user can see the full text]\n{}",
+            estimate_tokens(&result),
+            tool,
+            summary
+        )
+    }
+}
+
+fn sandbox_decision_for_tool(
+    name: &str,
+    args: &serde_json::Value,
+    policy: &SandboxPolicy,
+) -> crate::sandbox::SandboxDecision {
+    use crate::sandbox::SandboxDecision;
+    use std::path::Path;
+
+    if let Err(e) = policy.check_tool_allowed(name) {
+        return SandboxDecision::deny(e.to_string());
+    }
+
+    match name {
+        "bash" | "run_background" => {
+            let cmd = args.get("command").and_then(|v| v.as_str()).unwrap_or("");
+            if let Err(e) = policy.check_read_only_command(cmd) {
+                return SandboxDecision::deny(e.to_string());
+            }
+            if let Err(e) = policy.check_command_allowed(cmd) {
+                return SandboxDecision::deny(e.to_string());
+            }
+            if let Err(e) = policy.check_bash_paths(cmd) {
+                return SandboxDecision::deny(e.to_string());
+            }
+            SandboxDecision::allow()
+        }
+        "verify" => {
+            // Without a command this checks every stage the tool would run.
+            let cmd = crate::tools::command_arg(args, "command");
+            match crate::tools::verify_plan(&cmd, &policy.root, policy) {
+                Ok(_) => SandboxDecision::allow(),
+                Err(e) => SandboxDecision::deny(e),
+            }
+        }
+        "rg" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            match policy.check_path_allowed(Path::new(path)) {
+                Ok(_) => SandboxDecision::allow(),
+                Err(e) => SandboxDecision::deny(e.to_string()),
+            }
+        }
+        "open_at" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            match policy.check_path_allowed(Path::new(path)) {
+                Ok(_) => SandboxDecision::allow(),
+                Err(e) => SandboxDecision::deny(e.to_string()),
+            }
+        }
+        "smart_search" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+            match policy.check_path_allowed(Path::new(path)) {
+                Ok(_) => SandboxDecision::allow(),
+                Err(e) => SandboxDecision::deny(e.to_string()),
+            }
+        }
+        "read_file" | "write_file" | "list_files" | "edit_file" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            match policy.check_path_allowed(Path::new(path)) {
                 Ok(_) => SandboxDecision::allow(),
                 Err(e) => SandboxDecision::deny(e.to_string()),
             }
-        }
-        "read_file" | "write_file" | "list_files" | "edit_file" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            match policy.check_path_allowed(Path::new(path)) {
-                Ok(_) => SandboxDecision::allow(),
-                Err(e) => SandboxDecision::deny(e.to_string()),
+        }
+        "apply_patch" => {
+            // Best-effort: scan for paths in the patch headers and validate them.
+            let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
+            for line in patch.lines() {
+                let path = if let Some(p) = line.strip_prefix("*** Add File: ") {
+                    Some(p.trim())
+                } else if let Some(p) = line.strip_prefix("*** Update File: ") {
+                    Some(p.trim())
+                } else if let Some(p) = line.strip_prefix("*** Delete File: ") {
+                    Some(p.trim())
+                } else {
+                    None
+                };
+                if let Some(p) = path {
+                    if let Err(e) = policy.check_path_allowed(Path::new(p)) {
+                        return SandboxDecision::deny(e.to_string());
+                    }
+                }
+            }
+            SandboxDecision::allow()
+        }
+        "lsp" => {
+            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            if let Err(e) = policy.check_path_allowed(Path::new(path)) {
+                return SandboxDecision::deny(e.to_string());
+            }
+
+            let language = args
+                .get("language")
+                .and_then(|v| v.as_str())
+                .unwrap_or("auto");
+
+            let lang = match crate::lsp::LspLanguage::from_user(language, Path::new(path)) {
+                Some(l) => l,
+                None => {
+                    return SandboxDecision::deny(
+                        "Error: unsupported lsp language (use auto|rust|typescript).".to_string(),
+                    )
+                }
+            };
+            let exe = lang.resolve_executable(&policy.root);
+            if let Err(e) = policy.check_command_allowed(exe.to_string_lossy().as_ref()) {
+                return SandboxDecision::deny(e.to_string());
+            }
+            SandboxDecision::allow()
+        }
+        "github" if policy.enabled && !policy.network => SandboxDecision::deny(
+            "the sandbox has network access off (sandbox.network = false)".to_string(),
+        ),
+        _ => SandboxDecision::allow(),
+    }
+}
+
+fn build_turn_summary(user_message: &str, agent_response: &str, tools: &[ToolOutput]) -> String {
+    let mut out = String::new();
+
+    out.push_str("## User Message\n");
+    out.push_str(user_message.trim());
+    out.push_str("\n\n");
+
+    if !tools.is_empty() {
+        out.push_str("## Tool Calls\n");
+        for t in tools {
+            let status = match t.status {
+                ToolStatus::Running => "RUNNING",
+                ToolStatus::Success => "SUCCESS",
+                ToolStatus::Error => "FAILED",
+            };
+
+            out.push_str(&format!(
+                "- {} ({}) {}\n",
+                t.tool,
+                status,
+                truncate_for_summary(&t.target, 140)
+            ));
+
+            if !t.output.is_empty() {
+                let first = t.output.lines().next().unwrap_or("").trim();
+                if !first.is_empty() {
+                    out.push_str(&format!("  Output: {}\n", truncate_for_summary(first, 180)));
+                }
+            }
+        }
+        out.push('\n');
+    }
+
+    out.push_str("## Agent Response\n");
+    out.push_str(agent_response.trim());
+
+    out
+}
+
+fn truncate_for_summary(s: &str, max: usize) -> String {
+    let t = s.trim();
+    if t.len() <= max {
+        return t.to_string();
+    }
+    if max <= 3 {
+        return "...".to_string();
+    }
+    let mut out = t.to_string();
+    out.truncate(max.saturating_sub(3));
+    out.push_str("...");
+    out
+}
+
+/// Run background indexing - called from a blocking task
+fn run_background_index(
+    tx: mpsc::UnboundedSender<AppEvent>,
+    policy: Arc<SandboxPolicy>,
+    workspace_root: PathBuf,
+) -> Result<(usize, usize), String> {
+    // Catch any panics from dependencies
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let checked_root = match policy.check_path_allowed(&workspace_root) {
+            Ok(p) => p,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if let Some(result) = daemon::index(&checked_root) {
+            return result.map(|stats| (stats.total_chunks, stats.total_files));
+        }
+
+        let cfg = SearchConfig::for_workspace(&workspace_root);
+        let search = match SemanticSearch::new(cfg) {
+            Ok(s) => s,
+            Err(e) => return Err(format!("init: {}", e)),
+        };
+
+        // Every file reports while chunking; a few updates a second are plenty.
+        let last_sent = std::cell::Cell::new(None::<Instant>);
+        let progress = |p: crate::semantic_search::types::IndexProgress| {
+            let due = last_sent
+                .get()
+                .map_or(true, |t| t.elapsed() >= INDEX_PROGRESS_INTERVAL);
+            if due || p.files_done == p.total_files {
+                last_sent.set(Some(Instant::now()));
+                let _ = tx.send(AppEvent::IndexingProgress(p));
+            }
+        };
+        match search.index_directory_with_progress(&checked_root, &progress) {
+            Ok(stats) => Ok((stats.total_chunks, stats.total_files)),
+            Err(e) => Err(format!("index: {}", e)),
+        }
+    }));
+
+    match result {
+        Ok(Ok((chunks, files))) => {
+            let _ = tx.send(AppEvent::IndexingComplete(chunks, files));
+            Ok((chunks, files))
+        }
+        Ok(Err(e)) => {
+            let _ = tx.send(AppEvent::IndexingError(e.clone()));
+            Err(e)
+        }
+        Err(_) => {
+            let err = "indexing panicked".to_string();
+            let _ = tx.send(AppEvent::IndexingError(err.clone()));
+            Err(err)
+        }
+    }
+}
+
+fn index_file_exists(workspace_root: &std::path::Path) -> bool {
+    let index_dir = index_dir_for_workspace(workspace_root);
+    let index_path = index_dir.join("index.bin");
+    std::fs::metadata(index_path)
+        .map(|m| m.len() > 0)
+        .unwrap_or(false)
+}
+
+/// `path:line` locations in search tool output, in order and without repeats. Handles
+/// semantic_search (`1. src/a.rs:10-20 (...)`), rg (`src/a.rs:12:5:text`) and
+/// smart_search (`RG   score=0.80  src/a.rs:12:5  ...`); rg context lines (`a.rs-11-`) and
+/// summary lines don't match.
+fn search_result_locations(output: &str) -> Vec<(String, usize)> {
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let re = LOCATION.get_or_init(|| {
+        Regex::new(r"(?:^|\s)([^\s:]+):(\d+)(?:[:\-\s]|$)").expect("valid location regex")
+    });
+
+    let mut out: Vec<(String, usize)> = Vec::new();
+    for line in output.lines() {
+        let Some(caps) = re.captures(line) else {
+            continue;
+        };
+        let Ok(line_no) = caps[2].parse::<usize>() else {
+            continue;
+        };
+        let location = (caps[1].to_string(), line_no.max(1));
+        if !out.contains(&location) {
+            out.push(location);
+        }
+    }
+    out
+}
+
+/// Add errors the edit introduced (per the language server) to an edit tool's result, so
+/// the model fixes them in the same turn. Each report uses one of the turn's `budget`.
+async fn append_new_lsp_errors(
+    mut out: String,
+    edited: &[PathBuf],
+    call_id: &str,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    policy: &SandboxPolicy,
+    reports: &AtomicU32,
+    budget: u32,
+) -> String {
+    let Some(mut report) = crate::lsp::new_errors_after_edit(policy, edited).await else {
+        return out;
+    };
+    if reports.fetch_add(1, Ordering::SeqCst) + 1 >= budget {
+        report.push_str("\n(No more automatic diagnostics this turn.)");
+    }
+    let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+        call_id: call_id.to_string(),
+        chunk: format!("\n{}", report),
+    }));
+    out.push_str("\n\n");
+    out.push_str(&report);
+    out
+}
+
+/// The chat list shown when a verify run has failing tests.
+/// List the store for the /memory browser; `status` replaces its hint line.
+async fn send_memories(
+    memory: &MemoryManager,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    status: Option<String>,
+) {
+    let memories = memory
+        .list(MEMORY_BROWSER_LIMIT, None)
+        .await
+        .map_err(|e| e.to_string());
+    let _ = tx.send(AppEvent::MemoriesLoaded { memories, status });
+}
+
+/// Project memories first, then global; by type within a scope, most important first.
+fn sort_memories(memories: &mut [Memory]) {
+    memories.sort_by(|a, b| {
+        (a.scope != MemoryScope::Project, a.memory_type.as_str())
+            .cmp(&(b.scope != MemoryScope::Project, b.memory_type.as_str()))
+            .then(
+                b.importance
+                    .partial_cmp(&a.importance)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+    });
+}
+
+/// `term` appears in `haystack`, or its letters appear in order within one word.
+fn fuzzy_term_matches(term: &str, haystack: &str) -> bool {
+    if haystack.contains(term) {
+        return true;
+    }
+    haystack.split_whitespace().any(|word| {
+        let mut letters = word.chars();
+        term.chars().all(|c| letters.any(|w| w == c))
+    })
+}
+
+fn failing_tests_notice(failures: &[crate::verify::TestFailure]) -> String {
+    let mut out = format!(
+        "{} failing test{} (Ctrl+G opens each):",
+        failures.len(),
+        if failures.len() == 1 { "" } else { "s" }
+    );
+    for f in failures {
+        out.push_str("\n- ");
+        out.push_str(&f.line());
+    }
+    out
+}
+
+/// Cheap pre-call classifier: true for prompts that only ask about the code
+/// ("what does this function do?") and contain no request to change anything.
+fn is_informational_prompt(prompt: &str) -> bool {
+    const QUESTION_STARTS: &[&str] = &[
+        "what",
+        "why",
+        "how does",
+        "how do",
+        "how is",
+        "how are",
+        "where",
+        "which",
+        "who",
+        "when",
+        "explain",
+        "describe",
+        "summarize",
+        "is ",
+        "are ",
+        "does ",
+        "do ",
+        "can you explain",
+        "tell me",
+    ];
+    const ACTION_WORDS: &[&str] = &[
+        "fix",
+        "add",
+        "implement",
+        "change",
+        "update",
+        "refactor",
+        "write",
+        "create",
+        "remove",
+        "delete",
+        "rename",
+        "run",
+        "make",
+        "edit",
+        "install",
+        "build",
+        "test",
+        "migrate",
+        "replace",
+        "move",
+        "bump",
+        "commit",
+        "apply",
+        "generate",
+    ];
+
+    let lower = prompt.trim().to_lowercase();
+    if lower.is_empty() {
+        return false;
+    }
+    let asks = lower.ends_with('?') || QUESTION_STARTS.iter().any(|q| lower.starts_with(q));
+    if !asks {
+        return false;
+    }
+    !lower
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| ACTION_WORDS.contains(&word))
+}
+
+fn index_age_secs(workspace_root: &std::path::Path) -> Option<u64> {
+    let index_path = index_dir_for_workspace(workspace_root).join("index.bin");
+    let modified = std::fs::metadata(index_path).ok()?.modified().ok()?;
+    modified.elapsed().ok().map(|d| d.as_secs())
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+fn load_existing_index_status() -> IndexingStatus {
+    // Note: this reads *existing* index metadata only; it does not start indexing.
+    // The app will kick off background indexing from main.rs depending on config.
+    let workspace_root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    if !index_file_exists(&workspace_root) {
+        return IndexingStatus::NotStarted;
+    }
+
+    // A running daemon already has (or keeps) the model loaded; ask it instead.
+    let (stats, stale_backend) = match daemon::stats(&workspace_root) {
+        Some(Ok(v)) => v,
+        _ => match SemanticSearch::new(SearchConfig::for_workspace(&workspace_root)) {
+            Ok(search) => (search.stats(), search.stale_backend()),
+            Err(_) => return IndexingStatus::NotStarted,
+        },
+    };
+    if stale_backend.is_some() {
+        return IndexingStatus::Error(
+            "embedding backend changed; re-index (lorikeet index / auto_index)".to_string(),
+        );
+    }
+    IndexingStatus::Complete {
+        chunks: stats.total_chunks,
+        files: stats.total_files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::path::Path;
+
+    /// An `App` on the workspace at `dir` with the default config. Its events go nowhere.
+    async fn test_app(dir: &Path) -> App {
+        test_app_with(dir, AppConfig::default()).await
+    }
+
+    async fn test_app_with(dir: &Path, config: AppConfig) -> App {
+        let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
+        let policy = Arc::new(SandboxPolicy::from_config(
+            config.clone(),
+            dir.to_path_buf(),
+            &crate::tool_registry::names(),
+        ));
+        let memory = Arc::new(MemoryManager::init(dir).await.unwrap());
+        App::new(
+            tx,
+            LlmProvider::OpenRouter,
+            "k".into(),
+            None,
+            policy,
+            config,
+            dir.to_path_buf(),
+            memory,
+        )
+    }
+
+    #[test]
+    fn informational_prompts_are_routed_to_read_only_tools() {
+        assert!(is_informational_prompt("what does this function do?"));
+        assert!(is_informational_prompt("Explain the session store"));
+        assert!(is_informational_prompt(
+            "where is the sandbox policy built?"
+        ));
+        assert!(!is_informational_prompt("why does the test fail? fix it"));
+        assert!(!is_informational_prompt("add a --debug flag"));
+        assert!(!is_informational_prompt("how do I run the tests?"));
+    }
+
+    #[test]
+    fn verify_settings_reorder_and_validate() {
+        let (moved, cursor) = move_command_entry("a; cargo test; c", 5, true).unwrap();
+        assert_eq!((moved.as_str(), cursor), ("cargo test; a; c", 0));
+        let (moved, cursor) = move_command_entry("a; b; c", 3, false).unwrap();
+        assert_eq!((moved.as_str(), cursor), ("a; c; b", 6));
+        assert!(move_command_entry("a; b", 0, true).is_none());
+        assert!(move_command_entry("a; b", 4, false).is_none());
+
+        let root = std::env::temp_dir();
+        let mut draft = AppConfig {
+            verify: Some(crate::config::VerifyConfig {
+                commands: Some(vec!["cargo test".into(), "cargo test".into()]),
+                pipeline: None,
+            }),
+            ..Default::default()
+        };
+        assert!(validate_verify_settings(&draft, &root)
+            .unwrap_err()
+            .contains("listed twice"));
+        draft.verify = Some(crate::config::VerifyConfig {
+            commands: Some(vec!["cargo test".into()]),
+            pipeline: Some(vec!["definitely-not-allowed --check".into()]),
+        });
+        assert!(validate_verify_settings(&draft, &root)
+            .unwrap_err()
+            .starts_with("Verify pipeline"));
+        draft.verify = None;
+        draft.sandbox = Some(crate::config::SandboxConfig {
+            allow_tools: Some(vec!["read_file".into(), "raed_file".into()]),
+            ..Default::default()
+        });
+        assert!(validate_verify_settings(&draft, &root)
+            .unwrap_err()
+            .contains("raed_file"));
+    }
+
+    #[test]
+    fn search_output_locations_are_parsed_in_order() {
+        let semantic = "1. src/app.rs:120-140 (Function: start) [score: 0.81]\n   fn start() {\n\n2. src/ui.rs:9-12 [score: 0.70]\n";
+        assert_eq!(
+            search_result_locations(semantic),
+            vec![
+                ("src/app.rs".to_string(), 120),
+                ("src/ui.rs".to_string(), 9)
+            ]
+        );
+
+        let rg =
+            "/repo/src/a.rs:12:5:let x = 1;\n/repo/src/a.rs-13-context\n/repo/src/a.rs:12:9:again";
+        assert_eq!(
+            search_result_locations(rg),
+            vec![("/repo/src/a.rs".to_string(), 12)]
+        );
+
+        let smart =
+            "BOTH score=0.95  src/b.rs:3:1  fn b()\n\n(counts: rg=1 sem=1 deduped=1 shown=1)";
+        assert_eq!(
+            search_result_locations(smart),
+            vec![("src/b.rs".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn tool_runs_are_linked_by_call_id() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut app = test_app(&tmp).await;
+            app.current_turn_id = 1;
+
+            app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
+                call_id: "call-a".into(),
+                tool: "bash".into(),
+                args_raw: r#"{"command":"echo a"}"#.into(),
+                args_summary: "echo a".into(),
+                cwd: tmp.clone(),
+                sandbox: crate::sandbox::SandboxDecision::allow(),
+            }));
+            app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
+                call_id: "call-b".into(),
+                tool: "bash".into(),
+                args_raw: r#"{"command":"echo b"}"#.into(),
+                args_summary: "echo b".into(),
+                cwd: tmp.clone(),
+                sandbox: crate::sandbox::SandboxDecision::allow(),
+            }));
+
+            app.handle_event(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: "call-a".into(),
+                chunk: "A
+"
+                .into(),
+            }));
+
+            assert_eq!(app.tool_outputs.len(), 2);
+            assert!(app.tool_outputs[0].output.contains('A'));
+            assert!(app.tool_outputs[1].output.is_empty());
+        });
+    }
+
+    #[test]
+    fn tab_toggles_plan_mode_backtab_switches_pane() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+
+            assert!(!app.plan_mode);
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Tab,
+                KeyModifiers::NONE,
+            )));
+            assert!(app.plan_mode);
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Tab,
+                KeyModifiers::NONE,
+            )));
+            assert!(!app.plan_mode);
+
+            assert_eq!(app.active_pane, Pane::Chat);
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::BackTab,
+                KeyModifiers::SHIFT,
+            )));
+            assert_eq!(app.active_pane, Pane::Context);
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::BackTab,
+                KeyModifiers::SHIFT,
+            )));
+            assert_eq!(app.active_pane, Pane::Chat);
+        });
+    }
+
+    #[test]
+    fn trust_prompt_holds_keys_until_answered() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            app.trust_prompt_open = true;
+
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Tab,
+                KeyModifiers::NONE,
+            )));
+            assert!(!app.plan_mode);
+            assert!(app.workspace_restricted());
+
+            // Typed text and a bare Enter don't decide anything.
+            for code in [KeyCode::Char('t'), KeyCode::Char('y'), KeyCode::Enter] {
+                app.handle_event(AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE)));
+            }
+            assert!(app.trust_prompt_open);
+            assert_eq!(app.workspace_trust, None);
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Up,
+                KeyModifiers::NONE,
+            )));
+            assert_eq!(app.trust_choice, Some(TrustLevel::Trusted));
+
+            // Esc decides for this session only (nothing is written).
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Esc,
+                KeyModifiers::NONE,
+            )));
+            assert!(!app.trust_prompt_open);
+            assert_eq!(app.workspace_trust, Some(TrustLevel::Restricted));
+            assert!(app.workspace_restricted());
+
+            // `/verify` would run project-defined commands.
+            app.set_input("/verify");
+            app.submit_message();
+            assert!(app.tool_outputs.is_empty());
+            assert!(app
+                .messages
+                .iter()
+                .any(|m| m.content.contains("not trusted")));
+        });
+    }
+
+    #[test]
+    fn infra_failures_do_not_trip_the_tool_loop_guard() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut config = AppConfig::default();
+            config.memory = Some(crate::config::MemoryConfig {
+                enabled: Some(false),
+                ..Default::default()
+            });
+
+            let mut app = test_app_with(&tmp, config).await;
+
+            fn run(app: &mut App, cwd: &std::path::Path, n: usize, output: &str) {
+                let call_id = format!("call-{}", n);
+                app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
+                    call_id: call_id.clone(),
+                    tool: "bash".into(),
+                    args_raw: r#"{"command":"cargo test"}"#.into(),
+                    args_summary: "cargo test".into(),
+                    cwd: cwd.to_path_buf(),
+                    sandbox: crate::sandbox::SandboxDecision::allow(),
+                }));
+                app.handle_event(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                    call_id: call_id.clone(),
+                    chunk: output.into(),
+                }));
+                app.handle_event(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                    call_id,
+                    success: false,
+                }));
+            }
+            for n in 0..3 {
+                let output = "Error spawning process: Resource temporarily unavailable";
+                run(&mut app, &tmp, n, output);
             }
-        }
-        "apply_patch" => {
-            // Best-effort: scan for paths in the patch headers and validate them.
-            let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
-            for line in patch.lines() {
-                let path = if let Some(p) = line.strip_prefix("*** Add File: ") {
-                    Some(p.trim())
-                } else if let Some(p) = line.strip_prefix("*** Update File: ") {
-                    Some(p.trim())
-                } else if let Some(p) = line.strip_prefix("*** Delete File: ") {
-                    Some(p.trim())
-                } else {
-                    None
-                };
-                if let Some(p) = path {
-                    if let Err(e) = policy.check_path_allowed(Path::new(p)) {
-                        return SandboxDecision::deny(e.to_string());
-                    }
-                }
+            assert!(app.tool_loop_abort.is_none());
+
+            for n in 3..6 {
+                run(
+                    &mut app,
+                    &tmp,
+                    n,
+                    "[stderr] error: could not compile\n[exit] 101\n",
+                );
             }
-            SandboxDecision::allow()
-        }
-        "lsp" => {
-            let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            if let Err(e) = policy.check_path_allowed(Path::new(path)) {
-                return SandboxDecision::deny(e.to_string());
+            assert!(app.tool_loop_abort.is_some());
+        });
+    }
+
+    #[test]
+    fn failed_verify_lists_failing_tests_and_ctrl_g_opens_them() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut config = AppConfig::default();
+            config.memory = Some(crate::config::MemoryConfig {
+                enabled: Some(false),
+                ..Default::default()
+            });
+            let _ = std::fs::create_dir_all(tmp.join("src"));
+            std::fs::write(tmp.join("src/lib.rs"), "fn a() {}\n".repeat(20)).unwrap();
+
+            let mut app = test_app_with(&tmp, config).await;
+
+            app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
+                call_id: "call-verify".into(),
+                tool: "verify".into(),
+                args_raw: "{}".into(),
+                args_summary: "pipeline".into(),
+                cwd: tmp.clone(),
+                sandbox: crate::sandbox::SandboxDecision::allow(),
+            }));
+            app.handle_event(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: "call-verify".into(),
+                chunk: "---- tests::adds stdout ----\n\
+thread 'tests::adds' panicked at src/lib.rs:12:5:\n\
+assertion failed: ok\n"
+                    .into(),
+            }));
+            app.handle_event(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: "call-verify".into(),
+                success: false,
+            }));
+            assert_eq!(app.verify_history, vec![false]);
+
+            let notice = app.messages.last().unwrap();
+            assert!(notice.local);
+            assert_eq!(
+                notice.content,
+                "1 failing test (Ctrl+G opens each):\n- tests::adds (src/lib.rs:12): assertion failed: ok"
+            );
+
+            app.goto_next_search_result();
+            let view = app.file_view.as_ref().expect("failure opened");
+            assert_eq!((view.path.as_str(), view.line), ("src/lib.rs", 12));
+        });
+    }
+
+    #[test]
+    fn agent_error_keeps_partial_reply_as_local_draft() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            app.is_processing = true;
+            app.handle_event(AppEvent::AgentChunk("Step one: read the ".into()));
+            app.handle_event(AppEvent::AgentError("stream reset".into()));
+
+            let n = app.messages.len();
+            let draft = &app.messages[n - 2];
+            assert_eq!(draft.role, Role::Draft);
+            assert_eq!(draft.content, "Step one: read the ");
+            assert!(draft.local, "drafts never go back to the model");
+            assert_eq!(app.messages[n - 1].content, "[Error: stream reset]");
+            assert!(app.current_response.is_empty());
+            assert!(!app.is_processing);
+
+            // Classified errors come with what to do about them.
+            app.is_processing = true;
+            app.handle_event(AppEvent::AgentError(AgentError::new(
+                crate::llm::ErrorKind::ContextOverflow,
+                "prompt is too long",
+            )));
+            let last = &app.messages.last().unwrap().content;
+            assert!(last.starts_with("[Error: Context too long: prompt is too long]\n"));
+            assert!(last.contains("/new"));
+        });
+    }
+
+    #[test]
+    fn plan_show_reopens_the_latest_plan_and_execute_pins_it() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+
+            assert!(app.maybe_handle_command("/plan show"));
+            assert!(!app.plan_popup_open);
+            assert!(app.messages.last().unwrap().content.starts_with("No plan yet"));
+
+            let draft = parse_plan_response(
+                r#"{"plan":"1. Add the flag","questions":[{"id":"name","prompt":"Flag name?","type":"text","options":[],"default":"--fast"}]}"#,
+            )
+            .unwrap();
+            app.plan_draft = Some(draft);
+            assert!(app.maybe_handle_command("/plan show"));
+            assert!(app.plan_popup_open);
+            assert_eq!(app.plan_answer_input, "--fast");
+
+            app.plan_focus = PlanFocus::Answer;
+            app.handle_plan_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+            app.handle_plan_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+            assert!(!app.plan_popup_open);
+            let edited = app.plan_draft.clone().unwrap();
+            assert_eq!(edited.answers.get("name").map(String::as_str), Some("--fastx"));
+
+            app.pin_accepted_plan(&edited);
+            let pinned = app.messages.last().unwrap();
+            assert_eq!(pinned.role, Role::Plan);
+            assert!(pinned.local, "the pinned plan is display-only");
+            assert!(pinned.content.contains("1. Add the flag"));
+            assert!(pinned.content.contains("- Flag name?: --fastx"));
+        });
+    }
+
+    #[test]
+    fn plan_steps_run_one_at_a_time_and_get_checked_off() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+
+            let plan = parse_plan_response(
+                r#"{"plan":"Intro\n\n1. Read config.rs\n2) Add the flag\n   - wire it into main\n3. Run tests\n\nNotes","questions":[]}"#,
+            )
+            .unwrap();
+            assert_eq!(
+                plan.steps(),
+                vec![
+                    "Read config.rs".to_string(),
+                    "Add the flag\n   - wire it into main".to_string(),
+                    "Run tests".to_string(),
+                ]
+            );
+            app.plan_draft = Some(plan.clone());
+
+            app.prepare_go_with_plan(&plan, Some(2));
+            let msg = app.ephemeral_user_message.take().unwrap();
+            assert!(msg.contains("only step 2"));
+            assert!(msg.contains("2. Add the flag"));
+            assert!(!msg.contains("Read config.rs"));
+            assert_eq!(app.plan_steps_running, vec![2]);
+
+            app.complete_running_plan_steps();
+            let plan = app.plan_draft.clone().unwrap();
+            assert_eq!(plan.completed_steps, vec![2]);
+            assert_eq!(plan.pending_steps(), vec![1, 3]);
+
+            app.prepare_go_with_plan(&plan, None);
+            let msg = app.ephemeral_user_message.take().unwrap();
+            assert!(msg.contains("1. Read config.rs"));
+            assert!(msg.contains("3. Run tests"));
+            assert!(!msg.contains("Add the flag"));
+        });
+    }
+
+    #[test]
+    fn quick_answers_stay_out_of_the_conversation() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+
+            assert!(app.maybe_handle_command("/quick"));
+            assert_eq!(
+                app.messages.last().unwrap().content,
+                "Usage: /quick <question>"
+            );
+            assert!(!app.is_processing);
+
+            // As start_quick_call leaves things, without going to the network.
+            app.is_processing = true;
+            app.quick_call = true;
+            app.handle_event(AppEvent::AgentChunk("Four.".into()));
+            app.handle_event(AppEvent::AgentDone);
+
+            let answer = app.messages.last().unwrap();
+            assert_eq!(answer.content, "Four.");
+            assert!(answer.local, "quick answers never go back to the model");
+            assert!(!app.quick_call);
+            assert!(!app.is_processing);
+        });
+    }
+
+    #[test]
+    fn recent_files_open_in_viewer_and_pin_for_next_call() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            std::fs::write(tmp.join("a.rs"), "fn a() {}\n").unwrap();
+            std::fs::write(tmp.join("b.rs"), "fn b() {}\n").unwrap();
+
+            let mut app = test_app(&tmp).await;
+            app.push_recent_file("a.rs");
+            app.push_recent_file("b.rs");
+            app.active_pane = Pane::Context;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+
+            app.handle_event(key(KeyCode::Down));
+            assert_eq!(app.selected_recent_file().map(String::as_str), Some("a.rs"));
+            app.handle_event(key(KeyCode::Enter));
+            let view = app.recent_file_popup.as_ref().expect("viewer opened");
+            assert_eq!(view.lines, vec!["fn a() {}".to_string()]);
+            assert!(app.messages.iter().all(|m| m.role != Role::User));
+
+            app.handle_event(key(KeyCode::Char('p')));
+            assert_eq!(app.pinned_files, vec!["a.rs".to_string()]);
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.recent_file_popup.is_none());
+            assert!(!app.should_quit);
+
+            let context = app.take_pinned_context().unwrap();
+            assert!(context.contains("--- a.rs ---\nfn a() {}"), "{}", context);
+            assert!(app.pinned_files.is_empty(), "pins last one call");
+        });
+    }
+
+    #[test]
+    fn go_sets_tools_override_and_ephemeral_message_then_consumes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+
+            app.plan_mode = true;
+            app.prepare_go();
+            assert!(app.tools_enabled_for_next_call());
+
+            let (tools_enabled, ephemeral) = app.take_next_call_overrides();
+            assert!(tools_enabled);
+            assert!(ephemeral.is_some());
+
+            // One-shot override resets.
+            assert!(!app.tools_override_next);
+            assert!(app.ephemeral_user_message.is_none());
+        });
+    }
+
+    #[test]
+    fn repeated_edits_rank_files_in_the_heatmap() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut app = test_app(&tmp).await;
+            app.file_edits_before.insert("README.md".into(), 9);
+
+            let edits = [
+                (
+                    "e1",
+                    "edit_file",
+                    r#"{"path":"src/a.rs","old_string":"a","new_string":"b"}"#,
+                ),
+                (
+                    "e2",
+                    "edit_file",
+                    r#"{"path":"./src/a.rs","old_string":"b","new_string":"c"}"#,
+                ),
+                ("e3", "write_file", r#"{"path":"src/b.rs","content":"x"}"#),
+                ("e4", "read_file", r#"{"path":"src/c.rs"}"#),
+            ];
+            for (id, tool, args) in edits {
+                app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
+                    call_id: id.into(),
+                    tool: tool.into(),
+                    args_raw: args.into(),
+                    args_summary: String::new(),
+                    cwd: tmp.clone(),
+                    sandbox: crate::sandbox::SandboxDecision::allow(),
+                }));
+                app.handle_event(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                    call_id: id.into(),
+                    success: true,
+                }));
             }
 
-            let language = args
-                .get("language")
-                .and_then(|v| v.as_str())
-                .unwrap_or("auto");
+            assert_eq!(
+                app.most_touched_files(10),
+                vec![
+                    ("src/a.rs".to_string(), 2, 0),
+                    ("src/b.rs".to_string(), 1, 0),
+                    ("README.md".to_string(), 0, 9),
+                ]
+            );
+        });
+    }
 
-            let lang = match crate::lsp::LspLanguage::from_user(language, Path::new(path)) {
-                Some(l) => l,
-                None => {
-                    return SandboxDecision::deny(
-                        "Error: unsupported lsp language (use auto|rust|typescript).".to_string(),
+    #[test]
+    fn memory_browser_filters_edits_and_forgets() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+            app.event_tx = tx;
+            let memory = app.memory.clone();
+            for (t, content) in [
+                (
+                    crate::memory::MemoryType::Preference,
+                    "Use tabs in the config parser",
+                ),
+                (
+                    crate::memory::MemoryType::Fact,
+                    "Integration tests need docker",
+                ),
+            ] {
+                memory
+                    .save_explicit(
+                        t,
+                        content,
+                        None,
+                        None,
+                        vec![],
+                        crate::memory::MemoryScope::Project,
+                        None,
+                        crate::memory::MemorySource::User,
+                        None,
+                        None,
                     )
-                }
+                    .await
+                    .unwrap();
+            }
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+
+            assert!(app.maybe_handle_command("/memory"));
+            let loaded = rx.recv().await.unwrap();
+            app.handle_event(loaded);
+            assert_eq!(app.memories.len(), 2);
+            // Grouped by type: fact before preference.
+            assert!(app.memories[0].content.contains("docker"));
+
+            // "cfg" matches "config" as a subsequence.
+            for c in "cfg".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
+            }
+            let filtered = app.filtered_memories();
+            assert_eq!(filtered.len(), 1);
+            assert!(filtered[0].content.contains("tabs"));
+
+            // Edit: content gets a suffix, tags and importance are replaced.
+            app.handle_event(key(KeyCode::Enter));
+            assert!(app.memory_edit.is_some());
+            app.handle_event(key(KeyCode::Char('!')));
+            app.handle_event(key(KeyCode::Tab));
+            for c in "style, fmt".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
+            }
+            app.handle_event(key(KeyCode::Tab));
+            for _ in 0..4 {
+                app.handle_event(key(KeyCode::Backspace));
+            }
+            app.handle_event(key(KeyCode::Char('2')));
+            app.handle_event(key(KeyCode::Enter));
+            assert_eq!(
+                app.memories_status.as_deref(),
+                Some("Importance must be a number from 0 to 1.")
+            );
+            app.handle_event(key(KeyCode::Backspace));
+            for c in "0.3".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
+            }
+            app.handle_event(key(KeyCode::Enter));
+            assert!(app.memory_edit.is_none());
+            let saved = rx.recv().await.unwrap();
+            app.handle_event(saved);
+            assert_eq!(app.memories_status.as_deref(), Some("Memory saved."));
+            let stored = memory.list(10, None).await.unwrap();
+            let edited = stored.iter().find(|m| m.content.contains("tabs")).unwrap();
+            assert_eq!(edited.content, "Use tabs in the config parser!");
+            assert_eq!(edited.tags, vec!["style".to_string(), "fmt".to_string()]);
+            assert!((edited.importance - 0.3).abs() < 1e-6);
+
+            // Delete asks once, then forgets.
+            app.handle_event(key(KeyCode::Delete));
+            assert_eq!(app.memories.len(), 2);
+            app.handle_event(key(KeyCode::Delete));
+            assert_eq!(app.memories.len(), 1);
+            let forgotten = rx.recv().await.unwrap();
+            app.handle_event(forgotten);
+            assert_eq!(memory.list(10, None).await.unwrap().len(), 1);
+
+            app.handle_event(key(KeyCode::Esc));
+            assert!(!app.memories_open);
+        });
+    }
+
+    #[test]
+    fn last_tool_command_covers_bash_and_verify_pipelines() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut app = test_app(&tmp).await;
+            app.current_turn_id = 1;
+            assert_eq!(app.last_tool_command(), None);
+
+            let start = |call_id: &str, tool: &str, args_raw: &str| {
+                AppEvent::ToolStart(crate::events::ToolStartEvent {
+                    call_id: call_id.into(),
+                    tool: tool.into(),
+                    args_raw: args_raw.into(),
+                    args_summary: String::new(),
+                    cwd: tmp.clone(),
+                    sandbox: crate::sandbox::SandboxDecision::allow(),
+                })
             };
-            let exe = lang.resolve_executable(&policy.root);
-            if let Err(e) = policy.check_command_allowed(exe.to_string_lossy().as_ref()) {
-                return SandboxDecision::deny(e.to_string());
+            app.handle_event(start(
+                "c1",
+                "bash",
+                r#"{"command":"cargo test -p core -- --nocapture"}"#,
+            ));
+            app.handle_event(start("c2", "read_file", r#"{"path":"src/lib.rs"}"#));
+            assert_eq!(
+                app.last_tool_command().as_deref(),
+                Some("cargo test -p core -- --nocapture")
+            );
+
+            app.handle_event(start("c3", "verify", "{}"));
+            app.handle_event(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: "c3".into(),
+                chunk: "── fmt: cargo fmt --check\n── test: cargo test\nok\n".into(),
+            }));
+            assert_eq!(
+                app.last_tool_command().as_deref(),
+                Some("cargo fmt --check && cargo test")
+            );
+
+            app.open_copy_mode();
+            assert_eq!(
+                app.copy_targets[0].content,
+                "cargo fmt --check && cargo test"
+            );
+            assert!(app.copy_targets[0].label.ends_with("(last command)"));
+        });
+    }
+
+    #[test]
+    fn bookmarks_persist_and_complete_mentions() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            std::fs::create_dir_all(tmp.join("docs")).unwrap();
+            std::fs::write(tmp.join("docs/ARCHITECTURE.md"), "").unwrap();
+            let mut app = test_app(&tmp).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+
+            assert!(app.maybe_handle_command("/bookmark add docs/ARCHITECTURE.md"));
+            assert!(app.maybe_handle_command("/bookmark add missing.rs"));
+            assert_eq!(app.bookmarks, vec!["docs/ARCHITECTURE.md".to_string()]);
+            assert_eq!(
+                crate::bookmarks::load(&tmp),
+                vec!["docs/ARCHITECTURE.md".to_string()]
+            );
+
+            for c in "read @arch".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
             }
-            SandboxDecision::allow()
-        }
-        _ => SandboxDecision::allow(),
+            assert_eq!(app.mention_suggestions(), vec!["docs/ARCHITECTURE.md"]);
+            app.handle_event(key(KeyCode::Tab));
+            assert_eq!(app.input, "read @docs/ARCHITECTURE.md ");
+            assert_eq!(app.cursor_pos, app.input.len());
+            assert!(!app.plan_mode);
+
+            assert!(app.maybe_handle_command("/bookmark remove docs/ARCHITECTURE.md"));
+            assert!(app.bookmarks.is_empty());
+            assert!(crate::bookmarks::load(&tmp).is_empty());
+        });
+    }
+
+    #[test]
+    fn destructive_commands_need_an_explicit_yes() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+
+            let (request, mut reply) = ApprovalRequest::new(
+                "c1".into(),
+                "git reset --hard".into(),
+                vec!["git reset".into()],
+                vec!["destructive: `git reset --hard`".into()],
+                true,
+            );
+            app.handle_event(AppEvent::ToolApproval(request));
+            app.handle_event(key(KeyCode::Enter));
+            app.handle_event(key(KeyCode::Char('a')));
+            assert!(app.pending_approval.is_some());
+            assert!(reply.try_recv().is_err());
+
+            app.handle_event(key(KeyCode::Char('y')));
+            assert!(app.pending_approval.is_none());
+            assert_eq!(reply.try_recv().unwrap(), Approval::Once);
+            assert!(app
+                .config
+                .sandbox
+                .as_ref()
+                .and_then(|s| s.trusted_commands.as_ref())
+                .is_none());
+
+            // A stray `a` only arms "always allow"; anything but Enter takes it back.
+            let (request, mut reply) = ApprovalRequest::new(
+                "c2".into(),
+                "npm install left-pad".into(),
+                vec!["npm install".into()],
+                vec!["`npm install` has never run in this workspace".into()],
+                false,
+            );
+            app.handle_event(AppEvent::ToolApproval(request));
+            app.handle_event(key(KeyCode::Char('a')));
+            assert!(app.approval_always_armed);
+            app.handle_event(key(KeyCode::Char('n')));
+            assert!(!app.approval_always_armed);
+            assert!(app.pending_approval.is_some());
+            assert!(reply.try_recv().is_err());
+            app.handle_event(key(KeyCode::Char('y')));
+            assert_eq!(reply.try_recv().unwrap(), Approval::Once);
+        });
+    }
+
+    #[test]
+    fn only_long_command_and_search_results_are_summarized() {
+        let summarizer = ToolSummarizer {
+            provider: LlmProvider::OpenRouter,
+            api_key: "k".into(),
+            model: "cheap".into(),
+            threshold_tokens: 100,
+        };
+        let long = "x".repeat(401);
+        assert!(summarizer.wants("bash", &long));
+        assert!(summarizer.wants("rg", &long));
+        assert!(!summarizer.wants("bash", &"x".repeat(400)));
+        assert!(!summarizer.wants("read_file", &long));
+        assert!(!summarizer.wants("open_at", &long));
+    }
+
+    #[test]
+    fn stats_popup_tracks_model_time_per_turn() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+
+            app.current_turn_id = 1;
+            app.handle_event(AppEvent::LlmTiming(Duration::from_millis(700)));
+            app.handle_event(AppEvent::LlmTiming(Duration::from_millis(300)));
+            app.handle_event(AppEvent::LlmUsage(TokenUsage {
+                prompt_tokens: 120,
+                completion_tokens: 30,
+                ..TokenUsage::default()
+            }));
+            let turns = app.turn_stats();
+            assert_eq!(turns.len(), 1);
+            assert_eq!(turns[0].llm, Duration::from_secs(1));
+            assert_eq!(turns[0].llm_calls, 2);
+            assert_eq!(turns[0].usage.prompt_tokens, 120);
+
+            assert!(app.maybe_handle_command("/stats"));
+            assert!(app.stats_open);
+            app.handle_event(key(KeyCode::Char('j')));
+            assert_eq!(app.stats_scroll, 1);
+            app.handle_event(key(KeyCode::Esc));
+            assert!(!app.stats_open);
+
+            assert!(app.maybe_handle_command("/new"));
+            assert!(app.turn_stats().is_empty());
+        });
+    }
+
+    #[test]
+    fn quitting_mid_turn_asks_first() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+            let ctrl = |c| AppEvent::Input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+
+            app.handle_event(key(KeyCode::Char('h')));
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.input.is_empty());
+            app.handle_event(key(KeyCode::Esc));
+            assert!(!app.should_quit, "Esc no longer quits by default");
+
+            app.is_processing = true;
+            app.handle_event(ctrl('c'));
+            assert!(app.quit_confirm_open);
+            assert!(!app.should_quit);
+            app.handle_event(key(KeyCode::Char('n')));
+            assert!(!app.quit_confirm_open);
+            app.handle_event(ctrl('q'));
+            app.handle_event(key(KeyCode::Char('y')));
+            assert!(app.should_quit);
+
+            app.should_quit = false;
+            app.quit_confirm_open = false;
+            app.is_processing = false;
+            app.handle_event(ctrl('q'));
+            assert!(app.should_quit, "nothing in flight: quit right away");
+        });
+    }
+
+    #[test]
+    fn quitting_closes_and_compresses_the_session() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config: AppConfig =
+                toml::from_str("[general]\ncompress_sessions = true\n").unwrap();
+            let mut app = test_app_with(dir.path(), config).await;
+            app.new_session();
+            let store = app.session.clone().unwrap();
+            store.record_title("Before quitting");
+
+            // What main does on the way out
+            app.close_session();
+            assert!(app.session.is_none());
+            assert!(!store.events_path.exists());
+            assert!(store.compressed_path().exists());
+            let events = store.load_events().unwrap();
+            assert_eq!(
+                crate::session::latest_title(&events).as_deref(),
+                Some("Before quitting")
+            );
+            let _ = std::fs::remove_file(store.compressed_path());
+        });
+    }
+
+    #[test]
+    fn normal_mode_scrolls_and_searches_the_transcript() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+            for content in ["first Needle", "nothing here", "second needle"] {
+                app.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: content.into(),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+            }
+            let mut store = std::mem::take(&mut app.render_store);
+            store.ensure_up_to_date(&app, &crate::theme::ui_theme(&app.config, None), 60);
+            app.render_store = store;
+
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.chat_normal_mode);
+            app.handle_event(key(KeyCode::Char('j')));
+            assert!(app.input.is_empty(), "normal mode keys don't type");
+
+            app.handle_event(key(KeyCode::Char('g')));
+            app.handle_event(key(KeyCode::Char('g')));
+            assert_eq!(app.chat_scroll_rows, 0);
+            assert!(!app.chat_follow);
+
+            app.handle_event(key(KeyCode::Char('/')));
+            for c in "needle".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
+            }
+            assert_eq!(app.transcript_search_prompt.as_deref(), Some("needle"));
+            app.handle_event(key(KeyCode::Enter));
+            let rows = app.render_store.find("needle");
+            assert_eq!(rows.len(), 2);
+            assert_eq!(app.transcript_match_row, Some(rows[0]));
+            app.handle_event(key(KeyCode::Char('n')));
+            assert_eq!(app.transcript_match_row, Some(rows[1]));
+            app.handle_event(key(KeyCode::Char('n')));
+            assert_eq!(app.transcript_match_row, Some(rows[0]), "wraps around");
+            app.handle_event(key(KeyCode::Char('N')));
+            assert_eq!(app.transcript_match_row, Some(rows[1]));
+            let second = app
+                .messages
+                .iter()
+                .find(|m| m.content == "second needle")
+                .unwrap()
+                .id;
+            assert_eq!(app.selected_message_id(), Some(second));
+
+            app.handle_event(key(KeyCode::Char('G')));
+            assert!(app.chat_follow);
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.transcript_query.is_none());
+            app.handle_event(key(KeyCode::Char('i')));
+            assert!(!app.chat_normal_mode);
+            app.handle_event(key(KeyCode::Char('x')));
+            assert_eq!(app.input, "x");
+        });
+    }
+
+    #[test]
+    fn find_jumps_to_the_latest_match_and_cycles() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let ctrl = |c| AppEvent::Input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+            for content in ["the build broke", "unrelated", "fixed the BUILD"] {
+                app.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: content.into(),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: true,
+                });
+            }
+            let mut store = std::mem::take(&mut app.render_store);
+            store.ensure_up_to_date(&app, &crate::theme::ui_theme(&app.config, None), 60);
+            app.render_store = store;
+
+            let rows = app.render_store.find("build");
+            assert_eq!(rows.len(), 2);
+            assert!(app.maybe_handle_command("/find build"));
+            assert_eq!(app.transcript_match_row, Some(rows[1]));
+            assert!(!app.chat_follow);
+            app.handle_event(ctrl('n'));
+            assert_eq!(app.transcript_match_row, Some(rows[0]));
+            app.handle_event(ctrl('p'));
+            assert_eq!(app.transcript_match_row, Some(rows[1]));
+
+            app.handle_event(ctrl('f'));
+            assert_eq!(app.transcript_search_prompt.as_deref(), Some(""));
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Esc,
+                KeyModifiers::NONE,
+            )));
+            assert!(app.transcript_search_prompt.is_none());
+            assert!(!app.chat_normal_mode);
+
+            assert!(app.maybe_handle_command("/find"));
+            assert!(app.transcript_query.is_none());
+        });
+    }
+
+    #[test]
+    fn long_agent_messages_fold_except_the_latest() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let long: String = (1..=50).map(|i| format!("line {}\n\n", i)).collect();
+            app.messages.retain(|m| m.role == Role::System);
+            for _ in 0..2 {
+                app.push_message(Message {
+                    id: 0,
+                    role: Role::Agent,
+                    content: long.clone(),
+                    reasoning: None,
+                    tool_calls: None,
+                    tool_group_id: None,
+                    local: false,
+                });
+            }
+            let theme = crate::theme::ui_theme(&app.config, None);
+            let render = |app: &mut App| {
+                let mut store = std::mem::take(&mut app.render_store);
+                store.ensure_up_to_date(app, &theme, 60);
+                app.render_store = store;
+            };
+            render(&mut app);
+
+            let older = app.display_messages().next().unwrap().id;
+            let latest = app.latest_agent_message_id().unwrap();
+            assert!(app.message_collapsed(older, Some(latest)));
+            assert!(!app.message_collapsed(latest, Some(latest)));
+            let (_, folded) = app.render_store.block_at(0).unwrap();
+            assert_eq!(folded.len(), 13, "12 lines plus the expand marker");
+
+            // Clicking the header row unfolds it.
+            app.chat_scroll_rows = 0;
+            app.click_chat_row(0);
+            render(&mut app);
+            let (_, unfolded) = app.render_store.block_at(0).unwrap();
+            assert!(unfolded.len() > 30);
+
+            // `z` in normal mode folds the message at the top of the view again.
+            app.chat_normal_mode = true;
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Char('z'),
+                KeyModifiers::NONE,
+            )));
+            render(&mut app);
+            assert_eq!(app.render_store.block_at(0).unwrap().1.len(), 13);
+        });
     }
-}
 
-fn build_turn_summary(user_message: &str, agent_response: &str, tools: &[ToolOutput]) -> String {
-    let mut out = String::new();
+    #[test]
+    fn file_references_open_by_click_or_selection() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            std::fs::create_dir_all(tmp.join("src")).unwrap();
+            std::fs::write(tmp.join("src/lib.rs"), "a\nb\nc\n").unwrap();
+            std::fs::write(tmp.join("notes.md"), "x\n").unwrap();
+            let mut app = test_app(&tmp).await;
+            app.messages.retain(|m| m.role == Role::System);
+            app.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: "The bug is at src/lib.rs:2 (see notes.md:1).".into(),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local: false,
+            });
+            let mut store = std::mem::take(&mut app.render_store);
+            store.ensure_up_to_date(&app, &crate::theme::ui_theme(&app.config, None), 80);
+            app.render_store = store;
+            app.chat_area = Rect::new(0, 0, 84, 12);
+            app.chat_scroll_rows = 0;
+
+            // "● The bug is at " is 16 cells wide.
+            assert!(!app.click_file_ref(0, 2));
+            assert!(app.click_file_ref(0, 18));
+            let view = app.file_view.take().expect("viewer opened");
+            assert_eq!((view.path.as_str(), view.line), ("src/lib.rs", 2));
+
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+            app.chat_normal_mode = true;
+            app.handle_event(key(KeyCode::Char('f')));
+            app.handle_event(key(KeyCode::Char('f')));
+            assert_eq!(
+                app.selected_file_ref.as_ref().map(|(_, r)| r.path.as_str()),
+                Some("notes.md")
+            );
+            app.handle_event(key(KeyCode::Char('o')));
+            assert_eq!(app.file_view.as_ref().map(|v| v.line), Some(1));
+            assert!(app.pending_editor.is_none());
+        });
+    }
 
-    out.push_str("## User Message\n");
-    out.push_str(user_message.trim());
-    out.push_str("\n\n");
+    #[test]
+    fn compose_key_hands_the_input_to_the_editor() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config = AppConfig {
+                editor: Some(crate::config::EditorConfig {
+                    compose_key: Some("alt+e".into()),
+                    ..Default::default()
+                }),
+                ..AppConfig::default()
+            };
+            let mut app = test_app_with(dir.path(), config).await;
+            app.set_input("draft prompt");
 
-    if !tools.is_empty() {
-        out.push_str("## Tool Calls\n");
-        for t in tools {
-            let status = match t.status {
-                ToolStatus::Running => "RUNNING",
-                ToolStatus::Success => "SUCCESS",
-                ToolStatus::Error => "FAILED",
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Char('e'),
+                KeyModifiers::ALT,
+            )));
+            let Some(EditorRequest::Compose { path }) = app.pending_editor.clone() else {
+                panic!("compose not requested: {:?}", app.pending_editor);
             };
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "draft prompt");
+            assert_eq!(
+                app.input, "draft prompt",
+                "unchanged until the editor exits"
+            );
+            let _ = std::fs::remove_file(path);
+        });
+    }
 
-            out.push_str(&format!(
-                "- {} ({}) {}\n",
-                t.tool,
-                status,
-                truncate_for_summary(&t.target, 140)
-            ));
+    #[test]
+    fn pastes_insert_verbatim_without_sending() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let messages = app.messages.len();
+            app.set_input("fix: ");
+
+            app.handle_event(AppEvent::Paste("fn a() {\r\n    b();\r\n}".into()));
+            assert_eq!(app.input, "fix: fn a() {\n    b();\n}");
+            assert_eq!(app.cursor_pos, app.input.len());
+            assert_eq!(app.messages.len(), messages, "nothing was sent");
+            assert!(!app.is_processing);
+
+            app.stats_open = true;
+            app.handle_event(AppEvent::Paste("ignored".into()));
+            assert!(!app.input.contains("ignored"));
+        });
+    }
 
-            if !t.output.is_empty() {
-                let first = t.output.lines().next().unwrap_or("").trim();
-                if !first.is_empty() {
-                    out.push_str(&format!("  Output: {}\n", truncate_for_summary(first, 180)));
-                }
-            }
-        }
-        out.push('\n');
+    #[test]
+    fn notifications_only_for_long_work_while_unfocused() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config = AppConfig {
+                notifications: Some(crate::config::NotificationsConfig {
+                    turn: Some("osc9".into()),
+                    min_seconds: Some(60),
+                    ..Default::default()
+                }),
+                ..AppConfig::default()
+            };
+            let mut app = test_app_with(dir.path(), config).await;
+            let long = Duration::from_secs(90);
+
+            assert_eq!(app.notification_for(NotifyEvent::Turn, long), Notify::Off);
+            app.handle_event(AppEvent::Focus(false));
+            assert_eq!(app.notification_for(NotifyEvent::Turn, long), Notify::Osc9);
+            assert_eq!(
+                app.notification_for(NotifyEvent::Turn, Duration::from_secs(5)),
+                Notify::Off
+            );
+            assert_eq!(app.notification_for(NotifyEvent::Verify, long), Notify::Off);
+            app.handle_event(AppEvent::Focus(true));
+            assert_eq!(app.notification_for(NotifyEvent::Turn, long), Notify::Off);
+        });
     }
 
-    out.push_str("## Agent Response\n");
-    out.push_str(agent_response.trim());
+    #[test]
+    fn tool_status_marks_are_shapes_and_can_drop_colors() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config = AppConfig {
+                accessibility: Some(crate::config::AccessibilityConfig {
+                    no_color_status: Some(true),
+                }),
+                ..AppConfig::default()
+            };
+            let mut app = test_app_with(dir.path(), config).await;
+            app.push_message(Message {
+                id: 0,
+                role: Role::Agent,
+                content: String::new(),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: Some(1),
+                local: false,
+            });
+            for (call_id, success) in [("c1", true), ("c2", false)] {
+                let mut tool = ToolOutput::new(
+                    call_id.into(),
+                    "bash".into(),
+                    r#"{"command":"ls"}"#.into(),
+                    "ls".into(),
+                    ".".into(),
+                    crate::sandbox::SandboxDecision::allow(),
+                    1,
+                    1,
+                );
+                tool.complete(success);
+                app.tool_outputs.push(tool);
+            }
 
-    out
-}
+            let mut store = std::mem::take(&mut app.render_store);
+            store.ensure_up_to_date(&app, &crate::theme::ui_theme(&app.config, None), 80);
+            app.render_store = store;
 
-fn truncate_for_summary(s: &str, max: usize) -> String {
-    let t = s.trim();
-    if t.len() <= max {
-        return t.to_string();
-    }
-    if max <= 3 {
-        return "...".to_string();
+            assert_eq!(app.render_store.find("✗ done, 1 failed").len(), 1);
+            let lines = app
+                .render_store
+                .visible_lines(0, app.render_store.total_height());
+            let marks: Vec<&ratatui::text::Span> = lines
+                .iter()
+                .flat_map(|l| l.spans.iter())
+                .filter(|s| s.content.trim() == "✓" || s.content.trim() == "✗")
+                .collect();
+            assert_eq!(marks.len(), 2, "one mark per tool");
+            assert!(marks.iter().all(|s| s.style.fg.is_none()));
+        });
     }
-    let mut out = t.to_string();
-    out.truncate(max.saturating_sub(3));
-    out.push_str("...");
-    out
-}
 
-/// Run background indexing - called from a blocking task
-fn run_background_index(
-    tx: mpsc::UnboundedSender<AppEvent>,
-    policy: Arc<SandboxPolicy>,
-    workspace_root: PathBuf,
-) -> Result<(usize, usize), String> {
-    // Catch any panics from dependencies
-    let result = std::panic::catch_unwind(|| {
-        let cwd = match std::env::current_dir() {
-            Ok(d) => d,
-            Err(e) => return Err(format!("cwd: {}", e)),
-        };
+    #[test]
+    fn readonly_toggle_blocks_edits_and_mutating_commands() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let edit = serde_json::json!({ "path": "src/main.rs" });
+            let commit = serde_json::json!({ "command": "git commit -am wip" });
+            let status = serde_json::json!({ "command": "git status" });
+            assert!(sandbox_decision_for_tool("edit_file", &edit, &app.sandbox_policy).allowed);
+
+            assert!(app.maybe_handle_command("/readonly"));
+            assert!(app.read_only);
+            let denied = sandbox_decision_for_tool("edit_file", &edit, &app.sandbox_policy);
+            assert!(!denied.allowed);
+            assert!(denied.reason.unwrap().starts_with("Read-only mode"));
+            assert!(!sandbox_decision_for_tool("bash", &commit, &app.sandbox_policy).allowed);
+            assert!(sandbox_decision_for_tool("bash", &status, &app.sandbox_policy).allowed);
+
+            assert!(app.maybe_handle_command("/readonly off"));
+            assert!(!app.read_only);
+            assert!(sandbox_decision_for_tool("edit_file", &edit, &app.sandbox_policy).allowed);
+        });
+    }
 
-        let checked_root = match policy.check_path_allowed(&cwd) {
-            Ok(p) => p,
-            Err(e) => return Err(e.to_string()),
-        };
+    #[test]
+    fn tool_budgets_and_model_hiding_refuse_calls_and_trim_the_tool_list() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config: AppConfig = toml::from_str(
+                r#"
+[tools]
+disabled = ["github"]
+max_calls_per_turn = { semantic_search = 2 }
+
+[tools.hide_for_models]
+"cheap/*" = ["lsp"]
+"#,
+            )
+            .unwrap();
+
+            let mut app = test_app_with(dir.path(), config).await;
+            app.model = "cheap/mini".into();
+            let call = |id: &str, name: &str| ToolCallMessage {
+                id: id.into(),
+                call_type: "function".into(),
+                function: crate::types::ToolCallFunction {
+                    name: name.into(),
+                    arguments: "{}".into(),
+                },
+                thought_signature: None,
+            };
 
-        let cfg = SearchConfig::for_workspace(&workspace_root);
-        let search = match SemanticSearch::new(cfg) {
-            Ok(s) => s,
-            Err(e) => return Err(format!("init: {}", e)),
-        };
+            assert_eq!(app.hidden_tools_for("cheap/mini"), ["github", "lsp"]);
+            assert_eq!(app.hidden_tools_for("big/model"), ["github"]);
+
+            let refusals = app.tool_budget_refusals(&[
+                call("1", "semantic_search"),
+                call("2", "lsp"),
+                call("3", "github"),
+                call("4", "semantic_search"),
+                call("5", "semantic_search"),
+                call("6", "rg"),
+            ]);
+            let mut refused: Vec<&str> = refusals.keys().map(String::as_str).collect();
+            refused.sort();
+            assert_eq!(refused, ["2", "3", "5"]);
+            assert!(refusals["5"].starts_with("Budget exceeded: `semantic_search`"));
+            assert!(refusals["2"].contains("not available to cheap/mini"));
+            // A spent budget also drops the tool from later requests this turn.
+            assert!(app
+                .hidden_tools_for("big/model")
+                .contains(&"semantic_search".to_string()));
+
+            app.tool_calls_this_turn.clear();
+            assert!(app
+                .tool_budget_refusals(&[call("7", "semantic_search")])
+                .is_empty());
+        });
+    }
 
-        match search.index_directory(&checked_root) {
-            Ok(stats) => Ok((stats.total_chunks, stats.total_files)),
-            Err(e) => Err(format!("index: {}", e)),
-        }
-    });
+    #[test]
+    fn turns_pause_at_autonomy_limits_until_the_user_decides() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let config: AppConfig =
+                toml::from_str("[general]\nmax_tool_rounds = 2\nmax_turn_minutes = 5\n").unwrap();
+
+            let mut app = test_app_with(dir.path(), config).await;
+            app.is_processing = true;
+            app.turn_tool_rounds = 1;
+            assert!(app.turn_limit_reached().is_none());
+
+            app.turn_tool_rounds = 2;
+            app.handle_event(AppEvent::ToolResultsReady(vec![("c1".into(), "ok".into())]));
+            let prompt = app.turn_limit_prompt.clone().unwrap();
+            assert_eq!((prompt.limit.as_str(), prompt.rounds), ("2 tool rounds", 2));
+            assert!(!app.is_processing);
+            assert!(app
+                .messages
+                .iter()
+                .any(|m| m.content.starts_with("[Paused: this turn reached 2 tool rounds")));
 
-    match result {
-        Ok(Ok((chunks, files))) => {
-            let _ = tx.send(AppEvent::IndexingComplete(chunks, files));
-            Ok((chunks, files))
-        }
-        Ok(Err(e)) => {
-            let _ = tx.send(AppEvent::IndexingError(e.clone()));
-            Err(e)
-        }
-        Err(_) => {
-            let err = "indexing panicked".to_string();
-            let _ = tx.send(AppEvent::IndexingError(err.clone()));
-            Err(err)
-        }
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Char('n'),
+                KeyModifiers::NONE,
+            )));
+            assert!(app.turn_limit_prompt.is_none());
+            assert!(!app.is_processing);
+
+            // Continuing counts from where the turn was paused, rounds and time alike.
+            app.turn_limits_from = (2, Instant::now());
+            assert!(app.turn_limit_reached().is_none());
+            app.turn_limits_from.1 = Instant::now() - Duration::from_secs(6 * 60);
+            assert_eq!(app.turn_limit_reached().as_deref(), Some("6 minutes"));
+        });
     }
-}
 
-fn index_file_exists(workspace_root: &std::path::Path) -> bool {
-    let index_dir = index_dir_for_workspace(workspace_root);
-    let index_path = index_dir.join("index.bin");
-    std::fs::metadata(index_path)
-        .map(|m| m.len() > 0)
-        .unwrap_or(false)
-}
+    #[test]
+    fn unreachable_provider_queues_the_turn_until_it_reconnects() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            let notes = |app: &App, prefix: &str| {
+                app.messages
+                    .iter()
+                    .filter(|m| m.content.starts_with(prefix))
+                    .count()
+            };
 
-fn load_existing_index_status() -> IndexingStatus {
-    // Note: this reads *existing* index metadata only; it does not start indexing.
-    // The app will kick off background indexing from main.rs depending on config.
-    let workspace_root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-    if !index_file_exists(&workspace_root) {
-        return IndexingStatus::NotStarted;
+            app.is_processing = true;
+            app.handle_event(AppEvent::AgentOffline("connection refused".into()));
+            app.handle_event(AppEvent::AgentOffline("connection refused".into()));
+            let offline = app.offline.clone().unwrap();
+            assert_eq!(offline.attempts, 2);
+            assert!(offline.retry_at.is_some());
+            // The turn stays in flight: nothing failed, one note explains the wait.
+            assert!(app.is_processing);
+            assert_eq!(notes(&app, "[Offline: connection refused]"), 1);
+            assert_eq!(notes(&app, "[Error:"), 0);
+            assert!(app
+                .offline_banner()
+                .is_some_and(|b| b.contains("resending in 10s (attempt 3)")));
+
+            // A timer from an earlier attempt doesn't resend.
+            app.handle_event(AppEvent::Reconnect(1));
+            assert!(app.offline.as_ref().unwrap().retry_at.is_some());
+
+            app.handle_event(AppEvent::AgentChunk("Hello".into()));
+            assert!(app.offline.is_none());
+            assert_eq!(notes(&app, "[Reconnected after"), 1);
+
+            app.handle_event(AppEvent::AgentOffline("timed out".into()));
+            app.handle_event(AppEvent::Input(KeyEvent::new(
+                KeyCode::Esc,
+                KeyModifiers::NONE,
+            )));
+            assert!(app.offline.is_none());
+            assert!(!app.is_processing);
+            assert_eq!(notes(&app, "[Error: timed out]"), 1);
+        });
     }
 
-    let cfg = SearchConfig::for_workspace(&workspace_root);
-    match SemanticSearch::new(cfg) {
-        Ok(search) => {
-            let stats = search.stats();
-            IndexingStatus::Complete {
-                chunks: stats.total_chunks,
-                files: stats.total_files,
-            }
-        }
-        Err(_) => IndexingStatus::NotStarted,
+    #[test]
+    fn token_meter_tracks_the_transcript_and_model_window() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let dir = tempfile::TempDir::new().unwrap();
+            let mut app = test_app(dir.path()).await;
+            app.model = "openai/gpt-4o".into();
+            assert_eq!(app.context_window(), Some(128_000));
+            app.models = vec![ModelInfo {
+                id: "openai/gpt-4o".into(),
+                context_window: Some(64_000),
+                prompt_price: None,
+                completion_price: None,
+            }];
+            assert_eq!(app.context_window(), Some(64_000));
+
+            let before = app.context_tokens();
+            let message = |content: &str, local| Message {
+                id: 0,
+                role: Role::User,
+                content: content.into(),
+                reasoning: None,
+                tool_calls: None,
+                tool_group_id: None,
+                local,
+            };
+            // Local notes never reach the model.
+            app.push_message(message("a note that stays in the terminal", true));
+            assert_eq!(app.context_tokens(), before);
+            app.push_message(message(&"word ".repeat(1_000), false));
+            let after = app.context_tokens();
+            assert!(
+                (before + 1_000..before + 1_100).contains(&after),
+                "{}",
+                after
+            );
+        });
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
     #[test]
-    fn tool_runs_are_linked_by_call_id() {
+    fn tool_pager_pages_the_whole_output_and_follows_running_tools() {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
-            let tmp = std::env::temp_dir()
-                .join(format!("lorikeet-test-{}", crate::memory::types::unix_ts()));
-            let _ = std::fs::create_dir_all(&tmp);
-
-            let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
-            let config = AppConfig::default();
-            let policy = Arc::new(SandboxPolicy::from_config(
-                config.clone(),
-                tmp.clone(),
-                crate::tools::TOOL_NAMES,
-            ));
-            let memory = Arc::new(MemoryManager::init(&tmp).await.unwrap());
-
-            let mut app = App::new(
-                tx,
-                LlmProvider::OpenRouter,
-                "k".into(),
-                None,
-                policy.clone(),
-                config,
-                tmp.clone(),
-                memory,
-            );
-            app.current_turn_id = 1;
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut app = test_app(&tmp).await;
+            let key = |code| AppEvent::Input(KeyEvent::new(code, KeyModifiers::NONE));
+            let ctrl = |c| AppEvent::Input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+            let output = |chunk: &str| {
+                AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                    call_id: "call_1".into(),
+                    chunk: chunk.into(),
+                })
+            };
+
+            app.handle_event(ctrl('o'));
+            assert!(app.tool_pager.is_none());
 
             app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
-                call_id: "call-a".into(),
-                tool: "bash".into(),
-                args_raw: r#"{"command":"echo a"}"#.into(),
-                args_summary: "echo a".into(),
-                cwd: tmp.clone(),
-                sandbox: crate::sandbox::SandboxDecision::allow(),
-            }));
-            app.handle_event(AppEvent::ToolStart(crate::events::ToolStartEvent {
-                call_id: "call-b".into(),
+                call_id: "call_1".into(),
                 tool: "bash".into(),
-                args_raw: r#"{"command":"echo b"}"#.into(),
-                args_summary: "echo b".into(),
-                cwd: tmp.clone(),
+                args_raw: r#"{"command":"cargo test"}"#.into(),
+                args_summary: "cargo test".into(),
+                cwd: tmp,
                 sandbox: crate::sandbox::SandboxDecision::allow(),
             }));
-
-            app.handle_event(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
-                call_id: "call-a".into(),
-                chunk: "A
-"
-                .into(),
-            }));
-
-            assert_eq!(app.tool_outputs.len(), 2);
-            assert!(app.tool_outputs[0].output.contains('A'));
-            assert!(app.tool_outputs[1].output.is_empty());
+            let long: String = (1..=6_000).map(|i| format!("line {}\n", i)).collect();
+            app.handle_event(output(&long));
+            app.handle_event(output("test result: o"));
+            app.handle_event(ctrl('o'));
+            {
+                let pager = app.tool_pager.as_ref().expect("pager opened");
+                // Everything, not just the tail the trace keeps.
+                assert_eq!(pager.line_count(), 6_001);
+                assert_eq!(pager.lines[0], "line 1");
+                assert!(pager.follow);
+            }
+
+            // Output that arrives while it's open is appended, the open line included.
+            app.handle_event(output("k. 3 passed\ndone\n"));
+            let pager = app.tool_pager.as_mut().unwrap();
+            assert_eq!(pager.line_count(), 6_002);
+            assert_eq!(pager.lines[6_000], "test result: ok. 3 passed");
+            pager.page = 20;
+
+            app.handle_event(key(KeyCode::Char('g')));
+            let pager = app.tool_pager.as_ref().unwrap();
+            assert_eq!(pager.scroll, 0);
+            assert!(!pager.follow);
+
+            app.handle_event(key(KeyCode::Char('/')));
+            for c in "LINE 4500".chars() {
+                app.handle_event(key(KeyCode::Char(c)));
+            }
+            app.handle_event(key(KeyCode::Enter));
+            let pager = app.tool_pager.as_ref().unwrap();
+            assert_eq!(pager.match_line, Some(4_499));
+            assert!((pager.scroll..pager.scroll + 20).contains(&4_499));
+
+            app.handle_event(key(KeyCode::Char('w')));
+            assert!(app.tool_pager.as_ref().unwrap().wrap);
+            // Esc clears the search first, then closes.
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.tool_pager.as_ref().unwrap().query.is_none());
+            app.handle_event(key(KeyCode::Esc));
+            assert!(app.tool_pager.is_none());
         });
     }
 
     #[test]
-    fn tab_toggles_plan_mode_backtab_switches_pane() {
+    fn replay_plays_tool_calls_and_never_sends() {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
-            let tmp = std::env::temp_dir()
-                .join(format!("lorikeet-test-{}", crate::memory::types::unix_ts()));
-            let _ = std::fs::create_dir_all(&tmp);
-
-            let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
-            let config = AppConfig::default();
-            let policy = Arc::new(SandboxPolicy::from_config(
-                config.clone(),
-                tmp.clone(),
-                crate::tools::TOOL_NAMES,
-            ));
-            let memory = Arc::new(MemoryManager::init(&tmp).await.unwrap());
-            let mut app = App::new(
-                tx,
-                LlmProvider::OpenRouter,
-                "k".into(),
-                None,
-                policy,
-                config,
-                tmp,
-                memory,
-            );
-
-            assert!(!app.plan_mode);
-            app.handle_event(AppEvent::Input(KeyEvent::new(
-                KeyCode::Tab,
-                KeyModifiers::NONE,
-            )));
-            assert!(app.plan_mode);
-            app.handle_event(AppEvent::Input(KeyEvent::new(
-                KeyCode::Tab,
-                KeyModifiers::NONE,
-            )));
-            assert!(!app.plan_mode);
-
-            assert_eq!(app.active_pane, Pane::Chat);
-            app.handle_event(AppEvent::Input(KeyEvent::new(
-                KeyCode::BackTab,
-                KeyModifiers::SHIFT,
-            )));
-            assert_eq!(app.active_pane, Pane::Context);
-            app.handle_event(AppEvent::Input(KeyEvent::new(
-                KeyCode::BackTab,
-                KeyModifiers::SHIFT,
-            )));
-            assert_eq!(app.active_pane, Pane::Chat);
+            let dir = tempfile::TempDir::new().unwrap();
+            let tmp = dir.path().to_path_buf();
+            let mut app = test_app(&tmp).await;
+            app.replaying = Some(" REPLAY 1 · 1x ".into());
+            let message = |role: &str, content: &str, group: Option<u64>| SessionEvent::Message {
+                ts: 100,
+                role: role.into(),
+                content: content.into(),
+                reasoning: None,
+                tool_group_id: group,
+                local: false,
+                model: Some("openai/gpt-5".into()),
+                plan_mode: None,
+                config_hash: None,
+            };
+            let events = vec![
+                message("user", "run the tests", None),
+                message("assistant", "", Some(1)),
+                SessionEvent::Tool {
+                    ts: 104,
+                    tool: "bash".into(),
+                    target: "cargo test".into(),
+                    output: "test result: ok".into(),
+                    status: "success".into(),
+                    elapsed_ms: 3_000,
+                    call_id: Some("call_1".into()),
+                    args_raw: None,
+                    cwd: None,
+                    sandbox_allowed: None,
+                    sandbox_reason: None,
+                    group_id: Some(1),
+                    output_file: None,
+                },
+            ];
+            for cue in [
+                replay::Cue::Event(0),
+                replay::Cue::Event(1),
+                replay::Cue::ToolStart(2),
+            ] {
+                app.replay_cue(&events, cue, &tmp);
+            }
+            assert_eq!(app.messages.len(), 2);
+            let last = app.messages.last().unwrap().id;
+            assert_eq!(app.message_models.get(&last).unwrap(), "openai/gpt-5");
+            assert_eq!(app.tool_outputs.len(), 1);
+            assert_eq!(app.tool_outputs[0].status, ToolStatus::Running);
+            assert_eq!(app.tool_outputs[0].turn_id, 1);
+            // A running tool keeps the screen ticking for its spinner and elapsed time.
+            assert!(app.animating());
+            assert!(app.spinner_frame(4) < 4);
+
+            app.replay_cue(&events, replay::Cue::ToolDone(2), &tmp);
+            assert_eq!(app.tool_outputs.len(), 1);
+            assert_eq!(app.tool_outputs[0].status, ToolStatus::Success);
+            assert_eq!(app.tool_outputs[0].output, "test result: ok");
+
+            // Typing into a replay doesn't reach the model.
+            app.set_input("and now?");
+            app.submit_message();
+            assert!(!app.is_processing);
+            assert_eq!(app.input, "");
+            assert!(app.messages.last().unwrap().content.contains("Replay"));
         });
     }
 
     #[test]
-    fn go_sets_tools_override_and_ephemeral_message_then_consumes() {
+    fn search_and_read_loops_route_to_the_explore_model() {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
-            let tmp = std::env::temp_dir()
-                .join(format!("lorikeet-test-{}", crate::memory::types::unix_ts()));
-            let _ = std::fs::create_dir_all(&tmp);
-
-            let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
-            let config = AppConfig::default();
-            let policy = Arc::new(SandboxPolicy::from_config(
-                config.clone(),
-                tmp.clone(),
-                crate::tools::TOOL_NAMES,
-            ));
-            let memory = Arc::new(MemoryManager::init(&tmp).await.unwrap());
-            let mut app = App::new(
-                tx,
-                LlmProvider::OpenRouter,
-                "k".into(),
-                None,
-                policy,
-                config,
-                tmp,
-                memory,
-            );
-
-            app.plan_mode = true;
-            app.prepare_go();
-            assert!(app.tools_enabled_for_next_call());
+            let dir = tempfile::TempDir::new().unwrap();
+            let config: AppConfig = toml::from_str(
+                "[routing]\nexplore_model = \"cheap/mini\"\nmax_explore_calls = 2\n",
+            )
+            .unwrap();
 
-            let (tools_enabled, ephemeral) = app.take_next_call_overrides();
-            assert!(tools_enabled);
-            assert!(ephemeral.is_some());
+            let mut app = test_app_with(dir.path(), config).await;
+            app.model = "big/model".into();
 
-            // One-shot override resets.
-            assert!(!app.tools_override_next);
-            assert!(app.ephemeral_user_message.is_none());
+            // The first call of a turn, and any after edits, stay with the main model.
+            assert_eq!(app.explore_model_for_call(ToolSet::Full), None);
+            app.last_round_exploring = true;
+            assert_eq!(
+                app.explore_model_for_call(ToolSet::Full).as_deref(),
+                Some("cheap/mini")
+            );
+            assert_eq!(app.explore_model_for_call(ToolSet::Plan), None);
+            app.explore_calls = 2;
+            assert_eq!(app.explore_model_for_call(ToolSet::Full), None);
+
+            app.push_local_note("found it in src/lib.rs:10");
+            app.call_model = "cheap/mini".into();
+            app.tag_last_message_model();
+            let id = app.messages.last().unwrap().id;
+            assert_eq!(app.message_model(id), Some("cheap/mini"));
+            app.config.routing = None;
+            assert_eq!(app.message_model(id), None);
         });
     }
 }
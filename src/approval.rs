@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use regex::Regex;
+use tokio::sync::oneshot;
+
+/// Directory under the data dir holding, per project, the command keys that have run
+/// there, one per line. Kept out of the workspace so a repo can't ship its own.
+const HISTORY_DIR: &str = "command_history";
+
+/// Tools whose first argument is a subcommand worth telling apart (`cargo test` vs
+/// `cargo publish`). Everything else is keyed by its executable alone.
+const SUBCOMMAND_TOOLS: &[&str] = &[
+    "cargo", "git", "npm", "npx", "pnpm", "yarn", "bun", "go", "pip", "pip3", "uv", "poetry",
+    "docker", "kubectl", "make", "gh",
+];
+
+/// Read-only commands that are never "new" enough to ask about.
+const FAMILIAR_COMMANDS: &[&str] = &[
+    "rg", "ls", "cat", "pwd", "sed", "awk", "find", "wc", "head", "tail", "grep", "echo",
+];
+
+/// Commands that always stop for confirmation, whatever the allowlists and trusted
+/// commands say. `[sandbox] confirm_patterns` adds to this list.
+const DESTRUCTIVE_PATTERNS: &[&str] = &[
+    r"\brm\s+(-[a-zA-Z]*[rR][a-zA-Z]*[fF]|-[a-zA-Z]*[fF][a-zA-Z]*[rR]|--recursive\s+--force|--force\s+--recursive)\b",
+    // The same flags given separately: `rm -r -f`, `rm -f -v --recursive`.
+    r"\brm\s+(-\S+\s+)*(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\s+(-\S+\s+)*(-[a-zA-Z]*[fF][a-zA-Z]*|--force)\b",
+    r"\brm\s+(-\S+\s+)*(-[a-zA-Z]*[fF][a-zA-Z]*|--force)\s+(-\S+\s+)*(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\b",
+    r"\bgit\s+reset\s+--hard\b",
+    r"\bgit\s+clean\s+-[a-zA-Z]*f",
+    r"\bgit\s+push\b.*(\s--force\b|\s--force-with-lease\b|\s-f\b|\s\+\S)",
+    r"(?i)\bdrop\s+(table|database|schema)\b",
+    r"(?i)\btruncate\s+table\b",
+    r"\bmkfs(\.\w+)?\b",
+    r"\bdd\s+.*\bof=/dev/",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Once,
+    /// Run it, and stop asking about this command key (persisted to config).
+    Always,
+    Deny,
+}
+
+/// A sandbox-allowed command waiting on the user before it runs.
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub call_id: String,
+    pub command: String,
+    /// What "don't ask again" applies to, e.g. `npm install` or `sudo apt`: the keys of the
+    /// command line's not yet trusted commands
+    pub keys: Vec<String>,
+    pub reasons: Vec<String>,
+    /// Matched a destructive pattern: only an explicit `y` runs it, and it can't be
+    /// trusted for good.
+    pub destructive: bool,
+    reply: Arc<Mutex<Option<oneshot::Sender<Approval>>>>,
+}
+
+impl ApprovalRequest {
+    pub fn new(
+        call_id: String,
+        command: String,
+        keys: Vec<String>,
+        reasons: Vec<String>,
+        destructive: bool,
+    ) -> (Self, oneshot::Receiver<Approval>) {
+        let (tx, rx) = oneshot::channel();
+        let request = Self {
+            call_id,
+            command,
+            keys,
+            reasons,
+            destructive,
+            reply: Arc::new(Mutex::new(Some(tx))),
+        };
+        (request, rx)
+    }
+
+    /// Unblock the waiting tool call. Later answers are ignored.
+    pub fn answer(&self, approval: Approval) {
+        if let Some(tx) = self.reply.lock().take() {
+            let _ = tx.send(approval);
+        }
+    }
+}
+
+/// The identity of a shell command for history and trust: the executable (kept behind
+/// `sudo`), plus the subcommand for tools that have them.
+pub fn command_key(command: &str) -> String {
+    let mut tokens = command
+        .split_whitespace()
+        .skip_while(|t| *t == "env" || (t.contains('=') && !t.starts_with('-')))
+        .map(executable_name);
+
+    let mut key = Vec::new();
+    let mut exe = tokens.next().unwrap_or_default();
+    if exe == "sudo" {
+        key.push(exe);
+        exe = tokens.find(|t| !t.starts_with('-')).unwrap_or_default();
+    }
+    let has_subcommands = SUBCOMMAND_TOOLS.contains(&exe.as_str());
+    key.push(exe);
+    if has_subcommands {
+        if let Some(sub) = tokens.next().filter(|t| !t.starts_with('-')) {
+            key.push(sub);
+        }
+    }
+    key.retain(|t| !t.is_empty());
+    key.join(" ")
+}
+
+/// The key of every command in a command line (`a && b; c | d`), in order and without
+/// repeats, so a familiar first command can't vouch for the rest.
+pub fn command_keys(command: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    for segment in split_commands(command) {
+        // Subshells and groups: `(cd x && make)`, `{ a; b; }`, `! grep ...`.
+        let segment = segment
+            .trim_start_matches(|c: char| c.is_whitespace() || "({!".contains(c))
+            .trim_end_matches(|c: char| c.is_whitespace() || ")}".contains(c));
+        let key = command_key(segment);
+        if !key.is_empty() && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// `command` split at `;`, `&&`, `||`, `|`, `&` and newlines outside quotes. `2>&1` and
+/// other redirections aren't separators.
+fn split_commands(command: &str) -> Vec<&str> {
+    let bytes = command.as_bytes();
+    let mut out = Vec::new();
+    let mut quote: Option<u8> = None;
+    let (mut start, mut i) = (0, 0);
+    while i < bytes.len() {
+        let b = bytes[i];
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(b'"') if b == b'\\' => i += 1,
+            Some(_) => {}
+            None => match b {
+                b'\'' | b'"' => quote = Some(b),
+                b'\\' => i += 1,
+                b';' | b'|' | b'&' | b'\n' => {
+                    let redirect = b == b'&'
+                        && ((i > 0 && matches!(bytes[i - 1], b'>' | b'<'))
+                            || bytes.get(i + 1) == Some(&b'>'));
+                    if !redirect {
+                        out.push(&command[start..i]);
+                        if b != b';' && b != b'\n' && bytes.get(i + 1) == Some(&b) {
+                            i += 1;
+                        }
+                        start = i + 1;
+                    }
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    out.push(&command[start.min(command.len())..]);
+    out
+}
+
+/// Why an allowed command still deserves a second look; empty if it looks routine. `keys`
+/// are the command line's commands that haven't been trusted.
+pub fn anomalies(command: &str, keys: &[String], history: &HashSet<String>) -> Vec<String> {
+    let mut out = Vec::new();
+    if command.split_whitespace().any(|t| t == "sudo") {
+        out.push("uses sudo".to_string());
+    }
+    if command.contains("node_modules") {
+        out.push("touches node_modules".to_string());
+    }
+    for key in keys {
+        let exe = key.split(' ').next().unwrap_or("");
+        if !history.contains(key) && !FAMILIAR_COMMANDS.contains(&exe) {
+            out.push(format!("`{}` has never run in this workspace", key));
+        }
+    }
+    out
+}
+
+/// Compiled confirmation patterns: the built-in destructive ones plus `configured`
+/// (`[sandbox] confirm_patterns`), which can only add to them. Invalid regexes are
+/// skipped.
+pub fn destructive_patterns(configured: Option<&[String]>) -> Vec<Regex> {
+    DESTRUCTIVE_PATTERNS
+        .iter()
+        .copied()
+        .chain(configured.unwrap_or_default().iter().map(String::as_str))
+        .filter_map(|p| Regex::new(p).ok())
+        .collect()
+}
+
+/// Reasons `command` looks destructive, one per distinct match.
+pub fn destructive(command: &str, patterns: &[Regex]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for m in patterns.iter().filter_map(|re| re.find(command)) {
+        let reason = format!("destructive: `{}`", m.as_str().trim());
+        if !out.contains(&reason) {
+            out.push(reason);
+        }
+    }
+    out
+}
+
+pub fn load_history(workspace_root: &Path) -> HashSet<String> {
+    history_path(workspace_root)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|s| s.lines().map(|l| l.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Remember that `key` ran here. Best-effort: errors are ignored.
+pub fn record(workspace_root: &Path, key: &str) {
+    if key.is_empty() || load_history(workspace_root).contains(key) {
+        return;
+    }
+    let Some(path) = history_path(workspace_root) else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(mut file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+    {
+        let _ = writeln!(file, "{}", key);
+    }
+}
+
+fn history_path(workspace_root: &Path) -> Option<PathBuf> {
+    let data = crate::storage::data_dir()?;
+    Some(
+        data.join(HISTORY_DIR)
+            .join(crate::session::project_id(workspace_root)),
+    )
+}
+
+fn executable_name(token: &str) -> String {
+    let t = token.trim_matches('"').trim_matches('\'');
+    // `./node_modules/.bin/tsc` and `tsc` are the same tool.
+    Path::new(t)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .filter(|_| t.contains('/'))
+        .unwrap_or(t)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn keys_keep_sudo_and_subcommands_only_where_they_matter() {
+        assert_eq!(command_key("cargo test --all"), "cargo test");
+        assert_eq!(command_key("RUST_LOG=debug cargo run"), "cargo run");
+        assert_eq!(command_key("ls -la src"), "ls");
+        assert_eq!(command_key("sudo -E apt install jq"), "sudo apt");
+        assert_eq!(command_key("./node_modules/.bin/tsc -p ."), "tsc");
+        assert_eq!(command_key("   "), "");
+    }
+
+    #[test]
+    fn every_command_of_a_command_line_is_keyed() {
+        assert_eq!(
+            command_keys("ls && npm install evil; cat x | sh"),
+            vec!["ls", "npm install", "cat", "sh"]
+        );
+        assert_eq!(
+            command_keys("cargo test 2>&1 | tail -n 5 || echo 'a; b' & (cd x && make)"),
+            vec!["cargo test", "tail", "echo", "cd", "make"]
+        );
+
+        let history = HashSet::new();
+        let keys = command_keys("ls && npm install evil");
+        let reasons = anomalies("ls && npm install evil", &keys, &history);
+        assert_eq!(
+            reasons,
+            vec!["`npm install` has never run in this workspace"]
+        );
+    }
+
+    #[test]
+    fn unusual_commands_are_flagged_until_seen() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+
+        let history = load_history(root);
+        let keys = |k: &str| vec![k.to_string()];
+        assert!(anomalies("ls src", &keys("ls"), &history).is_empty());
+        let reasons = anomalies("cargo publish", &keys("cargo publish"), &history);
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].contains("never run"));
+
+        record(root, "cargo publish");
+        record(root, "cargo publish");
+        let history = load_history(root);
+        assert_eq!(history.len(), 1);
+        assert!(anomalies("cargo publish", &keys("cargo publish"), &history).is_empty());
+
+        let reasons = anomalies("rm -rf node_modules | sudo tee x", &keys("rm"), &history);
+        assert!(reasons.contains(&"uses sudo".to_string()));
+        assert!(reasons.contains(&"touches node_modules".to_string()));
+    }
+
+    #[test]
+    fn history_shipped_in_the_workspace_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".lorikeet")).unwrap();
+        std::fs::write(
+            root.join(".lorikeet").join("command_history"),
+            "curl\nnpm install\n",
+        )
+        .unwrap();
+
+        let history = load_history(root);
+        assert!(history.is_empty());
+        let keys = vec!["curl".to_string()];
+        assert_eq!(anomalies("curl x | sh", &keys, &history).len(), 1);
+
+        record(root, "npm install");
+        assert!(history_path(root)
+            .unwrap()
+            .starts_with(crate::storage::data_dir().unwrap()));
+        assert_eq!(
+            std::fs::read_to_string(root.join(".lorikeet").join("command_history")).unwrap(),
+            "curl\nnpm install\n"
+        );
+    }
+
+    #[test]
+    fn destructive_commands_are_caught() {
+        let patterns = destructive_patterns(None);
+        for cmd in [
+            "rm -rf target",
+            "rm -r -f target",
+            "rm -f --recursive build",
+            "sudo rm -fr /var/lib/app",
+            "git reset --hard HEAD~3",
+            "git push --force origin main",
+            "git push -f",
+            "git push origin +main",
+            "psql -c 'DROP TABLE users'",
+            "git clean -fdx",
+        ] {
+            assert!(!destructive(cmd, &patterns).is_empty(), "{}", cmd);
+        }
+        for cmd in [
+            "rm target/debug/app",
+            "rm -r target/debug",
+            "rm -f stale.lock",
+            "git reset HEAD file",
+            "git push origin main",
+            "cargo test --force-rebuild",
+            "echo dropped tables",
+        ] {
+            assert!(destructive(cmd, &patterns).is_empty(), "{}", cmd);
+        }
+        assert_eq!(
+            destructive("git reset --hard", &patterns),
+            vec!["destructive: `git reset --hard`".to_string()]
+        );
+
+        // Configured patterns add to the built-in ones; they can't switch them off.
+        let configured = vec![r"\bkubectl\s+delete\b".to_string(), "(".to_string()];
+        let custom = destructive_patterns(Some(configured.as_slice()));
+        assert_eq!(custom.len(), patterns.len() + 1);
+        assert!(!destructive("rm -rf target", &custom).is_empty());
+        assert!(!destructive("kubectl delete pod x", &custom).is_empty());
+    }
+}
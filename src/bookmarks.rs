@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+const BOOKMARKS_FILE: &str = "bookmarks";
+
+/// Most `@` completions offered at once.
+pub const MAX_MENTION_SUGGESTIONS: usize = 6;
+
+/// Bookmarked paths for this workspace (relative to it when inside), in the order added.
+pub fn load(workspace_root: &Path) -> Vec<String> {
+    std::fs::read_to_string(bookmarks_path(workspace_root))
+        .map(|s| {
+            s.lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn save(workspace_root: &Path, bookmarks: &[String]) -> std::io::Result<()> {
+    let path = bookmarks_path(workspace_root);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut content = bookmarks.join("\n");
+    content.push('\n');
+    std::fs::write(path, content)
+}
+
+/// The form a path is bookmarked under: relative to the workspace when inside it.
+/// Errors if it doesn't exist.
+pub fn normalize(workspace_root: &Path, path: &str) -> Result<String, String> {
+    let path = path.trim().trim_start_matches('@');
+    if path.is_empty() {
+        return Err("Usage: /bookmark add <path>".to_string());
+    }
+    let full = if Path::new(path).is_absolute() {
+        PathBuf::from(path)
+    } else {
+        workspace_root.join(path)
+    };
+    if !full.exists() {
+        return Err(format!("No such file or directory: {}", path));
+    }
+    let shown = full
+        .strip_prefix(workspace_root)
+        .map(|p| p.to_path_buf())
+        .unwrap_or(full);
+    Ok(shown
+        .to_string_lossy()
+        .trim_start_matches("./")
+        .trim_end_matches('/')
+        .to_string())
+}
+
+/// System-context note naming the bookmarks as the files that matter most here.
+pub fn system_hint(bookmarks: &[String]) -> Option<String> {
+    if bookmarks.is_empty() {
+        return None;
+    }
+    let mut out = String::from(
+        "Bookmarked paths (the user marked these as important in this workspace; check them first when relevant):",
+    );
+    for b in bookmarks {
+        out.push_str("\n- ");
+        out.push_str(b);
+    }
+    Some(out)
+}
+
+/// The `@` mention being typed at byte offset `cursor`: (offset of the `@`, text after it).
+pub fn mention_at(input: &str, cursor: usize) -> Option<(usize, &str)> {
+    let before = input.get(..cursor)?;
+    let start = before
+        .rfind(|c: char| c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let word = &before[start..];
+    word.strip_prefix('@').map(|partial| (start, partial))
+}
+
+/// Completions for a partial mention: bookmarks first (always offered), then the other
+/// known paths, case-insensitive substring matches, without repeats.
+pub fn mention_candidates(partial: &str, bookmarks: &[String], others: &[String]) -> Vec<String> {
+    let needle = partial.to_lowercase();
+    let mut out: Vec<String> = Vec::new();
+    for path in bookmarks.iter().chain(others) {
+        if out.len() >= MAX_MENTION_SUGGESTIONS {
+            break;
+        }
+        if path.to_lowercase().contains(&needle) && !out.contains(path) {
+            out.push(path.clone());
+        }
+    }
+    out
+}
+
+fn bookmarks_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(".lorikeet").join(BOOKMARKS_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn bookmarks_round_trip_relative_to_the_workspace() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/app")).unwrap();
+        std::fs::write(dir.path().join("src/app/main.rs"), "").unwrap();
+
+        let inside = normalize(dir.path(), "@src/app/main.rs").unwrap();
+        assert_eq!(inside, "src/app/main.rs");
+        let absolute = dir.path().join("src/app").to_string_lossy().to_string();
+        assert_eq!(normalize(dir.path(), &absolute).unwrap(), "src/app");
+        assert!(normalize(dir.path(), "nope.rs").is_err());
+
+        save(dir.path(), &[inside.clone(), "src/app".into()]).unwrap();
+        assert_eq!(load(dir.path()), vec![inside, "src/app".to_string()]);
+    }
+
+    #[test]
+    fn mentions_complete_bookmarks_first() {
+        assert_eq!(mention_at("look at @src/ma", 15), Some((8, "src/ma")));
+        assert_eq!(mention_at("@", 1), Some((0, "")));
+        assert_eq!(mention_at("email me@x", 10), None);
+        assert_eq!(mention_at("@src and more", 13), None);
+
+        let bookmarks = vec!["docs/API.md".to_string()];
+        let recent = vec!["src/api.rs".to_string(), "docs/API.md".to_string()];
+        assert_eq!(
+            mention_candidates("api", &bookmarks, &recent),
+            vec!["docs/API.md".to_string(), "src/api.rs".to_string()]
+        );
+        assert_eq!(
+            mention_candidates("", &bookmarks, &[]),
+            vec!["docs/API.md".to_string()]
+        );
+    }
+}
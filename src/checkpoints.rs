@@ -6,7 +6,9 @@ use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
+use crate::config::CheckpointsConfig;
 use crate::session::SessionStore;
+use crate::tool_registry::ToolAccess;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -43,9 +45,30 @@ struct Manifest {
     files: Vec<FileEntry>,
 }
 
+/// Whether a tool group that changes files on disk should get an automatic checkpoint, given when the previous one
+/// was taken (`(turn, unix seconds)`). At most one per turn, and none within `min_interval`
+/// seconds of the last, so bursts of small edit groups don't pile up checkpoints.
+pub fn auto_checkpoint_due<'a>(
+    tools: impl IntoIterator<Item = &'a str>,
+    last: Option<(u64, i64)>,
+    turn: u64,
+    now: i64,
+    min_interval: u64,
+) -> bool {
+    let edits = |t: &str| crate::tool_registry::access(t) == Some(ToolAccess::Edit);
+    if !tools.into_iter().any(edits) {
+        return false;
+    }
+    match last {
+        Some((last_turn, _)) if last_turn == turn => false,
+        Some((_, at)) => now.saturating_sub(at) >= min_interval as i64,
+        None => true,
+    }
+}
+
 pub fn checkpoints_base_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home dir"))?;
-    Ok(home.join(".lorikeet").join("checkpoints"))
+    let data = crate::storage::data_dir().ok_or_else(|| anyhow!("Could not determine home dir"))?;
+    Ok(data.join("checkpoints"))
 }
 
 pub fn checkpoints_dir_for_workspace(workspace_root: &Path) -> Result<PathBuf> {
@@ -67,10 +90,16 @@ pub fn load_checkpoint_meta(workspace_root: &Path, id: &str) -> Result<Checkpoin
 }
 
 pub fn list_checkpoints(workspace_root: &Path, limit: usize) -> Result<Vec<CheckpointMeta>> {
-    let dir = checkpoints_dir_for_workspace(workspace_root)?;
+    let mut metas = list_in(&checkpoints_dir_for_workspace(workspace_root)?);
+    metas.truncate(limit);
+    Ok(metas)
+}
+
+/// Every checkpoint under `dir`, newest first.
+fn list_in(dir: &Path) -> Vec<CheckpointMeta> {
     let mut metas = Vec::new();
-    let Ok(rd) = fs::read_dir(&dir) else {
-        return Ok(Vec::new());
+    let Ok(rd) = fs::read_dir(dir) else {
+        return Vec::new();
     };
     for ent in rd.flatten() {
         let p = ent.path();
@@ -87,8 +116,89 @@ pub fn list_checkpoints(workspace_root: &Path, limit: usize) -> Result<Vec<Check
         metas.push(meta);
     }
     metas.sort_by_key(|m| std::cmp::Reverse(m.created_at_unix));
-    metas.truncate(limit);
-    Ok(metas)
+    metas
+}
+
+/// Disk space a checkpoint takes, in bytes.
+pub fn checkpoint_size(workspace_root: &Path, id: &str) -> u64 {
+    checkpoint_dir(workspace_root, id)
+        .map(|dir| dir_size(&dir))
+        .unwrap_or(0)
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// How many checkpoints to keep, from `[checkpoints]`. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    pub max_count: Option<usize>,
+    pub max_age_days: Option<u64>,
+    pub max_size_bytes: Option<u64>,
+}
+
+impl Retention {
+    pub fn from_config(cfg: Option<&CheckpointsConfig>) -> Self {
+        Self {
+            max_count: cfg.and_then(|c| c.max_count),
+            max_age_days: cfg.and_then(|c| c.max_age_days),
+            max_size_bytes: cfg.and_then(|c| c.max_size_mb).map(|mb| mb * 1024 * 1024),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a prune removed.
+#[derive(Debug, Clone, Default)]
+pub struct PruneReport {
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+/// Delete the oldest checkpoints beyond `retention`. The newest one is always kept.
+pub fn prune_checkpoints(workspace_root: &Path, retention: Retention) -> Result<PruneReport> {
+    prune_in(
+        &checkpoints_dir_for_workspace(workspace_root)?,
+        retention,
+        unix_ts(),
+    )
+}
+
+fn prune_in(dir: &Path, retention: Retention, now: i64) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+    if retention.is_unlimited() {
+        return Ok(report);
+    }
+    let mut kept_bytes = 0u64;
+    for (i, meta) in list_in(dir).iter().enumerate() {
+        let path = dir.join(&meta.id);
+        let size = dir_size(&path);
+        let age_days = now.saturating_sub(meta.created_at_unix).max(0) as u64 / 86_400;
+        let over = retention.max_count.is_some_and(|n| i >= n)
+            || retention.max_age_days.is_some_and(|d| age_days >= d)
+            || retention
+                .max_size_bytes
+                .is_some_and(|max| kept_bytes + size > max);
+        if i > 0 && over {
+            fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            report.removed.push(meta.id.clone());
+            report.freed_bytes += size;
+        } else {
+            kept_bytes += size;
+        }
+    }
+    Ok(report)
 }
 
 pub fn create_checkpoint(
@@ -182,6 +292,179 @@ pub fn restore_checkpoint(
     Ok(())
 }
 
+/// Undo entries kept per workspace; older ones are dropped as new edits come in.
+const MAX_UNDO_ENTRIES: usize = 50;
+
+/// A file as it was before an edit tool touched it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoFile {
+    pub path: PathBuf,
+    /// `false` if the edit created the file (undo deletes it)
+    pub existed: bool,
+    /// SHA-256 of the file right after the edit, `""` if the edit removed it. Undo won't
+    /// overwrite a file that has changed since; `None` for entries from older versions.
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+/// One entry on the `/undo` stack: the files a single write_file/edit_file/apply_patch
+/// call was about to change. Kept under `<checkpoints>/undo/`, apart from full checkpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub id: String,
+    pub created_at_unix: i64,
+    pub tool: String,
+    pub files: Vec<UndoFile>,
+}
+
+pub fn undo_dir_for_workspace(workspace_root: &Path) -> Result<PathBuf> {
+    Ok(checkpoints_dir_for_workspace(workspace_root)?.join("undo"))
+}
+
+/// Copy `paths` aside before `tool` modifies them.
+pub fn push_undo(workspace_root: &Path, tool: &str, paths: &[PathBuf]) -> Result<UndoEntry> {
+    push_undo_in(&undo_dir_for_workspace(workspace_root)?, tool, paths)
+}
+
+/// Forget an entry whose edit didn't happen (the tool failed).
+pub fn drop_undo(workspace_root: &Path, id: &str) -> Result<()> {
+    let dir = undo_dir_for_workspace(workspace_root)?.join(id);
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))
+}
+
+/// Note what the files of entry `id` look like now that its edit is done.
+pub fn seal_undo(workspace_root: &Path, id: &str) -> Result<()> {
+    seal_undo_in(&undo_dir_for_workspace(workspace_root)?, id)
+}
+
+/// Put back the files of the most recent entry and pop it. `None` if the stack is empty.
+/// Unless `force`, fails without touching anything if one of them changed after the edit.
+pub fn undo_last(workspace_root: &Path, force: bool) -> Result<Option<(UndoEntry, usize)>> {
+    undo_last_in(&undo_dir_for_workspace(workspace_root)?, force)
+}
+
+fn push_undo_in(undo_dir: &Path, tool: &str, paths: &[PathBuf]) -> Result<UndoEntry> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    // Zero-padded so directory names sort in push order.
+    let id = format!("{:024}", nanos);
+    let dir = undo_dir.join(&id);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let mut files = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let existed = path.is_file();
+        if existed {
+            fs::copy(path, dir.join(i.to_string()))
+                .with_context(|| format!("Failed to copy {}", path.display()))?;
+        }
+        files.push(UndoFile {
+            path: path.clone(),
+            existed,
+            after: None,
+        });
+    }
+
+    let entry = UndoEntry {
+        id,
+        created_at_unix: unix_ts(),
+        tool: tool.to_string(),
+        files,
+    };
+    fs::write(dir.join("undo.json"), serde_json::to_string_pretty(&entry)?)?;
+
+    let ids = undo_ids(undo_dir);
+    for old in ids.iter().take(ids.len().saturating_sub(MAX_UNDO_ENTRIES)) {
+        let _ = fs::remove_dir_all(undo_dir.join(old));
+    }
+    Ok(entry)
+}
+
+fn seal_undo_in(undo_dir: &Path, id: &str) -> Result<()> {
+    let dir = undo_dir.join(id);
+    let mut entry = read_undo_entry(&dir)?;
+    for file in &mut entry.files {
+        file.after = Some(current_hash(&file.path)?);
+    }
+    fs::write(dir.join("undo.json"), serde_json::to_string_pretty(&entry)?)?;
+    Ok(())
+}
+
+fn read_undo_entry(dir: &Path) -> Result<UndoEntry> {
+    let data = fs::read_to_string(dir.join("undo.json"))
+        .with_context(|| format!("Failed to read {}", dir.display()))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// SHA-256 of `path`, `""` if it doesn't exist.
+fn current_hash(path: &Path) -> Result<String> {
+    if path.exists() {
+        sha256_hex(path)
+    } else {
+        Ok(String::new())
+    }
+}
+
+fn undo_last_in(undo_dir: &Path, force: bool) -> Result<Option<(UndoEntry, usize)>> {
+    let mut ids = undo_ids(undo_dir);
+    let Some(id) = ids.pop() else {
+        return Ok(None);
+    };
+    let dir = undo_dir.join(&id);
+    let entry = read_undo_entry(&dir)?;
+
+    if !force {
+        let mut changed = Vec::new();
+        for file in &entry.files {
+            if file
+                .after
+                .as_ref()
+                .is_some_and(|after| current_hash(&file.path).ok().as_ref() != Some(after))
+            {
+                changed.push(file.path.display().to_string());
+            }
+        }
+        if !changed.is_empty() {
+            return Err(anyhow!(
+                "{} changed after {} wrote it; `/undo force` would discard that",
+                changed.join(", "),
+                entry.tool
+            ));
+        }
+    }
+
+    for (i, file) in entry.files.iter().enumerate() {
+        if file.existed {
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(dir.join(i.to_string()), &file.path)
+                .with_context(|| format!("Failed to restore {}", file.path.display()))?;
+        } else if file.path.exists() {
+            fs::remove_file(&file.path)
+                .with_context(|| format!("Failed to remove {}", file.path.display()))?;
+        }
+    }
+    fs::remove_dir_all(&dir)?;
+    Ok(Some((entry, ids.len())))
+}
+
+/// Entry ids, oldest first.
+fn undo_ids(undo_dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = fs::read_dir(undo_dir)
+        .map(|rd| {
+            rd.flatten()
+                .filter(|e| e.path().join("undo.json").is_file())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort();
+    ids
+}
+
 pub fn checkpoint_diff_summary(workspace_root: &Path, meta: &CheckpointMeta) -> Result<String> {
     let workspace_root =
         fs::canonicalize(workspace_root).unwrap_or_else(|_| workspace_root.to_path_buf());
@@ -252,6 +535,273 @@ pub fn checkpoint_diff_summary(workspace_root: &Path, meta: &CheckpointMeta) ->
     }
 }
 
+/// What `/restore` would do to one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreChange {
+    /// Missing now; the restore creates it
+    Added,
+    /// Exists now; the restore deletes it
+    Deleted,
+    /// Exists in both, with different content
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreFile {
+    pub path: String,
+    pub change: RestoreChange,
+    /// Lines the file gains and loses. `None` for binary files.
+    pub lines: Option<(usize, usize)>,
+}
+
+/// Dry run of a restore: the working tree compared against the checkpoint.
+#[derive(Debug, Clone, Default)]
+pub struct RestorePreview {
+    pub files: Vec<RestoreFile>,
+}
+
+impl RestorePreview {
+    pub fn count(&self, change: RestoreChange) -> usize {
+        self.files.iter().filter(|f| f.change == change).count()
+    }
+
+    /// Total lines gained and lost across text files.
+    pub fn line_totals(&self) -> (usize, usize) {
+        self.files
+            .iter()
+            .filter_map(|f| f.lines)
+            .fold((0, 0), |(a, r), (fa, fr)| (a + fa, r + fr))
+    }
+}
+
+/// Work out what restoring `meta` would change, without touching the workspace.
+pub fn restore_preview(workspace_root: &Path, meta: &CheckpointMeta) -> Result<RestorePreview> {
+    let workspace_root =
+        fs::canonicalize(workspace_root).unwrap_or_else(|_| workspace_root.to_path_buf());
+    let dir = checkpoint_dir(&workspace_root, &meta.id)?;
+    let mut files = match meta.backend {
+        CheckpointBackend::Git => git_restore_preview(&workspace_root, &dir, meta)?,
+        CheckpointBackend::Snapshot => snapshot_restore_preview(&workspace_root, &dir)?,
+    };
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(RestorePreview { files })
+}
+
+fn snapshot_restore_preview(workspace_root: &Path, dir: &Path) -> Result<Vec<RestoreFile>> {
+    let manifest_path = dir.join("manifest.json");
+    let data = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let manifest: Manifest = serde_json::from_str(&data)
+        .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+
+    let mut out = Vec::new();
+    let snap_dir = dir.join("snapshot");
+    let expected: HashSet<String> = manifest.files.iter().map(|f| f.path.clone()).collect();
+    for f in &manifest.files {
+        let current = workspace_root.join(&f.path);
+        if let Some(file) = compare_file(&current, &snap_dir.join(&f.path), &f.path)? {
+            out.push(file);
+        }
+    }
+    for rel in list_included_files(workspace_root)? {
+        if !expected.contains(&rel) {
+            out.push(deleted_file(&workspace_root.join(&rel), rel));
+        }
+    }
+    Ok(out)
+}
+
+fn git_restore_preview(
+    workspace_root: &Path,
+    dir: &Path,
+    meta: &CheckpointMeta,
+) -> Result<Vec<RestoreFile>> {
+    let head = meta
+        .git_head
+        .as_ref()
+        .ok_or_else(|| anyhow!("Missing git_head in checkpoint meta"))?;
+
+    let index = std::env::temp_dir().join(format!("lorikeet-preview-{}.index", meta.id));
+    let tree = checkpoint_tree(workspace_root, dir, head, &index);
+    let _ = fs::remove_file(&index);
+    let tree = tree?;
+
+    // -R: from the working tree to the checkpoint, i.e. what the restore does.
+    let mut out: BTreeMap<String, RestoreFile> = BTreeMap::new();
+    let status = run_git(
+        workspace_root,
+        &["diff", "-R", "--no-renames", "--name-status", &tree],
+    )?;
+    for line in status.lines() {
+        let Some((code, path)) = line.split_once('\t') else {
+            continue;
+        };
+        let change = match code {
+            "A" => RestoreChange::Added,
+            "D" => RestoreChange::Deleted,
+            _ => RestoreChange::Modified,
+        };
+        out.insert(
+            path.to_string(),
+            RestoreFile {
+                path: path.to_string(),
+                change,
+                lines: None,
+            },
+        );
+    }
+    let numstat = run_git(
+        workspace_root,
+        &["diff", "-R", "--no-renames", "--numstat", &tree],
+    )?;
+    for line in numstat.lines() {
+        let mut parts = line.splitn(3, '\t');
+        let (Some(added), Some(removed), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if let (Some(file), Ok(a), Ok(r)) = (out.get_mut(path), added.parse(), removed.parse()) {
+            file.lines = Some((a, r));
+        }
+    }
+
+    // Untracked files: `git clean` drops today's, then the checkpoint's are copied back.
+    let saved: HashSet<String> = fs::read_to_string(dir.join("untracked_manifest.json"))
+        .ok()
+        .and_then(|d| serde_json::from_str::<Manifest>(&d).ok())
+        .map(|m| m.files.into_iter().map(|f| f.path).collect())
+        .unwrap_or_default();
+    let in_tree: HashSet<String> = split_nul(&run_git_bytes(
+        workspace_root,
+        &["ls-tree", "-r", "-z", "--name-only", &tree],
+    )?)
+    .into_iter()
+    .collect();
+    let untracked = split_nul(&run_git_bytes(
+        workspace_root,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )?);
+    for rel in untracked {
+        if !saved.contains(&rel) && !in_tree.contains(&rel) && !out.contains_key(&rel) {
+            let file = deleted_file(&workspace_root.join(&rel), rel.clone());
+            out.insert(rel, file);
+        }
+    }
+    let untracked_dir = dir.join("untracked");
+    for rel in saved {
+        if out.contains_key(&rel) {
+            continue;
+        }
+        let current = workspace_root.join(&rel);
+        if let Some(file) = compare_file(&current, &untracked_dir.join(&rel), &rel)? {
+            out.insert(rel, file);
+        }
+    }
+    Ok(out.into_values().collect())
+}
+
+/// Rebuild the checkpoint's tracked state in the throwaway `index` (HEAD plus both patches)
+/// and write it out as a tree object.
+fn checkpoint_tree(workspace_root: &Path, dir: &Path, head: &str, index: &Path) -> Result<String> {
+    git_with_index(workspace_root, index, &["read-tree", head])?;
+    for patch in [dir.join("staged.patch"), dir.join("unstaged.patch")] {
+        if fs::metadata(&patch).map(|m| m.len() > 0).unwrap_or(false) {
+            git_with_index(
+                workspace_root,
+                index,
+                &[
+                    "apply",
+                    "--cached",
+                    "--binary",
+                    patch.to_string_lossy().as_ref(),
+                ],
+            )?;
+        }
+    }
+    Ok(git_with_index(workspace_root, index, &["write-tree"])?
+        .trim()
+        .to_string())
+}
+
+fn git_with_index(workspace_root: &Path, index: &Path, args: &[&str]) -> Result<String> {
+    let out = std::process::Command::new("git")
+        .current_dir(workspace_root)
+        .env("GIT_INDEX_FILE", index)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+    if !out.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// How restoring `saved` over `current` changes the file, if it does.
+fn compare_file(current: &Path, saved: &Path, rel: &str) -> Result<Option<RestoreFile>> {
+    let target = fs::read(saved).with_context(|| format!("Failed to read {}", saved.display()))?;
+    let Ok(now) = fs::read(current) else {
+        return Ok(Some(RestoreFile {
+            path: rel.to_string(),
+            change: RestoreChange::Added,
+            lines: text_lines(&target).map(|t| (t.len(), 0)),
+        }));
+    };
+    if now == target {
+        return Ok(None);
+    }
+    let lines = match (text_lines(&now), text_lines(&target)) {
+        (Some(a), Some(b)) => Some(line_delta(&a, &b)),
+        _ => None,
+    };
+    Ok(Some(RestoreFile {
+        path: rel.to_string(),
+        change: RestoreChange::Modified,
+        lines,
+    }))
+}
+
+fn deleted_file(current: &Path, rel: String) -> RestoreFile {
+    let lines = fs::read(current)
+        .ok()
+        .and_then(|b| text_lines(&b).map(|t| (0, t.len())));
+    RestoreFile {
+        path: rel,
+        change: RestoreChange::Deleted,
+        lines,
+    }
+}
+
+/// Lines of a text file; `None` for binary content (NUL bytes or invalid UTF-8).
+fn text_lines(bytes: &[u8]) -> Option<Vec<&str>> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok().map(|s| s.lines().collect())
+}
+
+/// Lines gained and lost going from `from` to `to`, ignoring moves: an approximation of
+/// a diff's `+`/`-` counts that needs no alignment.
+fn line_delta(from: &[&str], to: &[&str]) -> (usize, usize) {
+    let mut counts: std::collections::HashMap<&str, isize> = std::collections::HashMap::new();
+    for l in from {
+        *counts.entry(*l).or_default() -= 1;
+    }
+    for l in to {
+        *counts.entry(*l).or_default() += 1;
+    }
+    counts.values().fold((0, 0), |(a, r), &n| {
+        if n > 0 {
+            (a + n as usize, r)
+        } else {
+            (a, r + n.unsigned_abs())
+        }
+    })
+}
+
 pub fn truncate_session_to(store: &SessionStore, event_count: usize) -> Result<()> {
     Ok(store.truncate_to_lines(event_count)?)
 }
@@ -816,4 +1366,191 @@ mod tests {
         let idx = run_git(root, &["show", ":f.txt"]).unwrap();
         assert_eq!(idx, "1");
     }
+
+    #[test]
+    fn undo_stack_restores_edits_newest_first() {
+        let td = TempDir::new().unwrap();
+        let undo_dir = td.path().join("undo");
+        let a = td.path().join("a.txt");
+        let b = td.path().join("b.txt");
+        fs::write(&a, "v1").unwrap();
+
+        push_undo_in(&undo_dir, "edit_file", &[a.clone()]).unwrap();
+        fs::write(&a, "v2").unwrap();
+        push_undo_in(&undo_dir, "apply_patch", &[a.clone(), b.clone()]).unwrap();
+        fs::write(&a, "v3").unwrap();
+        fs::write(&b, "new").unwrap();
+
+        let (entry, left) = undo_last_in(&undo_dir, false).unwrap().unwrap();
+        assert_eq!((entry.tool.as_str(), left), ("apply_patch", 1));
+        assert_eq!(fs::read_to_string(&a).unwrap(), "v2");
+        assert!(!b.exists(), "files the edit created are removed");
+
+        let (entry, left) = undo_last_in(&undo_dir, false).unwrap().unwrap();
+        assert_eq!((entry.tool.as_str(), left), ("edit_file", 0));
+        assert_eq!(fs::read_to_string(&a).unwrap(), "v1");
+        assert!(undo_last_in(&undo_dir, false).unwrap().is_none());
+    }
+
+    #[test]
+    fn undo_keeps_files_changed_after_the_edit_unless_forced() {
+        let td = TempDir::new().unwrap();
+        let undo_dir = td.path().join("undo");
+        let a = td.path().join("a.txt");
+        let b = td.path().join("b.txt");
+        fs::write(&a, "v1").unwrap();
+
+        let entry = push_undo_in(&undo_dir, "edit_file", &[a.clone(), b.clone()]).unwrap();
+        fs::write(&a, "agent").unwrap();
+        fs::write(&b, "agent").unwrap();
+        seal_undo_in(&undo_dir, &entry.id).unwrap();
+        fs::write(&a, "user").unwrap();
+
+        let err = undo_last_in(&undo_dir, false).unwrap_err().to_string();
+        assert!(err.contains("a.txt changed after edit_file"), "{}", err);
+        assert!(!err.contains("b.txt"), "{}", err);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "user");
+        assert!(b.exists());
+
+        undo_last_in(&undo_dir, true).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "v1");
+        assert!(!b.exists());
+    }
+
+    #[test]
+    fn auto_checkpoints_only_for_edit_groups_and_deduped() {
+        let edit = ["read_file", "edit_file"];
+        assert!(!auto_checkpoint_due(["read_file", "bash"], None, 1, 100, 120));
+        assert!(auto_checkpoint_due(edit, None, 1, 100, 120));
+        // A second edit group in the same turn is covered by the first checkpoint.
+        assert!(!auto_checkpoint_due(edit, Some((1, 100)), 1, 1_000, 120));
+        // A new turn right after the last one is skipped too...
+        assert!(!auto_checkpoint_due(edit, Some((1, 100)), 2, 150, 120));
+        // ...until the interval has passed.
+        assert!(auto_checkpoint_due(edit, Some((1, 100)), 2, 220, 120));
+    }
+
+    #[test]
+    fn prune_keeps_newest_within_count_age_and_size() {
+        let td = TempDir::new().unwrap();
+        let day = 86_400;
+        let now = 100 * day;
+        // Ids cp0 (newest) .. cp4 (oldest), one day apart, 1000 bytes each.
+        for i in 0..5i64 {
+            let dir = td.path().join(format!("cp{}", i));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("data"), vec![0u8; 1000]).unwrap();
+            let meta = CheckpointMeta {
+                id: format!("cp{}", i),
+                name: None,
+                created_at_unix: now - i * day,
+                workspace_root: "/w".into(),
+                project_id: "p".into(),
+                backend: CheckpointBackend::Snapshot,
+                session_id: "s".into(),
+                session_event_count: 0,
+                git_head: None,
+                notes: None,
+            };
+            fs::write(dir.join("meta.json"), serde_json::to_string(&meta).unwrap()).unwrap();
+        }
+        let ids = |dir: &Path| list_in(dir).into_iter().map(|m| m.id).collect::<Vec<_>>();
+
+        let report = prune_in(td.path(), Retention::default(), now).unwrap();
+        assert!(report.removed.is_empty(), "no limits, nothing pruned");
+
+        let by_age = Retention {
+            max_age_days: Some(4),
+            ..Retention::default()
+        };
+        let report = prune_in(td.path(), by_age, now).unwrap();
+        assert_eq!(report.removed, vec!["cp4".to_string()]);
+        assert!(report.freed_bytes >= 1000);
+
+        let by_size = Retention {
+            max_size_bytes: Some(2500),
+            ..Retention::default()
+        };
+        prune_in(td.path(), by_size, now).unwrap();
+        assert_eq!(ids(td.path()).len(), 2);
+
+        let by_count = Retention {
+            max_count: Some(0),
+            ..Retention::default()
+        };
+        prune_in(td.path(), by_count, now).unwrap();
+        assert_eq!(ids(td.path()), vec!["cp0".to_string()], "the newest always stays");
+    }
+
+    #[test]
+    fn restore_preview_lists_changes_without_touching_files() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        fs::write(root.join("a.txt"), "1\n2\n").unwrap();
+        fs::write(root.join("b.txt"), "x\n").unwrap();
+
+        let store = SessionStore::new(root, "s1".into()).unwrap();
+        store.init_file();
+        let meta = create_checkpoint(root, &store, Some("snap".into())).unwrap();
+
+        fs::write(root.join("a.txt"), "1\n3\n4\n").unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        fs::write(root.join("c.txt"), "new\n").unwrap();
+
+        let preview = restore_preview(root, &meta).unwrap();
+        let summary: Vec<(&str, RestoreChange, Option<(usize, usize)>)> = preview
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.change, f.lines))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a.txt", RestoreChange::Modified, Some((1, 2))),
+                ("b.txt", RestoreChange::Added, Some((1, 0))),
+                ("c.txt", RestoreChange::Deleted, Some((0, 1))),
+            ]
+        );
+        assert_eq!(preview.line_totals(), (2, 3));
+        assert!(root.join("c.txt").exists(), "a preview changes nothing");
+    }
+
+    #[test]
+    fn restore_preview_git_covers_tracked_and_untracked() {
+        if !git_available() {
+            eprintln!("skipping: git not available");
+            return;
+        }
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        run_git_status_ok(root, &["init"]).unwrap();
+        fs::write(root.join("t.txt"), "base\n").unwrap();
+        run_git_status_ok(root, &["add", "t.txt"]).unwrap();
+        run_git_status_ok(root, &["commit", "-m", "init"]).unwrap();
+
+        let store = SessionStore::new(root, "s1".into()).unwrap();
+        store.init_file();
+        fs::write(root.join("t.txt"), "mod\n").unwrap();
+        fs::write(root.join("u.txt"), "untracked\n").unwrap();
+        let meta = create_checkpoint(root, &store, Some("git".into())).unwrap();
+
+        fs::write(root.join("t.txt"), "mod\nmore\n").unwrap();
+        fs::remove_file(root.join("u.txt")).unwrap();
+        fs::write(root.join("u2.txt"), "new\n").unwrap();
+
+        let preview = restore_preview(root, &meta).unwrap();
+        let summary: Vec<(&str, RestoreChange, Option<(usize, usize)>)> = preview
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.change, f.lines))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("t.txt", RestoreChange::Modified, Some((0, 1))),
+                ("u.txt", RestoreChange::Added, Some((1, 0))),
+                ("u2.txt", RestoreChange::Deleted, Some((0, 1))),
+            ]
+        );
+    }
 }
@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use base64::Engine as _;
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+
+use crate::app::{Message, Role};
+
+/// Something the user can pick in copy mode: a whole message or one of its code blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyTarget {
+    pub label: String,
+    pub content: String,
+}
+
+/// Build the copy-mode list in transcript order: each user/assistant message, followed by
+/// the fenced code blocks it contains.
+pub fn copy_targets(messages: &[Message]) -> Vec<CopyTarget> {
+    let mut out = Vec::new();
+    for msg in messages {
+        let who = match msg.role {
+            Role::User => "You",
+            Role::Agent => "Assistant",
+            Role::Draft => "Draft",
+            Role::Plan => "Plan",
+            Role::System | Role::Tool => continue,
+        };
+        let content = msg.content.trim();
+        if content.is_empty() {
+            continue;
+        }
+        out.push(CopyTarget {
+            label: format!("{}: {}", who, first_line(content)),
+            content: content.to_string(),
+        });
+        if matches!(msg.role, Role::Agent | Role::Draft) {
+            for (lang, code) in code_blocks(content) {
+                let lang = if lang.is_empty() { "code" } else { &lang };
+                out.push(CopyTarget {
+                    label: format!("  └ {} ({} lines)", lang, code.lines().count()),
+                    content: code,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Fenced/indented code blocks in a markdown string, as (language, code).
+pub fn code_blocks(markdown: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut current: Option<(String, String)> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                current = Some((lang, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, code)) = current.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((lang, code)) = current.take() {
+                    out.push((lang, code.trim_end_matches('\n').to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Copy text to the system clipboard using the OSC 52 terminal escape.
+///
+/// Works over SSH and inside the alternate screen, where mouse selection is unavailable
+/// because we capture the mouse. Inside tmux the sequence is wrapped in a DCS passthrough.
+pub fn copy(text: &str) -> io::Result<()> {
+    let seq = osc52_sequence(text, std::env::var_os("TMUX").is_some());
+    let mut stdout = io::stdout();
+    stdout.write_all(seq.as_bytes())?;
+    stdout.flush()
+}
+
+fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text.as_bytes());
+    if tmux {
+        format!("\x1bPtmux;\x1b\x1b]52;c;{}\x07\x1b\\", encoded)
+    } else {
+        format!("\x1b]52;c;{}\x07", encoded)
+    }
+}
+
+fn first_line(s: &str) -> &str {
+    s.lines().next().unwrap_or("").trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: Role, content: &str) -> Message {
+        Message {
+            id: 0,
+            role,
+            content: content.into(),
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: false,
+        }
+    }
+
+    #[test]
+    fn targets_include_messages_and_their_code_blocks() {
+        let messages = vec![
+            msg(Role::System, "system prompt"),
+            msg(Role::User, "show me main"),
+            msg(
+                Role::Agent,
+                "Here it is:\n\n```rust\nfn main() {}\n```\n\nand a shell line:\n\n```\nls\n```",
+            ),
+        ];
+        let targets = copy_targets(&messages);
+        assert_eq!(targets.len(), 4);
+        assert_eq!(targets[0].label, "You: show me main");
+        assert!(targets[1].label.starts_with("Assistant: Here it is:"));
+        assert_eq!(targets[2].content, "fn main() {}");
+        assert!(targets[2].label.contains("rust"));
+        assert_eq!(targets[3].content, "ls");
+    }
+
+    #[test]
+    fn osc52_encodes_payload_and_wraps_for_tmux() {
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        let wrapped = osc52_sequence("hi", true);
+        assert!(wrapped.starts_with("\x1bPtmux;\x1b\x1b]52;c;aGk="));
+        assert!(wrapped.ends_with("\x1b\\"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::io::Write;
 use std::path::PathBuf;
 
 use base64::Engine;
@@ -37,15 +38,66 @@ struct TokenResponse {
 
 const CODEX_CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
 const TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
+const AUTHORIZE_URL: &str = "https://auth.openai.com/oauth/authorize";
+/// The redirect registered for the Codex client; the browser is sent back here.
+const CALLBACK_PORT: u16 = 1455;
+const CALLBACK_PATH: &str = "/auth/callback";
+
+/// Prefix of errors that only signing in again can fix; see [`needs_reauth`].
+const REAUTH_REQUIRED: &str = "Codex sign-in expired";
+const NOT_SIGNED_IN: &str = "Codex OAuth not found";
 
 fn codex_auth_path() -> Option<PathBuf> {
     dirs::home_dir().map(|h| h.join(".codex").join("auth.json"))
 }
 
+/// Tokens from `lorikeet login` and from refreshes. Same format as the Codex CLI's file.
+fn lorikeet_auth_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".lorikeet").join("codex_auth.json"))
+}
+
+/// Lorikeet's own tokens if there are any, else the Codex CLI's.
 fn read_codex_auth() -> Option<CodexAuthFile> {
-    let path = codex_auth_path()?;
-    let data = std::fs::read_to_string(path).ok()?;
-    serde_json::from_str(&data).ok()
+    [lorikeet_auth_path(), codex_auth_path()]
+        .into_iter()
+        .flatten()
+        .find_map(|path| {
+            let data = std::fs::read_to_string(path).ok()?;
+            serde_json::from_str(&data).ok()
+        })
+}
+
+fn write_lorikeet_auth(auth: &CodexAuthFile) -> Result<(), String> {
+    let path = lorikeet_auth_path().ok_or("No home directory for Codex tokens")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let data = serde_json::to_string_pretty(auth).map_err(|e| e.to_string())?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Created owner-only so the tokens are never readable by others, even briefly
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let save = |e: std::io::Error| format!("Failed to save Codex tokens: {e}");
+    let mut file = options.open(&path).map_err(save)?;
+    // `mode` only applies on creation; tighten a file left by an older version
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(save)?;
+    }
+    file.write_all(data.as_bytes()).map_err(save)?;
+    Ok(())
+}
+
+/// Whether `err` (from [`codex_chatgpt_auth`] or an LLM call) means the user has to sign in
+/// again, rather than a transient failure.
+pub fn needs_reauth(err: &str) -> bool {
+    err.starts_with(REAUTH_REQUIRED) || err.starts_with(NOT_SIGNED_IN)
 }
 
 async fn refresh_tokens(refresh_token: &str) -> Result<TokenResponse, String> {
@@ -64,6 +116,12 @@ async fn refresh_tokens(refresh_token: &str) -> Result<TokenResponse, String> {
     if !resp.status().is_success() {
         let status = resp.status();
         let body = resp.text().await.unwrap_or_default();
+        // 400 (invalid_grant) and 401 mean the refresh token itself is expired or revoked.
+        if matches!(status.as_u16(), 400 | 401) {
+            return Err(format!(
+                "{REAUTH_REQUIRED}: the refresh token was rejected (HTTP {status}). Run `lorikeet login` or /login to sign in again."
+            ));
+        }
         return Err(format!("Codex OAuth refresh failed: HTTP {status}: {body}"));
     }
 
@@ -72,7 +130,7 @@ async fn refresh_tokens(refresh_token: &str) -> Result<TokenResponse, String> {
         .map_err(|e| format!("Codex OAuth refresh parse error: {e}"))
 }
 
-fn jwt_exp(jwt: &str) -> Option<i64> {
+fn jwt_claims(jwt: &str) -> Option<serde_json::Value> {
     let mut parts = jwt.split('.');
     let (_h, payload_b64, _s) = match (parts.next(), parts.next(), parts.next()) {
         (Some(h), Some(p), Some(s)) if !h.is_empty() && !p.is_empty() && !s.is_empty() => (h, p, s),
@@ -81,8 +139,26 @@ fn jwt_exp(jwt: &str) -> Option<i64> {
     let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
         .decode(payload_b64)
         .ok()?;
-    let v: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
-    v.get("exp").and_then(|x| x.as_i64())
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn jwt_exp(jwt: &str) -> Option<i64> {
+    jwt_claims(jwt)?.get("exp").and_then(|x| x.as_i64())
+}
+
+/// The ChatGPT account an ID token was issued for.
+fn account_id_from_id_token(id_token: &str) -> Option<String> {
+    jwt_claims(id_token)?
+        .get("https://api.openai.com/auth")?
+        .get("chatgpt_account_id")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn now_rfc3339() -> Option<String> {
+    time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .ok()
 }
 
 fn token_needs_refresh(access_token: &str) -> bool {
@@ -96,7 +172,7 @@ fn token_needs_refresh(access_token: &str) -> bool {
 
 async fn load_fresh_tokens() -> Result<CodexTokens, String> {
     let mut auth = read_codex_auth().ok_or_else(|| {
-        "Codex OAuth not found. Run `codex login` (sign in with ChatGPT) first.".to_string()
+        format!("{NOT_SIGNED_IN}. Run `lorikeet login` (or `codex login`) to sign in with ChatGPT.")
     })?;
 
     if auth.tokens.access_token.trim().is_empty() || auth.tokens.refresh_token.trim().is_empty() {
@@ -114,6 +190,10 @@ async fn load_fresh_tokens() -> Result<CodexTokens, String> {
         if let Some(rt) = refreshed.refresh_token {
             auth.tokens.refresh_token = rt;
         }
+        // Refresh tokens are single-use, so keep the new ones for the next call. They go to
+        // Lorikeet's own file; the Codex CLI's is left as it is.
+        auth.last_refresh = now_rfc3339();
+        write_lorikeet_auth(&auth)?;
     }
 
     Ok(auth.tokens)
@@ -121,8 +201,9 @@ async fn load_fresh_tokens() -> Result<CodexTokens, String> {
 
 /// Return a Bearer token suitable for calling the Codex ChatGPT backend.
 ///
-/// Lorikeet reads `~/.codex/auth.json` created by `codex login`, refreshes if needed,
-/// and returns the `access_token`. We do **not** write back to `auth.json`.
+/// Lorikeet reads `~/.lorikeet/codex_auth.json` (from `lorikeet login`), falling back to
+/// `~/.codex/auth.json` created by `codex login`, refreshes if needed, and returns the
+/// `access_token`. Refreshed tokens are saved to Lorikeet's file, never to `auth.json`.
 pub async fn codex_chatgpt_access_token() -> Result<String, String> {
     let auth = codex_chatgpt_auth().await?;
     if auth.access_token.trim().is_empty() {
@@ -142,3 +223,264 @@ pub async fn codex_chatgpt_auth() -> Result<CodexChatgptAuth, String> {
         account_id: tokens.account_id,
     })
 }
+
+/// A browser sign-in waiting for its callback.
+pub struct LoginRequest {
+    /// Page to open in the browser.
+    pub url: String,
+    verifier: String,
+    state: String,
+    listener: tokio::net::TcpListener,
+}
+
+fn redirect_uri() -> String {
+    format!("http://localhost:{CALLBACK_PORT}{CALLBACK_PATH}")
+}
+
+/// Start the OAuth authorization-code flow (with PKCE): listen for the callback and build
+/// the sign-in URL. Finish with [`LoginRequest::complete`].
+pub async fn start_login() -> Result<LoginRequest, String> {
+    use sha2::{Digest, Sha256};
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", CALLBACK_PORT))
+        .await
+        .map_err(|e| {
+            format!("Can't listen on port {CALLBACK_PORT} for the sign-in callback: {e}")
+        })?;
+    let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    let random: Vec<u8> = (0..2)
+        .flat_map(|_| uuid::Uuid::new_v4().into_bytes())
+        .collect();
+    let verifier = engine.encode(random);
+    let challenge = engine.encode(Sha256::digest(verifier.as_bytes()));
+    let state = uuid::Uuid::new_v4().simple().to_string();
+
+    let url = url::Url::parse_with_params(
+        AUTHORIZE_URL,
+        &[
+            ("response_type", "code"),
+            ("client_id", CODEX_CLIENT_ID),
+            ("redirect_uri", redirect_uri().as_str()),
+            ("scope", "openid profile email offline_access"),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("id_token_add_organizations", "true"),
+            ("codex_cli_simplified_flow", "true"),
+            ("state", state.as_str()),
+        ],
+    )
+    .map_err(|e| e.to_string())?
+    .to_string();
+
+    Ok(LoginRequest {
+        url,
+        verifier,
+        state,
+        listener,
+    })
+}
+
+impl LoginRequest {
+    /// Wait (up to five minutes) for the browser to come back, exchange the code for tokens
+    /// and save them to `~/.lorikeet/codex_auth.json`.
+    pub async fn complete(self) -> Result<CodexChatgptAuth, String> {
+        let code = tokio::time::timeout(
+            std::time::Duration::from_secs(300),
+            wait_for_code(&self.listener, &self.state),
+        )
+        .await
+        .map_err(|_| "Timed out waiting for the browser sign-in".to_string())??;
+
+        let resp = reqwest::Client::new()
+            .post(TOKEN_URL)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("client_id", CODEX_CLIENT_ID),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect_uri().as_str()),
+                ("code_verifier", self.verifier.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("Codex sign-in failed: HTTP {status}: {body}"));
+        }
+        let tokens = resp
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| format!("Codex sign-in parse error: {e}"))?;
+
+        let (Some(access_token), Some(refresh_token)) = (tokens.access_token, tokens.refresh_token)
+        else {
+            return Err("Codex sign-in returned no tokens".into());
+        };
+        let id_token = tokens.id_token.unwrap_or_default();
+        let account_id = account_id_from_id_token(&id_token);
+        write_lorikeet_auth(&CodexAuthFile {
+            tokens: CodexTokens {
+                access_token: access_token.clone(),
+                id_token,
+                refresh_token,
+                account_id: account_id.clone(),
+            },
+            last_refresh: now_rfc3339(),
+        })?;
+        Ok(CodexChatgptAuth {
+            access_token,
+            account_id,
+        })
+    }
+}
+
+/// Serve callback requests until one carries the authorization code for `state`.
+async fn wait_for_code(listener: &tokio::net::TcpListener, state: &str) -> Result<String, String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    loop {
+        let (mut stream, _) = listener.accept().await.map_err(|e| e.to_string())?;
+        let mut buf = vec![0u8; 8192];
+        let n = stream.read(&mut buf).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("");
+        let callback = parse_callback(target, state);
+        let (status, body) = match &callback {
+            // Browsers also ask for /favicon.ico and the like; keep waiting.
+            Callback::Other => ("404 Not Found", "Not found"),
+            // Not our sign-in (a stale tab, or someone else poking the port); keep waiting.
+            Callback::Stray => ("400 Bad Request", "Not a callback for this sign-in."),
+            Callback::Done(Ok(_)) => (
+                "200 OK",
+                "Signed in to Lorikeet. You can close this tab.",
+            ),
+            Callback::Done(Err(_)) => ("400 Bad Request", "Sign-in failed. Check the terminal."),
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        if let Callback::Done(result) = callback {
+            return result;
+        }
+    }
+}
+
+/// What a request to the callback listener means for the sign-in.
+#[derive(Debug, PartialEq)]
+enum Callback {
+    /// A request for some other path
+    Other,
+    /// A callback that isn't for this sign-in: wrong or missing state
+    Stray,
+    /// The callback for our state: the code, or why there isn't one
+    Done(Result<String, String>),
+}
+
+/// Classify a request target such as `/auth/callback?code=...&state=...`. Only a callback
+/// carrying our `state` can end the sign-in, errors included, so a forged or stale
+/// `?error=` can't cancel it.
+fn parse_callback(target: &str, state: &str) -> Callback {
+    let Ok(url) = url::Url::parse(&format!("http://localhost{target}")) else {
+        return Callback::Other;
+    };
+    if url.path() != CALLBACK_PATH {
+        return Callback::Other;
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    if param("state").as_deref() != Some(state) {
+        return Callback::Stray;
+    }
+    if let Some(error) = param("error") {
+        let detail = param("error_description").unwrap_or_default();
+        return Callback::Done(Err(format!("Codex sign-in was refused: {error} {detail}")
+            .trim()
+            .to_string()));
+    }
+    Callback::Done(param("code").ok_or_else(|| "Codex sign-in callback had no code".to_string()))
+}
+
+/// Best-effort: open `url` in the default browser.
+pub fn open_browser(url: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        // Not `cmd /C start`: cmd would split the URL at its `&`s.
+        let mut c = std::process::Command::new("rundll32");
+        c.arg("url.dll,FileProtocolHandler");
+        c
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let _ = cmd
+        .arg(url)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_yields_the_code_for_our_state_only() {
+        assert_eq!(
+            parse_callback("/auth/callback?code=abc&state=s1", "s1"),
+            Callback::Done(Ok("abc".to_string()))
+        );
+        assert!(matches!(
+            parse_callback("/auth/callback?code=abc&state=other", "s1"),
+            Callback::Stray
+        ));
+        assert!(matches!(
+            parse_callback("/auth/callback?error=access_denied&state=s1", "s1"),
+            Callback::Done(Err(e)) if e.contains("access_denied")
+        ));
+        assert_eq!(parse_callback("/favicon.ico", "s1"), Callback::Other);
+    }
+
+    #[test]
+    fn errors_without_our_state_do_not_end_the_sign_in() {
+        for target in [
+            "/auth/callback?error=access_denied",
+            "/auth/callback?error=access_denied&state=other",
+        ] {
+            assert!(
+                matches!(parse_callback(target, "s1"), Callback::Stray),
+                "{target}"
+            );
+        }
+    }
+
+    #[test]
+    fn account_id_comes_from_the_id_token_claims() {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let claims = serde_json::json!({
+            "exp": 1,
+            "https://api.openai.com/auth": { "chatgpt_account_id": "acct-1" },
+        });
+        let jwt = format!("h.{}.s", engine.encode(claims.to_string()));
+        assert_eq!(account_id_from_id_token(&jwt).as_deref(), Some("acct-1"));
+        assert_eq!(jwt_exp(&jwt), Some(1));
+        assert!(token_needs_refresh(&jwt));
+    }
+
+    #[test]
+    fn only_sign_in_problems_ask_for_reauth() {
+        assert!(needs_reauth(&format!("{REAUTH_REQUIRED}: rejected")));
+        assert!(needs_reauth(&format!("{NOT_SIGNED_IN}. Run `lorikeet login`")));
+        assert!(!needs_reauth("Codex OAuth refresh failed: HTTP 503"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::collections::HashMap;
 
@@ -6,10 +6,223 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct AppConfig {
+    /// Config format version; older files are migrated on load (see [`CONFIG_VERSION`]).
+    pub version: Option<u32>,
     pub general: Option<GeneralConfig>,
     pub sandbox: Option<SandboxConfig>,
     pub theme: Option<ThemeConfig>,
     pub memory: Option<MemoryConfig>,
+    pub embeddings: Option<EmbeddingsConfig>,
+    /// Language overrides: glob pattern -> language name (e.g. `"bin/*" = "python"`).
+    pub languages: Option<HashMap<String, String>>,
+    pub verify: Option<VerifyConfig>,
+    pub llm: Option<LlmConfig>,
+    pub lsp: Option<LspConfig>,
+    pub editor: Option<EditorConfig>,
+    pub notifications: Option<NotificationsConfig>,
+    pub accessibility: Option<AccessibilityConfig>,
+    pub github: Option<GithubConfig>,
+    pub shell: Option<ShellConfig>,
+    pub checkpoints: Option<CheckpointsConfig>,
+    pub storage: Option<StorageConfig>,
+    pub tools: Option<ToolsConfig>,
+    pub routing: Option<RoutingConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RoutingConfig {
+    /// Cheaper, faster model (same provider) for search/read loops; setting it turns
+    /// routing on. Edits and final answers stay with the main model.
+    pub explore_model: Option<String>,
+    /// Tools whose rounds count as exploring (default: every read-only tool).
+    pub explore_tools: Option<Vec<String>>,
+    /// Exploring calls in a row before the main model takes over anyway (default 6).
+    pub max_explore_calls: Option<u32>,
+}
+
+impl RoutingConfig {
+    /// `explore_model`, if set to something.
+    pub fn explore_model(&self) -> Option<&str> {
+        self.explore_model
+            .as_deref()
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+    }
+
+    /// Whether a tool round made only of `tools` is exploring.
+    pub fn explores<'a>(&self, mut tools: impl Iterator<Item = &'a str>) -> bool {
+        let mut any = false;
+        let all = tools.all(|t| {
+            any = true;
+            match &self.explore_tools {
+                Some(list) => list.iter().any(|e| e == t),
+                None => crate::llm::is_read_only_tool(t),
+            }
+        });
+        any && all
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ToolsConfig {
+    /// Tools never offered to any model; calls to them are refused.
+    pub disabled: Option<Vec<String>>,
+    /// Calls allowed per tool in one turn, e.g. `semantic_search = 3`. Later calls get a
+    /// "budget exceeded" result, and the tool is left out of the rest of the turn's requests.
+    pub max_calls_per_turn: Option<HashMap<String, u32>>,
+    /// Tools hidden from particular models: model name -> tool names. A trailing `*`
+    /// matches by prefix (`"openai/gpt-4o-mini*"`).
+    pub hide_for_models: Option<HashMap<String, Vec<String>>>,
+}
+
+impl ToolsConfig {
+    /// Tools `model` isn't offered: the disabled ones plus any hidden from it.
+    pub fn hidden_for(&self, model: &str) -> Vec<String> {
+        let mut hidden: Vec<String> = self.disabled.clone().unwrap_or_default();
+        for (pattern, tools) in self.hide_for_models.iter().flatten() {
+            if model_matches(pattern, model) {
+                hidden.extend(tools.iter().cloned());
+            }
+        }
+        hidden.sort();
+        hidden.dedup();
+        hidden
+    }
+
+    pub fn max_calls(&self, tool: &str) -> Option<u32> {
+        self.max_calls_per_turn.as_ref()?.get(tool).copied()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StorageConfig {
+    /// Keep sessions, checkpoints and global memory here instead of `~/.lorikeet`
+    /// (`~` expands). Point it at a synced folder to pick sessions up on another machine.
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CheckpointsConfig {
+    /// Checkpoint the workspace before a tool group that writes files (default: false).
+    pub auto: Option<bool>,
+    /// Skip an automatic checkpoint when the last one is younger than this (default 120).
+    /// Later edit groups of the same turn never get one.
+    pub auto_min_interval_secs: Option<u64>,
+    /// Keep at most this many checkpoints per workspace (default: unlimited).
+    pub max_count: Option<usize>,
+    /// Delete checkpoints older than this many days (default: never).
+    pub max_age_days: Option<u64>,
+    /// Delete the oldest checkpoints once together they take more than this (default: no cap).
+    pub max_size_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ShellConfig {
+    /// Run `bash` tool calls in one long-lived shell per conversation, so `cd`, exports
+    /// and activated virtualenvs persist between calls (default: false).
+    pub persistent: Option<bool>,
+    /// Run every `bash` call under a pseudo-terminal, as if `tty: true` were passed
+    /// (default: false). PTY commands are confined like the others.
+    pub pty: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GithubConfig {
+    /// Token for the `github` tool (default: `$GITHUB_TOKEN`, then `$GH_TOKEN`). Only read
+    /// from the user config.
+    pub token: Option<String>,
+    /// API base URL, for GitHub Enterprise (default: "https://api.github.com").
+    pub api_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AccessibilityConfig {
+    /// Show tool and verify status by glyph alone (✓ ✗ ◐), without green/red/yellow.
+    pub no_color_status: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NotificationsConfig {
+    /// How a finished agent turn notifies: "off" (default), "bell" or "osc9".
+    pub turn: Option<String>,
+    /// How a finished `verify` run notifies: "off" (default), "bell" or "osc9".
+    pub verify: Option<String>,
+    /// Only for turns and runs that took at least this long (default 30).
+    pub min_seconds: Option<u64>,
+    /// Notify even when the terminal has focus (default: false).
+    pub when_focused: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EditorConfig {
+    /// Editor to hand the terminal to (default: `$VISUAL`, then `$EDITOR`). `{file}` and
+    /// `{line}` are filled in when present, e.g. `"code --wait -g {file}:{line}"`.
+    pub command: Option<String>,
+    /// Open clicked `path:line` references in the editor instead of the built-in viewer
+    /// (default: false).
+    pub open_references: Option<bool>,
+    /// Key that opens the input in the editor to compose a prompt (default: "ctrl+x").
+    pub compose_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LspConfig {
+    /// After a successful edit, append errors the language server newly reports for the
+    /// changed files to the tool result (default: false).
+    pub auto_diagnostics: Option<bool>,
+    /// Edits per turn whose new errors are reported before the check stops (default 3).
+    pub auto_diagnostics_per_turn: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LlmConfig {
+    /// Retries for rate limits, 5xx and dropped connections (default 3; 0 disables).
+    pub max_retries: Option<u32>,
+    /// First backoff delay in milliseconds; doubles per retry, with jitter (default 500).
+    pub retry_base_ms: Option<u64>,
+    /// Provider to try once the primary gives up: "openrouter", "openai", "gemini" or
+    /// "codex".
+    pub fallback_provider: Option<String>,
+    /// Model for the fallback provider (default: the current model).
+    pub fallback_model: Option<String>,
+    /// Mark the system prompt and memory block as cacheable prefixes (default: true).
+    pub prompt_cache: Option<bool>,
+    /// Summarize command and search results longer than this many tokens before the
+    /// model sees them (default: off). The trace keeps the full output.
+    pub tool_summary_tokens: Option<usize>,
+    /// Model for those summaries; something cheap (default: the current model).
+    pub tool_summary_model: Option<String>,
+    /// OpenAI models sent through the Responses API instead of Chat Completions, e.g.
+    /// `["o3", "gpt-5*"]` (a trailing `*` matches by prefix). Default: none.
+    pub responses_models: Option<Vec<String>>,
+}
+
+impl LlmConfig {
+    /// Whether OpenAI calls to `model` use the Responses API.
+    pub fn uses_responses_api(&self, model: &str) -> bool {
+        self.responses_models
+            .iter()
+            .flatten()
+            .any(|pattern| model_matches(pattern, model))
+    }
+}
+
+/// `model` is `pattern`, or starts with it when the pattern ends in `*`.
+fn model_matches(pattern: &str, model: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => model == pattern,
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VerifyConfig {
+    /// Commands suggested ahead of auto-detected ones. Without `pipeline` they are also
+    /// what `verify` and /verify run, in order, instead of the detected pipeline.
+    pub commands: Option<Vec<String>>,
+    /// Stages `verify` runs in order when no command is given, stopping at the first
+    /// failure. Takes precedence over `commands`.
+    pub pipeline: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -19,6 +232,24 @@ pub struct GeneralConfig {
     pub split_ratio: Option<u16>,
     pub auto_index: Option<bool>,
     pub resume_last: Option<bool>,
+    /// Compress closed session files with zstd (`<id>.jsonl.zst`).
+    pub compress_sessions: Option<bool>,
+    /// Mode on launch: "auto" (default) or "plan".
+    pub default_mode: Option<String>,
+    /// Per-workspace overrides of `default_mode`, keyed by workspace path.
+    pub workspace_modes: Option<HashMap<String, String>>,
+    /// Offer only read-only tools for purely informational prompts (default: true).
+    pub intent_router: Option<bool>,
+    /// Esc quits from transcript normal mode once there is nothing left for it to close
+    /// or clear (default: false; Ctrl+C / Ctrl+Q always quit).
+    pub esc_quits: Option<bool>,
+    /// Send a compact map of the workspace (layout, public definitions) with each LLM
+    /// call (default: true).
+    pub repo_map: Option<bool>,
+    /// Pause a turn for confirmation after this many tool-call rounds (default 40; 0 = never).
+    pub max_tool_rounds: Option<u32>,
+    /// Pause a turn for confirmation after this many minutes (default 20; 0 = never).
+    pub max_turn_minutes: Option<u64>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -29,6 +260,23 @@ pub struct SandboxConfig {
     pub deny_paths: Option<Vec<PathBuf>>,
     pub allow_commands: Option<Vec<String>>,
     pub allow_tools: Option<Vec<String>>,
+    /// Ask before allowed-but-unusual shell commands: first run of a command in this
+    /// workspace, `sudo`, anything touching `node_modules` (default: true).
+    pub confirm_unusual: Option<bool>,
+    /// Command keys (`npm install`, `sudo apt`) the user chose "don't ask again" for.
+    pub trusted_commands: Option<Vec<String>>,
+    /// Regexes for `bash` commands that always need an explicit `y`, even when allowed
+    /// and trusted, on top of the built-in ones (rm -rf, git reset --hard, force pushes,
+    /// DROP TABLE, ...).
+    pub confirm_patterns: Option<Vec<String>>,
+    /// Confine `bash` at the OS level (Landlock/seccomp, Seatbelt) so it can only write
+    /// under `allow_paths` and `write_paths` (default: true).
+    pub enforce: Option<bool>,
+    /// Let confined commands open internet sockets (default: true).
+    pub network: Option<bool>,
+    /// Extra directories confined commands may write to (default: temp dirs and
+    /// download caches such as ~/.cargo/registry and ~/.npm/_cacache).
+    pub write_paths: Option<Vec<PathBuf>>,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -54,36 +302,815 @@ pub struct MemoryConfig {
     pub auto_extract: Option<bool>,
     /// Optional model override for extraction.
     pub extraction_model: Option<String>,
+    /// Keep per-file edit counts across sessions for the sidebar heatmap (default: false).
+    pub file_heatmap: Option<bool>,
+    /// Scopes whose memories are auto-injected: "project", "global" (default: both).
+    pub inject_scopes: Option<Vec<String>>,
+    /// Daily background pass that merges near-duplicates, decays and archives (default: true).
+    pub consolidate: Option<bool>,
+    /// Days unused before a memory's importance starts to decay (default: 30).
+    pub decay_after_days: Option<u64>,
+    /// Decayed memories below this importance are archived (default: 0.2).
+    pub archive_below: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EmbeddingsConfig {
+    /// "fastembed" (default, local), "openai", or "ollama".
+    pub backend: Option<String>,
+    /// Backend-specific model name (e.g. "bge-small-en-v1.5", "text-embedding-3-small").
+    pub model: Option<String>,
+    /// Override the API base URL (OpenAI-compatible servers, remote Ollama).
+    pub base_url: Option<String>,
+    /// Embedding dimension, when the backend can't report it up front.
+    pub dimension: Option<usize>,
+    /// Vector index: "hnsw" (default, approximate) or "flat" (exact scan).
+    pub index: Option<String>,
+    /// Vector storage: "f32" (default) or "int8" (~4x smaller index, tiny accuracy loss).
+    pub quantization: Option<String>,
+    /// Rerank the top semantic-search candidates with a local cross-encoder.
+    pub rerank: Option<bool>,
+    /// Where the local model runs: "cpu" (default), "cuda[:N]", "coreml", "directml", or
+    /// "auto". Accelerators require building with the matching cargo feature.
+    pub device: Option<String>,
 }
 
 impl AppConfig {
+    /// User-level config only (`~/.lorikeet/config.toml`).
     pub fn load() -> Self {
-        let Some(path) = default_config_path() else {
-            return Self::default();
+        read_migrated(default_config_path().as_deref())
+            .try_into()
+            .unwrap_or_default()
+    }
+
+    /// User config with the workspace's `.lorikeet/config.toml` layered on top, once the
+    /// workspace is trusted. A project file can turn the sandbox off, allow commands and
+    /// define verify stages, so an untrusted one is ignored.
+    ///
+    /// Tables merge key by key; any other value set by the project (including arrays such
+    /// as `allow_commands`) replaces the user's.
+    pub fn load_layered(workspace_root: &Path) -> Self {
+        Self::load_layered_as(workspace_root, project_layer_trusted(workspace_root))
+    }
+
+    /// [`AppConfig::load_layered`] with the trust decision made by the caller.
+    pub fn load_layered_as(workspace_root: &Path, trusted: bool) -> Self {
+        let mut merged = read_migrated(default_config_path().as_deref());
+        if trusted {
+            merge_tables(
+                &mut merged,
+                read_migrated(Some(&project_config_path(workspace_root))),
+            );
+        }
+        merged.try_into().unwrap_or_else(|_| Self::load())
+    }
+
+    /// Persist edits made to a layered config.
+    ///
+    /// Each value that differs from `previous` is written to the layer that defines it:
+    /// the project file if it sets that key, the user file otherwise. Untouched keys (and
+    /// keys this struct doesn't know about) are left as they are in both files. The project
+    /// file of an untrusted workspace isn't loaded, so it isn't written either.
+    pub fn save_layered(&self, previous: &AppConfig, workspace_root: &Path) -> std::io::Result<()> {
+        let to_io = |e: toml::ser::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+        let before = toml::Table::try_from(previous).map_err(to_io)?;
+        let after = toml::Table::try_from(self).map_err(to_io)?;
+
+        let user_path = default_config_path();
+        let project_path = project_config_path(workspace_root);
+        let mut user = read_table(user_path.as_deref());
+        let trusted = project_layer_trusted(workspace_root);
+        let mut project = if trusted {
+            read_table(Some(&project_path))
+        } else {
+            toml::Table::new()
         };
+        let (user_dirty, project_dirty) =
+            apply_edits(&before, &after, &mut user, &mut project, trusted);
+
+        if user_dirty {
+            if let Some(path) = user_path {
+                write_table(&path, &user)?;
+            }
+        }
+        if project_dirty {
+            write_table(&project_path, &project)?;
+        }
+        Ok(())
+    }
+
+    /// Whether plan mode should be active when launching in `workspace_root`.
+    pub fn default_plan_mode(&self, workspace_root: &std::path::Path) -> bool {
+        let general = self.general.as_ref();
+        let canon =
+            std::fs::canonicalize(workspace_root).unwrap_or_else(|_| workspace_root.to_path_buf());
+        let per_workspace = general
+            .and_then(|g| g.workspace_modes.as_ref())
+            .and_then(|modes| {
+                modes.iter().find_map(|(path, mode)| {
+                    let p = PathBuf::from(path);
+                    let p = std::fs::canonicalize(&p).unwrap_or(p);
+                    (p == canon).then(|| mode.clone())
+                })
+            });
+        let mode = per_workspace.or_else(|| general.and_then(|g| g.default_mode.clone()));
+        mode.is_some_and(|m| m.trim().eq_ignore_ascii_case("plan"))
+    }
 
-        let Ok(contents) = std::fs::read_to_string(&path) else {
+    /// Short hash of the settings that change agent behaviour (provider/model, sandbox,
+    /// memory, embeddings). Appearance settings are deliberately excluded.
+    pub fn fingerprint(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let relevant = serde_json::json!({
+            "provider": self.general.as_ref().and_then(|g| g.provider.clone()),
+            "model": self.general.as_ref().and_then(|g| g.model.clone()),
+            "sandbox": self.sandbox,
+            "memory": self.memory,
+            "embeddings": self.embeddings,
+        });
+        let digest = Sha256::digest(relevant.to_string().as_bytes());
+        digest
+            .iter()
+            .take(6)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".lorikeet").join("config.toml"))
+}
+
+pub fn project_config_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(".lorikeet").join("config.toml")
+}
+
+/// Whether the workspace's config file applies: only once the workspace is trusted.
+fn project_layer_trusted(workspace_root: &Path) -> bool {
+    crate::trust::load(workspace_root) == Some(crate::trust::TrustLevel::Trusted)
+}
+
+/// Which config file a setting comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLayer {
+    User,
+    Project,
+}
+
+impl ConfigLayer {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConfigLayer::User => "user (~/.lorikeet/config.toml)",
+            ConfigLayer::Project => "project (.lorikeet/config.toml)",
+        }
+    }
+}
+
+/// Keys set by the workspace config file, for attributing settings to their layer.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectLayer {
+    table: toml::Table,
+}
+
+impl ProjectLayer {
+    pub fn load(workspace_root: &Path) -> Self {
+        if !project_layer_trusted(workspace_root) {
             return Self::default();
-        };
+        }
+        Self {
+            table: read_table(Some(&project_config_path(workspace_root))),
+        }
+    }
 
-        toml::from_str(&contents).unwrap_or_default()
+    /// Layer of a dotted key such as `general.model`.
+    pub fn layer_of(&self, key: &str) -> ConfigLayer {
+        let path: Vec<String> = key.split('.').map(str::to_string).collect();
+        if table_get(&self.table, &path).is_some() {
+            ConfigLayer::Project
+        } else {
+            ConfigLayer::User
+        }
     }
+}
 
-    pub fn save(&self) -> std::io::Result<()> {
-        let Some(path) = default_config_path() else {
-            return Ok(());
+/// Sections that describe one workspace rather than the user; edits to them always go to
+/// the project file.
+const PROJECT_SCOPED: &[&str] = &["verify"];
+
+/// Route every changed leaf between `before` and `after` to the layer that defines it
+/// (sections in [`PROJECT_SCOPED`] always to the project), or everything to the user
+/// when the project can't be written. Returns which of (user, project) changed.
+fn apply_edits(
+    before: &toml::Table,
+    after: &toml::Table,
+    user: &mut toml::Table,
+    project: &mut toml::Table,
+    project_writable: bool,
+) -> (bool, bool) {
+    let mut old = Vec::new();
+    flatten(before, &mut Vec::new(), &mut old);
+    let mut new = Vec::new();
+    flatten(after, &mut Vec::new(), &mut new);
+
+    let mut keys: Vec<&Vec<String>> = old.iter().chain(new.iter()).map(|(k, _)| k).collect();
+    keys.sort();
+    keys.dedup();
+
+    let (mut user_dirty, mut project_dirty) = (false, false);
+    for key in keys {
+        let was = old.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        let now = new.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        if was == now {
+            continue;
+        }
+        let scoped = key
+            .first()
+            .is_some_and(|section| PROJECT_SCOPED.contains(&section.as_str()));
+        if project_writable && (scoped || table_get(project, key).is_some()) {
+            table_set(project, key, now.cloned());
+            project_dirty = true;
+        } else {
+            table_set(user, key, now.cloned());
+            user_dirty = true;
+        }
+    }
+    (user_dirty, project_dirty)
+}
+
+fn read_table(path: Option<&Path>) -> toml::Table {
+    path.and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| s.parse::<toml::Table>().ok())
+        .unwrap_or_default()
+}
+
+/// Like [`read_table`], upgraded to the current format in memory.
+fn read_migrated(path: Option<&Path>) -> toml::Table {
+    let mut table = read_table(path);
+    migrate(&mut table);
+    table
+}
+
+fn write_table(path: &Path, table: &toml::Table) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let contents = toml::to_string_pretty(table)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+    std::fs::write(path, contents)
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge_tables(b, o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Leaf values (anything but a table) keyed by their path.
+fn flatten(
+    table: &toml::Table,
+    prefix: &mut Vec<String>,
+    out: &mut Vec<(Vec<String>, toml::Value)>,
+) {
+    for (key, value) in table {
+        prefix.push(key.clone());
+        match value {
+            toml::Value::Table(t) => flatten(t, prefix, out),
+            other => out.push((prefix.clone(), other.clone())),
+        }
+        prefix.pop();
+    }
+}
+
+fn table_get<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut cur = table;
+    for key in parents {
+        cur = cur.get(key)?.as_table()?;
+    }
+    cur.get(last)
+}
+
+/// Set (or, with `None`, remove) a nested value, creating intermediate tables.
+fn table_set(table: &mut toml::Table, path: &[String], value: Option<toml::Value>) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut cur = table;
+    for key in parents {
+        let entry = cur
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        cur = entry.as_table_mut().expect("just ensured a table");
+    }
+    match value {
+        Some(v) => {
+            cur.insert(last.clone(), v);
+        }
+        None => {
+            cur.remove(last);
+        }
+    }
+}
+
+/// Version written by this build. Files without a `version` key are version 0.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Known keys per section, for `lorikeet config check`. Keep in step with the structs above.
+const SCHEMA: &[(&str, &[&str])] = &[
+    (
+        "general",
+        &[
+            "provider",
+            "model",
+            "split_ratio",
+            "auto_index",
+            "resume_last",
+            "compress_sessions",
+            "default_mode",
+            "workspace_modes",
+            "intent_router",
+            "esc_quits",
+            "repo_map",
+            "max_tool_rounds",
+            "max_turn_minutes",
+        ],
+    ),
+    (
+        "sandbox",
+        &[
+            "enabled",
+            "root",
+            "allow_paths",
+            "deny_paths",
+            "allow_commands",
+            "allow_tools",
+            "confirm_unusual",
+            "trusted_commands",
+            "confirm_patterns",
+            "enforce",
+            "network",
+            "write_paths",
+        ],
+    ),
+    (
+        "theme",
+        &["preset", "background", "file_categories", "file_extensions"],
+    ),
+    (
+        "memory",
+        &[
+            "enabled",
+            "auto_inject",
+            "auto_learn_failures",
+            "auto_learn_user",
+            "auto_extract",
+            "extraction_model",
+            "file_heatmap",
+            "inject_scopes",
+            "consolidate",
+            "decay_after_days",
+            "archive_below",
+        ],
+    ),
+    (
+        "embeddings",
+        &[
+            "backend",
+            "model",
+            "base_url",
+            "dimension",
+            "index",
+            "quantization",
+            "rerank",
+            "device",
+        ],
+    ),
+    ("languages", &[]),
+    ("verify", &["commands", "pipeline"]),
+    (
+        "llm",
+        &[
+            "max_retries",
+            "retry_base_ms",
+            "fallback_provider",
+            "fallback_model",
+            "prompt_cache",
+            "tool_summary_tokens",
+            "tool_summary_model",
+            "responses_models",
+        ],
+    ),
+    ("lsp", &["auto_diagnostics", "auto_diagnostics_per_turn"]),
+    ("editor", &["command", "open_references", "compose_key"]),
+    (
+        "notifications",
+        &["turn", "verify", "min_seconds", "when_focused"],
+    ),
+    ("accessibility", &["no_color_status"]),
+    ("github", &["token", "api_url"]),
+    ("shell", &["persistent", "pty"]),
+    (
+        "checkpoints",
+        &[
+            "auto",
+            "auto_min_interval_secs",
+            "max_count",
+            "max_age_days",
+            "max_size_mb",
+        ],
+    ),
+    ("storage", &["dir"]),
+    (
+        "tools",
+        &["disabled", "max_calls_per_turn", "hide_for_models"],
+    ),
+    (
+        "routing",
+        &["explore_model", "explore_tools", "max_explore_calls"],
+    ),
+];
+
+/// Sections whose keys are chosen by the user (glob patterns, workspace paths).
+const FREE_FORM: &[&str] = &["languages"];
+
+/// Upgrade a config table to [`CONFIG_VERSION`] in place. Returns a line per change made;
+/// an already-current table comes back untouched.
+pub fn migrate(table: &mut toml::Table) -> Vec<String> {
+    let version = table
+        .get("version")
+        .and_then(|v| v.as_integer())
+        .unwrap_or(0);
+    let mut changes = Vec::new();
+    if version < 1 {
+        // 0 -> 1: the "codex_oauth" provider alias became plain "codex".
+        for key in [["general", "provider"], ["llm", "fallback_provider"]] {
+            let path: Vec<String> = key.iter().map(|k| k.to_string()).collect();
+            if table_get(table, &path).and_then(|v| v.as_str()) == Some("codex_oauth") {
+                table_set(table, &path, Some(toml::Value::String("codex".into())));
+                changes.push(format!("{} = \"codex_oauth\" -> \"codex\"", key.join(".")));
+            }
+        }
+    }
+    if !changes.is_empty() {
+        table.insert(
+            "version".to_string(),
+            toml::Value::Integer(CONFIG_VERSION as i64),
+        );
+    }
+    changes
+}
+
+/// One problem found in a config file. Line and column are 1-based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConfigCheck {
+    pub issues: Vec<ConfigIssue>,
+    /// Changes `lorikeet config check --migrate` would make.
+    pub migrations: Vec<String>,
+}
+
+impl ConfigCheck {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Validate the user config and the workspace's project config, skipping missing files.
+pub fn check_all(workspace_root: &Path) -> Vec<(PathBuf, ConfigCheck)> {
+    default_config_path()
+        .into_iter()
+        .chain(std::iter::once(project_config_path(workspace_root)))
+        .filter_map(|path| {
+            let text = std::fs::read_to_string(&path).ok()?;
+            Some((path, check_text(&text)))
+        })
+        .collect()
+}
+
+/// Rewrite `path` in the current format. Returns the changes made (none: file untouched).
+pub fn migrate_file(path: &Path) -> std::io::Result<Vec<String>> {
+    let mut table = read_table(Some(path));
+    let changes = migrate(&mut table);
+    if !changes.is_empty() {
+        write_table(path, &table)?;
+    }
+    Ok(changes)
+}
+
+/// Check config text for syntax errors, unknown keys (with the nearest valid key), values
+/// of the wrong type and a version newer than this build.
+pub fn check_text(text: &str) -> ConfigCheck {
+    let mut check = ConfigCheck::default();
+    let mut table = match text.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(e) => {
+            check.issues.push(issue_at(text, e.span(), e.message()));
+            return check;
+        }
+    };
+
+    let sections: Vec<&str> = SCHEMA.iter().map(|(name, _)| *name).collect();
+    for (section, value) in &table {
+        if section == "version" {
+            continue;
+        }
+        let Some((_, keys)) = SCHEMA.iter().find(|(name, _)| name == section) else {
+            let (line, column) = key_position(text, None, section).unzip();
+            check.issues.push(ConfigIssue {
+                line,
+                column,
+                message: unknown_key(section, section, &sections),
+            });
+            continue;
+        };
+        let Some(entries) = value.as_table() else {
+            continue; // reported as a type error below
         };
+        if FREE_FORM.contains(&section.as_str()) {
+            continue;
+        }
+        for key in entries.keys() {
+            if !keys.contains(&key.as_str()) {
+                let (line, column) = key_position(text, Some(section), key).unzip();
+                check.issues.push(ConfigIssue {
+                    line,
+                    column,
+                    message: unknown_key(&format!("{}.{}", section, key), key, keys),
+                });
+            }
+        }
+    }
 
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
+    if let Err(e) = toml::from_str::<AppConfig>(text) {
+        check.issues.push(issue_at(text, e.span(), e.message()));
+    }
+    if let Some(version) = table.get("version").and_then(|v| v.as_integer()) {
+        if version > CONFIG_VERSION as i64 {
+            let (line, column) = key_position(text, None, "version").unzip();
+            check.issues.push(ConfigIssue {
+                line,
+                column,
+                message: format!(
+                    "version {} is newer than this lorikeet understands ({})",
+                    version, CONFIG_VERSION
+                ),
+            });
         }
+    }
+    check.migrations = migrate(&mut table);
+    check.issues.sort_by_key(|i| (i.line, i.column));
+    check
+}
 
-        let contents = toml::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        std::fs::write(path, contents)
+fn unknown_key(full: &str, key: &str, valid: &[&str]) -> String {
+    match nearest_key(key, valid) {
+        Some(near) => format!("unknown key `{}` (did you mean `{}`?)", full, near),
+        None => format!("unknown key `{}`", full),
     }
 }
 
-fn default_config_path() -> Option<PathBuf> {
-    dirs::home_dir().map(|home| home.join(".lorikeet").join("config.toml"))
+/// The valid key closest to a misspelt one, if any is close enough to be a typo.
+fn nearest_key<'a>(key: &str, valid: &[&'a str]) -> Option<&'a str> {
+    let max = (key.chars().count() / 3).max(2);
+    valid
+        .iter()
+        .map(|v| (edit_distance(key, v), *v))
+        .filter(|(d, _)| *d <= max)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, v)| v)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn issue_at(text: &str, span: Option<std::ops::Range<usize>>, message: &str) -> ConfigIssue {
+    let (line, column) = span.map(|s| line_col(text, s.start)).unzip();
+    ConfigIssue {
+        line,
+        column,
+        message: message.trim().to_string(),
+    }
+}
+
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+/// Where `key` is written: `key = ...` under `[section]`, `section.key = ...` at the top
+/// level, or a `[section.key]` / `[key]` header.
+fn key_position(text: &str, section: Option<&str>, key: &str) -> Option<(usize, usize)> {
+    let assigns = |line: &str, name: &str| {
+        line.strip_prefix(name)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let header = match section {
+        Some(s) => format!("[{}.{}]", s, key),
+        None => format!("[{}]", key),
+    };
+    let dotted = section.map(|s| format!("{}.{}", s, key));
+    let mut current: Option<String> = None;
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim_start();
+        let column = raw.len() - line.len() + 1;
+        if line.starts_with('[') {
+            if line.starts_with(&header) {
+                return Some((i + 1, column));
+            }
+            let name = line.trim_start_matches('[');
+            current = name.split(']').next().map(|n| n.trim().to_string());
+            continue;
+        }
+        let found = match (&current, section) {
+            (None, None) => assigns(line, key),
+            (None, Some(_)) => dotted.as_deref().is_some_and(|d| assigns(line, d)),
+            (Some(cur), Some(s)) => cur == s && assigns(line, key),
+            (Some(_), None) => false,
+        };
+        if found {
+            return Some((i + 1, column));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(s: &str) -> toml::Table {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn project_layer_overrides_user_values() {
+        let mut merged = table(
+            "[general]\nmodel = \"a\"\nauto_index = true\n[sandbox]\nallow_commands = [\"ls\", \"rg\"]\n",
+        );
+        merge_tables(
+            &mut merged,
+            table("[general]\nmodel = \"b\"\n[sandbox]\nallow_commands = [\"cargo\"]\n"),
+        );
+        let cfg: AppConfig = merged.try_into().unwrap();
+        let general = cfg.general.unwrap();
+        assert_eq!(general.model.as_deref(), Some("b"));
+        assert_eq!(general.auto_index, Some(true));
+        assert_eq!(
+            cfg.sandbox.unwrap().allow_commands,
+            Some(vec!["cargo".to_string()])
+        );
+    }
+
+    #[test]
+    fn edits_are_written_to_the_layer_that_defines_them() {
+        let mut user = table("[general]\nmodel = \"a\"\nauto_index = true\n");
+        let mut project = table("[general]\nmodel = \"b\"\n");
+        let before = table("[general]\nmodel = \"b\"\nauto_index = true\n");
+        let after = table(
+            "[general]\nmodel = \"c\"\nauto_index = false\n[memory]\nenabled = false\n[verify]\ncommands = [\"make check\"]\n",
+        );
+
+        // An untrusted workspace's file is left alone; its edits go to the user file.
+        let (mut user_only, mut untouched) = (user.clone(), project.clone());
+        let dirty = apply_edits(&before, &after, &mut user_only, &mut untouched, false);
+        assert_eq!(dirty, (true, false));
+        assert_eq!(user_only["general"]["model"].as_str(), Some("c"));
+        assert_eq!(untouched["general"]["model"].as_str(), Some("b"));
+
+        let dirty = apply_edits(&before, &after, &mut user, &mut project, true);
+        assert_eq!(dirty, (true, true));
+        // The project pins the model, so the edit lands there and the user value survives.
+        assert_eq!(project["general"]["model"].as_str(), Some("c"));
+        assert_eq!(user["general"]["model"].as_str(), Some("a"));
+        assert_eq!(user["general"]["auto_index"].as_bool(), Some(false));
+        assert_eq!(user["memory"]["enabled"].as_bool(), Some(false));
+        assert!(project.get("memory").is_none());
+        // Verify commands belong to the workspace even when the project file lacks them.
+        assert!(project["verify"]["commands"].is_array());
+        assert!(user.get("verify").is_none());
+
+        let layer = ProjectLayer { table: project };
+        assert_eq!(layer.layer_of("general.model"), ConfigLayer::Project);
+        assert_eq!(layer.layer_of("general.auto_index"), ConfigLayer::User);
+    }
+
+    #[test]
+    fn check_points_at_unknown_keys_with_suggestions() {
+        let check = check_text("[general]\nmodel = \"a\"\nmodle = \"b\"\n\n[gneral]\n");
+        assert_eq!(
+            check.issues,
+            vec![
+                ConfigIssue {
+                    line: Some(3),
+                    column: Some(1),
+                    message: "unknown key `general.modle` (did you mean `model`?)".to_string(),
+                },
+                ConfigIssue {
+                    line: Some(5),
+                    column: Some(1),
+                    message: "unknown key `gneral` (did you mean `general`?)".to_string(),
+                },
+            ]
+        );
+
+        let check = check_text("[general]\nauto_index = \"yes\"\n");
+        assert_eq!(check.issues.len(), 1);
+        assert!(check.issues[0].message.contains("invalid type"));
+
+        let check = check_text("[general\n");
+        assert_eq!(check.issues.len(), 1);
+        assert_eq!(check.issues[0].line, Some(1));
+    }
+
+    #[test]
+    fn old_configs_are_migrated_once() {
+        let mut old = table("[general]\nprovider = \"codex_oauth\"\n");
+        assert_eq!(migrate(&mut old).len(), 1);
+        assert_eq!(old["general"]["provider"].as_str(), Some("codex"));
+        assert_eq!(old["version"].as_integer(), Some(CONFIG_VERSION as i64));
+        assert!(migrate(&mut old).is_empty());
+
+        let cfg: AppConfig = old.try_into().unwrap();
+        assert_eq!(cfg.version, Some(CONFIG_VERSION));
+        assert!(check_text("version = 99\n").issues[0]
+            .message
+            .contains("newer than"));
+    }
+
+    #[test]
+    fn tools_are_hidden_per_model_and_budgeted() {
+        let text = r#"
+[tools]
+disabled = ["github"]
+max_calls_per_turn = { semantic_search = 3 }
+
+[tools.hide_for_models]
+"openai/gpt-4o-mini*" = ["semantic_search", "lsp"]
+"#;
+        assert!(check_text(text).issues.is_empty());
+        let tools = toml::from_str::<AppConfig>(text).unwrap().tools.unwrap();
+
+        assert_eq!(
+            tools.hidden_for("openai/gpt-4o-mini-2024"),
+            ["github", "lsp", "semantic_search"]
+        );
+        assert_eq!(tools.hidden_for("openai/gpt-4o"), ["github"]);
+        assert_eq!(tools.max_calls("semantic_search"), Some(3));
+        assert_eq!(tools.max_calls("rg"), None);
+    }
+
+    #[test]
+    fn responses_api_is_chosen_per_model() {
+        let llm = LlmConfig {
+            responses_models: Some(vec!["o3".into(), "gpt-5*".into()]),
+            ..Default::default()
+        };
+        assert!(llm.uses_responses_api("o3"));
+        assert!(llm.uses_responses_api("gpt-5-mini"));
+        assert!(!llm.uses_responses_api("o3-mini"));
+        assert!(!LlmConfig::default().uses_responses_api("gpt-5"));
+    }
+
+    #[test]
+    fn routing_counts_only_search_and_read_rounds_as_exploring() {
+        let default = RoutingConfig::default();
+        assert!(default.explores(["rg", "read_file"].into_iter()));
+        assert!(!default.explores(["rg", "edit_file"].into_iter()));
+        assert!(!default.explores(std::iter::empty()));
+
+        let custom = RoutingConfig {
+            explore_tools: Some(vec!["rg".into()]),
+            ..Default::default()
+        };
+        assert!(!custom.explores(["read_file"].into_iter()));
+        assert_eq!(custom.explore_model(), None);
+    }
 }
@@ -0,0 +1,411 @@
+//! OS-level confinement for the shell commands the agent runs. The sandbox policy decides
+//! which commands may start; this has the kernel hold them to it: writes only land in the
+//! allowed directories, and with `sandbox.network = false` no internet sockets can be
+//! opened. Landlock and seccomp on Linux, Seatbelt (`sandbox-exec`) on macOS.
+
+use std::path::{Path, PathBuf};
+
+use portable_pty::{CommandBuilder, SlavePty};
+use tokio::process::Command;
+
+use crate::sandbox::SandboxPolicy;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confinement {
+    /// Directories (canonicalized) the command may write under; everything else is read-only
+    pub writable: Vec<PathBuf>,
+    /// Whether internet (IPv4/IPv6) sockets are allowed; Unix sockets always are
+    pub network: bool,
+}
+
+/// How much of a [`Confinement`] the OS actually enforces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enforcement {
+    Full,
+    /// Some of it: the kernel's Landlock lacks access rights the ruleset asks for
+    Partial,
+    /// None of it (no Landlock in this kernel): commands run with the policy checks only
+    Unsupported,
+}
+
+impl Confinement {
+    /// None when the policy doesn't ask for enforcement (`sandbox.enabled` or
+    /// `sandbox.enforce` off).
+    pub fn from_policy(policy: &SandboxPolicy) -> Option<Self> {
+        if !policy.enabled || !policy.enforce {
+            return None;
+        }
+        let mut writable: Vec<PathBuf> = Vec::new();
+        // Read-only mode keeps the workspace (and other allowed paths) unwritable; caches
+        // and temp dirs in `write_paths` stay open so builds and tests still run.
+        let allowed: &[PathBuf] = if policy.read_only {
+            &[]
+        } else {
+            &policy.allow_paths
+        };
+        for path in allowed.iter().chain(&policy.write_paths) {
+            let path = if path.is_absolute() {
+                path.clone()
+            } else {
+                policy.root.join(path)
+            };
+            // Missing directories can't be written to anyway (and can't be ruled on).
+            if let Ok(path) = std::fs::canonicalize(path) {
+                if !writable.contains(&path) {
+                    writable.push(path);
+                }
+            }
+        }
+        Some(Self {
+            writable,
+            network: policy.network,
+        })
+    }
+
+    /// `bash -c script`, confined. Errors when this platform (or kernel) can't enforce it.
+    pub fn bash_command(&self, script: &str) -> Result<Command, String> {
+        self.confined_bash(script)
+    }
+
+    /// `bash -c script` run under the PTY `slave`, confined like [`Confinement::bash_command`].
+    /// portable-pty has no `pre_exec` hook, so on Linux the child is spawned from a
+    /// short-lived thread that restricts itself first: Landlock and seccomp apply to the
+    /// calling thread and are inherited by what it starts, and this process stays free.
+    pub fn spawn_pty(
+        &self,
+        slave: Box<dyn SlavePty + Send>,
+        script: &str,
+        cwd: &Path,
+    ) -> Result<Box<dyn portable_pty::Child + Send + Sync>, String> {
+        let mut cmd = self.pty_bash(script)?;
+        cmd.cwd(cwd);
+        cmd.env("TERM", "xterm-256color");
+        self.on_confined_thread(move || slave.spawn_command(cmd).map_err(|e| e.to_string()))?
+    }
+
+    /// Apply the rules on a throwaway thread and report what the kernel made of them.
+    /// Landlock is best effort, so an older kernel accepts the ruleset and enforces less
+    /// (or nothing) without failing the commands.
+    #[cfg(target_os = "linux")]
+    pub fn probe(&self) -> Result<Enforcement, String> {
+        use landlock::RulesetStatus;
+
+        let status = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let (ruleset, _) = self.linux_rules()?;
+                    ruleset
+                        .restrict_self()
+                        .map_err(|e| format!("landlock: {}", e))
+                })
+                .join()
+                .map_err(|_| "confinement probe panicked".to_string())?
+        })?;
+        Ok(match status.ruleset {
+            RulesetStatus::FullyEnforced => Enforcement::Full,
+            RulesetStatus::PartiallyEnforced => Enforcement::Partial,
+            RulesetStatus::NotEnforced => Enforcement::Unsupported,
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pty_bash(&self, script: &str) -> Result<CommandBuilder, String> {
+        let mut cmd = CommandBuilder::new("bash");
+        cmd.arg("-c");
+        cmd.arg(script);
+        Ok(cmd)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_confined_thread<T: Send>(&self, run: impl FnOnce() -> T + Send) -> Result<T, String> {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let (ruleset, filter) = self.linux_rules()?;
+                    ruleset
+                        .restrict_self()
+                        .map_err(|e| format!("landlock: {}", e))?;
+                    if let Some(filter) = &filter {
+                        seccompiler::apply_filter(filter).map_err(|e| format!("seccomp: {}", e))?;
+                    }
+                    Ok(run())
+                })
+                .join()
+                .map_err(|_| "confined spawn panicked".to_string())?
+        })
+    }
+
+    /// The Landlock ruleset (everything readable, `writable` writable) and, without
+    /// `network`, the seccomp filter.
+    #[cfg(target_os = "linux")]
+    fn linux_rules(
+        &self,
+    ) -> Result<(landlock::RulesetCreated, Option<seccompiler::BpfProgram>), String> {
+        use landlock::{
+            path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, ABI,
+        };
+
+        let abi = ABI::V3;
+        let ruleset = Ruleset::default()
+            .handle_access(AccessFs::from_all(abi))
+            .and_then(|r| r.create())
+            .and_then(|r| r.add_rules(path_beneath_rules(["/"], AccessFs::from_read(abi))))
+            .and_then(|r| r.add_rules(path_beneath_rules(&self.writable, AccessFs::from_all(abi))))
+            .map_err(|e| format!("landlock: {}", e))?;
+        let filter = if self.network {
+            None
+        } else {
+            Some(no_inet_sockets_filter()?)
+        };
+        Ok((ruleset, filter))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn confined_bash(&self, script: &str) -> Result<Command, String> {
+        // Built here rather than in the child: after fork only async-signal-safe work is
+        // allowed, and restricting is just two syscalls.
+        let (ruleset, filter) = self.linux_rules()?;
+
+        let ruleset = std::sync::Mutex::new(Some(ruleset));
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c").arg(script);
+        // SAFETY: the closure only issues prctl/landlock/seccomp syscalls on state prepared
+        // before the fork; it doesn't allocate unless reporting an error.
+        unsafe {
+            cmd.pre_exec(move || {
+                let ruleset = ruleset.lock().ok().and_then(|mut r| r.take());
+                if let Some(ruleset) = ruleset {
+                    ruleset
+                        .restrict_self()
+                        .map_err(|e| std::io::Error::other(e.to_string()))?;
+                }
+                if let Some(filter) = &filter {
+                    seccompiler::apply_filter(filter)
+                        .map_err(|e| std::io::Error::other(e.to_string()))?;
+                }
+                Ok(())
+            });
+        }
+        Ok(cmd)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn confined_bash(&self, script: &str) -> Result<Command, String> {
+        let mut cmd = Command::new(SANDBOX_EXEC);
+        cmd.args(self.seatbelt_args(script)?);
+        Ok(cmd)
+    }
+
+    /// On macOS the PTY child is wrapped in `sandbox-exec` instead.
+    #[cfg(target_os = "macos")]
+    fn pty_bash(&self, script: &str) -> Result<CommandBuilder, String> {
+        let mut cmd = CommandBuilder::new(SANDBOX_EXEC);
+        cmd.args(self.seatbelt_args(script)?);
+        Ok(cmd)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn on_confined_thread<T: Send>(&self, run: impl FnOnce() -> T + Send) -> Result<T, String> {
+        Ok(run())
+    }
+
+    /// Seatbelt applies the whole profile or fails the command.
+    #[cfg(target_os = "macos")]
+    pub fn probe(&self) -> Result<Enforcement, String> {
+        self.seatbelt_args("true").map(|_| Enforcement::Full)
+    }
+
+    /// `sandbox-exec` arguments running `bash -c script` under the Seatbelt profile.
+    #[cfg(target_os = "macos")]
+    fn seatbelt_args(&self, script: &str) -> Result<Vec<String>, String> {
+        if !Path::new(SANDBOX_EXEC).exists() {
+            return Err(format!("{} not found", SANDBOX_EXEC));
+        }
+        Ok(vec![
+            "-p".to_string(),
+            seatbelt_profile(&self.writable, self.network),
+            "bash".to_string(),
+            "-c".to_string(),
+            script.to_string(),
+        ])
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn confined_bash(&self, _script: &str) -> Result<Command, String> {
+        Err("not supported on this platform".to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn pty_bash(&self, _script: &str) -> Result<CommandBuilder, String> {
+        Err("not supported on this platform".to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn on_confined_thread<T: Send>(&self, _run: impl FnOnce() -> T + Send) -> Result<T, String> {
+        Err("not supported on this platform".to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn probe(&self) -> Result<Enforcement, String> {
+        Err("not supported on this platform".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+
+/// Seccomp filter failing `socket(AF_INET|AF_INET6, ...)` with EPERM.
+#[cfg(target_os = "linux")]
+fn no_inet_sockets_filter() -> Result<seccompiler::BpfProgram, String> {
+    use seccompiler::{
+        SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter, SeccompRule,
+    };
+
+    let family = |af: libc::c_int| {
+        SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, af as u64)
+            .and_then(|c| SeccompRule::new(vec![c]))
+    };
+    let rules = [family(libc::AF_INET), family(libc::AF_INET6)]
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("seccomp: {}", e))?;
+    let arch: seccompiler::TargetArch = std::env::consts::ARCH
+        .try_into()
+        .map_err(|e| format!("seccomp: {}", e))?;
+    SeccompFilter::new(
+        [(libc::SYS_socket, rules)].into_iter().collect(),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EPERM as u32),
+        arch,
+    )
+    .and_then(|f| f.try_into())
+    .map_err(|e| format!("seccomp: {}", e))
+}
+
+/// Seatbelt profile: everything but writes outside `writable` (and, without `network`,
+/// non-Unix sockets) stays allowed.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn seatbelt_profile(writable: &[PathBuf], network: bool) -> String {
+    let mut out = String::from("(version 1)\n(allow default)\n(deny file-write*)\n");
+    out.push_str("(allow file-write* (literal \"/dev/null\") (subpath \"/dev/fd\")");
+    for dir in writable {
+        out.push_str(&format!(" (subpath \"{}\")", seatbelt_escape(dir)));
+    }
+    out.push_str(")\n");
+    if !network {
+        out.push_str("(deny network*)\n(allow network* (remote unix-socket))\n");
+    }
+    out
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn seatbelt_escape(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+/// Download caches builds write to outside the workspace, plus temp dirs; the default
+/// for `sandbox.write_paths`. Only caches: a writable `~/.cargo/bin` or toolchain would
+/// let a confined command plant binaries the user later runs unconfined.
+pub fn default_write_paths() -> Vec<PathBuf> {
+    let mut out = vec![std::env::temp_dir(), PathBuf::from("/tmp")];
+    if let Some(home) = dirs::home_dir() {
+        for dir in [".cargo/registry", ".cargo/git", ".npm/_cacache", ".cache"] {
+            out.push(home.join(dir));
+        }
+        out.push(
+            std::env::var_os("GOMODCACHE")
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| home.join("go").join("pkg").join("mod")),
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, SandboxConfig};
+    use tempfile::TempDir;
+
+    fn policy(root: &Path, sandbox: SandboxConfig) -> SandboxPolicy {
+        let config = AppConfig {
+            sandbox: Some(sandbox),
+            ..AppConfig::default()
+        };
+        SandboxPolicy::from_config(config, root.to_path_buf(), &crate::tool_registry::names())
+    }
+
+    #[test]
+    fn confinement_follows_the_policy() {
+        let dir = TempDir::new().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::create_dir(root.join("cache")).unwrap();
+
+        let cached = policy(
+            &root,
+            SandboxConfig {
+                network: Some(false),
+                write_paths: Some(vec!["cache".into(), "missing".into()]),
+                ..SandboxConfig::default()
+            },
+        );
+        let confined = Confinement::from_policy(&cached).unwrap();
+        assert_eq!(
+            confined,
+            Confinement {
+                writable: vec![root.clone(), root.join("cache")],
+                network: false,
+            }
+        );
+        // Read-only mode drops the workspace but keeps the write paths.
+        let read_only = Confinement::from_policy(&cached.with_read_only(true)).unwrap();
+        assert_eq!(read_only.writable, vec![root.join("cache")]);
+
+        let open = Confinement::from_policy(&policy(&root, SandboxConfig::default())).unwrap();
+        assert!(open.network);
+        assert!(Confinement::from_policy(&policy(
+            &root,
+            SandboxConfig {
+                enforce: Some(false),
+                ..SandboxConfig::default()
+            },
+        ))
+        .is_none());
+    }
+
+    #[test]
+    fn probe_reports_enforcement_without_confining_the_caller() {
+        let dir = TempDir::new().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let confined = Confinement::from_policy(&policy(&root, SandboxConfig::default())).unwrap();
+        // Whatever this kernel supports, probing must leave this thread unrestricted.
+        let _ = confined.probe();
+        let outside = TempDir::new_in(std::env::current_dir().unwrap()).unwrap();
+        std::fs::write(outside.path().join("still-writable"), "ok").unwrap();
+    }
+
+    #[test]
+    fn seatbelt_profile_denies_writes_and_network() {
+        let profile = seatbelt_profile(&[PathBuf::from("/work/my \"repo\"")], false);
+        assert!(profile.contains("(deny file-write*)"));
+        assert!(profile.contains("(subpath \"/work/my \\\"repo\\\"\")"));
+        assert!(profile.contains("(deny network*)"));
+        assert!(!seatbelt_profile(&[], true).contains("network"));
+    }
+
+    #[test]
+    fn default_write_paths_are_caches_only() {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        let paths = default_write_paths();
+        assert!(paths.contains(&home.join(".cargo/registry")));
+        for planted in [".cargo/bin", ".cargo/config.toml", ".rustup"] {
+            let path = home.join(planted);
+            assert!(!paths.iter().any(|p| path.starts_with(p)), "{}", planted);
+        }
+    }
+}
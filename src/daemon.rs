@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::semantic_search::types::{ChunkKind, IndexStats, SearchResult};
+use crate::semantic_search::{SearchConfig, SemanticSearch};
+
+/// Socket under `~/.lorikeet/` that `lorikeet daemon` listens on.
+const SOCKET_FILE: &str = "daemon.sock";
+
+/// Generous because the first search in a workspace loads the model (and may index).
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Quick requests (ping, stop) against a daemon that should answer immediately.
+pub const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One request per line, as JSON, over the daemon socket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    Ping,
+    Search {
+        root: PathBuf,
+        query: String,
+        #[serde(default)]
+        kind: Option<ChunkKind>,
+    },
+    Index {
+        root: PathBuf,
+    },
+    Stats {
+        root: PathBuf,
+    },
+    Shutdown,
+}
+
+/// The daemon's one-line JSON answer to a [`Request`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Pong {
+        pid: u32,
+        /// Workspaces whose model and index are loaded
+        workspaces: Vec<String>,
+    },
+    Results {
+        results: Vec<SearchResult>,
+    },
+    Indexed {
+        stats: IndexStats,
+    },
+    Stats {
+        stats: IndexStats,
+        stale_backend: Option<String>,
+    },
+    Stopping,
+    Error {
+        message: String,
+    },
+}
+
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".lorikeet").join(SOCKET_FILE))
+}
+
+/// Send `request` to a running daemon. `None` if none is listening, in which case
+/// callers do the work in-process as before.
+pub fn request(request: &Request, timeout: Option<Duration>) -> Option<Result<Response, String>> {
+    request_at(&socket_path()?, request, timeout)
+}
+
+/// Search `root`'s index through the daemon.
+pub fn search(
+    root: &Path,
+    query: &str,
+    kind: Option<ChunkKind>,
+) -> Option<Result<Vec<SearchResult>, String>> {
+    let req = Request::Search {
+        root: root.to_path_buf(),
+        query: query.to_string(),
+        kind,
+    };
+    Some(match request(&req, Some(SEARCH_TIMEOUT))? {
+        Ok(Response::Results { results }) => Ok(results),
+        Ok(other) => Err(unexpected(&other)),
+        Err(e) => Err(e),
+    })
+}
+
+/// Index (or refresh) `root` in the daemon, waiting until it's done.
+pub fn index(root: &Path) -> Option<Result<IndexStats, String>> {
+    let req = Request::Index {
+        root: root.to_path_buf(),
+    };
+    Some(match request(&req, None)? {
+        Ok(Response::Indexed { stats }) => Ok(stats),
+        Ok(other) => Err(unexpected(&other)),
+        Err(e) => Err(e),
+    })
+}
+
+/// Index statistics for `root`, plus the id of a stale embedding backend if any.
+pub fn stats(root: &Path) -> Option<Result<(IndexStats, Option<String>), String>> {
+    let req = Request::Stats {
+        root: root.to_path_buf(),
+    };
+    Some(match request(&req, Some(SEARCH_TIMEOUT))? {
+        Ok(Response::Stats {
+            stats,
+            stale_backend,
+        }) => Ok((stats, stale_backend)),
+        Ok(other) => Err(unexpected(&other)),
+        Err(e) => Err(e),
+    })
+}
+
+fn unexpected(response: &Response) -> String {
+    format!("Error: unexpected daemon response: {:?}", response)
+}
+
+#[cfg(unix)]
+fn request_at(
+    socket: &Path,
+    request: &Request,
+    timeout: Option<Duration>,
+) -> Option<Result<Response, String>> {
+    let stream = std::os::unix::net::UnixStream::connect(socket).ok()?;
+    Some(exchange(stream, request, timeout))
+}
+
+#[cfg(not(unix))]
+fn request_at(_: &Path, _: &Request, _: Option<Duration>) -> Option<Result<Response, String>> {
+    None
+}
+
+/// Errors the daemon reports come back as-is; trouble reaching it is prefixed.
+#[cfg(unix)]
+fn exchange(
+    stream: std::os::unix::net::UnixStream,
+    request: &Request,
+    timeout: Option<Duration>,
+) -> Result<Response, String> {
+    let transport = |e: &dyn std::fmt::Display| format!("Error talking to lorikeet daemon: {}", e);
+    stream
+        .set_read_timeout(timeout)
+        .map_err(|e| transport(&e))?;
+    let mut line = serde_json::to_string(request).map_err(|e| transport(&e))?;
+    line.push('\n');
+    (&stream)
+        .write_all(line.as_bytes())
+        .map_err(|e| transport(&e))?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut reply)
+        .map_err(|e| transport(&e))?;
+    if reply.trim().is_empty() {
+        return Err(transport(&"connection closed without a reply"));
+    }
+    match serde_json::from_str(&reply) {
+        Ok(Response::Error { message }) => Err(message),
+        Ok(response) => Ok(response),
+        Err(e) => Err(transport(&format!("bad reply: {}", e))),
+    }
+}
+
+/// Loaded engines, one per workspace, each with its own embedder and index.
+#[derive(Default)]
+struct Daemon {
+    engines: Mutex<HashMap<PathBuf, Arc<Mutex<SemanticSearch>>>>,
+}
+
+impl Daemon {
+    fn engine(&self, root: &Path) -> Result<Arc<Mutex<SemanticSearch>>, String> {
+        let root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        if let Some(engine) = self.engines.lock().get(&root) {
+            return Ok(engine.clone());
+        }
+        // Loading takes seconds; don't hold the map while other workspaces wait.
+        let search = SemanticSearch::new(SearchConfig::for_workspace(&root))
+            .map_err(|e| format!("Error initializing semantic search: {}", e))?;
+        search.set_project_root(root.clone());
+        Ok(self
+            .engines
+            .lock()
+            .entry(root)
+            .or_insert_with(|| Arc::new(Mutex::new(search)))
+            .clone())
+    }
+
+    fn handle(&self, request: Request) -> Response {
+        let result = match request {
+            Request::Ping => Ok(Response::Pong {
+                pid: std::process::id(),
+                workspaces: self
+                    .engines
+                    .lock()
+                    .keys()
+                    .map(|p| p.display().to_string())
+                    .collect(),
+            }),
+            Request::Search { root, query, kind } => self.engine(&root).and_then(|engine| {
+                let results = engine
+                    .lock()
+                    .search_kind(&query, kind)
+                    .map_err(|e| format!("Error searching: {}", e))?;
+                Ok(Response::Results { results })
+            }),
+            Request::Index { root } => self.engine(&root).and_then(|engine| {
+                let stats = engine
+                    .lock()
+                    .index_directory(&root)
+                    .map_err(|e| format!("Error indexing: {}", e))?;
+                Ok(Response::Indexed { stats })
+            }),
+            Request::Stats { root } => self.engine(&root).map(|engine| {
+                let engine = engine.lock();
+                Response::Stats {
+                    stats: engine.stats(),
+                    stale_backend: engine.stale_backend(),
+                }
+            }),
+            Request::Shutdown => Ok(Response::Stopping),
+        };
+        result.unwrap_or_else(|message| Response::Error { message })
+    }
+
+    /// Answer requests on one connection until it closes. True if asked to shut down.
+    #[cfg(unix)]
+    fn serve_connection(&self, stream: std::os::unix::net::UnixStream) -> bool {
+        let mut writer = &stream;
+        for line in BufReader::new(&stream).lines() {
+            let Ok(line) = line else {
+                return false;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, stop) = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Shutdown) => (Response::Stopping, true),
+                Ok(request) => (self.handle(request), false),
+                Err(e) => (
+                    Response::Error {
+                        message: format!("bad request: {}", e),
+                    },
+                    false,
+                ),
+            };
+            let Ok(mut out) = serde_json::to_string(&response) else {
+                return false;
+            };
+            out.push('\n');
+            if writer.write_all(out.as_bytes()).is_err() || stop {
+                return stop;
+            }
+        }
+        false
+    }
+}
+
+/// Run the daemon on `socket` until a [`Request::Shutdown`] arrives. Fails if another
+/// daemon is already listening there; a leftover socket from a crashed one is replaced.
+#[cfg(unix)]
+pub fn serve(socket: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    if UnixStream::connect(socket).is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            format!("a daemon is already listening on {}", socket.display()),
+        ));
+    }
+    let _ = std::fs::remove_file(socket);
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    // Only this user may use the daemon.
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))?;
+
+    let daemon = Arc::new(Daemon::default());
+    let stop = Arc::new(AtomicBool::new(false));
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let daemon = daemon.clone();
+        let stop = stop.clone();
+        let socket = socket.to_path_buf();
+        // Thread per client: requests block on embedding and indexing anyway.
+        std::thread::spawn(move || {
+            if daemon.serve_connection(stream) {
+                stop.store(true, Ordering::SeqCst);
+                // Wake the accept loop so it sees the flag.
+                let _ = UnixStream::connect(&socket);
+            }
+        });
+    }
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the daemon needs Unix domain sockets",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn daemon_answers_and_stops_on_request() {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join(SOCKET_FILE);
+        assert!(request_at(&socket, &Request::Ping, Some(CONTROL_TIMEOUT)).is_none());
+
+        let server = {
+            let socket = socket.clone();
+            std::thread::spawn(move || serve(&socket))
+        };
+        let mut pong = None;
+        for _ in 0..100 {
+            pong = request_at(&socket, &Request::Ping, Some(CONTROL_TIMEOUT));
+            if pong.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        match pong.expect("daemon is listening").unwrap() {
+            Response::Pong { pid, workspaces } => {
+                assert_eq!(pid, std::process::id());
+                assert!(workspaces.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(serve(&socket).is_err(), "a second daemon refuses to start");
+
+        let stopped = request_at(&socket, &Request::Shutdown, Some(CONTROL_TIMEOUT));
+        assert!(matches!(stopped, Some(Ok(Response::Stopping))));
+        server.join().unwrap().unwrap();
+        assert!(!socket.exists());
+    }
+}
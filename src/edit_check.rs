@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tree_sitter::{Node, Parser};
+
+use crate::semantic_search::chunker::get_tree_sitter_language;
+use crate::semantic_search::types::Language;
+
+/// Changed files checked after `verify`; a huge dirty tree isn't worth parsing.
+const MAX_WORKING_TREE_FILES: usize = 50;
+
+/// Files larger than this skip the syntax check; marker scanning is always cheap.
+const MAX_PARSE_BYTES: usize = 512 * 1024;
+
+/// A file an edit is about to change (or just changed), with its content beforehand.
+#[derive(Debug, Clone)]
+pub struct EditedFile {
+    /// Name shown in warnings (as the model wrote it)
+    pub display: String,
+    pub path: PathBuf,
+    /// `None` for a file that didn't exist
+    pub before: Option<String>,
+}
+
+impl EditedFile {
+    pub fn snapshot(display: impl Into<String>, path: PathBuf) -> Self {
+        let before = std::fs::read_to_string(&path).ok();
+        Self {
+            display: display.into(),
+            path,
+            before,
+        }
+    }
+}
+
+/// Files git sees as changed under `root` (modified or untracked), each with its
+/// committed content as `before`. Empty outside a git repository.
+pub fn working_tree_changes(root: &Path) -> Vec<EditedFile> {
+    let Ok(output) = Command::new("git")
+        .args(["ls-files", "--modified", "--others", "--exclude-standard"])
+        .current_dir(root)
+        .output()
+    else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|rel| root.join(rel).is_file())
+        .take(MAX_WORKING_TREE_FILES)
+        .map(|rel| {
+            // `./` makes the path relative to `root` rather than the repository top.
+            let before = Command::new("git")
+                .args(["show", &format!("HEAD:./{}", rel)])
+                .current_dir(root)
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).into_owned());
+            EditedFile {
+                display: rel.to_string(),
+                path: root.join(rel),
+                before,
+            }
+        })
+        .collect()
+}
+
+/// Warnings about leftover merge conflict markers or a file that stopped parsing, one
+/// line per problem, or `None` if the edited files look fine.
+///
+/// A syntax error is only reported when the file parsed cleanly before (or is new), so
+/// grammars that lag the language don't cause noise on files they never understood.
+pub fn review(files: &[EditedFile]) -> Option<String> {
+    let mut warnings = Vec::new();
+    for file in files {
+        let Ok(content) = std::fs::read_to_string(&file.path) else {
+            continue;
+        };
+
+        let markers = conflict_markers(&content);
+        if !markers.is_empty() {
+            let lines: Vec<String> = markers.iter().take(5).map(|l| l.to_string()).collect();
+            warnings.push(format!(
+                "{}: merge conflict markers at line{} {}",
+                file.display,
+                if markers.len() == 1 { "" } else { "s" },
+                lines.join(", ")
+            ));
+            continue;
+        }
+
+        let was_clean = file
+            .before
+            .as_deref()
+            .is_none_or(|before| syntax_error_line(&file.path, before).is_none());
+        if was_clean {
+            if let Some(line) = syntax_error_line(&file.path, &content) {
+                warnings.push(format!(
+                    "{}: no longer parses (syntax error near line {}; unbalanced braces?)",
+                    file.display, line
+                ));
+            }
+        }
+    }
+
+    if warnings.is_empty() {
+        return None;
+    }
+    let mut out = String::from("⚠ Check these files before finishing:\n");
+    for w in warnings {
+        out.push_str("- ");
+        out.push_str(&w);
+        out.push('\n');
+    }
+    Some(out.trim_end().to_string())
+}
+
+/// 1-based lines of `<<<<<<<`/`=======`/`>>>>>>>` markers. A bare `=======` only counts
+/// inside an open conflict, since Markdown and reST use it for headings.
+pub fn conflict_markers(content: &str) -> Vec<usize> {
+    let is_marker = |line: &str, prefix: &str| {
+        line.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+
+    let mut out = Vec::new();
+    let mut open = false;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if is_marker(line, "<<<<<<<") {
+            open = true;
+        } else if is_marker(line, ">>>>>>>") {
+            open = false;
+        } else if !(open && line == "=======") {
+            continue;
+        }
+        out.push(i + 1);
+    }
+    out
+}
+
+/// First line (1-based) where tree-sitter finds an error, for languages with a grammar.
+pub fn syntax_error_line(path: &Path, content: &str) -> Option<usize> {
+    // TSX would need its own grammar; the TypeScript one rejects JSX.
+    if content.len() > MAX_PARSE_BYTES || path.extension().is_some_and(|e| e == "tsx") {
+        return None;
+    }
+    let language = get_tree_sitter_language(Language::detect(path, content))?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(content, None)?;
+    first_error(tree.root_node()).map(|node| node.start_position().row + 1)
+}
+
+fn first_error(node: Node<'_>) -> Option<Node<'_>> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node<'_>> = node.children(&mut cursor).collect();
+    children.into_iter().find_map(first_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn markers_are_found_but_setext_headings_are_not() {
+        let merged = "fn a() {}\n<<<<<<< HEAD\nlet x = 1;\n=======\nlet x = 2;\n>>>>>>> feature\n";
+        assert_eq!(conflict_markers(merged), vec![2, 4, 6]);
+        assert!(conflict_markers("Title\n=======\n\ntext\n").is_empty());
+        assert!(conflict_markers("<<<<<<<< not a marker\n").is_empty());
+    }
+
+    #[test]
+    fn review_flags_markers_and_newly_broken_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lib.rs");
+
+        std::fs::write(&path, "fn main() {\n    let x = 1;\n}\n").unwrap();
+        let file = EditedFile::snapshot("lib.rs", path.clone());
+        std::fs::write(&path, "fn main() {\n    let x = 1;\n\nfn other() {}\n").unwrap();
+        let warning = review(&[file]).expect("broken braces are reported");
+        assert!(warning.contains("lib.rs: no longer parses"), "{}", warning);
+
+        // Already broken before the edit: not the edit's fault.
+        let file = EditedFile::snapshot("lib.rs", path.clone());
+        std::fs::write(&path, "fn main() {\n    let y = 2;\n\nfn other() {}\n").unwrap();
+        assert!(review(&[file]).is_none());
+
+        let file = EditedFile::snapshot("lib.rs", path.clone());
+        std::fs::write(
+            &path,
+            "<<<<<<< ours\nfn a() {}\n=======\nfn b() {}\n>>>>>>> theirs\n",
+        )
+        .unwrap();
+        let warning = review(&[file]).unwrap();
+        assert!(warning.contains("lib.rs: merge conflict markers at lines 1, 3, 5"));
+
+        let file = EditedFile::snapshot("lib.rs", path.clone());
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        assert!(review(&[file]).is_none());
+    }
+}
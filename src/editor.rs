@@ -0,0 +1,178 @@
+//! Handing the terminal to the user's editor. The TUI asks for it through
+//! `App::pending_editor`; the main loop suspends the alternate screen and raw mode while
+//! the editor runs and redraws afterwards.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crossterm::{
+    event::{
+        DisableMouseCapture, EnableBracketedPaste, EnableFocusChange, EnableMouseCapture, KeyCode,
+        KeyModifiers,
+    },
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+
+use crate::config::AppConfig;
+
+/// Set while an editor owns the terminal, so the input reader stops taking its keys.
+pub static INPUT_PAUSED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorRequest {
+    /// Open a file at a 1-based line.
+    Open { path: PathBuf, line: usize },
+    /// Edit the prompt draft saved at `path`; what's there afterwards becomes the input.
+    Compose { path: PathBuf },
+}
+
+/// Default for `editor.compose_key` (Ctrl+G already opens search results).
+const DEFAULT_COMPOSE_KEY: &str = "ctrl+x";
+
+/// The editor command: `editor.command`, else `$VISUAL`, else `$EDITOR`. A restricted
+/// workspace's config could name any program, so there only the environment counts.
+pub fn editor_command(config: &AppConfig, restricted: bool) -> Option<String> {
+    let configured = config
+        .editor
+        .as_ref()
+        .and_then(|e| e.command.clone())
+        .filter(|_| !restricted);
+    configured
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|c| !c.trim().is_empty())
+}
+
+/// Argv for opening `path` at `line`. `{file}` and `{line}` in the command are filled in
+/// (e.g. `code -g {file}:{line}`); otherwise it's `<command> +<line> <file>`, which vi,
+/// vim, nano, emacs and micro understand.
+pub fn open_args(command: &str, path: &Path, line: usize) -> Vec<String> {
+    editor_args(command, path, Some(line))
+}
+
+/// Argv for editing a prompt draft: like [`open_args`], without jumping to a line.
+pub fn compose_args(command: &str, path: &Path) -> Vec<String> {
+    editor_args(command, path, None)
+}
+
+fn editor_args(command: &str, path: &Path, line: Option<usize>) -> Vec<String> {
+    let file = path.display().to_string();
+    let mut args: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+    if command.contains("{file}") {
+        let line = line.unwrap_or(1).to_string();
+        for arg in &mut args {
+            *arg = arg.replace("{file}", &file).replace("{line}", &line);
+        }
+    } else {
+        if let Some(line) = line {
+            args.push(format!("+{}", line.max(1)));
+        }
+        args.push(file);
+    }
+    args
+}
+
+/// Where the input is saved for composing in the editor.
+pub fn draft_path() -> PathBuf {
+    std::env::temp_dir().join(format!("lorikeet-prompt-{}.md", std::process::id()))
+}
+
+/// The key that opens the input in the editor: `editor.compose_key`, default Ctrl+X.
+pub fn compose_key(config: &AppConfig) -> Option<(KeyModifiers, KeyCode)> {
+    let spec = config
+        .editor
+        .as_ref()
+        .and_then(|e| e.compose_key.as_deref())
+        .unwrap_or(DEFAULT_COMPOSE_KEY);
+    parse_key(spec)
+}
+
+/// `"ctrl+x"`, `"alt+e"`, `"f2"`, ... (case-insensitive).
+pub fn parse_key(spec: &str) -> Option<(KeyModifiers, KeyCode)> {
+    let spec = spec.trim().to_ascii_lowercase();
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = spec.split('+').collect();
+    let key = parts.pop()?;
+    for part in parts {
+        modifiers |= match part {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => KeyCode::F(key.strip_prefix('f')?.parse().ok()?),
+    };
+    Some((modifiers, code))
+}
+
+/// Run `args` with the terminal handed over, then take it back.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let Some((program, rest)) = args.split_first() else {
+        return Err("no editor configured (set $EDITOR or editor.command)".to_string());
+    };
+    INPUT_PAUSED.store(true, Ordering::SeqCst);
+    // Let the input reader finish its current poll so it can't steal the editor's keys.
+    std::thread::sleep(Duration::from_millis(60));
+    let _ = disable_raw_mode();
+    let _ = io::stdout().execute(DisableMouseCapture);
+    let _ = io::stdout().execute(LeaveAlternateScreen);
+
+    let status = std::process::Command::new(program).args(rest).status();
+
+    let _ = io::stdout().execute(EnterAlternateScreen);
+    let _ = io::stdout().execute(EnableMouseCapture);
+    // Editors like vim switch bracketed paste and focus reporting off on exit.
+    let _ = io::stdout().execute(EnableBracketedPaste);
+    let _ = io::stdout().execute(EnableFocusChange);
+    let _ = enable_raw_mode();
+    INPUT_PAUSED.store(false, Ordering::SeqCst);
+
+    match status {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(format!("{} exited with {}", program, s)),
+        Err(e) => Err(format!("can't run {}: {}", program, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_args_fill_the_template_or_use_plus_line() {
+        let path = Path::new("/work/src/main.rs");
+        assert_eq!(
+            open_args("vim", path, 42),
+            vec!["vim", "+42", "/work/src/main.rs"]
+        );
+        assert_eq!(
+            open_args("code --wait -g {file}:{line}", path, 7),
+            vec!["code", "--wait", "-g", "/work/src/main.rs:7"]
+        );
+        assert_eq!(
+            compose_args("nvim", Path::new("/tmp/p.md")),
+            vec!["nvim", "/tmp/p.md"]
+        );
+    }
+
+    #[test]
+    fn compose_keys_parse() {
+        assert_eq!(
+            parse_key("Ctrl+G"),
+            Some((KeyModifiers::CONTROL, KeyCode::Char('g')))
+        );
+        assert_eq!(parse_key("f2"), Some((KeyModifiers::NONE, KeyCode::F(2))));
+        assert_eq!(parse_key("hyper+x"), None);
+        assert_eq!(
+            compose_key(&AppConfig::default()),
+            Some((KeyModifiers::CONTROL, KeyCode::Char('x')))
+        );
+    }
+}
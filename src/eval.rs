@@ -0,0 +1,501 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::app::{App, Role};
+use crate::approval::Approval;
+use crate::config::{AppConfig, MemoryConfig};
+use crate::events::AppEvent;
+use crate::llm::LlmProvider;
+use crate::memory::MemoryManager;
+use crate::sandbox::SandboxPolicy;
+use crate::trust::TrustLevel;
+
+/// Agent time per task unless the task sets `timeout_secs`.
+const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A suite file (`lorikeet eval suite.toml`): tasks run in order, each in a fresh copy
+/// of its fixture.
+#[derive(Debug, Deserialize)]
+pub struct Suite {
+    /// Model for every task (default: the configured one)
+    pub model: Option<String>,
+    #[serde(rename = "task", default)]
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Task {
+    pub name: String,
+    /// Directory copied to a temp dir for the run, relative to the suite file
+    pub fixture: PathBuf,
+    pub prompt: String,
+    pub timeout_secs: Option<u64>,
+    #[serde(rename = "assert", default)]
+    pub asserts: Vec<Assertion>,
+}
+
+/// One `[[task.assert]]` check against the workspace once the agent is done.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Assertion {
+    FileExists(String),
+    FileAbsent(String),
+    FileContains {
+        path: String,
+        text: String,
+    },
+    /// Shell command that must exit 0, e.g. `cargo test`
+    Command(String),
+    /// Unified diff (relative to the suite file) whose added and removed lines the run
+    /// must reproduce, in order; hunk positions and context are ignored
+    Diff(PathBuf),
+}
+
+impl Assertion {
+    fn describe(&self) -> String {
+        match self {
+            Self::FileExists(path) => format!("file exists: {}", path),
+            Self::FileAbsent(path) => format!("file absent: {}", path),
+            Self::FileContains { path, text } => format!("{} contains {:?}", path, text),
+            Self::Command(command) => format!("command: {}", command),
+            Self::Diff(path) => format!("diff matches {}", path.display()),
+        }
+    }
+}
+
+/// Credentials and model the agent runs with.
+pub struct EvalTarget {
+    pub provider: LlmProvider,
+    pub api_key: String,
+    pub codex_account_id: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TaskReport {
+    pub name: String,
+    /// Set when the agent itself failed (error, timeout) before checks ran
+    pub error: Option<String>,
+    pub checks: Vec<(String, Result<(), String>)>,
+    pub tool_calls: usize,
+    pub elapsed: Duration,
+}
+
+impl TaskReport {
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.checks.iter().all(|(_, r)| r.is_ok())
+    }
+}
+
+pub fn load_suite(path: &Path) -> Result<Suite, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+/// Run one task: copy the fixture, let the agent work on the prompt headlessly (unusual
+/// commands are approved; the sandbox still applies), then run the checks.
+pub async fn run_task(
+    suite_dir: &Path,
+    task: &Task,
+    target: &EvalTarget,
+    workdir: &Path,
+) -> TaskReport {
+    let started = Instant::now();
+    let mut report = TaskReport {
+        name: task.name.clone(),
+        error: None,
+        checks: Vec::new(),
+        tool_calls: 0,
+        elapsed: Duration::ZERO,
+    };
+
+    if let Err(e) = prepare_workdir(&suite_dir.join(&task.fixture), workdir) {
+        report.error = Some(e);
+        return report;
+    }
+
+    // Tools resolve paths against the process cwd, as in the TUI.
+    let previous = std::env::current_dir().ok();
+    let _ = std::env::set_current_dir(workdir);
+    let timeout = task
+        .timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TASK_TIMEOUT);
+    match run_agent(&task.prompt, workdir, target, timeout).await {
+        Ok(tool_calls) => report.tool_calls = tool_calls,
+        Err(e) => report.error = Some(e),
+    }
+    if report.error.is_none() {
+        for assertion in &task.asserts {
+            let result = check(assertion, suite_dir, workdir).await;
+            report.checks.push((assertion.describe(), result));
+        }
+    }
+    if let Some(dir) = previous {
+        let _ = std::env::set_current_dir(dir);
+    }
+
+    report.elapsed = started.elapsed();
+    report
+}
+
+/// Copy `fixture` to `workdir` and commit it, so the run's changes are a plain `git diff`.
+fn prepare_workdir(fixture: &Path, workdir: &Path) -> Result<(), String> {
+    if !fixture.is_dir() {
+        return Err(format!(
+            "Error: fixture {} is not a directory",
+            fixture.display()
+        ));
+    }
+    copy_dir(fixture, workdir).map_err(|e| format!("Error copying fixture: {}", e))?;
+    git(workdir, &["init", "-q"])?;
+    // Lorikeet keeps memory and command history under .lorikeet/; not part of the result.
+    std::fs::write(workdir.join(".git/info/exclude"), ".lorikeet/\n")
+        .map_err(|e| format!("Error writing git exclude: {}", e))?;
+    git(workdir, &["add", "-A"])?;
+    git(
+        workdir,
+        &[
+            "-c",
+            "user.name=lorikeet-eval",
+            "-c",
+            "user.email=eval@lorikeet.invalid",
+            "-c",
+            "commit.gpgsign=false",
+            "commit",
+            "-q",
+            "--allow-empty",
+            "-m",
+            "fixture",
+        ],
+    )?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in walkdir::WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(from).unwrap_or(entry.path());
+        let dest = to.join(rel);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&dest)?;
+        } else {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Error running git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Error: git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Drive an [`App`] without a terminal until the turn ends. Returns the number of tool
+/// calls made.
+async fn run_agent(
+    prompt: &str,
+    workdir: &Path,
+    target: &EvalTarget,
+    timeout: Duration,
+) -> Result<usize, String> {
+    // Eval workdirs are trusted (see below), project config included.
+    let mut config = AppConfig::load_layered_as(workdir, true);
+    // Runs shouldn't learn from, or be steered by, the user's memories.
+    config.memory = Some(MemoryConfig {
+        enabled: Some(false),
+        ..Default::default()
+    });
+    let policy = Arc::new(SandboxPolicy::from_config(
+        config.clone(),
+        workdir.to_path_buf(),
+        &crate::tool_registry::names(),
+    ));
+    let memory = Arc::new(
+        MemoryManager::init(workdir)
+            .await
+            .map_err(|e| format!("Error initializing memory: {}", e))?,
+    );
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+    let mut app = App::new(
+        tx,
+        target.provider,
+        target.api_key.clone(),
+        target.codex_account_id.clone(),
+        policy,
+        config,
+        workdir.to_path_buf(),
+        memory,
+    );
+    if let Some(model) = &target.model {
+        app.model = model.clone();
+    }
+    app.workspace_trust = Some(TrustLevel::Trusted);
+    app.submit_prompt(prompt);
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    while app.is_processing {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(AppEvent::ToolApproval(request))) => request.answer(Approval::Once),
+            Ok(Some(event)) => app.handle_event(event),
+            Ok(None) => break,
+            Err(_) => return Err(format!("timed out after {}s", timeout.as_secs())),
+        }
+    }
+
+    let failed = app
+        .messages
+        .iter()
+        .rev()
+        .take_while(|m| m.role != Role::User)
+        .find(|m| m.local && m.content.starts_with("[Error:"));
+    if let Some(m) = failed {
+        return Err(m.content.clone());
+    }
+    Ok(app.tool_outputs.len())
+}
+
+async fn check(assertion: &Assertion, suite_dir: &Path, workdir: &Path) -> Result<(), String> {
+    match assertion {
+        Assertion::FileExists(path) => {
+            if workdir.join(path).exists() {
+                Ok(())
+            } else {
+                Err("missing".into())
+            }
+        }
+        Assertion::FileAbsent(path) => {
+            if workdir.join(path).exists() {
+                Err("still exists".into())
+            } else {
+                Ok(())
+            }
+        }
+        Assertion::FileContains { path, text } => {
+            match std::fs::read_to_string(workdir.join(path)) {
+                Ok(content) if content.contains(text.as_str()) => Ok(()),
+                Ok(_) => Err("text not found".into()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        Assertion::Command(command) => {
+            let output = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .current_dir(workdir)
+                .output()
+                .await
+                .map_err(|e| e.to_string())?;
+            if output.status.success() {
+                return Ok(());
+            }
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            let tail: Vec<&str> = text.lines().rev().take(3).collect();
+            Err(format!(
+                "{}: {}",
+                output.status,
+                tail.into_iter().rev().collect::<Vec<_>>().join(" | ")
+            ))
+        }
+        Assertion::Diff(expected) => {
+            let expected = std::fs::read_to_string(suite_dir.join(expected))
+                .map_err(|e| format!("can't read expected diff: {}", e))?;
+            git(workdir, &["add", "-A"])?;
+            let actual = git(workdir, &["diff", "--cached"])?;
+            let (want, got) = (changed_lines(&expected), changed_lines(&actual));
+            if want == got {
+                return Ok(());
+            }
+            let first = want
+                .iter()
+                .zip(&got)
+                .position(|(w, g)| w != g)
+                .unwrap_or(want.len().min(got.len()));
+            Err(format!(
+                "differs at changed line {}: expected {:?}, got {:?}",
+                first + 1,
+                want.get(first).map(String::as_str).unwrap_or("<end>"),
+                got.get(first).map(String::as_str).unwrap_or("<end>")
+            ))
+        }
+    }
+}
+
+/// `+`/`-` lines of a unified diff without file headers, trailing whitespace trimmed.
+fn changed_lines(diff: &str) -> Vec<String> {
+    diff.lines()
+        .filter(|l| !l.starts_with("+++") && !l.starts_with("---"))
+        .filter(|l| l.starts_with('+') || l.starts_with('-'))
+        .map(|l| l.trim_end().to_string())
+        .collect()
+}
+
+/// Per-task lines and the pass rate, as printed by `lorikeet eval`.
+pub fn format_reports(reports: &[TaskReport]) -> String {
+    let mut out = String::new();
+    for r in reports {
+        out.push_str(&format!(
+            "{} {} ({:.0}s, {} tool call{})\n",
+            if r.passed() { "✓" } else { "✗" },
+            r.name,
+            r.elapsed.as_secs_f64(),
+            r.tool_calls,
+            if r.tool_calls == 1 { "" } else { "s" }
+        ));
+        if let Some(e) = &r.error {
+            out.push_str(&format!("    ✗ agent: {}\n", e));
+        }
+        for (what, result) in &r.checks {
+            if let Err(e) = result {
+                out.push_str(&format!("    ✗ {}: {}\n", what, e));
+            }
+        }
+    }
+    let passed = reports.iter().filter(|r| r.passed()).count();
+    let rate = if reports.is_empty() {
+        0.0
+    } else {
+        passed as f64 * 100.0 / reports.len() as f64
+    };
+    out.push_str(&format!(
+        "Passed {}/{} ({:.0}%)",
+        passed,
+        reports.len(),
+        rate
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn suite_parses_tasks_and_assertions() {
+        let suite: Suite = toml::from_str(
+            r#"
+model = "openai/gpt-4o-mini"
+
+[[task]]
+name = "add-flag"
+fixture = "fixtures/cli"
+prompt = "Add a --verbose flag"
+timeout_secs = 120
+
+[[task.assert]]
+file_exists = "src/main.rs"
+
+[[task.assert]]
+file_contains = { path = "src/main.rs", text = "verbose" }
+
+[[task.assert]]
+command = "cargo test"
+
+[[task.assert]]
+diff = "expected/add-flag.diff"
+"#,
+        )
+        .unwrap();
+        assert_eq!(suite.model.as_deref(), Some("openai/gpt-4o-mini"));
+        let task = &suite.tasks[0];
+        assert_eq!(task.timeout_secs, Some(120));
+        assert_eq!(task.asserts.len(), 4);
+        assert!(matches!(
+            &task.asserts[1],
+            Assertion::FileContains { path, text } if path == "src/main.rs" && text == "verbose"
+        ));
+        assert!(matches!(&task.asserts[3], Assertion::Diff(p) if p.ends_with("add-flag.diff")));
+    }
+
+    #[tokio::test]
+    async fn checks_run_against_the_workdir() {
+        let suite = TempDir::new().unwrap();
+        let fixture = suite.path().join("fixture");
+        std::fs::create_dir_all(fixture.join("src")).unwrap();
+        std::fs::write(fixture.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        let work = TempDir::new().unwrap();
+        if prepare_workdir(&fixture, work.path()).is_err() {
+            // No git here; nothing else to check.
+            return;
+        }
+
+        // What an agent might have done.
+        std::fs::write(work.path().join("src/lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        std::fs::write(
+            suite.path().join("expected.diff"),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1,2 @@\n fn a() {}\n+fn b() {}\n",
+        )
+        .unwrap();
+
+        let ok = [
+            Assertion::FileExists("src/lib.rs".into()),
+            Assertion::FileAbsent("src/gone.rs".into()),
+            Assertion::FileContains {
+                path: "src/lib.rs".into(),
+                text: "fn b()".into(),
+            },
+            Assertion::Command("test -f src/lib.rs".into()),
+            Assertion::Diff("expected.diff".into()),
+        ];
+        for a in &ok {
+            assert_eq!(check(a, suite.path(), work.path()).await, Ok(()), "{:?}", a);
+        }
+        let failing = Assertion::Command("echo boom; exit 3".into());
+        let err = check(&failing, suite.path(), work.path())
+            .await
+            .unwrap_err();
+        assert!(err.contains("boom"), "{}", err);
+        assert!(check(
+            &Assertion::FileExists("nope".into()),
+            suite.path(),
+            work.path()
+        )
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn reports_show_failures_and_the_pass_rate() {
+        let report = |name: &str, checks: Vec<(String, Result<(), String>)>| TaskReport {
+            name: name.into(),
+            error: None,
+            checks,
+            tool_calls: 1,
+            elapsed: Duration::from_secs(3),
+        };
+        let out = format_reports(&[
+            report("ok", vec![("file exists: a".into(), Ok(()))]),
+            report(
+                "bad",
+                vec![("command: cargo test".into(), Err("exit 101".into()))],
+            ),
+        ]);
+        assert!(out.contains("✓ ok (3s, 1 tool call)"));
+        assert!(out.contains("✗ bad"));
+        assert!(out.contains("    ✗ command: cargo test: exit 101"));
+        assert!(out.ends_with("Passed 1/2 (50%)"));
+    }
+}
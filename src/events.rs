@@ -1,6 +1,11 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::approval::ApprovalRequest;
+use crate::llm::{AgentError, ModelInfo, TokenUsage};
+use crate::memory::types::Memory;
 use crate::sandbox::SandboxDecision;
+use crate::semantic_search::types::IndexProgress;
 use crate::types::ToolCallMessage;
 
 #[derive(Debug, Clone)]
@@ -29,22 +34,174 @@ pub struct ToolCompleteEvent {
 pub enum AppEvent {
     Input(crossterm::event::KeyEvent),
     Mouse(crossterm::event::MouseEvent),
+    /// Text pasted into the terminal (bracketed paste), delivered in one piece.
+    Paste(String),
+    /// The terminal gained (`true`) or lost focus.
+    Focus(bool),
+    /// The terminal was resized; the next draw picks up the new size.
+    Resize,
     AgentChunk(String),
     AgentReasoning(String),
     AgentDone,
     AgentToolCalls(Vec<ToolCallMessage>),
     ToolResultsReady(Vec<(String, String)>), // (tool_call_id, result)
-    AgentError(String),
+    AgentError(AgentError),
+    /// The provider was unreachable (after retries and fallback); the turn waits to be
+    /// resent instead of failing.
+    AgentOffline(AgentError),
+    /// Time for reconnect attempt `n` of the turn waiting in [`AppEvent::AgentOffline`].
+    Reconnect(u32),
+    /// Token usage reported at the end of an LLM response.
+    LlmUsage(TokenUsage),
+    /// Wall time of one model call, retries and fallback included, for `/stats`.
+    LlmTiming(Duration),
+    /// Result of fetching the provider's model list for the `/model` picker.
+    ModelsLoaded(Result<Vec<ModelInfo>, String>),
+    /// This project's memories for the `/memory` browser, with the outcome of the edit
+    /// or delete that triggered the reload.
+    MemoriesLoaded {
+        memories: Result<Vec<Memory>, String>,
+        status: Option<String>,
+    },
+    /// A transient LLM failure is being retried (or failed over); the turn continues.
+    AgentRetry(String),
 
     ToolStart(ToolStartEvent),
+    /// An allowed but unusual command is waiting for the user to approve it.
+    ToolApproval(ApprovalRequest),
     /// A chunk of streaming tool output to append.
     ToolOutput(ToolOutputEvent),
+    /// A line of a PTY-run command as the terminal got it, escape sequences intact; the
+    /// cleaned text arrives as `ToolOutput`.
+    ToolOutputRaw(ToolOutputEvent),
     ToolComplete(ToolCompleteEvent),
 
     // Indexing events
     IndexingStarted,
-    #[allow(dead_code)]
-    IndexingProgress(usize, usize), // (files_indexed, total_files)
+    IndexingProgress(IndexProgress),
     IndexingComplete(usize, usize), // (chunks, files)
     IndexingError(String),
+
+    /// A freshly generated repository map for the system context.
+    RepoMapReady(String),
+
+    /// The ChatGPT sign-in page for a `/login` in progress.
+    CodexLoginUrl(String),
+    /// How a `/login` ended.
+    CodexLoginDone(Result<crate::codex_oauth::CodexChatgptAuth, String>),
+}
+
+impl AppEvent {
+    /// The event as one `--output json` line: `{"type": "...", ...}`. Terminal input and
+    /// events that only feed TUI popups have none.
+    pub fn to_json(&self) -> Option<serde_json::Value> {
+        use serde_json::json;
+        Some(match self {
+            AppEvent::Input(_)
+            | AppEvent::Mouse(_)
+            | AppEvent::Paste(_)
+            | AppEvent::Focus(_)
+            | AppEvent::Resize
+            | AppEvent::ToolOutputRaw(_)
+            | AppEvent::RepoMapReady(_)
+            | AppEvent::CodexLoginUrl(_)
+            | AppEvent::CodexLoginDone(_)
+            | AppEvent::ModelsLoaded(_)
+            | AppEvent::Reconnect(_)
+            | AppEvent::MemoriesLoaded { .. } => return None,
+            AppEvent::AgentChunk(text) => json!({ "type": "message_delta", "text": text }),
+            AppEvent::AgentReasoning(text) => json!({ "type": "reasoning_delta", "text": text }),
+            AppEvent::AgentDone => json!({ "type": "message_done" }),
+            AppEvent::AgentToolCalls(calls) => json!({
+                "type": "tool_calls",
+                "calls": calls
+                    .iter()
+                    .map(|c| json!({
+                        "id": c.id,
+                        "name": c.function.name,
+                        "arguments": c.function.arguments,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            AppEvent::ToolResultsReady(results) => json!({
+                "type": "tool_results",
+                "results": results
+                    .iter()
+                    .map(|(call_id, result)| json!({ "call_id": call_id, "result": result }))
+                    .collect::<Vec<_>>(),
+            }),
+            AppEvent::AgentError(err) => json!({
+                "type": "error",
+                "kind": err.kind.name(),
+                "message": err.to_string(),
+            }),
+            AppEvent::AgentOffline(err) => json!({ "type": "offline", "message": err.to_string() }),
+            AppEvent::AgentRetry(message) => json!({ "type": "retry", "message": message }),
+            AppEvent::LlmUsage(usage) => json!({ "type": "usage", "usage": usage }),
+            AppEvent::LlmTiming(elapsed) => {
+                json!({ "type": "llm_timing", "ms": elapsed.as_millis() as u64 })
+            }
+            AppEvent::ToolStart(ev) => json!({
+                "type": "tool_start",
+                "call_id": ev.call_id,
+                "tool": ev.tool,
+                "arguments": ev.args_raw,
+                "summary": ev.args_summary,
+                "cwd": ev.cwd,
+                "sandbox": { "allowed": ev.sandbox.allowed, "reason": ev.sandbox.reason },
+            }),
+            AppEvent::ToolApproval(request) => json!({
+                "type": "approval_request",
+                "call_id": request.call_id,
+                "command": request.command,
+            }),
+            AppEvent::ToolOutput(ev) => {
+                json!({ "type": "tool_output", "call_id": ev.call_id, "chunk": ev.chunk })
+            }
+            AppEvent::ToolComplete(ev) => json!({
+                "type": "tool_complete",
+                "call_id": ev.call_id,
+                "success": ev.success,
+            }),
+            AppEvent::IndexingStarted => json!({ "type": "indexing_started" }),
+            AppEvent::IndexingProgress(p) => json!({
+                "type": "indexing_progress",
+                "files_done": p.files_done,
+                "total_files": p.total_files,
+                "current": p.current.as_ref().map(|c| c.display().to_string()),
+            }),
+            AppEvent::IndexingComplete(chunks, files) => {
+                json!({ "type": "indexing_complete", "chunks": chunks, "files": files })
+            }
+            AppEvent::IndexingError(message) => {
+                json!({ "type": "indexing_error", "message": message })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_serialize_with_a_type_tag() {
+        let start = AppEvent::ToolStart(ToolStartEvent {
+            call_id: "c1".into(),
+            tool: "bash".into(),
+            args_raw: r#"{"command":"ls"}"#.into(),
+            args_summary: "ls".into(),
+            cwd: PathBuf::from("/work"),
+            sandbox: SandboxDecision::allow(),
+        });
+        let v = start.to_json().unwrap();
+        assert_eq!(v["type"], "tool_start");
+        assert_eq!(v["tool"], "bash");
+        assert_eq!(v["sandbox"]["allowed"], true);
+
+        let chunk = AppEvent::AgentChunk("Hel".into()).to_json().unwrap();
+        assert_eq!(chunk["type"], "message_delta");
+        assert_eq!(chunk["text"], "Hel");
+        assert!(AppEvent::Focus(true).to_json().is_none());
+    }
 }
@@ -0,0 +1,427 @@
+use std::fmt::Write as _;
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, Event, Options, Parser};
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::app::{Message, Role, ToolOutput, ToolStatus};
+use crate::memory::redaction::Redactor;
+use crate::stats::SessionStats;
+
+/// Tool outputs longer than this are cut in exports; the session file keeps the full text.
+const MAX_TOOL_OUTPUT_LINES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    /// Provider-agnostic messages for eval sets and fine-tuning data
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" => Some(Self::Html),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Render a transcript (messages + tool traces) as a shareable document, or as JSON
+/// messages (see [`render_json`]).
+///
+/// Tool runs are placed after the assistant message that requested them (matched by
+/// tool group id), with outputs collapsed in `<details>` blocks. Session stats close the
+/// document.
+pub fn render(
+    format: ExportFormat,
+    title: &str,
+    messages: &[Message],
+    tools: &[ToolOutput],
+    stats: &SessionStats,
+) -> String {
+    if format == ExportFormat::Json {
+        return render_json(title, messages, tools, stats);
+    }
+    let mut md = render_markdown(title, messages, tools);
+    let _ = writeln!(md, "## Stats\n\n{}", stats.to_markdown());
+    if format == ExportFormat::Html {
+        return render_html(title, &md);
+    }
+    md
+}
+
+fn render_markdown(title: &str, messages: &[Message], tools: &[ToolOutput]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", title);
+
+    for msg in messages {
+        let heading = match msg.role {
+            Role::User => "User",
+            Role::Agent => "Assistant",
+            Role::Draft => "Assistant (interrupted draft)",
+            Role::Plan => "Plan",
+            // System prompt and raw tool results are represented by the tool traces.
+            Role::System | Role::Tool => continue,
+        };
+        if msg.content.trim().is_empty() && msg.tool_group_id.is_none() {
+            continue;
+        }
+
+        let _ = writeln!(out, "## {}\n", heading);
+        if let Some(reasoning) = msg.reasoning.as_deref().filter(|r| !r.trim().is_empty()) {
+            let _ = writeln!(
+                out,
+                "<details><summary>Reasoning</summary>\n\n{}\n\n</details>\n",
+                reasoning.trim()
+            );
+        }
+        if !msg.content.trim().is_empty() {
+            let _ = writeln!(out, "{}\n", msg.content.trim());
+        }
+
+        if let Some(group_id) = msg.tool_group_id {
+            for tool in tools.iter().filter(|t| t.group_id == group_id) {
+                push_tool(&mut out, tool);
+            }
+        }
+    }
+
+    out
+}
+
+fn push_tool(out: &mut String, tool: &ToolOutput) {
+    let status = match tool.status {
+        ToolStatus::Running => "running",
+        ToolStatus::Success => "ok",
+        ToolStatus::Error => "error",
+    };
+    let _ = writeln!(
+        out,
+        "<details><summary>{} <code>{}</code> — {}</summary>\n",
+        tool.tool,
+        escape_html(&tool.args_summary),
+        status
+    );
+
+    let output = tool.full_output();
+    let lines: Vec<&str> = output.lines().collect();
+    let fence = if output.contains("```") {
+        "~~~~"
+    } else {
+        "```"
+    };
+    let _ = writeln!(out, "{}text", fence);
+    for line in lines.iter().take(MAX_TOOL_OUTPUT_LINES) {
+        let _ = writeln!(out, "{}", line);
+    }
+    if lines.len() > MAX_TOOL_OUTPUT_LINES {
+        let _ = writeln!(out, "… {} more lines", lines.len() - MAX_TOOL_OUTPUT_LINES);
+    }
+    let _ = writeln!(out, "{}\n\n</details>\n", fence);
+}
+
+/// The conversation as the model saw it: system, user, assistant (with tool calls) and
+/// tool result messages, without local notices, drafts or injected memory. Secrets are
+/// redacted everywhere, including inside tool arguments.
+fn render_json(
+    title: &str,
+    messages: &[Message],
+    tools: &[ToolOutput],
+    stats: &SessionStats,
+) -> String {
+    let redactor = Redactor::new();
+    let redact = |s: &str| redactor.redact_secrets(s);
+
+    let mut out = Vec::new();
+    for msg in messages.iter().filter(|m| !m.local) {
+        let entry = match msg.role {
+            Role::System if msg.content.starts_with("\n[Memory]\n") => continue,
+            Role::System => json!({"role": "system", "content": redact(&msg.content)}),
+            Role::User => json!({"role": "user", "content": redact(&msg.content)}),
+            Role::Agent | Role::Draft | Role::Plan => {
+                let mut entry = json!({
+                    "role": "assistant",
+                    "content": (!msg.content.is_empty()).then(|| redact(&msg.content)),
+                });
+                if let Some(reasoning) = msg.reasoning.as_deref().filter(|r| !r.is_empty()) {
+                    entry["reasoning"] = json!(redact(reasoning));
+                }
+                if let Some(calls) = msg.tool_calls.as_ref().filter(|c| !c.is_empty()) {
+                    let calls: Vec<Value> = calls
+                        .iter()
+                        .map(|c| {
+                            let mut arguments = serde_json::from_str(&c.function.arguments)
+                                .unwrap_or_else(|_| Value::String(c.function.arguments.clone()));
+                            redact_value(&mut arguments, &redact);
+                            json!({
+                                "id": c.id,
+                                "name": c.function.name,
+                                "arguments": arguments,
+                            })
+                        })
+                        .collect();
+                    entry["tool_calls"] = Value::Array(calls);
+                }
+                entry
+            }
+            Role::Tool => {
+                // Tool messages keep their call id in `reasoning`.
+                let call_id = msg.reasoning.clone().unwrap_or_default();
+                let tool = tools.iter().find(|t| t.call_id == call_id);
+                json!({
+                    "role": "tool",
+                    "tool_call_id": call_id,
+                    "name": tool.map(|t| t.tool.clone()),
+                    "ok": tool.map(|t| t.status == ToolStatus::Success),
+                    "content": redact(&msg.content),
+                })
+            }
+        };
+        out.push(entry);
+    }
+
+    let calls = stats.tool_calls();
+    let doc = json!({
+        "title": title,
+        "messages": out,
+        "stats": {
+            "turns": stats.turns,
+            "tool_calls": calls.ok + calls.failed,
+            "failed_tool_calls": calls.failed,
+            "files_changed": stats.files_changed.len(),
+        },
+    });
+    serde_json::to_string_pretty(&doc).unwrap_or_default() + "\n"
+}
+
+fn redact_value(v: &mut Value, redact: &dyn Fn(&str) -> String) {
+    match v {
+        Value::String(s) => *s = redact(s),
+        Value::Array(items) => items.iter_mut().for_each(|x| redact_value(x, redact)),
+        Value::Object(map) => map.values_mut().for_each(|x| redact_value(x, redact)),
+        _ => {}
+    }
+}
+
+fn render_html(title: &str, md: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    // Raw HTML in messages and tool output is shown as text, so a transcript that quotes
+    // `<script>` or `<img onerror=...>` can't run anything in the exported page. Only the
+    // `<details>` blocks this module writes stay markup.
+    let events = Parser::new_ext(md, options).map(|event| match event {
+        Event::Html(raw) if is_export_markup(&raw) => Event::Html(raw),
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        other => other,
+    });
+    let mut body = String::new();
+    html::push_html(&mut body, events);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ max-width: 52rem; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; line-height: 1.5; }}
+pre {{ background: #f6f8fa; padding: 0.75rem; overflow-x: auto; }}
+details {{ margin: 0.5rem 0; }}
+summary {{ cursor: pointer; color: #555; }}
+h2 {{ border-bottom: 1px solid #ddd; font-size: 1.1rem; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        escape_html(title),
+        body
+    )
+}
+
+/// Whether a line of raw HTML is one of the `<details>` / `<summary>` lines written by
+/// [`render_markdown`]: no attributes, and at most a `<code>` in the summary.
+fn is_export_markup(raw: &str) -> bool {
+    static MARKUP: OnceLock<Regex> = OnceLock::new();
+    let markup = MARKUP.get_or_init(|| {
+        Regex::new(r"^(</details>|<details><summary>[^<>]*(<code>[^<>]*</code>)?[^<>]*</summary>)$")
+            .expect("valid regex")
+    });
+    markup.is_match(raw.trim())
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::SandboxDecision;
+
+    fn msg(role: Role, content: &str, group: Option<u64>) -> Message {
+        Message {
+            id: 0,
+            role,
+            content: content.into(),
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: group,
+            local: false,
+        }
+    }
+
+    #[test]
+    fn export_places_tool_traces_under_their_message() {
+        let messages = vec![
+            msg(Role::System, "system prompt", None),
+            msg(Role::User, "list files", None),
+            msg(Role::Agent, "", Some(1)),
+            msg(Role::Agent, "Done.", None),
+        ];
+        let mut tool = ToolOutput::new(
+            "c1".into(),
+            "bash".into(),
+            r#"{"command":"ls"}"#.into(),
+            "ls".into(),
+            ".".into(),
+            SandboxDecision::allow(),
+            1,
+            1,
+        );
+        tool.set_output("Cargo.toml\nsrc".into());
+        tool.complete(true);
+
+        let stats = SessionStats::collect(&messages, &[tool.clone()], Default::default());
+        let md = render(
+            ExportFormat::Markdown,
+            "Session",
+            &messages,
+            &[tool.clone()],
+            &stats,
+        );
+        assert!(!md.contains("system prompt"));
+        let user = md.find("list files").unwrap();
+        let trace = md.find("<code>ls</code>").unwrap();
+        let done = md.find("Done.").unwrap();
+        assert!(user < trace && trace < done);
+        assert!(md.contains("## Stats\n\n| Session | |"));
+        assert!(md.contains("| Tool calls | 1 (1 ok, 0 failed) |"));
+
+        let html = render(ExportFormat::Html, "Session", &messages, &[tool], &stats);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<details>"));
+    }
+
+    #[test]
+    fn html_export_shows_raw_html_from_the_transcript_as_text() {
+        let mut answer = msg(
+            Role::Agent,
+            "Try <img src=x onerror=alert(1)> here.\n\n<script>alert(2)</script>",
+            Some(1),
+        );
+        answer.reasoning = Some("<iframe src=\"https://example.com\"></iframe>".into());
+        let messages = vec![
+            msg(Role::User, "<div onclick=\"x()\">hi</div>", None),
+            answer,
+        ];
+        let mut tool = ToolOutput::new(
+            "c1".into(),
+            "read_file".into(),
+            r#"{"path":"<b>.html"}"#.into(),
+            "<b>.html".into(),
+            ".".into(),
+            SandboxDecision::allow(),
+            1,
+            1,
+        );
+        tool.set_output("<script>alert(3)</script>".into());
+        tool.complete(true);
+        let stats = SessionStats::collect(&messages, &[tool.clone()], Default::default());
+
+        let html = render(ExportFormat::Html, "Session", &messages, &[tool], &stats);
+        for raw in ["<script", "<img", "<iframe", "<div", "<b>"] {
+            assert!(!html.contains(raw), "{} in {}", raw, html);
+        }
+        assert!(html.contains("&lt;script&gt;alert(2)&lt;/script&gt;"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+        assert!(html.contains("<details><summary>Reasoning</summary>"));
+        assert!(
+            html.contains("<details><summary>read_file <code>&lt;b&gt;.html</code> — ok</summary>")
+        );
+    }
+
+    #[test]
+    fn json_export_keeps_tool_calls_and_redacts_secrets() {
+        let mut call = msg(Role::Agent, "Checking.", Some(1));
+        call.tool_calls = Some(vec![crate::types::ToolCallMessage {
+            id: "c1".into(),
+            call_type: "function".into(),
+            function: crate::types::ToolCallFunction {
+                name: "bash".into(),
+                arguments: r#"{"command":"curl -H 'token: abcdefgh12345'"}"#.into(),
+            },
+            thought_signature: None,
+        }]);
+        let mut result = msg(Role::Tool, "key sk-abcdefghijklmnopqrstu", None);
+        result.reasoning = Some("c1".into());
+        let mut notice = msg(Role::Agent, "Undid edit_file", None);
+        notice.local = true;
+        let messages = vec![
+            msg(Role::System, "system prompt", None),
+            msg(Role::System, "\n[Memory]\nprefers tabs", None),
+            msg(Role::User, "call the api", None),
+            call,
+            result,
+            notice,
+            msg(Role::Agent, "Done.", None),
+        ];
+        let mut tool = ToolOutput::new(
+            "c1".into(),
+            "bash".into(),
+            "{}".into(),
+            "curl".into(),
+            ".".into(),
+            SandboxDecision::allow(),
+            1,
+            1,
+        );
+        tool.complete(false);
+
+        let stats = SessionStats::collect(&messages, &[tool.clone()], Default::default());
+        let doc = render(ExportFormat::Json, "Session", &messages, &[tool], &stats);
+        let v: Value = serde_json::from_str(&doc).unwrap();
+        let msgs = v["messages"].as_array().unwrap();
+        let roles: Vec<&str> = msgs.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool", "assistant"]);
+
+        let call = &msgs[2]["tool_calls"][0];
+        assert_eq!(call["name"], "bash");
+        assert_eq!(call["arguments"]["command"], "curl -H '[REDACTED]");
+        assert_eq!(msgs[3]["name"], "bash");
+        assert_eq!(msgs[3]["ok"], false);
+        assert_eq!(msgs[3]["content"], "key [REDACTED]");
+        assert_eq!(v["stats"]["failed_tool_calls"], 1);
+        assert!(!doc.contains("Undid"));
+    }
+}
@@ -0,0 +1,457 @@
+//! The `github` tool: read-only GitHub lookups (issues, PR files, review comments, CI
+//! status) so "fix issue #123" doesn't need the issue pasted in.
+//!
+//! The repository comes from the `repo` argument or the workspace's `origin` remote. The
+//! token comes from `[github] token` in the user config, else `GITHUB_TOKEN`/`GH_TOKEN`;
+//! without one, public repositories still work at GitHub's lower anonymous rate limit.
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::AppConfig;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Lines of each file's patch shown by `pr_files`; the rest is summarized.
+const MAX_PATCH_LINES: usize = 200;
+
+/// The token to send: `github.token` from the user config, else the environment. The
+/// project config isn't consulted, so a checked-out repository can't point your token at
+/// another server.
+pub fn token(user_config: &AppConfig) -> Option<String> {
+    user_config
+        .github
+        .as_ref()
+        .and_then(|g| g.token.clone())
+        .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+        .or_else(|| std::env::var("GH_TOKEN").ok())
+        .filter(|t| !t.trim().is_empty())
+}
+
+/// API base URL (`github.api_url` in the user config for GitHub Enterprise).
+pub fn api_url(user_config: &AppConfig) -> String {
+    user_config
+        .github
+        .as_ref()
+        .and_then(|g| g.api_url.clone())
+        .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// `owner/name` from a remote URL: `git@github.com:o/r.git`, `https://github.com/o/r`,
+/// `ssh://git@github.com/o/r.git`.
+pub fn parse_remote(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+    let path = if let Some((_, rest)) = url.split_once("://") {
+        rest.split_once('/')?.1
+    } else {
+        url.split_once(':')?.1
+    };
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let mut parts = path.rsplit('/');
+    let name = parts.next().filter(|s| !s.is_empty())?;
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    Some(format!("{}/{}", owner, name))
+}
+
+/// Whether `repo` is a plain `owner/name`. It's pasted into API paths, so anything else
+/// (`a/b/../../user`) could point the token at another endpoint.
+fn valid_repo(repo: &str) -> bool {
+    static REPO: OnceLock<Regex> = OnceLock::new();
+    REPO.get_or_init(|| Regex::new(r"^[\w.-]+/[\w.-]+$").expect("valid regex"))
+        .is_match(repo)
+        && repo.split('/').all(|part| part != "." && part != "..")
+}
+
+/// Whether `reference` is a commit sha or a well-formed ref name, safe to put in a path.
+fn valid_ref(reference: &str) -> bool {
+    static REF: OnceLock<Regex> = OnceLock::new();
+    REF.get_or_init(|| Regex::new(r"^[\w][\w./-]*$").expect("valid regex"))
+        .is_match(reference)
+        && !reference.contains("..")
+        && !reference.contains("//")
+        && !reference.contains("/.")
+        && !reference.ends_with('/')
+        && !reference.ends_with(".lock")
+}
+
+fn git(root: &Path, args: &[&str]) -> Option<String> {
+    let out = std::process::Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let s = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+/// `owner/name` of the workspace's `origin` remote.
+pub fn workspace_repo(root: &Path) -> Option<String> {
+    parse_remote(&git(root, &["remote", "get-url", "origin"])?)
+}
+
+struct Client {
+    http: reqwest::Client,
+    api: String,
+    repo: String,
+    token: Option<String>,
+}
+
+impl Client {
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        let url = format!("{}/repos/{}/{}", self.api, self.repo, path);
+        let mut req = self
+            .http
+            .get(&url)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "lorikeet");
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.map_err(|e| format!("Error: {}", e))?;
+        let status = resp.status();
+        let body: Value = resp.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let message = body
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("request failed");
+            let hint = match status.as_u16() {
+                401 | 403 if self.token.is_none() => {
+                    " (set GITHUB_TOKEN or github.token in the user config)"
+                }
+                404 if self.token.is_none() => " (private repositories need a token)",
+                _ => "",
+            };
+            return Err(format!(
+                "Error: GitHub {} for {}: {}{}",
+                status, url, message, hint
+            ));
+        }
+        Ok(body)
+    }
+}
+
+/// Run one `github` tool call from the workspace at `root`.
+pub async fn run(args: &Value, root: &Path) -> Result<String, String> {
+    let user_config = AppConfig::load();
+    let action = args.get("action").and_then(Value::as_str).unwrap_or("");
+    let number = args.get("number").and_then(|v| match v {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().trim_start_matches('#').parse().ok(),
+        _ => None,
+    });
+    let repo = args
+        .get("repo")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(|r| parse_remote(r).unwrap_or_else(|| r.to_string()))
+        .or_else(|| workspace_repo(root))
+        .ok_or("Error: no repo given and the workspace has no GitHub origin remote")?;
+    if !valid_repo(&repo) {
+        return Err(format!("Error: repo must be owner/name, got '{}'", repo));
+    }
+    let client = Client {
+        http: reqwest::Client::new(),
+        api: api_url(&user_config),
+        repo,
+        token: token(&user_config),
+    };
+    let need_number = || number.ok_or(format!("Error: {} needs a number", action));
+
+    match action {
+        "issue" => {
+            let n = need_number()?;
+            let issue = client.get(&format!("issues/{}", n)).await?;
+            let comments = client
+                .get(&format!("issues/{}/comments?per_page=100", n))
+                .await?;
+            Ok(format_issue(&issue, &comments))
+        }
+        "pr_files" => {
+            let n = need_number()?;
+            let files = client
+                .get(&format!("pulls/{}/files?per_page=100", n))
+                .await?;
+            Ok(format_pr_files(&files))
+        }
+        "review_comments" => {
+            let n = need_number()?;
+            let reviews = client
+                .get(&format!("pulls/{}/reviews?per_page=100", n))
+                .await?;
+            let comments = client
+                .get(&format!("pulls/{}/comments?per_page=100", n))
+                .await?;
+            Ok(format_review_comments(&reviews, &comments))
+        }
+        "ci_status" => {
+            // A PR's head commit, else the given ref, else the checked-out commit.
+            let reference = match number {
+                Some(n) => {
+                    let pr = client.get(&format!("pulls/{}", n)).await?;
+                    str_at(&pr, &["head", "sha"]).to_string()
+                }
+                None => args
+                    .get("ref")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .filter(|r| !r.trim().is_empty())
+                    .or_else(|| git(root, &["rev-parse", "HEAD"]))
+                    .ok_or("Error: ci_status needs a number or ref")?,
+            };
+            if !valid_ref(&reference) {
+                return Err(format!(
+                    "Error: '{}' is not a commit sha or ref name",
+                    reference
+                ));
+            }
+            let runs = client
+                .get(&format!("commits/{}/check-runs?per_page=100", reference))
+                .await?;
+            let status = client.get(&format!("commits/{}/status", reference)).await?;
+            Ok(format_ci_status(&reference, &runs, &status))
+        }
+        other => Err(format!(
+            "Error: unknown action '{}' (use issue, pr_files, review_comments or ci_status)",
+            other
+        )),
+    }
+}
+
+fn str_at<'a>(v: &'a Value, path: &[&str]) -> &'a str {
+    path.iter()
+        .try_fold(v, |v, key| v.get(key))
+        .and_then(Value::as_str)
+        .unwrap_or("")
+}
+
+fn items(v: &Value) -> &[Value] {
+    v.as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+pub fn format_issue(issue: &Value, comments: &Value) -> String {
+    let kind = if issue.get("pull_request").is_some() {
+        "PR"
+    } else {
+        "Issue"
+    };
+    let labels: Vec<&str> = items(&issue["labels"])
+        .iter()
+        .filter_map(|l| l.get("name").and_then(Value::as_str))
+        .collect();
+    let mut out = format!(
+        "{} #{}: {}\nState: {} | Author: {}",
+        kind,
+        issue["number"],
+        str_at(issue, &["title"]),
+        str_at(issue, &["state"]),
+        str_at(issue, &["user", "login"]),
+    );
+    if !labels.is_empty() {
+        out.push_str(&format!(" | Labels: {}", labels.join(", ")));
+    }
+    out.push_str(&format!("\nURL: {}\n\n", str_at(issue, &["html_url"])));
+    let body = str_at(issue, &["body"]).trim();
+    out.push_str(if body.is_empty() {
+        "(no description)"
+    } else {
+        body
+    });
+    out.push('\n');
+    let comments = items(comments);
+    if !comments.is_empty() {
+        out.push_str(&format!("\n## Comments ({})\n", comments.len()));
+        for c in comments {
+            out.push_str(&format!(
+                "\n{} ({}):\n{}\n",
+                str_at(c, &["user", "login"]),
+                str_at(c, &["created_at"]),
+                str_at(c, &["body"]).trim()
+            ));
+        }
+    }
+    out
+}
+
+pub fn format_pr_files(files: &Value) -> String {
+    let files = items(files);
+    if files.is_empty() {
+        return "No files changed.".to_string();
+    }
+    let mut out = format!("{} files changed\n", files.len());
+    for f in files {
+        out.push_str(&format!(
+            "\n{} ({}, +{} -{})\n",
+            str_at(f, &["filename"]),
+            str_at(f, &["status"]),
+            f["additions"],
+            f["deletions"]
+        ));
+        let patch = str_at(f, &["patch"]);
+        let lines: Vec<&str> = patch.lines().collect();
+        for line in lines.iter().take(MAX_PATCH_LINES) {
+            out.push_str(line);
+            out.push('\n');
+        }
+        if lines.len() > MAX_PATCH_LINES {
+            out.push_str(&format!(
+                "... {} more patch lines\n",
+                lines.len() - MAX_PATCH_LINES
+            ));
+        }
+    }
+    out
+}
+
+pub fn format_review_comments(reviews: &Value, comments: &Value) -> String {
+    let mut out = String::new();
+    for r in items(reviews) {
+        let body = str_at(r, &["body"]).trim();
+        if body.is_empty() {
+            continue;
+        }
+        out.push_str(&format!(
+            "Review by {} ({}):\n{}\n\n",
+            str_at(r, &["user", "login"]),
+            str_at(r, &["state"]),
+            body
+        ));
+    }
+    for c in items(comments) {
+        let line = c
+            .get("line")
+            .or_else(|| c.get("original_line"))
+            .and_then(Value::as_u64)
+            .map(|l| format!(":{}", l))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{}{} — {}:\n{}\n\n",
+            str_at(c, &["path"]),
+            line,
+            str_at(c, &["user", "login"]),
+            str_at(c, &["body"]).trim()
+        ));
+    }
+    if out.is_empty() {
+        return "No review comments.".to_string();
+    }
+    out.trim_end().to_string()
+}
+
+pub fn format_ci_status(reference: &str, runs: &Value, status: &Value) -> String {
+    let mut out = format!("CI for {}\n", reference);
+    let runs = items(&runs["check_runs"]);
+    let statuses = items(&status["statuses"]);
+    for run in runs {
+        let state = match str_at(run, &["conclusion"]) {
+            "" => str_at(run, &["status"]),
+            c => c,
+        };
+        out.push_str(&format!("{}: {}", str_at(run, &["name"]), state));
+        let summary = str_at(run, &["output", "title"]);
+        if !summary.is_empty() {
+            out.push_str(&format!(" — {}", summary));
+        }
+        out.push('\n');
+    }
+    for s in statuses {
+        out.push_str(&format!(
+            "{}: {} — {}\n",
+            str_at(s, &["context"]),
+            str_at(s, &["state"]),
+            str_at(s, &["description"])
+        ));
+    }
+    if runs.is_empty() && statuses.is_empty() {
+        out.push_str("No checks reported.\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn remotes_parse_to_owner_and_name() {
+        for url in [
+            "git@github.com:jayasuryajsk/lorikeet.git",
+            "https://github.com/jayasuryajsk/lorikeet",
+            "https://github.com/jayasuryajsk/lorikeet.git/",
+            "ssh://git@github.com/jayasuryajsk/lorikeet.git",
+        ] {
+            assert_eq!(parse_remote(url).as_deref(), Some("jayasuryajsk/lorikeet"));
+        }
+        assert_eq!(parse_remote("lorikeet"), None);
+    }
+
+    #[test]
+    fn repos_and_refs_that_could_leave_the_repo_path_are_refused() {
+        assert!(valid_repo("jayasuryajsk/lorikeet"));
+        assert!(valid_repo("o/r.js"));
+        for repo in ["a/b/../../user", "../x", "o/..", "o", "o/r?x=1", "o/r#x"] {
+            assert!(!valid_repo(repo), "{}", repo);
+        }
+
+        for reference in ["main", "v1.2.0", "feature/x-y", "0123abcd"] {
+            assert!(valid_ref(reference), "{}", reference);
+        }
+        for reference in [
+            "../../../user/emails",
+            "main/../x",
+            "/main",
+            "-x",
+            "a?b",
+            "a b",
+            "x.lock",
+            "a//b",
+        ] {
+            assert!(!valid_ref(reference), "{}", reference);
+        }
+    }
+
+    #[test]
+    fn responses_format_for_the_model() {
+        let issue = json!({
+            "number": 123,
+            "title": "Crash on empty input",
+            "state": "open",
+            "user": {"login": "ana"},
+            "labels": [{"name": "bug"}],
+            "html_url": "https://github.com/o/r/issues/123",
+            "body": "Steps to reproduce...",
+        });
+        let comments = json!([{"user": {"login": "bo"}, "created_at": "t", "body": "Same here"}]);
+        let text = format_issue(&issue, &comments);
+        assert!(text.starts_with("Issue #123: Crash on empty input"));
+        assert!(text.contains("Labels: bug"));
+        assert!(text.contains("Steps to reproduce..."));
+        assert!(text.contains("bo (t):\nSame here"));
+
+        let runs = json!({"check_runs": [
+            {"name": "test", "status": "completed", "conclusion": "failure"},
+            {"name": "lint", "status": "in_progress", "conclusion": null},
+        ]});
+        let text = format_ci_status("abc", &runs, &json!({"statuses": []}));
+        assert!(text.contains("test: failure\n"));
+        assert!(text.contains("lint: in_progress\n"));
+
+        let comments =
+            json!([{"path": "src/a.rs", "line": 7, "user": {"login": "r"}, "body": "nit"}]);
+        assert_eq!(
+            format_review_comments(&json!([]), &comments),
+            "src/a.rs:7 — r:\nnit"
+        );
+    }
+}
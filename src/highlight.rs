@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use ratatui::style::Color;
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+use crate::theme::SyntaxTheme;
+
+/// Capture names we colour. tree-sitter-highlight matches captures by dotted prefix, so
+/// `punctuation.bracket` resolves to `punctuation`, `function.macro` to `function`, etc.
+const CAPTURE_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constructor",
+    "escape",
+    "function",
+    "keyword",
+    "label",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "string",
+    "tag",
+    "type",
+    "variable",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grammar {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+    C,
+    Cpp,
+    Java,
+    Ruby,
+}
+
+impl Grammar {
+    /// Accepts both fence info strings (`rust`, `python`) and file extensions (`rs`, `py`).
+    fn from_lang(lang: &str) -> Option<Self> {
+        let lang = lang
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        match lang.as_str() {
+            "rust" | "rs" => Some(Self::Rust),
+            "python" | "py" | "python3" => Some(Self::Python),
+            "javascript" | "js" | "jsx" | "mjs" | "cjs" | "node" => Some(Self::JavaScript),
+            "typescript" | "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            "go" | "golang" => Some(Self::Go),
+            "c" | "h" => Some(Self::C),
+            "cpp" | "c++" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Self::Cpp),
+            "java" => Some(Self::Java),
+            "ruby" | "rb" => Some(Self::Ruby),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn build(self) -> Option<HighlightConfiguration> {
+        // TypeScript and C++ queries extend their parent grammar's queries.
+        let ts_highlights = || {
+            format!(
+                "{}\n{}",
+                tree_sitter_javascript::HIGHLIGHT_QUERY,
+                tree_sitter_typescript::HIGHLIGHTS_QUERY
+            )
+        };
+        let (language, name, highlights): (tree_sitter::Language, &str, String) = match self {
+            Self::Rust => (
+                tree_sitter_rust::LANGUAGE.into(),
+                "rust",
+                tree_sitter_rust::HIGHLIGHTS_QUERY.to_string(),
+            ),
+            Self::Python => (
+                tree_sitter_python::LANGUAGE.into(),
+                "python",
+                tree_sitter_python::HIGHLIGHTS_QUERY.to_string(),
+            ),
+            Self::JavaScript => (
+                tree_sitter_javascript::LANGUAGE.into(),
+                "javascript",
+                format!(
+                    "{}\n{}",
+                    tree_sitter_javascript::HIGHLIGHT_QUERY,
+                    tree_sitter_javascript::JSX_HIGHLIGHT_QUERY
+                ),
+            ),
+            Self::TypeScript => (
+                tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+                "typescript",
+                ts_highlights(),
+            ),
+            Self::Tsx => (
+                tree_sitter_typescript::LANGUAGE_TSX.into(),
+                "tsx",
+                format!(
+                    "{}\n{}",
+                    ts_highlights(),
+                    tree_sitter_javascript::JSX_HIGHLIGHT_QUERY
+                ),
+            ),
+            Self::Go => (
+                tree_sitter_go::LANGUAGE.into(),
+                "go",
+                tree_sitter_go::HIGHLIGHTS_QUERY.to_string(),
+            ),
+            Self::C => (
+                tree_sitter_c::LANGUAGE.into(),
+                "c",
+                tree_sitter_c::HIGHLIGHT_QUERY.to_string(),
+            ),
+            Self::Cpp => (
+                tree_sitter_cpp::LANGUAGE.into(),
+                "cpp",
+                format!(
+                    "{}\n{}",
+                    tree_sitter_cpp::HIGHLIGHT_QUERY,
+                    tree_sitter_c::HIGHLIGHT_QUERY
+                ),
+            ),
+            Self::Java => (
+                tree_sitter_java::LANGUAGE.into(),
+                "java",
+                tree_sitter_java::HIGHLIGHTS_QUERY.to_string(),
+            ),
+            Self::Ruby => (
+                tree_sitter_ruby::LANGUAGE.into(),
+                "ruby",
+                tree_sitter_ruby::HIGHLIGHTS_QUERY.to_string(),
+            ),
+        };
+
+        let mut config = HighlightConfiguration::new(language, name, &highlights, "", "").ok()?;
+        config.configure(CAPTURE_NAMES);
+        Some(config)
+    }
+}
+
+const GRAMMAR_COUNT: usize = 10;
+
+/// Compiled query configs, built on first use per language (query compilation is not free).
+static CONFIGS: [OnceLock<Option<HighlightConfiguration>>; GRAMMAR_COUNT] =
+    [const { OnceLock::new() }; GRAMMAR_COUNT];
+
+thread_local! {
+    static HIGHLIGHTER: RefCell<Highlighter> = RefCell::new(Highlighter::new());
+}
+
+fn config_for(grammar: Grammar) -> Option<&'static HighlightConfiguration> {
+    CONFIGS[grammar.index()]
+        .get_or_init(|| grammar.build())
+        .as_ref()
+}
+
+/// Highlight a whole snippet with tree-sitter, returning coloured segments per line.
+///
+/// Returns `None` for unsupported languages or if highlighting fails, so callers can fall
+/// back to their line heuristics. Partial input (e.g. a code block still streaming in) is
+/// fine: the parser recovers from syntax errors.
+pub fn highlight_lines(
+    code: &str,
+    lang: &str,
+    syn: SyntaxTheme,
+) -> Option<Vec<Vec<(String, Color)>>> {
+    let config = config_for(Grammar::from_lang(lang)?)?;
+
+    HIGHLIGHTER.with(|cell| {
+        let mut highlighter = cell.borrow_mut();
+        let events = highlighter
+            .highlight(config, code.as_bytes(), None, |_| None)
+            .ok()?;
+
+        let mut lines: Vec<Vec<(String, Color)>> = vec![Vec::new()];
+        let mut stack: Vec<usize> = Vec::new();
+        for event in events {
+            match event.ok()? {
+                HighlightEvent::HighlightStart(h) => stack.push(h.0),
+                HighlightEvent::HighlightEnd => {
+                    stack.pop();
+                }
+                HighlightEvent::Source { start, end } => {
+                    let color = stack
+                        .last()
+                        .map(|&i| capture_color(CAPTURE_NAMES[i], syn))
+                        .unwrap_or(syn.ident);
+                    let text = code.get(start..end)?;
+                    for (i, part) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            lines.push(Vec::new());
+                        }
+                        if part.is_empty() {
+                            continue;
+                        }
+                        let line = lines.last_mut().expect("at least one line");
+                        match line.last_mut() {
+                            Some((prev, c)) if *c == color => prev.push_str(part),
+                            _ => line.push((part.to_string(), color)),
+                        }
+                    }
+                }
+            }
+        }
+
+        // `str::lines` semantics: a trailing newline does not start another line.
+        if code.ends_with('\n') && lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        Some(lines)
+    })
+}
+
+fn capture_color(name: &str, syn: SyntaxTheme) -> Color {
+    match name {
+        "comment" => syn.comment,
+        "keyword" | "attribute" | "label" => syn.keyword,
+        "string" | "escape" => syn.string,
+        "number" | "constant" => syn.number,
+        "type" | "constructor" | "tag" => syn.ty,
+        "punctuation" | "operator" => syn.punct,
+        _ => syn.ident,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syn() -> SyntaxTheme {
+        SyntaxTheme {
+            keyword: Color::Magenta,
+            ty: Color::Yellow,
+            string: Color::Green,
+            number: Color::Cyan,
+            comment: Color::DarkGray,
+            punct: Color::Gray,
+            ident: Color::White,
+        }
+    }
+
+    fn color_of(lines: &[Vec<(String, Color)>], needle: &str) -> Option<Color> {
+        lines
+            .iter()
+            .flatten()
+            .find(|(text, _)| text.contains(needle))
+            .map(|(_, c)| *c)
+    }
+
+    #[test]
+    fn rust_block_gets_language_aware_colors() {
+        let code = "// entry\nfn main() {\n    let s = \"hi\";\n}\n";
+        let lines = highlight_lines(code, "rust", syn()).expect("rust is supported");
+
+        assert_eq!(lines.len(), code.lines().count());
+        assert_eq!(color_of(&lines, "// entry"), Some(Color::DarkGray));
+        assert_eq!(color_of(&lines, "fn"), Some(Color::Magenta));
+        assert_eq!(color_of(&lines, "\"hi\""), Some(Color::Green));
+
+        // Every byte of the input survives, line by line.
+        for (line, src) in lines.iter().zip(code.lines()) {
+            let joined: String = line.iter().map(|(t, _)| t.as_str()).collect();
+            assert_eq!(joined, src);
+        }
+    }
+
+    #[test]
+    fn extensions_and_fence_names_resolve_to_the_same_grammar() {
+        assert_eq!(Grammar::from_lang("py"), Grammar::from_lang("python"));
+        assert_eq!(Grammar::from_lang("rust,ignore"), Some(Grammar::Rust));
+        assert!(highlight_lines("x", "brainfuck", syn()).is_none());
+    }
+}
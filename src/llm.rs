@@ -1,20 +1,78 @@
+use std::time::{Duration, Instant};
+
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
 use crate::events::AppEvent;
+use crate::llm_debug;
 use crate::types::{ToolCallFunction, ToolCallMessage};
 
 pub const MODEL: &str = "z-ai/glm-4.7-flash";
 const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
 const OPENAI_URL: &str = "https://api.openai.com/v1/chat/completions";
+const OPENAI_RESPONSES_URL: &str = "https://api.openai.com/v1/responses";
+const OPENROUTER_MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+const OPENAI_MODELS_URL: &str = "https://api.openai.com/v1/models";
 const CODEX_BASE_URL: &str = "https://chatgpt.com/backend-api/codex";
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LlmProvider {
     OpenRouter,
     OpenAI,
     Codex,
+    Gemini,
+}
+
+impl LlmProvider {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "openrouter" => Some(Self::OpenRouter),
+            "openai" => Some(Self::OpenAI),
+            "codex" | "codex_oauth" => Some(Self::Codex),
+            "gemini" | "google" => Some(Self::Gemini),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenRouter => "openrouter",
+            Self::OpenAI => "openai",
+            Self::Codex => "codex",
+            Self::Gemini => "gemini",
+        }
+    }
+
+    /// The environment variable holding this provider's API key (Codex signs in instead).
+    pub fn key_env(self) -> Option<&'static str> {
+        match self {
+            Self::OpenRouter => Some("OPENROUTER_API_KEY"),
+            Self::OpenAI => Some("OPENAI_API_KEY"),
+            Self::Gemini => Some("GEMINI_API_KEY"),
+            Self::Codex => None,
+        }
+    }
+}
+
+/// The Google AI Studio key: `GEMINI_API_KEY`, else `GOOGLE_API_KEY`.
+pub fn gemini_api_key() -> Option<String> {
+    ["GEMINI_API_KEY", "GOOGLE_API_KEY"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|k| k.trim().to_string())
+        .find(|k| !k.is_empty())
+}
+
+/// Gemini model ids without the `google/` (OpenRouter) or `models/` (API) prefix.
+fn normalize_gemini_model(model: &str) -> String {
+    let trimmed = model.trim();
+    trimmed
+        .strip_prefix("google/")
+        .or_else(|| trimmed.strip_prefix("models/"))
+        .unwrap_or(trimmed)
+        .to_string()
 }
 
 fn normalize_codex_model(model: &str) -> String {
@@ -32,7 +90,8 @@ fn normalize_codex_model(model: &str) -> String {
         .unwrap_or_else(|| model.to_string())
 }
 
-fn fallback_codex_model() -> Option<String> {
+/// Model slugs the Codex CLI last saw (`~/.codex/models_cache.json`).
+fn codex_cached_slugs() -> Option<Vec<String>> {
     #[derive(serde::Deserialize)]
     struct Cache {
         models: Vec<Model>,
@@ -45,12 +104,18 @@ fn fallback_codex_model() -> Option<String> {
     let path = dirs::home_dir()?.join(".codex").join("models_cache.json");
     let raw = std::fs::read_to_string(path).ok()?;
     let cache: Cache = serde_json::from_str(&raw).ok()?;
-    let mut slugs: Vec<String> = cache
-        .models
-        .into_iter()
-        .filter_map(|m| m.slug)
-        .filter(|s| !s.trim().is_empty())
-        .collect();
+    Some(
+        cache
+            .models
+            .into_iter()
+            .filter_map(|m| m.slug)
+            .filter(|s| !s.trim().is_empty())
+            .collect(),
+    )
+}
+
+fn fallback_codex_model() -> Option<String> {
+    let mut slugs = codex_cached_slugs()?;
 
     // Prefer a codex-capable slug if present.
     if let Some(p) = slugs.iter().find(|s| s.contains("gpt-5.2-codex")) {
@@ -66,10 +131,78 @@ fn fallback_codex_model() -> Option<String> {
 #[derive(Debug, Serialize)]
 struct ChatRequest {
     model: String,
-    messages: Vec<ChatMessage>,
+    messages: Vec<serde_json::Value>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    /// Ask for a final chunk carrying token usage (OpenRouter adds the cost).
+    include_usage: bool,
+}
+
+/// Tokens used by LLM calls, summed over a session for `/stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache (part of `prompt_tokens`)
+    #[serde(default)]
+    pub cached_tokens: u64,
+    /// USD, when the provider reports it (OpenRouter) or it could be estimated
+    #[serde(default)]
+    pub cost: Option<f64>,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.cost = match (self.cost, other.cost) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+        };
+    }
+
+    /// Read a `usage` object in either Chat Completions (`prompt_tokens`,
+    /// `prompt_tokens_details`) or Responses API (`input_tokens`, ...) naming.
+    fn from_json(usage: &serde_json::Value) -> Self {
+        let tokens = |pointers: &[&str]| {
+            pointers
+                .iter()
+                .find_map(|p| usage.pointer(p).and_then(|x| x.as_u64()))
+                .unwrap_or(0)
+        };
+        Self {
+            prompt_tokens: tokens(&["/prompt_tokens", "/input_tokens"]),
+            completion_tokens: tokens(&["/completion_tokens", "/output_tokens"]),
+            cached_tokens: tokens(&[
+                "/prompt_tokens_details/cached_tokens",
+                "/input_tokens_details/cached_tokens",
+            ]),
+            cost: usage.get("cost").and_then(|c| c.as_f64()),
+        }
+    }
+
+    /// Read Gemini's `usageMetadata`. Thinking tokens are billed as output.
+    fn from_gemini(meta: &serde_json::Value) -> Self {
+        let tokens = |key: &str| meta.get(key).and_then(|x| x.as_u64()).unwrap_or(0);
+        Self {
+            prompt_tokens: tokens("promptTokenCount"),
+            completion_tokens: tokens("candidatesTokenCount") + tokens("thoughtsTokenCount"),
+            cached_tokens: tokens("cachedContentTokenCount"),
+            cost: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +216,10 @@ pub struct ChatMessage {
     pub tool_call_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Ask the provider to cache the prompt up to and including this message. Only sent
+    /// where caching needs explicit breakpoints (see [`explicit_cache_breakpoints`]).
+    #[serde(skip)]
+    pub cache: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,7 +238,9 @@ struct FunctionDef {
 
 #[derive(Debug, Deserialize)]
 struct StreamChunk {
+    #[serde(default)]
     choices: Vec<StreamChoice>,
+    usage: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -139,405 +278,685 @@ struct PendingToolCall {
     arguments: String,
 }
 
-fn get_tools() -> Vec<Tool> {
-    vec![
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "bash".into(),
-                description: "Run a shell command. Use for: reading files (cat), listing dirs (ls, tree), git, builds, tests, installs, etc.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "command": {
-                            "type": "string",
-                            "description": "The bash command to execute"
-                        }
-                    },
-                    "required": ["command"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "rg".into(),
-                description: "Fast text search using ripgrep. Use for exact symbol or string search across the codebase.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "The text or regex to search for"
-                        },
-                        "path": {
-                            "type": "string",
-                            "description": "Directory or file to search (defaults to current directory)"
-                        },
-                        "context": {
-                            "type": "integer",
-                            "description": "Optional number of context lines to include before/after matches"
-                        }
-                    },
-                    "required": ["query"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "smart_search".into(),
-                description: "Combined search: runs ripgrep (exact) + semantic search (meaning) and returns a merged, ranked list. Prefer this when you don't know exact symbol names.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "What to search for (natural language or exact text)"
-                        },
-                        "path": {
-                            "type": "string",
-                            "description": "Optional scope within the workspace (directory or file). Defaults to current directory."
-                        },
-                        "limit": {
-                            "type": "integer",
-                            "description": "Max number of results to return (default 20, max 50)"
-                        },
-                        "rg": {
-                            "type": "boolean",
-                            "description": "Whether to run rg (default true)"
-                        },
-                        "semantic": {
-                            "type": "boolean",
-                            "description": "Whether to run semantic search (default true)"
-                        }
-                    },
-                    "required": ["query"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "lsp".into(),
-                description: "Language Server Protocol bridge for code-aware operations. Actions: definition, references, rename, diagnostics. Provide path + 1-based line/column for symbol operations.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "language": {"type": "string", "description": "auto|rust|typescript (default auto)"},
-                        "action": {"type": "string", "description": "definition|references|rename|diagnostics"},
-                        "path": {"type": "string", "description": "File path (workspace-relative or absolute)"},
-                        "line": {"type": "integer", "description": "1-based line number (required for definition/references/rename)"},
-                        "column": {"type": "integer", "description": "1-based column number (required for definition/references/rename)"},
-                        "new_name": {"type": "string", "description": "New symbol name (required for rename)"},
-                        "include_declaration": {"type": "boolean", "description": "For references: include the declaration (default false)"},
-                        "limit": {"type": "integer", "description": "Max results (default 20)"}
-                    },
-                    "required": ["action","path"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "read_file".into(),
-                description: "Read the contents of a file at the given path.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "The file path to read"
-                        }
-                    },
-                    "required": ["path"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "write_file".into(),
-                description: "Write content to a file at the given path. Creates the file if it doesn't exist, overwrites if it does.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "The file path to write to"
-                        },
-                        "content": {
-                            "type": "string",
-                            "description": "The content to write to the file"
-                        }
-                    },
-                    "required": ["path", "content"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "list_files".into(),
-                description: "List files and directories at the given path.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "The directory path to list (defaults to current directory)"
-                        }
-                    },
-                    "required": []
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "edit_file".into(),
-                description: "Make a surgical edit to a file by replacing old_string with new_string. The old_string must match exactly and be unique in the file.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {
-                            "type": "string",
-                            "description": "The file path to edit"
-                        },
-                        "old_string": {
-                            "type": "string",
-                            "description": "The exact text to find and replace (must be unique in file)"
-                        },
-                        "new_string": {
-                            "type": "string",
-                            "description": "The text to replace it with"
-                        }
-                    },
-                    "required": ["path", "old_string", "new_string"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "apply_patch".into(),
-                description: "Apply a patch to one or more files. Prefer this for non-trivial edits/refactors. Patch format uses *** Begin Patch / *** Update File / *** Add File / *** Delete File / *** End Patch blocks with diff-style lines starting with ' ', '+', '-'.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "patch": {
-                            "type": "string",
-                            "description": "Patch text to apply"
-                        }
-                    },
-                    "required": ["patch"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "open_at".into(),
-                description: "Read a file around a specific 1-based line number with context and line numbers. Use after smart_search/rg when you have path:line results.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "path": {"type": "string", "description": "File path"},
-                        "line": {"type": "integer", "description": "1-based line number to center on"},
-                        "context": {"type": "integer", "description": "Lines of context before/after (default 40)"}
-                    },
-                    "required": ["path", "line"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "semantic_search".into(),
-                description: "Search code semantically using natural language. Returns ranked results with file paths and line numbers. Use for finding code related to concepts, features, or functionality. Auto-indexes on first use.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "Natural language query describing what you're looking for (e.g., 'authentication handling', 'database connection', 'error logging')"
-                        }
-                    },
-                    "required": ["query"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "verify".into(),
-                description: "Run the workspace verify command (tests/build). If command is omitted, auto-detects a suggestion. Respects sandbox allow_commands; if blocked, adjust sandbox allowlist.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "command": {"type": "string", "description": "Optional verify command (e.g. 'cargo test', 'pnpm test')"}
-                    },
-                    "required": []
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "memory_recall".into(),
-                description: "Recall relevant long-term memory for the current project. Use before making decisions or repeating actions. Returns ranked memories.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "query": {"type": "string", "description": "Search query"},
-                        "limit": {"type": "integer", "description": "Max memories to return (default 8)"},
-                        "types": {"type": "array", "items": {"type": "string"}, "description": "Optional list of memory types"}
-                    },
-                    "required": ["query"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "memory_save".into(),
-                description: "Save a long-term memory. Use for user preferences, decisions, and mistakes. Never store secrets.".into(),
-                parameters: serde_json::json!({
+/// Which tools are advertised to the model for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolSet {
+    /// No tools.
+    None,
+    /// No tools; the reply is a plan object, held to [`plan_schema`] by providers with
+    /// structured outputs.
+    Plan,
+    /// Only tools that can't modify the workspace (informational prompts).
+    ReadOnly,
+    /// Everything.
+    Full,
+}
+
+impl ToolSet {
+    pub fn enabled(self) -> bool {
+        !matches!(self, ToolSet::None | ToolSet::Plan)
+    }
+}
+
+/// JSON schema of a plan-mode reply: the plan as markdown plus clarifying questions. Strict
+/// mode wants every property required, so an absent default is `null`.
+pub fn plan_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["plan", "questions"],
+        "properties": {
+            "plan": { "type": "string", "description": "The plan, as markdown" },
+            "questions": {
+                "type": "array",
+                "items": {
                     "type": "object",
+                    "additionalProperties": false,
+                    "required": ["id", "prompt", "type", "options", "default"],
                     "properties": {
-                        "type": {"type": "string", "description": "mistake|preference|decision|fact|avoid"},
-                        "content": {"type": "string", "description": "The memory content"},
-                        "why": {"type": "string", "description": "Why this memory matters / how it should change future behavior"},
-                        "context": {"type": "string", "description": "Optional context"},
-                        "tags": {"type": "array", "items": {"type": "string"}, "description": "Optional tags"},
-                        "scope": {"type": "string", "description": "project|global (default project)"},
-                        "confidence": {"type": "number", "description": "0..1"},
-                        "importance": {"type": "number", "description": "0..1"}
+                        "id": { "type": "string" },
+                        "prompt": { "type": "string" },
+                        "type": { "type": "string", "enum": ["text", "select"] },
+                        "options": { "type": "array", "items": { "type": "string" } },
+                        "default": { "type": ["string", "null"] },
                     },
-                    "required": ["type", "content"]
-                }),
-            },
-        },
-        Tool {
-            tool_type: "function".into(),
-            function: FunctionDef {
-                name: "memory_list".into(),
-                description: "List stored memories for this project.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "limit": {"type": "integer", "description": "Max to return (default 30)"},
-                        "type": {"type": "string", "description": "Optional memory type filter"}
-                    }
-                }),
+                },
             },
         },
-        Tool {
+    })
+}
+
+/// Whether `target` accepts a JSON schema for its reply. OpenRouter only passes it on for
+/// some model families; other models get the schema in the prompt alone.
+fn supports_structured_output(target: &LlmTarget) -> bool {
+    match target.provider {
+        LlmProvider::OpenAI => !target.model.starts_with("gpt-3.5"),
+        LlmProvider::Codex | LlmProvider::Gemini => true,
+        LlmProvider::OpenRouter => ["openai/", "google/"]
+            .iter()
+            .any(|prefix| target.model.starts_with(prefix)),
+    }
+}
+
+/// Chat Completions `response_format` for `tool_set`, when the target supports it.
+fn chat_response_format(target: &LlmTarget, tool_set: ToolSet) -> Option<serde_json::Value> {
+    (tool_set == ToolSet::Plan && supports_structured_output(target)).then(|| {
+        serde_json::json!({
+            "type": "json_schema",
+            "json_schema": { "name": "plan", "strict": true, "schema": plan_schema() },
+        })
+    })
+}
+
+/// Whether `name` is one of the tools [`ToolSet::ReadOnly`] offers: those that only read.
+pub fn is_read_only_tool(name: &str) -> bool {
+    crate::tool_registry::access(name) == Some(crate::tool_registry::ToolAccess::Read)
+}
+
+/// The tools `tool_set` offers, minus `hidden` (disabled, hidden from the model, or out of
+/// budget for this turn).
+fn tools_for(tool_set: ToolSet, hidden: &[String]) -> Option<Vec<Tool>> {
+    let offered = |name: &str| !hidden.iter().any(|h| h == name);
+    match tool_set {
+        ToolSet::None | ToolSet::Plan => None,
+        ToolSet::ReadOnly => Some(
+            get_tools()
+                .into_iter()
+                .filter(|t| is_read_only_tool(&t.function.name) && offered(&t.function.name))
+                .collect(),
+        ),
+        ToolSet::Full => Some(
+            get_tools()
+                .into_iter()
+                .filter(|t| offered(&t.function.name))
+                .collect(),
+        ),
+    }
+}
+
+fn get_tools() -> Vec<Tool> {
+    crate::tool_registry::specs()
+        .into_iter()
+        .map(|spec| Tool {
             tool_type: "function".into(),
             function: FunctionDef {
-                name: "memory_forget".into(),
-                description: "Delete a memory by id.".into(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "id": {"type": "string", "description": "Memory id"}
-                    },
-                    "required": ["id"]
-                }),
+                name: spec.name.into(),
+                description: spec.description,
+                parameters: spec.parameters,
             },
-        },
-    ]
+        })
+        .collect()
 }
 
-pub async fn call_llm(
-    tx: mpsc::UnboundedSender<AppEvent>,
-    provider: LlmProvider,
-    api_key: String,
-    codex_account_id: Option<String>,
-    model: String,
-    messages: Vec<ChatMessage>,
-    tools_enabled: bool,
-) {
-    if matches!(provider, LlmProvider::Codex) {
-        let model = normalize_codex_model(&model);
-        // Refresh on every call (mirrors OpenCode behavior) so long-running sessions don't
-        // die on token expiry.
-        let auth = match crate::codex_oauth::codex_chatgpt_auth().await {
-            Ok(a) => a,
-            Err(e) => {
-                let _ = tx.send(AppEvent::AgentError(e));
-                let _ = tx.send(AppEvent::AgentDone);
-                return;
-            }
-        };
-        let account_id = codex_account_id.or(auth.account_id);
-        call_llm_codex_responses(tx, auth.access_token, account_id, model, messages, tools_enabled)
-            .await;
-        return;
-    }
-
-    let client = reqwest::Client::new();
+/// Where a chat request goes.
+#[derive(Debug, Clone)]
+pub struct LlmTarget {
+    pub provider: LlmProvider,
+    pub api_key: String,
+    pub codex_account_id: Option<String>,
+    pub model: String,
+    /// Tools left out of the request for this model (`[tools]` config and spent budgets).
+    pub hidden_tools: Vec<String>,
+    /// Use OpenAI's Responses API instead of Chat Completions (`[llm] responses_models`).
+    pub responses_api: bool,
+}
 
-    let request = ChatRequest {
-        model,
-        messages,
-        stream: true,
-        tools: if tools_enabled {
-            Some(get_tools())
-        } else {
-            None
-        },
-    };
+/// Retries for transient failures (rate limits, 5xx, dropped connections).
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// First backoff step; doubles per attempt (with jitter) up to `MAX_RETRY_DELAY`.
+    pub base_delay: Duration,
+}
 
-    let url = match provider {
-        LlmProvider::OpenRouter => OPENROUTER_URL,
-        LlmProvider::OpenAI => OPENAI_URL,
-        LlmProvider::Codex => OPENAI_URL, // unreachable (handled above)
-    };
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
 
-    let mut req = client
-        .post(url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
-    // OpenRouter recommends these headers; OpenAI ignores unknown headers.
-    if provider == LlmProvider::OpenRouter {
-        req = req
-            .header("HTTP-Referer", "https://github.com/jayasuryajsk/lorikeet")
-            .header("X-Title", "Lorikeet");
+impl RetryPolicy {
+    /// Backoff before retry number `attempt` (0-based): a random point in the upper half
+    /// of the exponential step, so concurrent clients don't retry in lockstep. A server
+    /// `Retry-After` takes precedence.
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(wait) = retry_after {
+            return wait.min(MAX_RETRY_DELAY);
+        }
+        let step = self
+            .base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(MAX_RETRY_DELAY);
+        step / 2 + (step / 2).mul_f64(jitter())
     }
+}
 
-    let response = req.send().await;
+/// A value in [0, 1); `RandomState` is freshly keyed per call, which is random enough here.
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// What kind of failure ended an LLM call, so the UI can say what to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Missing, invalid or expired credentials.
+    Auth,
+    RateLimit,
+    /// The prompt doesn't fit the model's context window.
+    ContextOverflow,
+    /// The provider's safety or moderation filter refused the request.
+    ContentFilter,
+    /// The provider couldn't be reached, or the connection broke.
+    Network,
+    Other,
+}
 
-    let response = match response {
-        Ok(r) => r,
-        Err(e) => {
-            let _ = tx.send(AppEvent::AgentError(e.to_string()));
-            return;
+impl ErrorKind {
+    /// Stable name for `--output json`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::RateLimit => "rate_limit",
+            Self::ContextOverflow => "context_overflow",
+            Self::ContentFilter => "content_filter",
+            Self::Network => "network",
+            Self::Other => "other",
         }
-    };
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        let _ = tx.send(AppEvent::AgentError(format!("HTTP {}: {}", status, body)));
-        return;
+    fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Auth => Some("Not authorized"),
+            Self::RateLimit => Some("Rate limited"),
+            Self::ContextOverflow => Some("Context too long"),
+            Self::ContentFilter => Some("Blocked by the content filter"),
+            Self::Network => Some("Network error"),
+            Self::Other => None,
+        }
     }
+}
 
-    let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
-    let mut pending_tool_calls: Vec<PendingToolCall> = Vec::new();
-    let mut finish_reason: Option<String> = None;
+/// An LLM call that failed for good: its kind, and the provider's message without the
+/// JSON around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentError {
+    pub kind: ErrorKind,
+    /// HTTP status, when the provider answered with one
+    pub status: Option<u16>,
+    pub message: String,
+}
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = match chunk {
-            Ok(c) => c,
-            Err(e) => {
-                let _ = tx.send(AppEvent::AgentError(e.to_string()));
+impl AgentError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            status: None,
+            message: message.into(),
+        }
+    }
+
+    /// Classify an HTTP error response from its status and body.
+    fn from_http(status: u16, body: &str) -> Self {
+        let message = provider_message(body);
+        let kind = match kind_from_text(&message) {
+            Some(kind) => kind,
+            None => match status {
+                401 | 403 => ErrorKind::Auth,
+                429 => ErrorKind::RateLimit,
+                413 => ErrorKind::ContextOverflow,
+                _ => ErrorKind::Other,
+            },
+        };
+        Self {
+            kind,
+            status: Some(status),
+            message,
+        }
+    }
+
+    /// Classify an error reported inside a stream, which only has text to go on.
+    fn from_message(message: &str) -> Self {
+        Self::new(kind_from_text(message).unwrap_or(ErrorKind::Other), message)
+    }
+
+    /// A short form for retry notices: the kind alone when it says enough.
+    pub fn headline(&self) -> String {
+        match self.kind {
+            ErrorKind::RateLimit => "Rate limited".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// What the user can do about it, for the error line in the transcript.
+    pub fn guidance(&self, provider: LlmProvider) -> Option<String> {
+        let hint = match self.kind {
+            ErrorKind::Auth => {
+                return Some(match provider.key_env() {
+                    Some(var) => format!("Check {} (or switch provider in /settings).", var),
+                    None => "Sign in again with /login.".to_string(),
+                })
+            }
+            ErrorKind::RateLimit => {
+                "Wait a moment and resend, or set [llm] fallback_provider to fail over."
+            }
+            ErrorKind::ContextOverflow => {
+                "Start a fresh session with /new, or pick a larger-context model in /model."
+            }
+            ErrorKind::ContentFilter => "Rephrase the request, or try another model in /model.",
+            ErrorKind::Network => "Check your connection or proxy, then resend.",
+            ErrorKind::Other => return None,
+        };
+        Some(hint.to_string())
+    }
+}
+
+impl std::fmt::Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.kind.label(), self.status) {
+            (Some(label), _) => write!(f, "{}: {}", label, self.message),
+            (None, Some(status)) => write!(f, "HTTP {}: {}", status, self.message),
+            (None, None) => f.write_str(&self.message),
+        }
+    }
+}
+
+impl From<String> for AgentError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<&str> for AgentError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
+
+/// Error kinds providers only tell apart in their wording (both usually come as 400s).
+fn kind_from_text(message: &str) -> Option<ErrorKind> {
+    let lower = message.to_lowercase();
+    let any = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+    if any(&[
+        "context_length_exceeded",
+        "maximum context length",
+        "context window",
+        "prompt is too long",
+        "too many tokens",
+        "input token count",
+    ]) {
+        Some(ErrorKind::ContextOverflow)
+    } else if any(&[
+        "content_filter",
+        "content policy",
+        "content management",
+        "flagged",
+    ]) {
+        Some(ErrorKind::ContentFilter)
+    } else {
+        None
+    }
+}
+
+/// The human-readable part of an error body: `error.message` in the OpenAI, OpenRouter and
+/// Gemini shapes, else the body itself, shortened.
+fn provider_message(body: &str) -> String {
+    let parsed: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    let message = parsed.as_ref().and_then(|v| {
+        // Gemini wraps streamed errors in an array.
+        let v = v.get(0).unwrap_or(v);
+        ["/error/message", "/message", "/error", "/detail"]
+            .iter()
+            .find_map(|p| v.pointer(p)?.as_str())
+    });
+    let message = message.unwrap_or(body).trim();
+    if message.is_empty() {
+        return "no details".to_string();
+    }
+    let mut short: String = message.chars().take(300).collect();
+    if short.len() < message.len() {
+        short.push('…');
+    }
+    short
+}
+
+/// Why one request attempt failed.
+#[derive(Debug)]
+struct AttemptError {
+    error: AgentError,
+    /// Rate limits, server errors and network failures are worth retrying.
+    transient: bool,
+    /// The provider couldn't be reached at all (no connection, or it timed out).
+    unreachable: bool,
+    retry_after: Option<Duration>,
+}
+
+impl AttemptError {
+    fn fatal(error: impl Into<AgentError>) -> Self {
+        Self {
+            error: error.into(),
+            transient: false,
+            unreachable: false,
+            retry_after: None,
+        }
+    }
+
+    fn network(err: reqwest::Error) -> Self {
+        Self {
+            unreachable: err.is_connect() || err.is_timeout(),
+            error: AgentError::new(ErrorKind::Network, err.to_string()),
+            transient: true,
+            retry_after: None,
+        }
+    }
+
+    fn http(status: reqwest::StatusCode, retry_after: Option<Duration>, body: &str) -> Self {
+        Self {
+            error: AgentError::from_http(status.as_u16(), body),
+            transient: is_transient_status(status.as_u16()),
+            unreachable: false,
+            retry_after,
+        }
+    }
+}
+
+fn is_transient_status(code: u16) -> bool {
+    // 529: "overloaded", used by some upstreams behind OpenRouter.
+    matches!(code, 408 | 425 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// What a completed stream asked for.
+enum Reply {
+    Done,
+    ToolCalls(Vec<ToolCallMessage>),
+}
+
+/// What an attempt has already streamed to the UI, to decide how (or whether) to retry.
+#[derive(Default)]
+struct Progress {
+    text: String,
+    tool_call_started: bool,
+}
+
+/// Stream a chat turn to `tx`, retrying transient failures with backoff and, if the
+/// primary target still fails before producing output, trying `fallback` once. Reports
+/// how long it all took as [`AppEvent::LlmTiming`].
+#[tracing::instrument(
+    name = "llm_call",
+    skip_all,
+    fields(provider = target.provider.name(), model = %target.model, messages = messages.len())
+)]
+pub async fn call_llm(
+    tx: mpsc::UnboundedSender<AppEvent>,
+    target: LlmTarget,
+    fallback: Option<LlmTarget>,
+    retry: RetryPolicy,
+    messages: Vec<ChatMessage>,
+    tool_set: ToolSet,
+) {
+    let started = Instant::now();
+    call_llm_with_fallback(tx.clone(), target, fallback, retry, messages, tool_set).await;
+    let _ = tx.send(AppEvent::LlmTiming(started.elapsed()));
+}
+
+async fn call_llm_with_fallback(
+    tx: mpsc::UnboundedSender<AppEvent>,
+    target: LlmTarget,
+    fallback: Option<LlmTarget>,
+    retry: RetryPolicy,
+    messages: Vec<ChatMessage>,
+    tool_set: ToolSet,
+) {
+    let mut last_error = AgentError::from("no target to call");
+    let mut offline = false;
+    for (i, target) in std::iter::once(target).chain(fallback).enumerate() {
+        if i > 0 {
+            tracing::warn!(model = %target.model, error = %last_error, "falling back");
+            let _ = tx.send(AppEvent::AgentRetry(format!(
+                "{} — falling back to {} ({})",
+                last_error,
+                target.model,
+                target.provider.name()
+            )));
+        }
+        match call_with_retries(&tx, &target, retry, &messages, tool_set).await {
+            Ok(Reply::ToolCalls(_)) if !tool_set.enabled() => {
+                let _ = tx.send(AppEvent::AgentError(
+                    "Plan mode: tool calls requested but tools are disabled".into(),
+                ));
+                let _ = tx.send(AppEvent::AgentDone);
                 return;
             }
+            Ok(Reply::ToolCalls(tool_calls)) => {
+                let _ = tx.send(AppEvent::AgentToolCalls(tool_calls));
+                return;
+            }
+            Ok(Reply::Done) => {
+                let _ = tx.send(AppEvent::AgentDone);
+                return;
+            }
+            Err((err, streamed)) => {
+                offline = err.unreachable && !streamed;
+                last_error = err.error;
+                // Switching models halfway through a reply would splice two answers.
+                if streamed {
+                    break;
+                }
+            }
+        }
+    }
+    tracing::error!(offline, error = %last_error, "llm call failed");
+    // Nothing reached the provider: the app keeps the turn and resends it later.
+    let _ = tx.send(if offline {
+        AppEvent::AgentOffline(last_error)
+    } else {
+        AppEvent::AgentError(last_error)
+    });
+}
+
+/// Run one target until it succeeds, fails permanently, or runs out of retries. The error
+/// side says whether any text already reached the UI.
+async fn call_with_retries(
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    target: &LlmTarget,
+    retry: RetryPolicy,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+) -> Result<Reply, (AttemptError, bool)> {
+    let client = reqwest::Client::new();
+    let mut target = target.clone();
+    let mut messages = messages.to_vec();
+    let mut streamed = String::new();
+    let mut resumed = false;
+    let mut attempt = 0;
+
+    loop {
+        if target.provider == LlmProvider::Codex {
+            target.model = normalize_codex_model(&target.model);
+            // Refresh on every call (mirrors OpenCode behavior) so long-running sessions
+            // don't die on token expiry.
+            let auth = crate::codex_oauth::codex_chatgpt_auth()
+                .await
+                .map_err(|e| {
+                    let error = AgentError::new(ErrorKind::Auth, e);
+                    (AttemptError::fatal(error), !streamed.is_empty())
+                })?;
+            target.api_key = auth.access_token;
+            target.codex_account_id = target.codex_account_id.or(auth.account_id);
+        }
+
+        let mut progress = Progress::default();
+        let result = match target.provider {
+            LlmProvider::Codex => {
+                call_llm_codex_responses(tx, &client, &target, &messages, tool_set, &mut progress)
+                    .await
+            }
+            LlmProvider::Gemini => {
+                call_gemini(tx, &client, &target, &messages, tool_set, &mut progress).await
+            }
+            LlmProvider::OpenAI if target.responses_api => {
+                call_openai_responses(tx, &client, &target, &messages, tool_set, &mut progress)
+                    .await
+            }
+            LlmProvider::OpenRouter | LlmProvider::OpenAI => {
+                stream_chat_completions(tx, &client, &target, &messages, tool_set, &mut progress)
+                    .await
+            }
+        };
+        let err = match result {
+            Ok(reply) => return Ok(reply),
+            Err(err) => err,
         };
+        streamed.push_str(&progress.text);
+        tracing::warn!(
+            attempt,
+            transient = err.transient,
+            error = %err.error,
+            "llm attempt failed"
+        );
+
+        if !err.transient || attempt >= retry.max_retries {
+            return Err((err, !streamed.is_empty()));
+        }
+        if !progress.text.is_empty() {
+            // OpenRouter continues a trailing assistant message (prefill), so a broken
+            // stream can pick up where it stopped. Elsewhere a retry would repeat text.
+            if target.provider != LlmProvider::OpenRouter || progress.tool_call_started {
+                return Err((err, true));
+            }
+            match messages.last_mut() {
+                Some(last) if resumed => last.content = Some(streamed.clone()),
+                _ => messages.push(ChatMessage {
+                    role: "assistant".into(),
+                    content: Some(streamed.clone()),
+                    tool_calls: None,
+                    tool_call_id: None,
+                    name: None,
+                    cache: false,
+                }),
+            }
+            resumed = true;
+        }
+
+        let delay = retry.delay(attempt, err.retry_after);
+        let _ = tx.send(AppEvent::AgentRetry(format!(
+            "{} — retrying in {:.1}s ({}/{})",
+            err.error.headline(),
+            delay.as_secs_f32(),
+            attempt + 1,
+            retry.max_retries
+        )));
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// OpenAI (and Codex) cache long prompt prefixes on their own. Anthropic and Gemini models
+/// behind OpenRouter only cache up to messages carrying a `cache_control` breakpoint.
+fn explicit_cache_breakpoints(target: &LlmTarget) -> bool {
+    target.provider == LlmProvider::OpenRouter
+        && (target.model.starts_with("anthropic/") || target.model.starts_with("google/gemini"))
+}
+
+/// Request messages as JSON. With `breakpoints`, messages marked `cache` send their text as
+/// a content part with `cache_control`, the form OpenRouter passes on to the provider.
+fn wire_messages(messages: &[ChatMessage], breakpoints: bool) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|m| {
+            let mut value = serde_json::to_value(m).unwrap_or_default();
+            if let Some(text) = m
+                .content
+                .as_deref()
+                .filter(|t| breakpoints && m.cache && !t.is_empty())
+            {
+                value["content"] = serde_json::json!([{
+                    "type": "text",
+                    "text": text,
+                    "cache_control": { "type": "ephemeral" },
+                }]);
+            }
+            value
+        })
+        .collect()
+}
+
+async fn stream_chat_completions(
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    target: &LlmTarget,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+    progress: &mut Progress,
+) -> Result<Reply, AttemptError> {
+    let request = ChatRequest {
+        model: target.model.clone(),
+        messages: wire_messages(messages, explicit_cache_breakpoints(target)),
+        stream: true,
+        tools: tools_for(tool_set, &target.hidden_tools),
+        stream_options: Some(StreamOptions {
+            include_usage: true,
+        }),
+        response_format: chat_response_format(target, tool_set),
+    };
+
+    let url = match target.provider {
+        LlmProvider::OpenRouter => OPENROUTER_URL,
+        LlmProvider::OpenAI => OPENAI_URL,
+        // unreachable (handled by the caller)
+        LlmProvider::Codex | LlmProvider::Gemini => OPENAI_URL,
+    };
+
+    let mut req = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", target.api_key))
+        .header("Content-Type", "application/json")
+        .json(&request);
+
+    // OpenRouter recommends these headers; OpenAI ignores unknown headers.
+    if target.provider == LlmProvider::OpenRouter {
+        req = req
+            .header("HTTP-Referer", "https://github.com/jayasuryajsk/lorikeet")
+            .header("X-Title", "Lorikeet");
+    }
+
+    let mut exchange = llm_debug::start(target.provider, &target.model, url, &request);
+    let response = req.send().await.map_err(AttemptError::network)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let wait = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        exchange.failed(status, &body);
+        return Err(AttemptError::http(status, wait, &body));
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    let mut pending_tool_calls: Vec<PendingToolCall> = Vec::new();
+    let mut finish_reason: Option<String> = None;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(AttemptError::network)?;
+        exchange.chunk(&chunk);
 
         buffer.push_str(&String::from_utf8_lossy(&chunk));
 
@@ -551,6 +970,9 @@ pub async fn call_llm(
 
             if let Some(data) = line.strip_prefix("data: ") {
                 if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
+                    if let Some(usage) = chunk.usage.filter(|u| u.is_object()) {
+                        let _ = tx.send(AppEvent::LlmUsage(TokenUsage::from_json(&usage)));
+                    }
                     for choice in chunk.choices {
                         // Track finish reason
                         if let Some(reason) = choice.finish_reason {
@@ -566,11 +988,13 @@ pub async fn call_llm(
 
                         // Handle content tokens
                         if let Some(content) = choice.delta.content {
+                            progress.text.push_str(&content);
                             let _ = tx.send(AppEvent::AgentChunk(content));
                         }
 
                         // Handle tool calls (assembled from deltas)
                         if let Some(tool_calls) = choice.delta.tool_calls {
+                            progress.tool_call_started = true;
                             for tc in tool_calls {
                                 // Ensure we have enough slots
                                 while pending_tool_calls.len() <= tc.index {
@@ -599,15 +1023,10 @@ pub async fn call_llm(
         }
     }
 
+    exchange.finish();
+
     // Check if we have tool calls to execute
     if finish_reason.as_deref() == Some("tool_calls") && !pending_tool_calls.is_empty() {
-        if !tools_enabled {
-            let _ = tx.send(AppEvent::AgentError(
-                "Plan mode: tool calls requested but tools are disabled".to_string(),
-            ));
-            let _ = tx.send(AppEvent::AgentDone);
-            return;
-        }
         let tool_calls: Vec<ToolCallMessage> = pending_tool_calls
             .into_iter()
             .filter(|tc| !tc.id.is_empty())
@@ -618,23 +1037,21 @@ pub async fn call_llm(
                     name: tc.name,
                     arguments: tc.arguments,
                 },
+                thought_signature: None,
             })
             .collect();
 
         if !tool_calls.is_empty() {
-            let _ = tx.send(AppEvent::AgentToolCalls(tool_calls));
-            return;
+            return Ok(Reply::ToolCalls(tool_calls));
         }
     }
 
-    let _ = tx.send(AppEvent::AgentDone);
+    Ok(Reply::Done)
 }
 
-fn tools_for_responses(tools_enabled: bool) -> Vec<serde_json::Value> {
-    if !tools_enabled {
-        return Vec::new();
-    }
-    get_tools()
+fn tools_for_responses(tool_set: ToolSet, hidden: &[String]) -> Vec<serde_json::Value> {
+    tools_for(tool_set, hidden)
+        .unwrap_or_default()
         .into_iter()
         .map(|t| {
             serde_json::json!({
@@ -647,12 +1064,13 @@ fn tools_for_responses(tools_enabled: bool) -> Vec<serde_json::Value> {
         .collect()
 }
 
-fn build_codex_responses_request(
+fn build_responses_request(
     model: &str,
     messages: &[ChatMessage],
-    tools_enabled: bool,
+    tool_set: ToolSet,
+    hidden: &[String],
 ) -> serde_json::Value {
-    // Codex Responses API expects:
+    // The Responses API (OpenAI and Codex) expects:
     // - instructions: string (system prompt)
     // - input: array of items (messages + function_call + function_call_output)
     let mut instructions_parts: Vec<String> = Vec::new();
@@ -711,9 +1129,9 @@ fn build_codex_responses_request(
     }
 
     let instructions = instructions_parts.join("\n\n");
-    let tools = tools_for_responses(tools_enabled);
+    let tools = tools_for_responses(tool_set, hidden);
 
-    serde_json::json!({
+    let mut body = serde_json::json!({
         "model": model,
         "instructions": instructions,
         "input": input,
@@ -723,160 +1141,60 @@ fn build_codex_responses_request(
         "store": false,
         "stream": true,
         "include": [],
-    })
+    });
+    if tool_set == ToolSet::Plan {
+        body["text"] = serde_json::json!({
+            "format": {
+                "type": "json_schema",
+                "name": "plan",
+                "strict": true,
+                "schema": plan_schema(),
+            },
+        });
+    }
+    body
 }
 
 async fn call_llm_codex_responses(
-    tx: mpsc::UnboundedSender<AppEvent>,
-    access_token: String,
-    account_id: Option<String>,
-    model: String,
-    messages: Vec<ChatMessage>,
-    tools_enabled: bool,
-) {
-    let client = reqwest::Client::new();
-    let mut model = model;
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    target: &LlmTarget,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+    progress: &mut Progress,
+) -> Result<Reply, AttemptError> {
+    let mut model = target.model.clone();
     let url = format!("{}/responses", CODEX_BASE_URL.trim_end_matches('/'));
 
     // One retry to recover from common "wrong model id" mistakes when using Codex OAuth.
     for attempt in 0..2 {
-        let body = build_codex_responses_request(&model, &messages, tools_enabled);
+        let body = build_responses_request(&model, messages, tool_set, &target.hidden_tools);
         let mut req = client
             .post(&url)
-            .header("Authorization", format!("Bearer {}", access_token))
+            .header("Authorization", format!("Bearer {}", target.api_key))
             .header("Content-Type", "application/json")
             .header("Accept", "text/event-stream")
             .json(&body);
-        if let Some(id) = account_id.as_ref().filter(|s| !s.trim().is_empty()) {
+        if let Some(id) = target
+            .codex_account_id
+            .as_ref()
+            .filter(|s| !s.trim().is_empty())
+        {
             // Matches OpenCode's behavior; required for some ChatGPT subscription org setups.
             req = req.header("ChatGPT-Account-Id", id);
         }
 
-        let resp = match req.send().await {
-            Ok(r) => r,
-            Err(e) => {
-                let _ = tx.send(AppEvent::AgentError(e.to_string()));
-                let _ = tx.send(AppEvent::AgentDone);
-                return;
-            }
-        };
+        let mut exchange = llm_debug::start(target.provider, &model, &url, &body);
+        let resp = req.send().await.map_err(AttemptError::network)?;
 
         if resp.status().is_success() {
-            // Continue below to stream SSE.
-            let mut stream = resp.bytes_stream();
-            let mut buffer = String::new();
-            let mut tool_calls: Vec<ToolCallMessage> = Vec::new();
-
-            while let Some(chunk) = stream.next().await {
-                let chunk = match chunk {
-                    Ok(c) => c,
-                    Err(e) => {
-                        let _ = tx.send(AppEvent::AgentError(e.to_string()));
-                        let _ = tx.send(AppEvent::AgentDone);
-                        return;
-                    }
-                };
-
-                buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                // SSE events are separated by a blank line.
-                while let Some(pos) = buffer.find("\n\n") {
-                    let event_block = buffer[..pos].to_string();
-                    buffer = buffer[pos + 2..].to_string();
-
-                    let mut data_lines = Vec::new();
-                    for line in event_block.lines() {
-                        let line = line.trim_end();
-                        if let Some(d) = line.strip_prefix("data:") {
-                            data_lines.push(d.trim_start().to_string());
-                        }
-                    }
-                    if data_lines.is_empty() {
-                        continue;
-                    }
-                    let data = data_lines.join("\n").trim().to_string();
-                    if data.is_empty() || data == "[DONE]" {
-                        continue;
-                    }
-
-                    let v: serde_json::Value = match serde_json::from_str(&data) {
-                        Ok(v) => v,
-                        Err(_) => continue,
-                    };
-                    let kind = v.get("type").and_then(|x| x.as_str()).unwrap_or("");
-                    match kind {
-                        "response.output_text.delta" => {
-                            if let Some(delta) = v.get("delta").and_then(|x| x.as_str()) {
-                                let _ = tx.send(AppEvent::AgentChunk(delta.to_string()));
-                            }
-                        }
-                        "response.reasoning_text.delta" => {
-                            if let Some(delta) = v.get("delta").and_then(|x| x.as_str()) {
-                                let _ = tx.send(AppEvent::AgentReasoning(delta.to_string()));
-                            }
-                        }
-                        "response.output_item.done" => {
-                            if let Some(item) = v.get("item").and_then(|x| x.as_object()) {
-                                let item_type =
-                                    item.get("type").and_then(|x| x.as_str()).unwrap_or("");
-                                if item_type == "function_call" {
-                                    let call_id = item
-                                        .get("call_id")
-                                        .and_then(|x| x.as_str())
-                                        .unwrap_or("")
-                                        .to_string();
-                                    let name = item
-                                        .get("name")
-                                        .and_then(|x| x.as_str())
-                                        .unwrap_or("")
-                                        .to_string();
-                                    let arguments = item
-                                        .get("arguments")
-                                        .and_then(|x| x.as_str())
-                                        .unwrap_or("{}")
-                                        .to_string();
-                                    if !call_id.is_empty() && !name.is_empty() {
-                                        tool_calls.push(ToolCallMessage {
-                                            id: call_id,
-                                            call_type: "function".into(),
-                                            function: ToolCallFunction { name, arguments },
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                        "response.failed" => {
-                            let msg = v
-                                .get("response")
-                                .and_then(|r| r.get("error"))
-                                .and_then(|e| e.get("message"))
-                                .and_then(|m| m.as_str())
-                                .unwrap_or("response.failed");
-                            let _ = tx.send(AppEvent::AgentError(msg.to_string()));
-                        }
-                        _ => {}
-                    }
-                }
-            }
-
-            if !tool_calls.is_empty() {
-                if !tools_enabled {
-                    let _ = tx.send(AppEvent::AgentError(
-                        "Plan mode: tool calls requested but tools are disabled".to_string(),
-                    ));
-                    let _ = tx.send(AppEvent::AgentDone);
-                    return;
-                }
-                let _ = tx.send(AppEvent::AgentToolCalls(tool_calls));
-                return;
-            }
-
-            let _ = tx.send(AppEvent::AgentDone);
-            return;
+            return read_responses_stream(tx, resp, progress, exchange).await;
         }
 
         let status = resp.status();
+        let wait = retry_after(resp.headers());
         let body_txt = resp.text().await.unwrap_or_default();
+        exchange.failed(status, &body_txt);
 
         // Retry with a Codex model slug if the backend rejects the current model.
         if attempt == 0
@@ -892,31 +1210,661 @@ async fn call_llm_codex_responses(
             }
         }
 
-        let _ = tx.send(AppEvent::AgentError(format!("HTTP {}: {}", status, body_txt)));
-        let _ = tx.send(AppEvent::AgentDone);
-        return;
+        return Err(AttemptError::http(status, wait, &body_txt));
+    }
+
+    unreachable!("the model-fallback loop returns on success or error")
+}
+
+/// OpenAI's Responses API, for models listed in `[llm] responses_models`. Reasoning models
+/// are asked for reasoning summaries, which stream like reasoning on other paths.
+async fn call_openai_responses(
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    target: &LlmTarget,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+    progress: &mut Progress,
+) -> Result<Reply, AttemptError> {
+    let mut body = build_responses_request(&target.model, messages, tool_set, &target.hidden_tools);
+    if supports_reasoning_summary(&target.model) {
+        body["reasoning"] = serde_json::json!({ "summary": "auto" });
+    }
+    let mut exchange =
+        llm_debug::start(target.provider, &target.model, OPENAI_RESPONSES_URL, &body);
+    let resp = client
+        .post(OPENAI_RESPONSES_URL)
+        .header("Authorization", format!("Bearer {}", target.api_key))
+        .header("Content-Type", "application/json")
+        .header("Accept", "text/event-stream")
+        .json(&body)
+        .send()
+        .await
+        .map_err(AttemptError::network)?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let wait = retry_after(resp.headers());
+        let body_txt = resp.text().await.unwrap_or_default();
+        exchange.failed(status, &body_txt);
+        return Err(AttemptError::http(status, wait, &body_txt));
+    }
+    read_responses_stream(tx, resp, progress, exchange).await
+}
+
+/// OpenAI reasoning models (o-series, gpt-5) return reasoning summaries when asked; others
+/// reject the `reasoning` parameter.
+fn supports_reasoning_summary(model: &str) -> bool {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    model.starts_with("gpt-5")
+        || (model.starts_with('o') && model[1..].starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Stream a Responses API reply: text and reasoning go to the UI as they arrive, function
+/// calls come back as [`ToolCallMessage`]s.
+async fn read_responses_stream(
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    resp: reqwest::Response,
+    progress: &mut Progress,
+    mut exchange: llm_debug::Exchange,
+) -> Result<Reply, AttemptError> {
+    let mut stream = resp.bytes_stream();
+    let mut buffer = String::new();
+    let mut tool_calls: Vec<ToolCallMessage> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(AttemptError::network)?;
+        exchange.chunk(&chunk);
+
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        // SSE events are separated by a blank line.
+        while let Some(pos) = buffer.find("\n\n") {
+            let event_block = buffer[..pos].to_string();
+            buffer = buffer[pos + 2..].to_string();
+
+            let mut data_lines = Vec::new();
+            for line in event_block.lines() {
+                let line = line.trim_end();
+                if let Some(d) = line.strip_prefix("data:") {
+                    data_lines.push(d.trim_start().to_string());
+                }
+            }
+            if data_lines.is_empty() {
+                continue;
+            }
+            let data = data_lines.join("\n").trim().to_string();
+            if data.is_empty() || data == "[DONE]" {
+                continue;
+            }
+
+            let v: serde_json::Value = match serde_json::from_str(&data) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            apply_responses_event(&v, tx, progress, &mut tool_calls)?;
+        }
+    }
+    exchange.finish();
+
+    if !tool_calls.is_empty() {
+        return Ok(Reply::ToolCalls(tool_calls));
+    }
+
+    Ok(Reply::Done)
+}
+
+/// Handle one Responses API stream event.
+fn apply_responses_event(
+    v: &serde_json::Value,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    progress: &mut Progress,
+    tool_calls: &mut Vec<ToolCallMessage>,
+) -> Result<(), AttemptError> {
+    let kind = v.get("type").and_then(|x| x.as_str()).unwrap_or("");
+    match kind {
+        "response.output_text.delta" => {
+            if let Some(delta) = v.get("delta").and_then(|x| x.as_str()) {
+                progress.text.push_str(delta);
+                let _ = tx.send(AppEvent::AgentChunk(delta.to_string()));
+            }
+        }
+        "response.reasoning_text.delta" | "response.reasoning_summary_text.delta" => {
+            if let Some(delta) = v.get("delta").and_then(|x| x.as_str()) {
+                let _ = tx.send(AppEvent::AgentReasoning(delta.to_string()));
+            }
+        }
+        "response.reasoning_summary_part.done" => {
+            // Summaries come in parts; keep them apart like paragraphs.
+            let _ = tx.send(AppEvent::AgentReasoning("\n\n".to_string()));
+        }
+        "response.output_item.done" => {
+            let Some(item) = v.get("item").and_then(|x| x.as_object()) else {
+                return Ok(());
+            };
+            if item.get("type").and_then(|x| x.as_str()) != Some("function_call") {
+                return Ok(());
+            }
+            progress.tool_call_started = true;
+            let field = |key: &str| item.get(key).and_then(|x| x.as_str()).unwrap_or("");
+            let (call_id, name) = (field("call_id"), field("name"));
+            if !call_id.is_empty() && !name.is_empty() {
+                let arguments = item
+                    .get("arguments")
+                    .and_then(|x| x.as_str())
+                    .unwrap_or("{}");
+                tool_calls.push(ToolCallMessage {
+                    id: call_id.to_string(),
+                    call_type: "function".into(),
+                    function: ToolCallFunction {
+                        name: name.to_string(),
+                        arguments: arguments.to_string(),
+                    },
+                    thought_signature: None,
+                });
+            }
+        }
+        "response.completed" => {
+            if let Some(usage) = v.pointer("/response/usage") {
+                let _ = tx.send(AppEvent::LlmUsage(TokenUsage::from_json(usage)));
+            }
+        }
+        "response.failed" | "error" => {
+            let msg = v
+                .pointer("/response/error/message")
+                .or_else(|| v.get("message"))
+                .and_then(|m| m.as_str())
+                .unwrap_or(kind);
+            return Err(AttemptError::fatal(AgentError::from_message(msg)));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Gemini models that think (2.5 and later) can stream thought summaries; older ones reject
+/// `thinkingConfig`.
+fn supports_gemini_thinking(model: &str) -> bool {
+    let model = normalize_gemini_model(model);
+    model.starts_with("gemini-2.5") || model.starts_with("gemini-3")
+}
+
+/// A `generateContent` request body. Gemini keeps the system prompt apart, calls the
+/// assistant `model`, and sends tool calls and results as `functionCall` / `functionResponse`
+/// parts. Results are matched to calls by name and order, so call ids stay on our side.
+fn build_gemini_request(
+    model: &str,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+    hidden: &[String],
+) -> serde_json::Value {
+    let mut system_parts: Vec<serde_json::Value> = Vec::new();
+    let mut contents: Vec<serde_json::Value> = Vec::new();
+    let mut call_names: std::collections::HashMap<&str, &str> = std::collections::HashMap::new();
+
+    for m in messages {
+        let text = m.content.as_deref().unwrap_or("");
+        let (role, parts) = match m.role.as_str() {
+            "system" => {
+                if !text.trim().is_empty() {
+                    system_parts.push(serde_json::json!({ "text": text }));
+                }
+                continue;
+            }
+            "tool" => {
+                let name = m.name.as_deref().or_else(|| {
+                    let id = m.tool_call_id.as_deref()?;
+                    call_names.get(id).copied()
+                });
+                let Some(name) = name else {
+                    continue;
+                };
+                let part = serde_json::json!({
+                    "functionResponse": { "name": name, "response": { "output": text } },
+                });
+                ("user", vec![part])
+            }
+            "assistant" => {
+                let mut parts = Vec::new();
+                if !text.trim().is_empty() {
+                    parts.push(serde_json::json!({ "text": text }));
+                }
+                for tc in m.tool_calls.iter().flatten() {
+                    call_names.insert(&tc.id, &tc.function.name);
+                    let args: serde_json::Value = serde_json::from_str(&tc.function.arguments)
+                        .unwrap_or_else(|_| serde_json::json!({}));
+                    let mut part = serde_json::json!({
+                        "functionCall": { "name": tc.function.name, "args": args },
+                    });
+                    if let Some(signature) = &tc.thought_signature {
+                        part["thoughtSignature"] = signature.as_str().into();
+                    }
+                    parts.push(part);
+                }
+                ("model", parts)
+            }
+            _ if text.trim().is_empty() => continue,
+            _ => ("user", vec![serde_json::json!({ "text": text })]),
+        };
+        if parts.is_empty() {
+            continue;
+        }
+        // Turns alternate; the results of parallel calls share one user turn.
+        match contents.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(existing) = last["parts"].as_array_mut() {
+                    existing.extend(parts);
+                }
+            }
+            _ => contents.push(serde_json::json!({ "role": role, "parts": parts })),
+        }
+    }
+
+    let mut body = serde_json::json!({ "contents": contents });
+    if !system_parts.is_empty() {
+        body["systemInstruction"] = serde_json::json!({ "parts": system_parts });
+    }
+    let declarations: Vec<serde_json::Value> = tools_for(tool_set, hidden)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| {
+            // `parametersJsonSchema` takes plain JSON Schema (e.g. `"type": [..]` unions),
+            // which the OpenAPI-style `parameters` field does not.
+            serde_json::json!({
+                "name": t.function.name,
+                "description": t.function.description,
+                "parametersJsonSchema": t.function.parameters,
+            })
+        })
+        .collect();
+    if !declarations.is_empty() {
+        body["tools"] = serde_json::json!([{ "functionDeclarations": declarations }]);
+    }
+
+    let mut generation = serde_json::Map::new();
+    if supports_gemini_thinking(model) {
+        generation.insert(
+            "thinkingConfig".into(),
+            serde_json::json!({ "includeThoughts": true }),
+        );
+    }
+    if tool_set == ToolSet::Plan {
+        generation.insert("responseMimeType".into(), "application/json".into());
+        generation.insert("responseJsonSchema".into(), plan_schema());
+    }
+    if !generation.is_empty() {
+        body["generationConfig"] = serde_json::Value::Object(generation);
+    }
+    body
+}
+
+/// Google's Gemini API (AI Studio key), streamed over SSE.
+async fn call_gemini(
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    client: &reqwest::Client,
+    target: &LlmTarget,
+    messages: &[ChatMessage],
+    tool_set: ToolSet,
+    progress: &mut Progress,
+) -> Result<Reply, AttemptError> {
+    let model = normalize_gemini_model(&target.model);
+    let body = build_gemini_request(&model, messages, tool_set, &target.hidden_tools);
+    let url = format!(
+        "{}/models/{}:streamGenerateContent?alt=sse",
+        GEMINI_BASE_URL, model
+    );
+    let mut exchange = llm_debug::start(target.provider, &model, &url, &body);
+    let resp = client
+        .post(url)
+        .header("x-goog-api-key", &target.api_key)
+        .header("Content-Type", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .map_err(AttemptError::network)?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let wait = retry_after(resp.headers());
+        let body_txt = resp.text().await.unwrap_or_default();
+        exchange.failed(status, &body_txt);
+        return Err(AttemptError::http(status, wait, &body_txt));
+    }
+
+    let mut stream = resp.bytes_stream();
+    let mut buffer = String::new();
+    let mut tool_calls: Vec<ToolCallMessage> = Vec::new();
+    let mut usage: Option<TokenUsage> = None;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(AttemptError::network)?;
+        exchange.chunk(&chunk);
+
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(pos) = buffer.find('\n') {
+            let line = buffer[..pos].trim().to_string();
+            buffer = buffer[pos + 1..].to_string();
+
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            if let Ok(v) = serde_json::from_str::<serde_json::Value>(data.trim()) {
+                apply_gemini_chunk(&v, tx, progress, &mut tool_calls, &mut usage)?;
+            }
+        }
+    }
+
+    exchange.finish();
+
+    // Every chunk carries the running totals; report them once.
+    if let Some(usage) = usage {
+        let _ = tx.send(AppEvent::LlmUsage(usage));
+    }
+    if !tool_calls.is_empty() {
+        return Ok(Reply::ToolCalls(tool_calls));
+    }
+    Ok(Reply::Done)
+}
+
+/// Handle one streamed `GenerateContentResponse`: thought parts are reasoning, text parts
+/// the reply, and each `functionCall` part a complete tool call.
+fn apply_gemini_chunk(
+    v: &serde_json::Value,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    progress: &mut Progress,
+    tool_calls: &mut Vec<ToolCallMessage>,
+    usage: &mut Option<TokenUsage>,
+) -> Result<(), AttemptError> {
+    if let Some(err) = v.pointer("/error/message").and_then(|m| m.as_str()) {
+        return Err(AttemptError::fatal(AgentError::from_message(err)));
+    }
+    if let Some(reason) = v
+        .pointer("/promptFeedback/blockReason")
+        .and_then(|r| r.as_str())
+    {
+        return Err(AttemptError::fatal(AgentError::new(
+            ErrorKind::ContentFilter,
+            format!("Gemini blocked the prompt ({})", reason),
+        )));
+    }
+    if let Some(meta) = v.get("usageMetadata") {
+        *usage = Some(TokenUsage::from_gemini(meta));
     }
 
-    // Unreachable: loop returns on success or error.
+    let parts = v
+        .pointer("/candidates/0/content/parts")
+        .and_then(|p| p.as_array());
+    for part in parts.into_iter().flatten() {
+        if let Some(call) = part.get("functionCall") {
+            progress.tool_call_started = true;
+            let Some(name) = call.get("name").and_then(|n| n.as_str()) else {
+                continue;
+            };
+            // Gemini rarely sends call ids; make one that stays unique across the session.
+            let id = call
+                .get("id")
+                .and_then(|x| x.as_str())
+                .filter(|id| !id.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    let nanos = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_nanos();
+                    format!("gemini_{}_{}", nanos, tool_calls.len())
+                });
+            let args = call
+                .get("args")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({}));
+            tool_calls.push(ToolCallMessage {
+                id,
+                call_type: "function".into(),
+                function: ToolCallFunction {
+                    name: name.to_string(),
+                    arguments: args.to_string(),
+                },
+                thought_signature: part
+                    .get("thoughtSignature")
+                    .and_then(|s| s.as_str())
+                    .map(str::to_string),
+            });
+        } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+            if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                let _ = tx.send(AppEvent::AgentReasoning(text.to_string()));
+            } else {
+                progress.text.push_str(text);
+                let _ = tx.send(AppEvent::AgentChunk(text.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A model offered by the active provider, as listed in the `/model` picker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub context_window: Option<u64>,
+    /// USD per million input tokens
+    pub prompt_price: Option<f64>,
+    /// USD per million output tokens
+    pub completion_price: Option<f64>,
+}
+
+impl ModelInfo {
+    /// `128k ctx  $0.15/$0.60 per M` (parts the provider doesn't report are left out).
+    pub fn details(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ctx) = self.context_window {
+            parts.push(if ctx >= 1000 {
+                format!("{}k ctx", ctx / 1000)
+            } else {
+                format!("{} ctx", ctx)
+            });
+        }
+        match (self.prompt_price, self.completion_price) {
+            (Some(p), Some(c)) if p == 0.0 && c == 0.0 => parts.push("free".to_string()),
+            (Some(p), Some(c)) => parts.push(format!("${:.2}/${:.2} per M", p, c)),
+            _ => {}
+        }
+        parts.join("  ")
+    }
+
+    /// Estimated USD cost of `usage` at this model's list price.
+    pub fn cost_of(&self, usage: &TokenUsage) -> Option<f64> {
+        let per_token = |price: f64, tokens: u64| price * tokens as f64 / 1_000_000.0;
+        Some(
+            per_token(self.prompt_price?, usage.prompt_tokens)
+                + per_token(self.completion_price?, usage.completion_tokens),
+        )
+    }
+}
+
+/// List the models the provider offers. OpenRouter reports context windows and pricing;
+/// OpenAI only ids; Gemini ids and input limits; Codex falls back to the Codex CLI's model
+/// cache.
+#[tracing::instrument(skip_all, fields(provider = provider.name()), err(level = "warn"))]
+pub async fn list_models(provider: LlmProvider, api_key: String) -> Result<Vec<ModelInfo>, String> {
+    let url = match provider {
+        LlmProvider::OpenRouter => OPENROUTER_MODELS_URL,
+        LlmProvider::OpenAI => OPENAI_MODELS_URL,
+        LlmProvider::Codex => {
+            let mut models: Vec<ModelInfo> = codex_cached_slugs()
+                .ok_or_else(|| "No Codex model cache (~/.codex/models_cache.json)".to_string())?
+                .into_iter()
+                .map(|id| ModelInfo {
+                    id,
+                    context_window: None,
+                    prompt_price: None,
+                    completion_price: None,
+                })
+                .collect();
+            models.sort_by(|a, b| a.id.cmp(&b.id));
+            return Ok(models);
+        }
+        LlmProvider::Gemini => {
+            let response = reqwest::Client::new()
+                .get(format!("{}/models?pageSize=1000", GEMINI_BASE_URL))
+                .header("x-goog-api-key", api_key)
+                .timeout(Duration::from_secs(20))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(format!("HTTP {}: {}", status, body.trim()));
+            }
+            let body = response.text().await.map_err(|e| e.to_string())?;
+            return parse_gemini_model_list(&body);
+        }
+    };
+
+    let mut req = reqwest::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(20));
+    if !api_key.is_empty() {
+        req = req.header("Authorization", format!("Bearer {}", api_key));
+    }
+    let response = req.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("HTTP {}: {}", status, body.trim()));
+    }
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    parse_model_list(provider, &body)
+}
+
+/// Parse an OpenAI-style `{"data": [...]}` model listing (OpenRouter extends each entry
+/// with `context_length` and per-token `pricing` strings).
+fn parse_model_list(provider: LlmProvider, body: &str) -> Result<Vec<ModelInfo>, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("Error parsing model list: {}", e))?;
+    let entries = root
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| "Error parsing model list: no `data` array".to_string())?;
+
+    // Per-token price as a decimal string; negative means "varies" (e.g. openrouter/auto).
+    let per_million = |v: Option<&serde_json::Value>| {
+        let price = match v? {
+            serde_json::Value::String(s) => s.parse::<f64>().ok()?,
+            other => other.as_f64()?,
+        };
+        (price >= 0.0).then_some(price * 1_000_000.0)
+    };
+
+    let mut models: Vec<ModelInfo> = entries
+        .iter()
+        .filter_map(|m| {
+            let id = m.get("id")?.as_str()?.to_string();
+            Some(ModelInfo {
+                context_window: m.get("context_length").and_then(|v| v.as_u64()),
+                prompt_price: per_million(m.pointer("/pricing/prompt")),
+                completion_price: per_million(m.pointer("/pricing/completion")),
+                id,
+            })
+        })
+        // The OpenAI listing mixes in embedding, audio and image models.
+        .filter(|m| {
+            provider != LlmProvider::OpenAI
+                || ![
+                    "embedding",
+                    "tts",
+                    "whisper",
+                    "dall-e",
+                    "moderation",
+                    "transcribe",
+                    "image",
+                ]
+                .iter()
+                .any(|skip| m.id.contains(skip))
+        })
+        .collect();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(models)
+}
+
+/// Parse Gemini's `{"models": [...]}` listing, keeping models that can chat.
+fn parse_gemini_model_list(body: &str) -> Result<Vec<ModelInfo>, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("Error parsing model list: {}", e))?;
+    let entries = root
+        .get("models")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| "Error parsing model list: no `models` array".to_string())?;
+
+    let mut models: Vec<ModelInfo> = entries
+        .iter()
+        .filter(|m| {
+            m.get("supportedGenerationMethods")
+                .and_then(|g| g.as_array())
+                .is_some_and(|g| g.iter().any(|x| x == "generateContent"))
+        })
+        .filter_map(|m| {
+            Some(ModelInfo {
+                id: normalize_gemini_model(m.get("name")?.as_str()?),
+                context_window: m.get("inputTokenLimit").and_then(|v| v.as_u64()),
+                prompt_price: None,
+                completion_price: None,
+            })
+        })
+        .filter(|m| !m.id.contains("embedding"))
+        .collect();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(models)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn model_listings_parse_context_and_pricing() {
+        let openrouter = r#"{"data": [
+            {"id": "z-ai/glm-4.7-flash", "context_length": 128000,
+             "pricing": {"prompt": "0.00000015", "completion": "0.0000006"}},
+            {"id": "openrouter/auto", "context_length": 2000000,
+             "pricing": {"prompt": "-1", "completion": "-1"}},
+            {"id": "meta/free", "pricing": {"prompt": "0", "completion": "0"}}
+        ]}"#;
+        let models = parse_model_list(LlmProvider::OpenRouter, openrouter).unwrap();
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["meta/free", "openrouter/auto", "z-ai/glm-4.7-flash"]);
+        assert_eq!(models[2].details(), "128k ctx  $0.15/$0.60 per M");
+        assert_eq!(models[1].details(), "2000k ctx");
+        assert_eq!(models[0].details(), "free");
+
+        let openai = r#"{"object": "list", "data": [
+            {"id": "gpt-4o", "object": "model"},
+            {"id": "text-embedding-3-small", "object": "model"}
+        ]}"#;
+        let models = parse_model_list(LlmProvider::OpenAI, openai).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].details(), "");
+        assert!(parse_model_list(LlmProvider::OpenAI, "{}").is_err());
+    }
+
     #[test]
     fn llm_request_tools_disabled_in_plan_mode() {
         let req = ChatRequest {
             model: "m".into(),
-            messages: vec![ChatMessage {
-                role: "user".into(),
-                content: Some("hi".into()),
-                tool_calls: None,
-                tool_call_id: None,
-                name: None,
-            }],
+            messages: wire_messages(
+                &[ChatMessage {
+                    role: "user".into(),
+                    content: Some("hi".into()),
+                    tool_calls: None,
+                    tool_call_id: None,
+                    name: None,
+                    cache: false,
+                }],
+                false,
+            ),
             stream: true,
             tools: None,
+            stream_options: None,
+            response_format: None,
         };
         let v = serde_json::to_value(&req).unwrap();
         assert!(
@@ -924,6 +1872,399 @@ mod tests {
             "expected tools to be omitted when None"
         );
     }
+
+    #[test]
+    fn responses_stream_events_map_to_reasoning_text_and_tool_calls() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+        let mut progress = Progress::default();
+        let mut calls = Vec::new();
+        let events = [
+            serde_json::json!({ "type": "response.reasoning_summary_text.delta", "delta": "Look" }),
+            serde_json::json!({ "type": "response.output_text.delta", "delta": "Reading" }),
+            serde_json::json!({ "type": "response.output_item.done", "item": {
+                "type": "function_call", "call_id": "call_1", "name": "read_file",
+                "arguments": "{\"path\":\"a.rs\"}",
+            }}),
+            serde_json::json!({ "type": "response.output_item.done", "item": {
+                "type": "reasoning", "summary": [],
+            }}),
+        ];
+        for event in &events {
+            apply_responses_event(event, &tx, &mut progress, &mut calls).unwrap();
+        }
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::AgentReasoning(r)) if r == "Look"));
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::AgentChunk(t)) if t == "Reading"));
+        assert_eq!(progress.text, "Reading");
+        assert!(progress.tool_call_started);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, "{\"path\":\"a.rs\"}");
+
+        let failed = serde_json::json!({
+            "type": "response.failed",
+            "response": { "error": { "message": "model overloaded" } },
+        });
+        let err = apply_responses_event(&failed, &tx, &mut progress, &mut calls).unwrap_err();
+        assert_eq!(err.error.message, "model overloaded");
+
+        assert!(supports_reasoning_summary("o4-mini"));
+        assert!(supports_reasoning_summary("gpt-5"));
+        assert!(!supports_reasoning_summary("gpt-4.1"));
+        assert!(!supports_reasoning_summary("omni-moderation-latest"));
+    }
+
+    #[test]
+    fn gemini_requests_translate_roles_tool_calls_and_results() {
+        let msg = |role: &str, content: &str| ChatMessage {
+            role: role.into(),
+            content: Some(content.into()),
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+            cache: false,
+        };
+        let mut call = msg("assistant", "");
+        call.tool_calls = Some(
+            ["a.rs", "b.rs"]
+                .iter()
+                .enumerate()
+                .map(|(i, path)| ToolCallMessage {
+                    id: format!("call_{}", i),
+                    call_type: "function".into(),
+                    function: ToolCallFunction {
+                        name: "read_file".into(),
+                        arguments: format!("{{\"path\":\"{}\"}}", path),
+                    },
+                    thought_signature: None,
+                })
+                .collect(),
+        );
+        let mut first = msg("tool", "fn a() {}");
+        first.tool_call_id = Some("call_0".into());
+        let mut second = msg("tool", "fn b() {}");
+        second.tool_call_id = Some("call_1".into());
+        let messages = [
+            msg("system", "Be brief."),
+            msg("user", "Read both files"),
+            call,
+            first,
+            second,
+        ];
+
+        let body = build_gemini_request("gemini-2.5-pro", &messages, ToolSet::ReadOnly, &[]);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(
+            contents[1]["parts"][1]["functionCall"]["args"]["path"],
+            "b.rs"
+        );
+        // Both results answer the model's turn together.
+        assert_eq!(contents[2]["role"], "user");
+        let results = contents[2]["parts"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["functionResponse"]["name"], "read_file");
+        assert_eq!(
+            results[1]["functionResponse"]["response"]["output"],
+            "fn b() {}"
+        );
+        let declarations = body["tools"][0]["functionDeclarations"].as_array().unwrap();
+        assert!(declarations.iter().any(|d| d["name"] == "read_file"));
+        assert!(!declarations.iter().any(|d| d["name"] == "bash"));
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"]["includeThoughts"],
+            true
+        );
+
+        let plan = build_gemini_request("gemini-2.0-flash", &messages[..2], ToolSet::Plan, &[]);
+        assert!(plan.get("tools").is_none());
+        assert_eq!(
+            plan["generationConfig"]["responseJsonSchema"],
+            plan_schema()
+        );
+        assert!(plan["generationConfig"].get("thinkingConfig").is_none());
+        assert_eq!(
+            normalize_gemini_model("google/gemini-2.5-flash"),
+            "gemini-2.5-flash"
+        );
+    }
+
+    #[test]
+    fn gemini_thought_signatures_go_back_with_their_calls() {
+        let (tx, _rx) = mpsc::unbounded_channel::<AppEvent>();
+        let mut calls = Vec::new();
+        let chunk = serde_json::json!({ "candidates": [{ "content": { "role": "model", "parts": [
+            { "functionCall": { "name": "read_file", "args": { "path": "a.rs" } },
+              "thoughtSignature": "c2lnLWE=" },
+            { "functionCall": { "name": "read_file", "args": { "path": "b.rs" } } },
+        ]}}]});
+        apply_gemini_chunk(&chunk, &tx, &mut Progress::default(), &mut calls, &mut None).unwrap();
+        assert_eq!(calls[0].thought_signature.as_deref(), Some("c2lnLWE="));
+        assert_eq!(calls[1].thought_signature, None);
+        // Other providers never see it
+        let openai = serde_json::to_value(&calls[0]).unwrap();
+        assert!(openai.get("thought_signature").is_none());
+
+        let call = ChatMessage {
+            role: "assistant".into(),
+            content: None,
+            tool_calls: Some(calls),
+            tool_call_id: None,
+            name: None,
+            cache: false,
+        };
+        let body = build_gemini_request("gemini-2.5-pro", &[call], ToolSet::ReadOnly, &[]);
+        let parts = body["contents"][0]["parts"].as_array().unwrap();
+        assert_eq!(parts[0]["thoughtSignature"], "c2lnLWE=");
+        assert_eq!(parts[0]["functionCall"]["args"]["path"], "a.rs");
+        assert!(parts[1].get("thoughtSignature").is_none());
+    }
+
+    #[test]
+    fn gemini_stream_chunks_map_to_reasoning_text_tool_calls_and_usage() {
+        let (tx, mut rx) = mpsc::unbounded_channel::<AppEvent>();
+        let mut progress = Progress::default();
+        let mut calls = Vec::new();
+        let mut usage = None;
+        let chunks = [
+            serde_json::json!({ "candidates": [{ "content": { "role": "model", "parts": [
+                { "text": "Look", "thought": true },
+            ]}}]}),
+            serde_json::json!({ "candidates": [{ "content": { "role": "model", "parts": [
+                { "text": "Reading" },
+                { "functionCall": { "name": "read_file", "args": { "path": "a.rs" } } },
+            ]}}], "usageMetadata": {
+                "promptTokenCount": 100, "candidatesTokenCount": 20,
+                "thoughtsTokenCount": 5, "cachedContentTokenCount": 60,
+            }}),
+        ];
+        for chunk in &chunks {
+            apply_gemini_chunk(chunk, &tx, &mut progress, &mut calls, &mut usage).unwrap();
+        }
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::AgentReasoning(r)) if r == "Look"));
+        assert!(matches!(rx.try_recv(), Ok(AppEvent::AgentChunk(t)) if t == "Reading"));
+        assert_eq!(progress.text, "Reading");
+        assert!(progress.tool_call_started);
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].id.starts_with("gemini_"));
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, "{\"path\":\"a.rs\"}");
+        let usage = usage.unwrap();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.cached_tokens
+            ),
+            (100, 25, 60)
+        );
+
+        let blocked = serde_json::json!({ "promptFeedback": { "blockReason": "SAFETY" } });
+        let err =
+            apply_gemini_chunk(&blocked, &tx, &mut progress, &mut calls, &mut None).unwrap_err();
+        assert_eq!(err.error.kind, ErrorKind::ContentFilter);
+        assert!(err.error.message.contains("SAFETY"));
+
+        let listing = r#"{"models": [
+            {"name": "models/gemini-2.5-pro", "inputTokenLimit": 1048576,
+             "supportedGenerationMethods": ["generateContent", "countTokens"]},
+            {"name": "models/text-embedding-004",
+             "supportedGenerationMethods": ["embedContent"]}
+        ]}"#;
+        let models = parse_gemini_model_list(listing).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "gemini-2.5-pro");
+        assert_eq!(models[0].context_window, Some(1048576));
+    }
+
+    #[test]
+    fn plan_replies_use_a_json_schema_where_supported() {
+        let target = |provider, model: &str| LlmTarget {
+            provider,
+            api_key: String::new(),
+            codex_account_id: None,
+            model: model.into(),
+            hidden_tools: Vec::new(),
+            responses_api: false,
+        };
+        let openai = target(LlmProvider::OpenAI, "gpt-4.1");
+        let format = chat_response_format(&openai, ToolSet::Plan).unwrap();
+        assert_eq!(format["type"], "json_schema");
+        assert_eq!(format["json_schema"]["schema"], plan_schema());
+        assert!(chat_response_format(&openai, ToolSet::None).is_none());
+        assert!(chat_response_format(
+            &target(LlmProvider::OpenRouter, "meta-llama/llama-3-8b"),
+            ToolSet::Plan
+        )
+        .is_none());
+        assert!(!ToolSet::Plan.enabled());
+        assert!(tools_for(ToolSet::Plan, &[]).is_none());
+
+        let body = build_responses_request("gpt-5", &[], ToolSet::Plan, &[]);
+        assert_eq!(body["text"]["format"]["name"], "plan");
+        assert!(build_responses_request("gpt-5", &[], ToolSet::Full, &[])
+            .get("text")
+            .is_none());
+    }
+
+    #[test]
+    fn cache_breakpoints_and_cached_usage() {
+        let message = |role: &str, cache: bool| ChatMessage {
+            role: role.into(),
+            content: Some("text".into()),
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+            cache,
+        };
+        let messages = [message("system", true), message("user", false)];
+
+        let wire = wire_messages(&messages, true);
+        assert_eq!(wire[0]["content"][0]["cache_control"]["type"], "ephemeral");
+        assert_eq!(wire[0]["content"][0]["text"], "text");
+        assert_eq!(wire[1]["content"], "text");
+        assert!(wire[0].get("cache").is_none());
+        assert_eq!(wire_messages(&messages, false)[0]["content"], "text");
+
+        let target = |provider, model: &str| LlmTarget {
+            provider,
+            api_key: String::new(),
+            codex_account_id: None,
+            model: model.into(),
+            hidden_tools: Vec::new(),
+            responses_api: false,
+        };
+        assert!(explicit_cache_breakpoints(&target(
+            LlmProvider::OpenRouter,
+            "anthropic/claude-sonnet-4"
+        )));
+        assert!(!explicit_cache_breakpoints(&target(
+            LlmProvider::OpenRouter,
+            "openai/gpt-5"
+        )));
+        assert!(!explicit_cache_breakpoints(&target(
+            LlmProvider::OpenAI,
+            "gpt-5"
+        )));
+
+        let chat = serde_json::json!({
+            "prompt_tokens": 2000,
+            "completion_tokens": 50,
+            "prompt_tokens_details": { "cached_tokens": 1536 },
+            "cost": 0.002
+        });
+        let usage = TokenUsage::from_json(&chat);
+        assert_eq!((usage.prompt_tokens, usage.cached_tokens), (2000, 1536));
+        assert_eq!(usage.cost, Some(0.002));
+
+        let responses = serde_json::json!({
+            "input_tokens": 900,
+            "output_tokens": 10,
+            "input_tokens_details": { "cached_tokens": 512 }
+        });
+        let usage = TokenUsage::from_json(&responses);
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (900, 10));
+        assert_eq!((usage.cached_tokens, usage.cost), (512, None));
+    }
+
+    #[test]
+    fn tool_sets_omit_write_and_hidden_tools() {
+        let names: Vec<String> = tools_for(ToolSet::ReadOnly, &[])
+            .unwrap()
+            .into_iter()
+            .map(|t| t.function.name)
+            .collect();
+        assert!(names.iter().any(|n| n == "read_file"));
+        for write_tool in [
+            "bash",
+            "write_file",
+            "edit_file",
+            "apply_patch",
+            "memory_save",
+        ] {
+            assert!(!names.iter().any(|n| n == write_tool), "{}", write_tool);
+        }
+        assert!(tools_for(ToolSet::None, &[]).is_none());
+
+        let hidden = ["semantic_search".to_string(), "bash".to_string()];
+        let names: Vec<String> = tools_for(ToolSet::Full, &hidden)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.function.name)
+            .collect();
+        assert!(names.iter().any(|n| n == "edit_file"));
+        assert!(!names.iter().any(|n| hidden.contains(n)));
+    }
+
+    #[test]
+    fn provider_errors_are_classified_without_their_json() {
+        let overflow = AgentError::from_http(
+            400,
+            r#"{"error": {"message": "This model's maximum context length is 128000 tokens.",
+                "type": "invalid_request_error", "code": "context_length_exceeded"}}"#,
+        );
+        assert_eq!(overflow.kind, ErrorKind::ContextOverflow);
+        assert_eq!(
+            overflow.to_string(),
+            "Context too long: This model's maximum context length is 128000 tokens."
+        );
+        assert!(overflow
+            .guidance(LlmProvider::OpenAI)
+            .is_some_and(|g| g.contains("/new")));
+
+        let auth = AgentError::from_http(401, r#"{"error": {"message": "No auth credentials"}}"#);
+        assert_eq!(auth.kind, ErrorKind::Auth);
+        assert!(auth
+            .guidance(LlmProvider::OpenRouter)
+            .is_some_and(|g| g.contains("OPENROUTER_API_KEY")));
+        assert_eq!(
+            auth.guidance(LlmProvider::Codex).as_deref(),
+            Some("Sign in again with /login.")
+        );
+
+        // Gemini errors come wrapped in an array; moderation is told apart by wording.
+        let flagged = AgentError::from_http(
+            403,
+            r#"[{"error": {"code": 403, "message": "Input was flagged by moderation"}}]"#,
+        );
+        assert_eq!(flagged.kind, ErrorKind::ContentFilter);
+        assert_eq!(flagged.message, "Input was flagged by moderation");
+
+        let limited = AgentError::from_http(429, "slow down");
+        assert_eq!(limited.kind, ErrorKind::RateLimit);
+        assert_eq!(limited.headline(), "Rate limited");
+
+        let upstream = AgentError::from_http(502, "<html>Bad Gateway</html>");
+        assert_eq!(upstream.kind, ErrorKind::Other);
+        assert_eq!(upstream.to_string(), "HTTP 502: <html>Bad Gateway</html>");
+        assert!(upstream.guidance(LlmProvider::OpenAI).is_none());
+        assert_eq!(AgentError::from("stream reset").to_string(), "stream reset");
+    }
+
+    #[test]
+    fn retry_backoff_grows_with_jitter_and_honors_retry_after() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(400),
+        };
+        for attempt in 0..4 {
+            let step = Duration::from_millis(400 * (1 << attempt));
+            let delay = policy.delay(attempt, None);
+            assert!(delay >= step / 2 && delay <= step, "{attempt}: {delay:?}");
+        }
+        assert!(policy.delay(30, None) <= MAX_RETRY_DELAY);
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+
+        assert!(is_transient_status(429));
+        assert!(is_transient_status(503));
+        assert!(!is_transient_status(400));
+        assert!(!is_transient_status(401));
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -941,7 +2282,45 @@ struct ChatResponseMessage {
     content: Option<String>,
 }
 
+const TOOL_SUMMARY_SYSTEM_PROMPT: &str = "You condense tool output for a coding agent that will act on your summary instead of the full text. Keep every error and warning message verbatim, every file:line reference, failing test names, exit codes and final results. Drop progress bars, repeated lines and routine success noise. Reply with the summary only, as plain text.";
+
+/// Condense one long tool result with a (cheap) model; the agent sees this instead.
+pub async fn summarize_tool_output(
+    provider: LlmProvider,
+    api_key: String,
+    model: String,
+    tool: &str,
+    output: &str,
+) -> Result<String, String> {
+    let message = |role: &str, content: String| ChatMessage {
+        role: role.into(),
+        content: Some(content),
+        tool_calls: None,
+        tool_call_id: None,
+        name: None,
+        cache: false,
+    };
+    let messages = vec![
+        message("system", TOOL_SUMMARY_SYSTEM_PROMPT.to_string()),
+        message(
+            "user",
+            format!("Output of the `{}` tool:\n\n{}", tool, output),
+        ),
+    ];
+    let summary = call_llm_nonstream(provider, api_key, model, messages).await?;
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return Err("empty summary".to_string());
+    }
+    Ok(summary.to_string())
+}
+
 /// Non-streaming helper for one-shot calls (e.g., memory extraction).
+#[tracing::instrument(
+    skip_all,
+    fields(provider = provider.name(), model = %model),
+    err(level = "warn")
+)]
 pub async fn call_llm_nonstream(
     provider: LlmProvider,
     api_key: String,
@@ -950,11 +2329,46 @@ pub async fn call_llm_nonstream(
 ) -> Result<String, String> {
     let client = reqwest::Client::new();
 
+    if provider == LlmProvider::Gemini {
+        let model = normalize_gemini_model(&model);
+        let body = build_gemini_request(&model, &messages, ToolSet::None, &[]);
+        let response = client
+            .post(format!(
+                "{}/models/{}:generateContent",
+                GEMINI_BASE_URL, model
+            ))
+            .header("x-goog-api-key", api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("HTTP {}: {}", status, body));
+        }
+        let parsed: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Error parsing response: {}", e))?;
+        let parts = parsed
+            .pointer("/candidates/0/content/parts")
+            .and_then(|p| p.as_array());
+        return Ok(parts
+            .into_iter()
+            .flatten()
+            .filter(|p| p.get("thought").and_then(|t| t.as_bool()) != Some(true))
+            .filter_map(|p| p.get("text")?.as_str())
+            .collect());
+    }
+
     let request = ChatRequest {
         model,
-        messages,
+        messages: wire_messages(&messages, false),
         stream: false,
         tools: None,
+        stream_options: None,
+        response_format: None,
     };
 
     let url = match provider {
@@ -963,6 +2377,7 @@ pub async fn call_llm_nonstream(
         LlmProvider::Codex => {
             return Err("Codex provider: non-streaming calls are not supported yet".to_string())
         }
+        LlmProvider::Gemini => unreachable!("handled above"),
     };
 
     let mut req = client
@@ -0,0 +1,355 @@
+//! `--debug-llm`: each request payload sent to the model and the raw stream that came
+//! back, appended to `~/.lorikeet/logs/llm.jsonl` with secrets redacted. The latest
+//! request is also kept in memory for `/debug last`, with or without the flag.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::llm::LlmProvider;
+use crate::logging;
+use crate::memory::redaction::Redactor;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static LAST: Mutex<Option<LastRequest>> = Mutex::new(None);
+
+const LOG_NAME: &str = "llm.jsonl";
+/// The log is rotated past this size into `llm.1.jsonl` … `llm.<ROTATIONS>.jsonl`.
+const MAX_LOG_BYTES: u64 = 20 * 1024 * 1024;
+const ROTATIONS: usize = 3;
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    logging::log_dir().map(|d| d.join(LOG_NAME))
+}
+
+fn redactor() -> &'static Redactor {
+    static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+    REDACTOR.get_or_init(Redactor::new)
+}
+
+/// The latest chat request, as `/debug last` shows it.
+#[derive(Debug, Clone)]
+pub struct LastRequest {
+    pub ts: i64,
+    pub provider: LlmProvider,
+    pub model: String,
+    pub url: String,
+    pub body: Value,
+}
+
+/// The latest chat request, redacted. Kept raw until asked for, so calls made without
+/// `--debug-llm` don't pay for redaction.
+pub fn last_request() -> Option<LastRequest> {
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner()).clone()?;
+    redact_value(&mut last.body, redactor());
+    Some(last)
+}
+
+/// One request and its response. Dropping it writes the response record, so an attempt
+/// that errors out or is cancelled mid-stream is still logged (as `interrupted`).
+pub struct Exchange {
+    id: u64,
+    started: Instant,
+    /// Raw response text, only collected while logging.
+    response: Option<String>,
+    outcome: Option<String>,
+}
+
+/// Record a request about to be sent to `url`.
+pub fn start(
+    provider: LlmProvider,
+    model: &str,
+    url: &str,
+    body: &impl serde::Serialize,
+) -> Exchange {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let body = serde_json::to_value(body).unwrap_or_default();
+    let ts = crate::memory::types::unix_ts();
+    let logged = enabled();
+    if logged {
+        let mut redacted = body.clone();
+        redact_value(&mut redacted, redactor());
+        append(&json!({
+            "ts": ts,
+            "pid": std::process::id(),
+            "id": id,
+            "kind": "request",
+            "provider": provider.name(),
+            "model": model,
+            "url": url,
+            "body": redacted,
+        }));
+    }
+    *LAST.lock().unwrap_or_else(|e| e.into_inner()) = Some(LastRequest {
+        ts,
+        provider,
+        model: model.to_string(),
+        url: url.to_string(),
+        body,
+    });
+    Exchange {
+        id,
+        started: Instant::now(),
+        response: logged.then(String::new),
+        outcome: None,
+    }
+}
+
+impl Exchange {
+    /// A piece of the response stream, as received.
+    pub fn chunk(&mut self, bytes: &[u8]) {
+        if let Some(response) = self.response.as_mut() {
+            response.push_str(&String::from_utf8_lossy(bytes));
+        }
+    }
+
+    /// The stream ended normally.
+    pub fn finish(&mut self) {
+        self.outcome = Some("ok".to_string());
+    }
+
+    /// The provider answered with an error status instead of a stream.
+    pub fn failed(&mut self, status: reqwest::StatusCode, body: &str) {
+        self.chunk(body.as_bytes());
+        self.outcome = Some(format!("HTTP {}", status.as_u16()));
+    }
+}
+
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        let Some(response) = self.response.take() else {
+            return;
+        };
+        append(&json!({
+            "ts": crate::memory::types::unix_ts(),
+            "pid": std::process::id(),
+            "id": self.id,
+            "kind": "response",
+            "outcome": self.outcome.as_deref().unwrap_or("interrupted"),
+            "elapsed_ms": self.started.elapsed().as_millis() as u64,
+            "body": redactor().redact_secrets(&response),
+        }));
+    }
+}
+
+/// Redact secrets in every string of a payload, keeping it valid JSON.
+fn redact_value(value: &mut Value, redactor: &Redactor) {
+    match value {
+        Value::String(s) => {
+            if redactor.looks_sensitive(s) {
+                *s = redactor.redact_secrets(s);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| redact_value(v, redactor)),
+        Value::Object(map) => map.values_mut().for_each(|v| redact_value(v, redactor)),
+        _ => {}
+    }
+}
+
+/// Append one record to the log, rotating it first if it's grown too big. Failures are
+/// ignored: debugging output must never break a turn.
+fn append(record: &Value) {
+    let Some(dir) = logging::log_dir() else {
+        return;
+    };
+    let _ = std::fs::create_dir_all(&dir);
+    let path = dir.join(LOG_NAME);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        rotate(&dir);
+    }
+    let Ok(mut file) = open_log(&path) else {
+        return;
+    };
+    let mut line = record.to_string();
+    line.push('\n');
+    let _ = file.write_all(line.as_bytes());
+}
+
+/// Open the log for appending, readable by its owner only: it holds whole prompts, files
+/// and tool output, and redaction is best-effort. Rotated logs keep the mode.
+fn open_log(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // `mode` only applies on creation; tighten a log left by an older version
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+/// `llm.jsonl` -> `llm.1.jsonl` -> … -> `llm.<ROTATIONS>.jsonl`, dropping the oldest.
+fn rotate(dir: &std::path::Path) {
+    let rotated = |n: usize| dir.join(format!("llm.{}.jsonl", n));
+    let _ = std::fs::remove_file(rotated(ROTATIONS));
+    for n in (1..ROTATIONS).rev() {
+        let _ = std::fs::rename(rotated(n), rotated(n + 1));
+    }
+    let _ = std::fs::rename(dir.join(LOG_NAME), rotated(1));
+}
+
+/// `/debug last`: the request's messages one after another, each under a `── role ──`
+/// rule, with text shown as text and anything else (tool calls, parts) as JSON.
+pub fn request_lines(last: &LastRequest) -> Vec<String> {
+    let body = &last.body;
+    // Chat Completions send `messages`, the Responses API `input`, Gemini `contents`.
+    let messages = ["messages", "input", "contents"]
+        .iter()
+        .find_map(|k| body.get(*k).and_then(Value::as_array))
+        .cloned()
+        .unwrap_or_default();
+    let tools = body
+        .get("tools")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    let mut lines = vec![
+        format!(
+            "{} · {} · {} messages · {} tools",
+            last.provider.name(),
+            last.model,
+            messages.len(),
+            tools
+        ),
+        last.url.clone(),
+    ];
+
+    let mut system = Vec::new();
+    if let Some(text) = body.get("instructions").and_then(Value::as_str) {
+        system.push(json!({ "role": "system", "content": text }));
+    }
+    if let Some(instruction) = body.get("systemInstruction") {
+        system.push(json!({ "role": "system", "parts": instruction.get("parts") }));
+    }
+    for message in system.iter().chain(&messages) {
+        let role = ["role", "type"]
+            .iter()
+            .find_map(|k| message.get(*k).and_then(Value::as_str))
+            .unwrap_or("message");
+        lines.push(String::new());
+        lines.push(format!("── {} ──", role));
+        let mut rest = message.as_object().cloned().unwrap_or_default();
+        rest.remove("role");
+        match rest.remove("content") {
+            Some(Value::String(text)) => lines.extend(text.lines().map(str::to_string)),
+            Some(Value::Null) | None => {}
+            // Content parts (cache breakpoints, images) stay JSON.
+            Some(parts) => {
+                rest.insert("content".to_string(), parts);
+            }
+        }
+        if !rest.is_empty() {
+            let pretty = serde_json::to_string_pretty(&rest).unwrap_or_default();
+            lines.extend(pretty.lines().map(str::to_string));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_redacted_and_listed_by_message() {
+        let mut body = json!({
+            "model": "openai/gpt-5",
+            "messages": [
+                { "role": "system", "content": "You are Lorikeet." },
+                { "role": "user", "content": "use api_key=abcdefghijkl\nthen run it" },
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{ "id": "call_1", "function": { "name": "bash" } }],
+                },
+            ],
+            "tools": [{}, {}],
+        });
+        redact_value(&mut body, &Redactor::new());
+        assert_eq!(
+            body["messages"][1]["content"],
+            "use [REDACTED]\nthen run it"
+        );
+
+        let last = LastRequest {
+            ts: 0,
+            provider: LlmProvider::OpenRouter,
+            model: "openai/gpt-5".into(),
+            url: "https://openrouter.ai/api/v1/chat/completions".into(),
+            body,
+        };
+        let lines = request_lines(&last);
+        assert_eq!(lines[0], "openrouter · openai/gpt-5 · 3 messages · 2 tools");
+        let user = lines.iter().position(|l| l == "── user ──").unwrap();
+        assert_eq!(lines[user + 1], "use [REDACTED]");
+        assert_eq!(lines[user + 2], "then run it");
+        let assistant = lines.iter().position(|l| l == "── assistant ──").unwrap();
+        assert!(lines[assistant..].iter().any(|l| l.contains("\"call_1\"")));
+    }
+
+    #[test]
+    fn rotation_keeps_a_bounded_number_of_logs() {
+        let dir = std::env::temp_dir().join(format!(
+            "lorikeet-test-llm-debug-{}",
+            crate::memory::types::unix_ts()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for generation in 0..=ROTATIONS + 1 {
+            std::fs::write(dir.join(LOG_NAME), generation.to_string()).unwrap();
+            rotate(&dir);
+        }
+        assert!(!dir.join(LOG_NAME).exists());
+        let newest = std::fs::read_to_string(dir.join("llm.1.jsonl")).unwrap();
+        assert_eq!(newest, (ROTATIONS + 1).to_string());
+        assert!(dir.join(format!("llm.{}.jsonl", ROTATIONS)).exists());
+        assert!(!dir.join(format!("llm.{}.jsonl", ROTATIONS + 1)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn logs_are_private_to_their_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::TempDir::new().unwrap();
+        let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode();
+
+        let fresh = dir.path().join(LOG_NAME);
+        open_log(&fresh).unwrap();
+        assert_eq!(mode(&fresh) & 0o777, 0o600);
+
+        let old = dir.path().join("llm.1.jsonl");
+        std::fs::write(&old, "").unwrap();
+        std::fs::set_permissions(&old, std::fs::Permissions::from_mode(0o644)).unwrap();
+        open_log(&old).unwrap();
+        assert_eq!(mode(&old) & 0o777, 0o600);
+    }
+
+    #[test]
+    fn the_last_request_is_redacted_when_shown() {
+        let _exchange = start(
+            LlmProvider::OpenRouter,
+            "m",
+            "https://example.com",
+            &json!({ "messages": [{ "role": "user", "content": "api_key=abcdefghijkl" }] }),
+        );
+        let last = last_request().unwrap();
+        assert_eq!(last.body["messages"][0]["content"], "[REDACTED]");
+    }
+}
@@ -0,0 +1,122 @@
+//! Diagnostics log: `tracing` events and span timings (tool runs, model calls, indexing)
+//! written to `~/.lorikeet/logs/lorikeet.log`, never to the terminal the TUI draws on.
+//! `LORIKEET_LOG` picks what's recorded, in `RUST_LOG` syntax (`lorikeet=debug`,
+//! `lorikeet::llm=trace,info`); the default is `lorikeet=info`.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+pub const FILTER_ENV: &str = "LORIKEET_LOG";
+const DEFAULT_FILTER: &str = "lorikeet=info";
+const LOG_NAME: &str = "lorikeet.log";
+/// Past this size the log is moved to `lorikeet.1.log` at startup.
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+/// How far back `/logs` reads.
+const TAIL_BYTES: u64 = 256 * 1024;
+
+/// `~/.lorikeet/logs`. Logs describe runs on this machine, so they stay here when
+/// `[storage] dir` moves sessions to a synced folder.
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".lorikeet").join("logs"))
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    log_dir().map(|d| d.join(LOG_NAME))
+}
+
+/// Install the file subscriber, plus a panic hook that records panics: a panicking
+/// spawned task otherwise dies without a trace while the TUI owns the screen. Logging
+/// stays off if the file can't be opened.
+pub fn init() {
+    let Some(dir) = log_dir() else {
+        return;
+    };
+    if std::fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    let path = dir.join(LOG_NAME);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        let _ = std::fs::rename(&path, dir.join("lorikeet.1.log"));
+    }
+    let Ok(file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+    else {
+        return;
+    };
+    let filter =
+        EnvFilter::try_from_env(FILTER_ENV).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .finish();
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        return;
+    }
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current();
+        tracing::error!(thread = thread.name().unwrap_or("unnamed"), "{}", info);
+        previous(info);
+    }));
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        pid = std::process::id(),
+        "started"
+    );
+}
+
+/// The last `max_lines` lines of the log at `path`.
+pub fn tail(path: &Path, max_lines: usize) -> Vec<String> {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return Vec::new();
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let start = len.saturating_sub(TAIL_BYTES);
+    if file.seek(SeekFrom::Start(start)).is_err() {
+        return Vec::new();
+    }
+    let mut bytes = Vec::new();
+    let _ = file.read_to_end(&mut bytes);
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines: Vec<&str> = text.lines().collect();
+    // Reading from the middle of the file starts mid-line.
+    if start > 0 && !lines.is_empty() {
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(max_lines);
+    lines[skip..].iter().map(|l| l.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_returns_the_newest_whole_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "lorikeet-test-log-tail-{}.log",
+            crate::memory::types::unix_ts()
+        ));
+        let text: String = (1..=50_000).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(&path, &text).unwrap();
+
+        let lines = tail(&path, 3);
+        assert_eq!(lines, vec!["line 49998", "line 49999", "line 50000"]);
+        // Only the end of a big file is read, starting at a line boundary.
+        let all = tail(&path, usize::MAX);
+        assert!(all.len() < 50_000);
+        assert!(all[0].starts_with("line ") && text.contains(&format!("\n{}\n", all[0])));
+
+        assert!(tail(&path.with_extension("missing"), 3).is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
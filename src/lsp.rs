@@ -448,6 +448,43 @@ impl LspClient {
         Ok(resp.get("result").cloned().unwrap_or(Value::Null))
     }
 
+    /// Tell the server `path` changed on disk (opening it if needed). Returns its uri and
+    /// the diagnostics published before, cleared so the next publish is for the new text.
+    async fn sync_file(
+        &self,
+        lang: LspLanguage,
+        path: &Path,
+    ) -> Result<(String, Option<Value>), String> {
+        let canon = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let uri = file_uri(&canon)?;
+        let before = self.diagnostics.lock().remove(&uri);
+        let Some(version) = self.opened.lock().get(&uri).copied() else {
+            self.ensure_open(lang, path).await?;
+            return Ok((uri, before));
+        };
+
+        let text = tokio::fs::read_to_string(&canon)
+            .await
+            .map_err(|e| format!("Error: read file for lsp: {}", e))?;
+        let version = version + 1;
+        self.opened.lock().insert(uri.clone(), version);
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": {"uri": uri, "version": version},
+                "contentChanges": [{"text": text}]
+            }),
+        )
+        .await?;
+        // rust-analyzer only runs cargo check on save.
+        self.notify(
+            "textDocument/didSave",
+            json!({"textDocument": {"uri": uri}}),
+        )
+        .await?;
+        Ok((uri, before))
+    }
+
     async fn ensure_open(&self, lang: LspLanguage, path: &Path) -> Result<String, String> {
         let canon = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
         let uri = file_uri(&canon)?;
@@ -722,6 +759,126 @@ pub async fn lsp_diagnostics(
     }
 }
 
+/// How long to wait for diagnostics on a file the server is seeing for the first time.
+const BASELINE_WAIT: Duration = Duration::from_secs(2);
+
+/// How long to wait for diagnostics after an edit; slow checks simply miss the turn.
+const AFTER_EDIT_WAIT: Duration = Duration::from_secs(8);
+
+/// Open files an edit is about to change, so [`new_errors_after_edit`] can tell errors
+/// the edit introduced from ones that were already there. Best-effort and silent.
+pub async fn open_before_edit(policy: &SandboxPolicy, paths: &[PathBuf]) {
+    for path in paths {
+        let Some(lang) = LspLanguage::from_path(path) else {
+            continue;
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let _ = diagnostics_within(policy, lang, path, BASELINE_WAIT, false).await;
+    }
+}
+
+/// Errors the language server reports for `paths` after an edit that it didn't report
+/// before, as a warning for the tool result. `None` if there are none or no server could
+/// check the files.
+pub async fn new_errors_after_edit(policy: &SandboxPolicy, paths: &[PathBuf]) -> Option<String> {
+    let mut lines = Vec::new();
+    for path in paths {
+        let Some(lang) = LspLanguage::from_path(path) else {
+            continue;
+        };
+        let Ok((before, after)) =
+            diagnostics_within(policy, lang, path, AFTER_EDIT_WAIT, true).await
+        else {
+            continue;
+        };
+        let display = path
+            .strip_prefix(&policy.root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        for (line, message) in new_errors(before.as_ref(), after.as_ref()) {
+            lines.push(format!("- {}:{}: {}", display, line, message));
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "⚠ New errors from the language server after this edit:\n{}",
+        lines.join("\n")
+    ))
+}
+
+/// Diagnostics for `path` before and after (re)sending it to the server. With `changed`
+/// false an already open file is left alone and its cached diagnostics returned.
+async fn diagnostics_within(
+    policy: &SandboxPolicy,
+    lang: LspLanguage,
+    path: &Path,
+    wait: Duration,
+    changed: bool,
+) -> Result<(Option<Value>, Option<Value>), String> {
+    let checked = policy.check_path_allowed(path).map_err(|e| e.to_string())?;
+    let client = lsp_manager()
+        .get_or_start(lang, policy.root.clone(), policy)
+        .await?;
+    let c = client.lock().await;
+    let (uri, before) = if changed {
+        c.sync_file(lang, &checked).await?
+    } else {
+        (c.ensure_open(lang, &checked).await?, None)
+    };
+    let diag_map = c.diagnostics.clone();
+    drop(c);
+
+    let deadline = std::time::Instant::now() + wait;
+    loop {
+        if let Some(v) = diag_map.lock().get(&uri).cloned() {
+            return Ok((before, Some(v)));
+        }
+        if std::time::Instant::now() >= deadline {
+            return Ok((before, None));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Error-severity diagnostics in `after` whose message isn't among `before`'s errors
+/// (lines shift with edits, so messages are compared), as 1-based line and first line
+/// of the message.
+fn new_errors(before: Option<&Value>, after: Option<&Value>) -> Vec<(usize, String)> {
+    fn errors(v: Option<&Value>) -> Vec<(usize, String)> {
+        v.and_then(|v| v.get("diagnostics"))
+            .and_then(|d| d.as_array())
+            .into_iter()
+            .flatten()
+            .filter(|d| d.get("severity").and_then(|s| s.as_u64()) == Some(1))
+            .map(|d| {
+                let line = d
+                    .pointer("/range/start/line")
+                    .and_then(|l| l.as_u64())
+                    .unwrap_or(0) as usize;
+                let message = d
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("")
+                    .lines()
+                    .next()
+                    .unwrap_or("")
+                    .to_string();
+                (line + 1, message)
+            })
+            .collect()
+    }
+    let known: Vec<String> = errors(before).into_iter().map(|(_, m)| m).collect();
+    errors(after)
+        .into_iter()
+        .filter(|(_, m)| !known.contains(m))
+        .collect()
+}
+
 pub async fn format_locations_with_snippets(
     policy: &SandboxPolicy,
     locs: &[LspLocation],
@@ -782,3 +939,25 @@ fn truncate_single_line(s: &str, max: usize) -> String {
     out.push_str("...");
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_errors_missing_before_the_edit_are_new() {
+        let diag = |line: u64, severity: u64, message: &str| json!({"range": {"start": {"line": line, "character": 0}}, "severity": severity, "message": message});
+        let before = json!({"diagnostics": [diag(3, 1, "unresolved import `foo`")]});
+        let after = json!({"diagnostics": [
+            diag(5, 1, "unresolved import `foo`"),
+            diag(9, 1, "mismatched types\nexpected `u32`, found `&str`"),
+            diag(12, 2, "unused variable: `x`"),
+        ]});
+        assert_eq!(
+            new_errors(Some(&before), Some(&after)),
+            vec![(10, "mismatched types".to_string())]
+        );
+        assert_eq!(new_errors(None, Some(&after)).len(), 2);
+        assert!(new_errors(Some(&before), None).is_empty());
+    }
+}
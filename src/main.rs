@@ -4,7 +4,10 @@ use std::time::Duration;
 
 use color_eyre::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event, KeyEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -13,31 +16,59 @@ use ratatui::Terminal;
 use tokio::sync::mpsc;
 
 mod app;
+mod approval;
+mod bookmarks;
 mod checkpoints;
+mod clipboard;
 mod codex_oauth;
 mod config;
+mod confine;
+mod daemon;
+mod edit_check;
+mod editor;
+mod eval;
 mod events;
+mod export;
+mod github;
+mod highlight;
 mod llm;
+mod llm_debug;
+mod logging;
 mod lsp;
 mod markdown;
 mod memory;
+mod notify;
+mod processes;
+mod notebook;
+mod pty;
 mod render_store;
+mod replay;
+mod repo_map;
 mod sandbox;
+mod script;
+mod serve;
 mod semantic_search;
 mod session;
+mod shell;
+mod stats;
+mod storage;
 mod theme;
+mod tokens;
+mod tool_registry;
 mod tools;
+mod trust;
 mod types;
 mod ui;
 mod verify;
+mod workspaces;
 
 use app::App;
 use config::AppConfig;
 use events::AppEvent;
 use memory::MemoryManager;
 use sandbox::SandboxPolicy;
+use semantic_search::types::IndexStats;
 use semantic_search::{index_dir_for_workspace, SearchConfig, SemanticSearch};
-use tools::TOOL_NAMES;
 use ui::ui;
 
 use llm::LlmProvider;
@@ -77,6 +108,10 @@ async fn load_llm_credentials(
             .map(|k| (LlmProvider::OpenAI, k))
     };
 
+    let try_gemini = || -> Option<(LlmProvider, String)> {
+        llm::gemini_api_key().map(|k| (LlmProvider::Gemini, k))
+    };
+
     let try_codex = || async {
         let auth = codex_oauth::codex_chatgpt_auth().await?;
         if auth.access_token.trim().is_empty() {
@@ -98,6 +133,12 @@ async fn load_llm_credentials(
             }
             return Err("LORIKEET_PROVIDER=openai but OPENAI_API_KEY is not set".into());
         }
+        Some("gemini") | Some("google") => {
+            if let Some(v) = try_gemini() {
+                return Ok((v.0, v.1, None));
+            }
+            return Err("LORIKEET_PROVIDER=gemini but GEMINI_API_KEY is not set".into());
+        }
         Some("codex") | Some("codex_oauth") => return try_codex().await,
         _ => {}
     }
@@ -108,10 +149,13 @@ async fn load_llm_credentials(
     if let Some(v) = try_openai() {
         return Ok((v.0, v.1, None));
     }
+    if let Some(v) = try_gemini() {
+        return Ok((v.0, v.1, None));
+    }
     match try_codex().await {
         Ok(v) => Ok(v),
         Err(e) => Err(format!(
-            "No credentials found.\n\nSet OPENROUTER_API_KEY or OPENAI_API_KEY, or sign in via Codex CLI.\n\nOptional: set LORIKEET_PROVIDER=openrouter|openai|codex\n\nDetails: {}",
+            "No credentials found.\n\nSet OPENROUTER_API_KEY, OPENAI_API_KEY or GEMINI_API_KEY, or sign in with `lorikeet login`.\n\nOptional: set LORIKEET_PROVIDER=openrouter|openai|gemini|codex\n\nDetails: {}",
             e
         )),
     }
@@ -120,9 +164,22 @@ async fn load_llm_credentials(
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
+    logging::init();
 
     // Check for CLI subcommands
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    // `--read-only` is a TUI flag: `lorikeet --read-only`, `lorikeet continue --read-only`.
+    let read_only = args.iter().skip(1).any(|a| a == "--read-only");
+    args.retain(|a| a != "--read-only");
+    if read_only && args.len() > 1 && args[1] != "continue" {
+        eprintln!("--read-only applies to the interactive TUI (lorikeet [continue] --read-only)");
+        std::process::exit(1);
+    }
+    // `--debug-llm` works with every command that talks to a model.
+    if args.iter().skip(1).any(|a| a == "--debug-llm") {
+        llm_debug::enable();
+    }
+    args.retain(|a| a != "--debug-llm");
     let mut resume_override: Option<bool> = None;
     if args.len() > 1 {
         match args[1].as_str() {
@@ -133,6 +190,33 @@ async fn main() -> Result<()> {
             "index" => {
                 return run_index_command(&args[2..]).await;
             }
+            "daemon" => {
+                return run_daemon_command(&args[2..]);
+            }
+            "eval" => {
+                return run_eval_command(&args[2..]).await;
+            }
+            "serve" => {
+                return run_serve_command(&args[2..]).await;
+            }
+            "run" => {
+                return run_exec_command(&args[2..], true).await;
+            }
+            "exec" => {
+                return run_exec_command(&args[2..], false).await;
+            }
+            "theme" => {
+                return run_theme_command(&args[2..]);
+            }
+            "config" => {
+                return run_config_command(&args[2..]);
+            }
+            "login" => {
+                return run_login_command(&args[2..]).await;
+            }
+            "replay" => {
+                return run_replay_command(&args[2..]).await;
+            }
             "help" | "--help" | "-h" => {
                 print_help();
                 return Ok(());
@@ -145,7 +229,8 @@ async fn main() -> Result<()> {
         }
     }
     let workspace_root = std::env::current_dir()?;
-    let config = AppConfig::load();
+    let config = AppConfig::load_layered(&workspace_root);
+    storage::configure(&config);
     let preferred_provider = config
         .general
         .as_ref()
@@ -167,35 +252,19 @@ async fn main() -> Result<()> {
         }
     };
 
-    enable_raw_mode()?;
-    io::stdout().execute(EnterAlternateScreen)?;
-    io::stdout().execute(EnableMouseCapture)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut terminal = enter_tui()?;
 
     let (event_tx, mut event_rx) = mpsc::unbounded_channel::<AppEvent>();
+    spawn_input_reader(event_tx.clone());
 
-    let input_tx = event_tx.clone();
-    tokio::spawn(async move {
-        loop {
-            if event::poll(Duration::from_millis(50)).unwrap_or(false) {
-                match event::read() {
-                    Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
-                        let _ = input_tx.send(AppEvent::Input(key));
-                    }
-                    Ok(Event::Mouse(mouse)) => {
-                        let _ = input_tx.send(AppEvent::Mouse(mouse));
-                    }
-                    _ => {}
-                }
-            }
-        }
-    });
-
-    let sandbox_policy = std::sync::Arc::new(SandboxPolicy::from_config(
-        config.clone(),
-        workspace_root.clone(),
-        TOOL_NAMES,
-    ));
+    let sandbox_policy = std::sync::Arc::new(
+        SandboxPolicy::from_config(
+            config.clone(),
+            workspace_root.clone(),
+            &tool_registry::names(),
+        )
+        .with_read_only(read_only),
+    );
 
     let memory = std::sync::Arc::new(
         MemoryManager::init(&workspace_root)
@@ -222,6 +291,12 @@ async fn main() -> Result<()> {
             .unwrap_or(false)
     });
     app.init_session(resume);
+    if app.file_heatmap_enabled() {
+        app.file_edits_before = app.memory.file_edit_counts(200).await.into_iter().collect();
+    }
+    app.push_startup_banner();
+    app.init_trust();
+    app.start_memory_consolidation();
 
     if config
         .general
@@ -235,26 +310,120 @@ async fn main() -> Result<()> {
     }
 
     loop {
+        app.refresh_log_tail();
         terminal.draw(|frame| ui(frame, &mut app))?;
 
-        match tokio::time::timeout(Duration::from_millis(16), event_rx.recv()).await {
-            Ok(Some(event)) => app.handle_event(event),
+        let tick = app.redraw_tick();
+        match next_event(&mut event_rx, tick).await {
+            Ok(Some(event)) => {
+                app.handle_event(event);
+                drain_events(&mut app, &mut event_rx);
+            }
             Ok(None) => break,
             Err(_) => {}
         }
 
+        if app.pending_editor.is_some() {
+            app.run_pending_editor();
+            terminal.clear()?;
+        }
+
         if app.should_quit {
             break;
         }
     }
 
+    processes::kill_all();
+    app.close_session();
+    leave_tui()?;
+
+    Ok(())
+}
+
+fn enter_tui() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+    io::stdout().execute(EnableBracketedPaste)?;
+    io::stdout().execute(EnableFocusChange)?;
+    Ok(Terminal::new(CrosstermBackend::new(io::stdout()))?)
+}
+
+fn leave_tui() -> Result<()> {
     disable_raw_mode()?;
     io::stdout().execute(DisableMouseCapture)?;
+    io::stdout().execute(DisableBracketedPaste)?;
+    io::stdout().execute(DisableFocusChange)?;
     io::stdout().execute(LeaveAlternateScreen)?;
-
     Ok(())
 }
 
+/// Most queued events handled between two draws, so a flood of stream chunks can't hold
+/// off the screen (or a keypress) indefinitely.
+const MAX_EVENTS_PER_DRAW: usize = 256;
+
+/// The next event, waiting at most `timeout` (`Err` when it passes first) or, without
+/// one, for as long as it takes: an idle screen isn't redrawn at all.
+async fn next_event(
+    event_rx: &mut mpsc::UnboundedReceiver<AppEvent>,
+    timeout: Option<Duration>,
+) -> Result<Option<AppEvent>, tokio::time::error::Elapsed> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, event_rx.recv()).await,
+        None => Ok(event_rx.recv().await),
+    }
+}
+
+/// Handle the events already queued behind the one that woke the loop, so a burst of
+/// stream chunks costs one draw. Stops early for anything the loop has to act on first.
+fn drain_events(app: &mut App, event_rx: &mut mpsc::UnboundedReceiver<AppEvent>) {
+    for _ in 0..MAX_EVENTS_PER_DRAW {
+        if app.pending_editor.is_some() || app.should_quit {
+            return;
+        }
+        match event_rx.try_recv() {
+            Ok(event) => app.handle_event(event),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Forward terminal input to the app as events.
+fn spawn_input_reader(input_tx: mpsc::UnboundedSender<AppEvent>) {
+    tokio::spawn(async move {
+        loop {
+            // An external editor has the terminal; its keys aren't ours.
+            if editor::INPUT_PAUSED.load(std::sync::atomic::Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                continue;
+            }
+            if event::poll(Duration::from_millis(50)).unwrap_or(false) {
+                match event::read() {
+                    Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                        let _ = input_tx.send(AppEvent::Input(key));
+                    }
+                    Ok(Event::Mouse(mouse)) => {
+                        let _ = input_tx.send(AppEvent::Mouse(mouse));
+                    }
+                    Ok(Event::Paste(text)) => {
+                        let _ = input_tx.send(AppEvent::Paste(text));
+                    }
+                    Ok(Event::FocusGained) => {
+                        let _ = input_tx.send(AppEvent::Focus(true));
+                    }
+                    Ok(Event::FocusLost) => {
+                        let _ = input_tx.send(AppEvent::Focus(false));
+                    }
+                    Ok(Event::Resize(..)) => {
+                        let _ = input_tx.send(AppEvent::Resize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+}
+
 fn index_file_exists(workspace_root: &std::path::Path) -> bool {
     let index_dir = index_dir_for_workspace(workspace_root);
     let index_path = index_dir.join("index.bin");
@@ -269,19 +438,101 @@ fn print_help() {
     println!("USAGE:");
     println!("    lorikeet              Start a new interactive TUI session");
     println!("    lorikeet continue     Resume the latest session for this workspace");
+    println!("    lorikeet --read-only  Start the TUI unable to edit files or run mutating commands (/readonly toggles)");
     println!("    lorikeet index [DIR]  Index a directory for semantic search");
+    println!("    lorikeet daemon       Keep embedding models loaded and serve searches (also: status, stop)");
+    println!("    lorikeet eval SUITE   Run headless tasks from a suite file and report pass rates (--keep keeps temp dirs)");
+    println!("    lorikeet run PROMPT   Run one prompt without the TUI and print the reply");
+    println!("    lorikeet exec --script FILE  Run prompt/tool/verify steps headlessly, one JSON line per step (--yes approves commands that would ask)");
+    println!("                          run/exec --output json: stream every event as newline-delimited JSON");
+    println!("    lorikeet serve        Host a session for editors: JSON-RPC 2.0 over stdio (--yes approves commands that would ask)");
+    println!("    lorikeet theme check FILE  Validate a theme file and list keys it leaves at defaults");
+    println!("    lorikeet config check  Validate the user and project config files (--migrate rewrites old formats)");
+    println!("    lorikeet login        Sign in with ChatGPT for the codex provider (--no-browser prints the URL only)");
+    println!("    lorikeet replay ID    Play a recorded session back at its pace (--speed 4, --speed 0 for no waits; --no-tui prints it as text)");
     println!("    lorikeet help         Show this help message");
     println!();
+    println!("    --debug-llm           With any command: log each model request and its raw response (secrets redacted) to ~/.lorikeet/logs/llm.jsonl");
+    println!();
     println!("ENVIRONMENT:");
     println!(
         "    OPENROUTER_API_KEY    API key for OpenRouter (preferred)
     OPENAI_API_KEY         Fallback env var (if set)
+    GEMINI_API_KEY         Google AI Studio key for the gemini provider (or GOOGLE_API_KEY)
+    LORIKEET_LOG           What ~/.lorikeet/logs/lorikeet.log records, RUST_LOG syntax (default lorikeet=info; /logs shows it)
 
 NOTES:
     If installed globally, you can also store OPENROUTER_API_KEY in ~/.lorikeet/.env"
     );
 }
 
+async fn run_eval_command(args: &[String]) -> Result<()> {
+    let keep = args.iter().any(|a| a == "--keep");
+    let Some(suite_path) = args.iter().find(|a| !a.starts_with("--")).map(PathBuf::from) else {
+        eprintln!("Usage: lorikeet eval <suite.toml> [--keep]");
+        std::process::exit(1);
+    };
+    let suite = match eval::load_suite(&suite_path) {
+        Ok(s) => s,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        }
+    };
+    let suite_dir = suite_path
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+    let suite_dir = std::fs::canonicalize(&suite_dir).unwrap_or(suite_dir);
+
+    let config = AppConfig::load_layered(&std::env::current_dir()?);
+    storage::configure(&config);
+    let preferred_provider = config
+        .general
+        .as_ref()
+        .and_then(|g| g.provider.clone());
+    let (provider, api_key, codex_account_id) =
+        match load_llm_credentials(preferred_provider).await {
+            Ok(v) => v,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            }
+        };
+    let target = eval::EvalTarget {
+        provider,
+        api_key,
+        codex_account_id,
+        model: suite.model.clone(),
+    };
+
+    let mut reports = Vec::new();
+    for (i, task) in suite.tasks.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, suite.tasks.len(), task.name);
+        let workdir = std::env::temp_dir().join(format!(
+            "lorikeet-eval-{}-{}-{}",
+            std::process::id(),
+            i,
+            memory::types::unix_ts()
+        ));
+        let report = eval::run_task(&suite_dir, task, &target, &workdir).await;
+        if keep {
+            println!("    kept {}", workdir.display());
+        } else {
+            let _ = std::fs::remove_dir_all(&workdir);
+        }
+        reports.push(report);
+    }
+
+    println!();
+    println!("{}", eval::format_reports(&reports));
+    if reports.iter().all(|r| r.passed()) {
+        Ok(())
+    } else {
+        std::process::exit(1);
+    }
+}
+
 async fn run_index_command(args: &[String]) -> Result<()> {
     let dir = if args.is_empty() {
         std::env::current_dir()?
@@ -299,8 +550,10 @@ async fn run_index_command(args: &[String]) -> Result<()> {
         std::process::exit(1);
     }
 
-    let config = AppConfig::load();
-    let sandbox_policy = SandboxPolicy::from_config(config, std::env::current_dir()?, TOOL_NAMES);
+    let cwd = std::env::current_dir()?;
+    let config = AppConfig::load_layered(&cwd);
+    storage::configure(&config);
+    let sandbox_policy = SandboxPolicy::from_config(config, cwd, &tool_registry::names());
 
     let checked_dir = sandbox_policy
         .check_path_allowed(&dir)
@@ -310,27 +563,39 @@ async fn run_index_command(args: &[String]) -> Result<()> {
     println!("(This will download the embedding model on first run, ~22MB)");
     println!();
 
+    if let Some(result) = daemon::index(&checked_dir) {
+        match result {
+            Ok(stats) => print_index_stats(&stats),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     // Create semantic search and index (workspace-specific index dir)
     let cfg = SearchConfig::for_workspace(&checked_dir);
     let search = SemanticSearch::new(cfg)
         .map_err(|e| color_eyre::eyre::eyre!("Failed to initialize semantic search: {}", e))?;
 
-    match search.index_directory(&checked_dir) {
-        Ok(stats) => {
-            println!("Indexing complete!");
-            println!();
-            println!("Statistics:");
-            println!("  Chunks indexed: {}", stats.total_chunks);
-            println!("  Files indexed:  {}", stats.total_files);
-            println!("  Index size:     {} bytes", stats.index_size_bytes);
-            println!();
-            println!("Languages:");
-            for (lang, count) in &stats.languages {
-                println!("  {:?}: {} chunks", lang, count);
-            }
-            println!();
-            println!("Index stored at: ~/.lorikeet/index/");
+    // One status line on stderr, rewritten in place while embedding.
+    let last_shown = std::cell::Cell::new(0usize);
+    let progress = |p: semantic_search::types::IndexProgress| {
+        if p.files_done == last_shown.get() && p.files_done != 0 {
+            return;
         }
+        last_shown.set(p.files_done);
+        let current = p
+            .current
+            .map(|c| format!("  {}", c.display()))
+            .unwrap_or_default();
+        eprint!("\r\x1b[K{}/{} files{}", p.files_done, p.total_files, current);
+    };
+    let indexed = search.index_directory_with_progress(&checked_dir, &progress);
+    eprintln!();
+    match indexed {
+        Ok(stats) => print_index_stats(&stats),
         Err(e) => {
             eprintln!("Error indexing: {}", e);
             std::process::exit(1);
@@ -340,5 +605,463 @@ async fn run_index_command(args: &[String]) -> Result<()> {
     Ok(())
 }
 
+fn print_index_stats(stats: &IndexStats) {
+    println!("Indexing complete!");
+    println!();
+    println!("Statistics:");
+    println!("  Chunks indexed: {}", stats.total_chunks);
+    println!("  Files indexed:  {}", stats.total_files);
+    println!("  Index size:     {} bytes", stats.index_size_bytes);
+    println!();
+    println!("Languages:");
+    for (lang, count) in &stats.languages {
+        println!("  {:?}: {} chunks", lang, count);
+    }
+    println!();
+    println!("Index stored at: ~/.lorikeet/index/");
+}
+
+/// `lorikeet exec --script FILE` and `lorikeet run PROMPT`: steps run without the TUI.
+/// With `--output json`, every event is printed as it happens, followed by a `step` line
+/// per step.
+async fn run_exec_command(args: &[String], single_prompt: bool) -> Result<()> {
+    let approve = args.iter().any(|a| a == "--yes");
+    let output = args
+        .iter()
+        .position(|a| a == "--output")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str);
+    let json_events = match output {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(other) => {
+            eprintln!("Unknown output format: {} (expected json or text)", other);
+            std::process::exit(1);
+        }
+    };
+
+    let script = if single_prompt {
+        let mut words = Vec::new();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--yes" => {}
+                "--output" => {
+                    rest.next();
+                }
+                _ => words.push(arg.as_str()),
+            }
+        }
+        if words.is_empty() {
+            eprintln!("Usage: lorikeet run [--output json] [--yes] <prompt>");
+            std::process::exit(1);
+        }
+        script::Script {
+            model: None,
+            steps: vec![script::Step {
+                prompt: Some(words.join(" ")),
+                ..Default::default()
+            }],
+        }
+    } else {
+        let script_path = args
+            .iter()
+            .position(|a| a == "--script")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from);
+        let Some(script_path) = script_path else {
+            eprintln!(
+                "Usage: lorikeet exec --script <steps.yaml|json|toml> [--output json] [--yes]"
+            );
+            std::process::exit(1);
+        };
+        match script::load_script(&script_path) {
+            Ok(s) => s,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let workspace_root = std::env::current_dir()?;
+    let config = AppConfig::load_layered(&workspace_root);
+    storage::configure(&config);
+    // Tool and verify steps don't talk to a model, so they run without credentials.
+    let (provider, api_key, codex_account_id) = if script.has_prompts() {
+        let preferred_provider = config
+            .general
+            .as_ref()
+            .and_then(|g| g.provider.clone());
+        match load_llm_credentials(preferred_provider).await {
+            Ok(v) => v,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        (LlmProvider::OpenRouter, String::new(), None)
+    };
+    let target = eval::EvalTarget {
+        provider,
+        api_key,
+        codex_account_id,
+        model: script.model.clone(),
+    };
+    let mut runner = match script::ScriptRunner::new(&workspace_root, &target, approve).await {
+        Ok(r) => r,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        }
+    };
+    if json_events {
+        runner.observe(|event| {
+            if let Some(line) = event.to_json() {
+                println!("{}", line);
+            }
+        });
+    }
+    let ok = script::run_script(&script, &mut runner, |report| {
+        if json_events {
+            let mut line = serde_json::to_value(report).unwrap_or_default();
+            line["type"] = "step".into();
+            println!("{}", line);
+        } else if single_prompt {
+            match &report.error {
+                None => println!("{}", report.output.as_deref().unwrap_or("")),
+                Some(e) => eprintln!("{}", e),
+            }
+        } else {
+            println!("{}", serde_json::to_string(report).unwrap_or_default());
+        }
+    })
+    .await;
+    processes::kill_all();
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_serve_command(args: &[String]) -> Result<()> {
+    let approve = args.iter().any(|a| a == "--yes");
+    let workspace_root = std::env::current_dir()?;
+    let config = AppConfig::load_layered(&workspace_root);
+    storage::configure(&config);
+    let preferred_provider = config
+        .general
+        .as_ref()
+        .and_then(|g| g.provider.clone());
+    // Without credentials, tool and verify requests still work; prompts report the error.
+    let (provider, api_key, codex_account_id) =
+        match load_llm_credentials(preferred_provider).await {
+            Ok(v) => v,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                (LlmProvider::OpenRouter, String::new(), None)
+            }
+        };
+    let target = eval::EvalTarget {
+        provider,
+        api_key,
+        codex_account_id,
+        model: None,
+    };
+    let runner = script::ScriptRunner::new(&workspace_root, &target, approve)
+        .await
+        .map_err(|e| color_eyre::eyre::eyre!(e))?;
+    let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    let served = serve::serve(runner, stdin).await;
+    processes::kill_all();
+    served?;
+    Ok(())
+}
+
+/// `lorikeet replay <session-id>`: a recorded session played back at its original pace,
+/// in a TUI that sends nothing to the model, or with `--no-tui` as annotated text.
+async fn run_replay_command(args: &[String]) -> Result<()> {
+    let no_tui = args.iter().any(|a| a == "--no-tui");
+    let speed_arg = args.iter().position(|a| a == "--speed");
+    let speed = match speed_arg.map(|i| args.get(i + 1).and_then(|s| s.parse::<f64>().ok())) {
+        None => 1.0,
+        Some(Some(speed)) if speed >= 0.0 => speed,
+        Some(_) => {
+            eprintln!("--speed takes a number: 2 plays twice as fast, 0 skips the waits");
+            std::process::exit(1);
+        }
+    };
+    let session_id = args
+        .iter()
+        .enumerate()
+        .find(|(i, a)| !a.starts_with("--") && speed_arg.map(|s| s + 1) != Some(*i))
+        .map(|(_, a)| a.trim_end_matches(".jsonl.zst").trim_end_matches(".jsonl"));
+    let Some(session_id) = session_id else {
+        eprintln!("Usage: lorikeet replay <session-id> [--speed N] [--no-tui]");
+        std::process::exit(1);
+    };
+
+    let workspace_root = std::env::current_dir()?;
+    let config = AppConfig::load_layered(&workspace_root);
+    storage::configure(&config);
+    let store = session::SessionStore::new(&workspace_root, session_id.to_string())?;
+    let events = store.load_events().unwrap_or_default();
+    if events.is_empty() {
+        eprintln!("No session {} for this workspace.", session_id);
+        let sessions = session::list_sessions(&workspace_root).unwrap_or_default();
+        if !sessions.is_empty() {
+            eprintln!("Recent sessions:");
+        }
+        for info in sessions.iter().take(5) {
+            let title = info.title().unwrap_or_default();
+            eprintln!("  {}  {}", info.session_id, title);
+        }
+        std::process::exit(1);
+    }
+    let cues = replay::timeline(
+        &events,
+        replay::Pace {
+            speed,
+            max_pause: replay::MAX_PAUSE,
+        },
+    );
+    let started = tokio::time::Instant::now();
+
+    if no_tui {
+        let start_ts = events.first().map(|e| e.ts()).unwrap_or(0);
+        for (at, cue) in cues {
+            tokio::time::sleep_until(started + at).await;
+            if let Some(text) = replay::describe(&events, cue, start_ts) {
+                println!("{}", text);
+            }
+        }
+        return Ok(());
+    }
+
+    let mut terminal = enter_tui()?;
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<AppEvent>();
+    spawn_input_reader(event_tx.clone());
+    let sandbox_policy = std::sync::Arc::new(SandboxPolicy::from_config(
+        config.clone(),
+        workspace_root.clone(),
+        &tool_registry::names(),
+    ));
+    let memory = std::sync::Arc::new(
+        MemoryManager::init(&workspace_root)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!(e.to_string()))?,
+    );
+    let mut app = App::new(
+        event_tx,
+        LlmProvider::OpenRouter,
+        String::new(),
+        None,
+        sandbox_policy,
+        config,
+        workspace_root,
+        memory,
+    );
+    let outputs_dir = store.tool_outputs_dir();
+    app.replaying = Some(format!(" REPLAY {} · {}x ", session_id, speed));
+
+    let mut next = 0;
+    loop {
+        while let Some(&(at, cue)) = cues.get(next) {
+            if started.elapsed() < at {
+                break;
+            }
+            app.replay_cue(&events, cue, &outputs_dir);
+            next += 1;
+            if next == cues.len() {
+                app.replaying = Some(format!(" REPLAY {} · finished ", session_id));
+            }
+        }
+
+        terminal.draw(|frame| ui(frame, &mut app))?;
+
+        // Wake for the next cue as well as for input and animation.
+        let next_cue = cues
+            .get(next)
+            .map(|&(at, _)| at.saturating_sub(started.elapsed()));
+        let tick = app.redraw_tick();
+        let wait = match (next_cue, tick) {
+            (Some(cue), Some(tick)) => Some(cue.min(tick)),
+            (cue, tick) => cue.or(tick),
+        };
+        match next_event(&mut event_rx, wait).await {
+            Ok(Some(event)) => {
+                app.handle_event(event);
+                drain_events(&mut app, &mut event_rx);
+            }
+            Ok(None) => break,
+            Err(_) => {}
+        }
+
+        if app.pending_editor.is_some() {
+            app.run_pending_editor();
+            terminal.clear()?;
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    leave_tui()?;
+    Ok(())
+}
+
+fn run_theme_command(args: &[String]) -> Result<()> {
+    let (Some("check"), Some(file)) = (args.first().map(String::as_str), args.get(1)) else {
+        eprintln!("Usage: lorikeet theme check <file.json>");
+        std::process::exit(1);
+    };
+    let check = match theme::check_theme_file(std::path::Path::new(file)) {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for (key, value) in &check.invalid {
+        println!("invalid: {} = {} (expected \"#RRGGBB\", 0-255, \"none\" or a $def)", key, value);
+    }
+    for key in &check.unknown {
+        println!("unknown: {}", key);
+    }
+    if !check.missing.is_empty() {
+        println!("missing ({} keys use defaults):", check.missing.len());
+        for key in &check.missing {
+            println!("  {}", key);
+        }
+    }
+    if !check.is_ok() {
+        std::process::exit(1);
+    }
+    println!("{}: ok", file);
+    Ok(())
+}
+
+async fn run_login_command(args: &[String]) -> Result<()> {
+    let request = match codex_oauth::start_login().await {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Sign in with ChatGPT at:\n\n    {}\n", request.url);
+    if !args.iter().any(|a| a == "--no-browser") {
+        codex_oauth::open_browser(&request.url);
+    }
+    println!("Waiting for the browser...");
+    match request.complete().await {
+        Ok(auth) => {
+            match auth.account_id {
+                Some(account) => println!("Signed in (account {}).", account),
+                None => println!("Signed in."),
+            }
+            println!("Tokens saved to ~/.lorikeet/codex_auth.json; set provider = \"codex\" to use them.");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_config_command(args: &[String]) -> Result<()> {
+    if args.first().map(String::as_str) != Some("check") {
+        eprintln!("Usage: lorikeet config check [--migrate]");
+        std::process::exit(1);
+    }
+    let apply = args.iter().any(|a| a == "--migrate");
+    let workspace_root = std::env::current_dir()?;
+    let checks = config::check_all(&workspace_root);
+    if checks.is_empty() {
+        println!("No config files found");
+        return Ok(());
+    }
+    let mut ok = true;
+    for (path, check) in &checks {
+        for issue in &check.issues {
+            match (issue.line, issue.column) {
+                (Some(line), Some(column)) => {
+                    println!("{}:{}:{}: {}", path.display(), line, column, issue.message)
+                }
+                _ => println!("{}: {}", path.display(), issue.message),
+            }
+        }
+        ok &= check.is_ok();
+        if check.migrations.is_empty() {
+            if check.is_ok() {
+                println!("{}: ok", path.display());
+            }
+            continue;
+        }
+        if apply && check.is_ok() {
+            for change in config::migrate_file(path)? {
+                println!("{}: migrated {}", path.display(), change);
+            }
+        } else {
+            for change in &check.migrations {
+                println!("{}: needs migration: {}", path.display(), change);
+            }
+            if !apply {
+                println!("  (run `lorikeet config check --migrate` to rewrite the file)");
+            }
+        }
+    }
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_daemon_command(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        None | Some("start") => {
+            let socket = daemon::socket_path()
+                .ok_or_else(|| color_eyre::eyre::eyre!("No home directory for the socket"))?;
+            println!("Lorikeet daemon listening on {}", socket.display());
+            println!("Searches and indexing from other lorikeet processes now go through it.");
+            daemon::serve(&socket).map_err(|e| color_eyre::eyre::eyre!("daemon: {}", e))?;
+        }
+        Some("status") => {
+            match daemon::request(&daemon::Request::Ping, Some(daemon::CONTROL_TIMEOUT)) {
+                Some(Ok(daemon::Response::Pong { pid, workspaces })) => {
+                    println!("Daemon running (pid {})", pid);
+                    for w in workspaces {
+                        println!("  loaded: {}", w);
+                    }
+                }
+                Some(Ok(_)) => println!("Daemon running"),
+                Some(Err(e)) => {
+                    eprintln!("Daemon not responding: {}", e);
+                    std::process::exit(1);
+                }
+                None => println!("Daemon not running"),
+            }
+        }
+        Some("stop") => {
+            match daemon::request(&daemon::Request::Shutdown, Some(daemon::CONTROL_TIMEOUT)) {
+                Some(Ok(_)) => println!("Daemon stopped"),
+                Some(Err(e)) => {
+                    eprintln!("Error stopping daemon: {}", e);
+                    std::process::exit(1);
+                }
+                None => println!("Daemon not running"),
+            }
+        }
+        Some(other) => {
+            eprintln!("Unknown daemon command: {} (expected start, status or stop)", other);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod verify_tests;
@@ -1,6 +1,7 @@
 use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
 use ratatui::prelude::*;
 
+use crate::highlight;
 use crate::theme::{MarkdownTheme, SyntaxTheme};
 
 #[derive(Default, Clone, Copy)]
@@ -60,154 +61,17 @@ fn style_for_state(theme: MarkdownTheme, state: StyleState) -> Style {
     style
 }
 
-// Syntax highlighting colors for code blocks
-fn syntax_color(token: &str, lang: &str, syn: SyntaxTheme) -> Color {
-    let keywords = [
-        "fn", "let", "mut", "const", "if", "else", "match", "for", "while", "loop", "return",
-        "break", "continue", "struct", "enum", "impl", "trait", "pub", "use", "mod", "async",
-        "await", "self", "Self", "true", "false", "None", "Some", "Ok", "Err", "function", "var",
-        "const", "class", "import", "export", "from", "default", "new", "def", "class", "import",
-        "from", "as", "try", "except", "finally", "with", "lambda", "int", "str", "bool", "float",
-        "void", "char", "String", "Vec", "Option", "Result",
-    ];
-
-    let types = [
-        "i32", "i64", "u32", "u64", "f32", "f64", "usize", "isize", "bool", "char", "String",
-        "str", "Vec", "Option", "Result", "Box", "Rc", "Arc", "HashMap", "HashSet",
-    ];
-
-    if token.starts_with("//") || (token.starts_with('#') && lang != "markdown") {
-        syn.comment
-    } else if keywords.contains(&token) {
-        syn.keyword
-    } else if types.contains(&token) {
-        syn.ty
-    } else if token.starts_with('"') || token.starts_with('\'') || token.starts_with('`') {
-        syn.string
-    } else if token.chars().all(|c| c.is_ascii_digit() || c == '.') && !token.is_empty() {
-        syn.number
-    } else {
-        syn.ident
-    }
-}
-
-fn highlight_code(
-    code: &str,
-    lang: &str,
-    theme: MarkdownTheme,
-    syn: SyntaxTheme,
-) -> Vec<Span<'static>> {
-    let mut spans = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut string_char = '"';
-    let mut in_comment = false;
-
-    for c in code.chars() {
-        if in_comment {
-            current.push(c);
-            continue;
-        }
-
-        if !in_string && (c == '"' || c == '\'' || c == '`') {
-            if !current.is_empty() {
-                let color = syntax_color(&current, lang, syn);
-                spans.push(Span::styled(
-                    std::mem::take(&mut current),
-                    {
-                        let mut s = Style::default().fg(color);
-                        if theme.code_bg != Color::Reset {
-                            s = s.bg(theme.code_bg);
-                        }
-                        s
-                    },
-                ));
-            }
-            in_string = true;
-            string_char = c;
-            current.push(c);
-        } else if in_string && c == string_char {
-            current.push(c);
-            spans.push(Span::styled(
-                std::mem::take(&mut current),
-                {
-                    let mut s = Style::default().fg(Color::Green);
-                    if theme.code_bg != Color::Reset {
-                        s = s.bg(theme.code_bg);
-                    }
-                    s
-                },
-            ));
-            in_string = false;
-        } else if in_string {
-            current.push(c);
-        } else if c == '/' && current.ends_with('/') {
-            current.push(c);
-            in_comment = true;
-        } else if c.is_alphanumeric() || c == '_' {
-            current.push(c);
-        } else {
-            if !current.is_empty() {
-                let color = syntax_color(&current, lang, syn);
-                spans.push(Span::styled(
-                    std::mem::take(&mut current),
-                    {
-                        let mut s = Style::default().fg(color);
-                        if theme.code_bg != Color::Reset {
-                            s = s.bg(theme.code_bg);
-                        }
-                        s
-                    },
-                ));
+fn segments_to_spans(segments: &[(String, Color)], theme: MarkdownTheme) -> Vec<Span<'static>> {
+    segments
+        .iter()
+        .map(|(text, color)| {
+            let mut s = Style::default().fg(*color);
+            if theme.code_bg != Color::Reset {
+                s = s.bg(theme.code_bg);
             }
-            spans.push(Span::styled(
-                c.to_string(),
-                // Avoid hard-coded white, which becomes unreadable on light terminals.
-                {
-                    let mut s = Style::default().fg(syn.punct);
-                    if theme.code_bg != Color::Reset {
-                        s = s.bg(theme.code_bg);
-                    }
-                    s
-                },
-            ));
-        }
-    }
-
-    if !current.is_empty() {
-        let color = if in_comment {
-            syn.comment
-        } else if in_string {
-            syn.string
-        } else {
-            syntax_color(&current, lang, syn)
-        };
-        spans.push(Span::styled(
-            current,
-            {
-                let mut s = Style::default().fg(color);
-                if theme.code_bg != Color::Reset {
-                    s = s.bg(theme.code_bg);
-                }
-                s
-            },
-        ));
-    }
-
-    if spans.is_empty() {
-        spans.push(Span::styled(
-            code.to_string(),
-            {
-                let mut s = Style::default().fg(syn.ident);
-                if theme.code_bg != Color::Reset {
-                    s = s.bg(theme.code_bg);
-                }
-                s
-            },
-        ));
-    }
-
-    spans
+            Span::styled(text.clone(), s)
+        })
+        .collect()
 }
 
 pub fn render(
@@ -433,7 +297,11 @@ pub fn render(
                 TagEnd::Strikethrough => style_state.strikethrough = false,
                 TagEnd::Link => style_state.link = false,
                 TagEnd::CodeBlock => {
-                    for code_line in &code_block_content {
+                    // Highlight the block as a whole so multi-line constructs (block
+                    // comments, raw strings) are coloured correctly.
+                    let highlighted =
+                        highlight::highlight_lines(&code_block_content.join("\n"), &code_lang, syn);
+                    for (idx, code_line) in code_block_content.iter().enumerate() {
                         let mut spans =
                             vec![Span::styled(
                                 "  ",
@@ -443,7 +311,14 @@ pub fn render(
                                     Style::default().bg(theme.code_bg)
                                 },
                             )];
-                        spans.extend(highlight_code(code_line, &code_lang, theme, syn));
+                        match highlighted.as_ref().and_then(|h| h.get(idx)) {
+                            Some(segments) => spans.extend(segments_to_spans(segments, theme)),
+                            // No grammar for this language: plain text, not a guess.
+                            None => spans.extend(segments_to_spans(
+                                &[(code_line.clone(), syn.ident)],
+                                theme,
+                            )),
+                        }
                         let current_len: usize =
                             spans.iter().map(|s| s.content.chars().count()).sum();
                         if current_len < width.saturating_sub(2) {
@@ -742,8 +617,7 @@ fn wrap_spans(
 mod tests {
     use super::*;
 
-    #[test]
-    fn markdown_render_respects_heading_color() {
+    fn themes() -> (MarkdownTheme, SyntaxTheme) {
         let md = MarkdownTheme {
             text: Color::Reset,
             bold: Color::Blue,
@@ -772,7 +646,12 @@ mod tests {
             punct: Color::Reset,
             ident: Color::Reset,
         };
+        (md, syn)
+    }
 
+    #[test]
+    fn markdown_render_respects_heading_color() {
+        let (md, syn) = themes();
         let lines = render("# Title", md, syn, 80);
         // First non-empty line should be the heading.
         let first = lines.iter().find(|l| {
@@ -792,4 +671,19 @@ mod tests {
             "expected heading fg Color::Red"
         );
     }
+
+    #[test]
+    fn code_in_languages_without_a_grammar_is_plain_text() {
+        let (md, syn) = themes();
+        let lines = render("```brainfuck\nif \"x\" 42\n```", md, syn, 80);
+        let code = lines
+            .iter()
+            .find(|l| l.spans.iter().any(|s| s.content.contains("42")))
+            .expect("code line rendered");
+        assert!(code
+            .spans
+            .iter()
+            .filter(|s| !s.content.trim().is_empty())
+            .all(|s| s.style.fg == Some(syn.ident)));
+    }
 }
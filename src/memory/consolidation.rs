@@ -0,0 +1,236 @@
+//! Periodic upkeep of a memory store: near-duplicates are merged, memories nobody has
+//! used for a while lose importance, and the ones that sink too low are archived. The
+//! planning here is pure; `MemoryManager::consolidate` applies the result.
+
+use crate::memory::store::cosine_similarity;
+use crate::memory::types::Memory;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Each pass multiplies a stale memory's importance by this.
+const DECAY_FACTOR: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConsolidationPolicy {
+    /// Memories unused for this many days start to decay (`[memory] decay_after_days`)
+    pub decay_after_days: u64,
+    /// Stale memories whose importance falls below this are archived
+    /// (`[memory] archive_below`)
+    pub archive_below: f32,
+    /// Same-type memories at least this similar (cosine) are merged
+    pub merge_similarity: f32,
+}
+
+impl Default for ConsolidationPolicy {
+    fn default() -> Self {
+        Self {
+            decay_after_days: 30,
+            archive_below: 0.2,
+            merge_similarity: 0.92,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ConsolidationAction {
+    /// Store a surviving memory's new tags, importance, use count and last use
+    Update { memory: Memory, decayed: bool },
+    /// Move a memory to the archive; `merged_into` is set when it was a near-duplicate
+    Archive {
+        id: String,
+        merged_into: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConsolidationReport {
+    pub merged: usize,
+    pub decayed: usize,
+    pub archived: usize,
+}
+
+impl ConsolidationReport {
+    pub fn add(&mut self, action: &ConsolidationAction) {
+        match action {
+            ConsolidationAction::Update { decayed, .. } => self.decayed += *decayed as usize,
+            ConsolidationAction::Archive {
+                merged_into: Some(_),
+                ..
+            } => self.merged += 1,
+            ConsolidationAction::Archive { .. } => self.archived += 1,
+        }
+    }
+}
+
+/// Decide what one pass does to `memories` (with their embeddings, when stored).
+pub fn plan(
+    memories: &[(Memory, Option<Vec<f32>>)],
+    now: i64,
+    policy: &ConsolidationPolicy,
+) -> Vec<ConsolidationAction> {
+    let n = memories.len();
+    let mut survivors: Vec<Memory> = memories.iter().map(|(m, _)| m.clone()).collect();
+    let mut merged_into: Vec<Option<usize>> = vec![None; n];
+
+    // Fold each near-duplicate into the more valuable memory of the pair.
+    for i in 0..n {
+        if merged_into[i].is_some() {
+            continue;
+        }
+        for j in (i + 1)..n {
+            if merged_into[j].is_some() || survivors[i].memory_type != survivors[j].memory_type {
+                continue;
+            }
+            let (Some(a), Some(b)) = (&memories[i].1, &memories[j].1) else {
+                continue;
+            };
+            if cosine_similarity(a, b) < policy.merge_similarity {
+                continue;
+            }
+            let (keep, drop) = if outranks(&survivors[j], &survivors[i]) {
+                (j, i)
+            } else {
+                (i, j)
+            };
+            let absorbed = survivors[drop].clone();
+            absorb(&mut survivors[keep], &absorbed);
+            merged_into[drop] = Some(keep);
+            if drop == i {
+                break;
+            }
+        }
+    }
+
+    let stale_after = policy.decay_after_days as i64 * DAY_SECS;
+    let mut actions = Vec::new();
+    for i in 0..n {
+        if let Some(mut target) = merged_into[i] {
+            // A survivor may itself have been merged later on.
+            while let Some(next) = merged_into[target] {
+                target = next;
+            }
+            actions.push(ConsolidationAction::Archive {
+                id: survivors[i].id.clone(),
+                merged_into: Some(survivors[target].id.clone()),
+            });
+            continue;
+        }
+        let original = &memories[i].0;
+        let mut memory = survivors[i].clone();
+        let changed = memory.tags != original.tags
+            || memory.use_count != original.use_count
+            || memory.last_used != original.last_used
+            || memory.importance != original.importance;
+        let stale = now - memory.last_used >= stale_after;
+        if stale {
+            memory.importance *= DECAY_FACTOR;
+            if memory.importance < policy.archive_below {
+                actions.push(ConsolidationAction::Archive {
+                    id: memory.id,
+                    merged_into: None,
+                });
+                continue;
+            }
+        }
+        if stale || changed {
+            actions.push(ConsolidationAction::Update {
+                memory,
+                decayed: stale,
+            });
+        }
+    }
+    actions
+}
+
+fn absorb(keep: &mut Memory, other: &Memory) {
+    for tag in &other.tags {
+        if !keep.tags.contains(tag) {
+            keep.tags.push(tag.clone());
+        }
+    }
+    keep.importance = keep.importance.max(other.importance);
+    keep.use_count += other.use_count;
+    keep.last_used = keep.last_used.max(other.last_used);
+}
+
+/// Whether `b` should survive over its duplicate `a`: more important, then more used.
+fn outranks(b: &Memory, a: &Memory) -> bool {
+    (b.importance, b.use_count) > (a.importance, a.use_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::types::{MemoryScope, MemoryType};
+
+    const NOW: i64 = 1_000 * DAY_SECS;
+
+    fn memory(id: &str, importance: f32, idle_days: i64, tags: &[&str]) -> Memory {
+        let mut m = Memory::new(
+            id.into(),
+            None,
+            MemoryScope::Project,
+            MemoryType::Preference,
+            format!("memory {}", id),
+        );
+        m.importance = importance;
+        m.last_used = NOW - idle_days * DAY_SECS;
+        m.tags = tags.iter().map(|t| t.to_string()).collect();
+        m
+    }
+
+    #[test]
+    fn near_duplicates_fold_into_the_more_important() {
+        let memories = vec![
+            (memory("a", 0.5, 1, &["pnpm"]), Some(vec![1.0, 0.0])),
+            (memory("b", 0.9, 2, &["js"]), Some(vec![0.99, 0.05])),
+            (memory("c", 0.7, 1, &[]), Some(vec![0.0, 1.0])),
+        ];
+        let actions = plan(&memories, NOW, &ConsolidationPolicy::default());
+
+        let mut report = ConsolidationReport::default();
+        actions.iter().for_each(|a| report.add(a));
+        assert_eq!(
+            report,
+            ConsolidationReport {
+                merged: 1,
+                decayed: 0,
+                archived: 0
+            }
+        );
+        assert!(actions.iter().any(|a| matches!(
+            a,
+            ConsolidationAction::Archive { id, merged_into: Some(into) } if id == "a" && into == "b"
+        )));
+        let kept = actions
+            .iter()
+            .find_map(|a| match a {
+                ConsolidationAction::Update { memory, .. } => Some(memory),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(kept.id, "b");
+        assert_eq!(kept.tags, vec!["js".to_string(), "pnpm".to_string()]);
+        assert_eq!(kept.last_used, NOW - DAY_SECS);
+    }
+
+    #[test]
+    fn stale_memories_decay_then_get_archived() {
+        let memories = vec![
+            (memory("fresh", 0.8, 5, &[]), None),
+            (memory("stale", 0.8, 40, &[]), None),
+            (memory("faded", 0.21, 90, &[]), None),
+        ];
+        let actions = plan(&memories, NOW, &ConsolidationPolicy::default());
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(
+            &actions[0],
+            ConsolidationAction::Update { memory, decayed: true }
+                if memory.id == "stale" && (memory.importance - 0.72).abs() < 1e-6
+        ));
+        assert!(matches!(
+            &actions[1],
+            ConsolidationAction::Archive { id, merged_into: None } if id == "faded"
+        ));
+    }
+}
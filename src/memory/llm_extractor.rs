@@ -73,6 +73,7 @@ pub async fn extract_memories_from_turn(
             tool_calls: None,
             tool_call_id: None,
             name: None,
+            cache: false,
         },
         ChatMessage {
             role: "user".into(),
@@ -83,6 +84,7 @@ pub async fn extract_memories_from_turn(
             tool_calls: None,
             tool_call_id: None,
             name: None,
+            cache: false,
         },
     ];
 
@@ -4,14 +4,23 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::memory::consolidation::{
+    self, ConsolidationAction, ConsolidationPolicy, ConsolidationReport,
+};
 use crate::memory::redaction::Redactor;
-use crate::memory::store::MemoryStore;
+use crate::memory::store::{normalize_content, MemoryStore};
 use crate::memory::types::{
-    default_importance, Memory, MemoryScope, MemorySource, MemoryType, ScoredMemory,
+    default_importance, unix_ts, Memory, MemoryScope, MemorySource, MemoryType, ScoredMemory,
 };
+use crate::semantic_search::embedder::Embedder;
+
+/// A store is consolidated at most once per this many seconds (decay is per pass).
+const CONSOLIDATE_EVERY_SECS: i64 = 24 * 60 * 60;
 
 pub struct MemoryManager {
     store: Arc<MemoryStore>,
+    /// Cross-project memories (`~/.lorikeet/memory`); `None` without a home directory.
+    global: Option<Arc<MemoryStore>>,
     redactor: Arc<Redactor>,
     project_root: PathBuf,
     // Rate limiting / dedupe
@@ -21,9 +30,28 @@ pub struct MemoryManager {
 
 impl MemoryManager {
     pub async fn init(project_root: &Path) -> anyhow::Result<Self> {
-        let store = Arc::new(MemoryStore::init(project_root).await?);
+        Self::init_with_global(project_root, global_memory_dir().as_deref()).await
+    }
+
+    /// Like [`MemoryManager::init`], with the global store in `global_dir` (or none).
+    pub async fn init_with_global(
+        project_root: &Path,
+        global_dir: Option<&Path>,
+    ) -> anyhow::Result<Self> {
+        // Best-effort embedding init. If model can't load, we fall back to keyword search.
+        let embedder = Embedder::new().ok().map(Arc::new);
+        let store = Arc::new(MemoryStore::init(project_root, embedder.clone()).await?);
+        // A broken global store shouldn't take project memory down with it.
+        let global = match global_dir {
+            Some(dir) => MemoryStore::init_global(dir, embedder)
+                .await
+                .ok()
+                .map(Arc::new),
+            None => None,
+        };
         Ok(Self {
             store,
+            global,
             redactor: Arc::new(Redactor::new()),
             project_root: project_root.to_path_buf(),
             last_failure: Mutex::new(None),
@@ -54,13 +82,22 @@ impl MemoryManager {
         confidence: Option<f32>,
         importance: Option<f32>,
     ) -> anyhow::Result<Memory> {
+        let content = self.redactor.redact(content);
+        if let Some(merged) = self
+            .merge_duplicate(memory_type, &content, &tags, importance, scope)
+            .await?
+        {
+            return Ok(merged);
+        }
+
+        let store = self.store_for(scope);
         let id = Uuid::new_v4().to_string();
         let mut memory = Memory::new(
             id,
-            Some(self.store.project_id().to_string()),
+            Some(store.project_id().to_string()),
             scope,
             memory_type,
-            self.redactor.redact(content),
+            content,
         );
         memory.why = why.map(|s| self.redactor.redact(s));
         memory.context = context.map(|s| self.redactor.redact(s));
@@ -75,33 +112,213 @@ impl MemoryManager {
         if let Some(p) = source_file {
             memory.source_file = Some(p.to_path_buf());
         }
-        self.store.insert(&memory).await?;
+        store.insert(&memory).await?;
         Ok(memory)
     }
 
+    /// When the same fact is already stored, fold the new tags and importance into it
+    /// instead of adding a copy. A global copy covers every project, so it wins; saving
+    /// globally something this project already has moves it to the global store.
+    async fn merge_duplicate(
+        &self,
+        memory_type: MemoryType,
+        content: &str,
+        tags: &[String],
+        importance: Option<f32>,
+        scope: MemoryScope,
+    ) -> anyhow::Result<Option<Memory>> {
+        let importance = importance.map(|i| i.clamp(0.0, 1.0));
+        let merge = |mut existing: Memory| {
+            for tag in tags {
+                if !existing.tags.contains(tag) {
+                    existing.tags.push(tag.clone());
+                }
+            }
+            if let Some(i) = importance {
+                existing.importance = existing.importance.max(i);
+            }
+            existing
+        };
+
+        if let Some(global) = &self.global {
+            if let Some(existing) = global.find_duplicate(memory_type, content).await? {
+                let merged = merge(existing);
+                global
+                    .update(&merged.id, &merged.content, &merged.tags, merged.importance)
+                    .await?;
+                return Ok(Some(merged));
+            }
+        }
+        let Some(existing) = self.store.find_duplicate(memory_type, content).await? else {
+            return Ok(None);
+        };
+        let mut merged = merge(existing);
+        match (&self.global, scope) {
+            (Some(global), MemoryScope::Global) => {
+                merged.scope = MemoryScope::Global;
+                merged.project_id = Some(global.project_id().to_string());
+                global.insert(&merged).await?;
+                self.store.delete(&merged.id).await?;
+            }
+            _ => {
+                self.store
+                    .update(&merged.id, &merged.content, &merged.tags, merged.importance)
+                    .await?;
+            }
+        }
+        Ok(Some(merged))
+    }
+
+    fn store_for(&self, scope: MemoryScope) -> &MemoryStore {
+        match (scope, &self.global) {
+            (MemoryScope::Global, Some(global)) => global,
+            _ => &self.store,
+        }
+    }
+
+    /// Project store first, then the global one (if present).
+    fn stores(&self) -> impl Iterator<Item = &MemoryStore> {
+        std::iter::once(self.store.as_ref()).chain(self.global.as_deref())
+    }
+
     pub async fn recall(
         &self,
         query: &str,
         limit: usize,
         type_filter: Option<Vec<MemoryType>>,
     ) -> anyhow::Result<Vec<ScoredMemory>> {
-        let results = self.store.search(query, limit, type_filter).await?;
+        self.recall_scopes(
+            query,
+            limit,
+            type_filter,
+            &[MemoryScope::Project, MemoryScope::Global],
+        )
+        .await
+    }
+
+    /// Search the stores for `scopes`, keeping the best-scoring copy of any fact stored
+    /// in both.
+    async fn recall_scopes(
+        &self,
+        query: &str,
+        limit: usize,
+        type_filter: Option<Vec<MemoryType>>,
+        scopes: &[MemoryScope],
+    ) -> anyhow::Result<Vec<ScoredMemory>> {
+        let mut results: Vec<ScoredMemory> = Vec::new();
+        if scopes.contains(&MemoryScope::Project) {
+            results.extend(self.store.search(query, limit, type_filter.clone()).await?);
+        }
+        if let (Some(global), true) = (&self.global, scopes.contains(&MemoryScope::Global)) {
+            results.extend(global.search(query, limit, type_filter).await?);
+        }
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut seen = std::collections::HashSet::new();
+        results.retain(|sm| seen.insert(normalize_content(&sm.memory.content)));
+        results.truncate(limit);
+
         let ids: Vec<String> = results.iter().map(|sm| sm.memory.id.clone()).collect();
         // Best-effort reinforcement: bump recency/frequency for surfaced memories.
-        let _ = self.store.mark_used(&ids).await;
+        for store in self.stores() {
+            let _ = store.mark_used(&ids).await;
+        }
         Ok(results)
     }
 
+    /// Project and global memories, most important first.
     pub async fn list(
         &self,
         limit: usize,
         type_filter: Option<MemoryType>,
     ) -> anyhow::Result<Vec<Memory>> {
-        self.store.list(limit, type_filter).await
+        let mut out = Vec::new();
+        for store in self.stores() {
+            out.extend(store.list(limit, type_filter).await?);
+        }
+        out.sort_by(|a, b| {
+            b.importance
+                .partial_cmp(&a.importance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.last_used.cmp(&a.last_used))
+        });
+        out.truncate(limit);
+        Ok(out)
+    }
+
+    /// Edit a memory in place (the /memory browser). Content is redacted like new memories.
+    pub async fn update(
+        &self,
+        id: &str,
+        content: &str,
+        tags: Vec<String>,
+        importance: f32,
+    ) -> anyhow::Result<bool> {
+        let content = self.redactor.redact(content);
+        for store in self.stores() {
+            if store
+                .update(id, &content, &tags, importance.clamp(0.0, 1.0))
+                .await?
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     pub async fn forget(&self, id: &str) -> anyhow::Result<bool> {
-        self.store.delete(id).await
+        for store in self.stores() {
+            if store.delete(id).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Merge near-duplicates, decay memories unused for a while and archive the ones
+    /// that sank too low, in each store not consolidated within the last day.
+    pub async fn consolidate(
+        &self,
+        policy: &ConsolidationPolicy,
+    ) -> anyhow::Result<ConsolidationReport> {
+        let now = unix_ts();
+        let mut report = ConsolidationReport::default();
+        for store in self.stores() {
+            if store
+                .last_consolidated()
+                .await
+                .is_some_and(|t| now - t < CONSOLIDATE_EVERY_SECS)
+            {
+                continue;
+            }
+            let memories = store.all_with_embeddings().await?;
+            for action in consolidation::plan(&memories, now, policy) {
+                match &action {
+                    ConsolidationAction::Update { memory, .. } => {
+                        store.save_consolidated(memory).await?
+                    }
+                    ConsolidationAction::Archive { id, .. } => {
+                        store.archive(id).await?;
+                    }
+                }
+                report.add(&action);
+            }
+            store.set_last_consolidated(now).await?;
+        }
+        Ok(report)
+    }
+
+    pub async fn record_file_edits(&self, paths: &[String]) {
+        if let Err(e) = self.store.record_file_edits(paths).await {
+            tracing::warn!(error = %e, "file edit counts not recorded");
+        }
+    }
+
+    pub async fn file_edit_counts(&self, limit: usize) -> Vec<(String, u64)> {
+        self.store.file_edit_counts(limit).await.unwrap_or_default()
     }
 
     /// Called after a tool call completes.
@@ -267,7 +484,10 @@ impl MemoryManager {
         .await
         {
             Ok(c) => c,
-            Err(_) => return 0,
+            Err(e) => {
+                tracing::warn!(error = %e, "memory extraction failed");
+                return 0;
+            }
         };
 
         let mut saved = 0usize;
@@ -309,11 +529,13 @@ impl MemoryManager {
         saved
     }
 
-    /// Build a compact context block to inject into the system prompt.
+    /// Build a compact context block to inject into the system prompt, from the
+    /// memories in `scopes` (`[memory] inject_scopes`).
     pub async fn build_injection_context(
         &self,
         user_message: &str,
         active_paths: &[PathBuf],
+        scopes: &[MemoryScope],
     ) -> String {
         let mut q = user_message.to_string();
         for p in active_paths.iter().take(5) {
@@ -322,7 +544,7 @@ impl MemoryManager {
         }
 
         let results = self
-            .recall(
+            .recall_scopes(
                 &q,
                 8,
                 Some(vec![
@@ -331,6 +553,7 @@ impl MemoryManager {
                     MemoryType::Preference,
                     MemoryType::Decision,
                 ]),
+                scopes,
             )
             .await
             .unwrap_or_default();
@@ -354,6 +577,14 @@ impl MemoryManager {
     }
 }
 
+fn global_memory_dir() -> Option<PathBuf> {
+    // Unit tests must not read or write the user's real global memories.
+    if cfg!(test) {
+        return None;
+    }
+    crate::storage::data_dir().map(|d| d.join("memory"))
+}
+
 fn single_line(s: &str) -> String {
     let mut out = s.lines().next().unwrap_or("").trim().to_string();
     if out.len() > 200 {
@@ -373,3 +604,98 @@ fn normalize_sig(s: &str) -> String {
         trimmed.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn save(
+        manager: &MemoryManager,
+        content: &str,
+        tags: &[&str],
+        scope: MemoryScope,
+    ) -> Memory {
+        manager
+            .save_explicit(
+                MemoryType::Preference,
+                content,
+                None,
+                None,
+                tags.iter().map(|t| t.to_string()).collect(),
+                scope,
+                None,
+                MemorySource::User,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn global_memories_are_shared_and_duplicates_merge() {
+        let global_dir = TempDir::new().unwrap();
+        let (repo_a, repo_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let a = MemoryManager::init_with_global(repo_a.path(), Some(global_dir.path()))
+            .await
+            .unwrap();
+        let b = MemoryManager::init_with_global(repo_b.path(), Some(global_dir.path()))
+            .await
+            .unwrap();
+
+        save(&a, "Always use pnpm", &["js"], MemoryScope::Global).await;
+        save(&a, "Tabs in this repo", &[], MemoryScope::Project).await;
+        let seen_by_b = b.list(10, None).await.unwrap();
+        assert_eq!(seen_by_b.len(), 1);
+        assert_eq!(seen_by_b[0].scope, MemoryScope::Global);
+
+        // The same fact saved per-project folds into the global copy.
+        let merged = save(&b, "always use pnpm.", &["tooling"], MemoryScope::Project).await;
+        assert_eq!(merged.scope, MemoryScope::Global);
+        assert_eq!(merged.tags, vec!["js".to_string(), "tooling".to_string()]);
+        assert_eq!(b.list(10, None).await.unwrap().len(), 1);
+
+        // Saving globally a fact this project already has moves it to the global store.
+        save(&a, "Tabs  in this repo", &[], MemoryScope::Global).await;
+        let listed = a.list(10, None).await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().all(|m| m.scope == MemoryScope::Global));
+        assert_eq!(b.list(10, None).await.unwrap().len(), 2);
+
+        assert!(b.forget(&merged.id).await.unwrap());
+        assert_eq!(a.list(10, None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn consolidation_archives_faded_memories_once_a_day() {
+        let repo = TempDir::new().unwrap();
+        let manager = MemoryManager::init_with_global(repo.path(), None)
+            .await
+            .unwrap();
+        let mut faded = save(&manager, "Prefer yarn", &[], MemoryScope::Project).await;
+        save(
+            &manager,
+            "Prefer rustfmt defaults",
+            &[],
+            MemoryScope::Project,
+        )
+        .await;
+        faded.importance = 0.1;
+        faded.last_used -= 90 * 24 * 60 * 60;
+        manager.store.save_consolidated(&faded).await.unwrap();
+
+        let policy = ConsolidationPolicy::default();
+        let report = manager.consolidate(&policy).await.unwrap();
+        assert_eq!(report.archived, 1);
+        let left = manager.list(10, None).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].content, "Prefer rustfmt defaults");
+
+        // Already ran today.
+        assert_eq!(
+            manager.consolidate(&policy).await.unwrap(),
+            ConsolidationReport::default()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod consolidation;
 pub mod llm_extractor;
 pub mod manager;
 pub mod redaction;
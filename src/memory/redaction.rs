@@ -13,6 +13,10 @@ impl Redactor {
         let patterns = vec![
             // OpenAI-style keys, OpenRouter keys, generic API key patterns.
             Regex::new(r"(?i)\bsk-[a-z0-9_\-]{16,}\b").unwrap(),
+            // GitHub tokens and AWS access key ids.
+            Regex::new(r"\bgh[pousr]_[A-Za-z0-9]{36,}\b").unwrap(),
+            Regex::new(r"\bgithub_pat_[A-Za-z0-9_]{22,}\b").unwrap(),
+            Regex::new(r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b").unwrap(),
             Regex::new(r"(?i)\bapi[_-]?key\b\s*[:=]\s*[^\s\n]{8,}").unwrap(),
             Regex::new(r"(?i)\bsecret\b\s*[:=]\s*[^\s\n]{8,}").unwrap(),
             Regex::new(r"(?i)\btoken\b\s*[:=]\s*[^\s\n]{8,}").unwrap(),
@@ -32,10 +36,15 @@ impl Redactor {
             out.push_str("…");
         }
 
+        self.redact_secrets(&out)
+    }
+
+    /// Only the secret patterns, without the length cap (for exports).
+    pub fn redact_secrets(&self, input: &str) -> String {
+        let mut out = input.to_string();
         for re in &self.patterns {
             out = re.replace_all(&out, "[REDACTED]").to_string();
         }
-
         out
     }
 
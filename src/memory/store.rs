@@ -11,6 +11,9 @@ use crate::semantic_search::embedder::Embedder;
 
 const DB_FILENAME: &str = "memories.db";
 
+/// `project_id` of every row in the global store (`~/.lorikeet/memory`).
+pub const GLOBAL_PROJECT_ID: &str = "global";
+
 pub struct MemoryStore {
     conn: Arc<Mutex<Connection>>,
     #[allow(dead_code)]
@@ -20,22 +23,31 @@ pub struct MemoryStore {
 }
 
 impl MemoryStore {
-    pub async fn init(project_root: &Path) -> anyhow::Result<Self> {
+    /// The project store under `<project_root>/.lorikeet/memory`.
+    pub async fn init(
+        project_root: &Path,
+        embedder: Option<Arc<Embedder>>,
+    ) -> anyhow::Result<Self> {
         let dir = project_root.join(".lorikeet").join("memory");
-        tokio::fs::create_dir_all(&dir).await?;
+        Self::open(&dir, project_id(project_root), embedder).await
+    }
+
+    /// The cross-project store in `dir` (normally `~/.lorikeet/memory`).
+    pub async fn init_global(dir: &Path, embedder: Option<Arc<Embedder>>) -> anyhow::Result<Self> {
+        Self::open(dir, GLOBAL_PROJECT_ID.to_string(), embedder).await
+    }
+
+    async fn open(
+        dir: &Path,
+        project_id: String,
+        embedder: Option<Arc<Embedder>>,
+    ) -> anyhow::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
         let db_path = dir.join(DB_FILENAME);
 
         let conn = Connection::open(&db_path)?;
         init_schema(&conn)?;
 
-        // Best-effort embedding init. If model can't load, we fall back to keyword search.
-        let embedder = match Embedder::new() {
-            Ok(e) => Some(Arc::new(e)),
-            Err(_) => None,
-        };
-
-        let project_id = project_id(project_root);
-
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
             db_path,
@@ -91,6 +103,113 @@ impl MemoryStore {
         Ok(())
     }
 
+    /// Rewrite a memory's editable fields in place (re-embedding the new content).
+    /// Returns false if no memory has this id.
+    pub async fn update(
+        &self,
+        id: &str,
+        content: &str,
+        tags: &[String],
+        importance: f32,
+    ) -> anyhow::Result<bool> {
+        let embedding: Option<Vec<u8>> = self
+            .embedder
+            .as_ref()
+            .and_then(|emb| emb.embed(content).ok())
+            .map(|v| embedding_to_bytes(&v));
+
+        let conn = self.conn.lock().await;
+        let n = conn.execute(
+            "UPDATE memories SET content = ?2, tags = ?3, importance = ?4, embedding = ?5 WHERE id = ?1",
+            params![id, content, tags.join(","), importance, embedding],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// A stored memory of this type saying the same thing (ignoring case, spacing and
+    /// trailing punctuation), if any.
+    pub async fn find_duplicate(
+        &self,
+        memory_type: MemoryType,
+        content: &str,
+    ) -> anyhow::Result<Option<Memory>> {
+        let wanted = normalize_content(content);
+        let conn = self.conn.lock().await;
+        let mut stmt =
+            conn.prepare("SELECT * FROM memories WHERE project_id = ?1 AND type = ?2")?;
+        let rows = stmt.query_map(params![self.project_id, memory_type.as_str()], |row| {
+            row_to_memory(row)
+        })?;
+        Ok(rows
+            .filter_map(|r| r.ok())
+            .find(|m| normalize_content(&m.content) == wanted))
+    }
+
+    /// Every memory of this store with its embedding (if one was computed).
+    pub async fn all_with_embeddings(&self) -> anyhow::Result<Vec<(Memory, Option<Vec<f32>>)>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT * FROM memories WHERE project_id = ?1")?;
+        let rows = stmt.query_map(params![self.project_id], |row| {
+            let bytes: Option<Vec<u8>> = row.get("embedding")?;
+            Ok((
+                row_to_memory(row)?,
+                bytes.and_then(|b| bytes_to_embedding(&b)),
+            ))
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Store the bookkeeping fields consolidation changes (content and embedding stay).
+    pub async fn save_consolidated(&self, memory: &Memory) -> anyhow::Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE memories SET tags = ?2, importance = ?3, use_count = ?4, last_used = ?5 WHERE id = ?1",
+            params![
+                memory.id,
+                memory.tags.join(","),
+                memory.importance,
+                memory.use_count as i64,
+                memory.last_used
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Move a memory to `memories_archive`, out of recall, listing and injection.
+    pub async fn archive(&self, id: &str) -> anyhow::Result<bool> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO memories_archive SELECT * FROM memories WHERE id = ?1",
+            params![id],
+        )?;
+        let n = tx.execute("DELETE FROM memories WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(n > 0)
+    }
+
+    /// When consolidation last ran on this store (unix seconds).
+    pub async fn last_consolidated(&self) -> Option<i64> {
+        let conn = self.conn.lock().await;
+        conn.query_row(
+            "SELECT value FROM meta WHERE key = 'last_consolidated'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|v| v.parse().ok())
+    }
+
+    pub async fn set_last_consolidated(&self, ts: i64) -> anyhow::Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO meta (key, value) VALUES ('last_consolidated', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = ?1",
+            params![ts.to_string()],
+        )?;
+        Ok(())
+    }
+
     pub async fn delete(&self, id: &str) -> anyhow::Result<bool> {
         let conn = self.conn.lock().await;
         let n = conn.execute("DELETE FROM memories WHERE id = ?1", params![id])?;
@@ -141,6 +260,43 @@ impl MemoryStore {
         Ok(())
     }
 
+    /// Bump the cross-session edit count of each path (the file heatmap).
+    pub async fn record_file_edits(&self, paths: &[String]) -> anyhow::Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let now = crate::memory::types::unix_ts();
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        for path in paths {
+            tx.execute(
+                r#"
+                INSERT INTO file_edits (path, count, last_edit) VALUES (?1, 1, ?2)
+                ON CONFLICT(path) DO UPDATE SET count = count + 1, last_edit = ?2
+                "#,
+                params![path, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Edit counts recorded by earlier sessions, most edited first.
+    pub async fn file_edit_counts(&self, limit: usize) -> anyhow::Result<Vec<(String, u64)>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT path, count FROM file_edits ORDER BY count DESC, last_edit DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?.max(0) as u64,
+            ))
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
     pub async fn search(
         &self,
         query: &str,
@@ -344,11 +500,36 @@ fn init_schema(conn: &Connection) -> anyhow::Result<()> {
         CREATE INDEX IF NOT EXISTS idx_memories_type ON memories(type);
         CREATE INDEX IF NOT EXISTS idx_memories_importance ON memories(importance);
         CREATE INDEX IF NOT EXISTS idx_memories_last_used ON memories(last_used);
+
+        CREATE TABLE IF NOT EXISTS file_edits (
+            path TEXT PRIMARY KEY,
+            count INTEGER NOT NULL,
+            last_edit INTEGER NOT NULL
+        );
+
+        -- Memories consolidation retired (merged or decayed away); kept for reference.
+        CREATE TABLE IF NOT EXISTS memories_archive AS SELECT * FROM memories WHERE 0;
+
+        CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
         "#,
     )?;
     Ok(())
 }
 
+/// Comparison key for "the same fact": lowercase, single-spaced, without trailing `.!`.
+pub fn normalize_content(content: &str) -> String {
+    content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .trim_end_matches(['.', '!'])
+        .to_string()
+}
+
 fn row_to_memory(row: &rusqlite::Row<'_>) -> rusqlite::Result<Memory> {
     let id: String = row.get("id")?;
     let project_id: Option<String> = row.get("project_id")?;
@@ -422,7 +603,7 @@ fn bytes_to_embedding(bytes: &[u8]) -> Option<Vec<f32>> {
     Some(out)
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() || a.is_empty() {
         return 0.0;
     }
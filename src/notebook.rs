@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Output lines kept per cell when rendering; plots and long logs are noise to the model.
+const MAX_OUTPUT_LINES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellKind {
+    Code,
+    Markdown,
+    Raw,
+}
+
+impl CellKind {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Code => "code",
+            Self::Markdown => "markdown",
+            Self::Raw => "raw",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub kind: CellKind,
+    pub source: String,
+    /// Text outputs (stream, `text/plain` results, errors) joined together
+    pub outputs: String,
+}
+
+/// A Jupyter notebook reduced to what matters for reading and indexing.
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    /// Kernel language (`python`, `julia`, ...) if the metadata names one
+    pub language: Option<String>,
+    pub cells: Vec<Cell>,
+}
+
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("ipynb"))
+}
+
+/// The readable rendering of a notebook file, or `None` if `path` isn't a notebook (or
+/// doesn't parse as one).
+pub fn readable(path: &Path, content: &str) -> Option<String> {
+    if !is_notebook(path) {
+        return None;
+    }
+    Notebook::parse(content).map(|nb| nb.render())
+}
+
+impl Notebook {
+    pub fn parse(json: &str) -> Option<Self> {
+        let root: Value = serde_json::from_str(json).ok()?;
+        let cells = root.get("cells")?.as_array()?;
+
+        let metadata = root.get("metadata");
+        let language = metadata
+            .and_then(|m| m.pointer("/kernelspec/language"))
+            .or_else(|| metadata.and_then(|m| m.pointer("/language_info/name")))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let cells = cells
+            .iter()
+            .map(|cell| {
+                let kind = match cell.get("cell_type").and_then(|v| v.as_str()) {
+                    Some("code") => CellKind::Code,
+                    Some("markdown") => CellKind::Markdown,
+                    _ => CellKind::Raw,
+                };
+                let outputs = cell
+                    .get("outputs")
+                    .and_then(|v| v.as_array())
+                    .map(|outputs| outputs.iter().filter_map(output_text).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .join("\n");
+                Cell {
+                    kind,
+                    source: multiline(cell.get("source")),
+                    outputs,
+                }
+            })
+            .collect();
+
+        Some(Self { language, cells })
+    }
+
+    /// Render cells in a percent-script style (`# %% [code] cell 2`) that reads naturally
+    /// and keeps each cell's source lines intact.
+    pub fn render(&self) -> String {
+        self.render_with_offsets().0
+    }
+
+    /// The rendering plus, per cell, the 1-based line where its source starts. Search
+    /// results and `read_file` share these line numbers.
+    pub fn render_with_offsets(&self) -> (String, Vec<usize>) {
+        let mut out = String::new();
+        let mut offsets = Vec::with_capacity(self.cells.len());
+        let mut line = 1;
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+                line += 1;
+            }
+            out.push_str(&format!("# %% [{}] cell {}\n", cell.kind.label(), i + 1));
+            line += 1;
+
+            offsets.push(line);
+            for src in cell.source.lines() {
+                out.push_str(src);
+                out.push('\n');
+                line += 1;
+            }
+
+            if !cell.outputs.trim().is_empty() {
+                out.push_str("# [output]\n");
+                line += 1;
+                let lines: Vec<&str> = cell.outputs.lines().collect();
+                for l in lines.iter().take(MAX_OUTPUT_LINES) {
+                    out.push_str(l);
+                    out.push('\n');
+                    line += 1;
+                }
+                if lines.len() > MAX_OUTPUT_LINES {
+                    out.push_str(&format!(
+                        "… {} more output lines\n",
+                        lines.len() - MAX_OUTPUT_LINES
+                    ));
+                    line += 1;
+                }
+            }
+        }
+
+        (out, offsets)
+    }
+}
+
+/// nbformat stores text either as one string or as a list of lines (with their newlines).
+fn multiline(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts.iter().filter_map(|p| p.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+fn output_text(output: &Value) -> Option<String> {
+    let text = match output.get("output_type")?.as_str()? {
+        "stream" => multiline(output.get("text")),
+        "execute_result" | "display_data" => {
+            let data = output.get("data")?;
+            match data.get("text/plain") {
+                Some(text) => multiline(Some(text)),
+                None if data.get("image/png").is_some() => "<image>".to_string(),
+                None => return None,
+            }
+        }
+        "error" => format!(
+            "{}: {}",
+            output
+                .get("ename")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Error"),
+            output.get("evalue").and_then(|v| v.as_str()).unwrap_or("")
+        ),
+        _ => return None,
+    };
+    let text = text.trim_end_matches('\n');
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "\n", "Load the data."]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {},
+   "source": ["import pandas as pd\n", "df = pd.read_csv(\"x.csv\")\n", "df.shape"],
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["loading\n"]},
+    {"output_type": "execute_result", "data": {"text/plain": ["(3, 2)"]}, "metadata": {}}
+   ]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": "1 / 0",
+   "outputs": [{"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero", "traceback": []}]}
+ ],
+ "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn notebook_renders_cells_and_text_outputs() {
+        let nb = Notebook::parse(NOTEBOOK).expect("valid notebook");
+        assert_eq!(nb.language.as_deref(), Some("python"));
+        assert_eq!(nb.cells.len(), 3);
+        assert_eq!(nb.cells[0].kind, CellKind::Markdown);
+
+        let text = nb.render();
+        assert!(text.starts_with("# %% [markdown] cell 1\n# Analysis\n"));
+        assert!(text.contains("# %% [code] cell 2\nimport pandas as pd\n"));
+        assert!(text.contains("# [output]\nloading\n(3, 2)\n"));
+        assert!(text.contains("ZeroDivisionError: division by zero"));
+        assert!(!text.contains("execution_count"));
+
+        assert!(readable(Path::new("a.ipynb"), NOTEBOOK).is_some());
+        assert!(readable(Path::new("a.json"), NOTEBOOK).is_none());
+        assert!(readable(Path::new("a.ipynb"), "not json").is_none());
+    }
+
+    #[test]
+    fn offsets_point_at_each_cells_source() {
+        let nb = Notebook::parse(NOTEBOOK).unwrap();
+        let (text, offsets) = nb.render_with_offsets();
+        let lines: Vec<&str> = text.lines().collect();
+        for (cell, start) in nb.cells.iter().zip(offsets) {
+            let first = cell.source.lines().next().unwrap();
+            assert_eq!(lines[start - 1], first);
+        }
+    }
+}
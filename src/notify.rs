@@ -0,0 +1,94 @@
+//! Completion notifications for when you've switched away from the terminal: a bell, or
+//! OSC 9, which iTerm2, WezTerm, kitty, foot and Windows Terminal show as a desktop
+//! notification.
+
+use std::io::Write;
+
+use crate::config::AppConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notify {
+    Off,
+    Bell,
+    Osc9,
+}
+
+impl Notify {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "" => Some(Self::Off),
+            "bell" => Some(Self::Bell),
+            "osc9" | "desktop" => Some(Self::Osc9),
+            _ => None,
+        }
+    }
+}
+
+/// Events that can notify, each configured separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyEvent {
+    /// An agent turn finished (or stopped on an error)
+    Turn,
+    /// A `verify` run finished
+    Verify,
+}
+
+/// How `event` notifies under `config` (default: off).
+pub fn channel(config: &AppConfig, event: NotifyEvent) -> Notify {
+    let notifications = config.notifications.as_ref();
+    let setting = notifications.and_then(|n| match event {
+        NotifyEvent::Turn => n.turn.as_deref(),
+        NotifyEvent::Verify => n.verify.as_deref(),
+    });
+    setting.and_then(Notify::parse).unwrap_or(Notify::Off)
+}
+
+/// The bytes to write for `message`; control characters are dropped so the text can't
+/// end the escape sequence early.
+pub fn sequence(kind: Notify, message: &str) -> String {
+    let clean: String = message.chars().filter(|c| !c.is_control()).collect();
+    match kind {
+        Notify::Off => String::new(),
+        Notify::Bell => "\x07".to_string(),
+        Notify::Osc9 => format!("\x1b]9;{}\x07", clean),
+    }
+}
+
+/// Write the notification to the terminal (best-effort).
+pub fn send(kind: Notify, message: &str) {
+    let seq = sequence(kind, message);
+    if seq.is_empty() {
+        return;
+    }
+    let mut out = std::io::stdout();
+    let _ = out.write_all(seq.as_bytes());
+    let _ = out.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NotificationsConfig;
+
+    #[test]
+    fn channels_follow_config_per_event() {
+        let config = AppConfig {
+            notifications: Some(NotificationsConfig {
+                turn: Some("osc9".into()),
+                verify: Some("bell".into()),
+                ..Default::default()
+            }),
+            ..AppConfig::default()
+        };
+        assert_eq!(channel(&config, NotifyEvent::Turn), Notify::Osc9);
+        assert_eq!(channel(&config, NotifyEvent::Verify), Notify::Bell);
+        assert_eq!(
+            channel(&AppConfig::default(), NotifyEvent::Turn),
+            Notify::Off
+        );
+
+        assert_eq!(sequence(Notify::Osc9, "done\x07\nok"), "\x1b]9;doneok\x07");
+        assert_eq!(sequence(Notify::Bell, "x"), "\x07");
+        assert_eq!(sequence(Notify::Off, "x"), "");
+    }
+}
@@ -0,0 +1,290 @@
+//! Long-running commands the agent starts with `run_background` (dev servers, watchers):
+//! they outlive the turn, their recent output is kept for `process_status`, and
+//! `kill_process` or quitting Lorikeet stops them.
+
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+use crate::confine::Confinement;
+use crate::sandbox::SandboxPolicy;
+
+/// Output lines kept per process.
+const MAX_LOG_LINES: usize = 1000;
+/// Processes that may run at once.
+const MAX_RUNNING: usize = 8;
+/// How long a stopped process gets after SIGTERM before it's killed.
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub id: u32,
+    pub command: String,
+    pub pid: Option<u32>,
+    pub started: Instant,
+    /// Exit code once it has ended (-1 when killed by a signal)
+    pub exit: Option<i32>,
+}
+
+impl ProcessInfo {
+    pub fn running(&self) -> bool {
+        self.exit.is_none()
+    }
+
+    /// `[1] npm run dev (pid 4242, running 3m)` and the like.
+    pub fn describe(&self) -> String {
+        let state = match self.exit {
+            None => format!(
+                "running {}",
+                crate::stats::format_duration(self.started.elapsed())
+            ),
+            Some(code) => format!("exited {}", code),
+        };
+        match self.pid {
+            Some(pid) => format!("[{}] {} (pid {}, {})", self.id, self.command, pid, state),
+            None => format!("[{}] {} ({})", self.id, self.command, state),
+        }
+    }
+}
+
+struct Entry {
+    info: ProcessInfo,
+    logs: VecDeque<String>,
+    kill: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u32,
+    entries: Vec<Entry>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<Registry> {
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+/// Every process started this session, oldest first.
+pub fn list() -> Vec<ProcessInfo> {
+    registry()
+        .lock()
+        .entries
+        .iter()
+        .map(|e| e.info.clone())
+        .collect()
+}
+
+pub fn info(id: u32) -> Option<ProcessInfo> {
+    let reg = registry().lock();
+    reg.entries
+        .iter()
+        .find(|e| e.info.id == id)
+        .map(|e| e.info.clone())
+}
+
+/// The last `n` output lines of process `id`.
+pub fn recent_logs(id: u32, n: usize) -> Vec<String> {
+    let reg = registry().lock();
+    let Some(entry) = reg.entries.iter().find(|e| e.info.id == id) else {
+        return Vec::new();
+    };
+    let skip = entry.logs.len().saturating_sub(n);
+    entry.logs.iter().skip(skip).cloned().collect()
+}
+
+fn push_log(id: u32, line: String) {
+    let mut reg = registry().lock();
+    if let Some(entry) = reg.entries.iter_mut().find(|e| e.info.id == id) {
+        entry.logs.push_back(line);
+        while entry.logs.len() > MAX_LOG_LINES {
+            entry.logs.pop_front();
+        }
+    }
+}
+
+/// Start `command` in the workspace, confined like a `bash` call.
+pub fn start(command: &str, policy: &SandboxPolicy) -> Result<ProcessInfo, String> {
+    let running = list().iter().filter(|p| p.running()).count();
+    if running >= MAX_RUNNING {
+        return Err(format!(
+            "Error: {} background processes are already running; stop one with kill_process",
+            running
+        ));
+    }
+
+    let mut note = None;
+    let mut cmd = match Confinement::from_policy(policy).map(|c| c.bash_command(command)) {
+        Some(Ok(cmd)) => cmd,
+        unconfined => {
+            if let Some(Err(e)) = unconfined {
+                note = Some(format!(
+                    "[sandbox] OS confinement unavailable ({}); running with policy checks only",
+                    e
+                ));
+            }
+            let mut cmd = Command::new("bash");
+            cmd.arg("-c").arg(command);
+            cmd
+        }
+    };
+    cmd.current_dir(&policy.root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Its own process group, so stopping it reaches whatever it started (node under npm).
+    #[cfg(unix)]
+    cmd.process_group(0);
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Error spawning process: {}", e))?;
+
+    let (kill_tx, kill_rx) = oneshot::channel();
+    let info = {
+        let mut reg = registry().lock();
+        reg.next_id += 1;
+        let info = ProcessInfo {
+            id: reg.next_id,
+            command: command.trim().to_string(),
+            pid: child.id(),
+            started: Instant::now(),
+            exit: None,
+        };
+        reg.entries.push(Entry {
+            info: info.clone(),
+            logs: note.into_iter().collect(),
+            kill: Some(kill_tx),
+        });
+        info
+    };
+
+    let id = info.id;
+    if let Some(out) = child.stdout.take() {
+        tokio::spawn(collect(out, id, ""));
+    }
+    if let Some(err) = child.stderr.take() {
+        tokio::spawn(collect(err, id, "[stderr] "));
+    }
+    let pid = info.pid;
+    tokio::spawn(async move {
+        let status = tokio::select! {
+            status = child.wait() => status.ok(),
+            _ = kill_rx => {
+                signal_group(pid, "TERM");
+                match tokio::time::timeout(KILL_GRACE, child.wait()).await {
+                    Ok(status) => status.ok(),
+                    Err(_) => {
+                        signal_group(pid, "KILL");
+                        let _ = child.kill().await;
+                        child.wait().await.ok()
+                    }
+                }
+            }
+        };
+        let code = status.and_then(|s| s.code()).unwrap_or(-1);
+        let mut reg = registry().lock();
+        if let Some(entry) = reg.entries.iter_mut().find(|e| e.info.id == id) {
+            entry.info.exit = Some(code);
+            entry.kill = None;
+        }
+    });
+    Ok(info)
+}
+
+async fn collect<R: tokio::io::AsyncRead + Unpin>(r: R, id: u32, prefix: &'static str) {
+    let mut reader = BufReader::new(r);
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\r', '\n']);
+                push_log(id, format!("{}{}", prefix, line));
+            }
+        }
+    }
+}
+
+/// Send `signal` to the process group led by `pid` (best-effort).
+fn signal_group(pid: Option<u32>, signal: &str) {
+    #[cfg(unix)]
+    if let Some(pid) = pid {
+        let _ = std::process::Command::new("kill")
+            .args(["-s", signal, "--", &format!("-{}", pid)])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    #[cfg(not(unix))]
+    let _ = (pid, signal);
+}
+
+/// Ask process `id` to stop. False when there's no such running process.
+pub fn kill(id: u32) -> bool {
+    let sender = {
+        let mut reg = registry().lock();
+        reg.entries
+            .iter_mut()
+            .find(|e| e.info.id == id && e.info.running())
+            .and_then(|e| e.kill.take())
+    };
+    sender.map(|tx| tx.send(()).is_ok()).unwrap_or(false)
+}
+
+/// Stop everything still running; called on exit, when the runtime may not get to finish
+/// the usual shutdown.
+pub fn kill_all() {
+    let pids: Vec<Option<u32>> = registry()
+        .lock()
+        .entries
+        .iter()
+        .filter(|e| e.info.running())
+        .map(|e| e.info.pid)
+        .collect();
+    for pid in pids {
+        signal_group(pid, "TERM");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn processes_keep_logs_and_can_be_stopped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let policy = SandboxPolicy::from_config(
+            crate::config::AppConfig::default(),
+            dir.path().to_path_buf(),
+            &crate::tool_registry::names(),
+        );
+        let p = start("echo ready; sleep 30", &policy).unwrap();
+        for _ in 0..50 {
+            if !recent_logs(p.id, 10).is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(recent_logs(p.id, 10).contains(&"ready".to_string()));
+        assert!(info(p.id).unwrap().running());
+
+        assert!(kill(p.id));
+        for _ in 0..100 {
+            if !info(p.id).unwrap().running() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!info(p.id).unwrap().running());
+        assert!(!kill(p.id));
+    }
+}
@@ -0,0 +1,258 @@
+//! Running a `bash` command under a pseudo-terminal, for programs that behave differently
+//! (or refuse to run) without one: interactive installers, `git add -p`, watch modes,
+//! progress bars. The model gets the output with escape sequences and carriage-return
+//! redraws cleaned up; the raw bytes go to the trace for `/raw`.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use tokio::sync::mpsc;
+
+use crate::confine::Confinement;
+
+/// Terminal size the command sees.
+const PTY_SIZE: PtySize = PtySize {
+    rows: 40,
+    cols: 160,
+    pixel_width: 0,
+    pixel_height: 0,
+};
+
+/// Run `bash -c command` in `cwd` under a PTY, held to `confinement` when given. `input`
+/// is typed into the terminal after start, then end-of-file, so prompts the input doesn't
+/// answer fail instead of hanging. Each line goes to `on_line` raw and cleaned. Returns
+/// the exit code; errors are messages for the output (`Error spawning process: ...` when
+/// it couldn't start).
+pub async fn run(
+    command: &str,
+    input: Option<&str>,
+    cwd: &Path,
+    timeout: Duration,
+    confinement: Option<&Confinement>,
+    mut on_line: impl FnMut(String, String),
+) -> Result<i32, String> {
+    let spawn_err = |e: anyhow::Error| format!("Error spawning process: {}", e);
+    let pair = native_pty_system().openpty(PTY_SIZE).map_err(spawn_err)?;
+    // The slave is consumed by the spawn: our copy has to go, or the master never sees
+    // end-of-file.
+    let mut child = match confinement {
+        Some(confinement) => confinement
+            .spawn_pty(pair.slave, command, cwd)
+            .map_err(|e| format!("Error spawning process: {}", e))?,
+        None => {
+            let mut cmd = CommandBuilder::new("bash");
+            cmd.arg("-c");
+            cmd.arg(command);
+            cmd.cwd(cwd);
+            cmd.env("TERM", "xterm-256color");
+            let child = pair.slave.spawn_command(cmd).map_err(spawn_err)?;
+            drop(pair.slave);
+            child
+        }
+    };
+    let mut killer = child.clone_killer();
+    let mut reader = pair.master.try_clone_reader().map_err(spawn_err)?;
+    let mut writer = pair.master.take_writer().map_err(spawn_err)?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let mut read_task = tokio::task::spawn_blocking(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                // Linux reports EIO once the last slave handle closes.
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    let input = input_bytes(input);
+    tokio::task::spawn_blocking(move || {
+        if let Some(input) = input {
+            let _ = writer.write_all(&input);
+            let _ = writer.flush();
+        }
+        // Dropping the writer sends end-of-file (^D).
+        drop(writer);
+    });
+    let mut wait_task = tokio::task::spawn_blocking(move || child.wait());
+
+    let mut pending: Vec<u8> = Vec::new();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut emit_lines = |pending: &mut Vec<u8>, all: bool| {
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let raw = String::from_utf8_lossy(&line).into_owned();
+            on_line(raw.clone(), clean_line(&raw));
+        }
+        if all && !pending.is_empty() {
+            let raw = String::from_utf8_lossy(pending).into_owned();
+            pending.clear();
+            on_line(raw.clone(), clean_line(&raw));
+        }
+    };
+
+    let status = loop {
+        tokio::select! {
+            chunk = rx.recv() => match chunk {
+                Some(bytes) => {
+                    pending.extend_from_slice(&bytes);
+                    emit_lines(&mut pending, false);
+                }
+                // Output closed; the exit status follows.
+                None => break tokio::time::timeout_at(deadline, &mut wait_task).await,
+            },
+            status = &mut wait_task => {
+                // Give the reader a moment to drain what's left in the terminal.
+                let _ = tokio::time::timeout(Duration::from_millis(500), &mut read_task).await;
+                while let Ok(bytes) = rx.try_recv() {
+                    pending.extend_from_slice(&bytes);
+                }
+                break Ok(status);
+            }
+            _ = tokio::time::sleep_until(deadline) => {
+                let _ = killer.kill();
+                emit_lines(&mut pending, true);
+                return Err(format!(
+                    "Error: Command timed out after {} seconds\n",
+                    timeout.as_secs()
+                ));
+            }
+        }
+    };
+    emit_lines(&mut pending, true);
+
+    match status {
+        Ok(Ok(Ok(status))) => Ok(status.exit_code() as i32),
+        Ok(Ok(Err(e))) => Err(format!("Error executing command: {}\n", e)),
+        Ok(Err(e)) => Err(format!("Error executing command: {}\n", e)),
+        Err(_) => {
+            let _ = killer.kill();
+            Err(format!(
+                "Error: Command timed out after {} seconds\n",
+                timeout.as_secs()
+            ))
+        }
+    }
+}
+
+/// `input` as typed: a trailing newline is added so the last answer is submitted.
+fn input_bytes(input: Option<&str>) -> Option<Vec<u8>> {
+    let input = input.filter(|s| !s.is_empty())?;
+    let mut bytes = input.replace("\r\n", "\n").into_bytes();
+    if !bytes.ends_with(b"\n") {
+        bytes.push(b'\n');
+    }
+    Some(bytes)
+}
+
+/// One line of terminal output as the model should read it: escape sequences removed,
+/// `\r` redraws (progress bars, spinners) collapsed to what was left on screen, and
+/// backspaces applied.
+pub fn clean_line(raw: &str) -> String {
+    let text = strip_ansi(raw);
+    let (body, newline) = match text.strip_suffix('\n') {
+        Some(body) => (body.trim_end_matches('\r'), "\n"),
+        None => (text.as_str(), ""),
+    };
+    let shown = body.rsplit('\r').find(|s| !s.is_empty()).unwrap_or("");
+    format!("{}{}", shown, newline)
+}
+
+/// Remove ANSI escape sequences (CSI, OSC, two-character escapes) and control
+/// characters other than tab, newline and carriage return; backspace erases.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters, then a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC (and DCS/APC/PM strings): up to BEL or ESC \
+                Some(']') | Some('P') | Some('_') | Some('^') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Character set selection takes one more character.
+                Some('(') | Some(')') | Some('*') | Some('+') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\x08' => {
+                out.pop();
+            }
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A raw line as it can be shown in a text view: escapes become visible (`␛[31m`).
+pub fn visible_escapes(raw: &str) -> String {
+    raw.trim_end_matches(['\r', '\n'])
+        .chars()
+        .map(|c| match c {
+            '\x1b' => '␛',
+            '\r' => '␍',
+            '\x07' => '␇',
+            '\x08' => '␈',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal_output_is_cleaned_for_the_model() {
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: x"), "error: x");
+        assert_eq!(strip_ansi("\x1b]0;title\x07done"), "done");
+        assert_eq!(strip_ansi("ab\x08c"), "ac");
+        assert_eq!(clean_line(" 10%\r 50%\r100%\r\n"), "100%\n");
+        assert_eq!(clean_line("\x1b[32mok\x1b[0m\r\n"), "ok\n");
+        assert_eq!(visible_escapes("\x1b[31mred\r\n"), "␛[31mred");
+    }
+
+    #[tokio::test]
+    async fn commands_see_a_terminal_and_get_input() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut lines = Vec::new();
+        let code = run(
+            "test -t 0 && echo tty; read -r answer; echo \"got $answer\"; exit 3",
+            Some("yes"),
+            dir.path(),
+            Duration::from_secs(10),
+            None,
+            |_, clean| lines.push(clean),
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, 3);
+        assert!(lines.iter().any(|l| l == "tty\n"));
+        assert!(lines.iter().any(|l| l == "got yes\n"));
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::sync::Arc;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::prelude::Stylize;
+use regex::Regex;
 
 use unicode_width::UnicodeWidthStr;
 
@@ -12,6 +14,14 @@ use crate::app::{App, Role};
 use crate::markdown;
 use crate::theme::{self, UiTheme};
 
+/// Agent messages longer than this many rendered lines can be folded...
+const COLLAPSIBLE_MESSAGE_LINES: usize = 30;
+/// ...down to this many.
+const COLLAPSED_MESSAGE_LINES: usize = 12;
+
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const TOOL_SPINNER_FRAMES: &[&str] = &["◐", "◓", "◑", "◒"];
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum RenderedBlockId {
     Message(u64),
@@ -95,6 +105,7 @@ impl RenderStore {
         // Desired block order.
         let mut desired: Vec<(RenderedBlockId, BlockKind)> = Vec::new();
         let display_messages: Vec<_> = app.display_messages().cloned().collect();
+        let latest_agent = app.latest_agent_message_id();
         for msg in &display_messages {
             desired.push((RenderedBlockId::Message(msg.id), BlockKind::Message));
             desired.push((RenderedBlockId::Spacer(msg.id), BlockKind::Spacer));
@@ -124,11 +135,19 @@ impl RenderStore {
                     .find(|m| m.id == *msg_id)
                 {
                     Some(msg) => {
+                        let collapsed = app.message_collapsed(msg.id, latest_agent);
+                        let model = app.message_model(msg.id);
                         let h = hash64(&format!(
-                            "{:?}::{:?}::{:?}::{:?}",
-                            msg.role, msg.content, msg.reasoning, msg.tool_group_id
+                            "{:?}::{:?}::{:?}::{:?}::{}::{:?}",
+                            msg.role,
+                            msg.content,
+                            msg.reasoning,
+                            msg.tool_group_id,
+                            collapsed,
+                            model
                         ));
-                        let lines = render_message_block(msg, ui_theme, chat_width);
+                        let lines =
+                            render_message_block(msg, ui_theme, chat_width, collapsed, model);
                         (h, Arc::new(lines))
                     }
                     None => (0u64, Arc::new(Vec::new())),
@@ -140,8 +159,11 @@ impl RenderStore {
                 }
                 (RenderedBlockId::Streaming, BlockKind::Streaming) => {
                     let h = hash64(&format!(
-                        "{}::{:?}::{:?}",
-                        app.spinner_frame, app.current_reasoning, app.current_response
+                        "{}::{:?}::{:?}::{}",
+                        app.spinner_frame(SPINNER_FRAMES.len()),
+                        app.current_reasoning,
+                        app.current_response,
+                        app.call_model
                     ));
                     let lines = render_streaming_block(app, ui_theme, chat_width);
                     (h, Arc::new(lines))
@@ -219,6 +241,82 @@ impl RenderStore {
 
         out
     }
+
+    /// Rows whose text contains `query` (ignoring ASCII case), top to bottom.
+    pub fn find(&self, query: &str) -> Vec<usize> {
+        let mut rows = Vec::new();
+        if query.is_empty() {
+            return rows;
+        }
+        let mut row = 0;
+        for b in &self.blocks {
+            for line in b.lines.iter() {
+                if !match_ranges(&line_text(line), query).is_empty() {
+                    rows.push(row);
+                }
+                row += 1;
+            }
+        }
+        rows
+    }
+
+    /// The block covering transcript row `row`, with the rows it spans.
+    pub fn block_at(&self, row: usize) -> Option<(&RenderedBlockId, Range<usize>)> {
+        let mut start = 0;
+        for b in &self.blocks {
+            if row < start + b.height {
+                return Some((&b.id, start..start + b.height));
+            }
+            start += b.height;
+        }
+        None
+    }
+}
+
+pub fn line_text(line: &Line) -> String {
+    line.spans.iter().map(|s| s.content.as_ref()).collect()
+}
+
+/// Byte ranges of `query` in `text`, ignoring ASCII case (so offsets line up with `text`).
+pub fn match_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let hay = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    hay.match_indices(&needle)
+        .map(|(i, m)| (i, i + m.len()))
+        .collect()
+}
+
+/// A `path:line` reference in a rendered line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRef {
+    /// Byte range of `path:line` in the line's text
+    pub range: Range<usize>,
+    pub path: String,
+    pub line: usize,
+}
+
+/// `path:line` references in `text`. The path needs a `/` or an extension, so times and
+/// ports don't count.
+pub fn file_refs(text: &str) -> Vec<FileRef> {
+    static FILE_REF: OnceLock<Regex> = OnceLock::new();
+    let re = FILE_REF.get_or_init(|| {
+        Regex::new(r#"(?:^|[\s(\[`'"])(/?(?:[\w.\-]+/)+[\w.\-]+|[\w\-]+(?:\.[\w\-]+)*\.[A-Za-z]\w*):(\d+)"#)
+            .expect("valid file reference regex")
+    });
+    re.captures_iter(text)
+        .filter_map(|caps| {
+            let path = caps.get(1)?;
+            let line = caps.get(2)?;
+            Some(FileRef {
+                range: path.start()..line.end(),
+                path: path.as_str().to_string(),
+                line: line.as_str().parse::<usize>().ok()?.max(1),
+            })
+        })
+        .collect()
 }
 
 fn hash64(s: &str) -> u64 {
@@ -227,7 +325,13 @@ fn hash64(s: &str) -> u64 {
     h.finish()
 }
 
-fn render_message_block(msg: &crate::app::Message, ui_theme: &UiTheme, chat_width: usize) -> Vec<Line<'static>> {
+fn render_message_block(
+    msg: &crate::app::Message,
+    ui_theme: &UiTheme,
+    chat_width: usize,
+    collapsed: bool,
+    model: Option<&str>,
+) -> Vec<Line<'static>> {
     let pal = ui_theme.palette;
     let (prefix, md_theme, prefix_style) = match msg.role {
         Role::User => (
@@ -250,6 +354,8 @@ fn render_message_block(msg: &crate::app::Message, ui_theme: &UiTheme, chat_widt
             ui_theme.markdown,
             Style::default().fg(pal.accent).bold(),
         ),
+        Role::Draft => ("◌ ", ui_theme.markdown, pal.ghost()),
+        Role::Plan => ("📌 ", ui_theme.markdown, Style::default().fg(pal.ok).bold()),
     };
 
     let mut lines: Vec<Line<'static>> = Vec::new();
@@ -263,12 +369,20 @@ fn render_message_block(msg: &crate::app::Message, ui_theme: &UiTheme, chat_widt
         }
     }
 
-    let md_lines = markdown::render(
+    let mut md_lines = markdown::render(
         &msg.content,
         md_theme,
         ui_theme.syntax,
         chat_width.saturating_sub(2),
     );
+    if msg.role == Role::Draft {
+        // Greyed out so it doesn't read as a finished answer.
+        for line in &mut md_lines {
+            for span in &mut line.spans {
+                span.style = pal.ghost();
+            }
+        }
+    }
 
     let mut prev_empty = false;
     let mut is_first = true;
@@ -307,6 +421,18 @@ fn render_message_block(msg: &crate::app::Message, ui_theme: &UiTheme, chat_widt
         }
     }
 
+    if collapsed && msg.role == Role::Agent && lines.len() > COLLAPSIBLE_MESSAGE_LINES {
+        let hidden = lines.len() - COLLAPSED_MESSAGE_LINES;
+        lines.truncate(COLLAPSED_MESSAGE_LINES);
+        lines.push(Line::from(Span::styled(
+            format!("  ▸ … {} more lines (click or z to expand)", hidden),
+            pal.meta(),
+        )));
+    }
+    if let Some(model) = model.filter(|_| msg.role == Role::Agent) {
+        lines.push(Line::from(Span::styled(format!("  via {}", model), pal.meta())));
+    }
+
     lines
 }
 
@@ -329,8 +455,7 @@ fn render_tool_group_block(app: &App, ui_theme: &UiTheme, group_id: u64, chat_wi
     // Reuse the existing inline renderer (it builds Lines) by capturing into a vec.
     let pal = ui_theme.palette;
     let mut out: Vec<Line<'static>> = Vec::new();
-    let tool_spinner_frames: &[&str] = &["◐", "◓", "◑", "◒"];
-    let tool_spinner = tool_spinner_frames[app.tool_spinner_frame % tool_spinner_frames.len()];
+    let tool_spinner = TOOL_SPINNER_FRAMES[app.spinner_frame(TOOL_SPINNER_FRAMES.len())];
 
     // Header
     let tools: Vec<&crate::app::ToolOutput> = app
@@ -342,15 +467,24 @@ fn render_tool_group_block(app: &App, ui_theme: &UiTheme, group_id: u64, chat_wi
         return out;
     }
     let any_running = tools.iter().any(|t| matches!(t.status, crate::app::ToolStatus::Running));
+    let failed = tools
+        .iter()
+        .filter(|t| t.status == crate::app::ToolStatus::Error)
+        .count();
     let expanded = app.tool_trace_expanded.get(&group_id).copied().unwrap_or(any_running);
     let glyph = if expanded { "▾" } else { "▸" };
-    let status = if any_running { "running…" } else { "done" };
-    let status_glyph = if any_running { tool_spinner } else { "●" };
-    let status_style = if any_running {
-        Style::default().fg(pal.warn)
+    let (status_glyph, status, status_color) = if any_running {
+        (tool_spinner, "running…".to_string(), pal.warn)
+    } else if failed > 0 {
+        (
+            theme::STATUS_ERR,
+            format!("done, {} failed", failed),
+            pal.err,
+        )
     } else {
-        Style::default().fg(pal.ok)
+        (theme::STATUS_OK, "done".to_string(), pal.ok)
     };
+    let status_style = theme::status_style(&app.config, status_color);
 
     out.push(Line::from(vec![
         Span::raw("  "),
@@ -371,8 +505,7 @@ fn render_tool_group_block(app: &App, ui_theme: &UiTheme, group_id: u64, chat_wi
 
 fn render_streaming_block(app: &App, ui_theme: &UiTheme, chat_width: usize) -> Vec<Line<'static>> {
     let pal = ui_theme.palette;
-    let spinner_frames: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-    let spinner = spinner_frames[app.spinner_frame % spinner_frames.len()];
+    let spinner = SPINNER_FRAMES[app.spinner_frame(SPINNER_FRAMES.len())];
     let mut out: Vec<Line<'static>> = Vec::new();
 
     if !app.current_reasoning.is_empty() {
@@ -385,9 +518,14 @@ fn render_streaming_block(app: &App, ui_theme: &UiTheme, chat_width: usize) -> V
     }
 
     if app.current_response.is_empty() && app.current_reasoning.is_empty() {
+        // While routing is on, say which model is working.
+        let waiting = match app.config.routing.as_ref().and_then(|r| r.explore_model()) {
+            Some(_) => format!("… {}", app.call_model),
+            None => "…".to_string(),
+        };
         out.push(Line::from(vec![
             Span::styled(format!("{spinner} "), Style::default().fg(pal.warn)),
-            Span::styled("…", pal.meta()),
+            Span::styled(waiting, pal.meta()),
         ]));
         return out;
     }
@@ -455,9 +593,10 @@ fn render_tool_trace_item(
         .unwrap_or(true);
     let (status_indicator, status_color) = match tool.status {
         crate::app::ToolStatus::Running => (tool_spinner, pal.warn),
-        crate::app::ToolStatus::Success => ("●", pal.ok),
-        crate::app::ToolStatus::Error => ("●", pal.err),
+        crate::app::ToolStatus::Success => (theme::STATUS_OK, pal.ok),
+        crate::app::ToolStatus::Error => (theme::STATUS_ERR, pal.err),
     };
+    let no_color = theme::no_color_status(&app.config);
 
     let elapsed = tool.elapsed();
     let elapsed_str = if elapsed.as_secs() >= 60 {
@@ -491,11 +630,15 @@ fn render_tool_trace_item(
 
     out.push(Line::from(vec![
         Span::raw("  "),
-        Span::styled(format!("{} ", status_indicator), Style::default().fg(status_color)),
+        Span::styled(
+            format!("{} ", status_indicator),
+            theme::status_style(&app.config, status_color),
+        ),
         Span::styled(
             format!("{} ", tool.tool),
             Style::default()
                 .fg(match tool.status {
+                    _ if no_color => ui_theme.tool_trace.invocation,
                     crate::app::ToolStatus::Running => pal.warn,
                     crate::app::ToolStatus::Success => ui_theme.tool_trace.invocation,
                     crate::app::ToolStatus::Error => pal.err,
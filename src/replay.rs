@@ -0,0 +1,300 @@
+//! `lorikeet replay <session-id>`: play a recorded session back at its original pace, in
+//! the TUI or as annotated text (`--no-tui`).
+
+use std::time::Duration;
+
+use crate::session::SessionEvent;
+use crate::stats::format_duration;
+
+/// Longest wait between two steps of a replay, whatever the recording says: nobody wants
+/// to sit through the minutes a session spent waiting for its user.
+pub const MAX_PAUSE: Duration = Duration::from_secs(3);
+
+/// Output lines shown under a finished tool call in `--no-tui` mode.
+const TEXT_TOOL_TAIL: usize = 8;
+
+/// One step of a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cue {
+    /// Show event `i` (a message, title, checkpoint or plan).
+    Event(usize),
+    /// Tool call `i` starts running.
+    ToolStart(usize),
+    /// Tool call `i` finishes with its recorded output.
+    ToolDone(usize),
+}
+
+/// How fast a replay runs: `speed` times the recorded pace (`0` skips the waits), with no
+/// wait longer than `max_pause`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pace {
+    pub speed: f64,
+    pub max_pause: Duration,
+}
+
+impl Pace {
+    fn wait(&self, recorded_ms: i64) -> Duration {
+        if self.speed <= 0.0 || recorded_ms <= 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(recorded_ms as f64 / 1000.0 / self.speed).min(self.max_pause)
+    }
+}
+
+/// When each cue fires, counted from the start of the replay. Tool calls are recorded when
+/// they finish, so they start their recorded run time earlier.
+pub fn timeline(events: &[SessionEvent], pace: Pace) -> Vec<(Duration, Cue)> {
+    let mut cues = Vec::new();
+    let mut clock = Duration::ZERO;
+    let mut last_ms: Option<i64> = None;
+    for (i, ev) in events.iter().enumerate() {
+        let ms = ev.ts() * 1000;
+        let since_last = |at: i64| last_ms.map_or(0, |last| at - last);
+        match ev {
+            SessionEvent::Meta { .. } => {}
+            SessionEvent::Tool { elapsed_ms, .. } => {
+                let run = *elapsed_ms as i64;
+                clock += pace.wait(since_last(ms - run));
+                cues.push((clock, Cue::ToolStart(i)));
+                clock += pace.wait(run);
+                cues.push((clock, Cue::ToolDone(i)));
+            }
+            _ => {
+                clock += pace.wait(since_last(ms));
+                cues.push((clock, Cue::Event(i)));
+            }
+        }
+        last_ms = Some(last_ms.map_or(ms, |last| last.max(ms)));
+    }
+    cues
+}
+
+/// `cue` as `--no-tui` prints it, stamped with the recorded time since `start_ts`. System
+/// prompts and the tool results sent to the model are left out (tool calls show their
+/// output themselves).
+pub fn describe(events: &[SessionEvent], cue: Cue, start_ts: i64) -> Option<String> {
+    let ev = events.get(match cue {
+        Cue::Event(i) | Cue::ToolStart(i) | Cue::ToolDone(i) => i,
+    })?;
+    let stamp = |ms: i64| {
+        let secs = (ms / 1000 - start_ts).max(0);
+        if secs >= 3600 {
+            format!("[{}:{:02}:{:02}]", secs / 3600, secs / 60 % 60, secs % 60)
+        } else {
+            format!("[{:02}:{:02}]", secs / 60, secs % 60)
+        }
+    };
+    let ms = ev.ts() * 1000;
+    match (cue, ev) {
+        (
+            Cue::Event(_),
+            SessionEvent::Message {
+                role,
+                content,
+                reasoning,
+                local,
+                model,
+                ..
+            },
+        ) => {
+            let who = match (role.as_str(), *local) {
+                ("system" | "tool", _) => return None,
+                ("user", true) => "command".to_string(),
+                ("user", false) => "user".to_string(),
+                (_, true) => "note".to_string(),
+                ("assistant", false) => match model {
+                    Some(model) => format!("assistant ({})", model),
+                    None => "assistant".to_string(),
+                },
+                ("draft", false) => "draft, not sent".to_string(),
+                (other, false) => other.to_string(),
+            };
+            let mut out = format!("{} {}:", stamp(ms), who);
+            let indent = " ".repeat(stamp(ms).len() + 1);
+            if let Some(reasoning) = reasoning.as_deref().filter(|r| !r.trim().is_empty()) {
+                for line in reasoning.trim().lines() {
+                    out.push_str(&format!("\n{}┆ {}", indent, line));
+                }
+            }
+            push_indented(&mut out, content.trim(), &indent);
+            Some(out)
+        }
+        (Cue::Event(_), SessionEvent::Title { title, .. }) => {
+            Some(format!("{} title: {}", stamp(ms), title))
+        }
+        (Cue::Event(_), SessionEvent::Checkpoint { id, name, .. }) => Some(format!(
+            "{} checkpoint {}{}",
+            stamp(ms),
+            id,
+            name.as_deref()
+                .map(|n| format!(" ({})", n))
+                .unwrap_or_default()
+        )),
+        (Cue::Event(_), SessionEvent::Plan { accepted, .. }) => Some(format!(
+            "{} plan {}",
+            stamp(ms),
+            if *accepted { "executed" } else { "drafted" }
+        )),
+        (
+            Cue::ToolStart(_),
+            SessionEvent::Tool {
+                tool,
+                target,
+                elapsed_ms,
+                ..
+            },
+        ) => Some(format!(
+            "{} ▸ {} {}",
+            stamp(ms - *elapsed_ms as i64),
+            tool,
+            target
+        )),
+        (
+            Cue::ToolDone(_),
+            SessionEvent::Tool {
+                tool,
+                target,
+                output,
+                status,
+                elapsed_ms,
+                ..
+            },
+        ) => {
+            let mark = if status == "success" { "✓" } else { "✗" };
+            let took = format_duration(Duration::from_millis(*elapsed_ms as u64));
+            let mut out = format!("{} {} {} {} ({})", stamp(ms), mark, tool, target, took);
+            let indent = " ".repeat(stamp(ms).len() + 1);
+            let lines: Vec<&str> = output.lines().collect();
+            let start = lines.len().saturating_sub(TEXT_TOOL_TAIL);
+            if start > 0 {
+                out.push_str(&format!("\n{}│ … {} earlier lines", indent, start));
+            }
+            for line in &lines[start..] {
+                out.push_str(&format!("\n{}│ {}", indent, line));
+            }
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+/// Append `text` after a `who:` header: on the same line if it's one line, else below it.
+fn push_indented(out: &mut String, text: &str, indent: &str) {
+    let mut lines = text.lines();
+    match (lines.next(), lines.clone().next()) {
+        (Some(only), None) if !out.contains('\n') => {
+            out.push(' ');
+            out.push_str(only);
+        }
+        (first, _) => {
+            for line in first.into_iter().chain(lines) {
+                out.push_str(&format!("\n{}{}", indent, line));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(ts: i64, role: &str, content: &str) -> SessionEvent {
+        SessionEvent::Message {
+            ts,
+            role: role.into(),
+            content: content.into(),
+            reasoning: None,
+            tool_group_id: None,
+            local: false,
+            model: None,
+            plan_mode: None,
+            config_hash: None,
+        }
+    }
+
+    fn tool(ts: i64, elapsed_ms: u128, output: &str) -> SessionEvent {
+        SessionEvent::Tool {
+            ts,
+            tool: "bash".into(),
+            target: "cargo test".into(),
+            output: output.into(),
+            status: "success".into(),
+            elapsed_ms,
+            call_id: Some("call_1".into()),
+            args_raw: None,
+            cwd: None,
+            sandbox_allowed: None,
+            sandbox_reason: None,
+            group_id: Some(1),
+            output_file: None,
+        }
+    }
+
+    #[test]
+    fn timeline_keeps_the_recorded_pace_within_limits() {
+        let events = vec![
+            SessionEvent::Meta {
+                ts: 100,
+                session_id: "1".into(),
+                project_id: "p".into(),
+                version: "0".into(),
+            },
+            message(100, "user", "run the tests"),
+            message(102, "assistant", "Running them."),
+            // Finished at 106 after 3s, so it started at 103.
+            tool(106, 3_000, "ok"),
+            // The user came back an hour later.
+            message(3_706, "user", "thanks"),
+        ];
+        let pace = Pace {
+            speed: 2.0,
+            max_pause: MAX_PAUSE,
+        };
+        let ms = Duration::from_millis;
+        assert_eq!(
+            timeline(&events, pace),
+            vec![
+                (ms(0), Cue::Event(1)),
+                (ms(1_000), Cue::Event(2)),
+                (ms(1_500), Cue::ToolStart(3)),
+                (ms(3_000), Cue::ToolDone(3)),
+                (ms(6_000), Cue::Event(4)),
+            ]
+        );
+
+        let instant = Pace {
+            speed: 0.0,
+            max_pause: MAX_PAUSE,
+        };
+        assert!(timeline(&events, instant)
+            .iter()
+            .all(|(at, _)| at.is_zero()));
+    }
+
+    #[test]
+    fn text_replay_annotates_messages_and_tool_calls() {
+        let output: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let events = vec![
+            message(100, "system", "You are..."),
+            message(100, "user", "run the tests"),
+            message(165, "assistant", "All passed.\nNothing to fix."),
+            tool(106, 3_000, &output),
+        ];
+        assert_eq!(describe(&events, Cue::Event(0), 100), None);
+        assert_eq!(
+            describe(&events, Cue::Event(1), 100).unwrap(),
+            "[00:00] user: run the tests"
+        );
+        assert_eq!(
+            describe(&events, Cue::Event(2), 100).unwrap(),
+            "[01:05] assistant:\n        All passed.\n        Nothing to fix."
+        );
+        assert_eq!(
+            describe(&events, Cue::ToolStart(3), 100).unwrap(),
+            "[00:03] ▸ bash cargo test"
+        );
+        let done = describe(&events, Cue::ToolDone(3), 100).unwrap();
+        assert!(done.starts_with("[00:06] ✓ bash cargo test (3.0s)\n        │ … 2 earlier lines\n"));
+        assert!(done.ends_with("│ line 10"));
+    }
+}
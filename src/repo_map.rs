@@ -0,0 +1,191 @@
+//! A compact outline of the workspace (top-level layout, then the main public
+//! definitions per file) sent with each LLM call, so plans start from the codebase's
+//! structure instead of guesses.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::semantic_search::collect_files;
+use crate::semantic_search::symbols::{file_symbols, Symbol};
+use crate::semantic_search::types::{Language, SymbolType};
+use crate::semantic_search::SearchConfig;
+
+/// Upper bound for the map (~1.5k tokens).
+pub const MAX_MAP_BYTES: usize = 6 * 1024;
+/// Source files parsed for definitions; shallower paths first.
+const MAX_FILES_PARSED: usize = 3000;
+/// Definitions listed per file.
+const MAX_SYMBOLS_PER_FILE: usize = 10;
+/// Top-level entries listed in the layout line.
+const MAX_TOP_LEVEL: usize = 30;
+
+/// The map for `root`, or `None` when there's nothing to show.
+pub fn generate(root: &Path) -> Option<String> {
+    let config = SearchConfig::for_workspace(root);
+    let mut files: Vec<_> = collect_files(root, &config.exclude_patterns)
+        .ok()?
+        .into_iter()
+        .filter_map(|p| p.strip_prefix(root).ok().map(Path::to_path_buf))
+        .filter(|p| {
+            !p.components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+    files.sort_by_key(|p| (p.components().count(), p.clone()));
+
+    let mut out = String::from(
+        "[Repository map]\nWorkspace layout and the main public definitions per file. Use find_symbol or read_file for details.\n",
+    );
+    out.push_str(&layout(&files));
+
+    let mut modules: Vec<(usize, String)> = Vec::new();
+    for rel in files.iter().take(MAX_FILES_PARSED) {
+        let Ok(content) = std::fs::read_to_string(root.join(rel)) else {
+            continue;
+        };
+        let language = config.languages.detect(rel, &content);
+        let lines: Vec<&str> = content.lines().collect();
+        let public: Vec<Symbol> = file_symbols(&content, rel, &config.languages)
+            .into_iter()
+            .filter(|s| s.container.is_none() && s.kind != SymbolType::Method)
+            .filter(|s| {
+                let line = lines.get(s.line.saturating_sub(1)).copied().unwrap_or("");
+                is_public(line, &s.name, language)
+            })
+            .collect();
+        let entry_point = is_entry_point(rel);
+        if public.is_empty() && !entry_point {
+            continue;
+        }
+        let score = public.len() + if entry_point { 20 } else { 0 };
+        modules.push((score, module_line(rel, &public)));
+    }
+
+    // Most informative files first until the budget is spent, then shown in path order.
+    let mut order: Vec<usize> = (0..modules.len()).collect();
+    order.sort_by(|&a, &b| modules[b].0.cmp(&modules[a].0));
+    let mut budget = MAX_MAP_BYTES.saturating_sub(out.len() + 64);
+    let mut chosen = Vec::new();
+    for i in order {
+        let len = modules[i].1.len() + 1;
+        if len <= budget {
+            budget -= len;
+            chosen.push(i);
+        }
+    }
+    let omitted = modules.len() - chosen.len();
+    chosen.sort_unstable();
+    for i in chosen {
+        out.push_str(&modules[i].1);
+        out.push('\n');
+    }
+    if omitted > 0 {
+        out.push_str(&format!(
+            "[{} more files with definitions not shown]\n",
+            omitted
+        ));
+    }
+    Some(out)
+}
+
+/// `Layout: src/ (42 files), tests/ (6 files), Cargo.toml, README.md`
+fn layout(files: &[std::path::PathBuf]) -> String {
+    let mut dirs: BTreeMap<String, usize> = BTreeMap::new();
+    let mut top_files = Vec::new();
+    for f in files {
+        let mut parts = f.components();
+        let first = parts
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned());
+        match (first, parts.next()) {
+            (Some(dir), Some(_)) => *dirs.entry(dir).or_insert(0) += 1,
+            (Some(file), None) => top_files.push(file),
+            _ => {}
+        }
+    }
+    top_files.sort();
+    let mut entries: Vec<String> = dirs
+        .into_iter()
+        .map(|(dir, n)| format!("{}/ ({} file{})", dir, n, if n == 1 { "" } else { "s" }))
+        .chain(top_files)
+        .collect();
+    if entries.len() > MAX_TOP_LEVEL {
+        let more = entries.len() - MAX_TOP_LEVEL;
+        entries.truncate(MAX_TOP_LEVEL);
+        entries.push(format!("... {} more", more));
+    }
+    format!("Layout: {}\n", entries.join(", "))
+}
+
+/// `src/config.rs: AppConfig, GeneralConfig, load_layered()`
+fn module_line(rel: &Path, symbols: &[Symbol]) -> String {
+    let mut names: Vec<String> = symbols
+        .iter()
+        .take(MAX_SYMBOLS_PER_FILE)
+        .map(|s| match s.kind {
+            SymbolType::Function => format!("{}()", s.name),
+            SymbolType::Module => format!("mod {}", s.name),
+            _ => s.name.clone(),
+        })
+        .collect();
+    if symbols.len() > MAX_SYMBOLS_PER_FILE {
+        names.push(format!("+{}", symbols.len() - MAX_SYMBOLS_PER_FILE));
+    }
+    if names.is_empty() {
+        rel.display().to_string()
+    } else {
+        format!("{}: {}", rel.display(), names.join(", "))
+    }
+}
+
+/// Files that are worth listing even without public definitions of their own.
+fn is_entry_point(rel: &Path) -> bool {
+    let name = rel.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    matches!(
+        name,
+        "main.rs" | "lib.rs" | "mod.rs" | "__init__.py" | "__main__.py" | "main.go" | "main.py"
+    ) || name.starts_with("index.")
+}
+
+/// Whether a definition is part of its module's interface, judged from its first line
+/// and the language's visibility convention.
+fn is_public(line: &str, name: &str, language: Language) -> bool {
+    let line = line.trim_start();
+    match language {
+        Language::Rust => line.starts_with("pub ") || line.starts_with("pub("),
+        Language::Python | Language::Ruby => !name.starts_with('_'),
+        Language::Go => name.chars().next().is_some_and(char::is_uppercase),
+        Language::JavaScript | Language::TypeScript => line.starts_with("export "),
+        Language::Java => line.contains("public "),
+        Language::C | Language::Cpp => !line.starts_with("static "),
+        Language::Markdown | Language::Unknown => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_lists_layout_and_public_definitions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/config.rs"),
+            "pub struct Config;\n\nstruct Hidden;\n\npub fn load() {}\n\nfn helper() {}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("README.md"), "# Demo\n").unwrap();
+
+        let map = generate(root).unwrap();
+        assert!(map.contains("Layout: src/ (2 files), README.md"), "{}", map);
+        assert!(map.contains("src/config.rs: Config, load()"), "{}", map);
+        assert!(map.contains("\nsrc/main.rs\n"), "{}", map);
+        assert!(!map.contains("Hidden") && !map.contains("helper"));
+    }
+}
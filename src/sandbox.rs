@@ -1,7 +1,32 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-use crate::config::AppConfig;
+use regex::Regex;
+
+use crate::config::{AppConfig, ShellConfig, VerifyConfig};
+use crate::tool_registry::ToolAccess;
+
+/// Shell commands that change files or repository state, denied in read-only mode on top
+/// of the destructive ones (and of redirections into files). Matched with quoted strings
+/// blanked out, so `rg "a > b"` and `git log --grep "rm"` still run.
+const READ_ONLY_DENIED: &[&str] = &[
+    r"(^|[;&|({`]|\$\()\s*(sudo\s+)?(rm|rmdir|mv|cp|mkdir|touch|chmod|chown|ln|truncate|shred|unlink|install|patch|dd)\b",
+    r"\b(sed|perl|ruby)\s+(-\w+\s+)*(-[a-zA-Z]*i|--in-place)",
+    r"\btee\b",
+    r"\bfind\b.*\s-delete\b",
+    r"\bgit\s+(add|am|apply|branch\s+-[dDmM]|checkout|cherry-pick|clean|commit|merge|mv|pull|push|rebase|reset|restore|revert|rm|stash|switch|tag)\b",
+    r"\b(npm|pnpm|yarn|bun)\s+(i|install|add|remove|rm|uninstall|update|upgrade|ci|link)\b",
+    r"\b(pip|pip3|uv|poetry)\s+(install|uninstall|add|remove|sync)\b",
+    r"\bcargo\s+(add|remove|install|uninstall|update|fix)\b",
+    r"\bprettier\b.*\s--write\b",
+    r"\b(gofmt|goimports)\s+(-\w+\s+)*-w\b",
+    r"\bclang-format\b.*\s-i\b",
+];
+
+/// Formatters that rewrite files in place unless given `--check` (`isort --check-only`
+/// counts), which read-only mode requires.
+const FORMATTERS: &str = r"\b(cargo\s+fmt|rustfmt|ruff\s+format|black|isort)\b";
 
 #[derive(Debug, Clone)]
 pub struct SandboxPolicy {
@@ -11,6 +36,16 @@ pub struct SandboxPolicy {
     pub deny_paths: Vec<PathBuf>,
     pub allow_commands: HashSet<String>,
     pub allow_tools: HashSet<String>,
+    pub enforce: bool,
+    pub network: bool,
+    pub write_paths: Vec<PathBuf>,
+    /// `--read-only` / `/readonly`: no file edits or mutating commands, even with the
+    /// sandbox disabled, and confined commands can't write to the workspace.
+    pub read_only: bool,
+    /// `[verify]` from the config the policy was built from, for what `verify` runs
+    pub verify: Option<VerifyConfig>,
+    /// `[shell]` from the same config, for how `bash` runs
+    pub shell: Option<ShellConfig>,
 }
 
 #[derive(Debug)]
@@ -18,6 +53,8 @@ pub enum SandboxError {
     ToolNotAllowed(String),
     PathNotAllowed(PathBuf),
     CommandNotAllowed(String),
+    /// Denied by read-only mode; says what the call would have changed.
+    ReadOnly(String),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -52,6 +89,9 @@ impl std::fmt::Display for SandboxError {
             SandboxError::CommandNotAllowed(cmd) => {
                 write!(f, "Sandbox: command not allowed: {}", cmd)
             }
+            SandboxError::ReadOnly(what) => {
+                write!(f, "Read-only mode: {} is not allowed (/readonly to turn it off)", what)
+            }
         }
     }
 }
@@ -81,6 +121,10 @@ impl SandboxPolicy {
             .into_iter()
             .collect::<HashSet<_>>();
 
+        let write_paths = sandbox
+            .write_paths
+            .unwrap_or_else(crate::confine::default_write_paths);
+
         Self {
             enabled,
             root,
@@ -88,10 +132,35 @@ impl SandboxPolicy {
             deny_paths,
             allow_commands,
             allow_tools,
+            enforce: sandbox.enforce.unwrap_or(true),
+            network: sandbox.network.unwrap_or(true),
+            write_paths,
+            read_only: false,
+            verify: config.verify,
+            shell: config.shell,
+        }
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Also allow `roots` (other registered workspaces); deny paths still win.
+    pub fn with_roots(mut self, roots: &[PathBuf]) -> Self {
+        for root in roots {
+            if !self.allow_paths.iter().any(|allow| is_within(root, allow)) {
+                self.allow_paths.push(root.clone());
+            }
         }
+        self
     }
 
     pub fn check_tool_allowed(&self, tool_name: &str) -> Result<(), SandboxError> {
+        let edits = crate::tool_registry::access(tool_name) == Some(ToolAccess::Edit);
+        if self.read_only && edits {
+            return Err(SandboxError::ReadOnly(format!("`{}` (it edits files)", tool_name)));
+        }
         if !self.enabled {
             return Ok(());
         }
@@ -128,6 +197,28 @@ impl SandboxPolicy {
         }
     }
 
+    /// Allowed directories outside `root` (a shared proto checkout, say). Search tools
+    /// cover these alongside the workspace when no path is given. Ancestors of `root` are
+    /// left out: allowing `~` shouldn't make every search scan the home directory.
+    pub fn extra_search_roots(&self) -> Vec<PathBuf> {
+        let root = std::fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let mut out: Vec<PathBuf> = Vec::new();
+        for allow in &self.allow_paths {
+            let Ok(dir) = std::fs::canonicalize(allow) else {
+                continue;
+            };
+            let skip = !dir.is_dir()
+                || dir.starts_with(&root)
+                || root.starts_with(&dir)
+                || self.deny_paths.iter().any(|deny| is_within(&dir, deny))
+                || out.iter().any(|seen| dir.starts_with(seen));
+            if !skip {
+                out.push(dir);
+            }
+        }
+        out
+    }
+
     pub fn check_command_allowed(&self, cmd: &str) -> Result<(), SandboxError> {
         if !self.enabled {
             return Ok(());
@@ -143,6 +234,46 @@ impl SandboxPolicy {
         }
     }
 
+    /// In read-only mode, deny commands that look destructive or that write files or
+    /// repository state. A best-effort screen: confinement is what keeps the workspace
+    /// unwritable where the OS supports it.
+    pub fn check_read_only_command(&self, cmd: &str) -> Result<(), SandboxError> {
+        if !self.read_only {
+            return Ok(());
+        }
+        let destructive = crate::approval::destructive_patterns(None);
+        if let Some(reason) = crate::approval::destructive(cmd, &destructive).first() {
+            let what = reason.trim_start_matches("destructive: ");
+            return Err(SandboxError::ReadOnly(format!("{} (destructive)", what)));
+        }
+        static DENIED: OnceLock<Vec<Regex>> = OnceLock::new();
+        let denied = DENIED.get_or_init(|| {
+            READ_ONLY_DENIED
+                .iter()
+                .filter_map(|p| Regex::new(p).ok())
+                .collect()
+        });
+        let bare = blank_quotes(cmd);
+        if let Some(target) = redirect_targets(&bare).first() {
+            return Err(SandboxError::ReadOnly(format!("writing to `{}`", target)));
+        }
+        if let Some(m) = denied.iter().find_map(|re| re.find(&bare)) {
+            return Err(SandboxError::ReadOnly(format!(
+                "`{}` (it can change files)",
+                m.as_str().trim_start_matches(|c: char| ";&|({`$".contains(c)).trim()
+            )));
+        }
+        static FORMATTER: OnceLock<Option<Regex>> = OnceLock::new();
+        let formatter = FORMATTER.get_or_init(|| Regex::new(FORMATTERS).ok());
+        match formatter.as_ref().and_then(|re| re.find(&bare)) {
+            Some(m) if !bare.contains("--check") => Err(SandboxError::ReadOnly(format!(
+                "`{}` without --check (it rewrites files)",
+                m.as_str()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
     // Best-effort path allow/deny checks for shell commands. This is not a full shell parser;
     // it is intended to catch obvious path arguments like `./foo`, `../bar`, `/etc/passwd`.
     pub fn check_bash_paths(&self, command: &str) -> Result<(), SandboxError> {
@@ -166,6 +297,47 @@ impl SandboxPolicy {
     }
 }
 
+/// Files `cmd` redirects output into; `/dev/null`, other devices and descriptor copies
+/// (`2>&1`) don't count.
+fn redirect_targets(cmd: &str) -> Vec<String> {
+    static REDIRECT: OnceLock<Regex> = OnceLock::new();
+    let re = REDIRECT.get_or_init(|| {
+        Regex::new(r"(?:^|[^=\-<>])\d*&?>>?\|?\s*([^\s;&|()<>]*)").expect("redirect regex")
+    });
+    re.captures_iter(cmd)
+        .map(|c| c[1].to_string())
+        .filter(|target| !target.is_empty() && !target.starts_with("/dev/"))
+        .collect()
+}
+
+/// `cmd` with the contents of single- and double-quoted strings removed.
+fn blank_quotes(cmd: &str) -> String {
+    let mut out = String::with_capacity(cmd.len());
+    let mut quote = None;
+    let mut escaped = false;
+    for c in cmd.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                    out.push(c);
+                }
+            }
+            None => {
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
 fn default_allow_commands() -> Vec<String> {
     vec![
         "rg",
@@ -251,3 +423,97 @@ fn normalize_exe_token(token: &str) -> String {
     }
     t.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SandboxConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn extra_search_roots_skip_the_workspace_and_its_ancestors() {
+        let base = TempDir::new().unwrap();
+        let workspace = base.path().join("app");
+        let proto = base.path().join("proto");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::create_dir_all(proto.join("v1")).unwrap();
+
+        let config = AppConfig {
+            sandbox: Some(SandboxConfig {
+                allow_paths: Some(vec![
+                    workspace.clone(),
+                    workspace.join("src"),
+                    base.path().to_path_buf(),
+                    proto.clone(),
+                    proto.join("v1"),
+                    base.path().join("missing"),
+                ]),
+                ..SandboxConfig::default()
+            }),
+            ..AppConfig::default()
+        };
+        let policy = SandboxPolicy::from_config(config, workspace, &[]);
+        assert_eq!(
+            policy.extra_search_roots(),
+            vec![std::fs::canonicalize(&proto).unwrap()]
+        );
+    }
+
+    #[test]
+    fn read_only_mode_denies_edits_and_mutating_commands() {
+        let dir = TempDir::new().unwrap();
+        let config = AppConfig {
+            sandbox: Some(SandboxConfig {
+                enabled: Some(false),
+                ..SandboxConfig::default()
+            }),
+            ..AppConfig::default()
+        };
+        let policy = SandboxPolicy::from_config(config, dir.path().to_path_buf(), &[]);
+        assert!(policy.check_tool_allowed("edit_file").is_ok());
+        assert!(policy.check_read_only_command("rm -rf target").is_ok());
+
+        let policy = policy.with_read_only(true);
+        for tool in ["write_file", "edit_file", "apply_patch"] {
+            assert!(policy.check_tool_allowed(tool).is_err(), "{}", tool);
+        }
+        assert!(policy.check_tool_allowed("read_file").is_ok());
+
+        for cmd in [
+            "git reset --hard",
+            "rm src/main.rs",
+            "cd src && mv a.rs b.rs",
+            "sed -i 's/a/b/' src/lib.rs",
+            "echo hi > notes.txt",
+            "cargo test 2>&1 | tee out.log",
+            "git commit -am wip",
+            "npm install left-pad",
+            "find . -name '*.orig' -delete",
+            "cargo fmt --all",
+            "ruff format .",
+            "gofmt -l -w .",
+            "npx prettier --write src",
+        ] {
+            assert!(policy.check_read_only_command(cmd).is_err(), "{}", cmd);
+        }
+        for cmd in [
+            "rg 'a > b' src",
+            "git log --oneline -5",
+            "cargo test 2>/dev/null",
+            "ls -la 2>&1 | head",
+            "git diff HEAD~1 -- src",
+            "sed -n '1,20p' src/lib.rs",
+            "grep -rn \"mv \" scripts",
+            "cargo fmt --all -- --check",
+            "ruff format --check .",
+            "isort --check-only .",
+            "gofmt -l .",
+        ] {
+            assert!(policy.check_read_only_command(cmd).is_ok(), "{}", cmd);
+        }
+        let err = policy.check_read_only_command("git reset --hard").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Read-only mode: `git reset --hard` (destructive) is not allowed"));
+    }
+}
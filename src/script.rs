@@ -0,0 +1,457 @@
+//! `lorikeet exec --script steps.yaml`: run prompts, direct tool calls and verify gates
+//! in order against the current workspace, without the TUI, printing one JSON object per
+//! step. Prompts share one conversation, so later steps can build on earlier ones.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::app::{tool_start_event, App, Role};
+use crate::approval::Approval;
+use crate::config::{AppConfig, MemoryConfig};
+use crate::eval::EvalTarget;
+use crate::events::AppEvent;
+use crate::memory::MemoryManager;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::execute_tool;
+use crate::trust;
+
+/// `Ok((output, tool calls))` or `Err((error, output so far))`.
+type StepResult = Result<(String, Option<usize>), (String, Option<String>)>;
+
+/// Agent time per prompt step unless the step sets `timeout_secs`.
+const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A script file (YAML, JSON or TOML, by extension).
+#[derive(Debug, Deserialize)]
+pub struct Script {
+    /// Model for the prompts (default: the configured one)
+    pub model: Option<String>,
+    #[serde(alias = "step", default)]
+    pub steps: Vec<Step>,
+}
+
+/// One step: exactly one of `prompt`, `tool` or `verify`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Label for the output (default: `<kind> <n>`)
+    pub name: Option<String>,
+    /// Sent to the agent as a user message; the step waits for the turn to end
+    pub prompt: Option<String>,
+    /// Text the final reply must contain for a prompt step to pass
+    pub expect: Option<String>,
+    /// Tool to call directly, with `args`
+    pub tool: Option<String>,
+    #[serde(default)]
+    pub args: serde_json::Value,
+    /// Command that must pass; empty runs the `[verify]` or detected pipeline
+    pub verify: Option<String>,
+    pub timeout_secs: Option<u64>,
+    /// Keep going when this step fails (default: stop the script)
+    #[serde(default)]
+    pub continue_on_failure: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StepKind<'a> {
+    Prompt(&'a str),
+    Tool(&'a str),
+    Verify(&'a str),
+}
+
+impl Step {
+    fn kind(&self) -> Result<StepKind<'_>, String> {
+        match (&self.prompt, &self.tool, &self.verify) {
+            (Some(p), None, None) => Ok(StepKind::Prompt(p)),
+            (None, Some(t), None) => Ok(StepKind::Tool(t)),
+            (None, None, Some(v)) => Ok(StepKind::Verify(v)),
+            _ => Err("a step needs exactly one of prompt, tool or verify".into()),
+        }
+    }
+}
+
+/// What `lorikeet exec` prints for a step, as one JSON line.
+#[derive(Debug, Serialize)]
+pub struct StepReport {
+    pub step: usize,
+    pub name: String,
+    pub kind: &'static str,
+    pub ok: bool,
+    pub elapsed_ms: u128,
+    /// The agent's final reply (prompt steps) or the tool's result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Tool calls the agent made during a prompt step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Script {
+    /// Whether any step talks to the model (and so needs credentials).
+    pub fn has_prompts(&self) -> bool {
+        self.steps.iter().any(|s| s.prompt.is_some())
+    }
+}
+
+pub fn load_script(path: &Path) -> Result<Script, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let script: Script = match ext {
+        "json" => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        "toml" => toml::from_str(&text).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Error parsing {}: {}", path.display(), e))?;
+    for (i, step) in script.steps.iter().enumerate() {
+        step.kind().map_err(|e| format!("Step {}: {}", i + 1, e))?;
+    }
+    Ok(script)
+}
+
+/// Headless session the steps run in.
+pub struct ScriptRunner {
+    app: App,
+    rx: mpsc::UnboundedReceiver<AppEvent>,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    policy: Arc<SandboxPolicy>,
+    /// Answer for commands that would ask first (`--yes` approves them once)
+    approval: Approval,
+    /// Sees every event before it's handled (`--output json`)
+    observer: Option<Box<dyn FnMut(&AppEvent)>>,
+}
+
+impl ScriptRunner {
+    /// A session in `workspace` with its config and stored trust. Memory is off so runs
+    /// are reproducible.
+    pub async fn new(workspace: &Path, target: &EvalTarget, approve: bool) -> Result<Self, String> {
+        let mut config = AppConfig::load_layered(workspace);
+        config.memory = Some(MemoryConfig {
+            enabled: Some(false),
+            ..Default::default()
+        });
+        let policy = Arc::new(SandboxPolicy::from_config(
+            config.clone(),
+            workspace.to_path_buf(),
+            &crate::tool_registry::names(),
+        ));
+        let memory = Arc::new(
+            MemoryManager::init(workspace)
+                .await
+                .map_err(|e| format!("Error initializing memory: {}", e))?,
+        );
+        let (tx, rx) = mpsc::unbounded_channel::<AppEvent>();
+        let mut app = App::new(
+            tx.clone(),
+            target.provider,
+            target.api_key.clone(),
+            target.codex_account_id.clone(),
+            policy.clone(),
+            config,
+            workspace.to_path_buf(),
+            memory,
+        );
+        if let Some(model) = &target.model {
+            app.model = model.clone();
+        }
+        app.workspace_trust = trust::load(workspace);
+        Ok(Self {
+            app,
+            rx,
+            tx,
+            policy,
+            approval: if approve {
+                Approval::Once
+            } else {
+                Approval::Deny
+            },
+            observer: None,
+        })
+    }
+
+    /// Hand every event to `sink` as it happens: message deltas, tool calls, their output
+    /// and errors.
+    pub fn observe(&mut self, sink: impl FnMut(&AppEvent) + 'static) {
+        self.observer = Some(Box::new(sink));
+    }
+
+    fn dispatch(&mut self, event: AppEvent) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer(&event);
+        }
+        match event {
+            AppEvent::ToolApproval(request) => request.answer(self.approval),
+            event => self.app.handle_event(event),
+        }
+    }
+
+    pub async fn run_step(&mut self, index: usize, step: &Step) -> StepReport {
+        let started = Instant::now();
+        let timeout = step
+            .timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_STEP_TIMEOUT);
+        let (kind, result) = match step.kind() {
+            Ok(StepKind::Prompt(prompt)) => ("prompt", self.prompt(prompt, step, timeout).await),
+            Ok(StepKind::Tool(tool)) => {
+                let args = match &step.args {
+                    serde_json::Value::Null => serde_json::json!({}),
+                    args => args.clone(),
+                };
+                ("tool", self.tool(tool, &args, timeout).await)
+            }
+            Ok(StepKind::Verify(command)) => {
+                let args = if command.trim().is_empty() {
+                    serde_json::json!({})
+                } else {
+                    serde_json::json!({ "command": command })
+                };
+                ("verify", self.tool("verify", &args, timeout).await)
+            }
+            Err(e) => ("invalid", Err((e, None))),
+        };
+        let (output, tool_calls, error) = match result {
+            Ok((output, tool_calls)) => (Some(output), tool_calls, None),
+            Err((e, output)) => (output, None, Some(e)),
+        };
+        StepReport {
+            step: index + 1,
+            name: step
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{} {}", kind, index + 1)),
+            kind,
+            ok: error.is_none(),
+            elapsed_ms: started.elapsed().as_millis(),
+            output,
+            tool_calls,
+            error,
+        }
+    }
+
+    /// Run a prompt to the end of its turn; `Ok((final reply, tool calls))`.
+    async fn prompt(&mut self, prompt: &str, step: &Step, timeout: Duration) -> StepResult {
+        let tools_before = self.app.tool_outputs.len();
+        self.app.submit_prompt(prompt);
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.app.is_processing {
+            match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                Ok(Some(event)) => self.dispatch(event),
+                Ok(None) => break,
+                Err(_) => return Err((format!("timed out after {}s", timeout.as_secs()), None)),
+            }
+        }
+
+        let turn: Vec<_> = self
+            .app
+            .display_messages()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .take_while(|m| m.role != Role::User)
+            .collect();
+        let reply = turn
+            .iter()
+            .find(|m| m.role == Role::Agent && !m.local && !m.content.trim().is_empty())
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        if let Some(m) = turn
+            .iter()
+            .find(|m| m.local && m.content.starts_with("[Error:"))
+        {
+            return Err((m.content.clone(), Some(reply)));
+        }
+        if let Some(expect) = step.expect.as_deref() {
+            if !reply.contains(expect) {
+                return Err((format!("reply doesn't contain {:?}", expect), Some(reply)));
+            }
+        }
+        Ok((reply, Some(self.app.tool_outputs.len() - tools_before)))
+    }
+
+    /// Call a tool directly; `Ok((result, None))` when it succeeds.
+    async fn tool(
+        &mut self,
+        name: &str,
+        args: &serde_json::Value,
+        timeout: Duration,
+    ) -> StepResult {
+        let call_id = format!("script:{}:{}", name, uuid::Uuid::new_v4());
+        let args_raw = args.to_string();
+        self.dispatch(AppEvent::ToolStart(tool_start_event(
+            name,
+            &args_raw,
+            &call_id,
+            &self.policy,
+        )));
+        let (tx, policy, shell) = (self.tx.clone(), self.policy.clone(), self.app.shell.clone());
+        let (name_owned, id) = (name.to_string(), call_id.clone());
+        let mut handle = tokio::spawn(async move {
+            execute_tool(&name_owned, &args_raw, &id, &tx, &policy, &shell).await
+        });
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut success = None;
+        let output = loop {
+            tokio::select! {
+                joined = &mut handle => {
+                    break joined.map_err(|e| (e.to_string(), None))?;
+                }
+                Some(event) = self.rx.recv() => {
+                    if let AppEvent::ToolComplete(ev) = &event {
+                        if ev.call_id == call_id {
+                            success = Some(ev.success);
+                        }
+                    }
+                    self.dispatch(event);
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    handle.abort();
+                    return Err((format!("timed out after {}s", timeout.as_secs()), None));
+                }
+            }
+        };
+        // Completion can trail the result by an event.
+        while success.is_none() {
+            let Ok(event) = self.rx.try_recv() else {
+                break;
+            };
+            if let AppEvent::ToolComplete(ev) = &event {
+                if ev.call_id == call_id {
+                    success = Some(ev.success);
+                }
+            }
+            self.dispatch(event);
+        }
+        if success.unwrap_or_else(|| !output.starts_with("Error")) {
+            Ok((output, None))
+        } else {
+            Err((format!("{} failed", name), Some(output)))
+        }
+    }
+}
+
+/// Run every step, stopping at the first failure unless it sets `continue_on_failure`.
+/// Each report is handed to `emit` as soon as its step is done.
+pub async fn run_script(
+    script: &Script,
+    runner: &mut ScriptRunner,
+    mut emit: impl FnMut(&StepReport),
+) -> bool {
+    let mut all_ok = true;
+    for (i, step) in script.steps.iter().enumerate() {
+        let report = runner.run_step(i, step).await;
+        emit(&report);
+        if !report.ok {
+            all_ok = false;
+            if !step.continue_on_failure {
+                break;
+            }
+        }
+    }
+    all_ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn scripts_parse_from_yaml_json_and_toml() {
+        let dir = TempDir::new().unwrap();
+        let yaml = dir.path().join("steps.yaml");
+        std::fs::write(
+            &yaml,
+            r#"
+model: openai/gpt-4o-mini
+steps:
+  - prompt: Add a --verbose flag
+    expect: verbose
+  - tool: read_file
+    args: { path: src/main.rs }
+  - name: tests
+    verify: cargo test
+    continue_on_failure: true
+"#,
+        )
+        .unwrap();
+        let script = load_script(&yaml).unwrap();
+        assert_eq!(script.model.as_deref(), Some("openai/gpt-4o-mini"));
+        assert_eq!(
+            script.steps[0].kind(),
+            Ok(StepKind::Prompt("Add a --verbose flag"))
+        );
+        assert_eq!(script.steps[1].kind(), Ok(StepKind::Tool("read_file")));
+        assert_eq!(script.steps[1].args["path"], "src/main.rs");
+        assert_eq!(script.steps[2].kind(), Ok(StepKind::Verify("cargo test")));
+        assert!(script.steps[2].continue_on_failure);
+
+        let json = dir.path().join("steps.json");
+        std::fs::write(&json, r#"{"steps": [{"verify": ""}]}"#).unwrap();
+        assert_eq!(load_script(&json).unwrap().steps.len(), 1);
+
+        let toml = dir.path().join("steps.toml");
+        std::fs::write(&toml, "[[step]]\nprompt = \"hi\"\n").unwrap();
+        assert_eq!(load_script(&toml).unwrap().steps.len(), 1);
+
+        let bad = dir.path().join("bad.json");
+        std::fs::write(&bad, r#"{"steps": [{"prompt": "a", "tool": "rg"}]}"#).unwrap();
+        assert!(load_script(&bad).unwrap_err().starts_with("Step 1:"));
+    }
+
+    #[tokio::test]
+    async fn tool_steps_report_results_and_stop_on_failure() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello script\n").unwrap();
+        let target = EvalTarget {
+            provider: crate::llm::LlmProvider::OpenRouter,
+            api_key: "k".into(),
+            codex_account_id: None,
+            model: None,
+        };
+        let mut runner = ScriptRunner::new(dir.path(), &target, false).await.unwrap();
+        let read = |path: &str| Step {
+            tool: Some("read_file".into()),
+            args: serde_json::json!({ "path": dir.path().join(path) }),
+            ..Default::default()
+        };
+        let script = Script {
+            model: None,
+            steps: vec![read("notes.txt"), read("missing.txt"), read("notes.txt")],
+        };
+
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen = events.clone();
+        runner.observe(move |e| {
+            if let Some(v) = e.to_json() {
+                seen.borrow_mut()
+                    .push(v["type"].as_str().unwrap_or("").to_string());
+            }
+        });
+
+        let mut reports = Vec::new();
+        let ok = run_script(&script, &mut runner, |r| {
+            reports.push(serde_json::to_value(r).unwrap())
+        })
+        .await;
+        assert!(!ok);
+        assert_eq!(reports.len(), 2, "stops after the failing step");
+        assert_eq!(reports[0]["ok"], true);
+        assert_eq!(reports[0]["name"], "tool 1");
+        assert!(reports[0]["output"]
+            .as_str()
+            .unwrap()
+            .contains("hello script"));
+        assert_eq!(reports[1]["ok"], false);
+        let events = events.borrow();
+        assert_eq!(events.iter().filter(|t| *t == "tool_start").count(), 2);
+        assert_eq!(events.iter().filter(|t| *t == "tool_complete").count(), 2);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+
+const CACHE_FILE: &str = "embeddings.db";
+
+/// On-disk embedding cache keyed by (embedder id, content hash).
+///
+/// Re-indexing mostly sees unchanged chunks; looking their vectors up here is far
+/// cheaper than running the model again. Shared across workspaces.
+pub struct EmbeddingCache {
+    conn: Mutex<Connection>,
+}
+
+impl EmbeddingCache {
+    /// Open (or create) the cache at `~/.lorikeet/cache/embeddings.db`
+    pub fn open_default() -> Option<Self> {
+        Self::open(&default_cache_dir()).ok()
+    }
+
+    pub fn open(dir: &Path) -> rusqlite::Result<Self> {
+        let _ = std::fs::create_dir_all(dir);
+        let conn = Connection::open(dir.join(CACHE_FILE))?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS embeddings (
+                model TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                PRIMARY KEY (model, hash)
+            );
+            "#,
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Look up cached vectors; missing hashes are simply absent from the result.
+    pub fn get_many(&self, model: &str, hashes: &[String]) -> HashMap<String, Vec<f32>> {
+        let mut out = HashMap::new();
+        let conn = self.conn.lock();
+        let Ok(mut stmt) =
+            conn.prepare_cached("SELECT vector FROM embeddings WHERE model = ?1 AND hash = ?2")
+        else {
+            return out;
+        };
+        for hash in hashes {
+            let bytes: rusqlite::Result<Vec<u8>> =
+                stmt.query_row(params![model, hash], |row| row.get(0));
+            if let Some(vector) = bytes.ok().and_then(|b| bytes_to_vector(&b)) {
+                out.insert(hash.clone(), vector);
+            }
+        }
+        out
+    }
+
+    /// Store freshly computed vectors. Best-effort: errors are ignored.
+    pub fn put_many(&self, model: &str, entries: &[(String, Vec<f32>)]) {
+        if entries.is_empty() {
+            return;
+        }
+        let mut conn = self.conn.lock();
+        let Ok(tx) = conn.transaction() else {
+            return;
+        };
+        for (hash, vector) in entries {
+            let _ = tx.execute(
+                "INSERT OR REPLACE INTO embeddings (model, hash, vector) VALUES (?1, ?2, ?3)",
+                params![model, hash, vector_to_bytes(vector)],
+            );
+        }
+        let _ = tx.commit();
+    }
+}
+
+/// Hash of the exact text that is sent to the embedder.
+pub fn content_hash(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn default_cache_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".lorikeet")
+        .join("cache")
+}
+
+fn vector_to_bytes(v: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(v.len() * 4);
+    for f in v {
+        out.extend_from_slice(&f.to_le_bytes());
+    }
+    out
+}
+
+fn bytes_to_vector(bytes: &[u8]) -> Option<Vec<f32>> {
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    bytes
+        .chunks_exact(4)
+        .map(|c| c.try_into().ok().map(f32::from_le_bytes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn cache_round_trips_by_model_and_hash() {
+        let dir = TempDir::new().unwrap();
+        let cache = EmbeddingCache::open(dir.path()).unwrap();
+        let h = content_hash("fn main() {}");
+        cache.put_many("fastembed/a 4", &[(h.clone(), vec![0.5, -1.0, 0.0, 2.0])]);
+
+        let hit = cache.get_many("fastembed/a 4", &[h.clone(), content_hash("other")]);
+        assert_eq!(hit.len(), 1);
+        assert_eq!(hit[&h], vec![0.5, -1.0, 0.0, 2.0]);
+
+        // A different embedder never sees another model's vectors.
+        assert!(cache.get_many("openai/b 4", &[h]).is_empty());
+    }
+}
@@ -21,6 +21,11 @@ impl Chunker {
         file_path: &Path,
         language: Language,
     ) -> Vec<CodeChunk> {
+        // Docs don't have symbols; split them on headings instead
+        if language == Language::Markdown {
+            return self.chunk_prose(content, file_path, language);
+        }
+
         // Try AST-aware chunking first
         if let Some(ts_language) = get_tree_sitter_language(language) {
             if let Some(chunks) = self.chunk_with_ast(content, file_path, language, ts_language) {
@@ -187,6 +192,68 @@ impl Chunker {
         }
     }
 
+    /// Chunk a prose document by heading sections, packing whole paragraphs up to the
+    /// size limit. Each chunk is tagged with its heading path (`Install > Linux`).
+    fn chunk_prose(&self, content: &str, file_path: &Path, language: Language) -> Vec<CodeChunk> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut chunks = Vec::new();
+        let mut chunk_id = 0u64;
+
+        for (start, end, headings) in prose_sections(&lines) {
+            let symbol_name = (!headings.is_empty()).then(|| headings.join(" > "));
+            let symbol_type = symbol_name.as_ref().map(|_| SymbolType::Section);
+
+            // Pending range of whole paragraphs, as 0-indexed [from, to) line numbers
+            let mut pending: Option<(usize, usize)> = None;
+            let flush = |range: (usize, usize), chunks: &mut Vec<CodeChunk>, id: &mut u64| {
+                chunks.push(CodeChunk {
+                    id: *id,
+                    content: lines[range.0..range.1].join("\n"),
+                    metadata: ChunkMetadata {
+                        file_path: file_path.to_path_buf(),
+                        start_line: range.0 + 1,
+                        end_line: range.1,
+                        language,
+                        symbol_name: symbol_name.clone(),
+                        symbol_type,
+                    },
+                });
+                *id += 1;
+            };
+
+            for (from, to) in paragraphs(&lines, start, end) {
+                if let Some(range @ (p_from, _)) = pending {
+                    if joined_len(&lines[p_from..to]) > self.max_chunk_size {
+                        flush(range, &mut chunks, &mut chunk_id);
+                        pending = None;
+                    }
+                }
+
+                if joined_len(&lines[from..to]) > self.max_chunk_size {
+                    // A single paragraph (usually a code block) over the limit
+                    self.split_large_chunk(
+                        &lines[from..to].join("\n"),
+                        file_path,
+                        language,
+                        from + 1,
+                        symbol_name.clone(),
+                        symbol_type,
+                        &mut chunks,
+                        &mut chunk_id,
+                    );
+                    continue;
+                }
+
+                pending = Some((pending.map_or(from, |(p_from, _)| p_from), to));
+            }
+            if let Some(range) = pending {
+                flush(range, &mut chunks, &mut chunk_id);
+            }
+        }
+
+        chunks
+    }
+
     /// Fall back to line-based chunking
     fn chunk_by_lines(
         &self,
@@ -260,8 +327,188 @@ impl Chunker {
     }
 }
 
+/// Most omitted symbols listed after a truncated file.
+const MAX_OMITTED_OUTLINE: usize = 60;
+
+/// Cut `content` to at most `max_bytes` for the model, ending after the last whole
+/// definition (or Markdown section) that fits rather than mid-line, and list the symbols
+/// that were left out with their line ranges. Returns `content` unchanged when it fits.
+pub fn truncate_at_boundaries(content: &str, file_path: &Path, max_bytes: usize) -> String {
+    if content.len() <= max_bytes {
+        return content.to_string();
+    }
+
+    // Byte offset just past each line (1-indexed line n ends at line_ends[n - 1]).
+    let mut line_ends = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        line_ends.push(offset);
+    }
+    let total_lines = line_ends.len();
+    let fits = |line: usize| line > 0 && line_ends[line - 1] <= max_bytes;
+
+    let language = Language::detect(file_path, content);
+    let chunks = Chunker::new(max_bytes).chunk_file(content, file_path, language);
+    let cut = chunks
+        .iter()
+        .map(|c| c.metadata.end_line.min(total_lines))
+        .filter(|&end| fits(end))
+        .max()
+        // Nothing whole fits: fall back to the last complete line.
+        .unwrap_or_else(|| {
+            line_ends
+                .iter()
+                .take_while(|&&end| end <= max_bytes)
+                .count()
+        });
+
+    let mut out = content[..cut.checked_sub(1).map_or(0, |i| line_ends[i])].to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "[... truncated: showing lines 1-{} of {}]",
+        cut, total_lines
+    ));
+
+    // Pieces of one oversized definition share its name; list it once.
+    let mut omitted: Vec<(String, usize, usize)> = Vec::new();
+    for chunk in chunks.iter().filter(|c| c.metadata.end_line > cut) {
+        let (Some(name), Some(kind)) = (&chunk.metadata.symbol_name, chunk.metadata.symbol_type)
+        else {
+            continue;
+        };
+        let kind = format!("{:?}", kind).to_lowercase();
+        let label = format!("{} {}", kind, name);
+        let start = chunk.metadata.start_line.max(cut + 1);
+        match omitted.last_mut() {
+            Some((last, _, end)) if *last == label => *end = chunk.metadata.end_line,
+            _ => omitted.push((label, start, chunk.metadata.end_line)),
+        }
+    }
+    if !omitted.is_empty() {
+        out.push_str("\nNot shown:");
+        for (label, start, end) in omitted.iter().take(MAX_OMITTED_OUTLINE) {
+            out.push_str(&format!("\n- {} (lines {}-{})", label, start, end));
+        }
+        if omitted.len() > MAX_OMITTED_OUTLINE {
+            out.push_str(&format!(
+                "\n- ... and {} more",
+                omitted.len() - MAX_OMITTED_OUTLINE
+            ));
+        }
+    }
+    out
+}
+
+/// Split Markdown lines into heading sections as `(start, end, heading path)`, with
+/// 0-indexed `[start, end)` line ranges. Text before the first heading gets an empty path.
+fn prose_sections(lines: &[&str]) -> Vec<(usize, usize, Vec<String>)> {
+    let mut sections = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut start = 0;
+    let mut fence: Option<&str> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(marker) = fence {
+            if line.trim_start().starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = fence_marker(line) {
+            fence = Some(marker);
+            continue;
+        }
+        let Some((level, title)) = atx_heading(line) else {
+            continue;
+        };
+
+        if lines[start..i].iter().any(|l| !l.trim().is_empty()) {
+            let path = stack.iter().map(|(_, t)| t.clone()).collect();
+            sections.push((start, i, path));
+        }
+        while stack.last().is_some_and(|(l, _)| *l >= level) {
+            stack.pop();
+        }
+        stack.push((level, title));
+        start = i;
+    }
+
+    if lines[start..].iter().any(|l| !l.trim().is_empty()) {
+        let path = stack.into_iter().map(|(_, t)| t).collect();
+        sections.push((start, lines.len(), path));
+    }
+    sections
+}
+
+/// Blank-line separated blocks within `[start, end)`; fenced code blocks are kept whole.
+fn paragraphs(lines: &[&str], start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut from: Option<usize> = None;
+    let mut fence: Option<&str> = None;
+
+    for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+        if let Some(marker) = fence {
+            if line.trim_start().starts_with(marker) {
+                fence = None;
+            }
+        } else if let Some(marker) = fence_marker(line) {
+            fence = Some(marker);
+        } else if line.trim().is_empty() {
+            if let Some(f) = from.take() {
+                out.push((f, i));
+            }
+            continue;
+        }
+        from.get_or_insert(i);
+    }
+    if let Some(f) = from {
+        out.push((f, end));
+    }
+    out
+}
+
+fn fence_marker(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("```") {
+        Some("```")
+    } else if trimmed.starts_with("~~~") {
+        Some("~~~")
+    } else {
+        None
+    }
+}
+
+/// `## Title ##` -> `(2, "Title")`
+fn atx_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None; // indented code
+    }
+    let level = trimmed.bytes().take_while(|&b| b == b'#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None; // `#hashtag`
+    }
+    let title = rest.trim().trim_end_matches('#').trim();
+    (!title.is_empty()).then(|| (level, title.to_string()))
+}
+
+fn joined_len(lines: &[&str]) -> usize {
+    lines
+        .iter()
+        .map(|l| l.len() + 1)
+        .sum::<usize>()
+        .saturating_sub(1)
+}
+
 /// Get the tree-sitter language for a given Language enum
-fn get_tree_sitter_language(language: Language) -> Option<TSLanguage> {
+pub(crate) fn get_tree_sitter_language(language: Language) -> Option<TSLanguage> {
     match language {
         Language::Rust => Some(tree_sitter_rust::LANGUAGE.into()),
         Language::Python => Some(tree_sitter_python::LANGUAGE.into()),
@@ -272,12 +519,12 @@ fn get_tree_sitter_language(language: Language) -> Option<TSLanguage> {
         Language::Cpp => Some(tree_sitter_cpp::LANGUAGE.into()),
         Language::Java => Some(tree_sitter_java::LANGUAGE.into()),
         Language::Ruby => Some(tree_sitter_ruby::LANGUAGE.into()),
-        Language::Unknown => None,
+        Language::Markdown | Language::Unknown => None,
     }
 }
 
 /// Classify a tree-sitter node and extract its name
-fn classify_node(
+pub(crate) fn classify_node(
     node: &tree_sitter::Node,
     language: Language,
     content: &[u8],
@@ -292,7 +539,7 @@ fn classify_node(
         Language::C | Language::Cpp => classify_c_cpp_node(node, kind, content),
         Language::Java => classify_java_node(node, kind, content),
         Language::Ruby => classify_ruby_node(node, kind, content),
-        Language::Unknown => None,
+        Language::Markdown | Language::Unknown => None,
     }
 }
 
@@ -514,9 +761,50 @@ mod tests {
         assert_eq!(Language::from_extension("js"), Language::JavaScript);
         assert_eq!(Language::from_extension("ts"), Language::TypeScript);
         assert_eq!(Language::from_extension("go"), Language::Go);
+        assert_eq!(Language::from_extension("md"), Language::Markdown);
         assert_eq!(Language::from_extension("txt"), Language::Unknown);
     }
 
+    #[test]
+    fn test_language_detection_from_shebang_and_overrides() {
+        use crate::semantic_search::types::LanguageOverrides;
+        use std::collections::HashMap;
+
+        let script = Path::new("bin/deploy");
+        assert_eq!(
+            Language::detect(script, "#!/usr/bin/env python3\nprint(1)"),
+            Language::Python
+        );
+        assert_eq!(
+            Language::detect(script, "#!/usr/bin/env -S node --no-warnings"),
+            Language::JavaScript
+        );
+        assert_eq!(Language::detect(script, "#!/bin/bash"), Language::Unknown);
+        assert_eq!(Language::detect(Path::new("Rakefile"), ""), Language::Ruby);
+        // The extension wins over a misleading shebang.
+        assert_eq!(
+            Language::detect(Path::new("a.rs"), "#!/usr/bin/env python"),
+            Language::Rust
+        );
+
+        let map: HashMap<String, String> = [
+            ("bin/*".to_string(), "ruby".to_string()),
+            ("*.mjs.txt".to_string(), "javascript".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let overrides = LanguageOverrides::from_config(Some(&map));
+        assert_eq!(
+            overrides.detect(script, "#!/usr/bin/env python3"),
+            Language::Ruby
+        );
+        assert_eq!(
+            overrides.detect(Path::new("docs/x.mjs.txt"), ""),
+            Language::JavaScript
+        );
+        assert_eq!(overrides.detect(Path::new("x.py"), ""), Language::Python);
+    }
+
     #[test]
     fn test_chunker_rust() {
         let chunker = Chunker::new(2000);
@@ -553,4 +841,68 @@ class Foo:
         let chunks = chunker.chunk_file(content, Path::new("test.py"), Language::Python);
         assert!(!chunks.is_empty());
     }
+
+    #[test]
+    fn test_chunker_markdown_sections() {
+        let content = "Intro text.\n\n# Install\n\nGeneral steps.\n\n## Linux\n\n```sh\n# not a heading\napt install foo\n```\n\n## macOS\n\nbrew install foo\n\n# Usage\n\nRun it.\n";
+        let chunker = Chunker::new(2000);
+        let chunks = chunker.chunk_file(content, Path::new("README.md"), Language::Markdown);
+
+        let names: Vec<Option<&str>> = chunks
+            .iter()
+            .map(|c| c.metadata.symbol_name.as_deref())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                None,
+                Some("Install"),
+                Some("Install > Linux"),
+                Some("Install > macOS"),
+                Some("Usage"),
+            ]
+        );
+        let linux = &chunks[2];
+        assert_eq!(linux.metadata.symbol_type, Some(SymbolType::Section));
+        assert!(linux.content.contains("apt install foo"));
+        // Line ranges point back into the file so results can be re-read from disk.
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines[linux.metadata.start_line - 1..linux.metadata.end_line].join("\n"),
+            linux.content
+        );
+        assert!(linux
+            .embedding_text()
+            .starts_with("File: README.md\nSection: Install > Linux\n"));
+
+        // Long sections split between paragraphs, keeping the heading path.
+        let long = format!("# Guide\n\n{}\n\n{}\n", "a".repeat(60), "b".repeat(60));
+        let chunks = Chunker::new(100).chunk_file(&long, Path::new("g.md"), Language::Markdown);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[1].content.starts_with('b'));
+        assert_eq!(chunks[1].metadata.symbol_name.as_deref(), Some("Guide"));
+    }
+
+    #[test]
+    fn truncation_stops_at_definition_boundaries_and_lists_the_rest() {
+        let content = "use std::fmt;\n\nfn first() {\n    one();\n}\n\nfn second() {\n    two();\n}\n\nstruct Third {\n    field: u32,\n}\n\nfn fourth() {\n    four();\n}\n";
+        let path = Path::new("lib.rs");
+        assert_eq!(truncate_at_boundaries(content, path, 10_000), content);
+
+        // Room for `second` plus a few bytes of `Third`, which must not leak in.
+        let budget = content.find("struct Third").unwrap() + 10;
+        let out = truncate_at_boundaries(content, path, budget);
+        assert!(out.contains("fn second() {\n    two();\n}\n"));
+        assert!(!out.contains("field: u32"));
+        assert!(out.contains("[... truncated: showing lines 1-9 of 17]"));
+        assert!(out.contains("- struct Third (lines 11-13)"));
+        assert!(out.contains("- function fourth (lines 15-17)"));
+
+        // Plain text without symbols still ends on a whole line.
+        let text = "alpha\nbeta\ngamma\n";
+        assert_eq!(
+            truncate_at_boundaries(text, Path::new("notes.txt"), 13),
+            "alpha\nbeta\n[... truncated: showing lines 1-2 of 3]"
+        );
+    }
 }
@@ -1,14 +1,31 @@
 use std::{path::PathBuf, sync::Arc};
 
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use ort::execution_providers::ExecutionProviderDispatch;
 use parking_lot::RwLock;
+use serde_json::{json, Value};
 
-/// Wrapper around fastembed for generating text embeddings
+use crate::semantic_search::types::{EmbedderBackend, EmbedderConfig, EmbedderDevice};
+
+const OPENAI_EMBEDDINGS_URL: &str = "https://api.openai.com/v1";
+const OLLAMA_URL: &str = "http://localhost:11434";
+
+/// A source of text embeddings. Implementations must be cheap to share across threads.
+pub trait EmbeddingBackend: Send + Sync {
+    /// Stable identifier ("backend/model"), persisted next to the index.
+    fn id(&self) -> String;
+    /// Dimension of the vectors this backend produces.
+    fn dimension(&self) -> usize;
+    /// Embed a non-empty batch of texts.
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedderError>;
+}
+
+/// Wrapper around an embedding backend for generating text embeddings
 pub struct Embedder {
-    model: Arc<RwLock<TextEmbedding>>,
+    backend: Arc<dyn EmbeddingBackend>,
 }
 
-fn default_fastembed_cache_dir() -> PathBuf {
+pub(crate) fn default_fastembed_cache_dir() -> PathBuf {
     // Keep it consistent with other Lorikeet user-level state (config/index) under ~/.lorikeet.
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -18,35 +35,33 @@ fn default_fastembed_cache_dir() -> PathBuf {
 }
 
 impl Embedder {
-    /// Create a new embedder with the AllMiniLML6V2 model
+    /// Create a new embedder with the default local model (AllMiniLML6V2)
     /// This will download the model on first use (~22MB)
     pub fn new() -> Result<Self, EmbedderError> {
-        // fastembed defaults to a relative `.fastembed_cache` directory, which causes per-repo
-        // downloads when the CWD changes. Force a global Lorikeet cache dir instead.
-        let cache_dir = default_fastembed_cache_dir();
-        std::fs::create_dir_all(&cache_dir).map_err(|e| {
-            EmbedderError::ModelInit(format!(
-                "Failed to create cache dir {}: {e}",
-                cache_dir.display()
-            ))
-        })?;
+        Self::from_config(&EmbedderConfig::default())
+    }
 
-        let model = TextEmbedding::try_new(
-            InitOptions::new(EmbeddingModel::AllMiniLML6V2)
-                .with_cache_dir(cache_dir)
-                // Prevent progress bars / file names from writing to stdout/stderr and corrupting the TUI.
-                .with_show_download_progress(false),
-        )
-        .map_err(|e| EmbedderError::ModelInit(e.to_string()))?;
+    /// Create an embedder for the configured backend
+    pub fn from_config(config: &EmbedderConfig) -> Result<Self, EmbedderError> {
+        let backend: Arc<dyn EmbeddingBackend> = match config.backend {
+            EmbedderBackend::Fastembed => Arc::new(FastembedBackend::new(
+                config.model.as_deref(),
+                config.device,
+            )?),
+            EmbedderBackend::OpenAI => Arc::new(HttpBackend::openai(config)?),
+            EmbedderBackend::Ollama => Arc::new(HttpBackend::ollama(config)?),
+        };
+        Ok(Self { backend })
+    }
 
-        Ok(Self {
-            model: Arc::new(RwLock::new(model)),
-        })
+    /// Stable identifier of the backend/model producing the embeddings
+    pub fn backend_id(&self) -> String {
+        self.backend.id()
     }
 
     /// Get the embedding dimension for this model
     pub fn dimension(&self) -> usize {
-        384 // AllMiniLML6V2 produces 384-dimensional embeddings
+        self.backend.dimension()
     }
 
     /// Generate embeddings for a batch of texts
@@ -55,10 +70,7 @@ impl Embedder {
             return Ok(vec![]);
         }
 
-        let model = self.model.read();
-        model
-            .embed(texts.to_vec(), None)
-            .map_err(|e| EmbedderError::Embedding(e.to_string()))
+        self.backend.embed_batch(texts)
     }
 
     /// Generate embedding for a single text
@@ -96,13 +108,344 @@ impl Embedder {
 impl Clone for Embedder {
     fn clone(&self) -> Self {
         Self {
-            model: Arc::clone(&self.model),
+            backend: Arc::clone(&self.backend),
+        }
+    }
+}
+
+/// Local ONNX embedding models via fastembed
+struct FastembedBackend {
+    model: RwLock<TextEmbedding>,
+    name: &'static str,
+    dimension: usize,
+}
+
+impl FastembedBackend {
+    fn new(model: Option<&str>, device: EmbedderDevice) -> Result<Self, EmbedderError> {
+        let (variant, name, dimension) = match model.map(|m| m.trim().to_lowercase()).as_deref() {
+            None | Some("all-minilm-l6-v2") => {
+                (EmbeddingModel::AllMiniLML6V2, "all-minilm-l6-v2", 384)
+            }
+            Some("bge-small-en-v1.5") => (EmbeddingModel::BGESmallENV15, "bge-small-en-v1.5", 384),
+            Some("bge-base-en-v1.5") => (EmbeddingModel::BGEBaseENV15, "bge-base-en-v1.5", 768),
+            Some("nomic-embed-text-v1.5") => (
+                EmbeddingModel::NomicEmbedTextV15,
+                "nomic-embed-text-v1.5",
+                768,
+            ),
+            Some(other) => {
+                return Err(EmbedderError::ModelInit(format!(
+                    "Unknown fastembed model '{}' (try all-minilm-l6-v2, bge-small-en-v1.5, bge-base-en-v1.5, nomic-embed-text-v1.5)",
+                    other
+                )))
+            }
+        };
+
+        // fastembed defaults to a relative `.fastembed_cache` directory, which causes per-repo
+        // downloads when the CWD changes. Force a global Lorikeet cache dir instead.
+        let cache_dir = default_fastembed_cache_dir();
+        std::fs::create_dir_all(&cache_dir).map_err(|e| {
+            EmbedderError::ModelInit(format!(
+                "Failed to create cache dir {}: {e}",
+                cache_dir.display()
+            ))
+        })?;
+
+        let model = TextEmbedding::try_new(
+            InitOptions::new(variant)
+                .with_cache_dir(cache_dir)
+                // Prevent progress bars / file names from writing to stdout/stderr and corrupting the TUI.
+                .with_show_download_progress(false)
+                .with_execution_providers(execution_providers(device)?),
+        )
+        .map_err(|e| EmbedderError::ModelInit(e.to_string()))?;
+
+        Ok(Self {
+            model: RwLock::new(model),
+            name,
+            dimension,
+        })
+    }
+}
+
+impl EmbeddingBackend for FastembedBackend {
+    fn id(&self) -> String {
+        format!("fastembed/{}", self.name)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedderError> {
+        let model = self.model.read();
+        model
+            .embed(texts.to_vec(), None)
+            .map_err(|e| EmbedderError::Embedding(e.to_string()))
+    }
+}
+
+/// ONNX Runtime execution providers for the requested device.
+///
+/// An explicitly requested accelerator must register or model init fails, so a
+/// misconfigured GPU never silently degrades to CPU. `Auto` tries whatever was compiled
+/// in and lets ONNX Runtime fall back to CPU.
+fn execution_providers(
+    device: EmbedderDevice,
+) -> Result<Vec<ExecutionProviderDispatch>, EmbedderError> {
+    #[allow(unused_variables)]
+    let not_built = |feature: &str| {
+        EmbedderError::ModelInit(format!(
+            "embeddings device '{feature}' requires building lorikeet with `--features {feature}`"
+        ))
+    };
+
+    match device {
+        EmbedderDevice::Cpu => Ok(Vec::new()),
+        EmbedderDevice::Cuda(_id) => {
+            #[cfg(feature = "cuda")]
+            {
+                use ort::execution_providers::CUDAExecutionProvider;
+                Ok(vec![CUDAExecutionProvider::default()
+                    .with_device_id(_id)
+                    .build()
+                    .error_on_failure()])
+            }
+            #[cfg(not(feature = "cuda"))]
+            Err(not_built("cuda"))
+        }
+        EmbedderDevice::CoreML => {
+            #[cfg(feature = "coreml")]
+            {
+                use ort::execution_providers::CoreMLExecutionProvider;
+                Ok(vec![CoreMLExecutionProvider::default()
+                    .build()
+                    .error_on_failure()])
+            }
+            #[cfg(not(feature = "coreml"))]
+            Err(not_built("coreml"))
+        }
+        EmbedderDevice::DirectML => {
+            #[cfg(feature = "directml")]
+            {
+                use ort::execution_providers::DirectMLExecutionProvider;
+                Ok(vec![DirectMLExecutionProvider::default()
+                    .build()
+                    .error_on_failure()])
+            }
+            #[cfg(not(feature = "directml"))]
+            Err(not_built("directml"))
+        }
+        EmbedderDevice::Auto => {
+            #[allow(unused_mut)]
+            let mut providers: Vec<ExecutionProviderDispatch> = Vec::new();
+            #[cfg(feature = "cuda")]
+            providers.push(ort::execution_providers::CUDAExecutionProvider::default().build());
+            #[cfg(feature = "coreml")]
+            providers.push(ort::execution_providers::CoreMLExecutionProvider::default().build());
+            #[cfg(feature = "directml")]
+            providers.push(ort::execution_providers::DirectMLExecutionProvider::default().build());
+            Ok(providers)
+        }
+    }
+}
+
+/// Remote embedding APIs (OpenAI-compatible and Ollama)
+struct HttpBackend {
+    kind: EmbedderBackend,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    dimension: usize,
+    /// Shared by every request, so indexing reuses its connections
+    client: reqwest::blocking::Client,
+}
+
+impl HttpBackend {
+    fn openai(config: &EmbedderConfig) -> Result<Self, EmbedderError> {
+        let api_key = std::env::var("OPENAI_API_KEY")
+            .ok()
+            .filter(|k| !k.trim().is_empty())
+            .ok_or_else(|| {
+                EmbedderError::ModelInit("OPENAI_API_KEY is required for openai embeddings".into())
+            })?;
+        let model = config
+            .model
+            .clone()
+            .unwrap_or_else(|| "text-embedding-3-small".to_string());
+        let known = match model.as_str() {
+            "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+            "text-embedding-3-large" => Some(3072),
+            _ => None,
+        };
+        Self::connect(
+            EmbedderBackend::OpenAI,
+            config
+                .base_url
+                .clone()
+                .unwrap_or_else(|| OPENAI_EMBEDDINGS_URL.to_string()),
+            model,
+            Some(api_key),
+            config.dimension.or(known),
+        )
+    }
+
+    fn ollama(config: &EmbedderConfig) -> Result<Self, EmbedderError> {
+        Self::connect(
+            EmbedderBackend::Ollama,
+            config
+                .base_url
+                .clone()
+                .unwrap_or_else(|| OLLAMA_URL.to_string()),
+            config
+                .model
+                .clone()
+                .unwrap_or_else(|| "nomic-embed-text".to_string()),
+            None,
+            config.dimension,
+        )
+    }
+
+    fn connect(
+        kind: EmbedderBackend,
+        base_url: String,
+        model: String,
+        api_key: Option<String>,
+        dimension: Option<usize>,
+    ) -> Result<Self, EmbedderError> {
+        // Built off the caller's thread: the blocking client can't start inside a runtime.
+        let client = std::thread::spawn(|| {
+            reqwest::blocking::Client::builder()
+                .timeout(std::time::Duration::from_secs(60))
+                .build()
+        })
+        .join()
+        .map_err(|_| EmbedderError::ModelInit("HTTP client setup panicked".into()))?
+        .map_err(|e| EmbedderError::ModelInit(e.to_string()))?;
+        let mut backend = Self {
+            kind,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            api_key,
+            dimension: dimension.unwrap_or(0),
+            client,
+        };
+        if backend.dimension == 0 {
+            // Unknown model: ask the server once so the index can be sized correctly.
+            let probe = backend
+                .embed_batch(&["dimension probe".to_string()])
+                .map_err(|e| EmbedderError::ModelInit(e.to_string()))?;
+            backend.dimension = probe.first().map(|v| v.len()).unwrap_or(0);
+            if backend.dimension == 0 {
+                return Err(EmbedderError::ModelInit(
+                    "Embedding backend returned an empty vector".into(),
+                ));
+            }
+        }
+        Ok(backend)
+    }
+
+    fn request(&self, texts: &[String]) -> Result<Value, EmbedderError> {
+        let (url, body) = match self.kind {
+            EmbedderBackend::Ollama => (
+                format!("{}/api/embed", self.base_url),
+                json!({ "model": self.model, "input": texts }),
+            ),
+            _ => (
+                format!("{}/embeddings", self.base_url),
+                json!({ "model": self.model, "input": texts }),
+            ),
+        };
+        let api_key = self.api_key.clone();
+        let client = self.client.clone();
+
+        // Indexing and memory recall call us from both blocking tasks and async contexts;
+        // a dedicated thread keeps reqwest's blocking client away from the tokio runtime.
+        // The client (and its connection pool) is shared, only the thread is per batch.
+        std::thread::spawn(move || -> Result<Value, EmbedderError> {
+            let mut req = client.post(&url).json(&body);
+            if let Some(key) = api_key {
+                req = req.bearer_auth(key);
+            }
+            let resp = req
+                .send()
+                .map_err(|e| EmbedderError::Embedding(e.to_string()))?;
+            let status = resp.status();
+            let text = resp
+                .text()
+                .map_err(|e| EmbedderError::Embedding(e.to_string()))?;
+            if !status.is_success() {
+                return Err(EmbedderError::Embedding(format!(
+                    "HTTP {}: {}",
+                    status, text
+                )));
+            }
+            serde_json::from_str(&text).map_err(|e| EmbedderError::Embedding(e.to_string()))
+        })
+        .join()
+        .map_err(|_| EmbedderError::Embedding("embedding request panicked".into()))?
+    }
+}
+
+impl EmbeddingBackend for HttpBackend {
+    fn id(&self) -> String {
+        format!("{}/{}", self.kind.as_str(), self.model)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedderError> {
+        let resp = self.request(texts)?;
+        let vectors = match self.kind {
+            EmbedderBackend::Ollama => parse_ollama_embeddings(&resp),
+            _ => parse_openai_embeddings(&resp),
+        };
+        if vectors.len() != texts.len() {
+            return Err(EmbedderError::Embedding(format!(
+                "expected {} embeddings, got {}",
+                texts.len(),
+                vectors.len()
+            )));
         }
+        Ok(vectors)
     }
 }
 
+fn json_vector(v: &Value) -> Option<Vec<f32>> {
+    v.as_array()?
+        .iter()
+        .map(|x| x.as_f64().map(|f| f as f32))
+        .collect()
+}
+
+fn parse_openai_embeddings(resp: &Value) -> Vec<Vec<f32>> {
+    let Some(data) = resp.get("data").and_then(|d| d.as_array()) else {
+        return Vec::new();
+    };
+    let mut items: Vec<(u64, Vec<f32>)> = data
+        .iter()
+        .filter_map(|item| {
+            let index = item.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            Some((index, json_vector(item.get("embedding")?)?))
+        })
+        .collect();
+    items.sort_by_key(|(i, _)| *i);
+    items.into_iter().map(|(_, v)| v).collect()
+}
+
+fn parse_ollama_embeddings(resp: &Value) -> Vec<Vec<f32>> {
+    resp.get("embeddings")
+        .and_then(|e| e.as_array())
+        .map(|arr| arr.iter().filter_map(json_vector).collect())
+        .unwrap_or_default()
+}
+
 #[derive(Debug)]
 pub enum EmbedderError {
+    /// `[embeddings]` asks for something that doesn't exist
+    Config(String),
     ModelInit(String),
     Embedding(String),
 }
@@ -110,6 +453,7 @@ pub enum EmbedderError {
 impl std::fmt::Display for EmbedderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            EmbedderError::Config(msg) => write!(f, "Invalid [embeddings] config: {}", msg),
             EmbedderError::ModelInit(msg) => {
                 write!(f, "Failed to initialize embedding model: {}", msg)
             }
@@ -124,6 +468,60 @@ impl std::error::Error for EmbedderError {}
 mod tests {
     use super::*;
 
+    #[test]
+    fn device_parsing_and_cpu_providers() {
+        assert_eq!(
+            EmbedderDevice::parse("CUDA:1"),
+            Some(EmbedderDevice::Cuda(1))
+        );
+        assert_eq!(EmbedderDevice::parse("gpu"), Some(EmbedderDevice::Cuda(0)));
+        assert_eq!(EmbedderDevice::parse("tpu"), None);
+        assert!(execution_providers(EmbedderDevice::Cpu).unwrap().is_empty());
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    #[test]
+    fn cuda_without_feature_explains_how_to_enable_it() {
+        let err = execution_providers(EmbedderDevice::Cuda(0)).unwrap_err();
+        assert!(err.to_string().contains("--features cuda"));
+    }
+
+    #[test]
+    fn parses_openai_embeddings_in_index_order() {
+        let resp = json!({
+            "data": [
+                { "index": 1, "embedding": [0.0, 1.0] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ]
+        });
+        let vectors = parse_openai_embeddings(&resp);
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+
+    #[test]
+    fn parses_ollama_embeddings() {
+        let resp = json!({ "embeddings": [[0.5, 0.25, 0.0]] });
+        assert_eq!(parse_ollama_embeddings(&resp), vec![vec![0.5, 0.25, 0.0]]);
+    }
+
+    #[test]
+    fn misspelled_backends_are_a_config_error() {
+        let settings = |backend: &str| crate::config::EmbeddingsConfig {
+            backend: Some(backend.into()),
+            ..Default::default()
+        };
+        let err = EmbedderConfig::from_settings(&settings("opnai")).unwrap_err();
+        assert!(err.contains("'opnai'"), "{}", err);
+        assert!(err.contains("fastembed, openai or ollama"), "{}", err);
+        let config = EmbedderConfig::from_settings(&settings(" Ollama ")).unwrap();
+        assert_eq!(config.backend, EmbedderBackend::Ollama);
+        let default = crate::config::EmbeddingsConfig::default();
+        assert_eq!(
+            EmbedderConfig::from_settings(&default).unwrap().backend,
+            EmbedderBackend::Fastembed
+        );
+    }
+
     #[test]
     #[ignore] // Requires model download
     fn test_embedder_creation() {
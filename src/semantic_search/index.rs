@@ -6,30 +6,138 @@ use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use usearch::{Index, IndexOptions, MetricKind, ScalarKind};
 
-use crate::semantic_search::types::{ChunkMetadata, CodeChunk, IndexStats};
-
-const INDEX_FILE: &str = "index.bin";
+use crate::semantic_search::types::{
+    ChunkMetadata, CodeChunk, IndexKind, IndexStats, Quantization,
+};
+
+pub(crate) const INDEX_FILE: &str = "index.bin";
+/// Serialized HNSW graph, saved next to index.bin so loading skips the rebuild.
+const GRAPH_FILE: &str = "index.usearch";
+/// Prefix of the current index.bin format; files without it are the original f32-only layout.
+const INDEX_MAGIC: &[u8; 8] = b"LKIDX\x00\x00\x02";
+/// Vectors whose int8 round-trip drifts below this cosine similarity are kept as f32.
+const MIN_QUANTIZED_FIDELITY: f32 = 0.995;
+
+/// Original on-disk layout (f32 only)
+#[derive(Deserialize)]
+struct IndexDataV1 {
+    vectors: Vec<(u64, Vec<f32>)>,
+    metadata: HashMap<u64, ChunkMetadata>,
+}
 
 /// Serializable index data
 #[derive(Serialize, Deserialize)]
 struct IndexData {
-    vectors: Vec<(u64, Vec<f32>)>,
+    vectors: Vec<(u64, StoredVector)>,
     metadata: HashMap<u64, ChunkMetadata>,
 }
 
-/// Vector index using usearch (HNSW algorithm)
+/// A stored embedding, either full precision or symmetric int8 with a per-vector scale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum StoredVector {
+    F32(Vec<f32>),
+    Int8 { scale: f32, values: Vec<i8> },
+}
+
+impl StoredVector {
+    /// Encode `v` in the requested precision, keeping f32 when int8 would lose too much.
+    fn encode(v: &[f32], quantization: Quantization) -> Self {
+        if quantization == Quantization::F32 {
+            return Self::F32(v.to_vec());
+        }
+        let max_abs = v.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        if max_abs == 0.0 || !max_abs.is_finite() {
+            return Self::F32(v.to_vec());
+        }
+        let scale = max_abs / 127.0;
+        let values: Vec<i8> = v
+            .iter()
+            .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+            .collect();
+        let quantized = Self::Int8 { scale, values };
+        if cosine_similarity(v, &quantized.to_f32()) < MIN_QUANTIZED_FIDELITY {
+            return Self::F32(v.to_vec());
+        }
+        quantized
+    }
+
+    fn to_f32(&self) -> Vec<f32> {
+        match self {
+            Self::F32(v) => v.clone(),
+            Self::Int8 { scale, values } => values.iter().map(|&q| q as f32 * scale).collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::F32(v) => v.len(),
+            Self::Int8 { values, .. } => values.len(),
+        }
+    }
+}
+
+/// A query prepared once per flat scan for both stored precisions
+struct ScanQuery<'a> {
+    f32: &'a [f32],
+    /// Quantized query; cosine is scale-invariant, so int8·int8 needs no dequantization
+    int8: Option<Vec<i8>>,
+}
+
+impl<'a> ScanQuery<'a> {
+    fn new(query: &'a [f32]) -> Self {
+        let int8 = match StoredVector::encode(query, Quantization::Int8) {
+            StoredVector::Int8 { values, .. } => Some(values),
+            StoredVector::F32(_) => None,
+        };
+        Self { f32: query, int8 }
+    }
+
+    fn score(&self, stored: &StoredVector) -> f32 {
+        match (stored, &self.int8) {
+            (StoredVector::Int8 { values, .. }, Some(q)) => cosine_similarity_i8(q, values),
+            (StoredVector::F32(v), _) => cosine_similarity(self.f32, v),
+            (int8, None) => cosine_similarity(self.f32, &int8.to_f32()),
+        }
+    }
+}
+
+/// Vector index using usearch (HNSW algorithm), or an exact flat scan
 pub struct VectorIndex {
     index: RwLock<Index>,
     metadata: RwLock<HashMap<u64, ChunkMetadata>>,
-    vectors: RwLock<HashMap<u64, Vec<f32>>>, // Store vectors for persistence
+    vectors: RwLock<HashMap<u64, StoredVector>>, // Store vectors for persistence
     index_dir: PathBuf,
     dimension: usize,
+    kind: IndexKind,
+    quantization: Quantization,
     next_id: RwLock<u64>,
 }
 
 impl VectorIndex {
-    /// Create a new vector index or load an existing one
+    /// Create a new HNSW vector index or load an existing one
     pub fn new(index_dir: &Path, dimension: usize) -> Result<Self, IndexError> {
+        Self::with_kind(index_dir, dimension, IndexKind::Hnsw)
+    }
+
+    /// Create a vector index of the given kind or load an existing one
+    pub fn with_kind(
+        index_dir: &Path,
+        dimension: usize,
+        kind: IndexKind,
+    ) -> Result<Self, IndexError> {
+        Self::with_options(index_dir, dimension, kind, Quantization::F32)
+    }
+
+    /// Create a vector index with the given kind and vector storage precision.
+    ///
+    /// Existing vectors keep the precision they were written with; `quantization` applies
+    /// to vectors added from now on (a re-index converts everything).
+    pub fn with_options(
+        index_dir: &Path,
+        dimension: usize,
+        kind: IndexKind,
+        quantization: Quantization,
+    ) -> Result<Self, IndexError> {
         fs::create_dir_all(index_dir).map_err(|e| IndexError::Io(e.to_string()))?;
 
         let index_path = index_dir.join(INDEX_FILE);
@@ -37,7 +145,10 @@ impl VectorIndex {
         let options = IndexOptions {
             dimensions: dimension,
             metric: MetricKind::Cos,
-            quantization: ScalarKind::F32,
+            quantization: match quantization {
+                Quantization::F32 => ScalarKind::F32,
+                Quantization::Int8 => ScalarKind::I8,
+            },
             connectivity: 16,     // HNSW M parameter
             expansion_add: 128,   // ef_construction
             expansion_search: 64, // ef_search
@@ -57,6 +168,8 @@ impl VectorIndex {
             vectors: RwLock::new(HashMap::new()),
             index_dir: index_dir.to_path_buf(),
             dimension,
+            kind,
+            quantization,
             next_id: RwLock::new(0),
         };
 
@@ -85,7 +198,7 @@ impl VectorIndex {
         };
 
         // Try to add to the usearch index, expand capacity if needed
-        {
+        if self.kind == IndexKind::Hnsw {
             let index = self.index.write();
             let current_size = index.size();
             let current_capacity = index.capacity();
@@ -112,14 +225,14 @@ impl VectorIndex {
 
         {
             let mut vectors = self.vectors.write();
-            vectors.insert(id, embedding.to_vec());
+            vectors.insert(id, StoredVector::encode(embedding, self.quantization));
         }
 
         Ok(id)
     }
 
     /// Get all stored vectors (for persistence)
-    fn get_all_vectors(&self) -> Vec<(u64, Vec<f32>)> {
+    fn get_all_vectors(&self) -> Vec<(u64, StoredVector)> {
         let vectors = self.vectors.read();
         vectors.iter().map(|(k, v)| (*k, v.clone())).collect()
     }
@@ -141,7 +254,7 @@ impl VectorIndex {
         let mut ids = Vec::with_capacity(chunks.len());
 
         // Reserve capacity
-        {
+        if self.kind == IndexKind::Hnsw {
             let index = self.index.write();
             let needed = index.size() + chunks.len();
             if needed > index.capacity() {
@@ -172,6 +285,10 @@ impl VectorIndex {
             });
         }
 
+        if self.kind == IndexKind::Flat {
+            return Ok(self.search_flat(query_embedding, top_k));
+        }
+
         let index = self.index.read();
         if index.size() == 0 {
             return Ok(vec![]);
@@ -193,6 +310,25 @@ impl VectorIndex {
             .collect())
     }
 
+    /// Exact top-k by scanning every stored vector
+    fn search_flat(&self, query_embedding: &[f32], top_k: usize) -> Vec<(u64, f32)> {
+        let vectors = self.vectors.read();
+        let query = ScanQuery::new(query_embedding);
+        let mut scored: Vec<(u64, f32)> = vectors
+            .iter()
+            .map(|(id, v)| (*id, query.score(v)))
+            .collect();
+        let by_score_desc = |a: &(u64, f32), b: &(u64, f32)| {
+            b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal)
+        };
+        if scored.len() > top_k && top_k > 0 {
+            scored.select_nth_unstable_by(top_k - 1, by_score_desc);
+        }
+        scored.truncate(top_k);
+        scored.sort_by(by_score_desc);
+        scored
+    }
+
     /// Get metadata for a chunk by ID
     pub fn get_metadata(&self, id: u64) -> Option<ChunkMetadata> {
         let metadata = self.metadata.read();
@@ -209,10 +345,22 @@ impl VectorIndex {
             metadata: metadata.clone(),
         };
 
-        let serialized =
-            bincode::serialize(&data).map_err(|e| IndexError::Serialization(e.to_string()))?;
+        let mut serialized = INDEX_MAGIC.to_vec();
+        bincode::serialize_into(&mut serialized, &data)
+            .map_err(|e| IndexError::Serialization(e.to_string()))?;
         fs::write(&index_path, serialized).map_err(|e| IndexError::Io(e.to_string()))?;
 
+        let graph_path = self.index_dir.join(GRAPH_FILE);
+        if self.kind == IndexKind::Hnsw {
+            // Best-effort: without the graph file, load() simply rebuilds from vectors.
+            let index = self.index.read();
+            if index.save(&graph_path.to_string_lossy()).is_err() {
+                let _ = fs::remove_file(&graph_path);
+            }
+        } else if graph_path.exists() {
+            let _ = fs::remove_file(&graph_path);
+        }
+
         Ok(())
     }
 
@@ -222,12 +370,19 @@ impl VectorIndex {
 
         if index_path.exists() {
             let data = fs::read(&index_path).map_err(|e| IndexError::Io(e.to_string()))?;
-            let index_data: IndexData = bincode::deserialize(&data)
-                .map_err(|e| IndexError::Serialization(e.to_string()))?;
+            let index_data = decode_index_data(&data)?;
 
-            // Rebuild the usearch index from stored vectors
+            // Rebuild the usearch index from stored vectors, unless the saved graph matches
             let mut loaded_count = 0;
-            {
+            if self.kind == IndexKind::Flat {
+                loaded_count = index_data
+                    .vectors
+                    .iter()
+                    .filter(|(_, v)| v.len() == self.dimension)
+                    .count();
+            } else if self.load_graph(index_data.vectors.len()) {
+                loaded_count = index_data.vectors.len();
+            } else {
                 let index = self.index.write();
                 if !index_data.vectors.is_empty() {
                     // Reserve with some extra capacity
@@ -235,7 +390,7 @@ impl VectorIndex {
                 }
                 for (id, vector) in &index_data.vectors {
                     // Skip vectors that fail to load
-                    if index.add(*id, vector).is_ok() {
+                    if index.add(*id, &vector.to_f32()).is_ok() {
                         loaded_count += 1;
                     }
                 }
@@ -272,6 +427,23 @@ impl VectorIndex {
         Ok(())
     }
 
+    /// Load the persisted HNSW graph if it is consistent with `expected` vectors.
+    fn load_graph(&self, expected: usize) -> bool {
+        let graph_path = self.index_dir.join(GRAPH_FILE);
+        if expected == 0 || !graph_path.exists() {
+            return false;
+        }
+        let index = self.index.write();
+        let ok = index.load(&graph_path.to_string_lossy()).is_ok()
+            && index.size() == expected
+            && index.dimensions() == self.dimension;
+        if !ok {
+            let _ = index.reset();
+            let _ = index.reserve(10000);
+        }
+        ok
+    }
+
     /// Clear the index
     pub fn clear(&self) -> Result<(), IndexError> {
         {
@@ -294,10 +466,12 @@ impl VectorIndex {
             *next_id = 0;
         }
 
-        // Remove index file
-        let index_path = self.index_dir.join(INDEX_FILE);
-        if index_path.exists() {
-            fs::remove_file(&index_path).map_err(|e| IndexError::Io(e.to_string()))?;
+        // Remove index files
+        for file in [INDEX_FILE, GRAPH_FILE] {
+            let path = self.index_dir.join(file);
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| IndexError::Io(e.to_string()))?;
+            }
         }
 
         Ok(())
@@ -305,7 +479,6 @@ impl VectorIndex {
 
     /// Get index statistics
     pub fn stats(&self) -> IndexStats {
-        let index = self.index.read();
         let metadata = self.metadata.read();
 
         let mut languages = std::collections::HashMap::new();
@@ -322,7 +495,7 @@ impl VectorIndex {
         };
 
         IndexStats {
-            total_chunks: index.size(),
+            total_chunks: self.size(),
             total_files: files.len(),
             index_size_bytes: index_size,
             languages,
@@ -331,15 +504,74 @@ impl VectorIndex {
 
     /// Check if the index is empty
     pub fn is_empty(&self) -> bool {
-        let index = self.index.read();
-        index.size() == 0
+        self.size() == 0
     }
 
     /// Get the number of vectors in the index
-    #[allow(dead_code)]
     pub fn size(&self) -> usize {
-        let index = self.index.read();
-        index.size()
+        match self.kind {
+            IndexKind::Hnsw => self.index.read().size(),
+            IndexKind::Flat => self.vectors.read().len(),
+        }
+    }
+}
+
+fn decode_index_data(data: &[u8]) -> Result<IndexData, IndexError> {
+    let serialization = |e: bincode::Error| IndexError::Serialization(e.to_string());
+    match data.strip_prefix(INDEX_MAGIC.as_slice()) {
+        Some(body) => bincode::deserialize(body).map_err(serialization),
+        None => {
+            let v1: IndexDataV1 = bincode::deserialize(data).map_err(serialization)?;
+            Ok(IndexData {
+                vectors: v1
+                    .vectors
+                    .into_iter()
+                    .map(|(id, v)| (id, StoredVector::F32(v)))
+                    .collect(),
+                metadata: v1.metadata,
+            })
+        }
+    }
+}
+
+fn cosine_similarity_i8(a: &[i8], b: &[i8]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    // |x|,|y| <= 127, so i32 holds sums for any realistic embedding dimension.
+    let mut dot: i32 = 0;
+    let mut na: i32 = 0;
+    let mut nb: i32 = 0;
+    for (&x, &y) in a.iter().zip(b.iter()) {
+        let (x, y) = (x as i32, y as i32);
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0 || nb == 0 {
+        0.0
+    } else {
+        (dot as f64 / ((na as f64).sqrt() * (nb as f64).sqrt())) as f32
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let mut dot = 0.0;
+    let mut na = 0.0;
+    let mut nb = 0.0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    let denom = na.sqrt() * nb.sqrt();
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
     }
 }
 
@@ -446,4 +678,157 @@ mod tests {
             assert_eq!(index.size(), 1);
         }
     }
+
+    fn chunk(i: usize) -> CodeChunk {
+        CodeChunk {
+            id: 0,
+            content: String::new(),
+            metadata: ChunkMetadata {
+                file_path: PathBuf::from(format!("f{}.rs", i)),
+                start_line: 1,
+                end_line: 1,
+                language: Language::Rust,
+                symbol_name: None,
+                symbol_type: None,
+            },
+        }
+    }
+
+    /// Deterministic pseudo-random unit-ish vectors (no rand dependency).
+    fn random_vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        (0..n)
+            .map(|_| {
+                (0..dim)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 40) as f32 / (1u64 << 24) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flat_and_hnsw_agree_on_nearest_neighbor() {
+        let vectors = random_vectors(200, 8);
+        let flat_dir = TempDir::new().unwrap();
+        let hnsw_dir = TempDir::new().unwrap();
+        let flat = VectorIndex::with_kind(flat_dir.path(), 8, IndexKind::Flat).unwrap();
+        let hnsw = VectorIndex::with_kind(hnsw_dir.path(), 8, IndexKind::Hnsw).unwrap();
+        for (i, v) in vectors.iter().enumerate() {
+            flat.add(&chunk(i), v).unwrap();
+            hnsw.add(&chunk(i), v).unwrap();
+        }
+
+        let flat_hits = flat.search(&vectors[17], 3).unwrap();
+        let hnsw_hits = hnsw.search(&vectors[17], 3).unwrap();
+        assert_eq!(flat_hits[0].0, 17);
+        assert_eq!(hnsw_hits[0].0, 17);
+        assert!(flat_hits[0].1 >= flat_hits[1].1);
+    }
+
+    #[test]
+    fn int8_storage_shrinks_index_and_keeps_ranking() {
+        let dim = 64;
+        let vectors = random_vectors(300, dim);
+        let f32_dir = TempDir::new().unwrap();
+        let int8_dir = TempDir::new().unwrap();
+        let full = VectorIndex::with_kind(f32_dir.path(), dim, IndexKind::Flat).unwrap();
+        let quant =
+            VectorIndex::with_options(int8_dir.path(), dim, IndexKind::Flat, Quantization::Int8)
+                .unwrap();
+        for (i, v) in vectors.iter().enumerate() {
+            full.add(&chunk(i), v).unwrap();
+            quant.add(&chunk(i), v).unwrap();
+        }
+        full.save().unwrap();
+        quant.save().unwrap();
+
+        let size = |dir: &TempDir| fs::metadata(dir.path().join(INDEX_FILE)).unwrap().len();
+        assert!(size(&int8_dir) * 2 < size(&f32_dir));
+
+        // Reload to exercise dequantization-free scans over persisted int8 vectors.
+        let quant =
+            VectorIndex::with_options(int8_dir.path(), dim, IndexKind::Flat, Quantization::Int8)
+                .unwrap();
+        for q in [5, 42, 250] {
+            let exact = full.search(&vectors[q], 5).unwrap();
+            let approx = quant.search(&vectors[q], 5).unwrap();
+            assert_eq!(approx[0].0, q as u64);
+            assert!((exact[0].1 - approx[0].1).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn quantization_falls_back_to_f32_for_lossy_vectors() {
+        // One huge component flattens thousands of small ones to zero at int8 precision.
+        let mut spiky = vec![0.0035f32; 4096];
+        spiky[0] = 1.0;
+        assert!(matches!(
+            StoredVector::encode(&spiky, Quantization::Int8),
+            StoredVector::F32(_)
+        ));
+
+        let smooth: Vec<f32> = (0..32).map(|i| (i as f32 * 0.37).sin()).collect();
+        let stored = StoredVector::encode(&smooth, Quantization::Int8);
+        assert!(matches!(stored, StoredVector::Int8 { .. }));
+        assert!(cosine_similarity(&smooth, &stored.to_f32()) >= MIN_QUANTIZED_FIDELITY);
+    }
+
+    #[test]
+    fn legacy_f32_index_files_still_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut metadata = HashMap::new();
+        metadata.insert(7u64, chunk(7).metadata);
+        let legacy =
+            bincode::serialize(&(vec![(7u64, vec![0.0f32, 1.0, 0.0, 0.0])], metadata)).unwrap();
+        fs::write(temp_dir.path().join(INDEX_FILE), legacy).unwrap();
+
+        let index = VectorIndex::with_kind(temp_dir.path(), 4, IndexKind::Flat).unwrap();
+        assert_eq!(index.size(), 1);
+        assert_eq!(index.search(&[0.0, 1.0, 0.0, 0.0], 1).unwrap()[0].0, 7);
+    }
+
+    #[test]
+    fn hnsw_graph_is_persisted_and_reloaded() {
+        let temp_dir = TempDir::new().unwrap();
+        let vectors = random_vectors(50, 8);
+        {
+            let index = VectorIndex::new(temp_dir.path(), 8).unwrap();
+            for (i, v) in vectors.iter().enumerate() {
+                index.add(&chunk(i), v).unwrap();
+            }
+            index.save().unwrap();
+        }
+        assert!(temp_dir.path().join(GRAPH_FILE).exists());
+
+        let index = VectorIndex::new(temp_dir.path(), 8).unwrap();
+        assert_eq!(index.size(), 50);
+        assert_eq!(index.search(&vectors[3], 1).unwrap()[0].0, 3);
+    }
+
+    /// Benchmark: `cargo test --release bench_hnsw_100k -- --ignored --nocapture`
+    #[test]
+    #[ignore] // Slow: builds a 100k x 384 index
+    fn bench_hnsw_100k_top_k() {
+        let dim = 384;
+        let vectors = random_vectors(100_000, dim);
+        let temp_dir = TempDir::new().unwrap();
+        let index = VectorIndex::new(temp_dir.path(), dim).unwrap();
+        let chunks: Vec<CodeChunk> = (0..vectors.len()).map(chunk).collect();
+        index.add_batch(&chunks, &vectors).unwrap();
+
+        let queries = random_vectors(100, dim);
+        let start = std::time::Instant::now();
+        for q in &queries {
+            let hits = index.search(q, 10).unwrap();
+            assert_eq!(hits.len(), 10);
+        }
+        let per_query = start.elapsed() / queries.len() as u32;
+        println!("hnsw top-10 over 100k chunks: {:?}/query", per_query);
+        assert!(per_query < std::time::Duration::from_millis(10));
+    }
 }
@@ -1,23 +1,38 @@
+pub mod cache;
 pub mod chunker;
 pub mod embedder;
 pub mod index;
+pub mod reranker;
+pub mod symbols;
 pub mod types;
 
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use parking_lot::RwLock;
 use walkdir::WalkDir;
 
+use cache::{content_hash, EmbeddingCache};
 use chunker::Chunker;
 use embedder::{Embedder, EmbedderError};
 use index::{IndexError, VectorIndex};
-use types::{CodeChunk, IndexStats, Language, SearchResult};
+use reranker::Reranker;
+use symbols::{Symbol, SymbolTable};
+use types::{ChunkKind, CodeChunk, IndexProgress, IndexStats, Language, SearchResult};
+
+use crate::notebook::{self, CellKind, Notebook};
 
 // Re-export key types for external use
 pub use types::index_dir_for_workspace;
 pub use types::SearchConfig;
 
+/// File next to index.bin recording which embedder produced the vectors.
+const BACKEND_FILE: &str = "embedder";
+
+/// Candidates fetched per requested result when filtering by chunk kind, so a
+/// docs-heavy neighbourhood still leaves enough code (or the reverse).
+const KIND_OVERFETCH: usize = 5;
+
 /// Semantic search engine for code
 pub struct SemanticSearch {
     embedder: Embedder,
@@ -25,25 +40,57 @@ pub struct SemanticSearch {
     chunker: Chunker,
     config: SearchConfig,
     project_root: RwLock<Option<PathBuf>>,
+    /// Backend id of an existing index built by a different embedder (needs re-index).
+    stale_backend: RwLock<Option<String>>,
+    /// Lazily loaded cross-encoder; `None` inside means loading failed (skip reranking).
+    reranker: OnceLock<Option<Reranker>>,
+    /// Embeddings of previously seen chunk texts; `None` if the cache can't be opened.
+    cache: Option<EmbeddingCache>,
 }
 
 impl SemanticSearch {
     /// Create a new semantic search engine
     pub fn new(config: SearchConfig) -> Result<Self, SemanticSearchError> {
-        let embedder = Embedder::new().map_err(SemanticSearchError::Embedder)?;
-        let index = VectorIndex::new(&config.index_dir, embedder.dimension())
-            .map_err(SemanticSearchError::Index)?;
+        let embedder = config
+            .embedder
+            .as_ref()
+            .map_err(|e| EmbedderError::Config(e.clone()))
+            .and_then(Embedder::from_config)
+            .map_err(SemanticSearchError::Embedder)?;
+        let index = VectorIndex::with_options(
+            &config.index_dir,
+            embedder.dimension(),
+            config.index_kind,
+            config.quantization,
+        )
+        .map_err(SemanticSearchError::Index)?;
         let chunker = Chunker::new(config.max_chunk_size);
 
+        // Indexes written before backends were configurable carry no marker; they were
+        // always built with the default local model.
+        let marker = std::fs::read_to_string(config.index_dir.join(BACKEND_FILE))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "fastembed/all-minilm-l6-v2 384".to_string());
+        let current = backend_marker(&embedder);
+        let stale = config.index_dir.join(index::INDEX_FILE).exists() && marker != current;
+
         Ok(Self {
             embedder,
             index: Arc::new(index),
             chunker,
             config,
             project_root: RwLock::new(None),
+            stale_backend: RwLock::new(stale.then_some(marker)),
+            reranker: OnceLock::new(),
+            cache: EmbeddingCache::open_default(),
         })
     }
 
+    /// If the on-disk index was built by a different embedding backend, return its id.
+    pub fn stale_backend(&self) -> Option<String> {
+        self.stale_backend.read().clone()
+    }
+
     /// Create with default configuration
     pub fn with_defaults() -> Result<Self, SemanticSearchError> {
         Self::new(SearchConfig::default())
@@ -62,16 +109,35 @@ impl SemanticSearch {
 
     /// Search for code similar to the query
     pub fn search(&self, query: &str) -> Result<Vec<SearchResult>, SemanticSearchError> {
-        self.search_with_options(query, self.config.top_k, self.config.min_score)
+        self.search_with_options(query, self.config.top_k, self.config.min_score, None)
+    }
+
+    /// Search only chunks of `kind` (code, docs or config); `None` searches everything.
+    pub fn search_kind(
+        &self,
+        query: &str,
+        kind: Option<ChunkKind>,
+    ) -> Result<Vec<SearchResult>, SemanticSearchError> {
+        self.search_with_options(query, self.config.top_k, self.config.min_score, kind)
     }
 
     /// Search with custom options
+    #[tracing::instrument(skip_all, fields(top_k = top_k), err(level = "warn"))]
     pub fn search_with_options(
         &self,
         query: &str,
         top_k: usize,
         min_score: f32,
+        kind: Option<ChunkKind>,
     ) -> Result<Vec<SearchResult>, SemanticSearchError> {
+        // Vectors from another embedder live in a different space; never mix them.
+        if let Some(previous) = self.stale_backend() {
+            return Err(SemanticSearchError::BackendChanged {
+                previous,
+                current: backend_marker(&self.embedder),
+            });
+        }
+
         // Check if index is empty and we have a project root - auto-index
         if self.index.is_empty() {
             if let Some(root) = self.project_root() {
@@ -90,20 +156,35 @@ impl SemanticSearch {
             .embed_query(query)
             .map_err(SemanticSearchError::Embedder)?;
 
-        // Search the index
+        // Search the index. With reranking on, over-fetch candidates for the cross-encoder.
+        let candidates = if self.config.rerank {
+            top_k.max(self.config.rerank_candidates)
+        } else {
+            top_k
+        };
+        let fetch = match kind {
+            Some(_) => candidates.saturating_mul(KIND_OVERFETCH),
+            None => candidates,
+        };
         let results = self
             .index
-            .search(&query_embedding, top_k)
+            .search(&query_embedding, fetch)
             .map_err(SemanticSearchError::Index)?;
 
         // Convert to SearchResults with full chunk data
         let mut search_results = Vec::new();
         for (id, score) in results {
+            if search_results.len() >= candidates {
+                break;
+            }
             if score < min_score {
                 continue;
             }
 
             if let Some(metadata) = self.index.get_metadata(id) {
+                if kind.is_some_and(|k| metadata.kind() != k) {
+                    continue;
+                }
                 // Read the actual content from the file
                 let content = self.read_chunk_content(&metadata)?;
 
@@ -118,9 +199,40 @@ impl SemanticSearch {
             }
         }
 
+        if self.config.rerank {
+            search_results = self.rerank(query, search_results);
+        }
+        search_results.truncate(top_k);
+
         Ok(search_results)
     }
 
+    /// Re-score candidates with the cross-encoder. Falls back to vector order if the
+    /// reranker can't be loaded or fails.
+    fn rerank(&self, query: &str, candidates: Vec<SearchResult>) -> Vec<SearchResult> {
+        let Some(reranker) = self.reranker.get_or_init(|| Reranker::new().ok()) else {
+            return candidates;
+        };
+
+        let documents: Vec<String> = candidates
+            .iter()
+            .map(|r| r.chunk.embedding_text())
+            .collect();
+        let Ok(ranked) = reranker.rerank(query, &documents) else {
+            return candidates;
+        };
+
+        let mut slots: Vec<Option<SearchResult>> = candidates.into_iter().map(Some).collect();
+        ranked
+            .into_iter()
+            .filter_map(|(i, score)| {
+                let mut r = slots.get_mut(i)?.take()?;
+                r.score = score;
+                Some(r)
+            })
+            .collect()
+    }
+
     /// Read the content of a chunk from its source file
     fn read_chunk_content(
         &self,
@@ -140,6 +252,7 @@ impl SemanticSearch {
             Ok(s) => s,
             Err(_) => return Ok(String::new()), // Skip non-UTF8 (likely binary)
         };
+        let content = crate::notebook::readable(&file_path, &content).unwrap_or(content);
 
         // Extract the specific lines
         let lines: Vec<&str> = content.lines().collect();
@@ -155,6 +268,17 @@ impl SemanticSearch {
 
     /// Index a directory
     pub fn index_directory(&self, dir: &Path) -> Result<IndexStats, SemanticSearchError> {
+        self.index_directory_with_progress(dir, &|_| {})
+    }
+
+    /// Index a directory, reporting progress once the file list is known, per file while
+    /// chunking, and per embedding batch
+    #[tracing::instrument(skip_all, fields(dir = %dir.display()), err(level = "warn"))]
+    pub fn index_directory_with_progress(
+        &self,
+        dir: &Path,
+        progress: &dyn Fn(IndexProgress),
+    ) -> Result<IndexStats, SemanticSearchError> {
         // Clear existing index
         self.index.clear().map_err(SemanticSearchError::Index)?;
 
@@ -162,39 +286,57 @@ impl SemanticSearch {
         self.set_project_root(dir.to_path_buf());
 
         // Collect all files to index
-        let files = self.collect_files(dir)?;
-
-        // Process files and collect chunks
+        let files = collect_files(dir, &self.config.exclude_patterns)?;
+        let total_files = files.len();
+        let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+        progress(IndexProgress {
+            files_done: 0,
+            total_files,
+            current: None,
+        });
+
+        // Process files and collect chunks; `chunk_ends[i]` is where file i's chunks end
         let mut all_chunks = Vec::new();
+        let mut chunk_ends = Vec::with_capacity(total_files);
+        let mut symbol_table = SymbolTable::default();
         for file_path in &files {
-            let chunks = self.process_file(file_path)?;
+            progress(IndexProgress {
+                files_done: 0,
+                total_files,
+                current: Some(relative(file_path)),
+            });
+            let (chunks, symbols) = self.process_file(file_path)?;
             all_chunks.extend(chunks);
+            symbol_table.symbols.extend(symbols);
+            chunk_ends.push(all_chunks.len());
         }
 
         // Generate embeddings in batches
         let batch_size = 32;
         let mut indexed_count = 0;
+        let mut processed = 0;
 
         for chunk_batch in all_chunks.chunks(batch_size) {
-            let texts: Vec<String> = chunk_batch
-                .iter()
-                .map(|c| {
-                    // Include file path context for better embeddings
-                    format!("File: {}\n\n{}", c.metadata.file_path.display(), c.content)
-                })
-                .collect();
-
-            let embeddings = match self.embedder.embed_batch(&texts) {
-                Ok(e) => e,
-                Err(_) => continue, // Skip this batch on embedding error
-            };
-
-            // Add to index - handle individual chunk errors gracefully
-            for (chunk, embedding) in chunk_batch.iter().zip(embeddings.iter()) {
-                if self.index.add(chunk, embedding).is_ok() {
-                    indexed_count += 1;
+            // Include file path (and heading path for docs) context for better embeddings
+            let texts: Vec<String> = chunk_batch.iter().map(CodeChunk::embedding_text).collect();
+
+            // Skip this batch on embedding error
+            if let Ok(embeddings) = self.embed_batch_cached(&texts) {
+                // Add to index - handle individual chunk errors gracefully
+                for (chunk, embedding) in chunk_batch.iter().zip(embeddings.iter()) {
+                    if self.index.add(chunk, embedding).is_ok() {
+                        indexed_count += 1;
+                    }
                 }
             }
+
+            processed += chunk_batch.len();
+            let files_done = files_done(&chunk_ends, processed);
+            progress(IndexProgress {
+                files_done,
+                total_files,
+                current: files.get(files_done).map(|p| relative(p)),
+            });
         }
 
         // Only fail if we couldn't index anything at all
@@ -206,89 +348,61 @@ impl SemanticSearch {
 
         // Save the index
         self.index.save().map_err(SemanticSearchError::Index)?;
+        symbol_table
+            .save(&self.config.index_dir)
+            .map_err(|e| SemanticSearchError::Io(e.to_string()))?;
+        std::fs::write(
+            self.config.index_dir.join(BACKEND_FILE),
+            backend_marker(&self.embedder),
+        )
+        .map_err(|e| SemanticSearchError::Io(e.to_string()))?;
+        *self.stale_backend.write() = None;
 
         Ok(self.index.stats())
     }
 
-    /// Collect files to index from a directory
-    fn collect_files(&self, dir: &Path) -> Result<Vec<PathBuf>, SemanticSearchError> {
-        let mut files = Vec::new();
-
-        for entry in WalkDir::new(dir)
-            .follow_links(true)
-            .into_iter()
-            .filter_entry(|e| !self.should_exclude(e.path()))
-        {
-            let entry = entry.map_err(|e| SemanticSearchError::Io(e.to_string()))?;
-
-            if entry.file_type().is_file() && self.should_include(entry.path()) {
-                files.push(entry.path().to_path_buf());
-            }
-        }
-
-        Ok(files)
-    }
-
-    /// Check if a path should be excluded
-    fn should_exclude(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-
-        for pattern in &self.config.exclude_patterns {
-            if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
-                if glob_pattern.matches(&path_str) {
-                    return true;
-                }
-            }
-
-            // Also check just the path components
-            for component in path.components() {
-                if let std::path::Component::Normal(name) = component {
-                    let name_str = name.to_string_lossy();
-                    // Check for common excluded directories
-                    if matches!(
-                        name_str.as_ref(),
-                        "target"
-                            | "node_modules"
-                            | ".git"
-                            | "dist"
-                            | "build"
-                            | "__pycache__"
-                            | "vendor"
-                    ) {
-                        return true;
-                    }
-                }
-            }
-        }
-
-        false
-    }
+    /// Embed texts, reusing cached vectors for content seen before with the same embedder
+    fn embed_batch_cached(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedderError> {
+        let Some(cache) = &self.cache else {
+            return self.embedder.embed_batch(texts);
+        };
 
-    /// Check if a file should be included
-    fn should_include(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-        for pattern in &self.config.exclude_patterns {
-            if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
-                if glob_pattern.matches(&path_str) {
-                    return false;
-                }
-            }
+        let model = backend_marker(&self.embedder);
+        let hashes: Vec<String> = texts.iter().map(|t| content_hash(t)).collect();
+        let mut cached = cache.get_many(&model, &hashes);
+
+        let misses: Vec<usize> = (0..texts.len())
+            .filter(|&i| !cached.contains_key(&hashes[i]))
+            .collect();
+        if !misses.is_empty() {
+            let miss_texts: Vec<String> = misses.iter().map(|&i| texts[i].clone()).collect();
+            let fresh = self.embedder.embed_batch(&miss_texts)?;
+            let entries: Vec<(String, Vec<f32>)> = misses
+                .iter()
+                .zip(fresh)
+                .map(|(&i, v)| (hashes[i].clone(), v))
+                .collect();
+            cache.put_many(&model, &entries);
+            cached.extend(entries);
         }
 
-        true
+        Ok(hashes
+            .iter()
+            .filter_map(|h| cached.get(h).cloned())
+            .collect())
     }
 
-    /// Process a single file into chunks
-    fn process_file(&self, file_path: &Path) -> Result<Vec<CodeChunk>, SemanticSearchError> {
+    /// Process a single file into chunks and the definitions it contains
+    fn process_file(
+        &self,
+        file_path: &Path,
+    ) -> Result<(Vec<CodeChunk>, Vec<Symbol>), SemanticSearchError> {
         let bytes = std::fs::read(file_path).map_err(|e| SemanticSearchError::Io(e.to_string()))?;
         let content = match String::from_utf8(bytes) {
             Ok(s) => s,
-            Err(_) => return Ok(Vec::new()), // Skip non-UTF8 (likely binary) files
+            Err(_) => return Ok((Vec::new(), Vec::new())), // Skip non-UTF8 (likely binary) files
         };
 
-        let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let language = Language::from_extension(ext);
-
         // Make path relative to project root
         let relative_path = if let Some(root) = self.project_root() {
             file_path
@@ -298,8 +412,46 @@ impl SemanticSearch {
         } else {
             file_path.to_path_buf()
         };
+        let symbols = symbols::file_symbols(&content, &relative_path, &self.config.languages);
+        if let Some(chunks) = self.process_notebook(&content, &relative_path) {
+            return Ok((chunks, symbols));
+        }
+        let language = self.config.languages.detect(&relative_path, &content);
+
+        Ok((
+            self.chunker.chunk_file(&content, &relative_path, language),
+            symbols,
+        ))
+    }
 
-        Ok(self.chunker.chunk_file(&content, &relative_path, language))
+    /// Chunk a Jupyter notebook cell by cell: code in the kernel's language, markdown as
+    /// prose. Line numbers refer to the rendered notebook that `read_file` shows.
+    fn process_notebook(&self, content: &str, relative_path: &Path) -> Option<Vec<CodeChunk>> {
+        if !notebook::is_notebook(relative_path) {
+            return None;
+        }
+        let nb = Notebook::parse(content)?;
+        let code_language = nb
+            .language
+            .as_deref()
+            .and_then(Language::parse)
+            .unwrap_or(Language::Unknown);
+
+        let (_, offsets) = nb.render_with_offsets();
+        let mut chunks = Vec::new();
+        for (cell, first_line) in nb.cells.iter().zip(offsets) {
+            let language = match cell.kind {
+                CellKind::Code => code_language,
+                CellKind::Markdown => Language::Markdown,
+                CellKind::Raw => Language::Unknown,
+            };
+            for mut chunk in self.chunker.chunk_file(&cell.source, relative_path, language) {
+                chunk.metadata.start_line += first_line - 1;
+                chunk.metadata.end_line += first_line - 1;
+                chunks.push(chunk);
+            }
+        }
+        Some(chunks)
     }
 
     /// Get index statistics
@@ -325,6 +477,103 @@ impl SemanticSearch {
     }
 }
 
+/// Collect files to index from a directory
+pub(crate) fn collect_files(
+    dir: &Path,
+    exclude_patterns: &[String],
+) -> Result<Vec<PathBuf>, SemanticSearchError> {
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|e| !should_exclude(e.path(), exclude_patterns))
+    {
+        let entry = entry.map_err(|e| SemanticSearchError::Io(e.to_string()))?;
+
+        if entry.file_type().is_file() && should_include(entry.path(), exclude_patterns) {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Check if a path should be excluded
+fn should_exclude(path: &Path, exclude_patterns: &[String]) -> bool {
+    let path_str = path.to_string_lossy();
+
+    for pattern in exclude_patterns {
+        if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
+            if glob_pattern.matches(&path_str) {
+                return true;
+            }
+        }
+
+        // Also check just the path components
+        for component in path.components() {
+            if let std::path::Component::Normal(name) = component {
+                let name_str = name.to_string_lossy();
+                // Check for common excluded directories
+                if matches!(
+                    name_str.as_ref(),
+                    "target"
+                        | "node_modules"
+                        | ".git"
+                        | "dist"
+                        | "build"
+                        | "__pycache__"
+                        | "vendor"
+                ) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Check if a file should be included
+fn should_include(path: &Path, exclude_patterns: &[String]) -> bool {
+    let path_str = path.to_string_lossy();
+    for pattern in exclude_patterns {
+        if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
+            if glob_pattern.matches(&path_str) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Build `dir`'s symbol table without embedding anything (for `find_symbol` before the
+/// first full index) and save it with the index.
+pub fn build_symbol_table(
+    dir: &Path,
+    config: &SearchConfig,
+) -> Result<SymbolTable, SemanticSearchError> {
+    let mut table = SymbolTable::default();
+    for file_path in collect_files(dir, &config.exclude_patterns)? {
+        let Ok(content) = std::fs::read_to_string(&file_path) else {
+            continue;
+        };
+        let relative = file_path.strip_prefix(dir).unwrap_or(&file_path);
+        table
+            .symbols
+            .extend(symbols::file_symbols(&content, relative, &config.languages));
+    }
+    table
+        .save(&config.index_dir)
+        .map_err(|e| SemanticSearchError::Io(e.to_string()))?;
+    Ok(table)
+}
+
+fn backend_marker(embedder: &Embedder) -> String {
+    format!("{} {}", embedder.backend_id(), embedder.dimension())
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum SemanticSearchError {
@@ -332,6 +581,7 @@ pub enum SemanticSearchError {
     Index(IndexError),
     Io(String),
     NoProjectRoot,
+    BackendChanged { previous: String, current: String },
 }
 
 impl std::fmt::Display for SemanticSearchError {
@@ -341,6 +591,11 @@ impl std::fmt::Display for SemanticSearchError {
             SemanticSearchError::Index(e) => write!(f, "Index error: {}", e),
             SemanticSearchError::Io(msg) => write!(f, "IO error: {}", msg),
             SemanticSearchError::NoProjectRoot => write!(f, "No project root set"),
+            SemanticSearchError::BackendChanged { previous, current } => write!(
+                f,
+                "Embedding backend changed ({} -> {}); re-index with `lorikeet index`",
+                previous, current
+            ),
         }
     }
 }
@@ -391,6 +646,12 @@ pub fn format_search_results(results: &[SearchResult]) -> String {
     output
 }
 
+/// Files fully embedded once the first `processed` chunks are: those whose chunks all
+/// come before that point (files without chunks count as soon as they're reached)
+fn files_done(chunk_ends: &[usize], processed: usize) -> usize {
+    chunk_ends.partition_point(|&end| end <= processed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -404,4 +665,31 @@ mod tests {
         };
         let _search = SemanticSearch::new(config).unwrap();
     }
+
+    #[test]
+    fn files_count_as_done_once_their_chunks_are_embedded() {
+        // Files with 3, 0, 2 and 40 chunks.
+        let ends = [3, 3, 5, 45];
+        assert_eq!(files_done(&ends, 0), 0);
+        assert_eq!(files_done(&ends, 3), 2);
+        assert_eq!(files_done(&ends, 32), 3);
+        assert_eq!(files_done(&ends, 45), 4);
+    }
+
+    #[test]
+    fn chunks_are_classified_as_code_docs_or_config() {
+        let kind = |path: &str| {
+            let path = Path::new(path);
+            ChunkKind::of(path, Language::detect(path, ""))
+        };
+        assert_eq!(kind("src/main.rs"), ChunkKind::Code);
+        assert_eq!(kind("docs/guide.md"), ChunkKind::Doc);
+        assert_eq!(kind("README"), ChunkKind::Doc);
+        assert_eq!(kind("notes.rst"), ChunkKind::Doc);
+        assert_eq!(kind("Cargo.toml"), ChunkKind::Config);
+        assert_eq!(kind(".github/workflows/ci.yml"), ChunkKind::Config);
+        assert_eq!(kind("Makefile"), ChunkKind::Code);
+        assert_eq!(ChunkKind::parse("docs"), Some(ChunkKind::Doc));
+        assert_eq!(ChunkKind::parse("everything"), None);
+    }
 }
@@ -0,0 +1,91 @@
+use fastembed::{RerankInitOptions, RerankerModel, TextRerank};
+use parking_lot::RwLock;
+
+use crate::semantic_search::embedder::{default_fastembed_cache_dir, EmbedderError};
+
+/// Cross-encoder that re-scores (query, chunk) pairs jointly.
+///
+/// Bi-encoder similarity is good for recall but fuzzy for vague natural-language
+/// queries; the cross-encoder is slower and only runs over the top candidates.
+pub struct Reranker {
+    model: RwLock<TextRerank>,
+}
+
+impl Reranker {
+    /// Load the local JINA turbo reranker (downloaded on first use, ~150MB)
+    pub fn new() -> Result<Self, EmbedderError> {
+        let cache_dir = default_fastembed_cache_dir();
+        std::fs::create_dir_all(&cache_dir).map_err(|e| {
+            EmbedderError::ModelInit(format!(
+                "Failed to create cache dir {}: {e}",
+                cache_dir.display()
+            ))
+        })?;
+
+        let model = TextRerank::try_new(
+            RerankInitOptions::new(RerankerModel::JINARerankerV1TurboEn)
+                .with_cache_dir(cache_dir)
+                .with_show_download_progress(false),
+        )
+        .map_err(|e| EmbedderError::ModelInit(e.to_string()))?;
+
+        Ok(Self {
+            model: RwLock::new(model),
+        })
+    }
+
+    /// Score each document against the query. Returns `(document index, score)` sorted
+    /// best-first, with scores squashed into 0..1 so they read like similarity scores.
+    pub fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, EmbedderError> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let docs: Vec<&str> = documents.iter().map(|d| d.as_str()).collect();
+        let model = self.model.read();
+        let results = model
+            .rerank(query, docs, false, None)
+            .map_err(|e| EmbedderError::Embedding(e.to_string()))?;
+
+        let mut scored: Vec<(usize, f32)> = results
+            .into_iter()
+            .map(|r| (r.index, sigmoid(r.score)))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(scored)
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigmoid_maps_logits_into_unit_range() {
+        assert!((sigmoid(0.0) - 0.5).abs() < 1e-6);
+        assert!(sigmoid(8.0) > 0.99);
+        assert!(sigmoid(-8.0) < 0.01);
+    }
+
+    #[test]
+    #[ignore] // Requires model download
+    fn test_rerank_prefers_relevant_document() {
+        let reranker = Reranker::new().unwrap();
+        let docs = vec![
+            "fn parse_config(path: &Path) -> Config".to_string(),
+            "The weather is nice today".to_string(),
+        ];
+        let ranked = reranker
+            .rerank("where is the config parsed?", &docs)
+            .unwrap();
+        assert_eq!(ranked[0].0, 0);
+    }
+}
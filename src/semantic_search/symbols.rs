@@ -0,0 +1,312 @@
+//! A ctags-style table of definitions (name -> file:line, kind), built from the same
+//! tree-sitter parse as chunking and kept next to the vector index, so `find_symbol`
+//! can tell a definition from its usages without loading the embedding model.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Parser};
+
+use super::chunker::{classify_node, get_tree_sitter_language};
+use super::types::{Language, LanguageOverrides, SymbolType};
+use crate::notebook::{self, CellKind, Notebook};
+
+/// Symbol table file inside the workspace's index directory.
+pub const SYMBOLS_FILE: &str = "symbols.json";
+
+/// Most matches `find` returns.
+pub const MAX_MATCHES: usize = 50;
+
+/// One definition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolType,
+    /// Path relative to the workspace root
+    pub file_path: PathBuf,
+    /// First line of the definition (1-indexed)
+    pub line: usize,
+    /// Enclosing type, impl, class or module (`Config` for `impl Config { fn load }`)
+    pub container: Option<String>,
+}
+
+impl Symbol {
+    /// `src/config.rs:42 method Config::load`
+    pub fn describe(&self) -> String {
+        let kind = format!("{:?}", self.kind).to_lowercase();
+        match &self.container {
+            Some(container) => format!(
+                "{}:{} {} {}::{}",
+                self.file_path.display(),
+                self.line,
+                kind,
+                container,
+                self.name
+            ),
+            None => format!(
+                "{}:{} {} {}",
+                self.file_path.display(),
+                self.line,
+                kind,
+                self.name
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// The table saved with the index in `index_dir`, if there is one.
+    pub fn load(index_dir: &Path) -> Option<Self> {
+        let data = std::fs::read(index_dir.join(SYMBOLS_FILE)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn save(&self, index_dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(index_dir)?;
+        let data = serde_json::to_vec(self).map_err(std::io::Error::other)?;
+        std::fs::write(index_dir.join(SYMBOLS_FILE), data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Definitions named `query`, or starting with it when `prefix` is set. A qualified
+    /// query (`Config::load`, `Config.load`) also has to match the container. Case-exact
+    /// matches come first; case-insensitive ones are only used when there are none.
+    pub fn find(&self, query: &str, prefix: bool, kind: Option<SymbolType>) -> Vec<&Symbol> {
+        let query = query.trim();
+        let (container, name) = match query.rsplit_once("::").or_else(|| query.rsplit_once('.')) {
+            Some((container, name)) => (Some(container), name),
+            None => (None, query),
+        };
+        if name.is_empty() {
+            return Vec::new();
+        }
+
+        let matches = |sym: &Symbol, fold: bool| {
+            let eq = |a: &str, b: &str| {
+                if fold {
+                    a.eq_ignore_ascii_case(b)
+                } else {
+                    a == b
+                }
+            };
+            let name_ok = if prefix {
+                sym.name.len() >= name.len()
+                    && sym.name.is_char_boundary(name.len())
+                    && eq(&sym.name[..name.len()], name)
+            } else {
+                eq(&sym.name, name)
+            };
+            let container_ok = match (container, &sym.container) {
+                (None, _) => true,
+                (Some(want), Some(have)) => eq(have.rsplit("::").next().unwrap_or(have), want),
+                (Some(_), None) => false,
+            };
+            name_ok && container_ok && (kind.is_none() || kind == Some(sym.kind))
+        };
+
+        let mut found: Vec<&Symbol> = self.symbols.iter().filter(|s| matches(s, false)).collect();
+        if found.is_empty() {
+            found = self.symbols.iter().filter(|s| matches(s, true)).collect();
+        }
+        found.sort_by(|a, b| {
+            (a.name.len(), &a.file_path, a.line).cmp(&(b.name.len(), &b.file_path, b.line))
+        });
+        found.truncate(MAX_MATCHES);
+        found
+    }
+}
+
+/// Definitions in one file. Notebooks are read cell by cell, with line numbers in the
+/// rendered view that `read_file` shows.
+pub fn file_symbols(
+    content: &str,
+    relative_path: &Path,
+    languages: &LanguageOverrides,
+) -> Vec<Symbol> {
+    if notebook::is_notebook(relative_path) {
+        let Some(nb) = Notebook::parse(content) else {
+            return Vec::new();
+        };
+        let language = nb
+            .language
+            .as_deref()
+            .and_then(Language::parse)
+            .unwrap_or(Language::Unknown);
+        let (_, offsets) = nb.render_with_offsets();
+        let mut symbols = Vec::new();
+        for (cell, first_line) in nb.cells.iter().zip(offsets) {
+            if cell.kind != CellKind::Code {
+                continue;
+            }
+            for mut sym in extract(&cell.source, relative_path, language) {
+                sym.line += first_line - 1;
+                symbols.push(sym);
+            }
+        }
+        return symbols;
+    }
+    extract(
+        content,
+        relative_path,
+        languages.detect(relative_path, content),
+    )
+}
+
+/// Definitions in `content`, nested ones included (methods in impls and classes).
+pub fn extract(content: &str, file_path: &Path, language: Language) -> Vec<Symbol> {
+    let Some(ts_language) = get_tree_sitter_language(language) else {
+        return Vec::new();
+    };
+    let mut parser = Parser::new();
+    if parser.set_language(&ts_language).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(content, None) else {
+        return Vec::new();
+    };
+
+    let mut symbols = Vec::new();
+    walk(
+        tree.root_node(),
+        content.as_bytes(),
+        file_path,
+        language,
+        None,
+        &mut symbols,
+    );
+    symbols
+}
+
+fn walk(
+    node: Node,
+    content: &[u8],
+    file_path: &Path,
+    language: Language,
+    container: Option<&(SymbolType, String)>,
+    out: &mut Vec<Symbol>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        let Some((kind, raw_name)) = classify(&child, language, content) else {
+            walk(child, content, file_path, language, container, out);
+            continue;
+        };
+        let (qualifier, name) = clean_name(&raw_name);
+        let kind = match (kind, container) {
+            (SymbolType::Function, Some((outer, _)))
+                if matches!(
+                    outer,
+                    SymbolType::Impl
+                        | SymbolType::Trait
+                        | SymbolType::Class
+                        | SymbolType::Interface
+                ) =>
+            {
+                SymbolType::Method
+            }
+            _ => kind,
+        };
+        // Impl blocks aren't definitions, but name the container of what's inside.
+        if kind != SymbolType::Impl && is_real_name(&name) {
+            out.push(Symbol {
+                name: name.clone(),
+                kind,
+                file_path: file_path.to_path_buf(),
+                line: child.start_position().row + 1,
+                container: qualifier.or_else(|| container.map(|(_, c)| c.clone())),
+            });
+        }
+        let scope = (kind, name);
+        walk(child, content, file_path, language, Some(&scope), out);
+    }
+}
+
+/// The chunker's classification plus a few definitions it doesn't chunk on.
+fn classify(node: &Node, language: Language, content: &[u8]) -> Option<(SymbolType, String)> {
+    let name = || {
+        node.child_by_field_name("name")
+            .and_then(|n| n.utf8_text(content).ok())
+            .map(str::to_string)
+    };
+    match (language, node.kind()) {
+        (Language::Rust, "const_item" | "static_item" | "type_item" | "macro_definition") => {
+            Some((SymbolType::Other, name()?))
+        }
+        (Language::Go, "type_spec") => {
+            let kind = match node.child_by_field_name("type").map(|t| t.kind()) {
+                Some("interface_type") => SymbolType::Interface,
+                _ => SymbolType::Struct,
+            };
+            Some((kind, name()?))
+        }
+        // Declared as `type X ...`; the specs inside carry the names.
+        (Language::Go, "type_declaration") => None,
+        (Language::JavaScript | Language::TypeScript, "arrow_function" | "function_expression") => {
+            None
+        }
+        _ => classify_node(node, language, content),
+    }
+}
+
+/// `Foo::bar(int x)` -> (`Foo`, `bar`); `*parse(char *s)` -> `parse`; `Vec<T>` -> `Vec`
+fn clean_name(raw: &str) -> (Option<String>, String) {
+    let head = raw.split(['(', '<', '{', '\n']).next().unwrap_or(raw);
+    let head = head.trim().trim_start_matches(['*', '&']).trim();
+    match head.rsplit_once("::") {
+        Some((qualifier, name)) if !qualifier.is_empty() => {
+            (Some(qualifier.to_string()), name.trim().to_string())
+        }
+        _ => (None, head.to_string()),
+    }
+}
+
+fn is_real_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with("anonymous")
+        && name.chars().all(|c| {
+            c.is_alphanumeric() || c == '_' || c == '$' || c == '!' || c == '?' || c == '~'
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions_are_found_by_name_prefix_and_container() {
+        let src = "struct Config { path: String }\n\nimpl Config {\n    fn load() -> Self {\n        todo!()\n    }\n}\n\nfn load_all() {}\n\nconst LIMIT: usize = 3;\n";
+        let table = SymbolTable {
+            symbols: extract(src, Path::new("src/config.rs"), Language::Rust),
+        };
+        let describe = |found: Vec<&Symbol>| found.iter().map(|s| s.describe()).collect::<Vec<_>>();
+
+        assert_eq!(
+            describe(table.find("Config", false, None)),
+            ["src/config.rs:1 struct Config"]
+        );
+        assert_eq!(
+            describe(table.find("load", false, None)),
+            ["src/config.rs:4 method Config::load"]
+        );
+        assert_eq!(table.find("load", true, None).len(), 2);
+        assert_eq!(table.find("Config::load", false, None).len(), 1);
+        assert_eq!(table.find("Other::load", false, None).len(), 0);
+        assert_eq!(
+            describe(table.find("load_ALL", false, Some(SymbolType::Function))),
+            ["src/config.rs:9 function load_all"]
+        );
+        assert_eq!(table.find("LIMIT", false, None)[0].kind, SymbolType::Other);
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 
@@ -13,6 +14,26 @@ pub struct CodeChunk {
     pub metadata: ChunkMetadata,
 }
 
+impl CodeChunk {
+    /// Text sent to the embedder: the content prefixed with its file path and, for
+    /// prose, the heading path it sits under
+    pub fn embedding_text(&self) -> String {
+        match (&self.metadata.symbol_type, &self.metadata.symbol_name) {
+            (Some(SymbolType::Section), Some(section)) => format!(
+                "File: {}\nSection: {}\n\n{}",
+                self.metadata.file_path.display(),
+                section,
+                self.content
+            ),
+            _ => format!(
+                "File: {}\n\n{}",
+                self.metadata.file_path.display(),
+                self.content
+            ),
+        }
+    }
+}
+
 /// Metadata about a code chunk's origin
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkMetadata {
@@ -30,7 +51,77 @@ pub struct ChunkMetadata {
     pub symbol_type: Option<SymbolType>,
 }
 
-/// Supported programming languages for AST-aware chunking
+impl ChunkMetadata {
+    /// Whether this chunk is code, documentation or configuration
+    pub fn kind(&self) -> ChunkKind {
+        ChunkKind::of(&self.file_path, self.language)
+    }
+}
+
+/// What a chunk holds, for searching docs or code on their own. Derived from the path
+/// and language rather than stored, so existing indexes get it too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    Code,
+    Doc,
+    Config,
+}
+
+impl ChunkKind {
+    pub fn of(path: &Path, language: Language) -> Self {
+        if language == Language::Markdown {
+            return ChunkKind::Doc;
+        }
+        if language != Language::Unknown {
+            return ChunkKind::Code;
+        }
+
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match ext.as_str() {
+            "rst" | "txt" | "adoc" | "asciidoc" | "org" | "tex" => return ChunkKind::Doc,
+            "toml" | "yaml" | "yml" | "json" | "jsonc" | "json5" | "ini" | "cfg" | "conf"
+            | "properties" | "env" | "xml" | "plist" | "lock" => return ChunkKind::Config,
+            _ => {}
+        }
+        match file_name.as_str() {
+            "readme" | "changelog" | "license" | "copying" | "authors" | "contributing"
+            | "notice" => ChunkKind::Doc,
+            ".env" | ".editorconfig" | ".gitignore" | ".gitattributes" | ".npmrc"
+            | ".dockerignore" => ChunkKind::Config,
+            _ => ChunkKind::Code,
+        }
+    }
+
+    /// `code`, `doc`/`docs`/`documentation`, `config`/`configuration`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "code" => Some(ChunkKind::Code),
+            "doc" | "docs" | "documentation" | "markdown" => Some(ChunkKind::Doc),
+            "config" | "configuration" => Some(ChunkKind::Config),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkKind::Code => "code",
+            ChunkKind::Doc => "doc",
+            ChunkKind::Config => "config",
+        }
+    }
+}
+
+/// Supported languages for AST-aware (or, for Markdown, heading-aware) chunking
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Language {
     Rust,
@@ -43,6 +134,8 @@ pub enum Language {
     Java,
     Ruby,
     Unknown,
+    // Appended so existing bincode-serialized indexes keep their variant tags
+    Markdown,
 }
 
 impl Language {
@@ -58,12 +151,77 @@ impl Language {
             "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Language::Cpp,
             "java" => Language::Java,
             "rb" => Language::Ruby,
+            "md" | "markdown" | "mdx" => Language::Markdown,
             _ => Language::Unknown,
         }
     }
 
+    /// Parse a language name as used in config files (`python`, `ts`, `c++`, ...)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "rust" => Some(Language::Rust),
+            "python" | "python3" => Some(Language::Python),
+            "javascript" | "node" => Some(Language::JavaScript),
+            "typescript" => Some(Language::TypeScript),
+            "golang" => Some(Language::Go),
+            "c++" => Some(Language::Cpp),
+            "ruby" => Some(Language::Ruby),
+            other => match Self::from_extension(other) {
+                Language::Unknown => None,
+                lang => Some(lang),
+            },
+        }
+    }
+
+    /// Detect language from the path, falling back to well-known file names and the
+    /// content's shebang line for extensionless scripts
+    pub fn detect(path: &Path, content: &str) -> Self {
+        let by_ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(Self::from_extension)
+            .unwrap_or(Language::Unknown);
+        if by_ext != Language::Unknown {
+            return by_ext;
+        }
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        match file_name {
+            "Rakefile" | "Gemfile" | "Guardfile" | "Vagrantfile" | "Podfile" => {
+                return Language::Ruby
+            }
+            "SConstruct" | "SConscript" | "wscript" => return Language::Python,
+            _ => {}
+        }
+
+        content
+            .lines()
+            .next()
+            .and_then(Self::from_shebang)
+            .unwrap_or(Language::Unknown)
+    }
+
+    /// `#!/usr/bin/env python3`, `#!/usr/local/bin/node --flag`, `#!/usr/bin/env -S deno run`
+    fn from_shebang(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix("#!")?;
+        let mut words = rest.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|w| !w.starts_with('-'))?;
+        }
+        // python3.12 -> python, nodejs -> node
+        let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        match name {
+            "python" | "pypy" => Some(Language::Python),
+            "node" | "nodejs" | "bun" => Some(Language::JavaScript),
+            "deno" | "ts-node" | "tsx" => Some(Language::TypeScript),
+            "ruby" => Some(Language::Ruby),
+            "rust-script" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+
     /// Get common file extensions for this language
-    #[allow(dead_code)]
     pub fn extensions(&self) -> &'static [&'static str] {
         match self {
             Language::Rust => &["rs"],
@@ -75,11 +233,54 @@ impl Language {
             Language::Cpp => &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
             Language::Java => &["java"],
             Language::Ruby => &["rb"],
+            Language::Markdown => &["md", "markdown", "mdx"],
             Language::Unknown => &[],
         }
     }
 }
 
+/// User-configured language assignments (`[languages]` in config.toml)
+///
+/// Keys are glob patterns; patterns without a `/` match the file name, others the
+/// workspace-relative path. Overrides win over extension and shebang detection.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageOverrides {
+    rules: Vec<(glob::Pattern, Language)>,
+}
+
+impl LanguageOverrides {
+    pub fn from_config(map: Option<&HashMap<String, String>>) -> Self {
+        let mut rules: Vec<(glob::Pattern, Language)> = map
+            .into_iter()
+            .flatten()
+            .filter_map(|(pattern, lang)| {
+                Some((glob::Pattern::new(pattern).ok()?, Language::parse(lang)?))
+            })
+            .collect();
+        // HashMap order is arbitrary; make precedence deterministic (most specific first).
+        rules.sort_by(|a, b| b.0.as_str().len().cmp(&a.0.as_str().len()));
+        Self { rules }
+    }
+
+    pub fn lookup(&self, path: &Path) -> Option<Language> {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.rules.iter().find_map(|(pattern, lang)| {
+            let matched = if pattern.as_str().contains('/') {
+                pattern.matches_path(path)
+            } else {
+                pattern.matches(file_name)
+            };
+            matched.then_some(*lang)
+        })
+    }
+
+    /// Override if configured, otherwise [`Language::detect`]
+    pub fn detect(&self, path: &Path, content: &str) -> Language {
+        self.lookup(path)
+            .unwrap_or_else(|| Language::detect(path, content))
+    }
+}
+
 /// Type of code symbol
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SymbolType {
@@ -93,10 +294,12 @@ pub enum SymbolType {
     Module,
     Impl,
     Other,
+    /// A heading-delimited section of a prose document; the name is the heading path
+    Section,
 }
 
 /// A search result from the semantic search
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     /// The matched code chunk
     pub chunk: CodeChunk,
@@ -117,6 +320,19 @@ pub struct SearchConfig {
     pub max_chunk_size: usize,
     /// File patterns to exclude (glob patterns)
     pub exclude_patterns: Vec<String>,
+    /// Which embedding backend produces the vectors; `Err` says what's wrong with
+    /// `[embeddings]`
+    pub embedder: Result<EmbedderConfig, String>,
+    /// Vector index implementation
+    pub index_kind: IndexKind,
+    /// Storage precision for indexed vectors
+    pub quantization: Quantization,
+    /// Configured language assignments for files detection gets wrong
+    pub languages: LanguageOverrides,
+    /// Re-score the top candidates with a local cross-encoder
+    pub rerank: bool,
+    /// How many vector-search candidates the reranker sees
+    pub rerank_candidates: usize,
 }
 
 impl Default for SearchConfig {
@@ -135,6 +351,12 @@ impl Default for SearchConfig {
                 "**/__pycache__/**".into(),
                 "**/vendor/**".into(),
             ],
+            embedder: Ok(EmbedderConfig::default()),
+            index_kind: IndexKind::Hnsw,
+            quantization: Quantization::F32,
+            languages: LanguageOverrides::default(),
+            rerank: false,
+            rerank_candidates: 50,
         }
     }
 }
@@ -143,10 +365,178 @@ impl SearchConfig {
     pub fn for_workspace(workspace_root: &Path) -> Self {
         let mut cfg = Self::default();
         cfg.index_dir = index_dir_for_workspace(workspace_root);
+        let app_config = crate::config::AppConfig::load_layered(workspace_root);
+        cfg.languages = LanguageOverrides::from_config(app_config.languages.as_ref());
+        if let Some(embeddings) = &app_config.embeddings {
+            cfg.embedder = EmbedderConfig::from_settings(embeddings);
+            if let Some(kind) = embeddings.index.as_deref().and_then(IndexKind::parse) {
+                cfg.index_kind = kind;
+            }
+            if let Some(q) = embeddings
+                .quantization
+                .as_deref()
+                .and_then(Quantization::parse)
+            {
+                cfg.quantization = q;
+            }
+            cfg.rerank = embeddings.rerank.unwrap_or(false);
+        }
         cfg
     }
 }
 
+/// Vector index implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    /// Approximate nearest neighbours (usearch HNSW graph)
+    Hnsw,
+    /// Exact brute-force scan; fine for small repos
+    Flat,
+}
+
+impl IndexKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "hnsw" | "ann" => Some(Self::Hnsw),
+            "flat" | "exact" => Some(Self::Flat),
+            _ => None,
+        }
+    }
+}
+
+/// Storage precision for indexed vectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantization {
+    /// Full precision
+    F32,
+    /// Symmetric int8 with a per-vector scale (~4x smaller, faster flat scans)
+    Int8,
+}
+
+impl Quantization {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "f32" | "none" | "off" => Some(Self::F32),
+            "int8" | "i8" => Some(Self::Int8),
+            _ => None,
+        }
+    }
+}
+
+/// Embedding backend kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedderBackend {
+    /// Local ONNX models via fastembed
+    Fastembed,
+    /// OpenAI (or compatible) `/v1/embeddings` API
+    OpenAI,
+    /// Ollama `/api/embed` endpoint
+    Ollama,
+}
+
+impl EmbedderBackend {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "fastembed" | "local" => Some(Self::Fastembed),
+            "openai" => Some(Self::OpenAI),
+            "ollama" => Some(Self::Ollama),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fastembed => "fastembed",
+            Self::OpenAI => "openai",
+            Self::Ollama => "ollama",
+        }
+    }
+}
+
+/// Configuration for the embedding backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedderConfig {
+    pub backend: EmbedderBackend,
+    /// Model name; `None` picks the backend default
+    pub model: Option<String>,
+    /// API base URL override for remote backends
+    pub base_url: Option<String>,
+    /// Known embedding dimension (skips probing remote backends)
+    pub dimension: Option<usize>,
+    /// Execution device for local models
+    pub device: EmbedderDevice,
+}
+
+impl Default for EmbedderConfig {
+    fn default() -> Self {
+        Self {
+            backend: EmbedderBackend::Fastembed,
+            model: None,
+            base_url: None,
+            dimension: None,
+            device: EmbedderDevice::Cpu,
+        }
+    }
+}
+
+impl EmbedderConfig {
+    /// Fails on a backend name that isn't one of ours, rather than silently indexing
+    /// with the local model instead.
+    pub fn from_settings(settings: &crate::config::EmbeddingsConfig) -> Result<Self, String> {
+        let backend = match settings.backend.as_deref().filter(|b| !b.trim().is_empty()) {
+            None => EmbedderBackend::Fastembed,
+            Some(name) => EmbedderBackend::parse(name).ok_or_else(|| {
+                format!(
+                    "unknown backend '{}' (use fastembed, openai or ollama)",
+                    name.trim()
+                )
+            })?,
+        };
+        Ok(Self {
+            backend,
+            model: settings.model.clone().filter(|m| !m.trim().is_empty()),
+            base_url: settings.base_url.clone().filter(|u| !u.trim().is_empty()),
+            dimension: settings.dimension,
+            device: settings
+                .device
+                .as_deref()
+                .and_then(EmbedderDevice::parse)
+                .unwrap_or(EmbedderDevice::Cpu),
+        })
+    }
+}
+
+/// Execution device for local (ONNX) embedding models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedderDevice {
+    Cpu,
+    /// NVIDIA GPU by device index (`cuda` feature)
+    Cuda(i32),
+    /// Apple Neural Engine / GPU (`coreml` feature)
+    CoreML,
+    /// Windows GPU via DirectX 12 (`directml` feature)
+    DirectML,
+    /// Any accelerator compiled in, falling back to CPU
+    Auto,
+}
+
+impl EmbedderDevice {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        if let Some(id) = s.strip_prefix("cuda:") {
+            return id.parse().ok().map(Self::Cuda);
+        }
+        match s.as_str() {
+            "cpu" => Some(Self::Cpu),
+            "cuda" | "gpu" => Some(Self::Cuda(0)),
+            "coreml" => Some(Self::CoreML),
+            "directml" | "dml" => Some(Self::DirectML),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
 fn default_index_base_dir() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -168,8 +558,18 @@ fn project_id(root: &Path) -> String {
     format!("{:016x}", h.finish())
 }
 
+/// How far `index_directory_with_progress` has got
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexProgress {
+    /// Files whose chunks are all embedded (or that had none)
+    pub files_done: usize,
+    pub total_files: usize,
+    /// The file being embedded, relative to the project root when possible
+    pub current: Option<PathBuf>,
+}
+
 /// Statistics about the index
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IndexStats {
     /// Total number of chunks indexed
     pub total_chunks: usize,
@@ -0,0 +1,193 @@
+//! `lorikeet serve`: a session driven over stdio with JSON-RPC 2.0, one message per line,
+//! for editor extensions. Requests run in order on one conversation; while one runs, its
+//! events arrive as `event` notifications carrying the same objects as `--output json`.
+//!
+//! Methods: `initialize`, `prompt {text, expect?}`, `tool {name, args?}`,
+//! `verify {command?}` and `shutdown`. The last three return a step result, like
+//! `lorikeet exec` prints.
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::mpsc;
+
+use crate::script::{ScriptRunner, Step};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+pub struct Server {
+    runner: ScriptRunner,
+    out: mpsc::UnboundedSender<Value>,
+    steps: usize,
+    pub shutdown: bool,
+}
+
+impl Server {
+    /// Messages for the client (responses and notifications) go to `out`.
+    pub fn new(mut runner: ScriptRunner, out: mpsc::UnboundedSender<Value>) -> Self {
+        let events = out.clone();
+        runner.observe(move |event| {
+            if let Some(params) = event.to_json() {
+                let _ =
+                    events.send(json!({ "jsonrpc": "2.0", "method": "event", "params": params }));
+            }
+        });
+        Self {
+            runner,
+            out,
+            steps: 0,
+            shutdown: false,
+        }
+    }
+
+    /// Handle one line from the client; the response (if any) goes to `out`.
+    pub async fn handle_line(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let request: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => return self.reply(Value::Null, Err((PARSE_ERROR, e.to_string()))),
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let id = id.unwrap_or(Value::Null);
+            return self.reply(id, Err((INVALID_REQUEST, "missing method".into())));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.call(method, &params).await;
+        // Requests without an id are notifications: no response.
+        if let Some(id) = id {
+            self.reply(id, result);
+        }
+    }
+
+    async fn call(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let text = |key: &str| params.get(key).and_then(Value::as_str).map(str::to_string);
+        let step = match method {
+            "initialize" => {
+                return Ok(json!({
+                    "serverInfo": { "name": "lorikeet", "version": env!("CARGO_PKG_VERSION") },
+                    "methods": ["initialize", "prompt", "tool", "verify", "shutdown"],
+                }));
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                return Ok(Value::Null);
+            }
+            "prompt" => Step {
+                prompt: Some(text("text").ok_or((INVALID_PARAMS, "prompt needs text".into()))?),
+                expect: text("expect"),
+                ..Default::default()
+            },
+            "tool" => Step {
+                tool: Some(text("name").ok_or((INVALID_PARAMS, "tool needs a name".into()))?),
+                args: params.get("args").cloned().unwrap_or(Value::Null),
+                ..Default::default()
+            },
+            "verify" => Step {
+                verify: Some(text("command").unwrap_or_default()),
+                ..Default::default()
+            },
+            other => return Err((METHOD_NOT_FOUND, format!("unknown method {}", other))),
+        };
+        let report = self.runner.run_step(self.steps, &step).await;
+        self.steps += 1;
+        serde_json::to_value(report).map_err(|e| (INTERNAL_ERROR, e.to_string()))
+    }
+
+    fn reply(&self, id: Value, result: Result<Value, (i64, String)>) {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        let _ = self.out.send(message);
+    }
+}
+
+/// Serve `input` line by line until `shutdown` or end of input, writing every message to
+/// stdout as one line.
+pub async fn serve(runner: ScriptRunner, input: impl AsyncBufRead + Unpin) -> std::io::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            println!("{}", message);
+        }
+    });
+    let mut server = Server::new(runner, tx);
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        server.handle_line(&line).await;
+        if server.shutdown {
+            break;
+        }
+    }
+    drop(server);
+    let _ = writer.await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::EvalTarget;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn requests_get_responses_and_tool_events() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "served\n").unwrap();
+        let target = EvalTarget {
+            provider: crate::llm::LlmProvider::OpenRouter,
+            api_key: "k".into(),
+            codex_account_id: None,
+            model: None,
+        };
+        let runner = ScriptRunner::new(dir.path(), &target, false).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut server = Server::new(runner, tx);
+
+        server
+            .handle_line(r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#)
+            .await;
+        let init = rx.try_recv().unwrap();
+        assert_eq!(init["id"], 1);
+        assert_eq!(init["result"]["serverInfo"]["name"], "lorikeet");
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tool",
+            "params": { "name": "read_file", "args": { "path": dir.path().join("a.txt") } },
+        });
+        server.handle_line(&call.to_string()).await;
+        let mut messages = Vec::new();
+        while let Ok(m) = rx.try_recv() {
+            messages.push(m);
+        }
+        let response = messages.last().unwrap();
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"]["ok"], true);
+        assert!(messages
+            .iter()
+            .any(|m| m["method"] == "event" && m["params"]["type"] == "tool_start"));
+
+        server
+            .handle_line(r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#)
+            .await;
+        assert_eq!(rx.try_recv().unwrap()["error"]["code"], METHOD_NOT_FOUND);
+        server.handle_line("{oops").await;
+        assert_eq!(rx.try_recv().unwrap()["error"]["code"], PARSE_ERROR);
+        server
+            .handle_line(r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#)
+            .await;
+        assert!(server.shutdown);
+    }
+}
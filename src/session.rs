@@ -2,10 +2,23 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::io::{BufRead, Write};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 
 use serde::{Deserialize, Serialize};
 
-use crate::app::{Message, Role, ToolOutput, ToolStatus};
+use crate::app::{Message, PlanDraft, Role, ToolOutput, ToolStatus};
+use crate::storage::{FileLock, LockOwner};
+
+const COMPRESSED_EXT: &str = "jsonl.zst";
+const ZSTD_LEVEL: i32 = 3;
+
+/// An open session rewrites its `.owner` lease at most this often (on append).
+const LEASE_REFRESH_SECS: i64 = 60;
+/// A lease not refreshed for this long is abandoned: its owner exited, crashed or idled.
+const LEASE_STALE_SECS: i64 = 15 * 60;
+/// How long an append waits for another writer's `.lock`, and when that lock is stale.
+const APPEND_LOCK_WAIT: std::time::Duration = std::time::Duration::from_secs(1);
+const APPEND_LOCK_STALE_SECS: i64 = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -20,6 +33,16 @@ pub enum SessionEvent {
         tool_group_id: Option<u64>,
         #[serde(default)]
         local: bool,
+
+        /// Model that produced an assistant message.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+        /// Whether plan mode was active when the message was produced.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        plan_mode: Option<bool>,
+        /// Fingerprint of behaviour-relevant config (see `AppConfig::fingerprint`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config_hash: Option<String>,
     },
     Checkpoint {
         ts: i64,
@@ -47,6 +70,10 @@ pub enum SessionEvent {
         sandbox_reason: Option<String>,
         #[serde(default)]
         group_id: Option<u64>,
+        /// File in the session's tool output directory holding the complete output, when
+        /// `output` is only its tail.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output_file: Option<String>,
     },
     Meta {
         ts: i64,
@@ -54,6 +81,74 @@ pub enum SessionEvent {
         project_id: String,
         version: String,
     },
+    /// Session title, from the first request or `/rename`; the latest one wins.
+    Title {
+        ts: i64,
+        title: String,
+    },
+    /// Plan-mode draft with its answers; the latest one is restored on resume.
+    Plan {
+        ts: i64,
+        draft: PlanDraft,
+        /// Whether the plan was executed (as opposed to generated or edited).
+        #[serde(default)]
+        accepted: bool,
+    },
+}
+
+impl SessionEvent {
+    /// When the event was recorded (unix seconds).
+    pub fn ts(&self) -> i64 {
+        match self {
+            Self::Message { ts, .. }
+            | Self::Checkpoint { ts, .. }
+            | Self::Tool { ts, .. }
+            | Self::Meta { ts, .. }
+            | Self::Title { ts, .. }
+            | Self::Plan { ts, .. } => *ts,
+        }
+    }
+}
+
+/// Context recorded alongside assistant messages, for later transcript analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnMeta {
+    pub model: String,
+    pub plan_mode: bool,
+    pub config_hash: String,
+}
+
+/// Summary of a session file on disk, used by `/sessions`.
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub path: PathBuf,
+    pub compressed: bool,
+    pub bytes: u64,
+}
+
+impl SessionInfo {
+    /// The session's title, read from its event log (`None` if it never got one).
+    pub fn title(&self) -> Option<String> {
+        let data = if self.compressed {
+            let packed = std::fs::read(&self.path).ok()?;
+            String::from_utf8_lossy(&zstd::decode_all(packed.as_slice()).ok()?).into_owned()
+        } else {
+            std::fs::read_to_string(&self.path).ok()?
+        };
+        // Only title lines need parsing.
+        data.lines()
+            .filter(|l| l.contains(r#""type":"title""#))
+            .filter_map(|l| serde_json::from_str::<SessionEvent>(l).ok())
+            .filter_map(|ev| title_of(&ev))
+            .last()
+    }
+
+    /// The live process (here or on another machine) that has this session open.
+    pub fn owner(&self) -> Option<LockOwner> {
+        let lease = self.path.with_file_name(format!("{}.owner", self.session_id));
+        LockOwner::read(&lease).filter(|o| !o.is_current() && o.age_secs() <= LEASE_STALE_SECS)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +157,16 @@ pub struct SessionStore {
     pub project_id: String,
     pub events_path: PathBuf,
     pub latest_path: PathBuf,
+    /// Appends go through a writer thread, so waiting on another writer's `.lock` never
+    /// stalls the caller. `None` on the writer's own copy, which writes directly.
+    writer: Option<mpsc::Sender<WriteJob>>,
+}
+
+#[derive(Debug)]
+enum WriteJob {
+    Append(SessionEvent),
+    /// Answered once every append queued before it is on disk.
+    Flush(mpsc::Sender<()>),
 }
 
 impl SessionStore {
@@ -72,12 +177,38 @@ impl SessionStore {
         let events_path = base.join(format!("{}.jsonl", session_id));
         let latest_path = base.join("latest");
 
-        Ok(Self {
+        Self {
             project_id: project_id(project_root),
             session_id,
             events_path,
             latest_path,
-        })
+            writer: None,
+        }
+        .with_writer()
+    }
+
+    /// Start the thread this store's appends are queued to.
+    fn with_writer(mut self) -> std::io::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let direct = Self {
+            writer: None,
+            ..self.clone()
+        };
+        std::thread::Builder::new()
+            .name("session-writer".into())
+            .spawn(move || {
+                // Ends when the last clone of the store is dropped.
+                for job in rx {
+                    match job {
+                        WriteJob::Append(event) => direct.write_event(&event),
+                        WriteJob::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+        self.writer = Some(tx);
+        Ok(self)
     }
 
     pub fn open_latest(project_root: &Path) -> std::io::Result<Option<Self>> {
@@ -93,29 +224,188 @@ impl SessionStore {
         let store = Self::new(project_root, session_id)?;
         if store.events_path.exists() {
             Ok(Some(store))
+        } else if store.compressed_path().exists() {
+            // Appends need a plain file; inflate the closed session before reopening it.
+            store.decompress()?;
+            Ok(Some(store))
         } else {
             Ok(None)
         }
     }
 
+    /// Complete outputs of tool calls too long to keep in memory: `<session>.tools/`.
+    pub fn tool_outputs_dir(&self) -> PathBuf {
+        self.events_path.with_extension("tools")
+    }
+
+    /// Where the complete output of tool call `call_id` is spilled.
+    pub fn tool_output_path(&self, call_id: &str) -> PathBuf {
+        let name: String = call_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.tool_outputs_dir().join(format!("{}.txt", name))
+    }
+
+    pub fn compressed_path(&self) -> PathBuf {
+        self.events_path.with_extension(COMPRESSED_EXT)
+    }
+
+    /// Replace the plain `.jsonl` file with a zstd-compressed `.jsonl.zst` copy.
+    pub fn compress(&self) -> std::io::Result<()> {
+        self.flush();
+        let data = std::fs::read(&self.events_path)?;
+        let packed = zstd::encode_all(data.as_slice(), ZSTD_LEVEL)?;
+
+        let tmp = self
+            .events_path
+            .with_extension(format!("{}.tmp", COMPRESSED_EXT));
+        std::fs::write(&tmp, packed)?;
+        std::fs::rename(&tmp, self.compressed_path())?;
+        std::fs::remove_file(&self.events_path)?;
+        Ok(())
+    }
+
+    /// Restore the plain `.jsonl` file from its compressed copy.
+    pub fn decompress(&self) -> std::io::Result<()> {
+        let packed = std::fs::read(self.compressed_path())?;
+        let data = zstd::decode_all(packed.as_slice())?;
+        std::fs::write(&self.events_path, data)?;
+        std::fs::remove_file(self.compressed_path())?;
+        Ok(())
+    }
+
     pub fn set_latest(&self) {
         let _ = std::fs::write(&self.latest_path, format!("{}.jsonl", self.session_id));
     }
 
     pub fn append(&self, event: &SessionEvent) {
-        if let Ok(mut f) = std::fs::OpenOptions::new()
+        let queued = self
+            .writer
+            .as_ref()
+            .is_some_and(|tx| tx.send(WriteJob::Append(event.clone())).is_ok());
+        if !queued {
+            self.write_event(event);
+        }
+    }
+
+    /// Wait until every event appended so far is on disk.
+    pub fn flush(&self) {
+        let Some(tx) = &self.writer else {
+            return;
+        };
+        let (done, wait) = mpsc::channel();
+        if tx.send(WriteJob::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+
+    fn write_event(&self, event: &SessionEvent) {
+        // Another machine may share the log through a synced folder. Without the lock
+        // (say its holder hangs) the event is written anyway rather than lost.
+        let _lock = FileLock::acquire(&self.lock_path(), APPEND_LOCK_WAIT, APPEND_LOCK_STALE_SECS);
+        let written = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.events_path)
-        {
-            if let Ok(line) = serde_json::to_string(event) {
-                let _ = writeln_line(&mut f, &line);
-            }
+            .and_then(|mut f| {
+                let line = serde_json::to_string(event)?;
+                writeln_line(&mut f, &line)
+            });
+        if let Err(e) = written {
+            tracing::warn!(
+                path = %self.events_path.display(),
+                error = %e,
+                "session event not written"
+            );
+        }
+        self.refresh_lease();
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.events_path.with_extension("lock")
+    }
+
+    fn owner_path(&self) -> PathBuf {
+        self.events_path.with_extension("owner")
+    }
+
+    /// The live process (here or on another machine) that has this session open, if it
+    /// isn't us.
+    pub fn owner(&self) -> Option<LockOwner> {
+        LockOwner::read(&self.owner_path())
+            .filter(|o| !o.is_current() && o.age_secs() <= LEASE_STALE_SECS)
+    }
+
+    /// Take this session for writing. Fails with its owner when it's open elsewhere.
+    pub fn claim(&self) -> Result<(), LockOwner> {
+        let _lock = FileLock::acquire(&self.lock_path(), APPEND_LOCK_WAIT, APPEND_LOCK_STALE_SECS);
+        if let Some(owner) = self.owner() {
+            return Err(owner);
+        }
+        self.write_lease();
+        Ok(())
+    }
+
+    /// Give the session up, if we hold it.
+    pub fn release(&self) {
+        self.flush();
+        if LockOwner::read(&self.owner_path()).is_some_and(|o| o.is_current()) {
+            let _ = std::fs::remove_file(self.owner_path());
+        }
+    }
+
+    /// Keep our lease fresh; never takes over a session someone else has claimed since.
+    fn refresh_lease(&self) {
+        match LockOwner::read(&self.owner_path()) {
+            Some(o) if o.is_current() && o.age_secs() < LEASE_REFRESH_SECS => {}
+            Some(o) if !o.is_current() && o.age_secs() <= LEASE_STALE_SECS => {}
+            _ => self.write_lease(),
+        }
+    }
+
+    fn write_lease(&self) {
+        if let Ok(json) = serde_json::to_string(&LockOwner::current()) {
+            let _ = std::fs::write(self.owner_path(), json);
         }
     }
 
+    /// Copies of this session's log that a sync tool set aside after concurrent writes
+    /// (Dropbox "(conflicted copy …)", Syncthing ".sync-conflict-…").
+    pub fn conflicted_copies(&self) -> Vec<PathBuf> {
+        let Some(dir) = self.events_path.parent() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut out: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                let Some(name) = p.file_name().and_then(|n| n.to_str()) else {
+                    return false;
+                };
+                // "<id> (conflicted copy …)" but not a longer id that merely starts alike.
+                let own = name.strip_prefix(self.session_id.as_str()).is_some_and(|rest| {
+                    !rest.starts_with(|c: char| c.is_ascii_alphanumeric())
+                });
+                own && is_conflict_copy(name)
+            })
+            .collect();
+        out.sort();
+        out
+    }
+
     pub fn init_file(&self) {
         self.set_latest();
+        let _ = self.claim();
         self.append(&SessionEvent::Meta {
             ts: unix_ts(),
             session_id: self.session_id.clone(),
@@ -124,7 +414,7 @@ impl SessionStore {
         });
     }
 
-    pub fn record_message(&self, msg: &Message) {
+    pub fn record_message(&self, msg: &Message, meta: Option<&TurnMeta>) {
         // Persist tool/system prompts too; display filtering is UI-level.
         self.append(&SessionEvent::Message {
             ts: unix_ts(),
@@ -133,6 +423,9 @@ impl SessionStore {
             reasoning: msg.reasoning.clone(),
             tool_group_id: msg.tool_group_id,
             local: msg.local,
+            model: meta.map(|m| m.model.clone()),
+            plan_mode: meta.map(|m| m.plan_mode),
+            config_hash: meta.map(|m| m.config_hash.clone()),
         });
     }
 
@@ -144,6 +437,21 @@ impl SessionStore {
         });
     }
 
+    pub fn record_title(&self, title: &str) {
+        self.append(&SessionEvent::Title {
+            ts: unix_ts(),
+            title: title.to_string(),
+        });
+    }
+
+    pub fn record_plan(&self, draft: &PlanDraft, accepted: bool) {
+        self.append(&SessionEvent::Plan {
+            ts: unix_ts(),
+            draft: draft.clone(),
+            accepted,
+        });
+    }
+
     pub fn record_tool(&self, tool: &ToolOutput) {
         self.append(&SessionEvent::Tool {
             ts: unix_ts(),
@@ -159,11 +467,22 @@ impl SessionStore {
             sandbox_allowed: Some(tool.sandbox.allowed),
             sandbox_reason: tool.sandbox.reason.clone(),
             group_id: Some(tool.group_id),
+            output_file: tool
+                .spill_file()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
         });
     }
 
     pub fn load_events(&self) -> std::io::Result<Vec<SessionEvent>> {
-        let data = std::fs::read_to_string(&self.events_path)?;
+        self.flush();
+        let data = if self.events_path.exists() {
+            std::fs::read_to_string(&self.events_path)?
+        } else {
+            let packed = std::fs::read(self.compressed_path())?;
+            let data = zstd::decode_all(packed.as_slice())?;
+            String::from_utf8_lossy(&data).into_owned()
+        };
         let mut out = Vec::new();
         for line in data.lines() {
             let line = line.trim();
@@ -178,12 +497,14 @@ impl SessionStore {
     }
 
     pub fn count_events_lines(&self) -> std::io::Result<usize> {
+        self.flush();
         let f = std::fs::File::open(&self.events_path)?;
         let r = std::io::BufReader::new(f);
         Ok(r.lines().count())
     }
 
     pub fn truncate_to_lines(&self, keep_lines: usize) -> std::io::Result<()> {
+        self.flush();
         let src = std::fs::File::open(&self.events_path)?;
         let mut r = std::io::BufReader::new(src);
 
@@ -210,86 +531,202 @@ impl SessionStore {
     }
 }
 
+/// List session files for a project, newest first. Includes compressed sessions.
+pub fn list_sessions(project_root: &Path) -> std::io::Result<Vec<SessionInfo>> {
+    let base = sessions_dir(project_root)?;
+    let Ok(entries) = std::fs::read_dir(&base) else {
+        return Ok(Vec::new());
+    };
+
+    let mut out = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        // Sync conflict copies are reported on resume, not listed as sessions.
+        if is_conflict_copy(name) {
+            continue;
+        }
+        let (session_id, compressed) = if let Some(id) = name.strip_suffix(".jsonl.zst") {
+            (id, true)
+        } else if let Some(id) = name.strip_suffix(".jsonl") {
+            (id, false)
+        } else {
+            continue;
+        };
+        let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        out.push(SessionInfo {
+            session_id: session_id.to_string(),
+            path: path.clone(),
+            compressed,
+            bytes,
+        });
+    }
+
+    out.sort_by(|a, b| b.session_id.cmp(&a.session_id));
+    Ok(out)
+}
+
+/// Rebuild the transcript from `events`. Tool outputs that were spilled to disk are read
+/// from `outputs_dir` when they're needed.
 pub fn replay_into(
     events: &[SessionEvent],
+    outputs_dir: &Path,
     messages: &mut Vec<Message>,
     tools: &mut Vec<ToolOutput>,
 ) {
     let mut turn_id: u64 = 0;
     for ev in events {
-        match ev {
-            SessionEvent::Message {
-                role,
-                content,
-                reasoning,
-                tool_group_id,
-                local,
-                ..
-            } => {
-                if role.eq_ignore_ascii_case("user") {
-                    turn_id = turn_id.saturating_add(1);
-                }
-                messages.push(Message {
-                    id: 0,
-                    role: string_to_role(role),
-                    content: content.clone(),
-                    reasoning: reasoning.clone(),
-                    tool_calls: None,
-                    tool_group_id: *tool_group_id,
-                    local: *local,
-                });
-            }
-            SessionEvent::Checkpoint { .. } => {
-                // Session replay uses message/tool events only.
-            }
-            SessionEvent::Tool {
-                tool,
-                target,
-                output,
-                status,
-                call_id,
-                args_raw,
-                cwd,
-                sandbox_allowed,
-                sandbox_reason,
-                group_id,
-                ..
-            } => {
-                let call_id = call_id.clone().unwrap_or_else(|| "<legacy>".to_string());
-                let args_raw = args_raw.clone().unwrap_or_default();
-                let cwd_path = cwd
-                    .as_ref()
-                    .map(|s| std::path::PathBuf::from(s))
-                    .unwrap_or_else(|| std::path::PathBuf::from("."));
-                let sandbox = crate::sandbox::SandboxDecision {
-                    allowed: sandbox_allowed.unwrap_or(true),
-                    reason: sandbox_reason.clone(),
-                };
-
-                let mut t = ToolOutput::new(
-                    call_id,
-                    tool.clone(),
-                    args_raw,
-                    target.clone(),
-                    cwd_path,
-                    sandbox,
-                    turn_id,
-                    group_id.unwrap_or(0),
-                );
-                t.set_output(output.clone());
-                let success = status.eq_ignore_ascii_case("success");
-                t.complete(success);
-                // Preserve explicit error status if present.
-                if status.eq_ignore_ascii_case("error") {
-                    t.status = ToolStatus::Error;
-                }
-                tools.push(t);
+        if let Some(msg) = message_from_event(ev) {
+            if msg.role == Role::User {
+                turn_id = turn_id.saturating_add(1);
             }
-            SessionEvent::Meta { .. } => {}
+            messages.push(msg);
+        } else if let Some(t) = tool_from_event(ev, turn_id, outputs_dir) {
+            tools.push(t);
         }
     }
 }
 
+/// The transcript message a `Message` event records (with id 0, to be assigned).
+pub fn message_from_event(ev: &SessionEvent) -> Option<Message> {
+    let SessionEvent::Message {
+        role,
+        content,
+        reasoning,
+        tool_group_id,
+        local,
+        ..
+    } = ev
+    else {
+        return None;
+    };
+    Some(Message {
+        id: 0,
+        role: string_to_role(role),
+        content: content.clone(),
+        reasoning: reasoning.clone(),
+        tool_calls: None,
+        tool_group_id: *tool_group_id,
+        local: *local,
+    })
+}
+
+/// The finished tool call a `Tool` event records, as part of turn `turn_id`.
+pub fn tool_from_event(ev: &SessionEvent, turn_id: u64, outputs_dir: &Path) -> Option<ToolOutput> {
+    let SessionEvent::Tool {
+        tool,
+        target,
+        output,
+        status,
+        elapsed_ms,
+        call_id,
+        args_raw,
+        cwd,
+        sandbox_allowed,
+        sandbox_reason,
+        group_id,
+        output_file,
+        ..
+    } = ev
+    else {
+        return None;
+    };
+    let call_id = call_id.clone().unwrap_or_else(|| "<legacy>".to_string());
+    let args_raw = args_raw.clone().unwrap_or_default();
+    let cwd_path = cwd
+        .as_ref()
+        .map(|s| std::path::PathBuf::from(s))
+        .unwrap_or_else(|| std::path::PathBuf::from("."));
+    let sandbox = crate::sandbox::SandboxDecision {
+        allowed: sandbox_allowed.unwrap_or(true),
+        reason: sandbox_reason.clone(),
+    };
+
+    let mut t = ToolOutput::new(
+        call_id,
+        tool.clone(),
+        args_raw,
+        target.clone(),
+        cwd_path,
+        sandbox,
+        turn_id,
+        group_id.unwrap_or(0),
+    );
+    t.set_output(output.clone());
+    if let Some(file) = output_file {
+        t.attach_spilled(outputs_dir.join(file));
+    }
+    let success = status.eq_ignore_ascii_case("success");
+    t.complete(success);
+    t.set_elapsed(std::time::Duration::from_millis(*elapsed_ms as u64));
+    // Preserve explicit error status if present.
+    if status.eq_ignore_ascii_case("error") {
+        t.status = ToolStatus::Error;
+    }
+    Some(t)
+}
+
+/// The model recorded for each message event, in the order [`replay_into`] adds them.
+pub fn message_models(events: &[SessionEvent]) -> Vec<Option<String>> {
+    events
+        .iter()
+        .filter_map(|ev| match ev {
+            SessionEvent::Message { model, .. } => Some(model.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The session's current title.
+pub fn latest_title(events: &[SessionEvent]) -> Option<String> {
+    events.iter().rev().find_map(title_of)
+}
+
+fn title_of(ev: &SessionEvent) -> Option<String> {
+    match ev {
+        SessionEvent::Title { title, .. } => Some(title.clone()),
+        _ => None,
+    }
+}
+
+/// A short title for a session, made from its first request: the first line, cut to
+/// about six words.
+pub fn title_from_request(request: &str) -> Option<String> {
+    const MAX_WORDS: usize = 6;
+    const MAX_CHARS: usize = 48;
+
+    let line = request.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut taken = 1;
+    while taken < words.len().min(MAX_WORDS)
+        && words[..=taken].join(" ").chars().count() <= MAX_CHARS
+    {
+        taken += 1;
+    }
+    let full = words[..taken].join(" ");
+    let cut: String = full.chars().take(MAX_CHARS).collect();
+    let truncated = taken < words.len() || cut != full;
+
+    let cut = cut.trim_end_matches(|c: char| c.is_ascii_punctuation() && !matches!(c, ')' | '`'));
+    let mut chars = cut.chars();
+    let mut title: String = chars.next()?.to_uppercase().collect();
+    title.push_str(chars.as_str());
+    if truncated {
+        title.push('…');
+    }
+    Some(title)
+}
+
+/// The most recently recorded plan draft, for `/plan show` after a resume.
+pub fn latest_plan(events: &[SessionEvent]) -> Option<PlanDraft> {
+    events.iter().rev().find_map(|ev| match ev {
+        SessionEvent::Plan { draft, .. } => Some(draft.clone()),
+        _ => None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -304,6 +741,9 @@ mod tests {
                 reasoning: None,
                 tool_group_id: None,
                 local: false,
+                model: None,
+                plan_mode: None,
+                config_hash: None,
             },
             SessionEvent::Message {
                 ts: 0,
@@ -312,6 +752,9 @@ mod tests {
                 reasoning: None,
                 tool_group_id: None,
                 local: false,
+                model: None,
+                plan_mode: None,
+                config_hash: None,
             },
             SessionEvent::Tool {
                 ts: 0,
@@ -326,6 +769,7 @@ mod tests {
                 sandbox_allowed: None,
                 sandbox_reason: None,
                 group_id: None,
+                output_file: None,
             },
             SessionEvent::Message {
                 ts: 0,
@@ -334,6 +778,9 @@ mod tests {
                 reasoning: None,
                 tool_group_id: None,
                 local: false,
+                model: None,
+                plan_mode: None,
+                config_hash: None,
             },
             SessionEvent::Tool {
                 ts: 0,
@@ -348,12 +795,13 @@ mod tests {
                 sandbox_allowed: None,
                 sandbox_reason: None,
                 group_id: None,
+                output_file: None,
             },
         ];
 
         let mut messages = Vec::new();
         let mut tools = Vec::new();
-        replay_into(&events, &mut messages, &mut tools);
+        replay_into(&events, Path::new("."), &mut messages, &mut tools);
 
         assert_eq!(tools.len(), 2);
         assert_eq!(tools[0].turn_id, 1);
@@ -370,6 +818,9 @@ mod tests {
                 reasoning: None,
                 tool_group_id: None,
                 local: false,
+                model: None,
+                plan_mode: None,
+                config_hash: None,
             },
             SessionEvent::Tool {
                 ts: 0,
@@ -384,12 +835,13 @@ mod tests {
                 sandbox_allowed: Some(true),
                 sandbox_reason: None,
                 group_id: None,
+                output_file: None,
             },
         ];
 
         let mut messages = Vec::new();
         let mut tools = Vec::new();
-        replay_into(&events, &mut messages, &mut tools);
+        replay_into(&events, Path::new("."), &mut messages, &mut tools);
 
         assert_eq!(tools.len(), 1);
         assert_eq!(tools[0].call_id, "call-a");
@@ -397,20 +849,293 @@ mod tests {
         assert_eq!(tools[0].cwd.to_string_lossy(), "/tmp");
         assert!(tools[0].sandbox.allowed);
     }
+
+    #[test]
+    fn assistant_turn_metadata_round_trips() {
+        let legacy = r#"{"type":"message","ts":0,"role":"assistant","content":"ok"}"#;
+        let ev: SessionEvent = serde_json::from_str(legacy).unwrap();
+        assert!(matches!(ev, SessionEvent::Message { model: None, .. }));
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "1".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("1.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        let msg = Message {
+            id: 1,
+            role: Role::Agent,
+            content: "done".into(),
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: false,
+        };
+        let meta = TurnMeta {
+            model: "m-1".into(),
+            plan_mode: true,
+            config_hash: "abc123".into(),
+        };
+        store.record_message(&msg, Some(&meta));
+
+        match &store.load_events().unwrap()[0] {
+            SessionEvent::Message {
+                model,
+                plan_mode,
+                config_hash,
+                ..
+            } => {
+                assert_eq!(model.as_deref(), Some("m-1"));
+                assert_eq!(*plan_mode, Some(true));
+                assert_eq!(config_hash.as_deref(), Some("abc123"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn compressed_session_loads_transparently() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "1".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("1.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        store.record_checkpoint("cp-1", Some("before"));
+
+        store.compress().unwrap();
+        assert!(!store.events_path.exists());
+        assert!(store.compressed_path().exists());
+
+        let events = store.load_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], SessionEvent::Checkpoint { id, .. } if id == "cp-1"));
+
+        store.decompress().unwrap();
+        assert!(store.events_path.exists());
+        assert_eq!(store.load_events().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn titles_come_from_the_first_request_and_renames_win() {
+        assert_eq!(
+            title_from_request("\n  fix the flaky retry test.\nIt fails on CI").as_deref(),
+            Some("Fix the flaky retry test")
+        );
+        assert_eq!(
+            title_from_request("explain how the sandbox confines bash commands on macOS").as_deref(),
+            Some("Explain how the sandbox confines bash…")
+        );
+        assert_eq!(title_from_request("   \n"), None);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "1".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("1.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        store.record_title("Fix the flaky retry test");
+        store.record_title("Retry backoff");
+        assert_eq!(
+            latest_title(&store.load_events().unwrap()).as_deref(),
+            Some("Retry backoff")
+        );
+
+        store.compress().unwrap();
+        let info = SessionInfo {
+            session_id: "1".into(),
+            path: store.compressed_path(),
+            compressed: true,
+            bytes: 0,
+        };
+        assert_eq!(info.title().as_deref(), Some("Retry backoff"));
+    }
+
+    #[test]
+    fn latest_plan_survives_a_reload() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "1".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("1.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        let mut draft = PlanDraft {
+            plan_markdown: "1. Add the flag".into(),
+            questions: vec![crate::app::PlanQuestion {
+                id: "scope".into(),
+                prompt: "Which crate?".into(),
+                kind: crate::app::PlanQuestionKind::Select,
+                options: vec!["core".into(), "cli".into()],
+                default: Some("core".into()),
+            }],
+            answers: Default::default(),
+            completed_steps: vec![1],
+        };
+        store.record_plan(&draft, false);
+        draft.answers.insert("scope".into(), "cli".into());
+        store.record_plan(&draft, true);
+
+        let events = store.load_events().unwrap();
+        let restored = latest_plan(&events).expect("plan restored");
+        assert_eq!(restored.plan_markdown, "1. Add the flag");
+        assert_eq!(restored.answers.get("scope").map(String::as_str), Some("cli"));
+        assert_eq!(restored.questions[0].kind, crate::app::PlanQuestionKind::Select);
+        assert_eq!(restored.completed_steps, vec![1]);
+
+        let mut messages = Vec::new();
+        let mut tools = Vec::new();
+        replay_into(&events, dir.path(), &mut messages, &mut tools);
+        assert!(messages.is_empty(), "plan events are not transcript messages");
+    }
+
+    #[test]
+    fn long_tool_output_spills_to_disk_and_replays_in_full() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "1".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("1.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        let mut tool = ToolOutput::new(
+            "call/1".into(),
+            "bash".into(),
+            r#"{"command":"cargo test"}"#.into(),
+            "cargo test".into(),
+            dir.path().to_path_buf(),
+            crate::sandbox::SandboxDecision::allow(),
+            1,
+            1,
+        );
+        tool.spill_to(store.tool_output_path("call/1"));
+        let mut expected = String::new();
+        for i in 0..20_000 {
+            let chunk = format!("line {}\n", i);
+            expected.push_str(&chunk);
+            tool.append_chunk(chunk);
+        }
+        tool.complete(true);
+
+        let file = tool.spill_file().expect("spilled").clone();
+        assert_eq!(file, dir.path().join("1.tools").join("call_1.txt"));
+        assert!(tool.output.len() <= 64 * 1024);
+        assert!(tool.output.starts_with("line ") && tool.output.ends_with("line 19999\n"));
+        assert_eq!(tool.output_bytes, expected.len());
+        assert_eq!(tool.full_output(), expected);
+
+        // The log keeps the tail; the complete output comes back from the file.
+        store.record_tool(&tool);
+        let events = store.load_events().unwrap();
+        let outputs = store.tool_outputs_dir();
+        let mut messages = Vec::new();
+        let mut tools = Vec::new();
+        replay_into(&events, &outputs, &mut messages, &mut tools);
+        assert_eq!(tools[0].output, tool.output);
+        assert_eq!(tools[0].full_output(), expected);
+
+        // Without the file only the tail is left.
+        std::fs::remove_file(&file).unwrap();
+        let mut tools = Vec::new();
+        replay_into(&events, &outputs, &mut messages, &mut tools);
+        assert_eq!(tools[0].full_output(), tool.output);
+    }
+
+    #[test]
+    fn sessions_open_elsewhere_cannot_be_claimed_and_conflicts_are_found() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "17".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("17.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        };
+        store.init_file();
+        assert!(store.owner().is_none(), "our own lease isn't someone else's");
+        assert!(store.claim().is_ok());
+
+        let elsewhere = LockOwner {
+            host: "laptop".into(),
+            pid: 42,
+            ts: unix_ts(),
+        };
+        std::fs::write(
+            dir.path().join("17.owner"),
+            serde_json::to_string(&elsewhere).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(store.claim(), Err(elsewhere.clone()));
+        // Appends don't steal it back, and release leaves it alone.
+        store.record_title("Busy");
+        store.release();
+        assert_eq!(store.owner(), Some(elsewhere));
+        assert!(!dir.path().join("17.lock").exists());
+
+        // An abandoned lease can be taken over.
+        let stale = LockOwner {
+            host: "laptop".into(),
+            pid: 42,
+            ts: unix_ts() - LEASE_STALE_SECS - 1,
+        };
+        std::fs::write(dir.path().join("17.owner"), serde_json::to_string(&stale).unwrap())
+            .unwrap();
+        assert!(store.claim().is_ok());
+        store.release();
+        assert!(!dir.path().join("17.owner").exists());
+
+        let copy = dir.path().join("17 (conflicted copy 2026-10-01).jsonl");
+        std::fs::write(&copy, "").unwrap();
+        std::fs::write(dir.path().join("170.sync-conflict-20261001.jsonl"), "").unwrap();
+        assert_eq!(store.conflicted_copies(), vec![copy]);
+    }
+
+    #[test]
+    fn appends_wait_for_the_lock_on_the_writer_thread() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = SessionStore {
+            session_id: "18".into(),
+            project_id: "p".into(),
+            events_path: dir.path().join("18.jsonl"),
+            latest_path: dir.path().join("latest"),
+            writer: None,
+        }
+        .with_writer()
+        .unwrap();
+
+        // Another writer holds the lock: the append returns at once and lands later.
+        let held = FileLock::acquire(&store.lock_path(), APPEND_LOCK_WAIT, 60).unwrap();
+        let started = std::time::Instant::now();
+        store.record_title("Queued");
+        assert!(started.elapsed() < APPEND_LOCK_WAIT / 2);
+        drop(held);
+
+        let events = store.load_events().unwrap();
+        assert_eq!(latest_title(&events).as_deref(), Some("Queued"));
+    }
 }
 
 fn sessions_dir(project_root: &Path) -> std::io::Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| {
+    let data = crate::storage::data_dir().ok_or_else(|| {
         std::io::Error::new(std::io::ErrorKind::Other, "Could not determine home dir")
     })?;
 
-    Ok(home
-        .join(".lorikeet")
-        .join("sessions")
-        .join(project_id(project_root)))
+    Ok(data.join("sessions").join(project_id(project_root)))
+}
+
+fn is_conflict_copy(name: &str) -> bool {
+    name.to_ascii_lowercase().contains("conflict")
 }
 
-fn project_id(root: &Path) -> String {
+pub(crate) fn project_id(root: &Path) -> String {
     let canon = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
     let s = canon.to_string_lossy().to_string();
 
@@ -426,6 +1151,8 @@ fn role_to_string(r: Role) -> String {
         Role::Agent => "assistant",
         Role::System => "system",
         Role::Tool => "tool",
+        Role::Draft => "draft",
+        Role::Plan => "plan",
     }
     .to_string()
 }
@@ -436,6 +1163,8 @@ fn string_to_role(s: &str) -> Role {
         "assistant" => Role::Agent,
         "system" => Role::System,
         "tool" => Role::Tool,
+        "draft" => Role::Draft,
+        "plan" => Role::Plan,
         _ => Role::System,
     }
 }
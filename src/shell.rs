@@ -0,0 +1,286 @@
+//! A bash process kept alive between `bash` tool calls (`[shell] persistent = true`), so
+//! `cd`, exported variables and activated virtualenvs carry over like in a terminal.
+//!
+//! Each command is written to a file and sourced with stdin from `/dev/null`; a marker
+//! line with the exit status and `$PWD` follows on stdout, and a bare one on stderr, so
+//! we know when both streams are finished. If the command ends the shell (`exit`, `set -e`
+//! tripping) or times out, the session is dropped and the next call starts a fresh one.
+//!
+//! The shell runs on pipes, not a terminal: `tty: true` calls (and `[shell] pty`) run in
+//! their own PTY instead, starting from the shell's working directory.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::config::ShellConfig;
+use crate::confine::Confinement;
+use crate::sandbox::SandboxPolicy;
+
+/// Which stream a line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub struct ShellSession {
+    child: Child,
+    stdin: ChildStdin,
+    lines: mpsc::UnboundedReceiver<(Stream, String)>,
+    marker: String,
+    script: PathBuf,
+    /// The workspace root it was started in; a shell from another workspace isn't reused
+    pub root: PathBuf,
+    /// Working directory after the last command
+    pub cwd: PathBuf,
+    /// Set when OS confinement couldn't be applied to the shell
+    pub unconfined_note: Option<String>,
+}
+
+/// A conversation's shell, if one is running. The app owns one per conversation and
+/// hands it to every tool call; a new conversation gets a new one.
+pub type SharedShell = Arc<Mutex<Option<ShellSession>>>;
+
+/// Whether `bash` calls share one shell (`[shell] persistent`, default false).
+pub fn persistent(shell: Option<&ShellConfig>) -> bool {
+    shell.and_then(|s| s.persistent).unwrap_or(false)
+}
+
+/// Whether `bash` calls run under a PTY by default (`[shell] pty`, default false).
+pub fn pty(shell: Option<&ShellConfig>) -> bool {
+    shell.and_then(|s| s.pty).unwrap_or(false)
+}
+
+impl ShellSession {
+    /// Start bash in `policy.root`, under the same confinement as one-off commands.
+    pub fn spawn(policy: &SandboxPolicy) -> std::io::Result<Self> {
+        const SHELL: &str = "exec bash --noprofile --norc";
+        let mut unconfined_note = None;
+        let mut cmd = match Confinement::from_policy(policy).map(|c| c.bash_command(SHELL)) {
+            Some(Ok(cmd)) => cmd,
+            unconfined => {
+                if let Some(Err(e)) = unconfined {
+                    unconfined_note = Some(format!(
+                        "[sandbox] OS confinement unavailable ({}); running with policy checks only\n",
+                        e
+                    ));
+                }
+                let mut cmd = Command::new("bash");
+                cmd.arg("--noprofile").arg("--norc");
+                cmd
+            }
+        };
+        let mut child = cmd
+            .current_dir(&policy.root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let (tx, lines) = mpsc::unbounded_channel();
+        if let Some(out) = child.stdout.take() {
+            tokio::spawn(forward_lines(out, Stream::Stdout, tx.clone()));
+        }
+        if let Some(err) = child.stderr.take() {
+            tokio::spawn(forward_lines(err, Stream::Stderr, tx));
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            marker: format!("__LORIKEET_DONE_{}", id),
+            script: std::env::temp_dir().join(format!("lorikeet-shell-{}.sh", id)),
+            root: policy.root.clone(),
+            cwd: policy.root.clone(),
+            unconfined_note,
+        })
+    }
+
+    /// Run `command`, passing each output line to `on_line` as it arrives. Returns the
+    /// exit status; an error means the shell is gone (or was stuck) and should be dropped.
+    pub async fn run(
+        &mut self,
+        command: &str,
+        timeout: Duration,
+        mut on_line: impl FnMut(Stream, String),
+    ) -> Result<i32, String> {
+        std::fs::write(&self.script, format!("{}\n", command))
+            .map_err(|e| format!("Error: can't write {}: {}", self.script.display(), e))?;
+        let wrapped = format!(
+            ". {script} < /dev/null\n__lk=$?; printf '%s %d %s\\n' {m} \"$__lk\" \"$PWD\"; printf '%s\\n' {m} >&2\n",
+            script = sh_quote(&self.script.display().to_string()),
+            m = self.marker,
+        );
+        if let Err(e) = self.stdin.write_all(wrapped.as_bytes()).await {
+            return Err(self.exited(e.to_string()).await);
+        }
+        let _ = self.stdin.flush().await;
+
+        let mut status = None;
+        let mut stderr_done = false;
+        let deadline = tokio::time::Instant::now() + timeout;
+        while status.is_none() || !stderr_done {
+            let line = match tokio::time::timeout_at(deadline, self.lines.recv()).await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Err(self.exited("output closed".into()).await),
+                Err(_) => {
+                    let _ = self.child.start_kill();
+                    return Err(format!(
+                        "Error: Command timed out after {} seconds; the shell session was reset\n",
+                        timeout.as_secs()
+                    ));
+                }
+            };
+            let (stream, text) = line;
+            let Some(at) = text.find(&self.marker) else {
+                on_line(stream, text);
+                continue;
+            };
+            // Output without a trailing newline runs into the marker.
+            if at > 0 {
+                on_line(stream, format!("{}\n", &text[..at]));
+            }
+            match stream {
+                Stream::Stderr => stderr_done = true,
+                Stream::Stdout => {
+                    let rest = text[at + self.marker.len()..].trim_end_matches(['\r', '\n']);
+                    let (code, cwd) = parse_status(rest);
+                    if let Some(cwd) = cwd {
+                        self.cwd = cwd;
+                    }
+                    status = Some(code);
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&self.script);
+        Ok(status.unwrap_or(-1))
+    }
+
+    async fn exited(&mut self, reason: String) -> String {
+        let _ = std::fs::remove_file(&self.script);
+        let status = tokio::time::timeout(Duration::from_secs(2), self.child.wait()).await;
+        let how = match status {
+            Ok(Ok(s)) => format!("exited ({})", s),
+            _ => format!("stopped responding ({})", reason),
+        };
+        format!(
+            "[shell] the shell {}; the next command starts a new session in the workspace\n",
+            how
+        )
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.script);
+    }
+}
+
+/// `s` as one single-quoted shell word, whatever quotes it contains.
+pub(crate) fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// `" <status> <cwd>"` from the stdout marker line.
+fn parse_status(rest: &str) -> (i32, Option<PathBuf>) {
+    let rest = rest.trim_start();
+    let (code, cwd) = rest.split_once(' ').unwrap_or((rest, ""));
+    let cwd = (!cwd.is_empty()).then(|| PathBuf::from(cwd));
+    (code.parse().unwrap_or(-1), cwd)
+}
+
+async fn forward_lines<R: tokio::io::AsyncRead + Unpin>(
+    r: R,
+    stream: Stream,
+    tx: mpsc::UnboundedSender<(Stream, String)>,
+) {
+    let mut reader = BufReader::new(r);
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if tx
+                    .send((stream, String::from_utf8_lossy(&buf).into_owned()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn state_carries_over_between_commands() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let policy = SandboxPolicy::from_config(
+            crate::config::AppConfig::default(),
+            dir.path().to_path_buf(),
+            &crate::tool_registry::names(),
+        );
+        let mut shell = ShellSession::spawn(&policy).unwrap();
+        let timeout = Duration::from_secs(10);
+        let mut out = Vec::new();
+
+        let code = shell
+            .run("cd sub && export LK_TEST=kept", timeout, |_, l| out.push(l))
+            .await
+            .unwrap();
+        assert_eq!(code, 0);
+        assert!(shell.cwd.ends_with("sub"));
+        assert_eq!(shell.root, dir.path());
+
+        let code = shell
+            .run(
+                "echo \"$LK_TEST\"; printf partial; echo oops >&2; false",
+                timeout,
+                |s, l| out.push(format!("{:?}:{}", s, l)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(code, 1);
+        assert!(out.contains(&"Stdout:kept\n".to_string()));
+        assert!(out.contains(&"Stdout:partial\n".to_string()));
+        assert!(out.contains(&"Stderr:oops\n".to_string()));
+
+        let gone = shell.run("exit 3", timeout, |_, _| {}).await;
+        assert!(gone.unwrap_err().contains("exited"));
+    }
+
+    #[tokio::test]
+    async fn paths_with_quotes_stay_one_word() {
+        assert_eq!(sh_quote("it's"), "'it'\\''s'");
+        let dir = tempfile::TempDir::new().unwrap();
+        let odd = dir.path().join("it's; touch pwned");
+        std::fs::create_dir(&odd).unwrap();
+        let policy = SandboxPolicy::from_config(
+            crate::config::AppConfig::default(),
+            dir.path().to_path_buf(),
+            &crate::tool_registry::names(),
+        );
+        let mut shell = ShellSession::spawn(&policy).unwrap();
+        let cd = format!("cd -- {}", sh_quote(&odd.display().to_string()));
+        let code = shell
+            .run(&cd, Duration::from_secs(10), |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(code, 0);
+        assert_eq!(shell.cwd, odd);
+        assert!(!dir.path().join("pwned").exists());
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::app::{Message, Role, ToolOutput, ToolStatus};
+use crate::llm::TokenUsage;
+use crate::tools::patch_line_stats;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolCounts {
+    pub ok: usize,
+    pub failed: usize,
+}
+
+/// What happened in a session, for `/stats` and exported transcripts.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    pub turns: usize,
+    pub tools: BTreeMap<String, ToolCounts>,
+    /// Total run time per tool
+    pub tool_time: BTreeMap<String, Duration>,
+    pub files_read: BTreeSet<String>,
+    pub files_changed: BTreeSet<String>,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub usage: TokenUsage,
+}
+
+impl SessionStats {
+    /// Files and lines come from successful file tool calls (see [`file_changes`]);
+    /// edits made through `bash` aren't seen.
+    pub fn collect(messages: &[Message], tools: &[ToolOutput], usage: TokenUsage) -> Self {
+        let mut stats = Self {
+            turns: messages
+                .iter()
+                .filter(|m| m.role == Role::User && !m.local)
+                .count(),
+            usage,
+            ..Self::default()
+        };
+
+        for tool in tools {
+            let counts = stats.tools.entry(tool.tool.clone()).or_default();
+            match tool.status {
+                ToolStatus::Success => counts.ok += 1,
+                ToolStatus::Error => counts.failed += 1,
+                ToolStatus::Running => {}
+            }
+            if tool.status != ToolStatus::Running {
+                *stats.tool_time.entry(tool.tool.clone()).or_default() += tool.elapsed();
+            }
+            if tool.status != ToolStatus::Success {
+                continue;
+            }
+
+            if matches!(tool.tool.as_str(), "read_file" | "open_at") {
+                let args: serde_json::Value =
+                    serde_json::from_str(&tool.args_raw).unwrap_or_default();
+                if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
+                    if !path.trim().is_empty() {
+                        stats.files_read.insert(path.trim().to_string());
+                    }
+                }
+                continue;
+            }
+            for (path, added, removed) in file_changes(&tool.tool, &tool.args_raw) {
+                stats.lines_added += added;
+                stats.lines_removed += removed;
+                stats.files_changed.insert(path);
+            }
+        }
+        stats
+    }
+
+    pub fn tool_calls(&self) -> ToolCounts {
+        self.tools
+            .values()
+            .fold(ToolCounts::default(), |acc, c| ToolCounts {
+                ok: acc.ok + c.ok,
+                failed: acc.failed + c.failed,
+            })
+    }
+
+    /// Compact Markdown tables (rendered in chat and appended to exports).
+    pub fn to_markdown(&self) -> String {
+        let calls = self.tool_calls();
+        let mut out = String::new();
+        let _ = writeln!(out, "| Session | |");
+        let _ = writeln!(out, "|---|---|");
+        let _ = writeln!(out, "| Turns | {} |", self.turns);
+        let _ = writeln!(
+            out,
+            "| Tool calls | {} ({} ok, {} failed) |",
+            calls.ok + calls.failed,
+            calls.ok,
+            calls.failed
+        );
+        let _ = writeln!(
+            out,
+            "| Files | {} read, {} changed |",
+            self.files_read.len(),
+            self.files_changed.len()
+        );
+        let _ = writeln!(
+            out,
+            "| Lines | +{} / -{} |",
+            self.lines_added, self.lines_removed
+        );
+        let _ = writeln!(
+            out,
+            "| Tokens | {} in / {} out |",
+            compact_count(self.usage.prompt_tokens),
+            compact_count(self.usage.completion_tokens)
+        );
+        if self.usage.cached_tokens > 0 {
+            let _ = writeln!(
+                out,
+                "| Prompt cache | {:.1}% hit ({} tokens) |",
+                self.usage.cached_tokens as f64 * 100.0 / self.usage.prompt_tokens.max(1) as f64,
+                compact_count(self.usage.cached_tokens)
+            );
+        }
+        let cost = match self.usage.cost {
+            Some(c) => format!("${:.4}", c),
+            None => "n/a".to_string(),
+        };
+        let _ = writeln!(out, "| Cost | {} |", cost);
+
+        if !self.tools.is_empty() {
+            let _ = writeln!(out, "\n| Tool | Calls | Failed |");
+            let _ = writeln!(out, "|---|---:|---:|");
+            let mut tools: Vec<_> = self.tools.iter().collect();
+            tools.sort_by_key(|(_, c)| std::cmp::Reverse(c.ok + c.failed));
+            for (name, c) in tools {
+                let _ = writeln!(out, "| {} | {} | {} |", name, c.ok + c.failed, c.failed);
+            }
+        }
+
+        if !self.files_changed.is_empty() {
+            let files: Vec<&str> = self.files_changed.iter().map(String::as_str).collect();
+            let _ = writeln!(out, "\nChanged: {}", files.join(", "));
+        }
+        out
+    }
+}
+
+/// Model time and usage for one turn, as reported by `call_llm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TurnTiming {
+    pub llm: Duration,
+    pub llm_calls: usize,
+    pub usage: TokenUsage,
+}
+
+/// One row of the `/stats` dashboard: where a turn's time went.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TurnStats {
+    pub turn: u64,
+    pub llm: Duration,
+    pub llm_calls: usize,
+    pub tools: Duration,
+    pub tool_calls: ToolCounts,
+    pub usage: TokenUsage,
+}
+
+impl TurnStats {
+    pub fn total(&self) -> Duration {
+        self.llm + self.tools
+    }
+}
+
+/// Per-turn breakdown, oldest first, from the model timings and the finished tool calls
+/// (which run one after another, so their times add up).
+pub fn turn_breakdown(timings: &BTreeMap<u64, TurnTiming>, tools: &[ToolOutput]) -> Vec<TurnStats> {
+    let mut turns: BTreeMap<u64, TurnStats> = timings
+        .iter()
+        .map(|(&turn, t)| {
+            let row = TurnStats {
+                turn,
+                llm: t.llm,
+                llm_calls: t.llm_calls,
+                usage: t.usage,
+                ..TurnStats::default()
+            };
+            (turn, row)
+        })
+        .collect();
+    for tool in tools.iter().filter(|t| t.status != ToolStatus::Running) {
+        let row = turns.entry(tool.turn_id).or_insert_with(|| TurnStats {
+            turn: tool.turn_id,
+            ..TurnStats::default()
+        });
+        row.tools += tool.elapsed();
+        match tool.status {
+            ToolStatus::Success => row.tool_calls.ok += 1,
+            _ => row.tool_calls.failed += 1,
+        }
+    }
+    turns.into_values().collect()
+}
+
+/// `1.2s`, `850ms`, `2m05s`
+pub fn format_duration(d: Duration) -> String {
+    let ms = d.as_millis();
+    match ms {
+        0..=999 => format!("{}ms", ms),
+        1_000..=59_999 => format!("{:.1}s", d.as_secs_f64()),
+        _ => format!("{}m{:02}s", ms / 60_000, (ms / 1_000) % 60),
+    }
+}
+
+/// Files a file-editing tool call changes, with lines added and removed, worked out from
+/// its arguments (`edit_file` strings, `write_file` content, `apply_patch` hunks). Empty
+/// for every other tool and for `edit_file` dry runs.
+pub fn file_changes(tool: &str, args_raw: &str) -> Vec<(String, usize, usize)> {
+    let args: serde_json::Value = serde_json::from_str(args_raw).unwrap_or_default();
+    let str_arg = |key: &str| args.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let path = str_arg("path").trim().trim_start_matches("./").to_string();
+    if args.get("dry_run").and_then(|v| v.as_bool()) == Some(true) {
+        return Vec::new();
+    }
+    match tool {
+        "write_file" if !path.is_empty() => {
+            vec![(path, str_arg("content").lines().count(), 0)]
+        }
+        "edit_file" if !path.is_empty() => vec![(
+            path,
+            str_arg("new_string").lines().count(),
+            str_arg("old_string").lines().count(),
+        )],
+        "apply_patch" => patch_line_stats(str_arg("patch")),
+        _ => Vec::new(),
+    }
+}
+
+pub fn compact_count(n: u64) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_999 => format!("{:.1}k", n as f64 / 1_000.0),
+        _ => format!("{:.1}M", n as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::SandboxDecision;
+
+    fn tool(name: &str, args: &str, success: bool) -> ToolOutput {
+        let mut t = ToolOutput::new(
+            "c".into(),
+            name.into(),
+            args.into(),
+            String::new(),
+            ".".into(),
+            SandboxDecision::allow(),
+            1,
+            1,
+        );
+        t.complete(success);
+        t
+    }
+
+    #[test]
+    fn stats_count_turns_tools_files_and_lines() {
+        let user = Message {
+            id: 0,
+            role: Role::User,
+            content: "fix it".into(),
+            reasoning: None,
+            tool_calls: None,
+            tool_group_id: None,
+            local: false,
+        };
+        let tools = vec![
+            tool("read_file", r#"{"path":"src/a.rs"}"#, true),
+            tool(
+                "edit_file",
+                r#"{"path":"src/a.rs","old_string":"a\nb","new_string":"c"}"#,
+                true,
+            ),
+            tool(
+                "apply_patch",
+                r#"{"patch":"*** Begin Patch\n*** Update File: src/b.rs\n@@\n-x\n+y\n+z\n*** End Patch"}"#,
+                true,
+            ),
+            tool(
+                "write_file",
+                r#"{"path":"src/c.rs","content":"1\n2\n3\n"}"#,
+                false,
+            ),
+            tool("bash", r#"{"command":"cargo test"}"#, false),
+        ];
+        let usage = TokenUsage {
+            prompt_tokens: 12_345,
+            completion_tokens: 678,
+            cached_tokens: 6_000,
+            cost: Some(0.01),
+        };
+
+        let stats = SessionStats::collect(&[user], &tools, usage);
+        assert_eq!(stats.turns, 1);
+        assert_eq!(stats.tool_calls(), ToolCounts { ok: 3, failed: 2 });
+        assert_eq!(stats.files_read.len(), 1);
+        let changed: Vec<&str> = stats.files_changed.iter().map(String::as_str).collect();
+        assert_eq!(changed, ["src/a.rs", "src/b.rs"]);
+        assert_eq!((stats.lines_added, stats.lines_removed), (3, 3));
+
+        let md = stats.to_markdown();
+        assert!(md.contains("| Tokens | 12.3k in / 678 out |"));
+        assert!(md.contains("| Prompt cache | 48.6% hit (6.0k tokens) |"));
+        assert!(md.contains("| Cost | $0.0100 |"));
+        assert!(md.contains("| bash | 1 | 1 |"));
+    }
+
+    #[test]
+    fn turn_breakdown_splits_model_and_tool_time() {
+        let mut timings = BTreeMap::new();
+        timings.insert(
+            1,
+            TurnTiming {
+                llm: Duration::from_millis(1500),
+                llm_calls: 2,
+                usage: TokenUsage {
+                    prompt_tokens: 100,
+                    completion_tokens: 10,
+                    ..TokenUsage::default()
+                },
+            },
+        );
+        let mut running = tool("bash", "{}", true);
+        running.status = ToolStatus::Running;
+        let tools = vec![tool("bash", "{}", true), tool("rg", "{}", false), running];
+
+        let rows = turn_breakdown(&timings, &tools);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].turn, 1);
+        assert_eq!(rows[0].llm_calls, 2);
+        assert_eq!(rows[0].tool_calls, ToolCounts { ok: 1, failed: 1 });
+        assert!(rows[0].total() >= Duration::from_millis(1500));
+        assert_eq!(rows[0].usage.prompt_tokens, 100);
+
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_millis(1250)), "1.2s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+    }
+}
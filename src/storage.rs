@@ -0,0 +1,222 @@
+//! Where sessions, checkpoints and global memory live.
+//!
+//! Everything defaults to `~/.lorikeet/`; `[storage] dir` moves it elsewhere, e.g. into a
+//! synced folder so sessions can be picked up on another machine. Such a folder can be
+//! written from two machines at once, so session logs are guarded by [`FileLock`]s.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+
+/// `[storage] dir`, once [`configure`]d; `None` means `~/.lorikeet`.
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Apply `[storage] dir` from `config`. Call before opening sessions or memory.
+pub fn configure(config: &AppConfig) {
+    let dir = config
+        .storage
+        .as_ref()
+        .and_then(|s| s.dir.as_deref())
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(expand_home);
+    *DATA_DIR.write().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+/// Root for sessions, checkpoints and global memory.
+pub fn data_dir() -> Option<PathBuf> {
+    let configured = DATA_DIR.read().unwrap_or_else(|e| e.into_inner()).clone();
+    configured.or_else(|| dirs::home_dir().map(|h| h.join(".lorikeet")))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with('/') => &rest[1..],
+        _ => return PathBuf::from(path),
+    };
+    match dirs::home_dir() {
+        Some(home) if rest.is_empty() => home,
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Who holds a lock, written into the lock file so others can tell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub host: String,
+    pub pid: u32,
+    /// When the lock was taken or last refreshed (unix seconds).
+    pub ts: i64,
+}
+
+impl LockOwner {
+    pub fn current() -> Self {
+        Self {
+            host: host_name(),
+            pid: std::process::id(),
+            ts: unix_ts(),
+        }
+    }
+
+    /// Whether this is the running process.
+    pub fn is_current(&self) -> bool {
+        self.pid == std::process::id() && self.host == host_name()
+    }
+
+    pub fn age_secs(&self) -> i64 {
+        unix_ts().saturating_sub(self.ts)
+    }
+
+    /// Read the owner recorded in `path`; `None` if it's missing or unreadable.
+    pub fn read(path: &Path) -> Option<Self> {
+        let data = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn write_to(&self, f: &mut std::fs::File) -> std::io::Result<()> {
+        let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        f.write_all(json.as_bytes())
+    }
+}
+
+impl std::fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pid {} on {}", self.pid, self.host)
+    }
+}
+
+/// An exclusive lock, held by creating its file; the file is removed on drop.
+///
+/// `create_new` is atomic on local and network file systems, so this works across
+/// processes and, for shared mounts, across machines.
+#[derive(Debug)]
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Take the lock at `path`, waiting up to `wait` for its holder to let go. A lock
+    /// older than `stale_secs` is broken: its holder crashed or lost the connection.
+    pub fn acquire(path: &Path, wait: Duration, stale_secs: i64) -> std::io::Result<Self> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(mut f) => {
+                    let _ = LockOwner::current().write_to(&mut f);
+                    return Ok(Self {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    // A holder that hasn't finished writing its owner yet looks fresh.
+                    let stale = LockOwner::read(path).map_or_else(
+                        || file_age_secs(path) > stale_secs,
+                        |o| o.age_secs() > stale_secs,
+                    );
+                    if stale {
+                        let _ = std::fs::remove_file(path);
+                        continue;
+                    }
+                    if Instant::now() >= deadline {
+                        let holder = LockOwner::read(path)
+                            .map(|o| o.to_string())
+                            .unwrap_or_else(|| "another process".into());
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::WouldBlock,
+                            format!("{} is locked by {}", path.display(), holder),
+                        ));
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn file_age_secs(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// This machine's name, to tell lock holders on different machines apart.
+pub fn host_name() -> String {
+    for var in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(name) = std::env::var(var) {
+            if !name.trim().is_empty() {
+                return name.trim().to_string();
+            }
+        }
+    }
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "localhost".into())
+}
+
+fn unix_ts() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_lock_excludes_until_dropped_and_breaks_stale_locks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("events.lock");
+
+        let held = FileLock::acquire(&path, Duration::ZERO, 30).unwrap();
+        let err = FileLock::acquire(&path, Duration::from_millis(30), 30).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains(&format!("pid {}", std::process::id())));
+        drop(held);
+        assert!(!path.exists());
+
+        // A crashed holder's lock is taken over once it's old enough.
+        let crashed = LockOwner {
+            host: "elsewhere".into(),
+            pid: 1,
+            ts: unix_ts() - 120,
+        };
+        std::fs::write(&path, serde_json::to_string(&crashed).unwrap()).unwrap();
+        let taken = FileLock::acquire(&path, Duration::ZERO, 60).unwrap();
+        assert!(LockOwner::read(&path).unwrap().is_current());
+        drop(taken);
+    }
+
+    #[test]
+    fn expand_home_only_touches_a_leading_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/Sync/lorikeet"), home.join("Sync/lorikeet"));
+        assert_eq!(expand_home("/srv/~x"), PathBuf::from("/srv/~x"));
+        assert_eq!(expand_home("~other/x"), PathBuf::from("~other/x"));
+    }
+}
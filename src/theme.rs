@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use ratatui::style::{Color, Modifier, Style};
 
@@ -63,6 +64,30 @@ pub fn ui_theme_name(config: &AppConfig) -> String {
         .unwrap_or_else(|| "system".to_string())
 }
 
+/// Status marks. Each state has its own shape (running ones use the ◐ spinner), so status
+/// reads without color.
+pub const STATUS_OK: &str = "✓";
+pub const STATUS_ERR: &str = "✗";
+
+/// `accessibility.no_color_status`: status marks go without their colors.
+pub fn no_color_status(config: &AppConfig) -> bool {
+    config
+        .accessibility
+        .as_ref()
+        .and_then(|a| a.no_color_status)
+        .unwrap_or(false)
+}
+
+/// The style for a status mark or label that would be `color`: bold in the regular text
+/// color when statuses go without color.
+pub fn status_style(config: &AppConfig, color: Color) -> Style {
+    if no_color_status(config) {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(color)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct MarkdownTheme {
     pub text: Color,
@@ -164,6 +189,81 @@ pub fn ui_palette(config: &AppConfig, workspace_root: Option<&Path>) -> UiPalett
     ui_theme(config, workspace_root).palette
 }
 
+/// The active theme, kept between frames and re-read only when the theme name, the
+/// background mode or one of its theme files changes, so edits to a workspace theme show up
+/// while you work. A file that stops parsing keeps the last good theme.
+#[derive(Default)]
+pub struct ThemeCache {
+    stamp: Option<ThemeStamp>,
+    theme: Option<UiTheme>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ThemeStamp {
+    name: String,
+    background: BackgroundMode,
+    /// Modification time and size of `<dir>/<name>.json` in each theme directory
+    files: Vec<Option<(SystemTime, u64)>>,
+}
+
+impl ThemeCache {
+    pub fn get(&mut self, config: &AppConfig, workspace_root: Option<&Path>) -> UiTheme {
+        let name = normalize_theme_name(&ui_theme_name(config));
+        let files = if builtin_themes().contains_key(name.as_str()) {
+            Vec::new()
+        } else {
+            ui_theme_paths(workspace_root)
+                .into_iter()
+                .map(|dir| {
+                    let meta = std::fs::metadata(dir.join(format!("{}.json", name))).ok()?;
+                    Some((meta.modified().ok()?, meta.len()))
+                })
+                .collect()
+        };
+        let stamp = ThemeStamp {
+            name: name.clone(),
+            background: background_mode(config),
+            files,
+        };
+        if let (Some(old), Some(theme)) = (&self.stamp, &self.theme) {
+            if *old == stamp {
+                return theme.clone();
+            }
+        }
+
+        let same_theme = self.stamp.as_ref().is_some_and(|s| s.name == name);
+        let loaded = match builtin_themes().get(name.as_str()) {
+            Some(t) => Ok(Some(t.clone())),
+            None => load_custom_theme(&name, workspace_root),
+        };
+        let theme = match (loaded, self.theme.take()) {
+            (Ok(Some(t)), _) => {
+                self.error = None;
+                apply_background_mode(t, config)
+            }
+            (Err(e), Some(previous)) if same_theme => {
+                self.error = Some(e);
+                previous
+            }
+            _ => apply_background_mode(theme_from_palette(system_palette()), config),
+        };
+        self.stamp = Some(stamp);
+        self.theme = Some(theme.clone());
+        theme
+    }
+
+    /// Whether the current theme comes from a file that `get` checks for edits.
+    pub fn watches_files(&self) -> bool {
+        self.stamp.as_ref().is_some_and(|s| !s.files.is_empty())
+    }
+
+    /// Why the last reload kept the previous theme, if it did; cleared when read.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum BackgroundMode {
     Inherit,
@@ -227,6 +327,7 @@ pub fn builtin_theme_tagline(name: &str) -> Option<&'static str> {
         "nord" => Some("cold"),
         "matrix" => Some("green phosphor"),
         "one-dark" => Some("classic"),
+        "high-contrast" => Some("black + white, color-blind safe status"),
         _ => None,
     }
 }
@@ -404,6 +505,20 @@ fn builtin_palettes() -> HashMap<&'static str, UiPalette> {
                 err: rgb("#e06c75"),
             },
         ),
+        (
+            // Okabe-Ito colors for status: told apart with any kind of color blindness.
+            "high-contrast",
+            UiPalette {
+                fg: rgb("#ffffff"),
+                fg_dim: rgb("#c0c0c0"),
+                bg: rgb("#000000"),
+                border: rgb("#ffffff"),
+                accent: rgb("#56b4e9"),
+                ok: rgb("#009e73"),
+                warn: rgb("#f0e442"),
+                err: rgb("#d55e00"),
+            },
+        ),
     ])
 }
 
@@ -493,14 +608,21 @@ fn load_ui_palette(name: &str, workspace_root: Option<&Path>) -> Option<UiPalett
     load_custom_palette(name, workspace_root).ok().flatten()
 }
 
-fn load_custom_theme(name: &str, workspace_root: Option<&Path>) -> Result<Option<UiTheme>, ()> {
+/// The first theme file for `name` that parses. Files that exist but don't parse are
+/// skipped; if none parses, the first problem is the error.
+fn load_custom_theme(name: &str, workspace_root: Option<&Path>) -> Result<Option<UiTheme>, String> {
+    let mut error = None;
     for dir in ui_theme_paths(workspace_root) {
         let path = dir.join(format!("{}.json", name));
         let Ok(bytes) = std::fs::read(&path) else {
             continue;
         };
-        let Ok(v) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
-            continue;
+        let v = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+            Ok(v) => v,
+            Err(e) => {
+                error.get_or_insert_with(|| format!("{}: {}", path.display(), e));
+                continue;
+            }
         };
         if let Some(t) = theme_from_json(&v) {
             return Ok(Some(t));
@@ -509,8 +631,14 @@ fn load_custom_theme(name: &str, workspace_root: Option<&Path>) -> Result<Option
         if let Some(p) = palette_from_json(&v) {
             return Ok(Some(theme_from_palette(p)));
         }
+        error.get_or_insert_with(|| {
+            format!("{}: no \"theme\" or \"palette\" object", path.display())
+        });
+    }
+    match error {
+        Some(e) => Err(e),
+        None => Ok(None),
     }
-    Ok(None)
 }
 
 fn load_custom_palette(name: &str, workspace_root: Option<&Path>) -> Result<Option<UiPalette>, ()> {
@@ -814,6 +942,160 @@ pub fn list_ui_themes(workspace_root: Option<&Path>) -> Vec<String> {
     names
 }
 
+const UI_KEYS: &[&str] = &[
+    "fg", "fg_dim", "bg", "border", "accent", "ok", "warn", "err",
+];
+const MARKDOWN_KEYS: &[&str] = &[
+    "text",
+    "bold",
+    "italic",
+    "code",
+    "code_bg",
+    "heading",
+    "heading2",
+    "heading3",
+    "list_marker",
+    "link",
+    "blockquote",
+    "blockquote_bar",
+    "hr",
+    "table_border",
+    "table_header",
+    "strikethrough",
+    "checkbox",
+];
+const SYNTAX_KEYS: &[&str] = &[
+    "keyword", "type", "string", "number", "comment", "punct", "ident",
+];
+const TOOL_TRACE_KEYS: &[&str] = &[
+    "title",
+    "invocation",
+    "details_key",
+    "details_value",
+    "out_prefix",
+    "out_text",
+    "sandbox_allow",
+    "sandbox_deny",
+    "call_id",
+    "cwd",
+    "duration",
+];
+
+/// What `lorikeet theme check` found in a theme file. Missing keys only fall back to
+/// defaults; invalid and unknown keys are mistakes.
+#[derive(Debug, Default)]
+pub struct ThemeCheck {
+    /// Keys the file doesn't set, e.g. `theme.markdown.code_bg`
+    pub missing: Vec<String>,
+    /// Keys whose value isn't a color (or a `$name` that doesn't resolve), with the value
+    pub invalid: Vec<(String, String)>,
+    /// Keys Lorikeet doesn't read (usually typos)
+    pub unknown: Vec<String>,
+}
+
+impl ThemeCheck {
+    pub fn is_ok(&self) -> bool {
+        self.invalid.is_empty() && self.unknown.is_empty()
+    }
+}
+
+pub fn check_theme_file(path: &Path) -> Result<ThemeCheck, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let v = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?;
+    check_theme(&v)
+}
+
+pub fn check_theme(v: &serde_json::Value) -> Result<ThemeCheck, String> {
+    let obj = v.as_object().ok_or("a theme file is a JSON object")?;
+    let defs = obj
+        .get("defs")
+        .and_then(|d| d.as_object())
+        .cloned()
+        .unwrap_or_default();
+    let mut check = ThemeCheck::default();
+
+    // Minimal palette-only files, as `palette_from_json` reads them.
+    let palette = obj.get("palette").map(|p| ("palette", p)).or_else(|| {
+        obj.get("theme")
+            .filter(|t| t.get("fg").is_some() || t.get("accent").is_some())
+            .map(|t| ("theme", t))
+    });
+    if let Some((section, p)) = palette {
+        check_color_section(&mut check, section, Some(p), UI_KEYS, &defs);
+        return Ok(check);
+    }
+
+    let theme = obj
+        .get("theme")
+        .and_then(|t| t.as_object())
+        .ok_or("no \"theme\" or \"palette\" object")?;
+    for (section, keys) in [
+        ("ui", UI_KEYS),
+        ("markdown", MARKDOWN_KEYS),
+        ("syntax", SYNTAX_KEYS),
+        ("tool_trace", TOOL_TRACE_KEYS),
+    ] {
+        let name = format!("theme.{}", section);
+        check_color_section(&mut check, &name, theme.get(section), keys, &defs);
+    }
+    if let Some(files) = theme.get("files").and_then(|f| f.as_object()) {
+        for (group, entries) in files {
+            let Some(entries) = entries
+                .as_object()
+                .filter(|_| group == "categories" || group == "extensions")
+            else {
+                check.unknown.push(format!("theme.files.{}", group));
+                continue;
+            };
+            for (k, spec) in entries {
+                if spec.as_str().and_then(parse_style_spec).is_none() {
+                    check
+                        .invalid
+                        .push((format!("theme.files.{}.{}", group, k), spec.to_string()));
+                }
+            }
+        }
+    }
+    for k in theme.keys() {
+        if !["ui", "markdown", "syntax", "tool_trace", "files"].contains(&k.as_str()) {
+            check.unknown.push(format!("theme.{}", k));
+        }
+    }
+    Ok(check)
+}
+
+fn check_color_section(
+    check: &mut ThemeCheck,
+    section: &str,
+    value: Option<&serde_json::Value>,
+    keys: &[&str],
+    defs: &serde_json::Map<String, serde_json::Value>,
+) {
+    let empty = serde_json::Map::new();
+    let Some(map) = value.map(|v| v.as_object().unwrap_or(&empty)) else {
+        check
+            .missing
+            .extend(keys.iter().map(|k| format!("{}.{}", section, k)));
+        return;
+    };
+    for key in keys {
+        let alias = (*key == "type").then_some("ty");
+        if !map.contains_key(*key) && !alias.is_some_and(|a| map.contains_key(a)) {
+            check.missing.push(format!("{}.{}", section, key));
+        }
+    }
+    for (k, v) in map {
+        if !keys.contains(&k.as_str()) && !(k == "ty" && keys.contains(&"type")) {
+            check.unknown.push(format!("{}.{}", section, k));
+        } else if resolve_color(v, defs, &mut HashSet::new(), 0).is_none() {
+            check
+                .invalid
+                .push((format!("{}.{}", section, k), v.to_string()));
+        }
+    }
+}
+
 pub fn style_for_filename(name: &str, config: &AppConfig) -> Style {
     if name.ends_with('/') {
         return style_for_category("directory", config, None);
@@ -1119,4 +1401,84 @@ mod tests {
         let t = theme_from_palette(p);
         assert_eq!(t.palette.accent, rgb("#010203"));
     }
+
+    #[test]
+    fn check_reports_missing_invalid_and_unknown_keys() {
+        let v = json!({
+            "defs": { "primary": "#112233" },
+            "theme": {
+                "ui": { "accent": "$primary", "fg": "$nope", "acent": "#000000" },
+                "syntax": { "ty": "#445566" },
+                "files": { "categories": { "rust": "bold nocolor" } }
+            }
+        });
+        let check = check_theme(&v).unwrap();
+        assert!(check.missing.contains(&"theme.ui.bg".to_string()));
+        assert!(check
+            .missing
+            .contains(&"theme.markdown.code_bg".to_string()));
+        assert!(!check.missing.contains(&"theme.syntax.type".to_string()));
+        assert!(!check.missing.contains(&"theme.ui.accent".to_string()));
+        let invalid: Vec<&str> = check.invalid.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(invalid, ["theme.ui.fg", "theme.files.categories.rust"]);
+        assert_eq!(check.unknown, ["theme.ui.acent"]);
+        assert!(!check.is_ok());
+
+        let palette = json!({ "palette": { "accent": "#010203" } });
+        let check = check_theme(&palette).unwrap();
+        assert!(check.is_ok());
+        assert!(check.missing.contains(&"palette.fg".to_string()));
+        assert!(check_theme(&json!({ "colors": {} })).is_err());
+    }
+
+    #[test]
+    fn cache_reloads_edited_theme_files_and_keeps_the_last_good_one() {
+        let root = std::env::temp_dir().join(format!(
+            "lorikeet-test-theme-{}",
+            crate::memory::types::unix_ts()
+        ));
+        let dir = root.join(".lorikeet").join("themes");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("live-test.json");
+        let config = AppConfig {
+            theme: Some(crate::config::ThemeConfig {
+                preset: Some("live-test".into()),
+                background: Some("solid".into()),
+                ..Default::default()
+            }),
+            ..AppConfig::default()
+        };
+        let mut cache = ThemeCache::default();
+        cache.get(&AppConfig::default(), Some(&root));
+        assert!(!cache.watches_files());
+
+        std::fs::write(&file, r##"{"palette": {"accent": "#010203"}}"##).unwrap();
+        assert_eq!(
+            cache.get(&config, Some(&root)).palette.accent,
+            rgb("#010203")
+        );
+        // The idle screen keeps polling so edits show up without input
+        assert!(cache.watches_files());
+
+        // A different size, so the change is seen even within one mtime tick.
+        std::fs::write(
+            &file,
+            r##"{"palette": {"accent": "#0a0b0c", "ok": "#00ff00"}}"##,
+        )
+        .unwrap();
+        assert_eq!(
+            cache.get(&config, Some(&root)).palette.accent,
+            rgb("#0a0b0c")
+        );
+        assert!(cache.take_error().is_none());
+
+        std::fs::write(&file, "{ not json").unwrap();
+        assert_eq!(
+            cache.get(&config, Some(&root)).palette.accent,
+            rgb("#0a0b0c")
+        );
+        assert!(cache.take_error().unwrap().contains("live-test.json"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
@@ -0,0 +1,180 @@
+//! Offline token estimates for the status bar meter. Close enough to warn before a
+//! conversation outgrows the model's context window, without shipping tokenizer tables.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Tokenizer families, which differ mostly in how much of a word one token covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    /// `o200k_base`: gpt-4o, gpt-4.1, gpt-5, o-series
+    O200k,
+    /// `cl100k_base`: gpt-4, gpt-3.5
+    Cl100k,
+    Claude,
+    Gemini,
+    Other,
+}
+
+impl Family {
+    fn of(model: &str) -> Self {
+        let model = bare_model(model);
+        let o_series =
+            model.starts_with('o') && model[1..].starts_with(|c: char| c.is_ascii_digit());
+        if o_series
+            || ["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5"]
+                .iter()
+                .any(|p| model.starts_with(p))
+        {
+            Self::O200k
+        } else if model.starts_with("gpt-4") || model.starts_with("gpt-3.5") {
+            Self::Cl100k
+        } else if model.starts_with("claude") {
+            Self::Claude
+        } else if model.starts_with("gemini") {
+            Self::Gemini
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Letters of a run one token covers; common words are a single token.
+    fn word_len(self) -> usize {
+        match self {
+            Self::O200k | Self::Gemini => 7,
+            Self::Cl100k | Self::Other => 6,
+            Self::Claude => 5,
+        }
+    }
+}
+
+/// `openai/gpt-4o` -> `gpt-4o`, lowercased.
+fn bare_model(model: &str) -> String {
+    model
+        .rsplit('/')
+        .next()
+        .unwrap_or(model)
+        .trim()
+        .to_lowercase()
+}
+
+/// Estimate how many tokens `text` takes for `model`, splitting it roughly the way BPE
+/// pre-tokenizers do: letter runs (with one leading space), digit groups of three,
+/// punctuation, whitespace runs, and one token per other character (CJK, emoji).
+pub fn estimate(text: &str, model: &str) -> usize {
+    let word_len = Family::of(model).word_len();
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_alphabetic() {
+            tokens += run_len(&mut chars, |c| c.is_ascii_alphabetic()).div_ceil(word_len);
+        } else if c.is_ascii_digit() {
+            tokens += run_len(&mut chars, |c| c.is_ascii_digit()).div_ceil(3);
+        } else if c.is_whitespace() {
+            // A lone space belongs to the word after it; indentation and newlines don't.
+            if run_len(&mut chars, char::is_whitespace) > 1 || c != ' ' {
+                tokens += 1;
+            }
+        } else if c.is_ascii_punctuation() {
+            tokens += run_len(&mut chars, |c| c.is_ascii_punctuation()).div_ceil(2);
+        } else {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+/// Length of the run a just-taken character starts: it plus the `same` ones after it.
+fn run_len(chars: &mut Peekable<Chars<'_>>, same: impl Fn(char) -> bool) -> usize {
+    let mut n = 1;
+    while chars.next_if(|c| same(*c)).is_some() {
+        n += 1;
+    }
+    n
+}
+
+/// Tokens each chat message costs on top of its text (role and separators).
+pub const PER_MESSAGE: usize = 4;
+
+/// Context windows of well-known model families, for when the provider's model list
+/// hasn't been loaded (`/model`). Checked in order, so longer prefixes come first.
+const CONTEXT_WINDOWS: &[(&str, u64)] = &[
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini", 1_048_576),
+    ("glm-4.6", 200_000),
+    ("glm-4.7", 200_000),
+    ("deepseek", 128_000),
+];
+
+pub fn context_window(model: &str) -> Option<u64> {
+    let model = bare_model(model);
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+/// Counts as the meter shows them: `850`, `34k`, `1M`, `1.5M`.
+pub fn short(n: u64) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_499 => format!("{}k", (n + 500) / 1_000),
+        _ => {
+            let millions = format!("{:.1}", n as f64 / 1_000_000.0);
+            format!("{}M", millions.trim_end_matches(".0"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_follow_the_tokenizer_family() {
+        assert_eq!(estimate("", "gpt-4o"), 0);
+        // Common words are one token each, the space riding along with the word.
+        assert_eq!(estimate("the quick brown fox", "openai/gpt-4o"), 4);
+        assert_eq!(estimate("2025", "gpt-4o"), 2);
+        assert_eq!(estimate("fn main() {}", "gpt-4o"), 4);
+        // Claude's tokenizer splits long words more often.
+        let text = "internationalization configuration";
+        assert!(estimate(text, "anthropic/claude-sonnet-4") > estimate(text, "gpt-5"));
+        // Everything else is a token per character.
+        assert_eq!(estimate("日本語", "gemini-2.5-pro"), 3);
+
+        // Within reach of the usual four characters per token on prose.
+        let prose = "Lorikeet keeps a running estimate of the conversation so you can start \
+                     a new session before the model silently drops the oldest messages.";
+        let tokens = estimate(prose, "z-ai/glm-4.7-flash");
+        assert!(
+            (prose.len() / 6..=prose.len() / 3).contains(&tokens),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn context_windows_and_meter_counts() {
+        assert_eq!(context_window("openai/gpt-4.1-mini"), Some(1_047_576));
+        assert_eq!(context_window("gpt-4"), Some(8_192));
+        assert_eq!(context_window("anthropic/claude-opus-4"), Some(200_000));
+        assert_eq!(context_window("some/unknown-model"), None);
+
+        assert_eq!(short(850), "850");
+        assert_eq!(short(34_210), "34k");
+        assert_eq!(short(200_000), "200k");
+        assert_eq!(short(1_048_576), "1M");
+        assert_eq!(short(1_500_000), "1.5M");
+    }
+}
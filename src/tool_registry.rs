@@ -0,0 +1,694 @@
+//! One place that describes every tool: what the model is told (name, description, JSON
+//! schema), how the trace shows it (icon, verbs) and what it needs from the sandbox.
+//!
+//! The LLM tool list, sandbox defaults (`allow_tools`), the restricted/read-only tool sets
+//! and the settings popup all read from here. Built-in tools are dispatched by
+//! [`crate::tools::execute_tool`]; a spec that brings its own [`ToolHandler`] is run
+//! through that instead.
+
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use futures::future::BoxFuture;
+
+/// What a tool does to the machine, which decides where it's offered and allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolAccess {
+    /// Only reads and searches; offered in restricted workspaces and for plain questions
+    Read,
+    /// Writes workspace files; refused in read-only mode, checkpointed by `[checkpoints] auto`
+    Edit,
+    /// Runs shell commands, held to `allow_commands` and OS confinement
+    Exec,
+    /// Changes Lorikeet's own state (memories, background processes), not the workspace
+    State,
+}
+
+impl ToolAccess {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Read => "reads only",
+            Self::Edit => "edits files",
+            Self::Exec => "runs commands",
+            Self::State => "changes Lorikeet state",
+        }
+    }
+}
+
+/// Runs a registered tool: its parsed arguments and the workspace root in, the result for
+/// the model out (`Err` marks the call as failed).
+pub type ToolHandler = Arc<
+    dyn Fn(serde_json::Value, PathBuf) -> BoxFuture<'static, Result<String, String>> + Send + Sync,
+>;
+
+#[derive(Clone)]
+pub struct ToolSpec {
+    pub name: &'static str,
+    /// Told to the model, along with `parameters` (a JSON schema object)
+    pub description: String,
+    pub parameters: serde_json::Value,
+    /// Shown in front of the tool in the trace
+    pub icon: &'static str,
+    /// (while running, when done), e.g. ("Reading", "Read")
+    pub verbs: (&'static str, &'static str),
+    pub access: ToolAccess,
+    /// `None` for built-in tools, which `execute_tool` knows how to run
+    pub handler: Option<ToolHandler>,
+}
+
+impl std::fmt::Debug for ToolSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolSpec")
+            .field("name", &self.name)
+            .field("access", &self.access)
+            .field("handler", &self.handler.is_some())
+            .finish()
+    }
+}
+
+/// A set of tools, looked up by name. The process uses one with the built-ins
+/// ([`specs`], [`spec`], ...); tests build their own so they don't share state.
+pub struct ToolRegistry {
+    tools: Vec<ToolSpec>,
+}
+
+impl ToolRegistry {
+    pub fn with_builtins() -> Self {
+        Self {
+            tools: builtin_tools(),
+        }
+    }
+
+    /// Add a tool. Its name must be new and it must bring a handler.
+    #[cfg(test)]
+    pub fn register(&mut self, spec: ToolSpec) -> Result<(), String> {
+        if spec.handler.is_none() {
+            return Err(format!("Tool `{}` has no handler", spec.name));
+        }
+        if !spec.parameters.is_object() {
+            return Err(format!(
+                "Tool `{}`: parameters must be a JSON schema object",
+                spec.name
+            ));
+        }
+        if self.find(spec.name).is_some() {
+            return Err(format!("Tool `{}` is already registered", spec.name));
+        }
+        self.tools.push(spec);
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<&ToolSpec> {
+        self.tools.iter().find(|t| t.name == name)
+    }
+}
+
+static REGISTRY: OnceLock<ToolRegistry> = OnceLock::new();
+
+fn registry() -> &'static ToolRegistry {
+    REGISTRY.get_or_init(ToolRegistry::with_builtins)
+}
+
+/// Every tool, in the order the model sees them.
+pub fn specs() -> Vec<ToolSpec> {
+    registry().tools.clone()
+}
+
+pub fn spec(name: &str) -> Option<ToolSpec> {
+    registry().find(name).cloned()
+}
+
+pub fn names() -> Vec<&'static str> {
+    registry().tools.iter().map(|t| t.name).collect()
+}
+
+pub fn access(name: &str) -> Option<ToolAccess> {
+    registry().find(name).map(|t| t.access)
+}
+
+pub fn icon(name: &str) -> &'static str {
+    registry().find(name).map_or("○", |t| t.icon)
+}
+
+/// The verb for a call of `name`: present tense while it runs, past tense after.
+pub fn verb(name: &str, running: bool) -> &'static str {
+    let verbs = registry()
+        .find(name)
+        .map_or(("Processing", "Done"), |t| t.verbs);
+    if running {
+        verbs.0
+    } else {
+        verbs.1
+    }
+}
+
+/// The handler of a tool that isn't dispatched by `execute_tool` itself.
+pub fn handler(name: &str) -> Option<ToolHandler> {
+    registry().find(name).and_then(|t| t.handler.clone())
+}
+
+fn builtin_tools() -> Vec<ToolSpec> {
+    vec![
+        ToolSpec {
+            name: "bash",
+            description: "Run a shell command. Use for: reading files (cat), listing dirs (ls, tree), git, builds, tests, installs, etc.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The bash command to execute"
+                    },
+                    "reset_shell": {
+                        "type": "boolean",
+                        "description": "When the shell is persistent, start a fresh one (workspace directory, no exports) before running command; with an empty command, only reset"
+                    },
+                    "tty": {
+                        "type": "boolean",
+                        "description": "Run under a pseudo-terminal, for commands that need one (interactive prompts, git add -p, progress bars). Escape codes are stripped from the result."
+                    },
+                    "input": {
+                        "type": "string",
+                        "description": "With tty: text typed into the terminal, one answer per line (e.g. \"y\\nq\"); input ends after it"
+                    }
+                },
+                "required": ["command"]
+            }),
+            icon: "$",
+            verbs: ("Running", "Ran"),
+            access: ToolAccess::Exec,
+            handler: None,
+        },
+        ToolSpec {
+            name: "rg",
+            description: "Fast text search using ripgrep. Use for exact symbol or string search across the codebase.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The text or regex to search for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory or file to search. Defaults to the workspace plus any extra allowed roots, with results grouped by root"
+                    },
+                    "context": {
+                        "type": "integer",
+                        "description": "Optional number of context lines to include before/after matches"
+                    }
+                },
+                "required": ["query"]
+            }),
+            icon: "⌕",
+            verbs: ("Searching", "Searched"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "smart_search",
+            description: "Combined search: runs ripgrep (exact) + semantic search (meaning) and returns a merged, ranked list. Prefer this when you don't know exact symbol names.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to search for (natural language or exact text)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Optional scope (directory or file). Defaults to the workspace plus any extra allowed roots; hits from those are tagged [root]."
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Max number of results to return (default 20, max 50)"
+                    },
+                    "rg": {
+                        "type": "boolean",
+                        "description": "Whether to run rg (default true)"
+                    },
+                    "semantic": {
+                        "type": "boolean",
+                        "description": "Whether to run semantic search (default true)"
+                    }
+                },
+                "required": ["query"]
+            }),
+            icon: "≈",
+            verbs: ("Searching", "Searched"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "lsp",
+            description: "Language Server Protocol bridge for code-aware operations. Actions: definition, references, rename, diagnostics. Provide path + 1-based line/column for symbol operations.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "language": {"type": "string", "description": "auto|rust|typescript (default auto)"},
+                    "action": {"type": "string", "description": "definition|references|rename|diagnostics"},
+                    "path": {"type": "string", "description": "File path (workspace-relative or absolute)"},
+                    "line": {"type": "integer", "description": "1-based line number (required for definition/references/rename)"},
+                    "column": {"type": "integer", "description": "1-based column number (required for definition/references/rename)"},
+                    "new_name": {"type": "string", "description": "New symbol name (required for rename)"},
+                    "include_declaration": {"type": "boolean", "description": "For references: include the declaration (default false)"},
+                    "limit": {"type": "integer", "description": "Max results (default 20)"}
+                },
+                "required": ["action","path"]
+            }),
+            icon: "λ",
+            verbs: ("Querying", "Queried"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "read_file",
+            description: "Read the contents of a file at the given path. Jupyter notebooks (.ipynb) are shown as readable cells with their text outputs; pass raw=true for the underlying JSON (e.g. before editing). Very large files are cut after the last whole definition that fits, followed by the line ranges of the symbols not shown.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The file path to read"
+                    },
+                    "raw": {
+                        "type": "boolean",
+                        "description": "Return notebooks as raw JSON instead of rendered cells (default false)"
+                    }
+                },
+                "required": ["path"]
+            }),
+            icon: "▶",
+            verbs: ("Reading", "Read"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "write_file",
+            description: "Write content to a file at the given path. Creates the file if it doesn't exist, overwrites if it does.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The file path to write to"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The content to write to the file"
+                    }
+                },
+                "required": ["path", "content"]
+            }),
+            icon: "◀",
+            verbs: ("Writing", "Wrote"),
+            access: ToolAccess::Edit,
+            handler: None,
+        },
+        ToolSpec {
+            name: "list_files",
+            description: "List files and directories at the given path.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The directory path to list (defaults to current directory)"
+                    }
+                },
+                "required": []
+            }),
+            icon: "◇",
+            verbs: ("Listing", "Listed"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "edit_file",
+            description: "Make a surgical edit to a file by replacing old_string with new_string. old_string should match exactly and be unique; if it isn't, pass occurrence. When there is no exact match, lines matching apart from whitespace are used; fuzzy: true also accepts lines a few characters off (the result shows what was replaced), fuzzy: false requires the exact text. dry_run returns the diff without writing.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The file path to edit"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "The exact text to find and replace (must be unique in file)"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "The text to replace it with"
+                    },
+                    "occurrence": {
+                        "type": ["integer", "string"],
+                        "description": "Which match to replace when old_string occurs more than once: a 1-based number or \"all\""
+                    },
+                    "fuzzy": {
+                        "type": "boolean",
+                        "description": "Without an exact match: true also accepts lines a few characters off, false requires the exact text (default: whitespace differences only)"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "Return the would-be diff without changing the file"
+                    }
+                },
+                "required": ["path", "old_string", "new_string"]
+            }),
+            icon: "±",
+            verbs: ("Editing", "Edited"),
+            access: ToolAccess::Edit,
+            handler: None,
+        },
+        ToolSpec {
+            name: "apply_patch",
+            description: "Apply a patch to one or more files. Prefer this for non-trivial edits/refactors. Patch format uses *** Begin Patch / *** Update File / *** Add File / *** Delete File / *** End Patch blocks with diff-style lines starting with ' ', '+', '-'.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "patch": {
+                        "type": "string",
+                        "description": "Patch text to apply"
+                    }
+                },
+                "required": ["patch"]
+            }),
+            icon: "▦",
+            verbs: ("Applying", "Applied"),
+            access: ToolAccess::Edit,
+            handler: None,
+        },
+        ToolSpec {
+            name: "open_at",
+            description: "Read a file around a specific 1-based line number with context and line numbers. Use after smart_search/rg when you have path:line results.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string", "description": "File path"},
+                    "line": {"type": "integer", "description": "1-based line number to center on"},
+                    "context": {"type": "integer", "description": "Lines of context before/after (default 40)"}
+                },
+                "required": ["path", "line"]
+            }),
+            icon: "↗",
+            verbs: ("Opening", "Opened"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "read_tool_output",
+            description: "Read lines a bash or verify result left out. Long command output is returned as its head and tail with a note naming the omitted line range and the call_id to pass here.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "call_id": {"type": "string", "description": "call_id given in the omitted-lines note"},
+                    "start_line": {"type": "integer", "description": "First 1-based line to return (default 1)"},
+                    "end_line": {"type": "integer", "description": "Last line to return, inclusive (default: through the end, capped at 20k characters)"}
+                },
+                "required": ["call_id"]
+            }),
+            icon: "≡",
+            verbs: ("Reading", "Read"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "semantic_search",
+            description: "Search code semantically using natural language. Returns ranked results with file paths and line numbers. Use for finding code related to concepts, features, or functionality. Auto-indexes on first use.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Natural language query describing what you're looking for (e.g., 'authentication handling', 'database connection', 'error logging')"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["code", "doc", "config", "all"],
+                        "description": "Only return chunks of this kind: source code, documentation (Markdown, READMEs, text) or configuration files. Default: all"
+                    }
+                },
+                "required": ["query"]
+            }),
+            icon: "?",
+            verbs: ("Searching", "Searched"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "find_symbol",
+            description: "Find where a function, method, type, trait, class or module is defined (not where it's used), from the workspace symbol index. Returns path:line, kind and enclosing type. Accepts qualified names like 'Config::load' or 'Parser.parse'. Use rg for usages.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Symbol name, optionally qualified with its type ('Config::load')"
+                    },
+                    "prefix": {
+                        "type": "boolean",
+                        "description": "Match names starting with `name` instead of exactly (default: false)"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["function", "method", "class", "struct", "enum", "interface", "trait", "module", "other"],
+                        "description": "Only definitions of this kind"
+                    }
+                },
+                "required": ["name"]
+            }),
+            icon: "#",
+            verbs: ("Finding", "Found"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "verify",
+            description: "Run the workspace verify command (tests/build). If command is omitted, runs the verify pipeline (fmt, lint, build, test; stops at the first failure) and returns per-stage pass/fail with error counts. Respects sandbox allow_commands; if blocked, adjust sandbox allowlist.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Optional verify command (e.g. 'cargo test', 'pnpm test')"}
+                },
+                "required": []
+            }),
+            icon: "✓",
+            verbs: ("Verifying", "Verified"),
+            access: ToolAccess::Exec,
+            handler: None,
+        },
+        ToolSpec {
+            name: "run_background",
+            description: "Start a long-running command (dev server, watcher, database) in the background. It keeps running across turns; the reply has its id and first output. Use process_status to read its recent logs and kill_process to stop it. Not for commands that finish on their own: use bash for those.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "The bash command to start"}
+                },
+                "required": ["command"]
+            }),
+            icon: "&",
+            verbs: ("Starting", "Started"),
+            access: ToolAccess::Exec,
+            handler: None,
+        },
+        ToolSpec {
+            name: "process_status",
+            description: "List background processes started with run_background, or with id, show one's state and its recent output.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {"type": "integer", "description": "Process id from run_background (omit to list all)"},
+                    "lines": {"type": "integer", "description": "Recent output lines to show (default 40)"}
+                },
+                "required": []
+            }),
+            icon: "≋",
+            verbs: ("Checking", "Checked"),
+            access: ToolAccess::State,
+            handler: None,
+        },
+        ToolSpec {
+            name: "kill_process",
+            description: "Stop a background process started with run_background, along with anything it started.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {"type": "integer", "description": "Process id from run_background"}
+                },
+                "required": ["id"]
+            }),
+            icon: "■",
+            verbs: ("Stopping", "Stopped"),
+            access: ToolAccess::State,
+            handler: None,
+        },
+        ToolSpec {
+            name: "github",
+            description: "Read from GitHub: an issue or PR with its comments (issue), a PR's changed files and patches (pr_files), a PR's reviews and inline comments (review_comments), or check results for a PR, ref or the current commit (ci_status). Use it when the user refers to an issue or PR by number instead of asking them to paste it. The repo defaults to the workspace's origin remote.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "action": {"type": "string", "enum": ["issue", "pr_files", "review_comments", "ci_status"]},
+                    "number": {"type": "integer", "description": "Issue or PR number"},
+                    "repo": {"type": "string", "description": "owner/name (default: the origin remote)"},
+                    "ref": {"type": "string", "description": "Branch, tag or SHA for ci_status without a number (default: HEAD)"}
+                },
+                "required": ["action"]
+            }),
+            icon: "⎇",
+            verbs: ("Fetching", "Fetched"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "memory_recall",
+            description: "Recall relevant long-term memory for the current project. Use before making decisions or repeating actions. Returns ranked memories.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Search query"},
+                    "limit": {"type": "integer", "description": "Max memories to return (default 8)"},
+                    "types": {"type": "array", "items": {"type": "string"}, "description": "Optional list of memory types"}
+                },
+                "required": ["query"]
+            }),
+            icon: "○",
+            verbs: ("Recalling", "Recalled"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "memory_save",
+            description: "Save a long-term memory. Use for user preferences, decisions, and mistakes. Never store secrets.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "type": {"type": "string", "description": "mistake|preference|decision|fact|avoid"},
+                    "content": {"type": "string", "description": "The memory content"},
+                    "why": {"type": "string", "description": "Why this memory matters / how it should change future behavior"},
+                    "context": {"type": "string", "description": "Optional context"},
+                    "tags": {"type": "array", "items": {"type": "string"}, "description": "Optional tags"},
+                    "scope": {"type": "string", "description": "project|global (global is shared by every workspace; default project)"},
+                    "confidence": {"type": "number", "description": "0..1"},
+                    "importance": {"type": "number", "description": "0..1"}
+                },
+                "required": ["type", "content"]
+            }),
+            icon: "○",
+            verbs: ("Saving", "Saved"),
+            access: ToolAccess::State,
+            handler: None,
+        },
+        ToolSpec {
+            name: "memory_list",
+            description: "List stored memories for this project.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "limit": {"type": "integer", "description": "Max to return (default 30)"},
+                    "type": {"type": "string", "description": "Optional memory type filter"}
+                }
+            }),
+            icon: "○",
+            verbs: ("Listing", "Listed"),
+            access: ToolAccess::Read,
+            handler: None,
+        },
+        ToolSpec {
+            name: "memory_forget",
+            description: "Delete a memory by id.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {"type": "string", "description": "Memory id"}
+                },
+                "required": ["id"]
+            }),
+            icon: "○",
+            verbs: ("Forgetting", "Forgot"),
+            access: ToolAccess::State,
+            handler: None,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_tools_are_unique_and_described() {
+        let tools = builtin_tools();
+        for (i, tool) in tools.iter().enumerate() {
+            assert!(
+                !tools[..i].iter().any(|t| t.name == tool.name),
+                "{} twice",
+                tool.name
+            );
+            assert!(!tool.description.is_empty(), "{}", tool.name);
+            assert_eq!(tool.parameters["type"], "object", "{}", tool.name);
+            assert!(tool.handler.is_none(), "{}", tool.name);
+        }
+        assert_eq!(access("edit_file"), Some(ToolAccess::Edit));
+        assert_eq!(verb("read_file", true), "Reading");
+        assert_eq!(verb("nope", false), "Done");
+        assert_eq!(icon("bash"), "$");
+    }
+
+    #[test]
+    fn registered_tools_run_through_their_handler() {
+        let echo: ToolHandler = Arc::new(|args, _root| {
+            Box::pin(async move {
+                match args.get("text").and_then(|v| v.as_str()) {
+                    Some(text) => Ok(format!("echo: {}", text)),
+                    None => Err("Error: missing text".to_string()),
+                }
+            })
+        });
+        let echo_spec = ToolSpec {
+            name: "test_echo",
+            description: "Echo text back.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"]
+            }),
+            icon: "e",
+            verbs: ("Echoing", "Echoed"),
+            access: ToolAccess::Read,
+            handler: Some(echo),
+        };
+        let mut registry = ToolRegistry::with_builtins();
+        registry.register(echo_spec.clone()).unwrap();
+        assert!(registry
+            .register(echo_spec.clone())
+            .unwrap_err()
+            .contains("already registered"));
+        assert!(registry
+            .register(ToolSpec {
+                name: "test_no_handler",
+                handler: None,
+                ..echo_spec
+            })
+            .is_err());
+        let shadow = ToolSpec {
+            name: "bash",
+            ..registry.find("test_echo").unwrap().clone()
+        };
+        assert!(registry.register(shadow).is_err());
+        // The process-wide registry is untouched
+        assert!(spec("test_echo").is_none());
+
+        let handler = registry.find("test_echo").unwrap().handler.clone().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let out = rt.block_on(handler(
+            serde_json::json!({"text": "hi"}),
+            std::env::temp_dir(),
+        ));
+        assert_eq!(out, Ok("echo: hi".to_string()));
+        let missing = rt.block_on(handler(serde_json::json!({}), std::env::temp_dir()));
+        assert!(missing.is_err());
+    }
+}
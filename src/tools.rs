@@ -8,32 +8,38 @@ use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::mpsc;
 
+use crate::checkpoints;
+use crate::confine::Confinement;
+use crate::daemon;
+use crate::edit_check::{self, EditedFile};
 use crate::events::AppEvent;
 use crate::lsp::{
     format_locations_with_snippets, lsp_definition, lsp_diagnostics, lsp_references, lsp_rename,
     LspLanguage,
 };
 use crate::sandbox::SandboxPolicy;
-use crate::semantic_search::{format_search_results, SearchConfig, SemanticSearch};
-
-pub const TOOL_NAMES: &[&str] = &[
-    "bash",
-    "rg",
-    "smart_search",
-    "lsp",
-    "read_file",
-    "write_file",
-    "list_files",
-    "edit_file",
-    "apply_patch",
-    "open_at",
-    "semantic_search",
-    "verify",
-    "memory_recall",
-    "memory_save",
-    "memory_list",
-    "memory_forget",
-];
+use crate::semantic_search::chunker::truncate_at_boundaries;
+use crate::semantic_search::symbols::{SymbolTable, MAX_MATCHES as MAX_SYMBOL_MATCHES};
+use crate::semantic_search::types::{ChunkKind, SearchResult, SymbolType};
+use crate::semantic_search::{
+    build_symbol_table, format_search_results, SearchConfig, SemanticSearch,
+};
+use crate::shell::SharedShell;
+
+/// Largest read_file result sent to the model; bigger files are cut at a definition
+/// boundary with an outline of what was left out.
+const MAX_READ_FILE_BYTES: usize = 96 * 1024;
+
+/// Whether `output` says the tool never got to run properly: its process couldn't be
+/// spawned (missing binary, process or file-descriptor limits) or waited on. Those say
+/// nothing about the model's arguments, so they're retried and kept out of the
+/// repeated-failure guard.
+pub fn is_infra_failure(output: &str) -> bool {
+    output.trim_start().starts_with("Error spawning")
+        || output
+            .lines()
+            .any(|l| l.starts_with("Error executing command:"))
+}
 
 // Global semantic search engine (initialized lazily on first use)
 static SEMANTIC_SEARCH: OnceLock<Mutex<Option<SemanticSearch>>> = OnceLock::new();
@@ -98,7 +104,7 @@ fn string_arg(args: &serde_json::Value, key: &str) -> String {
     t.to_string()
 }
 
-fn command_arg(args: &serde_json::Value, key: &str) -> String {
+pub(crate) fn command_arg(args: &serde_json::Value, key: &str) -> String {
     let mut s = string_arg(args, key);
 
     // Support JSON array-of-strings commands like ["rg","-n","foo"] (model sometimes does this).
@@ -117,12 +123,14 @@ fn command_arg(args: &serde_json::Value, key: &str) -> String {
     s
 }
 
+#[tracing::instrument(name = "tool", skip_all, fields(tool = name, call_id = call_id))]
 pub async fn execute_tool(
     name: &str,
     args: &str,
     call_id: &str,
     tx: &mpsc::UnboundedSender<AppEvent>,
     policy: &SandboxPolicy,
+    shell: &SharedShell,
 ) -> String {
     if let Err(err) = policy.check_tool_allowed(name) {
         let msg = err.to_string();
@@ -158,6 +166,25 @@ pub async fn execute_tool(
         }
         "bash" => {
             let command = command_arg(&args, "command");
+            let reset = args
+                .get("reset_shell")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if reset {
+                shell.lock().await.take();
+                if command.trim().is_empty() {
+                    let msg = "Shell session reset.".to_string();
+                    let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                        call_id: call_id.to_string(),
+                        chunk: msg.clone(),
+                    }));
+                    let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                        call_id: call_id.to_string(),
+                        success: true,
+                    }));
+                    return msg;
+                }
+            }
 
             if let Err(err) = policy.check_command_allowed(&command) {
                 let msg = err.to_string();
@@ -185,7 +212,18 @@ pub async fn execute_tool(
                 return msg;
             }
 
-            let (result, success) = execute_bash_streaming(&command, call_id, tx.clone()).await;
+            let tty = args
+                .get("tty")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_else(|| crate::shell::pty(policy.shell.as_ref()));
+            let input = args.get("input").and_then(|v| v.as_str());
+            let (result, success) = if tty {
+                execute_bash_pty(&command, input, call_id, tx, policy, shell).await
+            } else if crate::shell::persistent(policy.shell.as_ref()) {
+                execute_bash_persistent(&command, call_id, tx, policy, shell).await
+            } else {
+                execute_bash_streaming(&command, call_id, tx.clone(), policy).await
+            };
             let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
                 call_id: call_id.to_string(),
                 success,
@@ -210,27 +248,48 @@ pub async fn execute_tool(
                 Err(e) => return fail(format!("Error: cwd: {}", e)),
             };
 
-            let mut command = command_arg(&args, "command");
-            if command.trim().is_empty() {
-                let suggestions = crate::verify::detect_suggestions(&cwd);
-                if let Some(s) = suggestions.first() {
-                    command = s.command.clone();
-                } else {
-                    return fail(
-                        "Error: no verify suggestions for this workspace (pass {\"command\": ...})."
-                            .to_string(),
-                    );
+            // Without a command: the staged pipeline, else the top suggestion.
+            let command = command_arg(&args, "command");
+            let (mut result, success) = match verify_plan(&command, &cwd, policy) {
+                Err(err) => return fail(err),
+                Ok(VerifyPlan::Command(command)) => {
+                    let (mut result, success) =
+                        execute_bash_streaming(&command, call_id, tx.clone(), policy).await;
+                    let failures = crate::verify::parse_test_failures(&result);
+                    if !failures.is_empty() {
+                        // Listed up front so the model doesn't dig through the log for them.
+                        result = format!(
+                            "{}\n\n{}",
+                            crate::verify::failures_summary(&failures),
+                            result
+                        );
+                    }
+                    (result, success)
                 }
+                Ok(VerifyPlan::Pipeline { stages, left_out }) => {
+                    for (stage, reason) in &left_out {
+                        let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                            call_id: call_id.to_string(),
+                            chunk: format!("── {}: left out ({})\n", stage.name, reason),
+                        }));
+                    }
+                    let (mut result, success) =
+                        run_verify_pipeline(stages, call_id, tx, policy).await;
+                    for (stage, reason) in &left_out {
+                        result.push_str(&format!("- {}: left out ({})\n", stage.name, reason));
+                    }
+                    (result, success)
+                }
+            };
+            if let Some(warning) = edit_check::review(&edit_check::working_tree_changes(&cwd)) {
+                // Streamed output is already on screen; show the warning the same way.
+                let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                    call_id: call_id.to_string(),
+                    chunk: format!("\n{}", warning),
+                }));
+                result.push_str("\n\n");
+                result.push_str(&warning);
             }
-
-            if let Err(err) = policy.check_command_allowed(&command) {
-                return fail(err.to_string());
-            }
-            if let Err(err) = policy.check_bash_paths(&command) {
-                return fail(err.to_string());
-            }
-
-            let (result, success) = execute_bash_streaming(&command, call_id, tx.clone()).await;
             let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
                 call_id: call_id.to_string(),
                 success,
@@ -266,7 +325,23 @@ pub async fn execute_tool(
                 }
             };
 
-            let result = execute_rg(&query, checked_path.to_string_lossy().as_ref(), context).await;
+            let extra_roots = policy.extra_search_roots();
+            let result = if path.trim().is_empty() && !extra_roots.is_empty() {
+                let mut sections = Vec::new();
+                let roots = std::iter::once(checked_path).chain(extra_roots);
+                for (i, root) in roots.enumerate() {
+                    let found = execute_rg(&query, root.to_string_lossy().as_ref(), context).await;
+                    let label = if i == 0 {
+                        "workspace".to_string()
+                    } else {
+                        root.display().to_string()
+                    };
+                    sections.push(format!("[root: {}]\n{}", label, found.trim_end()));
+                }
+                sections.join("\n\n")
+            } else {
+                execute_rg(&query, checked_path.to_string_lossy().as_ref(), context).await
+            };
             let success = !result.starts_with("Error:");
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
@@ -402,6 +477,7 @@ pub async fn execute_tool(
         }
         "read_file" => {
             let path = string_arg(&args, "path");
+            let raw = args.get("raw").and_then(|v| v.as_bool()).unwrap_or(false);
 
             let checked_path = match policy.check_path_allowed(Path::new(path.trim())) {
                 Ok(p) => p,
@@ -420,7 +496,12 @@ pub async fn execute_tool(
             };
 
             let result = match tokio::fs::read_to_string(&checked_path).await {
-                Ok(content) => content,
+                Ok(content) if raw => content,
+                Ok(content) => {
+                    let content =
+                        crate::notebook::readable(&checked_path, &content).unwrap_or(content);
+                    truncate_at_boundaries(&content, &checked_path, MAX_READ_FILE_BYTES)
+                }
                 Err(e) => format!("Error reading file: {}", e),
             };
             let success = !result.starts_with("Error");
@@ -454,11 +535,17 @@ pub async fn execute_tool(
                 }
             };
 
-            let result = match tokio::fs::write(&checked_path, content).await {
+            let edited = [EditedFile::snapshot(path.trim(), checked_path.clone())];
+            let undo = push_undo_entry(&policy.root, "write_file", &edited);
+            let mut result = match tokio::fs::write(&checked_path, content).await {
                 Ok(_) => format!("Successfully wrote {} bytes to {}", content.len(), path),
                 Err(e) => format!("Error writing file: {}", e),
             };
             let success = !result.starts_with("Error");
+            finish_undo_entry(undo, success);
+            if success {
+                append_edit_warnings(&mut result, &edited);
+            }
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
                 chunk: result.clone(),
@@ -544,8 +631,40 @@ pub async fn execute_tool(
                 }
             };
 
-            let result = edit_file(&checked_path, old_str, new_str).await;
+            let opts = match Occurrence::from_arg(args.get("occurrence")) {
+                Ok(occurrence) => EditOptions {
+                    occurrence,
+                    matching: Matching::from_arg(args.get("fuzzy").and_then(|v| v.as_bool())),
+                    dry_run: args
+                        .get("dry_run")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                },
+                Err(msg) => {
+                    let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                        call_id: call_id.to_string(),
+                        chunk: msg.clone(),
+                    }));
+                    let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                        call_id: call_id.to_string(),
+                        success: false,
+                    }));
+                    return msg;
+                }
+            };
+
+            let edited = [EditedFile::snapshot(path.trim(), checked_path.clone())];
+            let undo = if opts.dry_run {
+                None
+            } else {
+                push_undo_entry(&policy.root, "edit_file", &edited)
+            };
+            let mut result = edit_file(&checked_path, old_str, new_str, opts).await;
             let success = !result.starts_with("Error");
+            finish_undo_entry(undo, success);
+            if success && !opts.dry_run {
+                append_edit_warnings(&mut result, &edited);
+            }
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
                 chunk: result.clone(),
@@ -558,7 +677,39 @@ pub async fn execute_tool(
         }
         "apply_patch" => {
             let patch = args.get("patch").and_then(|v| v.as_str()).unwrap_or("");
-            let result = apply_patch_tool(patch, policy).await;
+            let edited: Vec<EditedFile> = patch_line_stats(patch)
+                .into_iter()
+                .filter_map(|(path, _, _)| {
+                    let checked = policy.check_path_allowed(Path::new(&path)).ok()?;
+                    Some(EditedFile::snapshot(path, checked))
+                })
+                .collect();
+            let undo = push_undo_entry(&policy.root, "apply_patch", &edited);
+            let mut result = apply_patch_tool(patch, policy).await;
+            let success = !result.starts_with("Error:");
+            finish_undo_entry(undo, success);
+            if success {
+                append_edit_warnings(&mut result, &edited);
+            }
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        "read_tool_output" => {
+            let target = string_arg(&args, "call_id");
+            let start = args.get("start_line").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+            let end = args
+                .get("end_line")
+                .and_then(|v| v.as_u64())
+                .map_or(usize::MAX, |v| v as usize);
+
+            let result = read_tool_output(target.trim(), start, end);
             let success = !result.starts_with("Error:");
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
@@ -603,10 +754,31 @@ pub async fn execute_tool(
             }));
             result
         }
+        "find_symbol" => {
+            let name = string_arg(&args, "name");
+            let prefix = args
+                .get("prefix")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let kind = string_arg(&args, "kind");
+
+            let result = execute_find_symbol(&name, prefix, &kind, policy);
+            let success = !result.starts_with("Error");
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
         "semantic_search" => {
             let query = string_arg(&args, "query");
+            let kind = string_arg(&args, "kind");
 
-            let result = execute_semantic_search(&query, policy).await;
+            let result = execute_semantic_search(&query, &kind, policy).await;
             let success = !result.starts_with("Error");
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
@@ -631,14 +803,158 @@ pub async fn execute_tool(
                 .and_then(|v| v.as_bool())
                 .unwrap_or(true);
 
-            let scope = if path.trim().is_empty() {
-                "."
+            let all_roots = path.trim().is_empty();
+            let scope = if all_roots { "." } else { path.trim() };
+            let result = execute_smart_search(
+                &query,
+                scope,
+                all_roots,
+                limit,
+                use_rg,
+                use_semantic,
+                policy,
+            )
+            .await;
+            let success = !result.starts_with("Error");
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        "run_background" => {
+            let command = command_arg(&args, "command");
+            let checked = policy
+                .check_command_allowed(&command)
+                .and_then(|_| policy.check_bash_paths(&command))
+                .map_err(|e| e.to_string());
+            let (result, success) =
+                match checked.and_then(|_| crate::processes::start(&command, policy)) {
+                    Ok(p) => {
+                        // Give it a moment, so startup errors and a bound port show up here.
+                        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+                        let p = crate::processes::info(p.id).unwrap_or(p);
+                        let mut out = format!("Started {}\n", p.describe());
+                        for line in crate::processes::recent_logs(p.id, 20) {
+                            out.push_str(&line);
+                            out.push('\n');
+                        }
+                        if p.running() {
+                            out.push_str(
+                                "Read its logs with process_status; stop it with kill_process.\n",
+                            );
+                        }
+                        (out, p.exit.map_or(true, |code| code == 0))
+                    }
+                    Err(msg) => (msg, false),
+                };
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        "process_status" => {
+            let lines = args.get("lines").and_then(|v| v.as_u64()).unwrap_or(40) as usize;
+            let result = match args.get("id").and_then(|v| v.as_u64()) {
+                Some(id) => match crate::processes::info(id as u32) {
+                    Some(p) => {
+                        let mut out = format!("{}\n", p.describe());
+                        let logs = crate::processes::recent_logs(p.id, lines);
+                        if logs.is_empty() {
+                            out.push_str("(no output yet)\n");
+                        }
+                        for line in logs {
+                            out.push_str(&line);
+                            out.push('\n');
+                        }
+                        Ok(out)
+                    }
+                    None => Err(format!("Error: no background process {}", id)),
+                },
+                None => {
+                    let all = crate::processes::list();
+                    if all.is_empty() {
+                        Ok("No background processes.".to_string())
+                    } else {
+                        Ok(all.iter().map(|p| format!("{}\n", p.describe())).collect())
+                    }
+                }
+            };
+            let success = result.is_ok();
+            let result = result.unwrap_or_else(|e| e);
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        "kill_process" => {
+            let id = args.get("id").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+            let result = if crate::processes::kill(id) {
+                // Wait for it to go, so the reply can say how it ended.
+                for _ in 0..40 {
+                    if crate::processes::info(id).is_some_and(|p| !p.running()) {
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+                match crate::processes::info(id) {
+                    Some(p) if !p.running() => Ok(format!("Stopped {}", p.describe())),
+                    _ => Ok(format!("Stopping process {}", id)),
+                }
             } else {
-                path.trim()
+                Err(format!("Error: no running background process {}", id))
             };
-            let result =
-                execute_smart_search(&query, scope, limit, use_rg, use_semantic, policy).await;
-            let success = !result.starts_with("Error");
+            let success = result.is_ok();
+            let result = result.unwrap_or_else(|e| e);
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        "github" => {
+            let result = if policy.enabled && !policy.network {
+                Err("Error: the sandbox has network access off (sandbox.network = false)".into())
+            } else {
+                crate::github::run(&args, &policy.root).await
+            };
+            let success = result.is_ok();
+            let result = result.unwrap_or_else(|e| e);
+            let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: result.clone(),
+            }));
+            let _ = tx.send(AppEvent::ToolComplete(crate::events::ToolCompleteEvent {
+                call_id: call_id.to_string(),
+                success,
+            }));
+            result
+        }
+        _ => {
+            let Some(handler) = crate::tool_registry::handler(name) else {
+                return format!("Unknown tool: {}", name);
+            };
+            let result = handler(args, policy.root.clone()).await;
+            let success = result.is_ok();
+            let result = result.unwrap_or_else(|e| e);
             let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
                 call_id: call_id.to_string(),
                 chunk: result.clone(),
@@ -649,64 +965,150 @@ pub async fn execute_tool(
             }));
             result
         }
-        _ => format!("Unknown tool: {}", name),
     }
 }
 
-async fn execute_bash_streaming(
+/// Run `stages` in order, stopping at the first failure. Output streams to the UI as
+/// usual; the result is the parsed per-stage summary rather than the raw logs.
+/// What a `verify` call runs, once it has passed the sandbox checks.
+pub(crate) enum VerifyPlan {
+    /// The given command, or the top suggestion when no pipeline applies
+    Command(String),
+    /// The staged pipeline, minus the stages read-only mode can't run
+    Pipeline {
+        stages: Vec<crate::verify::VerifyStage>,
+        left_out: Vec<(crate::verify::VerifyStage, String)>,
+    },
+}
+
+/// Resolve what `verify` runs in `root` and check every command of it against the
+/// policy. Used both for the sandbox decision and by the tool itself.
+pub(crate) fn verify_plan(
     command: &str,
-    call_id: &str,
-    tx: mpsc::UnboundedSender<AppEvent>,
-) -> (String, bool) {
-    // Tool results are fed back into the model, so we aggregate output.
-    // Keep it bounded and prefer tail output.
-    const MAX_TOOL_RESULT_CHARS: usize = 20_000;
-    const TIMEOUT_SECS: u64 = 60;
+    root: &Path,
+    policy: &SandboxPolicy,
+) -> Result<VerifyPlan, String> {
+    let check = |command: &str| -> Result<(), String> {
+        policy
+            .check_read_only_command(command)
+            .and_then(|_| policy.check_command_allowed(command))
+            .and_then(|_| policy.check_bash_paths(command))
+            .map_err(|e| e.to_string())
+    };
+    if !command.trim().is_empty() {
+        check(command)?;
+        return Ok(VerifyPlan::Command(command.to_string()));
+    }
 
-    #[derive(Default)]
-    struct OutputAcc {
-        lines: VecDeque<String>,
-        chars: usize,
-        truncated: bool,
-    }
-
-    impl OutputAcc {
-        fn push_line(&mut self, line: String) {
-            self.chars = self.chars.saturating_add(line.len());
-            self.lines.push_back(line);
-            while self.chars > MAX_TOOL_RESULT_CHARS {
-                self.truncated = true;
-                if let Some(front) = self.lines.pop_front() {
-                    self.chars = self.chars.saturating_sub(front.len());
-                } else {
-                    break;
-                }
-            }
+    let pipeline = crate::verify::pipeline_for(root, policy.verify.as_ref());
+    if pipeline.is_empty() {
+        let suggestions = crate::verify::suggestions_for(root, policy.verify.as_ref());
+        let Some(top) = suggestions.first() else {
+            return Err(
+                "Error: no verify suggestions for this workspace (pass {\"command\": ...})."
+                    .to_string(),
+            );
+        };
+        check(&top.command)?;
+        return Ok(VerifyPlan::Command(top.command.clone()));
+    }
+
+    let mut stages = Vec::new();
+    let mut left_out = Vec::new();
+    for stage in pipeline {
+        // A formatter stage without --check would rewrite files: skip it rather than
+        // refuse the whole run.
+        if let Err(err) = policy.check_read_only_command(&stage.command) {
+            left_out.push((stage, err.to_string()));
+            continue;
         }
+        check(&stage.command)?;
+        stages.push(stage);
+    }
+    if stages.is_empty() {
+        return Err(
+            "Error: read-only mode leaves no verify stage to run (pass {\"command\": ...})."
+                .to_string(),
+        );
+    }
+    Ok(VerifyPlan::Pipeline { stages, left_out })
+}
 
-        fn into_string(self) -> String {
-            let mut out = String::new();
-            if self.truncated {
-                out.push_str("... (output truncated; showing tail)\n");
-            }
-            for l in self.lines {
-                out.push_str(&l);
-            }
-            out
+async fn run_verify_pipeline(
+    stages: Vec<crate::verify::VerifyStage>,
+    call_id: &str,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    policy: &SandboxPolicy,
+) -> (String, bool) {
+    let mut results = Vec::new();
+    let mut remaining = stages.into_iter();
+    for stage in remaining.by_ref() {
+        let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+            call_id: call_id.to_string(),
+            chunk: format!("── {}: {}\n", stage.name, stage.command),
+        }));
+        let (output, passed) =
+            execute_bash_streaming(&stage.command, call_id, tx.clone(), policy).await;
+        let failures = crate::verify::parse_test_failures(&output);
+        let excerpt = if passed {
+            Vec::new()
+        } else if !failures.is_empty() {
+            failures.iter().map(|f| f.line()).collect()
+        } else {
+            crate::verify::error_excerpt(&output)
+        };
+        results.push(crate::verify::StageResult {
+            counts: crate::verify::count_problems(&output),
+            stage,
+            passed,
+            excerpt,
+        });
+        if !passed {
+            break;
         }
     }
+    let skipped: Vec<_> = remaining.collect();
+    let success = results.iter().all(|r| r.passed);
+    let summary = crate::verify::pipeline_summary(&results, &skipped);
+    let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+        call_id: call_id.to_string(),
+        chunk: format!("\n{}", summary),
+    }));
+    (summary, success)
+}
+
+async fn execute_bash_streaming(
+    command: &str,
+    call_id: &str,
+    tx: mpsc::UnboundedSender<AppEvent>,
+    policy: &SandboxPolicy,
+) -> (String, bool) {
+    const TIMEOUT_SECS: u64 = 60;
 
     let acc = std::sync::Arc::new(tokio::sync::Mutex::new(OutputAcc::default()));
 
     let call_id_owned = call_id.to_string();
 
-    let mut child = match Command::new("bash")
-        .arg("-c")
-        .arg(command)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
+    let mut cmd = match Confinement::from_policy(policy).map(|c| c.bash_command(command)) {
+        Some(Ok(cmd)) => cmd,
+        unconfined => {
+            if let Some(Err(e)) = unconfined {
+                let note = format!(
+                    "[sandbox] OS confinement unavailable ({}); running with policy checks only\n",
+                    e
+                );
+                let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+                    call_id: call_id.to_string(),
+                    chunk: note.clone(),
+                }));
+                acc.lock().await.push_line(note);
+            }
+            let mut cmd = Command::new("bash");
+            cmd.arg("-c").arg(command);
+            cmd
+        }
+    };
+    let mut child = match cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
         Ok(c) => c,
         Err(e) => return (format!("Error spawning process: {}", e), false),
     };
@@ -825,12 +1227,300 @@ async fn execute_bash_streaming(
 
     let out = {
         let mut guard = acc.lock().await;
-        std::mem::take(&mut *guard).into_string()
+        std::mem::take(&mut *guard).into_result(call_id)
     };
 
     (out, success)
 }
 
+/// Like [`execute_bash_streaming`], inside the conversation's persistent shell.
+async fn execute_bash_persistent(
+    command: &str,
+    call_id: &str,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    policy: &SandboxPolicy,
+    shell: &SharedShell,
+) -> (String, bool) {
+    const TIMEOUT_SECS: u64 = 60;
+
+    let mut acc = OutputAcc::default();
+    let emit = |acc: &mut OutputAcc, chunk: String| push_output(acc, tx, call_id, chunk);
+
+    let mut session = shell.lock().await;
+    // Started in another workspace: `/workspace use` switched since.
+    if session.as_ref().is_some_and(|s| s.root != policy.root) {
+        session.take();
+    }
+    if session.is_none() {
+        match crate::shell::ShellSession::spawn(policy) {
+            Ok(shell) => {
+                if let Some(note) = shell.unconfined_note.clone() {
+                    emit(&mut acc, note);
+                }
+                *session = Some(shell);
+            }
+            Err(e) => return (format!("Error spawning process: {}", e), false),
+        }
+    }
+    let Some(shell) = session.as_mut() else {
+        return (
+            "Error spawning process: no shell session".to_string(),
+            false,
+        );
+    };
+
+    let timeout = std::time::Duration::from_secs(TIMEOUT_SECS);
+    let result = shell
+        .run(command, timeout, |stream, line| {
+            let chunk = match stream {
+                crate::shell::Stream::Stdout => line,
+                crate::shell::Stream::Stderr => format!("[stderr] {}", line),
+            };
+            emit(&mut acc, chunk);
+        })
+        .await;
+    let code = match result {
+        Ok(code) => {
+            // `cd` can't take later commands outside the sandbox's paths.
+            if policy.check_path_allowed(&shell.cwd).is_err() {
+                let note = format!(
+                    "[shell] {} is outside the allowed paths; moved back to {}\n",
+                    shell.cwd.display(),
+                    policy.root.display()
+                );
+                let back = format!(
+                    "cd -- {}",
+                    crate::shell::sh_quote(&policy.root.display().to_string())
+                );
+                if shell.run(&back, timeout, |_, _| {}).await.is_err() {
+                    session.take();
+                }
+                emit(&mut acc, note);
+            }
+            code
+        }
+        Err(msg) => {
+            session.take();
+            emit(&mut acc, msg);
+            -1
+        }
+    };
+
+    emit(&mut acc, format!("[exit] {}\n", code));
+    (acc.into_result(call_id), code == 0)
+}
+
+/// Like [`execute_bash_streaming`], under a pseudo-terminal and the same OS confinement.
+/// The trace also gets the raw lines; the model only sees them cleaned.
+async fn execute_bash_pty(
+    command: &str,
+    input: Option<&str>,
+    call_id: &str,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    policy: &SandboxPolicy,
+    shell: &SharedShell,
+) -> (String, bool) {
+    const TIMEOUT_SECS: u64 = 60;
+
+    // Start where the persistent shell is, if there is one.
+    let cwd = match shell.try_lock() {
+        Ok(shell) => shell
+            .as_ref()
+            .filter(|s| s.root == policy.root)
+            .map(|s| s.cwd.clone()),
+        Err(_) => None,
+    }
+    .unwrap_or_else(|| policy.root.clone());
+
+    let mut acc = OutputAcc::default();
+    let timeout = std::time::Duration::from_secs(TIMEOUT_SECS);
+    let confinement = Confinement::from_policy(policy);
+    let result = crate::pty::run(
+        command,
+        input,
+        &cwd,
+        timeout,
+        confinement.as_ref(),
+        |raw, clean| {
+            let _ = tx.send(AppEvent::ToolOutputRaw(crate::events::ToolOutputEvent {
+                call_id: call_id.to_string(),
+                chunk: raw,
+            }));
+            push_output(&mut acc, tx, call_id, clean);
+        },
+    )
+    .await;
+    let code = match result {
+        Ok(code) => code,
+        Err(msg) if msg.starts_with("Error spawning") => return (msg, false),
+        Err(msg) => {
+            push_output(&mut acc, tx, call_id, msg);
+            -1
+        }
+    };
+
+    push_output(&mut acc, tx, call_id, format!("[exit] {}\n", code));
+    (acc.into_result(call_id), code == 0)
+}
+
+/// Stream `chunk` to the trace and keep it for the result.
+fn push_output(
+    acc: &mut OutputAcc,
+    tx: &mpsc::UnboundedSender<AppEvent>,
+    call_id: &str,
+    chunk: String,
+) {
+    let _ = tx.send(AppEvent::ToolOutput(crate::events::ToolOutputEvent {
+        call_id: call_id.to_string(),
+        chunk: chunk.clone(),
+    }));
+    acc.push_line(chunk);
+}
+
+/// Most command output sent back to the model: the head and tail of anything longer,
+/// with the middle left to `read_tool_output`.
+const MAX_TOOL_RESULT_CHARS: usize = 20_000;
+const TOOL_RESULT_HEAD_CHARS: usize = 6_000;
+/// Output kept per command for `read_tool_output`; older lines are dropped past this.
+const MAX_KEPT_OUTPUT_CHARS: usize = 4 * 1024 * 1024;
+/// Truncated outputs kept for `read_tool_output`, newest last.
+const KEPT_OUTPUTS: usize = 16;
+
+/// Full output of recent truncated commands, for `read_tool_output`.
+struct KeptOutput {
+    call_id: String,
+    /// Line number of `lines[0]` (earlier lines were dropped past the size cap)
+    first_line: usize,
+    lines: Vec<String>,
+}
+
+static KEPT_OUTPUT: OnceLock<Mutex<VecDeque<KeptOutput>>> = OnceLock::new();
+
+fn kept_outputs() -> &'static Mutex<VecDeque<KeptOutput>> {
+    KEPT_OUTPUT.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// A command's output as it streams, one entry per line (with its newline).
+#[derive(Default)]
+struct OutputAcc {
+    lines: VecDeque<String>,
+    chars: usize,
+    /// Lines dropped from the front past `MAX_KEPT_OUTPUT_CHARS`
+    dropped: usize,
+}
+
+impl OutputAcc {
+    fn push_line(&mut self, line: String) {
+        self.chars = self.chars.saturating_add(line.len());
+        self.lines.push_back(line);
+        while self.chars > MAX_KEPT_OUTPUT_CHARS {
+            let Some(front) = self.lines.pop_front() else {
+                break;
+            };
+            self.chars = self.chars.saturating_sub(front.len());
+            self.dropped += 1;
+        }
+    }
+
+    /// The model-visible result: everything when it's short, otherwise the head and
+    /// tail around a note on how to read the omitted lines (which are kept).
+    fn into_result(self, call_id: &str) -> String {
+        if self.chars <= MAX_TOOL_RESULT_CHARS && self.dropped == 0 {
+            return self.lines.into_iter().collect();
+        }
+        let lines: Vec<String> = self.lines.into();
+        let first_line = self.dropped + 1;
+        let total = self.dropped + lines.len();
+
+        let mut head_len = 0;
+        let mut budget = TOOL_RESULT_HEAD_CHARS;
+        while head_len < lines.len() && lines[head_len].len() <= budget {
+            budget -= lines[head_len].len();
+            head_len += 1;
+        }
+        let mut tail_start = lines.len();
+        let mut budget = MAX_TOOL_RESULT_CHARS - TOOL_RESULT_HEAD_CHARS;
+        while tail_start > head_len && lines[tail_start - 1].len() <= budget {
+            budget -= lines[tail_start - 1].len();
+            tail_start -= 1;
+        }
+
+        let mut out: String = lines[..head_len].concat();
+        let (from, to) = (first_line + head_len, first_line + tail_start - 1);
+        if self.dropped > 0 {
+            out.push_str(&format!("... [lines 1-{} were not kept]\n", self.dropped));
+        }
+        if from <= to {
+            out.push_str(&format!(
+                "... [{} of {} lines omitted ({}-{}); read_tool_output with call_id \"{}\", start_line and end_line shows them]\n",
+                to - from + 1,
+                total,
+                from,
+                to,
+                call_id
+            ));
+        }
+        out.push_str(&lines[tail_start..].concat());
+
+        let mut kept = kept_outputs().lock();
+        kept.retain(|k| k.call_id != call_id);
+        if kept.len() >= KEPT_OUTPUTS {
+            kept.pop_front();
+        }
+        kept.push_back(KeptOutput {
+            call_id: call_id.to_string(),
+            first_line,
+            lines,
+        });
+        out
+    }
+}
+
+/// Lines `start..=end` (1-based) of a kept command output, capped like any tool result.
+fn read_tool_output(call_id: &str, start: usize, end: usize) -> String {
+    let kept = kept_outputs().lock();
+    let Some(output) = kept.iter().find(|k| k.call_id == call_id) else {
+        return format!(
+            "Error: no kept output for call_id {} (only truncated output from recent commands is kept)",
+            call_id
+        );
+    };
+    let last = output.first_line + output.lines.len() - 1;
+    let start = start.max(output.first_line);
+    let end = end.min(last);
+    if start > end {
+        return format!(
+            "Error: lines {}-{} are available for {}",
+            output.first_line, last, call_id
+        );
+    }
+
+    let mut out = String::new();
+    let mut shown_end = start;
+    for n in start..=end {
+        let line = &output.lines[n - output.first_line];
+        if out.len() + line.len() > MAX_TOOL_RESULT_CHARS {
+            if n == start {
+                // One enormous line (minified JSON, say): show its start.
+                let mut cut = MAX_TOOL_RESULT_CHARS;
+                while !line.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                out.push_str(&line[..cut]);
+                out.push_str(" [... line cut]\n");
+            }
+            break;
+        }
+        out.push_str(line);
+        shown_end = n;
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!("[lines {}-{} of {}]", start, shown_end, last));
+    out
+}
+
 async fn execute_rg(query: &str, path: &str, context: Option<usize>) -> String {
     if query.trim().is_empty() {
         return "Error: Query cannot be empty".to_string();
@@ -889,10 +1579,22 @@ async fn execute_rg(query: &str, path: &str, context: Option<usize>) -> String {
     }
 }
 
-async fn execute_semantic_search(query: &str, policy: &SandboxPolicy) -> String {
+async fn execute_semantic_search(query: &str, kind: &str, policy: &SandboxPolicy) -> String {
     if query.trim().is_empty() {
         return "Error: Query cannot be empty".to_string();
     }
+    let kind = match kind.trim() {
+        "" | "all" | "any" => None,
+        name => match ChunkKind::parse(name) {
+            Some(kind) => Some(kind),
+            None => {
+                return format!(
+                    "Error: unknown kind '{}' (expected code, doc, config or all)",
+                    name
+                )
+            }
+        },
+    };
 
     let cwd = match std::env::current_dir() {
         Ok(dir) => dir,
@@ -904,39 +1606,127 @@ async fn execute_semantic_search(query: &str, policy: &SandboxPolicy) -> String
         Err(err) => return err.to_string(),
     };
 
-    // Initialize or get the semantic search engine
+    match search_workspace(&checked_root, query, kind) {
+        Ok(results) => {
+            if results.is_empty() && kind.is_some() {
+                format!(
+                    "No {} results found. Try without the kind filter.",
+                    kind.map(|k| k.as_str()).unwrap_or_default()
+                )
+            } else if results.is_empty() {
+                "No results found. The index may be empty - try indexing the project first."
+                    .to_string()
+            } else {
+                format_search_results(&results)
+            }
+        }
+        Err(e) => e,
+    }
+}
+
+/// Look up definitions in the workspace's symbol table, building it (without embeddings)
+/// if the workspace hasn't been indexed yet.
+fn execute_find_symbol(name: &str, prefix: bool, kind: &str, policy: &SandboxPolicy) -> String {
+    if name.trim().is_empty() {
+        return "Error: name cannot be empty".to_string();
+    }
+    let kind = match kind.trim().to_lowercase().as_str() {
+        "" | "any" | "all" => None,
+        "function" | "fn" => Some(SymbolType::Function),
+        "method" => Some(SymbolType::Method),
+        "class" => Some(SymbolType::Class),
+        "struct" | "type" => Some(SymbolType::Struct),
+        "enum" => Some(SymbolType::Enum),
+        "interface" => Some(SymbolType::Interface),
+        "trait" => Some(SymbolType::Trait),
+        "module" | "mod" => Some(SymbolType::Module),
+        "other" | "const" | "static" | "macro" => Some(SymbolType::Other),
+        other => return format!("Error: unknown symbol kind '{}'", other),
+    };
+
+    let cwd = match std::env::current_dir() {
+        Ok(dir) => dir,
+        Err(e) => return format!("Error: {}", e),
+    };
+    let root = match policy.check_path_allowed(&cwd) {
+        Ok(p) => p,
+        Err(err) => return err.to_string(),
+    };
+
+    let config = SearchConfig::for_workspace(&root);
+    let table = match SymbolTable::load(&config.index_dir) {
+        Some(table) => table,
+        None => match build_symbol_table(&root, &config) {
+            Ok(table) => table,
+            Err(e) => return format!("Error building symbol index: {}", e),
+        },
+    };
+
+    if table.is_empty() {
+        return "The symbol index is empty: no source files in a supported language were found. Use rg instead.".to_string();
+    }
+    let found = table.find(name, prefix, kind);
+    if found.is_empty() {
+        return format!(
+            "No definitions of '{}' in the symbol index ({} symbols). It may be defined in a file added since the last `lorikeet index`, or in a language without a parser; try rg.",
+            name.trim(),
+            table.len()
+        );
+    }
+    let mut out = found
+        .iter()
+        .map(|s| s.describe())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if found.len() == MAX_SYMBOL_MATCHES {
+        out.push_str(&format!(
+            "\n[first {} matches; narrow the name or kind]",
+            MAX_SYMBOL_MATCHES
+        ));
+    }
+    out
+}
+
+/// Semantic search over `root`'s index: through `lorikeet daemon` when one is running,
+/// otherwise with this process's engine (loaded on first use).
+fn search_workspace(
+    root: &Path,
+    query: &str,
+    kind: Option<ChunkKind>,
+) -> Result<Vec<SearchResult>, String> {
+    if let Some(result) = daemon::search(root, query, kind) {
+        return result;
+    }
+
     let search_mutex = get_semantic_search();
     let mut search_guard = search_mutex.lock();
 
+    // Each workspace root has its own index; switching roots loads the other one.
+    if search_guard
+        .as_ref()
+        .is_some_and(|s| s.project_root().as_deref() != Some(root))
+    {
+        *search_guard = None;
+    }
+
     // Initialize if not already done.
     if search_guard.is_none() {
-        let cfg = SearchConfig::for_workspace(&checked_root);
+        let cfg = SearchConfig::for_workspace(root);
         match SemanticSearch::new(cfg) {
             Ok(search) => {
-                // Set project root to current directory
-                search.set_project_root(checked_root);
+                search.set_project_root(root.to_path_buf());
                 *search_guard = Some(search);
             }
             Err(e) => {
-                return format!("Error initializing semantic search: {}", e);
+                return Err(format!("Error initializing semantic search: {}", e));
             }
         }
     }
 
     let search = search_guard.as_ref().unwrap();
-
-    // Perform the search
-    match search.search(query) {
-        Ok(results) => {
-            if results.is_empty() {
-                "No results found. The index may be empty - try indexing the project first."
-                    .to_string()
-            } else {
-                format_search_results(&results)
-            }
-        }
-        Err(e) => format!("Error searching: {}", e),
-    }
+    search
+        .search_kind(query, kind)
+        .map_err(|e| format!("Error searching: {}", e))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -954,6 +1744,8 @@ struct SmartHit {
     line: usize,
     col: Option<usize>,
     snippet: String,
+    /// Name of the extra search root the hit came from (`None` for the workspace)
+    root: Option<String>,
 }
 
 fn trunc_line(s: &str, max: usize) -> String {
@@ -987,9 +1779,12 @@ fn parse_rg_line(line: &str) -> Option<(String, usize, usize, String)> {
     Some((path, line_no, col, text))
 }
 
+/// `all_roots` also greps [`SandboxPolicy::extra_search_roots`]; their hits are tagged
+/// with the root's directory name. The semantic index only covers the workspace.
 async fn execute_smart_search(
     query: &str,
     path: &str,
+    all_roots: bool,
     limit: Option<usize>,
     use_rg: bool,
     use_semantic: bool,
@@ -1027,8 +1822,22 @@ async fn execute_smart_search(
     let mut rg_count = 0usize;
     let mut sem_count = 0usize;
 
+    let mut rg_roots = Vec::new();
     if use_rg {
-        let out = execute_rg(query, checked_scope.to_string_lossy().as_ref(), None).await;
+        rg_roots.push((None, checked_scope.clone()));
+    }
+    if use_rg && all_roots {
+        for root in policy.extra_search_roots() {
+            let label = root
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| root.display().to_string());
+            rg_roots.push((Some(label), root));
+        }
+    }
+
+    for (root_label, rg_root) in rg_roots {
+        let out = execute_rg(query, rg_root.to_string_lossy().as_ref(), None).await;
         if !out.starts_with("Error:") && !out.starts_with("No matches") {
             for l in out.lines() {
                 if let Some((p, line_no, col, text)) = parse_rg_line(l) {
@@ -1042,6 +1851,7 @@ async fn execute_smart_search(
                         line: line_no,
                         col: Some(col),
                         snippet,
+                        root: root_label.clone(),
                     };
                     hits_by_key
                         .entry(key)
@@ -1073,21 +1883,7 @@ async fn execute_smart_search(
             Err(e) => return e.to_string(),
         };
 
-        let search_mutex = get_semantic_search();
-        let mut search_guard = search_mutex.lock();
-        if search_guard.is_none() {
-            let cfg = SearchConfig::for_workspace(&checked_root);
-            match SemanticSearch::new(cfg) {
-                Ok(search) => {
-                    search.set_project_root(checked_root.clone());
-                    *search_guard = Some(search);
-                }
-                Err(e) => return format!("Error initializing semantic search: {}", e),
-            }
-        }
-        let search = search_guard.as_ref().unwrap();
-
-        match search.search(query) {
+        match search_workspace(&checked_root, query, None) {
             Ok(results) => {
                 for r in results {
                     let rel_path = r.chunk.metadata.file_path.clone();
@@ -1107,6 +1903,7 @@ async fn execute_smart_search(
                         line: line_no,
                         col: None,
                         snippet,
+                        root: None,
                     };
 
                     hits_by_key
@@ -1125,7 +1922,7 @@ async fn execute_smart_search(
                         .or_insert(candidate);
                 }
             }
-            Err(e) => return format!("Error searching: {}", e),
+            Err(e) => return e,
         }
     }
 
@@ -1161,11 +1958,14 @@ async fn execute_smart_search(
             SmartSource::Rg => "RG",
             SmartSource::Semantic => "SEM",
         };
-        let loc = if let Some(c) = h.col {
+        let mut loc = if let Some(c) = h.col {
             format!("{}:{}:{}", h.path, h.line, c)
         } else {
             format!("{}:{}", h.path, h.line)
         };
+        if let Some(root) = &h.root {
+            loc = format!("[{}] {}", root, loc);
+        }
         let snippet = if h.snippet.is_empty() {
             "".to_string()
         } else {
@@ -1189,6 +1989,8 @@ async fn open_at(path: &Path, line: usize, context: usize) -> String {
         Ok(c) => c,
         Err(e) => return format!("Error: {}", e),
     };
+    // Notebook line numbers (from search results) refer to the rendered cells.
+    let content = crate::notebook::readable(path, &content).unwrap_or(content);
 
     let lines: Vec<&str> = content.split('\n').collect();
     if lines.is_empty() {
@@ -1292,6 +2094,27 @@ fn parse_patch_ops(patch: &str) -> Result<Vec<PatchOp>, String> {
     Ok(ops)
 }
 
+/// Files an `apply_patch` patch touches, with the lines it adds and removes.
+pub(crate) fn patch_line_stats(patch: &str) -> Vec<(String, usize, usize)> {
+    let Ok(ops) = parse_patch_ops(patch) else {
+        return Vec::new();
+    };
+    ops.into_iter()
+        .map(|op| match op {
+            PatchOp::Add { path, content } => (path, content.lines().count(), 0),
+            PatchOp::Delete { path } => (path, 0, 0),
+            PatchOp::Update { path, diff } => {
+                let count = |prefix: char| {
+                    diff.iter()
+                        .filter(|l| l.starts_with(prefix) && !l.starts_with("***"))
+                        .count()
+                };
+                (path, count('+'), count('-'))
+            }
+        })
+        .collect()
+}
+
 fn normalize_line(s: &str) -> String {
     s.strip_suffix('\r').unwrap_or(s).to_string()
 }
@@ -1371,6 +2194,40 @@ fn apply_update_diff(original: &str, diff_lines: &[String]) -> Result<String, St
     Ok(out)
 }
 
+/// Point out conflict markers or broken syntax the edit left behind, so the model fixes
+/// them before it calls the turn done.
+fn append_edit_warnings(result: &mut String, edited: &[EditedFile]) {
+    if let Some(warning) = edit_check::review(edited) {
+        result.push_str("\n\n");
+        result.push_str(&warning);
+    }
+}
+
+/// Snapshot `edited` onto the `/undo` stack before a tool changes them. Best effort:
+/// a failed snapshot never blocks the edit.
+fn push_undo_entry(root: &Path, tool: &str, edited: &[EditedFile]) -> Option<(PathBuf, String)> {
+    if edited.is_empty() {
+        return None;
+    }
+    let paths: Vec<PathBuf> = edited.iter().map(|f| f.path.clone()).collect();
+    let entry = checkpoints::push_undo(root, tool, &paths).ok()?;
+    Some((root.to_path_buf(), entry.id))
+}
+
+/// Drop the snapshot again if the edit didn't happen, so `/undo` skips no-ops; otherwise
+/// note the result, so `/undo` can tell whether the files changed since.
+fn finish_undo_entry(undo: Option<(PathBuf, String)>, success: bool) {
+    match (undo, success) {
+        (Some((root, id)), true) => {
+            let _ = checkpoints::seal_undo(&root, &id);
+        }
+        (Some((root, id)), false) => {
+            let _ = checkpoints::drop_undo(&root, &id);
+        }
+        (None, _) => {}
+    }
+}
+
 async fn apply_patch_tool(patch: &str, policy: &SandboxPolicy) -> String {
     if patch.trim().is_empty() {
         return "Error: patch cannot be empty".to_string();
@@ -1508,45 +2365,617 @@ pub async fn index_directory_for_search(
     }
 }
 
-async fn edit_file(path: &Path, old_string: &str, new_string: &str) -> String {
-    // Read the file
+/// Lines in a near match (`fuzzy: true`) must be at least this similar, after collapsing
+/// whitespace.
+const FUZZY_MIN_SIMILARITY: f64 = 0.9;
+
+/// How far `old_string` may differ from the file when there is no exact match, from
+/// strictest to loosest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Matching {
+    /// `fuzzy: false`: the exact text only
+    Exact,
+    /// The default: lines equal once whitespace is collapsed
+    Whitespace,
+    /// `fuzzy: true`: also lines that differ in a few characters
+    Near,
+}
+
+impl Matching {
+    fn from_arg(fuzzy: Option<bool>) -> Self {
+        match fuzzy {
+            Some(false) => Self::Exact,
+            None => Self::Whitespace,
+            Some(true) => Self::Near,
+        }
+    }
+}
+
+/// Which matches of `old_string` an edit replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Occurrence {
+    /// The only match; several is an error
+    Unique,
+    /// The n-th match, 1-based
+    Nth(usize),
+    All,
+}
+
+impl Occurrence {
+    /// `occurrence` may be a number, a numeric string, or `"all"`.
+    fn from_arg(value: Option<&serde_json::Value>) -> Result<Self, String> {
+        let n = match value {
+            None | Some(serde_json::Value::Null) => return Ok(Self::Unique),
+            Some(serde_json::Value::String(s)) if s.trim().eq_ignore_ascii_case("all") => {
+                return Ok(Self::All)
+            }
+            Some(serde_json::Value::String(s)) => s.trim().parse::<usize>().ok(),
+            Some(v) => v.as_u64().map(|n| n as usize),
+        };
+        match n {
+            Some(n) if n >= 1 => Ok(Self::Nth(n)),
+            _ => Err("Error: occurrence must be a positive number or \"all\"".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EditOptions {
+    occurrence: Occurrence,
+    /// What counts as a match when `old_string` isn't found exactly
+    matching: Matching,
+    dry_run: bool,
+}
+
+/// A planned `edit_file` change.
+#[derive(Debug)]
+struct PlannedEdit {
+    content: String,
+    /// (1-based line, replaced text, replacement) per match
+    hunks: Vec<(usize, String, String)>,
+    /// How the replaced text matched `old_string`
+    matched: Matching,
+}
+
+impl PlannedEdit {
+    /// The change as `@@` hunks of removed and added lines.
+    fn diff(&self) -> String {
+        let mut out = String::new();
+        for (line, old, new) in &self.hunks {
+            out.push_str(&format!("@@ line {} @@\n", line));
+            for l in old.lines() {
+                out.push_str(&format!("-{}\n", l));
+            }
+            for l in new.lines() {
+                out.push_str(&format!("+{}\n", l));
+            }
+        }
+        out
+    }
+
+    /// Appended to the result when the file's text wasn't `old_string` verbatim. A near
+    /// match lists what was actually replaced, since it differs from what was asked.
+    fn match_note(&self) -> String {
+        match self.matched {
+            Matching::Exact => String::new(),
+            Matching::Whitespace => " (matched ignoring whitespace)".to_string(),
+            Matching::Near => {
+                let found: Vec<String> = self
+                    .hunks
+                    .iter()
+                    .map(|(line, found, _)| format!("line {}:\n{}", line, found))
+                    .collect();
+                format!(
+                    "\nold_string had no exact match; replaced this similar text instead:\n{}",
+                    found.join("\n")
+                )
+            }
+        }
+    }
+}
+
+fn plan_edit(
+    content: &str,
+    old_string: &str,
+    new_string: &str,
+    opts: EditOptions,
+) -> Result<PlannedEdit, String> {
+    if old_string.is_empty() {
+        return Err("Error: old_string cannot be empty".to_string());
+    }
+
+    let line_of = |offset: usize| content[..offset].matches('\n').count() + 1;
+    let mut matched = Matching::Exact;
+    let mut matches: Vec<(usize, usize)> = content
+        .match_indices(old_string)
+        .map(|(start, m)| (start, start + m.len()))
+        .collect();
+    // Looser matching only when the stricter one found nothing, so `matched` says how
+    // far the replaced text really was from old_string.
+    for (looser, min_similarity) in [
+        (Matching::Whitespace, 1.0),
+        (Matching::Near, FUZZY_MIN_SIMILARITY),
+    ] {
+        if matches.is_empty() && opts.matching >= looser {
+            matches = fuzzy_matches(content, old_string, min_similarity);
+            matched = looser;
+        }
+    }
+
+    let chosen: Vec<(usize, usize)> = match (opts.occurrence, matches.len()) {
+        (_, 0) => return Err("Error: Could not find the specified text".to_string()),
+        (Occurrence::Unique, 1) | (Occurrence::All, _) => matches,
+        (Occurrence::Unique, count) => {
+            let lines: Vec<String> = matches
+                .iter()
+                .take(10)
+                .map(|(start, _)| line_of(*start).to_string())
+                .collect();
+            return Err(format!(
+                "Error: Found {} occurrences of the text (lines {}). Pass occurrence: n or \"all\", or include more surrounding context.",
+                count,
+                lines.join(", ")
+            ));
+        }
+        (Occurrence::Nth(n), count) if n > count => {
+            return Err(format!(
+                "Error: occurrence {} requested but the text occurs {} time(s)",
+                n, count
+            ))
+        }
+        (Occurrence::Nth(n), _) => vec![matches[n - 1]],
+    };
+
+    // Loose matches leave out old_string's blank edges, newlines included; so must the
+    // replacement, or `"foo\n"` → `"bar\n"` would leave an extra blank line behind.
+    let new_string = if matched != Matching::Exact {
+        trim_blank_edges(new_string, old_string)
+    } else {
+        new_string
+    };
+    let mut out = String::with_capacity(content.len());
+    let mut hunks = Vec::new();
+    let mut last = 0;
+    for (start, end) in chosen {
+        let found = &content[start..end];
+        let replacement = if matched != Matching::Exact {
+            reindent(new_string, old_string, found)
+        } else {
+            new_string.to_string()
+        };
+        out.push_str(&content[last..start]);
+        out.push_str(&replacement);
+        hunks.push((line_of(start), found.to_string(), replacement));
+        last = end;
+    }
+    out.push_str(&content[last..]);
+
+    Ok(PlannedEdit {
+        content: out,
+        hunks,
+        matched,
+    })
+}
+
+/// Byte ranges of line windows that match `needle` line by line once whitespace is
+/// collapsed, at least `min_similarity` alike on average (1.0: equal). Ranges cover
+/// whole lines without the final newline, and don't overlap.
+fn fuzzy_matches(content: &str, needle: &str, min_similarity: f64) -> Vec<(usize, usize)> {
+    let wanted: Vec<String> = needle.lines().map(collapse_whitespace).collect();
+    // Blank edges make windows ambiguous; match on the meaningful lines.
+    let first = wanted.iter().position(|l| !l.is_empty());
+    let last = wanted.iter().rposition(|l| !l.is_empty());
+    let (Some(first), Some(last)) = (first, last) else {
+        return Vec::new();
+    };
+    let wanted = &wanted[first..=last];
+
+    let mut offsets = Vec::new();
+    let mut pos = 0;
+    for line in content.split('\n') {
+        offsets.push((pos, pos + line.trim_end_matches('\r').len()));
+        pos += line.len() + 1;
+    }
+    let lines: Vec<String> = offsets
+        .iter()
+        .map(|(s, e)| collapse_whitespace(&content[*s..*e]))
+        .collect();
+
+    let budget = (1.0 - min_similarity) * wanted.len() as f64;
+    // Whitespace-only matching needs no edit distance.
+    let score = |got: &str, want: &str| {
+        if min_similarity >= 1.0 {
+            f64::from(u8::from(got == want))
+        } else {
+            similarity(got, want)
+        }
+    };
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i + wanted.len() <= lines.len() {
+        let mut deficit = 0.0;
+        for (got, want) in lines[i..i + wanted.len()].iter().zip(wanted) {
+            deficit += 1.0 - score(got, want);
+            if deficit > budget {
+                break;
+            }
+        }
+        if deficit <= budget {
+            out.push((offsets[i].0, offsets[i + wanted.len() - 1].1));
+            i += wanted.len();
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+/// `new_string` without as many leading and trailing line breaks (and the blanks around
+/// them) as `old_string` has around its text.
+fn trim_blank_edges<'a>(new_string: &'a str, old_string: &str) -> &'a str {
+    let lead = old_string[..old_string.len() - old_string.trim_start().len()]
+        .matches('\n')
+        .count();
+    let trail = old_string[old_string.trim_end().len()..]
+        .matches('\n')
+        .count();
+    let mut out = new_string;
+    for _ in 0..lead {
+        match out.trim_start_matches([' ', '\t', '\r']).strip_prefix('\n') {
+            Some(rest) => out = rest,
+            None => break,
+        }
+    }
+    for _ in 0..trail {
+        match out.trim_end_matches([' ', '\t']).strip_suffix('\n') {
+            Some(rest) => out = rest.strip_suffix('\r').unwrap_or(rest),
+            None => break,
+        }
+    }
+    out
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 1 minus the normalized Levenshtein distance, by characters.
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    1.0 - prev[b.len()] as f64 / longest as f64
+}
+
+/// When a fuzzy match is indented differently from `old_string`, shift `new_string` by
+/// the same amount so the replacement lines up with the file.
+fn reindent(new_string: &str, old_string: &str, found: &str) -> String {
+    let indent = |s: &str| {
+        let line = s.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        line[..line.len() - line.trim_start().len()].to_string()
+    };
+    let (from, to) = (indent(old_string), indent(found));
+    if from == to {
+        return new_string.to_string();
+    }
+    new_string
+        .split('\n')
+        .map(|line| match line.strip_prefix(from.as_str()) {
+            Some(rest) if !line.trim().is_empty() => format!("{}{}", to, rest),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn edit_file(path: &Path, old_string: &str, new_string: &str, opts: EditOptions) -> String {
     let content = match tokio::fs::read_to_string(path).await {
         Ok(c) => c,
         Err(e) => return format!("Error reading file: {}", e),
     };
 
-    // Check if old_string exists
-    if !content.contains(old_string) {
-        return format!(
-            "Error: Could not find the specified text in {}",
-            path.display()
-        );
-    }
+    let edit = match plan_edit(&content, old_string, new_string, opts) {
+        Ok(edit) => edit,
+        Err(e) if e.starts_with("Error: Could not find") => {
+            return format!("{} in {}", e, path.display())
+        }
+        Err(e) => return e,
+    };
+    let note = edit.match_note();
 
-    // Check for uniqueness - count occurrences
-    let count = content.matches(old_string).count();
-    if count > 1 {
+    if opts.dry_run {
         return format!(
-            "Error: Found {} occurrences of the text. Please provide a more unique string.",
-            count
+            "Dry run: would edit {} ({} replacement(s)){}\n{}",
+            path.display(),
+            edit.hunks.len(),
+            note,
+            edit.diff()
         );
     }
 
-    // Replace
-    let new_content = content.replacen(old_string, new_string, 1);
-
-    // Write back
-    match tokio::fs::write(path, &new_content).await {
+    match tokio::fs::write(path, &edit.content).await {
         Ok(_) => {
             let old_lines = old_string.lines().count();
             let new_lines = new_string.lines().count();
+            let replaced = if edit.hunks.len() > 1 {
+                format!(", {} replacements", edit.hunks.len())
+            } else {
+                String::new()
+            };
             format!(
-                "Edited {} ({} lines → {} lines)",
+                "Edited {} ({} lines → {} lines{}){}",
                 path.display(),
                 old_lines,
-                new_lines
+                new_lines,
+                replaced,
+                note
             )
         }
         Err(e) => format!("Error writing file: {}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(occurrence: Occurrence) -> EditOptions {
+        EditOptions {
+            occurrence,
+            matching: Matching::Whitespace,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn edit_occurrences_pick_one_or_all_matches() {
+        let content = "a = 1\nb = 2\na = 1\n";
+        let err = plan_edit(content, "a = 1", "a = 3", opts(Occurrence::Unique)).unwrap_err();
+        assert!(
+            err.contains("2 occurrences of the text (lines 1, 3)"),
+            "{}",
+            err
+        );
+
+        let edit = plan_edit(content, "a = 1", "a = 3", opts(Occurrence::Nth(2))).unwrap();
+        assert_eq!(edit.content, "a = 1\nb = 2\na = 3\n");
+        assert_eq!(edit.diff(), "@@ line 3 @@\n-a = 1\n+a = 3\n");
+
+        let edit = plan_edit(content, "a = 1", "a = 3", opts(Occurrence::All)).unwrap();
+        assert_eq!(edit.content, "a = 3\nb = 2\na = 3\n");
+        assert_eq!(edit.hunks.len(), 2);
+
+        assert!(plan_edit(content, "a = 1", "x", opts(Occurrence::Nth(3))).is_err());
+        assert_eq!(
+            Occurrence::from_arg(Some(&serde_json::json!("all"))),
+            Ok(Occurrence::All)
+        );
+        assert_eq!(
+            Occurrence::from_arg(Some(&serde_json::json!("2"))),
+            Ok(Occurrence::Nth(2))
+        );
+        assert!(Occurrence::from_arg(Some(&serde_json::json!(0))).is_err());
+    }
+
+    #[test]
+    fn fuzzy_edit_ignores_whitespace_and_keeps_file_indentation() {
+        let content =
+            "fn main() {\n        let x  =  compute(1,2);\n        println!(\"{}\", x);\n}\n";
+        let old = "let x = compute(1, 2);\nprintln!(\"{}\", x);";
+        let new = "let x = compute(3, 4);\nprintln!(\"{x}\");";
+
+        let edit = plan_edit(content, old, new, opts(Occurrence::Unique)).unwrap();
+        assert_eq!(edit.matched, Matching::Whitespace);
+        assert_eq!(edit.match_note(), " (matched ignoring whitespace)");
+        assert_eq!(
+            edit.content,
+            "fn main() {\n        let x = compute(3, 4);\n        println!(\"{x}\");\n}\n"
+        );
+
+        let with = |matching| EditOptions {
+            matching,
+            ..opts(Occurrence::Unique)
+        };
+        assert!(plan_edit(content, old, new, with(Matching::Exact)).is_err());
+        assert!(plan_edit(content, "let y = other();", new, opts(Occurrence::Unique)).is_err());
+
+        // A character off only matches when asked for, and the note shows what was replaced
+        let typo = "let x = compute(1, 3);\nprintln!(\"{}\", x);";
+        assert!(plan_edit(content, typo, new, opts(Occurrence::Unique)).is_err());
+        let edit = plan_edit(content, typo, new, with(Matching::Near)).unwrap();
+        assert_eq!(edit.matched, Matching::Near);
+        let note = edit.match_note();
+        assert!(!note.contains("ignoring whitespace"), "{}", note);
+        assert!(
+            note.contains("line 2:\n        let x  =  compute(1,2);"),
+            "{}",
+            note
+        );
+        // Near matching still prefers a whitespace-only match
+        let edit = plan_edit(content, old, new, with(Matching::Near)).unwrap();
+        assert_eq!(edit.matched, Matching::Whitespace);
+        assert_eq!(Matching::from_arg(None), Matching::Whitespace);
+        assert_eq!(Matching::from_arg(Some(true)), Matching::Near);
+    }
+
+    #[test]
+    fn loose_edits_drop_the_line_breaks_old_string_ended_with() {
+        let content = "a\nfoo  \nb\n";
+        let edit = plan_edit(content, "foo\n", "bar\n", opts(Occurrence::Unique)).unwrap();
+        assert_eq!(edit.matched, Matching::Whitespace);
+        assert_eq!(edit.content, "a\nbar\nb\n");
+
+        // Only as many as old_string had: an added blank line stays
+        let edit = plan_edit(content, "\nfoo\n", "\nbar\n\n", opts(Occurrence::Unique)).unwrap();
+        assert_eq!(edit.content, "a\nbar\n\nb\n");
+        assert_eq!(trim_blank_edges("  \n  bar  \n", "\nfoo\n"), "  bar  ");
+    }
+
+    #[tokio::test]
+    async fn pty_commands_run_confined_under_the_default_policy() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let policy = SandboxPolicy::from_config(
+            crate::config::AppConfig::default(),
+            root.clone(),
+            &crate::tool_registry::names(),
+        );
+        assert!(Confinement::from_policy(&policy).is_some());
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (out, success) = execute_bash_pty(
+            "test -t 1 && echo tty; echo hi > inside.txt",
+            None,
+            "pty-call",
+            &tx,
+            &policy,
+            &SharedShell::default(),
+        )
+        .await;
+        assert!(success, "{}", out);
+        assert!(out.contains("tty\n"), "{}", out);
+        assert!(!out.contains("[sandbox]"), "{}", out);
+        let written = std::fs::read_to_string(root.join("inside.txt")).unwrap();
+        assert_eq!(written, "hi\n");
+    }
+
+    #[test]
+    fn read_only_verify_checks_every_stage_and_leaves_out_rewriting_formatters() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        let config = |pipeline: &[&str]| crate::config::AppConfig {
+            sandbox: Some(crate::config::SandboxConfig {
+                allow_commands: Some(vec!["cargo".into(), "git".into()]),
+                ..Default::default()
+            }),
+            verify: Some(crate::config::VerifyConfig {
+                commands: None,
+                pipeline: Some(pipeline.iter().map(|c| c.to_string()).collect()),
+            }),
+            ..Default::default()
+        };
+        let policy = |pipeline: &[&str]| {
+            SandboxPolicy::from_config(config(pipeline), root.clone(), &[]).with_read_only(true)
+        };
+
+        let stages = |plan: VerifyPlan| -> (Vec<String>, Vec<String>) {
+            match plan {
+                VerifyPlan::Pipeline { stages, left_out } => (
+                    stages.into_iter().map(|s| s.command).collect(),
+                    left_out.into_iter().map(|(s, _)| s.name).collect(),
+                ),
+                VerifyPlan::Command(c) => panic!("expected a pipeline, got `{}`", c),
+            }
+        };
+        let plan = verify_plan("", &root, &policy(&["cargo fmt --all", "cargo test"]));
+        assert_eq!(
+            stages(plan.unwrap()),
+            (vec!["cargo test".to_string()], vec!["fmt".to_string()])
+        );
+        let plan = verify_plan("", &root, &policy(&["cargo fmt -- --check", "cargo test"]));
+        assert_eq!(stages(plan.unwrap()).0.len(), 2);
+
+        // Anything else a stage can't do still refuses the run
+        let err = verify_plan("", &root, &policy(&["cargo test", "git commit -am wip"]));
+        assert!(err.is_err());
+        let err = verify_plan("", &root, &policy(&["cargo test", "make test"]));
+        assert!(err.is_err());
+        assert!(verify_plan("", &root, &policy(&["cargo fmt"])).is_err());
+        assert!(verify_plan("cargo fmt", &root, &policy(&[])).is_err());
+        assert!(verify_plan("cargo test", &root, &policy(&[])).is_ok());
+    }
+
+    #[tokio::test]
+    async fn each_conversation_has_its_own_shell() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let other = tempfile::TempDir::new().unwrap();
+        let policy_in = |root: &Path| {
+            SandboxPolicy::from_config(
+                crate::config::AppConfig::default(),
+                std::fs::canonicalize(root).unwrap(),
+                &crate::tool_registry::names(),
+            )
+        };
+        let policy = policy_in(dir.path());
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (first, second) = (SharedShell::default(), SharedShell::default());
+        let run = |command: &'static str, policy: &SandboxPolicy, shell: &SharedShell| {
+            let (tx, policy, shell) = (tx.clone(), policy.clone(), shell.clone());
+            async move { execute_bash_persistent(command, "c", &tx, &policy, &shell).await }
+        };
+
+        assert!(run("export LK_CONV=one", &policy, &first).await.1);
+        let (out, _) = run("echo \"[$LK_CONV]\"", &policy, &first).await;
+        assert!(out.contains("[one]"), "{}", out);
+        let (out, _) = run("echo \"[$LK_CONV]\"", &policy, &second).await;
+        assert!(out.contains("[]"), "{}", out);
+
+        // After a workspace switch the next call gets a fresh shell in the new root
+        let (out, _) = run("echo \"[$LK_CONV]\"", &policy_in(other.path()), &first).await;
+        assert!(out.contains("[]"), "{}", out);
+        let root = first.lock().await.as_ref().map(|s| s.root.clone());
+        assert_eq!(root, Some(std::fs::canonicalize(other.path()).unwrap()));
+    }
+
+    #[test]
+    fn infra_failures_are_told_apart_from_failing_commands() {
+        assert!(is_infra_failure(
+            "Error spawning process: No such file or directory (os error 2)"
+        ));
+        assert!(is_infra_failure(
+            "Error spawning rg: Resource temporarily unavailable"
+        ));
+        assert!(is_infra_failure(
+            "partial output\nError executing command: Interrupted\n[exit] -1\n"
+        ));
+        assert!(!is_infra_failure(
+            "[stderr] bash: line 1: carg: command not found\n[exit] 127\n"
+        ));
+        assert!(!is_infra_failure("Error: Query cannot be empty"));
+    }
+
+    #[test]
+    fn long_output_keeps_head_and_tail_and_the_rest_is_readable() {
+        let mut short = OutputAcc::default();
+        short.push_line("ok\n".into());
+        assert_eq!(short.into_result("short-call"), "ok\n");
+
+        let mut acc = OutputAcc::default();
+        for n in 1..=2000 {
+            acc.push_line(format!("line {:04} {}\n", n, "x".repeat(40)));
+        }
+        let result = acc.into_result("long-call");
+        assert!(result.len() <= MAX_TOOL_RESULT_CHARS + 200);
+        assert!(result.starts_with("line 0001 "));
+        assert!(result.trim_end().ends_with(&"x".repeat(40)));
+        assert!(result.contains("line 2000 "));
+        assert!(result.contains("read_tool_output with call_id \"long-call\""));
+        assert!(!result.contains("line 1000 "));
+
+        let middle = read_tool_output("long-call", 1000, 1001);
+        assert!(middle.starts_with("line 1000 "));
+        assert!(middle.contains("line 1001 "));
+        assert!(middle.ends_with("[lines 1000-1001 of 2000]"));
+        let capped = read_tool_output("long-call", 1, usize::MAX);
+        assert!(capped.len() <= MAX_TOOL_RESULT_CHARS + 40);
+        assert!(read_tool_output("long-call", 3000, 3001).starts_with("Error:"));
+        assert!(read_tool_output("short-call", 1, 1).starts_with("Error:"));
+    }
+}
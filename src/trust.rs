@@ -0,0 +1,133 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Remembered decisions, one `trusted <path>` or `restricted <path>` per line.
+const TRUST_FILE: &str = "trusted_workspaces";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustLevel {
+    /// Every tool, subject to the sandbox
+    Trusted,
+    /// Only tools that read and search; nothing that writes files or runs commands
+    Restricted,
+}
+
+impl TrustLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Trusted => "trusted",
+            Self::Restricted => "restricted",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trusted" => Some(Self::Trusted),
+            "restricted" => Some(Self::Restricted),
+            _ => None,
+        }
+    }
+}
+
+/// The remembered decision for `workspace_root`, or `None` if it's never been asked.
+pub fn load(workspace_root: &Path) -> Option<TrustLevel> {
+    load_from(&trust_file()?, workspace_root)
+}
+
+/// Remember `level` for `workspace_root`, replacing any earlier decision.
+pub fn remember(workspace_root: &Path, level: TrustLevel) -> std::io::Result<()> {
+    let Some(file) = trust_file() else {
+        return Ok(());
+    };
+    remember_in(&file, workspace_root, level)
+}
+
+/// What the agent may do here, for the trust prompt.
+pub fn summary(workspace_root: &Path, sandbox_enabled: bool) -> Vec<String> {
+    let mut out = vec![
+        "Trusted: the agent can read, search and edit files here and run shell commands."
+            .to_string(),
+        if sandbox_enabled {
+            "  Commands and paths stay within the sandbox policy.".to_string()
+        } else {
+            "  The sandbox is off: commands run unrestricted.".to_string()
+        },
+        "Restricted: read and search only; no edits, no shell commands.".to_string(),
+    ];
+    if workspace_root
+        .join(".lorikeet")
+        .join("config.toml")
+        .exists()
+    {
+        out.push(
+            "Its .lorikeet/config.toml (sandbox rules, verify commands) applies once trusted."
+                .into(),
+        );
+    }
+    out
+}
+
+fn trust_file() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".lorikeet").join(TRUST_FILE))
+}
+
+fn key(workspace_root: &Path) -> String {
+    std::fs::canonicalize(workspace_root)
+        .unwrap_or_else(|_| workspace_root.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn load_from(file: &Path, workspace_root: &Path) -> Option<TrustLevel> {
+    let key = key(workspace_root);
+    std::fs::read_to_string(file)
+        .ok()?
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(_, path)| *path == key)
+        .filter_map(|(level, _)| TrustLevel::parse(level))
+        .last()
+}
+
+fn remember_in(file: &Path, workspace_root: &Path, level: TrustLevel) -> std::io::Result<()> {
+    let key = key(workspace_root);
+    let kept: Vec<String> = std::fs::read_to_string(file)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| line.split_once(' ').is_some_and(|(_, path)| path != key))
+        .map(|line| line.to_string())
+        .collect();
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut out = std::fs::File::create(file)?;
+    for line in kept {
+        writeln!(out, "{}", line)?;
+    }
+    writeln!(out, "{} {}", level.as_str(), key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn decisions_are_remembered_per_workspace() {
+        let home = TempDir::new().unwrap();
+        let file = home.path().join(".lorikeet").join(TRUST_FILE);
+        let a = TempDir::new().unwrap();
+        let b = TempDir::new().unwrap();
+
+        assert_eq!(load_from(&file, a.path()), None);
+        remember_in(&file, a.path(), TrustLevel::Restricted).unwrap();
+        remember_in(&file, b.path(), TrustLevel::Trusted).unwrap();
+        remember_in(&file, a.path(), TrustLevel::Trusted).unwrap();
+
+        assert_eq!(load_from(&file, a.path()), Some(TrustLevel::Trusted));
+        assert_eq!(load_from(&file, b.path()), Some(TrustLevel::Trusted));
+        let text = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(text.lines().count(), 2);
+    }
+}
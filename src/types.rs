@@ -6,6 +6,11 @@ pub struct ToolCallMessage {
     #[serde(rename = "type")]
     pub call_type: String,
     pub function: ToolCallFunction,
+    /// Gemini's `thoughtSignature` for this call, sent back with it on the next request
+    /// (thinking models reject a history without it). Not part of the OpenAI format, so
+    /// never serialized.
+    #[serde(skip)]
+    pub thought_signature: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use ratatui::{
     layout::Margin,
     prelude::*,
@@ -10,15 +12,19 @@ use ratatui::{
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::app::{
-    App, IndexingStatus, Pane, PlanFocus, PlanQuestionKind, ToolOutput, ToolStatus,
+    App, FileView, IndexingStatus, MemoryField, Pane, PlanFocus, PlanQuestionKind, RestorePrompt,
+    ToolOutput, ToolStatus, TurnLimitPrompt, RECENT_FILES_SHOWN,
 };
+use crate::highlight;
 use crate::markdown;
+use crate::stats::{compact_count, format_duration};
 use crate::theme;
+use crate::trust::TrustLevel;
 
-const INDEXING_SPINNER: &[&str] = &["⣾", "⣽", "⣻", "⢿", "⡿", "⣟", "⣯", "⣷"];
+/// Rows the input box grows to before it scrolls.
+const MAX_INPUT_ROWS: u16 = 8;
 
-const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-const TOOL_SPINNER_FRAMES: &[&str] = &["◐", "◓", "◑", "◒"];
+const INDEXING_SPINNER: &[&str] = &["⣾", "⣽", "⣻", "⢿", "⡿", "⣟", "⣯", "⣷"];
 
 #[derive(Clone, Copy)]
 struct Fill {
@@ -38,7 +44,7 @@ impl Widget for Fill {
 }
 
 pub fn ui(frame: &mut Frame, app: &mut App) {
-    let ui_theme = theme::ui_theme(&app.config, Some(app.workspace_root_path()));
+    let ui_theme = app.ui_theme();
     let pal = ui_theme.palette;
     app.root_area = frame.area();
 
@@ -62,11 +68,13 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         ])
         .split(frame.area());
 
+    // The input grows with multi-line drafts, up to MAX_INPUT_ROWS.
+    let input_rows = (app.input.split('\n').count() as u16).clamp(1, MAX_INPUT_ROWS);
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Min(1),
-            Constraint::Length(3),
+            Constraint::Length(input_rows + 2),
             Constraint::Length(1),
         ])
         .split(main_chunks[0]);
@@ -80,17 +88,6 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
 
     let chat_width = left_chunks[0].width.saturating_sub(4) as usize;
 
-    // Advance tool spinner (used by inline tool rendering)
-    app.tool_spinner_frame = (app.tool_spinner_frame + 1) % TOOL_SPINNER_FRAMES.len();
-    let _tool_spinner = TOOL_SPINNER_FRAMES[app.tool_spinner_frame];
-
-    // Advance indexing spinner (used by context sidebar)
-    app.indexing_spinner_frame = (app.indexing_spinner_frame + 1) % INDEXING_SPINNER.len();
-
-    if app.is_processing {
-        app.spinner_frame = (app.spinner_frame + 1) % SPINNER_FRAMES.len();
-    }
-
     // Keep running tool spinners alive without forcing a full transcript rebuild.
     for gid in app
         .tool_outputs
@@ -120,25 +117,55 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         app.chat_follow = true;
     }
 
-    let visible_lines = app
+    let mut visible_lines = app
         .render_store
         .visible_lines(app.chat_scroll_rows, visible_height);
+    for (i, line) in visible_lines.iter_mut().enumerate() {
+        let row = app.chat_scroll_rows + i;
+        // `path:line` references are clickable; the one picked with `f` is selected.
+        let text = crate::render_store::line_text(line);
+        for file_ref in crate::render_store::file_refs(&text) {
+            let selected = app
+                .selected_file_ref
+                .as_ref()
+                .is_some_and(|(r, sel)| *r == row && *sel == file_ref);
+            let style = if selected {
+                pal.selection()
+            } else {
+                Style::default().underlined()
+            };
+            let range = (file_ref.range.start, file_ref.range.end);
+            *line = restyle_ranges(std::mem::take(line), &[range], style);
+        }
+        if let Some(query) = app.transcript_query.as_deref() {
+            let style = if app.transcript_match_row == Some(row) {
+                pal.selection().bold()
+            } else {
+                Style::default().fg(pal.warn).bold().underlined()
+            };
+            let ranges = crate::render_store::match_ranges(&text, query);
+            *line = restyle_ranges(std::mem::take(line), &ranges, style);
+        }
+    }
 
     app.chat_area = left_chunks[0];
 
-    let chat_title = if app.active_pane == Pane::Chat {
-        if app.plan_mode {
-            format!("* Lorikeet · {} · PLAN ", app.model)
-        } else {
-            format!("* Lorikeet · {} ", app.model)
-        }
+    let marker = if app.active_pane == Pane::Chat {
+        "*"
     } else {
-        if app.plan_mode {
-            format!("  Lorikeet · {} · PLAN ", app.model)
-        } else {
-            format!("  Lorikeet · {} ", app.model)
-        }
+        " "
     };
+    let session = app
+        .session_title
+        .as_deref()
+        .map(|t| format!(" · {}", t))
+        .unwrap_or_default();
+    let mode = if app.plan_mode { " · PLAN" } else { "" };
+    let read_only = if app.read_only { " · READ-ONLY" } else { "" };
+    let chat_title = format!(
+        "{} Lorikeet{} · {}{}{} ",
+        marker, session, app.model, mode, read_only
+    );
     let messages_widget = Paragraph::new(visible_lines)
         .block(
             Block::default()
@@ -199,34 +226,112 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
         );
     }
 
-    let input_widget = Paragraph::new(app.input.as_str())
+    let input_text = match app.transcript_search_prompt.as_deref() {
+        Some(prompt) => format!("/{}", prompt),
+        None => app.input.clone(),
+    };
+    // Cursor row/column within the draft; scroll to keep its row in view.
+    let before_cursor = app.input.get(..app.cursor_pos).unwrap_or(&app.input);
+    let cursor_row = before_cursor.matches('\n').count() as u16;
+    let cursor_col = UnicodeWidthStr::width(before_cursor.rsplit('\n').next().unwrap_or(""));
+    let input_scroll = cursor_row.saturating_sub(input_rows - 1);
+    let input_widget = Paragraph::new(input_text.as_str())
         .style(Style::default().fg(input_fg))
         .block(input_block)
-        .wrap(Wrap { trim: false });
+        .wrap(Wrap { trim: false })
+        .scroll((input_scroll, 0));
     frame.render_widget(input_widget, left_chunks[1]);
 
-    if app.active_pane == Pane::Chat
+    if let Some(prompt) = app.transcript_search_prompt.as_deref() {
+        let cursor_x = left_chunks[1].x + UnicodeWidthStr::width(prompt) as u16 + 2;
+        frame.set_cursor_position((cursor_x, left_chunks[1].y + 1));
+    } else if app.active_pane == Pane::Chat
+        && !app.chat_normal_mode
         && !app.is_processing
         && !app.settings_open
         && !app.themes_open
+        && !app.models_open
+        && !app.memories_open
+        && !app.copy_open
+        && !app.stats_open
         && !app.plan_popup_open
+        && app.pending_approval.is_none()
+        && !app.trust_prompt_open
+        && !app.reauth_prompt_open
+        && app.restore_prompt.is_none()
+        && app.turn_limit_prompt.is_none()
+        && !app.quit_confirm_open
+        && app.recent_file_popup.is_none()
+        && app.text_popup.is_none()
+        && app.tool_pager.is_none()
     {
-        let cursor_x = left_chunks[1].x + app.cursor_pos as u16 + 1;
-        let cursor_y = left_chunks[1].y + 1;
+        let cursor_x = left_chunks[1].x + cursor_col as u16 + 1;
+        let cursor_y = left_chunks[1].y + 1 + cursor_row - input_scroll;
         frame.set_cursor_position((cursor_x, cursor_y));
     }
 
     // Status bar (minimal; keep other shortcuts discoverable via /help)
-    let status_text = " ESC quit │ TAB plan │ ENTER send";
-    let status = Paragraph::new(status_text).style(pal.meta());
+    let status_text = if app.chat_normal_mode && app.active_pane == Pane::Chat {
+        normal_mode_status(app)
+    } else if let Some(search) = transcript_search_status(app) {
+        format!(" {} │ ^N/^P next/prev │ /find clears", search)
+    } else {
+        " ^C quit │ TAB plan │ ENTER send".to_string()
+    };
+    // Read-only mode gets a banner in front, so it's never mistaken for a normal session.
+    let mut status_spans = Vec::new();
+    if let Some(banner) = &app.replaying {
+        status_spans.push(Span::styled(
+            banner.clone(),
+            Style::default().fg(pal.accent).bold().reversed(),
+        ));
+    }
+    if let Some(banner) = app.offline_banner() {
+        status_spans.push(Span::styled(
+            banner,
+            Style::default().fg(pal.err).bold().reversed(),
+        ));
+    }
+    if app.read_only {
+        status_spans.push(Span::styled(
+            " READ-ONLY: no edits (/readonly) ",
+            Style::default().fg(pal.warn).bold().reversed(),
+        ));
+    }
+    status_spans.push(Span::raw(status_text));
+    let status = Paragraph::new(Line::from(status_spans)).style(pal.meta());
     frame.render_widget(status, left_chunks[2]);
+    frame.render_widget(
+        Paragraph::new(token_meter(app, &pal)).alignment(Alignment::Right),
+        left_chunks[2],
+    );
 
     // Context sidebar (right pane)
     app.context_area = main_chunks[2];
-    render_context_sidebar(frame, app, main_chunks[2], &ui_theme);
+    if app.file_view.is_some() {
+        render_file_view(frame, app, main_chunks[2], &ui_theme);
+    } else {
+        render_context_sidebar(frame, app, main_chunks[2], &ui_theme);
+    }
 
     // Slash command suggestions overlay (while typing)
-    if !app.settings_open && !app.themes_open && !app.plan_popup_open {
+    if !app.settings_open
+        && !app.themes_open
+        && !app.models_open
+        && !app.memories_open
+        && !app.copy_open
+        && !app.stats_open
+        && !app.plan_popup_open
+        && app.pending_approval.is_none()
+        && !app.trust_prompt_open
+        && !app.reauth_prompt_open
+        && app.restore_prompt.is_none()
+        && app.turn_limit_prompt.is_none()
+        && !app.quit_confirm_open
+        && app.recent_file_popup.is_none()
+        && app.text_popup.is_none()
+        && app.tool_pager.is_none()
+    {
         render_command_suggestions_overlay(frame, app, left_chunks[1], pal);
     }
     if app.settings_open {
@@ -235,9 +340,143 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     if app.themes_open {
         render_themes_popup(frame, app);
     }
+    if app.models_open {
+        render_models_popup(frame, app, &ui_theme);
+    }
+    if app.memories_open {
+        render_memories_popup(frame, app, &ui_theme);
+    }
+    if app.copy_open {
+        render_copy_popup(frame, app, &ui_theme);
+    }
+    if app.stats_open {
+        render_stats_popup(frame, app, &ui_theme);
+    }
     if app.plan_popup_open {
         render_plan_popup(frame, app, &ui_theme);
     }
+    if app.tool_pager.is_some() {
+        render_tool_pager(frame, app, &ui_theme);
+    }
+    if app.pending_approval.is_some() {
+        render_approval_popup(frame, app, &ui_theme);
+    }
+    if app.trust_prompt_open {
+        render_trust_popup(frame, app, &ui_theme);
+    }
+    if app.recent_file_popup.is_some() {
+        render_recent_file_popup(frame, app, &ui_theme);
+    }
+    if app.text_popup.is_some() {
+        render_text_popup(frame, app, &ui_theme);
+    }
+    if app.reauth_prompt_open {
+        render_reauth_popup(frame, &ui_theme);
+    }
+    if let Some(prompt) = &app.restore_prompt {
+        render_restore_popup(frame, prompt, &ui_theme);
+    }
+    if let Some(prompt) = &app.turn_limit_prompt {
+        render_turn_limit_popup(frame, prompt, &ui_theme);
+    }
+    if app.quit_confirm_open {
+        render_quit_popup(frame, app, &ui_theme);
+    }
+}
+
+/// `tokens: 34k/200k` for the context the next call sends, turning yellow past 75% of the
+/// model's window and red past 90%.
+fn token_meter(app: &mut App, pal: &theme::UiPalette) -> Line<'static> {
+    let used = app.context_tokens() as u64;
+    let (text, style) = match app.context_window() {
+        Some(window) => {
+            let ratio = used as f64 / window.max(1) as f64;
+            let style = if ratio >= 0.9 {
+                Style::default().fg(pal.err).bold()
+            } else if ratio >= 0.75 {
+                Style::default().fg(pal.warn)
+            } else {
+                pal.meta()
+            };
+            let text = format!(
+                "tokens: {}/{} ",
+                crate::tokens::short(used),
+                crate::tokens::short(window)
+            );
+            (text, style)
+        }
+        None => (
+            format!("tokens: {} ", crate::tokens::short(used)),
+            pal.meta(),
+        ),
+    };
+    Line::from(Span::styled(text, style))
+}
+
+fn normal_mode_status(app: &App) -> String {
+    let mut out = " NORMAL │ j/k scroll │ gg/G top/bottom │ / search".to_string();
+    if let Some(search) = transcript_search_status(app) {
+        out.push_str(&format!(" │ {} │ n/N next/prev", search));
+    }
+    out.push_str(" │ y yank │ p tool output │ i insert");
+    out
+}
+
+/// `"query" 2/5` for the active transcript search.
+fn transcript_search_status(app: &App) -> Option<String> {
+    let query = app.transcript_query.as_deref()?;
+    let rows = app.render_store.find(query);
+    let current = app
+        .transcript_match_row
+        .and_then(|row| rows.iter().position(|&r| r == row));
+    Some(match current {
+        Some(i) => format!("\"{}\" {}/{}", query, i + 1, rows.len()),
+        None if rows.is_empty() => format!("\"{}\" no matches", query),
+        None => format!("\"{}\" {} matches", query, rows.len()),
+    })
+}
+
+/// Patch `style` onto the given byte ranges of `line`'s text.
+fn restyle_ranges(line: Line<'static>, ranges: &[(usize, usize)], style: Style) -> Line<'static> {
+    if ranges.is_empty() {
+        return line;
+    }
+    let Line {
+        spans,
+        style: line_style,
+        alignment,
+    } = line;
+    let mut out: Vec<Span<'static>> = Vec::with_capacity(spans.len() + ranges.len() * 2);
+    let mut offset = 0;
+    for span in spans {
+        let text = span.content.as_ref();
+        let end = offset + text.len();
+        let mut cut = 0;
+        for &(start, stop) in ranges {
+            let (start, stop) = (start.max(offset), stop.min(end));
+            if start >= stop {
+                continue;
+            }
+            let (start, stop) = (start - offset, stop - offset);
+            if start > cut {
+                out.push(Span::styled(text[cut..start].to_string(), span.style));
+            }
+            out.push(Span::styled(
+                text[start..stop].to_string(),
+                span.style.patch(style),
+            ));
+            cut = stop;
+        }
+        if cut < text.len() {
+            out.push(Span::styled(text[cut..].to_string(), span.style));
+        }
+        offset = end;
+    }
+    Line {
+        spans: out,
+        style: line_style,
+        alignment,
+    }
 }
 
 fn render_vsplit(frame: &mut Frame, area: Rect, style: Style) {
@@ -258,15 +497,34 @@ fn render_command_suggestions_overlay(
 ) {
     let t = app.input.trim_start();
     // Show suggestions immediately when the user types '/', not only after a second character.
-    if !t.starts_with('/') {
-        return;
-    }
-    let suggestions = app.command_suggestions(t);
-    if suggestions.is_empty() {
+    let (title, rows): (&str, Vec<String>) = if t.starts_with('/') {
+        let rows = app
+            .command_suggestions(t)
+            .into_iter()
+            .map(|(cmd, desc)| format!("{:<10} {}", cmd, desc))
+            .collect();
+        (" Commands ", rows)
+    } else {
+        // `@` mentions: bookmarks are starred.
+        let rows = app
+            .mention_suggestions()
+            .into_iter()
+            .map(|path| {
+                let mark = if app.bookmarks.contains(&path) {
+                    "★"
+                } else {
+                    " "
+                };
+                format!("{} {}", mark, path)
+            })
+            .collect();
+        (" Files ", rows)
+    };
+    if rows.is_empty() {
         return;
     }
 
-    let max_items = suggestions.len().min(6);
+    let max_items = rows.len().min(6);
     let h = (max_items as u16).saturating_add(2);
     let y = input_area.y.saturating_sub(h);
     let area = Rect {
@@ -287,7 +545,7 @@ fn render_command_suggestions_overlay(
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(pal.border_style())
-        .title(Span::styled(" Commands ", pal.meta()));
+        .title(Span::styled(title, pal.meta()));
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
@@ -297,8 +555,7 @@ fn render_command_suggestions_overlay(
     let selected = app
         .command_suggest_selected
         .min(max_items.saturating_sub(1));
-    for (i, (cmd, desc)) in suggestions.into_iter().take(max_items).enumerate() {
-        let s = format!("{:<10} {}", cmd, desc);
+    for (i, s) in rows.into_iter().take(max_items).enumerate() {
         let style = if i == selected {
             pal.selection()
         } else {
@@ -319,24 +576,36 @@ fn render_indexing_status(status: &IndexingStatus, spinner_frame: usize) -> Stri
         IndexingStatus::Indexing {
             files_done,
             total_files,
+            current,
         } => {
+            // Just the file name: the border has little room.
+            let current = current
+                .as_deref()
+                .map(|c| c.rsplit(['/', '\\']).next().unwrap_or(c))
+                .map(|name| format!(" · {}", truncate_to_width(name, 24)))
+                .unwrap_or_default();
             if *total_files > 0 {
                 format!(
-                    " {} Indexing... {}/{} files",
-                    spinner, files_done, total_files
+                    " {} Indexing... {}/{} files{}",
+                    spinner, files_done, total_files, current
                 )
             } else {
                 format!(" {} Indexing...", spinner)
             }
         }
         IndexingStatus::Complete { chunks, files } => {
-            format!(" ● Indexed {} chunks from {} files", chunks, files)
+            format!(
+                " {} Indexed {} chunks from {} files",
+                theme::STATUS_OK,
+                chunks,
+                files
+            )
         }
         IndexingStatus::Error(err) => {
             let msg = if err.len() > 40 {
-                format!(" ● Index error: {}...", &err[..40])
+                format!(" {} Index error: {}...", theme::STATUS_ERR, &err[..40])
             } else {
-                format!(" ● Index error: {}", err)
+                format!(" {} Index error: {}", theme::STATUS_ERR, err)
             };
             msg
         }
@@ -499,6 +768,18 @@ fn render_settings_popup(frame: &mut Frame, app: &mut App) {
     for l in settings_item_help(app) {
         detail_lines.push(Line::from(l));
     }
+    detail_lines.push(Line::from(""));
+    detail_lines.push(Line::from(Span::styled(
+        format!("Source: {}", app.settings_selected_source()),
+        pal.meta(),
+    )));
+    if let Some(err) = &app.settings_error {
+        detail_lines.push(Line::from(""));
+        detail_lines.push(Line::from(Span::styled(
+            format!("Not saved: {}", err),
+            Style::default().fg(pal.err),
+        )));
+    }
 
     let details = Paragraph::new(detail_lines)
         .block(Block::default().borders(Borders::NONE))
@@ -591,6 +872,22 @@ fn settings_item_help(app: &App) -> Vec<String> {
             "Policy-only sandbox for tools + file access.".into(),
             "Use ←→ to toggle, then ENTER to save.".into(),
         ],
+        "Verify commands" => vec![
+            "Commands /verify offers before auto-detected ones, separated by ;".into(),
+            "ALT+↑↓ moves the command under the cursor. Saved to this workspace.".into(),
+        ],
+        "Verify pipeline" => vec![
+            "Stages verify runs in order, stopping at the first failure; separated by ;".into(),
+            "Empty uses the detected pipeline. ALT+↑↓ reorders. Saved to this workspace.".into(),
+        ],
+        name if crate::tool_registry::access(name).is_some() => vec![
+            format!(
+                "Offer the {} tool ({}) to the model (sandbox allow tools).",
+                name,
+                crate::tool_registry::access(name).map_or("", |a| a.label())
+            ),
+            "Use ←→ to toggle; toggles are kept until ENTER saves or ESC cancels.".into(),
+        ],
         _ => vec![
             "Use ↑↓ to pick an item, then edit the value below.".into(),
             "ENTER saves; ESC cancels.".into(),
@@ -599,7 +896,7 @@ fn settings_item_help(app: &App) -> Vec<String> {
 }
 
 fn render_themes_popup(frame: &mut Frame, app: &mut App) {
-    let pal = theme::ui_theme(&app.config, Some(app.workspace_root_path())).palette;
+    let pal = app.ui_theme().palette;
     let area = frame.area();
     let popup_area = centered_rect(72, 60, area);
 
@@ -747,9 +1044,961 @@ fn render_themes_popup(frame: &mut Frame, app: &mut App) {
         preview_inner,
     );
 
-    let hints =
-        Paragraph::new(" Enter apply • Esc close • ↑↓ select • type to filter").style(pal.meta());
-    frame.render_widget(hints, chunks[2]);
+    let hints =
+        Paragraph::new(" Enter apply • Esc close • ↑↓ select • type to filter").style(pal.meta());
+    frame.render_widget(hints, chunks[2]);
+}
+
+fn render_models_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(72, 70, frame.area());
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(pal.border_style())
+        .title(Span::styled(
+            format!(" Models ({}) ", app.llm_provider_name()),
+            Style::default().fg(pal.accent).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let filter_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(pal.border_style())
+        .title(Span::styled(" Filter ", pal.meta()));
+    let filter = Paragraph::new(app.models_query.as_str())
+        .block(filter_block)
+        .style(Style::default().fg(pal.fg));
+    frame.render_widget(filter, chunks[0]);
+    frame.set_cursor_position((
+        chunks[0].x + 1 + UnicodeWidthStr::width(app.models_query.as_str()) as u16,
+        chunks[0].y + 1,
+    ));
+
+    // Keep the selection visible in long lists.
+    let items = app.filtered_models();
+    let list_h = chunks[1].height as usize;
+    let start = (app.models_selected + 1).saturating_sub(list_h);
+    let list_w = chunks[1].width as usize;
+    let lines: Vec<Line> = items
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(list_h)
+        .map(|(i, model)| {
+            let details = model.details();
+            let current = if model.id == app.model { "● " } else { "  " };
+            let id_w = list_w.saturating_sub(UnicodeWidthStr::width(details.as_str()) + 4);
+            let id = truncate_line(&model.id, id_w);
+            let pad = id_w.saturating_sub(UnicodeWidthStr::width(id.as_str()));
+            let (id_style, details_style) = if i == app.models_selected {
+                (pal.selection(), pal.selection())
+            } else {
+                (Style::default().fg(pal.fg), pal.meta())
+            };
+            Line::from(vec![
+                Span::styled(current, Style::default().fg(pal.accent)),
+                Span::styled(format!("{}{}  ", id, " ".repeat(pad)), id_style),
+                Span::styled(details, details_style),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), chunks[1]);
+
+    let hints = match app.models_status.as_deref() {
+        Some(status) => Span::styled(format!(" {}", status), Style::default().fg(pal.warn)),
+        None => Span::styled(
+            format!(
+                " {} models • Enter select • Esc close • type to filter",
+                items.len()
+            ),
+            pal.meta(),
+        ),
+    };
+    frame.render_widget(Paragraph::new(Line::from(hints)), chunks[2]);
+}
+
+fn render_memories_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(80, 75, frame.area());
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(pal.border_style())
+        .title(Span::styled(
+            " Memories ",
+            Style::default().fg(pal.accent).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let edit_h = if app.memory_edit.is_some() { 5 } else { 0 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(4),
+            Constraint::Length(edit_h),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let filter_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(pal.border_style())
+        .title(Span::styled(" Filter ", pal.meta()));
+    let filter = Paragraph::new(app.memories_query.as_str())
+        .block(filter_block)
+        .style(Style::default().fg(pal.fg));
+    frame.render_widget(filter, chunks[0]);
+    if app.memory_edit.is_none() {
+        frame.set_cursor_position((
+            chunks[0].x + 1 + UnicodeWidthStr::width(app.memories_query.as_str()) as u16,
+            chunks[0].y + 1,
+        ));
+    }
+
+    // One header line per scope/type group, then its memories.
+    let items = app.filtered_memories();
+    let list_w = chunks[1].width as usize;
+    let mut lines: Vec<Line> = Vec::new();
+    let mut selected_line = 0;
+    let mut group: Option<(&str, &str)> = None;
+    for (i, m) in items.iter().enumerate() {
+        let key = (m.scope.as_str(), m.memory_type.as_str());
+        if group != Some(key) {
+            group = Some(key);
+            let count = items
+                .iter()
+                .filter(|o| (o.scope.as_str(), o.memory_type.as_str()) == key)
+                .count();
+            lines.push(Line::from(Span::styled(
+                format!("{} · {} ({})", key.0, key.1, count),
+                Style::default().fg(pal.accent).bold(),
+            )));
+        }
+        if i == app.memories_selected {
+            selected_line = lines.len();
+        }
+        let tags = if m.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  #{}", m.tags.join(" #"))
+        };
+        let content_w = list_w.saturating_sub(UnicodeWidthStr::width(tags.as_str()) + 8);
+        let content = truncate_line(&m.content.replace('\n', " "), content_w);
+        let (content_style, tags_style) = if i == app.memories_selected {
+            (pal.selection(), pal.selection())
+        } else {
+            (Style::default().fg(pal.fg), pal.meta())
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:.2}  ", m.importance), pal.meta()),
+            Span::styled(content, content_style),
+            Span::styled(tags, tags_style),
+        ]));
+    }
+    // Keep the selection visible in long lists.
+    let list_h = chunks[1].height as usize;
+    let start = (selected_line + 1).saturating_sub(list_h);
+    let visible: Vec<Line> = lines.into_iter().skip(start).take(list_h).collect();
+    frame.render_widget(Paragraph::new(visible), chunks[1]);
+
+    if let Some(edit) = &app.memory_edit {
+        let edit_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Plain)
+            .border_style(pal.border_style())
+            .title(Span::styled(" Edit ", pal.meta()));
+        let edit_inner = edit_block.inner(chunks[2]);
+        frame.render_widget(edit_block, chunks[2]);
+        let fields = [
+            (MemoryField::Content, "Content", edit.content.as_str()),
+            (MemoryField::Tags, "Tags", edit.tags.as_str()),
+            (
+                MemoryField::Importance,
+                "Importance",
+                edit.importance.as_str(),
+            ),
+        ];
+        let label_w = 12;
+        let value_w = (edit_inner.width as usize).saturating_sub(label_w + 1);
+        let mut edit_lines = Vec::new();
+        for (row, (field, label, value)) in fields.into_iter().enumerate() {
+            let focused = field == edit.field;
+            // The focused value shows its end, where typing happens.
+            let shown: String = if focused {
+                let chars: Vec<char> = value.chars().collect();
+                chars[chars.len().saturating_sub(value_w)..]
+                    .iter()
+                    .collect()
+            } else {
+                truncate_line(value, value_w)
+            };
+            if focused {
+                frame.set_cursor_position((
+                    edit_inner.x + label_w as u16 + UnicodeWidthStr::width(shown.as_str()) as u16,
+                    edit_inner.y + row as u16,
+                ));
+            }
+            let label_style = if focused {
+                Style::default().fg(pal.accent).bold()
+            } else {
+                pal.meta()
+            };
+            edit_lines.push(Line::from(vec![
+                Span::styled(format!("{:<width$}", label, width = label_w), label_style),
+                Span::styled(shown, Style::default().fg(pal.fg)),
+            ]));
+        }
+        frame.render_widget(Paragraph::new(edit_lines), edit_inner);
+    }
+
+    let hints = match app.memories_status.as_deref() {
+        Some(status) => Span::styled(format!(" {}", status), Style::default().fg(pal.warn)),
+        None if app.memory_edit.is_some() => Span::styled(
+            " Tab next field • Enter save • Esc cancel • tags comma-separated, importance 0–1",
+            pal.meta(),
+        ),
+        None => Span::styled(
+            format!(
+                " {} memories • Enter edit • Delete forget • Esc close • type to filter",
+                items.len()
+            ),
+            pal.meta(),
+        ),
+    };
+    frame.render_widget(Paragraph::new(Line::from(hints)), chunks[3]);
+}
+
+fn render_copy_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(72, 60, frame.area());
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(pal.border_style())
+        .title(Span::styled(
+            " Copy ",
+            Style::default().fg(pal.accent).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(4), Constraint::Length(1)])
+        .split(inner);
+    let content = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(45),
+            Constraint::Length(1),
+            Constraint::Percentage(55),
+        ])
+        .split(chunks[0]);
+
+    frame.render_widget(
+        Paragraph::new("│".repeat(content[1].height as usize)).style(pal.border_style()),
+        content[1],
+    );
+
+    // Keep the selection visible in long transcripts.
+    let list_h = content[0].height as usize;
+    let start = (app.copy_selected + 1).saturating_sub(list_h);
+    let list_w = content[0].width as usize;
+    let lines: Vec<Line> = app
+        .copy_targets
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(list_h)
+        .map(|(i, target)| {
+            let style = if i == app.copy_selected {
+                pal.selection()
+            } else {
+                Style::default().fg(pal.fg)
+            };
+            Line::from(Span::styled(truncate_line(&target.label, list_w), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), content[0]);
+
+    let preview = app
+        .copy_targets
+        .get(app.copy_selected)
+        .map(|t| t.content.as_str())
+        .unwrap_or("");
+    frame.render_widget(
+        Paragraph::new(preview)
+            .style(Style::default().fg(ui_theme.markdown.text))
+            .wrap(Wrap { trim: false }),
+        content[2],
+    );
+
+    let hints = Paragraph::new(" Enter/y copy • Esc close • j/k select").style(pal.meta());
+    frame.render_widget(hints, chunks[1]);
+}
+
+const STATS_BAR_WIDTH: usize = 20;
+
+fn render_stats_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(80, 75, frame.area());
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(pal.border_style())
+        .title(Span::styled(
+            " Session stats ",
+            Style::default().fg(pal.accent).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(inner);
+
+    let session = app.session_stats();
+    let turns = app.turn_stats();
+    let llm_time: Duration = turns.iter().map(|t| t.llm).sum();
+    let tool_time: Duration = turns.iter().map(|t| t.tools).sum();
+    let calls = session.tool_calls();
+    let failed: usize = session.tools.values().map(|c| c.failed).sum();
+    let heading = Style::default().fg(pal.accent).bold();
+    let text = Style::default().fg(pal.fg);
+
+    let mut lines = vec![
+        Line::from(Span::styled("Summary", heading)),
+        Line::from(vec![
+            Span::styled(format!("  {} turns  ", session.turns), text),
+            Span::styled(format!("LLM {}", format_duration(llm_time)), text),
+            Span::styled(" · ", pal.meta()),
+            Span::styled(format!("tools {}", format_duration(tool_time)), text),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                format!(
+                    "  {} in / {} out tokens",
+                    compact_count(session.usage.prompt_tokens),
+                    compact_count(session.usage.completion_tokens)
+                ),
+                text,
+            ),
+            Span::styled(
+                if session.usage.cached_tokens > 0 {
+                    format!(
+                        " · {} cached",
+                        percent(
+                            session.usage.cached_tokens as usize,
+                            session.usage.prompt_tokens as usize
+                        )
+                    )
+                } else {
+                    String::new()
+                },
+                pal.meta(),
+            ),
+            Span::styled(
+                match session.usage.cost {
+                    Some(cost) => format!(" · ${:.4}", cost),
+                    None => String::new(),
+                },
+                pal.meta(),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled(format!("  {} tool calls, ", calls), text),
+            Span::styled(
+                format!("{} failed ({})", failed, percent(failed, calls)),
+                Style::default().fg(if failed > 0 { pal.err } else { pal.fg }),
+            ),
+        ]),
+        Line::default(),
+        Line::from(Span::styled("Turns", heading)),
+        Line::from(Span::styled(
+            format!(
+                "  {:>4}  {:>8}  {:>8}  {:<w$}  {:>6}  {:>5}",
+                "#",
+                "LLM",
+                "tools",
+                "LLM ▮ tools ▯",
+                "tokens",
+                "calls",
+                w = STATS_BAR_WIDTH
+            ),
+            pal.meta(),
+        )),
+    ];
+    if turns.is_empty() {
+        lines.push(Line::from(Span::styled("  No turns yet.", pal.meta())));
+    }
+    for turn in &turns {
+        let total = turn.total().as_secs_f64();
+        let llm_cells = if total > 0.0 {
+            ((turn.llm.as_secs_f64() / total) * STATS_BAR_WIDTH as f64).round() as usize
+        } else {
+            0
+        };
+        let tool_cells = if total > 0.0 {
+            STATS_BAR_WIDTH - llm_cells
+        } else {
+            0
+        };
+        let tool_count = turn.tool_calls.ok + turn.tool_calls.failed;
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!(
+                    "  {:>4}  {:>8}  {:>8}  ",
+                    turn.turn,
+                    format_duration(turn.llm),
+                    format_duration(turn.tools)
+                ),
+                text,
+            ),
+            Span::styled("▮".repeat(llm_cells), Style::default().fg(pal.accent)),
+            Span::styled("▯".repeat(tool_cells), Style::default().fg(pal.warn)),
+            Span::raw(" ".repeat(STATS_BAR_WIDTH - llm_cells - tool_cells)),
+            Span::styled(
+                format!(
+                    "  {:>6}  {:>5}",
+                    compact_count(turn.usage.prompt_tokens + turn.usage.completion_tokens),
+                    tool_count
+                ),
+                text,
+            ),
+            Span::styled(
+                if turn.tool_calls.failed > 0 {
+                    format!(" ({} failed)", turn.tool_calls.failed)
+                } else {
+                    String::new()
+                },
+                Style::default().fg(pal.err),
+            ),
+        ]));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled("Tools", heading)));
+    lines.push(Line::from(Span::styled(
+        format!(
+            "  {:<18}  {:>5}  {:>6}  {:>6}  {:>8}",
+            "tool", "calls", "failed", "fail %", "avg"
+        ),
+        pal.meta(),
+    )));
+    if session.tools.is_empty() {
+        lines.push(Line::from(Span::styled("  No tool calls yet.", pal.meta())));
+    }
+    for (tool, counts) in &session.tools {
+        let n = counts.ok + counts.failed;
+        let avg = session
+            .tool_time
+            .get(tool)
+            .map(|t| *t / n.max(1) as u32)
+            .unwrap_or_default();
+        let style = if counts.failed > 0 {
+            Style::default().fg(pal.err)
+        } else {
+            text
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "  {:<18}  {:>5}  {:>6}  {:>6}  {:>8}",
+                truncate_line(tool, 18),
+                n,
+                counts.failed,
+                percent(counts.failed, n),
+                format_duration(avg)
+            ),
+            style,
+        )));
+    }
+
+    let max_scroll = lines.len().saturating_sub(chunks[0].height as usize);
+    let scroll = app.stats_scroll.min(max_scroll) as u16;
+    frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), chunks[0]);
+
+    let hints = Paragraph::new(" Esc close • j/k scroll").style(pal.meta());
+    frame.render_widget(hints, chunks[1]);
+}
+
+fn percent(part: usize, whole: usize) -> String {
+    if whole == 0 {
+        return "-".to_string();
+    }
+    format!("{:.0}%", part as f64 * 100.0 / whole as f64)
+}
+
+fn render_approval_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let Some(request) = app.pending_approval.as_ref() else {
+        return;
+    };
+    let pal = ui_theme.palette;
+    let (title, accent) = if request.destructive {
+        (" Dry run: destructive command ", pal.err)
+    } else {
+        (" Run this command? ", pal.warn)
+    };
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(72);
+    let height = (request.reasons.len() as u16 + 6).min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(accent))
+        .title(Span::styled(title, Style::default().fg(accent).bold()));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let mut lines = vec![
+        Line::from(Span::styled(
+            truncate_line(&format!("$ {}", request.command), w),
+            Style::default().fg(pal.fg).bold(),
+        )),
+        Line::from(""),
+    ];
+    for reason in &request.reasons {
+        lines.push(Line::from(Span::styled(
+            truncate_line(&format!("• {}", reason), w),
+            Style::default().fg(pal.fg_dim),
+        )));
+    }
+    lines.push(Line::from(""));
+    let keys = request.keys.join("`, `");
+    let hints = if request.destructive {
+        " Not run yet • y run it • n/Esc decline".to_string()
+    } else if app.approval_always_armed {
+        format!(
+            " Enter: always allow `{}` (saved to config) • any other key: back",
+            keys
+        )
+    } else {
+        format!(" y run once • a always allow `{}` • n/Esc decline", keys)
+    };
+    lines.push(Line::from(Span::styled(
+        truncate_line(&hints, w),
+        pal.meta(),
+    )));
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_quit_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let blockers = app.quit_blockers();
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(60);
+    let height = (blockers.len() as u16 + 4).min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.warn))
+        .title(Span::styled(
+            " Quit anyway? ",
+            Style::default().fg(pal.warn).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let mut lines: Vec<Line> = blockers
+        .iter()
+        .map(|line| {
+            Line::from(Span::styled(
+                truncate_line(line, w),
+                Style::default().fg(pal.fg),
+            ))
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        truncate_line(" y quit • n/Esc keep working", w),
+        pal.meta(),
+    )));
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_reauth_popup(frame: &mut Frame, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(64);
+    let height = 6.min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.warn))
+        .title(Span::styled(
+            " Sign in again? ",
+            Style::default().fg(pal.warn).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let lines = vec![
+        Line::from(Span::styled(
+            truncate_line("Your ChatGPT sign-in for the codex provider expired.", w),
+            Style::default().fg(pal.fg),
+        )),
+        Line::from(Span::styled(
+            truncate_line("Signing in opens the browser; `lorikeet login` works too.", w),
+            Style::default().fg(pal.fg),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            truncate_line(" y sign in • n/Esc later (/login)", w),
+            pal.meta(),
+        )),
+    ];
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_turn_limit_popup(
+    frame: &mut Frame,
+    prompt: &TurnLimitPrompt,
+    ui_theme: &theme::UiTheme,
+) {
+    let pal = ui_theme.palette;
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(72);
+    let height = 7.min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.warn))
+        .title(Span::styled(
+            " Keep going? ",
+            Style::default().fg(pal.warn).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let files = match prompt.files.len() {
+        0 => "No files changed yet.".to_string(),
+        n => format!(
+            "{} file{} changed: {}",
+            n,
+            if n == 1 { "" } else { "s" },
+            prompt.files.join(", ")
+        ),
+    };
+    let lines = vec![
+        Line::from(Span::styled(
+            truncate_line(
+                &format!(
+                    "This turn reached {}: {} tool rounds in {}.",
+                    prompt.limit,
+                    prompt.rounds,
+                    format_duration(prompt.elapsed)
+                ),
+                w,
+            ),
+            Style::default().fg(pal.fg),
+        )),
+        Line::from(Span::styled(
+            truncate_line(&files, w),
+            Style::default().fg(pal.fg),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            truncate_line(" y/Enter continue • n/Esc stop here", w),
+            pal.meta(),
+        )),
+    ];
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_restore_popup(frame: &mut Frame, prompt: &RestorePrompt, ui_theme: &theme::UiTheme) {
+    use crate::checkpoints::RestoreChange;
+
+    let pal = ui_theme.palette;
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(90);
+    let height = area.height.saturating_sub(4).min(24);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let name = prompt
+        .meta
+        .name
+        .as_deref()
+        .filter(|n| !n.trim().is_empty())
+        .map(|n| format!(" ({})", n))
+        .unwrap_or_default();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.warn))
+        .title(Span::styled(
+            format!(" Restore {}{}? ", prompt.meta.id, name),
+            Style::default().fg(pal.warn).bold(),
+        ))
+        .title_bottom(Span::styled(
+            " y/Enter restore • n/Esc cancel • ↑/↓ scroll ",
+            pal.meta(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let mut lines: Vec<Line> = Vec::new();
+    match &prompt.preview {
+        Err(e) => {
+            lines.push(Line::from(Span::styled(
+                truncate_line(&format!("Couldn't preview the changes: {}", e), w),
+                Style::default().fg(pal.err),
+            )));
+            lines.push(Line::from(Span::styled(
+                "Restoring may still overwrite uncommitted work.",
+                Style::default().fg(pal.fg),
+            )));
+        }
+        Ok(preview) if preview.files.is_empty() => {
+            lines.push(Line::from(Span::styled(
+                "The working tree already matches this checkpoint; only the session is rewound.",
+                Style::default().fg(pal.fg),
+            )));
+        }
+        Ok(preview) => {
+            let (plus, minus) = preview.line_totals();
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!(
+                        "{} changed, {} added, {} deleted ",
+                        preview.count(RestoreChange::Modified),
+                        preview.count(RestoreChange::Added),
+                        preview.count(RestoreChange::Deleted),
+                    ),
+                    Style::default().fg(pal.fg),
+                ),
+                Span::styled(format!("+{}", plus), Style::default().fg(pal.ok)),
+                Span::raw(" "),
+                Span::styled(format!("-{}", minus), Style::default().fg(pal.err)),
+            ]));
+            lines.push(Line::from(Span::styled(
+                "A pre-restore checkpoint of the current state is taken first.",
+                pal.meta(),
+            )));
+            lines.push(Line::from(""));
+            let rows = (inner.height as usize).saturating_sub(lines.len());
+            let scroll = prompt.scroll.min(preview.files.len().saturating_sub(rows));
+            for file in preview.files.iter().skip(scroll).take(rows) {
+                let (mark, color) = match file.change {
+                    RestoreChange::Modified => ("~ ", pal.warn),
+                    RestoreChange::Added => ("+ ", pal.ok),
+                    RestoreChange::Deleted => ("- ", pal.err),
+                };
+                let delta = match file.lines {
+                    Some((a, r)) => format!(" +{} -{}", a, r),
+                    None => " (binary)".to_string(),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(mark, Style::default().fg(color).bold()),
+                    Span::raw(truncate_line(&file.path, w.saturating_sub(2 + delta.len()))),
+                    Span::styled(delta, pal.meta()),
+                ]));
+            }
+        }
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_trust_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let summary = app.trust_summary();
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(80);
+    let height = (summary.len() as u16 + 10).min(area.height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(width)) / 2,
+        area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.warn))
+        .title(Span::styled(
+            " Trust this workspace? ",
+            Style::default().fg(pal.warn).bold(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let w = inner.width as usize;
+    let mut lines = vec![
+        Line::from(Span::styled(
+            truncate_line(&app.workspace_root_display(), w),
+            Style::default().fg(pal.fg).bold(),
+        )),
+        Line::from(""),
+    ];
+    for line in &summary {
+        lines.push(Line::from(Span::styled(
+            truncate_line(line, w),
+            Style::default().fg(pal.fg_dim),
+        )));
+    }
+    lines.push(Line::from(""));
+    for (level, label) in [
+        (TrustLevel::Trusted, "Trust this workspace"),
+        (TrustLevel::Restricted, "Keep it restricted"),
+    ] {
+        let selected = app.trust_choice == Some(level);
+        let style = if selected {
+            Style::default().fg(pal.warn).bold().reversed()
+        } else {
+            Style::default().fg(pal.fg)
+        };
+        let marker = if selected { "›" } else { " " };
+        lines.push(Line::from(Span::styled(
+            truncate_line(&format!(" {} {} ", marker, label), w),
+            style,
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        truncate_line(
+            " ↑/↓ choose • Enter confirm • Esc restricted this session only",
+            w,
+        ),
+        pal.meta(),
+    )));
+    frame.render_widget(Paragraph::new(lines), inner);
 }
 
 fn render_plan_popup(frame: &mut Frame, app: &mut App, ui_theme: &theme::UiTheme) {
@@ -1023,7 +2272,7 @@ fn render_tool_group_inline(
     let status = if any_running {
         format!("{} running…", tool_spinner)
     } else {
-        "● done".to_string()
+        format!("{} done", theme::STATUS_OK)
     };
 
     out.push(Line::from(vec![
@@ -1067,8 +2316,8 @@ fn render_tool_trace_item(
     let pal = ui_theme.palette;
     let (status_indicator, status_color) = match tool.status {
         ToolStatus::Running => (tool_spinner, pal.warn),
-        ToolStatus::Success => ("●", pal.ok),
-        ToolStatus::Error => ("●", pal.err),
+        ToolStatus::Success => (theme::STATUS_OK, pal.ok),
+        ToolStatus::Error => (theme::STATUS_ERR, pal.err),
     };
 
     let elapsed = tool.elapsed();
@@ -1240,12 +2489,22 @@ fn render_tool_trace_item(
     }
 
     let ext = if tool.tool == "read_file" {
-        std::path::Path::new(&tool.target)
-            .extension()
-            .and_then(|s| s.to_str())
+        // Detect the language (config overrides, shebangs) so extensionless scripts are
+        // highlighted too; otherwise keep the raw extension (md, toml, ...).
+        let path = std::path::Path::new(&tool.target);
+        let first_line = tool.output.lines().next().unwrap_or("");
+        app.languages
+            .detect(path, first_line)
+            .extensions()
+            .first()
+            .copied()
+            .or_else(|| path.extension().and_then(|s| s.to_str()))
     } else {
         None
     };
+    // Highlight the tail as one snippet so constructs spanning lines keep their colours.
+    let highlighted =
+        ext.and_then(|e| highlight::highlight_lines(&tail.join("\n"), e, ui_theme.syntax));
 
     for (i, l) in tail.iter().enumerate() {
         let is_first = i == 0;
@@ -1264,7 +2523,10 @@ fn render_tool_trace_item(
             let style = theme::style_for_filename_with_theme(&text, &app.config, ui_theme);
             spans.push(Span::styled(text, style));
         } else if tool.tool == "read_file" {
-            spans.extend(highlight_line_for_ext(&text, ext, ui_theme));
+            match highlighted.as_ref().and_then(|h| h.get(i)) {
+                Some(segments) => spans.extend(clip_segments(segments, &text)),
+                None => spans.extend(highlight_line_for_ext(&text, ext, ui_theme)),
+            }
         } else {
             let base_style = if tool.status == ToolStatus::Error {
                 Style::default().fg(pal.err)
@@ -1304,7 +2566,7 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
         .title(title)
         .title_bottom(render_indexing_status(
             &app.indexing_status,
-            app.indexing_spinner_frame,
+            app.spinner_frame(INDEXING_SPINNER.len()),
         ));
     let inner = block.inner(area);
     frame.render_widget(block, area);
@@ -1315,10 +2577,31 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
     lines.push(Line::from(vec![
         Span::styled("Workspace: ", pal.meta()),
         Span::raw(truncate_to_width(
-            &app.workspace_root_display(),
+            &app.active_root.display().to_string(),
             width.saturating_sub(11),
         )),
     ]));
+    if !app.workspaces.is_empty() {
+        // Every registered root by name; the active one highlighted.
+        let mut spans = vec![Span::styled("Roots: ", pal.meta())];
+        let mut used = 7;
+        for (i, root) in app.all_roots().iter().enumerate() {
+            let name = crate::workspaces::name(root);
+            let sep = if i == 0 { "" } else { ", " };
+            if used + sep.len() + name.width() > width {
+                spans.push(Span::styled(format!("{}…", sep), pal.meta()));
+                break;
+            }
+            used += sep.len() + name.width();
+            spans.push(Span::raw(sep));
+            if *root == app.active_root {
+                spans.push(Span::styled(name, Style::default().bold()));
+            } else {
+                spans.push(Span::styled(name, pal.meta()));
+            }
+        }
+        lines.push(Line::from(spans));
+    }
     lines.push(Line::from(vec![
         Span::styled("Model: ", pal.meta()),
         Span::raw(truncate_to_width(&app.model, width.saturating_sub(7))),
@@ -1346,18 +2629,96 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
         Span::raw(if sandbox_enabled { "on" } else { "off" }),
     ]));
 
-    if !app.verify_suggestions.is_empty() {
+    if let Some(plan) = &app.plan_draft {
+        let steps = plan.steps();
+        if !steps.is_empty() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                Span::styled("Plan", Style::default().fg(Color::Reset).bold()),
+                Span::styled(
+                    format!(" {}/{}", plan.completed_steps.len(), steps.len()),
+                    pal.meta(),
+                ),
+            ]));
+            for (i, step) in steps.iter().enumerate() {
+                let n = i + 1;
+                let (glyph, style) = if plan.completed_steps.contains(&n) {
+                    (theme::STATUS_OK, theme::status_style(&app.config, pal.ok))
+                } else if app.plan_steps_running.contains(&n) {
+                    ("◐", theme::status_style(&app.config, pal.warn))
+                } else {
+                    ("○", pal.meta())
+                };
+                let label = format!(" {}. ", n);
+                let title = step.lines().next().unwrap_or_default();
+                lines.push(Line::from(vec![
+                    Span::styled(glyph, style),
+                    Span::styled(label.clone(), pal.meta()),
+                    Span::raw(truncate_to_width(
+                        title,
+                        width.saturating_sub(1 + label.len()),
+                    )),
+                ]));
+            }
+            lines.push(Line::from(Span::styled("Run a step: /go <n>", pal.meta())));
+        }
+    }
+
+    if !app.verify_suggestions.is_empty() || !app.verify_history.is_empty() {
         lines.push(Line::from(""));
-        lines.push(Line::from(vec![
-            Span::styled("Verify: ", pal.meta()),
-            Span::raw(truncate_to_width(
-                &app.verify_suggestions[0].command,
-                width.saturating_sub(8),
-            )),
-        ]));
+        if let Some(suggestion) = app.verify_suggestions.first() {
+            lines.push(Line::from(vec![
+                Span::styled("Verify: ", pal.meta()),
+                Span::raw(truncate_to_width(
+                    &suggestion.command,
+                    width.saturating_sub(8),
+                )),
+            ]));
+        }
+        if !app.verify_history.is_empty() {
+            lines.push(verify_history_line(
+                &app.verify_history,
+                width,
+                pal,
+                theme::no_color_status(&app.config),
+            ));
+        }
         lines.push(Line::from(Span::styled("Run: /verify", pal.meta())));
     }
 
+    let processes = crate::processes::list();
+    if !processes.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Processes",
+            Style::default().fg(Color::Reset).bold(),
+        )));
+        // Running ones first, then the most recently ended few.
+        let (running, ended): (Vec<_>, Vec<_>) = processes.iter().partition(|p| p.running());
+        for p in running.iter().chain(ended.iter().rev().take(3)) {
+            let (glyph, color, state) = match p.exit {
+                None => (
+                    "◐",
+                    pal.warn,
+                    crate::stats::format_duration(p.started.elapsed()),
+                ),
+                Some(0) => (theme::STATUS_OK, pal.ok, "exited".to_string()),
+                Some(code) => (theme::STATUS_ERR, pal.err, format!("exit {}", code)),
+            };
+            let label = format!(" {} ", p.id);
+            let state = format!(" {}", state);
+            lines.push(Line::from(vec![
+                Span::styled(glyph, theme::status_style(&app.config, color)),
+                Span::styled(label.clone(), pal.meta()),
+                Span::raw(truncate_to_width(
+                    &p.command,
+                    width.saturating_sub(1 + label.len() + state.len()),
+                )),
+                Span::styled(state, pal.meta()),
+            ]));
+        }
+    }
+
     if let Some(cp) = &app.last_checkpoint {
         lines.push(Line::from(""));
         lines.push(Line::from(vec![
@@ -1378,6 +2739,20 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
         ]));
     }
 
+    if !app.bookmarks.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Bookmarks",
+            Style::default().fg(Color::Reset).bold(),
+        )));
+        for b in &app.bookmarks {
+            lines.push(Line::from(vec![
+                Span::styled("★ ", Style::default().fg(pal.accent)),
+                Span::raw(truncate_to_width(b, width.saturating_sub(2))),
+            ]));
+        }
+    }
+
     lines.push(Line::from(""));
     lines.push(Line::from(Span::styled(
         "Recent files",
@@ -1386,10 +2761,64 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
     if app.recent_files.is_empty() {
         lines.push(Line::from(Span::styled("(none yet)", pal.meta())));
     } else {
-        for p in app.recent_files.iter().take(10) {
-            lines.push(Line::from(vec![
-                Span::raw("- "),
+        let focused = app.active_pane == Pane::Context;
+        let selected = app.selected_recent_file();
+        for p in app.recent_files.iter().take(RECENT_FILES_SHOWN) {
+            let pinned = app.pinned_files.contains(p);
+            let line = Line::from(vec![
+                if pinned {
+                    Span::styled("* ", Style::default().fg(pal.accent).bold())
+                } else {
+                    Span::raw("- ")
+                },
                 Span::raw(truncate_to_width(p, width.saturating_sub(2))),
+            ]);
+            lines.push(if focused && selected == Some(p) {
+                line.style(pal.selection())
+            } else {
+                line
+            });
+        }
+        if focused {
+            lines.push(Line::from(Span::styled(
+                "↑/↓ select · Enter view · p pin (*)",
+                pal.meta(),
+            )));
+        }
+    }
+
+    let touched = app.most_touched_files(8);
+    if !touched.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Most-touched files",
+            Style::default().fg(Color::Reset).bold(),
+        )));
+        const BAR: usize = 6;
+        let max = touched.iter().map(|(_, n, _)| *n).max().unwrap_or(0).max(1);
+        for (path, n, before) in &touched {
+            // Hot colours flag files edited over and over in this session.
+            let heat = match n {
+                0..=2 => pal.accent,
+                3..=4 => pal.warn,
+                _ => pal.err,
+            };
+            let filled = (n * BAR).div_ceil(max);
+            let before = if *before > 0 {
+                format!(" +{}", before)
+            } else {
+                String::new()
+            };
+            let label = format!("{:>2}{} ", n, before);
+            lines.push(Line::from(vec![
+                Span::styled("█".repeat(filled), Style::default().fg(heat)),
+                Span::styled("░".repeat(BAR - filled), pal.meta()),
+                Span::raw(" "),
+                Span::styled(label.clone(), pal.meta()),
+                Span::raw(truncate_to_width(
+                    path,
+                    width.saturating_sub(BAR + 1 + label.len()),
+                )),
             ]));
         }
     }
@@ -1398,6 +2827,322 @@ fn render_context_sidebar(frame: &mut Frame, app: &mut App, area: Rect, ui_theme
     frame.render_widget(widget, inner);
 }
 
+/// Right pane while a search hit is open (Ctrl+G): the file with line numbers, scrolled
+/// to the hit, which is highlighted.
+fn render_file_view(frame: &mut Frame, app: &App, area: Rect, ui_theme: &theme::UiTheme) {
+    let Some(view) = app.file_view.as_ref() else {
+        return;
+    };
+    let pal = ui_theme.palette;
+    let marker = if app.active_pane == Pane::Context {
+        "*"
+    } else {
+        " "
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(pal.border_style())
+        .title(format!(" {} {}:{} ", marker, view.path, view.line))
+        .title_bottom(Span::styled(" Ctrl+G next hit · Esc close ", pal.meta()));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    frame.render_widget(
+        Paragraph::new(file_view_lines(app, view, inner, ui_theme)),
+        inner,
+    );
+}
+
+/// Read-only viewer for a file picked from the sidebar's recent files.
+fn render_recent_file_popup(frame: &mut Frame, app: &App, ui_theme: &theme::UiTheme) {
+    let Some(view) = app.recent_file_popup.as_ref() else {
+        return;
+    };
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(80, 80, frame.area());
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let pinned = app
+        .selected_recent_file()
+        .is_some_and(|p| app.pinned_files.contains(p));
+    let hint = if pinned {
+        " ↑/↓ PgUp/PgDn scroll · p unpin · Esc close "
+    } else {
+        " ↑/↓ PgUp/PgDn scroll · p pin for next message · Esc close "
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.accent))
+        .title(Span::styled(
+            format!(" {}{} ", view.path, if pinned { " (pinned)" } else { "" }),
+            Style::default().fg(pal.accent).bold(),
+        ))
+        .title_bottom(Span::styled(hint, pal.meta()));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+    frame.render_widget(
+        Paragraph::new(file_view_lines(app, view, inner, ui_theme)),
+        inner,
+    );
+}
+
+fn render_text_popup(frame: &mut Frame, app: &mut App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let popup_area = centered_rect(80, 80, frame.area());
+    // The last page ends at the last line; `/logs` stays there while following.
+    let rows = popup_area.height.saturating_sub(2) as usize;
+    let follow = app.log_tail.as_ref().is_some_and(|t| t.follow);
+    if let Some(view) = app.text_popup.as_mut() {
+        let last_page = view.lines.len().saturating_sub(rows);
+        view.scroll = if follow {
+            last_page
+        } else {
+            view.scroll.min(last_page)
+        };
+    }
+    let Some(view) = app.text_popup.as_ref() else {
+        return;
+    };
+    frame.render_widget(Clear, popup_area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(
+            Fill::new(Style::default().bg(pal.bg).fg(pal.fg)),
+            popup_area,
+        );
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.accent))
+        .title(Span::styled(
+            format!(" {} ", view.path),
+            Style::default().fg(pal.accent).bold(),
+        ))
+        .title_bottom(Span::styled(
+            if app.log_tail.is_some() {
+                " ↑/↓ PgUp/PgDn scroll · g/G top/follow · LORIKEET_LOG sets the level · Esc close "
+            } else {
+                " ↑/↓ PgUp/PgDn scroll · g/G top/end · Esc close "
+            },
+            pal.meta(),
+        ));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+    frame.render_widget(
+        Paragraph::new(file_view_lines(app, view, inner, ui_theme)),
+        inner,
+    );
+}
+
+/// Full-screen pager over a tool call's whole output, following the end while it runs.
+fn render_tool_pager(frame: &mut Frame, app: &mut App, ui_theme: &theme::UiTheme) {
+    let pal = ui_theme.palette;
+    let (title, running) = match app.tool_pager.as_ref().and_then(|pager| {
+        app.tool_outputs
+            .iter()
+            .rev()
+            .find(|t| t.call_id == pager.call_id)
+    }) {
+        Some(tool) => (
+            format!(" {} {} ", tool.tool, tool.args_summary),
+            tool.status == ToolStatus::Running,
+        ),
+        None => (" tool output ".to_string(), false),
+    };
+    let Some(pager) = app.tool_pager.as_mut() else {
+        return;
+    };
+
+    let area = frame.area();
+    frame.render_widget(Clear, area);
+    if pal.bg != Color::Reset {
+        frame.render_widget(Fill::new(Style::default().bg(pal.bg).fg(pal.fg)), area);
+    }
+
+    let count = pager.line_count();
+    let hint = match &pager.search_prompt {
+        Some(prompt) => format!(" /{}█ · Enter search · Esc cancel ", prompt),
+        None => " j/k PgUp/PgDn scroll · g/G top/end · / search · n/N next/prev · w wrap · l numbers · [/] prev/next call · q close ".to_string(),
+    };
+    let mut position = Vec::new();
+    if let Some(query) = &pager.query {
+        position.push(match pager.match_line {
+            Some(line) => format!("\"{}\" line {}", query, line + 1),
+            None => format!("\"{}\" not found", query),
+        });
+    }
+    match (running, pager.follow) {
+        (true, true) => position.push("running · following".to_string()),
+        (true, false) => position.push("running".to_string()),
+        _ => {}
+    }
+    position.push(format!("{} lines", count));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(pal.accent))
+        .title(Span::styled(
+            truncate_to_width(&title, area.width.saturating_sub(4) as usize),
+            Style::default().fg(pal.accent).bold(),
+        ))
+        .title_bottom(Span::styled(hint, pal.meta()))
+        .title_bottom(
+            Line::from(Span::styled(
+                format!(" {} ", position.join(" · ")),
+                pal.meta(),
+            ))
+            .right_aligned(),
+        );
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let height = inner.height as usize;
+    let gutter = if pager.line_numbers {
+        count.max(1).to_string().len() + 1
+    } else {
+        0
+    };
+    let text_width = (inner.width as usize).saturating_sub(gutter).max(1);
+    let wrap = pager.wrap;
+    let rows = |line: &str| {
+        if wrap {
+            UnicodeWidthStr::width(line).max(1).div_ceil(text_width)
+        } else {
+            1
+        }
+    };
+    pager.page = height;
+    if pager.follow {
+        // As many lines from the end as fit.
+        let mut used = 0;
+        let mut start = count;
+        while start > 0 {
+            let needed = rows(&pager.lines[start - 1]);
+            if used > 0 && used + needed > height {
+                break;
+            }
+            used += needed;
+            start -= 1;
+        }
+        pager.scroll = start;
+    }
+    let start = pager.scroll.min(count);
+
+    let mut lines = Vec::new();
+    let mut used = 0;
+    for (i, raw) in pager.lines[start..count].iter().enumerate() {
+        if used >= height {
+            break;
+        }
+        let text = raw.trim_end_matches('\r').replace('\t', "    ");
+        used += rows(&text);
+        let mut line = Line::from(Span::styled(text.clone(), Style::default().fg(pal.fg)));
+        if let Some(query) = &pager.query {
+            let style = if pager.match_line == Some(start + i) {
+                pal.selection().bold()
+            } else {
+                Style::default().fg(pal.warn).bold().underlined()
+            };
+            let ranges = crate::render_store::match_ranges(&text, query);
+            line = restyle_ranges(line, &ranges, style);
+        }
+        if pager.line_numbers {
+            line.spans.insert(
+                0,
+                Span::styled(
+                    format!("{:>width$} ", start + i + 1, width = gutter - 1),
+                    pal.meta(),
+                ),
+            );
+        }
+        lines.push(line);
+    }
+    if count == 0 {
+        let empty = if running {
+            "(no output yet)"
+        } else {
+            "(no output)"
+        };
+        lines.push(Line::from(Span::styled(empty, pal.meta())));
+    }
+
+    let mut body = Paragraph::new(lines);
+    if pager.wrap {
+        body = body.wrap(Wrap { trim: false });
+    }
+    frame.render_widget(body, inner);
+}
+
+/// The visible window of `view` with line numbers and syntax highlighting; the line it
+/// was opened at is highlighted.
+fn file_view_lines(
+    app: &App,
+    view: &FileView,
+    inner: Rect,
+    ui_theme: &theme::UiTheme,
+) -> Vec<Line<'static>> {
+    let pal = ui_theme.palette;
+    let path = std::path::Path::new(&view.path);
+    let first_line = view.lines.first().map(String::as_str).unwrap_or("");
+    let ext = app
+        .languages
+        .detect(path, first_line)
+        .extensions()
+        .first()
+        .copied()
+        .or_else(|| path.extension().and_then(|s| s.to_str()));
+
+    let start = view.scroll.min(view.lines.len());
+    let end = (start + inner.height as usize).min(view.lines.len());
+    let window: Vec<String> = view.lines[start..end]
+        .iter()
+        .map(|l| l.replace('\t', "    "))
+        .collect();
+    let highlighted =
+        ext.and_then(|e| highlight::highlight_lines(&window.join("\n"), e, ui_theme.syntax));
+
+    let gutter = view.lines.len().max(1).to_string().len();
+    let text_width = (inner.width as usize).saturating_sub(gutter + 1);
+    window
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            let line_no = start + i + 1;
+            let is_hit = line_no == view.line;
+            let number_style = if is_hit {
+                Style::default().fg(pal.accent).bold()
+            } else {
+                pal.meta()
+            };
+            let mut spans = vec![Span::styled(
+                format!("{:>width$} ", line_no, width = gutter),
+                number_style,
+            )];
+
+            let text = truncate_to_width(l, text_width);
+            match highlighted.as_ref().and_then(|h| h.get(i)) {
+                Some(segments) => spans.extend(clip_segments(segments, &text)),
+                None => spans.extend(highlight_line_for_ext(&text, ext, ui_theme)),
+            }
+
+            let line = Line::from(spans);
+            if is_hit {
+                line.style(pal.selection())
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1442,6 +3187,8 @@ fn truncate_line(line: &str, width: usize) -> String {
     out
 }
 
+/// A line tree-sitter couldn't colour. Markdown gets its own styling; other languages
+/// without a grammar are shown as plain text rather than guessed at.
 fn highlight_line_for_ext(
     line: &str,
     ext: Option<&str>,
@@ -1449,8 +3196,35 @@ fn highlight_line_for_ext(
 ) -> Vec<Span<'static>> {
     match ext {
         Some("md") | Some("mdx") => highlight_markdown_line(line, ui_theme),
-        _ => highlight_code_line(line, ext, ui_theme),
+        _ => vec![Span::styled(
+            line.to_string(),
+            Style::default().fg(ui_theme.syntax.ident),
+        )],
+    }
+}
+
+/// Highlighted `segments` of a line, cut down to `text`, its `truncate_to_width` form.
+fn clip_segments(segments: &[(String, Color)], text: &str) -> Vec<Span<'static>> {
+    let whole: String = segments.iter().map(|(t, _)| t.as_str()).collect();
+    let (mut left, ellipsis) = match text.strip_suffix('…') {
+        Some(prefix) if whole != text => (prefix.len(), true),
+        _ => (text.len(), false),
+    };
+    let mut spans = Vec::new();
+    let mut color = Color::Reset;
+    for (t, c) in segments {
+        if left == 0 {
+            break;
+        }
+        let take = t.len().min(left);
+        spans.push(Span::styled(t[..take].to_string(), Style::default().fg(*c)));
+        color = *c;
+        left -= take;
     }
+    if ellipsis {
+        spans.push(Span::styled("…", Style::default().fg(color)));
+    }
+    spans
 }
 
 fn highlight_markdown_line(line: &str, ui_theme: &theme::UiTheme) -> Vec<Span<'static>> {
@@ -1508,86 +3282,33 @@ fn highlight_markdown_line(line: &str, ui_theme: &theme::UiTheme) -> Vec<Span<'s
     spans
 }
 
-fn highlight_code_line(
-    line: &str,
-    ext: Option<&str>,
-    ui_theme: &theme::UiTheme,
-) -> Vec<Span<'static>> {
-    let syn = ui_theme.syntax;
-    let mut spans = Vec::new();
-    let mut buf = String::new();
-    let mut i = 0;
-    let chars: Vec<char> = line.chars().collect();
-    let allow_hash_comment = matches!(
-        ext,
-        Some("py")
-            | Some("rb")
-            | Some("sh")
-            | Some("bash")
-            | Some("zsh")
-            | Some("yml")
-            | Some("yaml")
-            | Some("toml")
-    );
-    let allow_backtick = matches!(ext, Some("js") | Some("ts") | Some("jsx") | Some("tsx"));
-
-    while i < chars.len() {
-        let ch = chars[i];
-
-        // Comment start
-        if allow_hash_comment && ch == '#' {
-            if !buf.is_empty() {
-                spans.push(Span::styled(buf.clone(), Style::default().fg(syn.ident)));
-                buf.clear();
-            }
-            let rest: String = chars[i..].iter().collect();
-            spans.push(Span::styled(rest, Style::default().fg(syn.comment)));
-            return spans;
-        }
-        if ch == '/' && i + 1 < chars.len() && chars[i + 1] == '/' {
-            if !buf.is_empty() {
-                spans.push(Span::styled(buf.clone(), Style::default().fg(syn.ident)));
-                buf.clear();
-            }
-            let rest: String = chars[i..].iter().collect();
-            spans.push(Span::styled(rest, Style::default().fg(syn.comment)));
-            return spans;
-        }
-
-        // String start
-        if ch == '"' || ch == '\'' || (allow_backtick && ch == '`') {
-            if !buf.is_empty() {
-                spans.push(Span::styled(buf.clone(), Style::default().fg(syn.ident)));
-                buf.clear();
-            }
-            let quote = ch;
-            let mut j = i + 1;
-            while j < chars.len() {
-                if chars[j] == '\\' {
-                    j += 2;
-                    continue;
-                }
-                if chars[j] == quote {
-                    j += 1;
-                    break;
-                }
-                j += 1;
-            }
-            let segment: String = chars[i..j.min(chars.len())].iter().collect();
-            spans.push(Span::styled(segment, Style::default().fg(syn.string)));
-            i = j;
-            continue;
-        }
-
-        buf.push(ch);
-        i += 1;
-    }
-
-    if !buf.is_empty() {
-        spans.push(Span::styled(buf, Style::default().fg(syn.ident)));
+/// "Runs: ✓✓✗✓ 3/4": the latest verify outcomes that fit, oldest on the left.
+fn verify_history_line(
+    history: &[bool],
+    width: usize,
+    pal: theme::UiPalette,
+    no_color: bool,
+) -> Line<'static> {
+    let passed = history.iter().filter(|ok| **ok).count();
+    let tally = format!(" {}/{}", passed, history.len());
+    let room = width.saturating_sub(6 + tally.len()).max(2);
+    let mut spans = vec![Span::styled("Runs: ", pal.meta())];
+    let shown = if history.len() > room {
+        spans.push(Span::styled("…", pal.meta()));
+        &history[history.len() - (room - 1)..]
+    } else {
+        history
+    };
+    for ok in shown {
+        spans.push(match (*ok, no_color) {
+            (true, false) => Span::styled(theme::STATUS_OK, Style::default().fg(pal.ok)),
+            (false, false) => Span::styled(theme::STATUS_ERR, Style::default().fg(pal.err)),
+            (true, true) => Span::raw(theme::STATUS_OK),
+            (false, true) => Span::styled(theme::STATUS_ERR, Style::default().bold()),
+        });
     }
-
-    spans
+    spans.push(Span::styled(tally, pal.meta()));
+    Line::from(spans)
 }
 
 fn truncate_to_width(text: &str, width: usize) -> String {
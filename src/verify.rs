@@ -2,6 +2,8 @@ use std::path::{Path, PathBuf};
 
 use serde_json::Value;
 
+use crate::config::VerifyConfig;
+
 #[derive(Debug, Clone)]
 pub struct VerifySuggestion {
     pub label: String,
@@ -9,6 +11,23 @@ pub struct VerifySuggestion {
     pub confidence: f32,
 }
 
+/// Commands from `[verify] commands` (user or project config) first, then auto-detected ones.
+pub fn suggestions_for(root: &Path, verify: Option<&VerifyConfig>) -> Vec<VerifySuggestion> {
+    let mut out: Vec<VerifySuggestion> = verify
+        .and_then(|v| v.commands.as_ref())
+        .into_iter()
+        .flatten()
+        .filter(|c| !c.trim().is_empty())
+        .map(|c| s("Configured", c.trim(), 1.0))
+        .collect();
+    for detected in detect_suggestions(root) {
+        if !out.iter().any(|o| o.command == detected.command) {
+            out.push(detected);
+        }
+    }
+    out
+}
+
 pub fn detect_suggestions(root: &Path) -> Vec<VerifySuggestion> {
     let mut out: Vec<VerifySuggestion> = Vec::new();
 
@@ -138,6 +157,459 @@ fn read_package_json_scripts(path: &Path) -> std::collections::HashMap<String, S
     out
 }
 
+/// One step of the verify pipeline, e.g. `clippy` running `cargo clippy ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyStage {
+    pub name: String,
+    pub command: String,
+}
+
+/// What a finished stage reported, parsed from its output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StageCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub failed_tests: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct StageResult {
+    pub stage: VerifyStage,
+    pub passed: bool,
+    pub counts: StageCounts,
+    /// The first few error lines, so the model sees what broke without the whole log
+    pub excerpt: Vec<String>,
+}
+
+/// Error lines kept per failed stage in the summary.
+const MAX_EXCERPT_LINES: usize = 12;
+
+/// Failing tests listed per run; past this the model should narrow the test filter.
+const MAX_TEST_FAILURES: usize = 20;
+
+/// A failing test pulled out of `cargo test`, pytest or jest output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    pub name: String,
+    /// Where it failed (the panic or assertion), else the test file
+    pub location: Option<(String, usize)>,
+    pub message: String,
+}
+
+impl TestFailure {
+    /// `name (path:line): message`, as listed in chat and in the tool result.
+    pub fn line(&self) -> String {
+        let mut out = self.name.clone();
+        if let Some((path, line)) = &self.location {
+            out.push_str(&format!(" ({}:{})", path, line));
+        }
+        if !self.message.is_empty() {
+            out.push_str(": ");
+            out.push_str(&self.message);
+        }
+        out
+    }
+}
+
+/// Stages from `[verify] pipeline` if set, else `[verify] commands` in order, otherwise
+/// fmt → lint → build → test as detected for the workspace. Empty if nothing applies.
+pub fn pipeline_for(root: &Path, verify: Option<&VerifyConfig>) -> Vec<VerifyStage> {
+    let configured = |commands: Option<&Vec<String>>| -> Vec<VerifyStage> {
+        commands
+            .into_iter()
+            .flatten()
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(|c| stage(&stage_name(c), c))
+            .collect()
+    };
+    for stages in [
+        configured(verify.and_then(|v| v.pipeline.as_ref())),
+        configured(verify.and_then(|v| v.commands.as_ref())),
+    ] {
+        if !stages.is_empty() {
+            return stages;
+        }
+    }
+    detect_pipeline(root)
+}
+
+pub fn detect_pipeline(root: &Path) -> Vec<VerifyStage> {
+    if root.join("Cargo.toml").exists() {
+        return vec![
+            stage("fmt", "cargo fmt --all -- --check"),
+            stage("clippy", "cargo clippy --all-targets -- -D warnings"),
+            stage("build", "cargo build"),
+            stage("test", "cargo test"),
+        ];
+    }
+
+    if root.join("package.json").exists() {
+        let pm = node_package_manager(root);
+        let scripts = read_package_json_scripts(&root.join("package.json"));
+        let mut out = Vec::new();
+        for (name, script) in [
+            ("lint", "lint"),
+            ("typecheck", "typecheck"),
+            ("build", "build"),
+        ] {
+            if scripts.contains_key(script) {
+                out.push(stage(name, &format!("{} run {}", pm, script)));
+            }
+        }
+        if scripts.contains_key("test") {
+            out.push(stage("test", &format!("{} test", pm)));
+        }
+        return out;
+    }
+
+    if root.join("pyproject.toml").exists() || root.join("requirements.txt").exists() {
+        return vec![
+            stage("fmt", "ruff format --check ."),
+            stage("lint", "ruff check ."),
+            stage("test", "pytest"),
+        ];
+    }
+
+    if root.join("go.mod").exists() {
+        return vec![
+            stage("vet", "go vet ./..."),
+            stage("build", "go build ./..."),
+            stage("test", "go test ./..."),
+        ];
+    }
+
+    Vec::new()
+}
+
+/// A short name for a configured pipeline command.
+fn stage_name(command: &str) -> String {
+    let lower = command.to_lowercase();
+    let known = [
+        ("fmt", "fmt"),
+        ("format", "fmt"),
+        ("clippy", "clippy"),
+        ("lint", "lint"),
+        ("vet", "vet"),
+        ("typecheck", "typecheck"),
+        ("tsc", "typecheck"),
+        ("build", "build"),
+        ("test", "test"),
+    ];
+    known
+        .iter()
+        .find(|(needle, _)| lower.contains(needle))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| {
+            command
+                .split_whitespace()
+                .next()
+                .unwrap_or("check")
+                .to_string()
+        })
+}
+
+/// Count errors, warnings and failed tests in a stage's output. Rust, TypeScript, ruff,
+/// pytest, jest and Go all print something recognisable; anything else counts as 0.
+pub fn count_problems(output: &str) -> StageCounts {
+    let mut counts = StageCounts::default();
+    for line in output.lines() {
+        let line = line.trim();
+        if is_error_line(line) {
+            counts.errors += 1;
+        } else if line.starts_with("warning:") && !line.contains(" generated ") {
+            counts.warnings += 1;
+        }
+        if line.starts_with("--- FAIL:") {
+            counts.failed_tests += 1;
+        } else if !line.starts_with("Test Suites:") {
+            counts.failed_tests += number_before(line, " failed");
+        }
+    }
+    counts
+}
+
+fn is_error_line(line: &str) -> bool {
+    if line.starts_with("error: aborting") || line.starts_with("error: could not compile") {
+        return false;
+    }
+    line.starts_with("error:")
+        || line.starts_with("error[")
+        || line.contains(": error TS")
+        || line.starts_with("Diff in ")
+        || line.starts_with("Would reformat: ")
+}
+
+/// `3` from "... 3 failed ...", summed if several results share a line.
+fn number_before(line: &str, marker: &str) -> usize {
+    let mut total = 0;
+    let mut rest = line;
+    while let Some(idx) = rest.find(marker) {
+        let digits: String = rest[..idx]
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        total += digits.parse::<usize>().unwrap_or(0);
+        rest = &rest[idx + marker.len()..];
+    }
+    total
+}
+
+/// Error lines (and the `-->` location after each) to show for a failed stage, falling
+/// back to the tail of the output when nothing looks like an error.
+pub fn error_excerpt(output: &str) -> Vec<String> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let failing_test = trimmed.starts_with("--- FAIL:")
+            || trimmed.starts_with("FAILED ")
+            || (trimmed.starts_with("test ") && trimmed.ends_with("... FAILED"));
+        if !(is_error_line(trimmed) || failing_test) {
+            continue;
+        }
+        out.push(trimmed.to_string());
+        if let Some(next) = lines.get(i + 1).map(|l| l.trim()) {
+            if next.starts_with("-->") {
+                out.push(format!("  {}", next));
+            }
+        }
+        if out.len() >= MAX_EXCERPT_LINES {
+            out.truncate(MAX_EXCERPT_LINES);
+            return out;
+        }
+    }
+    if out.is_empty() {
+        let tail = lines.iter().rev().filter(|l| !l.trim().is_empty()).take(5);
+        out = tail.map(|l| l.trim().to_string()).collect();
+        out.reverse();
+    }
+    out
+}
+
+/// Failing tests in test runner output, in the order reported and without repeats (jest
+/// lists each failure twice when several suites fail).
+pub fn parse_test_failures(output: &str) -> Vec<TestFailure> {
+    let lines: Vec<&str> = output.lines().map(|l| l.trim()).collect();
+    let mut out: Vec<TestFailure> = Vec::new();
+    let found = cargo_failures(&lines)
+        .into_iter()
+        .chain(pytest_failures(&lines))
+        .chain(jest_failures(&lines));
+    for failure in found {
+        if !out.iter().any(|f| f.name == failure.name) {
+            out.push(failure);
+        }
+    }
+    out.truncate(MAX_TEST_FAILURES);
+    out
+}
+
+/// `Failing tests:` followed by one line per failure, for the tool result.
+pub fn failures_summary(failures: &[TestFailure]) -> String {
+    let mut out = String::from("Failing tests:");
+    for f in failures {
+        out.push_str("\n- ");
+        out.push_str(&f.line());
+    }
+    out
+}
+
+/// `---- name stdout ----` blocks; the location is the panic, the message what follows it
+/// (or, before Rust 1.73, what's quoted in it).
+fn cargo_failures(lines: &[&str]) -> Vec<TestFailure> {
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        else {
+            continue;
+        };
+        let mut failure = TestFailure {
+            name: name.to_string(),
+            location: None,
+            message: String::new(),
+        };
+        let mut message: Vec<&str> = Vec::new();
+        for next in &lines[i + 1..] {
+            if next.starts_with("---- ") || *next == "failures:" || next.starts_with("note:") {
+                break;
+            }
+            if let Some(idx) = next.find("panicked at ") {
+                let rest = next[idx + "panicked at ".len()..].trim_end_matches(':');
+                let location = match rest.strip_prefix('\'').and_then(|r| r.rsplit_once("', ")) {
+                    Some((quoted, location)) => {
+                        message.push(quoted);
+                        location
+                    }
+                    None => rest,
+                };
+                failure.location = parse_location(location);
+            } else if failure.location.is_some() && !next.is_empty() && message.len() < 3 {
+                message.push(next);
+            }
+        }
+        failure.message = message.join("; ");
+        out.push(failure);
+    }
+    out
+}
+
+/// `FAILED path::test - message` summary lines, located by the first `path.py:N:` line
+/// in the test's `___ test ___` traceback section.
+fn pytest_failures(lines: &[&str]) -> Vec<TestFailure> {
+    let mut sections: Vec<(String, (String, usize))> = Vec::new();
+    let mut section: Option<String> = None;
+    for line in lines {
+        if line.len() > 6 && line.starts_with("___") && line.ends_with("___") {
+            section = Some(
+                line.trim_matches(|c| c == '_' || c == ' ')
+                    .replace('.', "::"),
+            );
+        } else if line.starts_with("===") {
+            section = None;
+        } else if let Some(name) = &section {
+            let location = line
+                .split_once(": ")
+                .map(|(loc, _)| loc)
+                .filter(|loc| loc.contains(".py:"))
+                .and_then(parse_location);
+            if let Some(location) = location {
+                if !sections.iter().any(|(n, _)| n == name) {
+                    sections.push((name.clone(), location));
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for line in lines {
+        let Some(rest) = line.strip_prefix("FAILED ") else {
+            continue;
+        };
+        let (node, message) = rest.split_once(" - ").unwrap_or((rest, ""));
+        let Some((file, name)) = node.split_once("::") else {
+            continue;
+        };
+        let location = sections
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, loc)| loc.clone())
+            .unwrap_or_else(|| (file.to_string(), 1));
+        out.push(TestFailure {
+            name: name.to_string(),
+            location: Some(location),
+            message: message.to_string(),
+        });
+    }
+    out
+}
+
+/// `● Suite › test` blocks: the first line after the title is the message, the first
+/// stack frame outside node_modules the location.
+fn jest_failures(lines: &[&str]) -> Vec<TestFailure> {
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(name) = line.strip_prefix("● ") else {
+            continue;
+        };
+        let mut failure = TestFailure {
+            name: name.to_string(),
+            location: None,
+            message: String::new(),
+        };
+        for next in &lines[i + 1..] {
+            if next.starts_with("● ") || next.starts_with("Test Suites:") {
+                break;
+            }
+            if failure.message.is_empty() && !next.is_empty() {
+                failure.message = next.to_string();
+            }
+            if let Some(frame) = next.strip_prefix("at ") {
+                let frame = frame
+                    .rsplit_once('(')
+                    .map(|(_, f)| f.trim_end_matches(')'))
+                    .unwrap_or(frame);
+                if !frame.contains("node_modules") {
+                    failure.location = parse_location(frame);
+                    if failure.location.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+        out.push(failure);
+    }
+    out
+}
+
+/// `("src/lib.rs", 10)` from `src/lib.rs:10` or `src/lib.rs:10:5`.
+fn parse_location(s: &str) -> Option<(String, usize)> {
+    let mut parts = s.trim().split(':');
+    let path = parts.next().filter(|p| !p.is_empty())?;
+    let line = parts.next()?.parse::<usize>().ok()?;
+    Some((path.to_string(), line))
+}
+
+/// The pipeline outcome for the model: one line per stage, error excerpts for the stage
+/// that failed, and the stages that were skipped after it.
+pub fn pipeline_summary(results: &[StageResult], skipped: &[VerifyStage]) -> String {
+    let passed = results.iter().filter(|r| r.passed).count();
+    let total = results.len() + skipped.len();
+    let mut out = match results.iter().find(|r| !r.passed) {
+        Some(failed) => format!(
+            "Verify pipeline: {}/{} stages passed, stopped at {}.\n",
+            passed, total, failed.stage.name
+        ),
+        None => format!("Verify pipeline: all {} stages passed.\n", total),
+    };
+    for r in results {
+        let mut counts = Vec::new();
+        let c = &r.counts;
+        for (n, what) in [
+            (c.errors, "error"),
+            (c.warnings, "warning"),
+            (c.failed_tests, "failed test"),
+        ] {
+            if n > 0 {
+                counts.push(format!("{} {}{}", n, what, if n == 1 { "" } else { "s" }));
+            }
+        }
+        out.push_str(&format!(
+            "{} {}: {}{}\n",
+            if r.passed { "✓" } else { "✗" },
+            r.stage.name,
+            r.stage.command,
+            if counts.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", counts.join(", "))
+            }
+        ));
+        for line in &r.excerpt {
+            out.push_str("    ");
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    for s in skipped {
+        out.push_str(&format!("- {}: skipped\n", s.name));
+    }
+    out.trim_end().to_string()
+}
+
+fn stage(name: &str, command: &str) -> VerifyStage {
+    VerifyStage {
+        name: name.to_string(),
+        command: command.to_string(),
+    }
+}
+
 fn s(label: &str, command: &str, confidence: f32) -> VerifySuggestion {
     VerifySuggestion {
         label: label.to_string(),
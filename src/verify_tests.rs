@@ -4,7 +4,11 @@ mod tests {
 
     use tempfile::TempDir;
 
-    use crate::verify::detect_suggestions;
+    use crate::config::{AppConfig, VerifyConfig};
+    use crate::verify::{
+        count_problems, detect_suggestions, error_excerpt, parse_test_failures, pipeline_for,
+        pipeline_summary, suggestions_for, StageResult, VerifyStage,
+    };
 
     #[test]
     fn node_suggestions_prefer_existing_scripts() {
@@ -30,4 +34,200 @@ mod tests {
         // Build shouldn't be confidently suggested when not present.
         assert!(!cmds.iter().any(|c| c == "pnpm run build"));
     }
+
+    #[test]
+    fn configured_verify_commands_come_first() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        let config = AppConfig {
+            verify: Some(VerifyConfig {
+                commands: Some(vec!["cargo test".into(), "just check".into()]),
+                pipeline: None,
+            }),
+            ..Default::default()
+        };
+
+        let cmds: Vec<String> = suggestions_for(dir.path(), config.verify.as_ref())
+            .into_iter()
+            .map(|s| s.command)
+            .collect();
+        assert_eq!(cmds[0], "cargo test");
+        assert_eq!(cmds[1], "just check");
+        assert_eq!(cmds.iter().filter(|c| *c == "cargo test").count(), 1);
+    }
+
+    #[test]
+    fn pipeline_is_configured_or_detected_in_order() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+
+        let names: Vec<String> = pipeline_for(dir.path(), None)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["fmt", "clippy", "build", "test"]);
+
+        let config = AppConfig {
+            verify: Some(VerifyConfig {
+                commands: None,
+                pipeline: Some(vec!["cargo fmt --check".into(), "just e2e".into()]),
+            }),
+            ..Default::default()
+        };
+        let stages = pipeline_for(dir.path(), config.verify.as_ref());
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].name, "fmt");
+        assert_eq!(stages[1].name, "just");
+        assert_eq!(stages[1].command, "just e2e");
+
+        // Configured commands run ahead of the detected pipeline, but not of `pipeline`
+        let commands = VerifyConfig {
+            commands: Some(vec!["just check".into()]),
+            pipeline: None,
+        };
+        let stages = pipeline_for(dir.path(), Some(&commands));
+        assert_eq!(stages.len(), 1);
+        assert_eq!(stages[0].command, "just check");
+        let both = VerifyConfig {
+            commands: commands.commands.clone(),
+            ..config.verify.clone().unwrap()
+        };
+        assert_eq!(pipeline_for(dir.path(), Some(&both))[1].command, "just e2e");
+    }
+
+    #[test]
+    fn stage_output_is_counted_and_summarized() {
+        let clippy = "warning: unused variable: `x`\n --> src/lib.rs:2:9\n\
+error[E0308]: mismatched types\n --> src/main.rs:3:5\n\
+warning: `demo` (bin \"demo\") generated 1 warning\n\
+error: aborting due to 1 previous error\n";
+        let counts = count_problems(clippy);
+        assert_eq!((counts.errors, counts.warnings), (1, 1));
+        assert_eq!(
+            error_excerpt(clippy),
+            vec!["error[E0308]: mismatched types", "  --> src/main.rs:3:5"]
+        );
+
+        let tests = "test a ... ok\ntest b ... FAILED\n\
+test result: FAILED. 1 passed; 2 failed; 0 ignored\n";
+        assert_eq!(count_problems(tests).failed_tests, 2);
+        assert_eq!(
+            count_problems("=== 3 failed, 10 passed in 1.2s ===").failed_tests,
+            3
+        );
+
+        let stage = |name: &str| VerifyStage {
+            name: name.into(),
+            command: format!("run {}", name),
+        };
+        let results = vec![
+            StageResult {
+                stage: stage("fmt"),
+                passed: true,
+                counts: Default::default(),
+                excerpt: Vec::new(),
+            },
+            StageResult {
+                stage: stage("clippy"),
+                passed: false,
+                counts: count_problems(clippy),
+                excerpt: error_excerpt(clippy),
+            },
+        ];
+        let summary = pipeline_summary(&results, &[stage("test")]);
+        assert!(summary.starts_with("Verify pipeline: 1/3 stages passed, stopped at clippy."));
+        assert!(summary.contains("✓ fmt: run fmt\n"));
+        assert!(summary.contains("✗ clippy: run clippy (1 error, 1 warning)"));
+        assert!(summary.contains("    error[E0308]: mismatched types"));
+        assert!(summary.ends_with("- test: skipped"));
+        assert!(!summary.contains("aborting"));
+    }
+
+    #[test]
+    fn cargo_test_failures_are_parsed() {
+        let output = "running 2 tests
+test tests::ok ... ok
+test tests::adds ... FAILED
+
+failures:
+
+---- tests::adds stdout ----
+thread 'tests::adds' panicked at src/lib.rs:10:9:
+assertion `left == right` failed
+  left: 3
+ right: 4
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- tests::old stdout ----
+thread 'tests::old' panicked at 'boom', src/old.rs:3:5
+
+failures:
+    tests::adds
+    tests::old
+
+test result: FAILED. 1 passed; 2 failed; 0 ignored
+";
+        let failures = parse_test_failures(output);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].name, "tests::adds");
+        assert_eq!(failures[0].location, Some(("src/lib.rs".into(), 10)));
+        assert_eq!(
+            failures[0].message,
+            "assertion `left == right` failed; left: 3; right: 4"
+        );
+        assert_eq!(failures[1].line(), "tests::old (src/old.rs:3): boom");
+    }
+
+    #[test]
+    fn pytest_and_jest_failures_are_parsed() {
+        let pytest =
+            "=================================== FAILURES ===================================
+___________________________________ test_add ___________________________________
+
+    def test_add():
+>       assert add(1, 2) == 4
+E       assert 3 == 4
+
+tests/test_math.py:5: AssertionError
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_add - assert 3 == 4
+FAILED tests/test_io.py::TestIo::test_read
+========================= 2 failed, 1 passed in 0.05s ==========================
+";
+        let failures = parse_test_failures(pytest);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(
+            failures[0].line(),
+            "test_add (tests/test_math.py:5): assert 3 == 4"
+        );
+        assert_eq!(failures[1].name, "TestIo::test_read");
+        assert_eq!(failures[1].location, Some(("tests/test_io.py".into(), 1)));
+
+        let jest = "FAIL src/math.test.js
+  ● Math › adds numbers
+
+    expect(received).toBe(expected) // Object.is equality
+
+    Expected: 4
+    Received: 3
+
+      at Object.<anonymous> (node_modules/expect/build/index.js:1:1)
+      at Object.<anonymous> (src/math.test.js:4:21)
+
+Summary of all failing tests
+  ● Math › adds numbers
+
+    expect(received).toBe(expected) // Object.is equality
+
+Test Suites: 1 failed, 1 total
+";
+        let failures = parse_test_failures(jest);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "Math › adds numbers");
+        assert_eq!(failures[0].location, Some(("src/math.test.js".into(), 4)));
+        assert_eq!(
+            failures[0].message,
+            "expect(received).toBe(expected) // Object.is equality"
+        );
+    }
 }
@@ -0,0 +1,129 @@
+//! Extra workspace roots registered with `/workspace add` (an app and its shared
+//! library, say). The sandbox allows all of them, each keeps its own index, and one is
+//! active: tools run there and resolve relative paths against it.
+
+use std::path::{Path, PathBuf};
+
+const WORKSPACES_FILE: &str = "workspaces";
+
+/// Most extra roots one workspace can register.
+pub const MAX_EXTRA_ROOTS: usize = 8;
+
+/// Extra roots registered for the workspace Lorikeet was started in, in the order added.
+pub fn load(workspace_root: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(workspaces_path(workspace_root))
+        .map(|s| {
+            s.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn save(workspace_root: &Path, roots: &[PathBuf]) -> std::io::Result<()> {
+    let path = workspaces_path(workspace_root);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut content = roots
+        .iter()
+        .map(|r| r.display().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    content.push('\n');
+    std::fs::write(path, content)
+}
+
+/// `path` (relative to the primary workspace, `~` expanded) as an absolute, canonical
+/// directory. Errors if it isn't one.
+pub fn normalize(workspace_root: &Path, path: &str) -> Result<PathBuf, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("Usage: /workspace add <path>".to_string());
+    }
+    let expanded = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|h| h.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    };
+    let full = if expanded.is_absolute() {
+        expanded
+    } else {
+        workspace_root.join(expanded)
+    };
+    match std::fs::canonicalize(&full) {
+        Ok(dir) if dir.is_dir() => Ok(dir),
+        Ok(_) => Err(format!("Not a directory: {}", path)),
+        Err(_) => Err(format!("No such directory: {}", path)),
+    }
+}
+
+/// Short name for a root: its directory name.
+pub fn name(root: &Path) -> String {
+    root.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string())
+}
+
+/// The root among `roots` that `arg` names: by directory name or by path.
+pub fn find<'a>(roots: &'a [PathBuf], workspace_root: &Path, arg: &str) -> Option<&'a PathBuf> {
+    let arg = arg.trim().trim_end_matches('/');
+    roots.iter().find(|r| name(r) == arg).or_else(|| {
+        let path = normalize(workspace_root, arg).ok()?;
+        roots.iter().find(|r| **r == path)
+    })
+}
+
+/// System-context note listing the roots, so the model knows where the other repos are.
+pub fn system_hint(active: &Path, roots: &[PathBuf]) -> Option<String> {
+    if roots.len() < 2 {
+        return None;
+    }
+    let mut out = String::from(
+        "Workspace roots (tools may read and edit all of them; relative paths and commands resolve against the active one, reach the others by absolute path):",
+    );
+    for root in roots {
+        out.push_str(&format!("\n- {} ({})", root.display(), name(root)));
+        if root == active {
+            out.push_str(" [active]");
+        }
+    }
+    Some(out)
+}
+
+fn workspaces_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(".lorikeet").join(WORKSPACES_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn roots_are_normalized_saved_and_found_by_name() {
+        let dir = TempDir::new().unwrap();
+        let main = dir.path().join("app");
+        let lib = dir.path().join("shared-lib");
+        std::fs::create_dir_all(&main).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+
+        let added = normalize(&main, "../shared-lib").unwrap();
+        assert_eq!(added, std::fs::canonicalize(&lib).unwrap());
+        assert!(normalize(&main, "../missing").is_err());
+
+        save(&main, std::slice::from_ref(&added)).unwrap();
+        assert_eq!(load(&main), vec![added.clone()]);
+
+        let roots = vec![std::fs::canonicalize(&main).unwrap(), added.clone()];
+        assert_eq!(find(&roots, &main, "shared-lib"), Some(&added));
+        assert_eq!(find(&roots, &main, "../shared-lib/"), Some(&added));
+        assert_eq!(find(&roots, &main, "other"), None);
+        let hint = system_hint(&added, &roots).unwrap();
+        assert!(hint.contains("(shared-lib) [active]"));
+        assert!(system_hint(&added, &roots[..1]).is_none());
+    }
+}